@@ -1,9 +1,17 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use miette::Result;
 use serde::Deserialize;
 
+use crate::aggregated_library::AggregatedLibraryConfiguration;
 use crate::error::ConfigurationError;
+use crate::library::{
+    DiscSubdirectoryLayout,
+    ExtensionTranscodingRule,
+    LibraryTranscodingConfiguration,
+};
+use crate::tools::FfmpegToolsConfiguration;
 
 
 /// The file name for the album overrides (see [`AlbumConfiguration`]).
@@ -24,6 +32,10 @@ pub struct AlbumConfiguration {
     /// Scanning options.
     #[serde(default)]
     pub scan: AlbumScanConfiguration,
+
+    /// Transcoding options (overrides of `tools.ffmpeg`).
+    #[serde(default)]
+    pub transcoding: AlbumTranscodingConfiguration,
 }
 
 impl AlbumConfiguration {
@@ -51,14 +63,24 @@ impl AlbumConfiguration {
                 }
             })?;
 
-        let album_override: AlbumConfiguration =
+        let mut album_override: AlbumConfiguration =
             toml::from_str(&album_override_string).map_err(|error| {
                 ConfigurationError::FileFormatError {
-                    file_path,
+                    file_path: file_path.clone(),
                     error: Box::new(error),
                 }
             })?;
 
+        album_override
+            .transcoding
+            .validate_and_normalize()
+            .map_err(
+                |reason| ConfigurationError::InvalidAlbumOverride {
+                    file_path,
+                    reason,
+                },
+            )?;
+
         Ok(album_override)
     }
 }
@@ -70,3 +92,220 @@ pub struct AlbumScanConfiguration {
     #[serde(default)]
     pub depth: u16,
 }
+
+
+/// Per-album overrides of the ffmpeg options in `tools.ffmpeg`
+/// (e.g. for transcoding spoken-word albums with different settings).
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct AlbumTranscodingConfiguration {
+    /// If set, overrides `audio_transcoding_args`, `audio_transcoding_args_per_extension`
+    /// and the arguments of the library's extension rules for all transcoded audio files in this album. Must contain the `{INPUT_FILE}` and `{OUTPUT_FILE}` placeholders.
+    #[serde(default)]
+    pub audio_transcoding_args: Option<Vec<String>>,
+
+    /// If set, overrides `audio_transcoding_output_extension` for this album.
+    #[serde(default)]
+    pub audio_transcoding_output_extension: Option<String>,
+}
+
+impl AlbumTranscodingConfiguration {
+    /// Ensure the overridden arguments contain the required placeholders
+    /// and lowercase the overridden output extension (like `tools.ffmpeg` does).
+    fn validate_and_normalize(&mut self) -> Result<(), String> {
+        if let Some(audio_transcoding_args) = &self.audio_transcoding_args {
+            for placeholder in ["{INPUT_FILE}", "{OUTPUT_FILE}"] {
+                if !audio_transcoding_args
+                    .iter()
+                    .any(|argument| argument.contains(placeholder))
+                {
+                    return Err(format!(
+                        "transcoding.audio_transcoding_args must contain \
+                        the {placeholder} placeholder"
+                    ));
+                }
+            }
+        }
+
+        if let Some(extension) = &mut self.audio_transcoding_output_extension {
+            if extension.is_empty() {
+                return Err(
+                    "transcoding.audio_transcoding_output_extension must not be empty"
+                        .to_string(),
+                );
+            }
+
+            *extension = extension.to_ascii_lowercase();
+        }
+
+        Ok(())
+    }
+
+    /// Returns the extension of transcoded audio files in this album:
+    /// the album override or, if there is none, the one from `tools.ffmpeg`.
+    pub fn audio_transcoding_output_extension<'a>(
+        &'a self,
+        ffmpeg: &'a FfmpegToolsConfiguration,
+    ) -> &'a str {
+        self.audio_transcoding_output_extension
+            .as_deref()
+            .unwrap_or(&ffmpeg.audio_transcoding_output_extension)
+    }
+
+    /// Returns the ffmpeg argument template to use when transcoding the given source file
+    /// of this album: the album override or, if there is none, the arguments of the library's
+    /// extension rule or, if there are none, the template from `tools.ffmpeg`
+    /// (see `audio_transcoding_args_for_path` there).
+    ///
+    /// Returns `Err` if the extension is not valid UTF-8.
+    pub fn audio_transcoding_args_for_path<'a, P: AsRef<Path>>(
+        &'a self,
+        ffmpeg: &'a FfmpegToolsConfiguration,
+        library: &'a LibraryTranscodingConfiguration,
+        source_file_path: P,
+    ) -> Result<&'a [String]> {
+        if let Some(audio_transcoding_args) = &self.audio_transcoding_args {
+            return Ok(audio_transcoding_args);
+        }
+
+        match library.audio_transcoding_args_for_path(&source_file_path)? {
+            Some(audio_transcoding_args) => Ok(audio_transcoding_args),
+            None => ffmpeg.audio_transcoding_args_for_path(source_file_path),
+        }
+    }
+
+    /// Returns a hash of the effective transcoding settings for this album:
+    /// the ffmpeg argument templates (the album override or, if there is none,
+    /// `audio_transcoding_args` and `audio_transcoding_args_per_extension`),
+    /// the library's extension rules, the embedded cover art file names (and how the embedded
+    /// cover art is downscaled) and the output extension.
+    ///
+    /// The hash is stored in the source album state, so that any change to these settings
+    /// can be detected and the album re-transcoded on the next run.
+    /// It is stable across platforms, euphony and Rust versions (64-bit FNV-1a, hex-encoded).
+    pub fn transcoding_settings_hash(
+        &self,
+        ffmpeg: &FfmpegToolsConfiguration,
+        library: &LibraryTranscodingConfiguration,
+        aggregated_library: &AggregatedLibraryConfiguration,
+    ) -> String {
+        let cover_art_downscaling = aggregated_library
+            .cover_art_max_dimension
+            .map(|max_dimension| {
+                [
+                    max_dimension.to_string(),
+                    aggregated_library.cover_art_jpeg_quality.to_string(),
+                ]
+            });
+
+        let mut hashed_values: Vec<&str> = Vec::new();
+
+        match &self.audio_transcoding_args {
+            Some(audio_transcoding_args) => {
+                hashed_values.push("album");
+                hashed_values
+                    .extend(audio_transcoding_args.iter().map(String::as_str));
+            }
+            None => {
+                hashed_values.push("default");
+                hashed_values.extend(
+                    ffmpeg.audio_transcoding_args.iter().map(String::as_str),
+                );
+
+                let mut per_extension_args = ffmpeg
+                    .audio_transcoding_args_per_extension
+                    .iter()
+                    .collect::<Vec<_>>();
+                per_extension_args
+                    .sort_unstable_by_key(|(extension, _)| *extension);
+
+                for (extension, arguments) in per_extension_args {
+                    hashed_values.push("extension");
+                    hashed_values.push(extension);
+                    hashed_values.extend(arguments.iter().map(String::as_str));
+                }
+            }
+        }
+
+        // Without any rules, the hash is the same as before rules existed.
+        let mut extension_rules =
+            library.extension_rules.iter().collect::<Vec<_>>();
+        extension_rules.sort_unstable_by_key(|(extension, _)| *extension);
+
+        for (extension, rule) in extension_rules {
+            hashed_values.push("rule");
+            hashed_values.push(extension);
+
+            match rule {
+                ExtensionTranscodingRule::Transcode { args } => {
+                    hashed_values.push("transcode");
+                    hashed_values
+                        .extend(args.iter().flatten().map(String::as_str));
+                }
+                ExtensionTranscodingRule::Copy => {
+                    hashed_values.push("copy");
+                }
+            }
+        }
+
+        // Output paths built from a template depend on the template and the source of its values.
+        if let Some(output_template) = &library.output_template {
+            hashed_values.push(library.album_info_source.name());
+            hashed_values.push(output_template.as_str());
+        }
+
+        // Flattened disc subdirectories change the output paths.
+        if library.disc_subdirectories == DiscSubdirectoryLayout::Flatten {
+            hashed_values.push("discs");
+            hashed_values.push(library.disc_subdirectories.name());
+        }
+
+        // ReplayGain tags change the output files, so enabling them processes all albums again.
+        if library.replaygain {
+            hashed_values.push("replaygain");
+        }
+
+        // Embedding cover art changes the output, so it is part of the hash (only if enabled).
+        if !ffmpeg.embedded_cover_art_file_names.is_empty() {
+            hashed_values.push("cover");
+            hashed_values.extend(
+                ffmpeg
+                    .embedded_cover_art_file_names
+                    .iter()
+                    .map(String::as_str),
+            );
+
+            if let Some(cover_art_downscaling) = &cover_art_downscaling {
+                hashed_values.push("cover-downscaling");
+                hashed_values
+                    .extend(cover_art_downscaling.iter().map(String::as_str));
+            }
+        }
+
+        hashed_values.push("output");
+        hashed_values.push(self.audio_transcoding_output_extension(ffmpeg));
+
+        format!("{:016x}", stable_hash(&hashed_values))
+    }
+}
+
+
+/// Hash the given values using 64-bit FNV-1a, separating them with a zero byte
+/// (so e.g. `["-q:a", "0"]` and `["-q:a 0"]` hash differently).
+///
+/// Unlike `DefaultHasher`, the result is stable across platforms, euphony and Rust versions,
+/// which is required for hashes saved to disk.
+fn stable_hash(values: &[&str]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x00000100000001b3;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for byte in values
+        .iter()
+        .flat_map(|value| value.bytes().chain(std::iter::once(0)))
+    {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
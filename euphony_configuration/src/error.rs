@@ -19,5 +19,60 @@ pub enum ConfigurationError {
         file_path: PathBuf,
         error: Box<toml::de::Error>,
     },
+
+    #[error("Invalid album override \"{file_path}\": {reason}.")]
+    InvalidAlbumOverride { file_path: PathBuf, reason: String },
+
+    #[error(
+        "The configuration is missing schema_version \
+        (expected schema_version = {expected})."
+    )]
+    #[diagnostic(help(
+        "Add `schema_version = {expected}` to the top of the configuration \
+        after checking it against the configuration template."
+    ))]
+    MissingSchemaVersion { expected: u32 },
+
+    #[error(
+        "The configuration has schema_version {found}, but this version \
+        of euphony expects schema_version {expected}."
+    )]
+    #[diagnostic(help(
+        "The configuration was written for a newer version of euphony, \
+        please update euphony."
+    ))]
+    NewerSchemaVersion { expected: u32, found: u32 },
+
+    #[error(
+        "The configuration has schema_version {found}, but this version \
+        of euphony expects schema_version {expected}."
+    )]
+    #[diagnostic(help(
+        "The configuration was written for an older version of euphony: \
+        update it according to the changelog and the configuration template, \
+        then set schema_version to {expected}."
+    ))]
+    OlderSchemaVersion { expected: u32, found: u32 },
+
+    #[error("transcode_threads is set to 0! The minimum value is 1.")]
+    ZeroTranscodeThreads,
     // TODO
 }
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum ToolsConfigurationError {
+    #[error("No ffmpeg binary exists at \"{binary}\" (tools.ffmpeg.binary).")]
+    #[diagnostic(help(
+        "Make sure ffmpeg is installed and tools.ffmpeg.binary points to it."
+    ))]
+    BinaryNotFound { binary: String },
+
+    #[error(
+        "{setting} is missing the {placeholder} placeholder \
+        (without it, ffmpeg wouldn't know where to read from or write to)."
+    )]
+    MissingPlaceholder {
+        placeholder: &'static str,
+        setting: String,
+    },
+}
@@ -1,7 +1,12 @@
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
 use miette::{miette, Result};
 
+/// Characters that aren't allowed in names on FAT32, exFAT and NTFS filesystems
+/// (in addition to control characters).
+pub const UNPORTABLE_FILE_NAME_CHARACTERS: [char; 9] =
+    ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
 /// Get a file's extension (or an empty string if none).
 /// Returns `Err` if the extension is not valid UTF-8.
 #[inline]
@@ -14,3 +19,64 @@ pub fn get_path_extension_or_empty<P: AsRef<Path>>(path: P) -> Result<String> {
         .ok_or_else(|| miette!("Could not convert extension to UTF-8."))?
         .to_ascii_lowercase())
 }
+
+/// Make a file or directory name usable on FAT32 and exFAT filesystems
+/// (see `aggregated_library.sanitize_file_names`): characters that aren't allowed
+/// (see `UNPORTABLE_FILE_NAME_CHARACTERS`) and control characters are replaced with `_`,
+/// and trailing dots and spaces are removed. Names that would end up empty become `_`.
+///
+/// The result only depends on the given name, so re-runs always produce the same names.
+pub fn sanitize_file_name_for_fat(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|character| {
+            if character.is_control()
+                || UNPORTABLE_FILE_NAME_CHARACTERS.contains(&character)
+            {
+                '_'
+            } else {
+                character
+            }
+        })
+        .collect();
+
+    let sanitized = sanitized.trim_end_matches(['.', ' ']);
+    if sanitized.is_empty() {
+        return String::from("_");
+    }
+
+    sanitized.to_string()
+}
+
+/// Sanitize each component of a relative path (see `sanitize_file_name_for_fat`).
+pub fn sanitize_relative_path_for_fat<P: AsRef<Path>>(path: P) -> PathBuf {
+    path.as_ref()
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => PathBuf::from(
+                sanitize_file_name_for_fat(&name.to_string_lossy()),
+            ),
+            other_component => PathBuf::from(other_component.as_os_str()),
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_names_for_fat() {
+        assert_eq!(
+            sanitize_file_name_for_fat("Song: Part 1?"),
+            "Song_ Part 1_"
+        );
+        assert_eq!(sanitize_file_name_for_fat("Vol. 2... "), "Vol. 2");
+        assert_eq!(sanitize_file_name_for_fat("..."), "_");
+        assert_eq!(
+            sanitize_relative_path_for_fat("AC/DC*/01 \"Song\".mp3"),
+            PathBuf::from("AC/DC_/01 _Song_.mp3")
+        );
+    }
+}
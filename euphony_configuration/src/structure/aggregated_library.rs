@@ -1,19 +1,260 @@
-use serde::Deserialize;
+use miette::miette;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    error::ConfigurationError,
+    output_template::OutputPathTemplate,
     paths::PathsConfiguration,
     traits::ResolvableWithPathsConfiguration,
+    utilities::replace_placeholders_in_str,
 };
 
-#[derive(Clone)]
+/// Default for `aggregated_library.preserve_permissions`.
+const DEFAULT_PRESERVE_PERMISSIONS: bool = false;
+
+/// Default for `aggregated_library.permissions_umask`.
+const DEFAULT_PERMISSIONS_UMASK: u32 = 0;
+
+/// Default for `aggregated_library.skip_unchanged_data`.
+const DEFAULT_SKIP_UNCHANGED_DATA: bool = false;
+
+/// Default for `aggregated_library.copy_buffer_bytes`: 1 MiB.
+const DEFAULT_COPY_BUFFER_BYTES: usize = 1024 * 1024;
+
+/// Default for `aggregated_library.generate_master_playlist`.
+const DEFAULT_GENERATE_MASTER_PLAYLIST: bool = false;
+
+/// Default for `aggregated_library.write_checksum_manifest`.
+const DEFAULT_WRITE_CHECKSUM_MANIFEST: bool = false;
+
+/// Default for `aggregated_library.free_space_margin_mib`.
+const DEFAULT_FREE_SPACE_MARGIN_MIB: u64 = 512;
+
+/// Default for `aggregated_library.sanitize_file_names`.
+const DEFAULT_SANITIZE_FILE_NAMES: bool = false;
+
+/// Default for `aggregated_library.cover_art_jpeg_quality`.
+const DEFAULT_COVER_ART_JPEG_QUALITY: u8 = 90;
+
+/// Default for `aggregated_library.minimum_transcoded_file_size_bytes`:
+/// only empty files are rejected.
+const DEFAULT_MINIMUM_TRANSCODED_FILE_SIZE_BYTES: u64 = 1;
+
+/// Default for `aggregated_library.verify_transcoded_files_decode`.
+const DEFAULT_VERIFY_TRANSCODED_FILES_DECODE: bool = false;
+
+/// Default for `aggregated_library.retryable_error_categories`:
+/// only errors that look transient are retried.
+const DEFAULT_RETRYABLE_ERROR_CATEGORIES: [FileJobErrorCategory; 3] = [
+    FileJobErrorCategory::Io,
+    FileJobErrorCategory::ProcessSpawn,
+    FileJobErrorCategory::Timeout,
+];
+
+/// Classification of a failed transcode or copy, used to decide whether it is worth retrying
+/// (see `aggregated_library.retryable_error_categories`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum FileJobErrorCategory {
+    /// A filesystem error (e.g. a file could not be read, written or created).
+    #[serde(rename = "io")]
+    Io,
+
+    /// ffmpeg could not be started.
+    #[serde(rename = "process_spawn")]
+    ProcessSpawn,
+
+    /// An operation timed out (e.g. on a network drive).
+    #[serde(rename = "timeout")]
+    Timeout,
+
+    /// ffmpeg rejected the input file (e.g. corrupted data or an unsupported codec).
+    /// Retrying these is usually pointless.
+    #[serde(rename = "invalid_data")]
+    InvalidData,
+
+    /// Any other failure (e.g. ffmpeg exited with an unrecognized error).
+    #[serde(rename = "other")]
+    Other,
+}
+
+impl FileJobErrorCategory {
+    /// The name of the category as used in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Io => "io",
+            Self::ProcessSpawn => "process_spawn",
+            Self::Timeout => "timeout",
+            Self::InvalidData => "invalid_data",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// The kind of filesystem the aggregated library is stored on (see `aggregated_library.filesystem`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFilesystem {
+    /// File names that only differ in case refer to different files (e.g. ext4).
+    #[default]
+    #[serde(rename = "case_sensitive")]
+    CaseSensitive,
+
+    /// File names that only differ in case refer to the same file
+    /// (e.g. FAT32, exFAT, NTFS or case-insensitive APFS).
+    #[serde(rename = "case_insensitive")]
+    CaseInsensitive,
+}
+
+impl OutputFilesystem {
+    /// The name of the filesystem kind as used in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CaseSensitive => "case_sensitive",
+            Self::CaseInsensitive => "case_insensitive",
+        }
+    }
+}
+
+/// What happens to albums whose output files would collide on a case-insensitive filesystem,
+/// i.e. whose output paths only differ in case (see `aggregated_library.case_collision_strategy`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CaseCollisionStrategy {
+    /// The collisions are reported and the album is not processed
+    /// until they are resolved in the source library.
+    #[default]
+    #[serde(rename = "skip")]
+    Skip,
+
+    /// The colliding output files are renamed by appending a number
+    /// (e.g. `song (2).mp3`), sorted by their source paths.
+    #[serde(rename = "rename")]
+    Rename,
+}
+
+impl CaseCollisionStrategy {
+    /// The name of the strategy as used in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Rename => "rename",
+        }
+    }
+}
+
+/// What happens to audio files when they are processed into the aggregated library.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AggregationMode {
+    /// Audio files are transcoded using ffmpeg (see `tools.ffmpeg`).
+    #[default]
+    Transcode,
+
+    /// Audio files are copied as-is, without transcoding (see the `mirror` command).
+    Mirror,
+}
+
+#[derive(Serialize, Clone)]
 pub struct AggregatedLibraryConfiguration {
     pub path: String,
 
+    /// Whether audio files are transcoded or copied as-is.
+    ///
+    /// This is not read from the configuration file - it is always `Transcode`,
+    /// unless the configuration is turned into a mirror configuration
+    /// (see `Configuration::into_mirror_configuration`).
+    #[serde(skip)]
+    pub mode: AggregationMode,
+
+    /// Name of the device profile this configuration was built from (see the `sync-device`
+    /// command and `Configuration::into_device_profile_configuration`).
+    /// Each profile keeps its own state files in the source libraries.
+    ///
+    /// Like `mode`, this is not read from the configuration file.
+    #[serde(skip)]
+    pub device_profile: Option<String>,
+
     pub transcode_threads: usize,
 
     pub failure_max_retries: u16,
 
     pub failure_delay_seconds: u16,
+
+    /// Only failures of these categories are retried (up to `failure_max_retries` times),
+    /// all others fail immediately.
+    pub retryable_error_categories: Vec<FileJobErrorCategory>,
+
+    /// If enabled, the permission (mode) bits of each source file are copied
+    /// onto its transcoded or copied counterpart. Only has an effect on Unix.
+    pub preserve_permissions: bool,
+
+    /// Bits to clear from the preserved permissions (same semantics as a umask).
+    /// Only used when `preserve_permissions` is enabled.
+    pub permissions_umask: u32,
+
+    /// If enabled, data files whose copy in the aggregated library already matches
+    /// by size (and by modification time, if `file_metadata.change_detection` compares it)
+    /// are not copied again.
+    pub skip_unchanged_data: bool,
+
+    /// Size of the buffer (in bytes) data files are copied with. Files larger than
+    /// the buffer are copied chunk by chunk, reporting their progress after each chunk.
+    pub copy_buffer_bytes: usize,
+
+    /// If set, a run is aborted when more than this percentage of the previously processed albums
+    /// would be processed again (unless confirmed with `--yes`).
+    pub max_reprocess_percentage: Option<f64>,
+
+    /// If enabled, a master playlist of all transcoded audio files (grouped by artist and album)
+    /// is generated in the root of the aggregated library after each full run.
+    pub generate_master_playlist: bool,
+
+    /// The kind of filesystem the aggregated library is stored on. On case-insensitive filesystems,
+    /// output paths within an album that only differ in case are handled
+    /// according to `case_collision_strategy`.
+    pub filesystem: OutputFilesystem,
+
+    /// How output paths that only differ in case are handled
+    /// (only when `filesystem` is case-insensitive).
+    pub case_collision_strategy: CaseCollisionStrategy,
+
+    /// If enabled, output file and directory names are made usable on FAT32 and exFAT
+    /// (see `euphony_configuration::sanitize_file_name_for_fat`): characters these
+    /// filesystems don't allow are replaced with `_` and trailing dots and spaces are removed.
+    pub sanitize_file_names: bool,
+
+    /// If enabled, a `checksums.sha256` file listing the SHA-256 hash of each output file
+    /// is written into each transcoded album directory whenever the album is processed.
+    pub write_checksum_manifest: bool,
+
+    /// If set, a warning is printed after a run when the aggregated library
+    /// takes up more than this many mebibytes.
+    pub size_budget_mib: Option<u64>,
+
+    /// Free space (in mebibytes) that must remain on the aggregated library's volume
+    /// after a run, according to the estimated output size. If there isn't enough space,
+    /// a run is aborted before processing anything (unless `--ignore-free-space` is used).
+    pub free_space_margin_mib: u64,
+
+    /// If set, copied JPEG and PNG images (e.g. `folder.jpg`) and embedded cover art
+    /// (see `tools.ffmpeg.embedded_cover_art_file_names`) larger than this many pixels
+    /// in width or height are downscaled to fit, keeping their aspect ratio.
+    /// Smaller images are used as-is.
+    pub cover_art_max_dimension: Option<u32>,
+
+    /// Quality (1 to 100) of downscaled JPEG images (see `cover_art_max_dimension`).
+    pub cover_art_jpeg_quality: u8,
+
+    /// Transcoded files smaller than this (in bytes) are treated as failed transcodes,
+    /// even if ffmpeg reported success (e.g. empty files written to a flaky drive).
+    pub minimum_transcoded_file_size_bytes: u64,
+
+    /// If enabled, each transcoded file is decoded once more with ffmpeg
+    /// (`ffmpeg -v error -i <file> -f null -`) and treated as a failed transcode if that fails.
+    /// This roughly doubles the time spent on each file.
+    pub verify_transcoded_files_decode: bool,
+
+    /// If set, output paths of all libraries are built from this template
+    /// (see `OutputPathTemplate`) instead of mirroring the source directory structure.
+    /// Libraries can override it with `libraries.<key>.transcoding.output_template`.
+    pub output_template: Option<OutputPathTemplate>,
 }
 
 #[derive(Deserialize, Clone)]
@@ -25,6 +266,42 @@ pub(crate) struct UnresolvedAggregatedLibraryConfiguration {
     failure_max_retries: u16,
 
     failure_delay_seconds: u16,
+
+    retryable_error_categories: Option<Vec<FileJobErrorCategory>>,
+
+    preserve_permissions: Option<bool>,
+
+    permissions_umask: Option<u32>,
+
+    skip_unchanged_data: Option<bool>,
+
+    copy_buffer_bytes: Option<usize>,
+
+    max_reprocess_percentage: Option<f64>,
+
+    generate_master_playlist: Option<bool>,
+
+    filesystem: Option<OutputFilesystem>,
+
+    case_collision_strategy: Option<CaseCollisionStrategy>,
+
+    sanitize_file_names: Option<bool>,
+
+    write_checksum_manifest: Option<bool>,
+
+    size_budget_mib: Option<u64>,
+
+    free_space_margin_mib: Option<u64>,
+
+    cover_art_max_dimension: Option<u32>,
+
+    cover_art_jpeg_quality: Option<u8>,
+
+    minimum_transcoded_file_size_bytes: Option<u64>,
+
+    verify_transcoded_files_decode: Option<bool>,
+
+    output_template: Option<String>,
 }
 
 impl ResolvableWithPathsConfiguration
@@ -36,20 +313,116 @@ impl ResolvableWithPathsConfiguration
         self,
         paths: &PathsConfiguration,
     ) -> miette::Result<Self::Resolved> {
-        let path = self
-            .path
-            .replace("{LIBRARY_BASE}", &paths.base_library_path);
+        let path = replace_placeholders_in_str(
+            &self.path,
+            "aggregated_library.path",
+            &[("{LIBRARY_BASE}", &paths.base_library_path)],
+        );
 
         if self.transcode_threads == 0 {
-            panic!("transcode_threads is set to 0! The minimum value is 1.");
+            return Err(ConfigurationError::ZeroTranscodeThreads.into());
+        }
+
+        let permissions_umask =
+            self.permissions_umask.unwrap_or(DEFAULT_PERMISSIONS_UMASK);
+        if permissions_umask > 0o7777 {
+            return Err(miette!(
+                "permissions_umask is set to {:o}, but the maximum value is 7777 (octal).",
+                permissions_umask
+            ));
+        }
+
+        if let Some(max_reprocess_percentage) = self.max_reprocess_percentage {
+            if !(0.0..=100.0).contains(&max_reprocess_percentage) {
+                return Err(miette!(
+                    "max_reprocess_percentage is set to {}, but it must be between 0 and 100.",
+                    max_reprocess_percentage
+                ));
+            }
+        }
+
+
+        if self.copy_buffer_bytes == Some(0) {
+            return Err(miette!(
+                "copy_buffer_bytes is set to 0, but it must be larger than 0."
+            ));
+        }
+
+        if self.size_budget_mib == Some(0) {
+            return Err(miette!(
+                "size_budget_mib is set to 0, but it must be larger than 0."
+            ));
+        }
+
+        if self.cover_art_max_dimension == Some(0) {
+            return Err(miette!(
+                "cover_art_max_dimension is set to 0, but it must be larger than 0."
+            ));
+        }
+
+        let cover_art_jpeg_quality = self
+            .cover_art_jpeg_quality
+            .unwrap_or(DEFAULT_COVER_ART_JPEG_QUALITY);
+        if !(1..=100).contains(&cover_art_jpeg_quality) {
+            return Err(miette!(
+                "cover_art_jpeg_quality is set to {}, but it must be between 1 and 100.",
+                cover_art_jpeg_quality
+            ));
         }
 
+        let output_template = self
+            .output_template
+            .as_deref()
+            .map(OutputPathTemplate::parse)
+            .transpose()?;
 
         Ok(AggregatedLibraryConfiguration {
             path,
+            mode: AggregationMode::default(),
+            device_profile: None,
             transcode_threads: self.transcode_threads,
             failure_max_retries: self.failure_max_retries,
             failure_delay_seconds: self.failure_delay_seconds,
+            retryable_error_categories: self
+                .retryable_error_categories
+                .unwrap_or_else(|| DEFAULT_RETRYABLE_ERROR_CATEGORIES.to_vec()),
+            preserve_permissions: self
+                .preserve_permissions
+                .unwrap_or(DEFAULT_PRESERVE_PERMISSIONS),
+            permissions_umask,
+            skip_unchanged_data: self
+                .skip_unchanged_data
+                .unwrap_or(DEFAULT_SKIP_UNCHANGED_DATA),
+            copy_buffer_bytes: self
+                .copy_buffer_bytes
+                .unwrap_or(DEFAULT_COPY_BUFFER_BYTES),
+            max_reprocess_percentage: self.max_reprocess_percentage,
+            generate_master_playlist: self
+                .generate_master_playlist
+                .unwrap_or(DEFAULT_GENERATE_MASTER_PLAYLIST),
+            filesystem: self.filesystem.unwrap_or_default(),
+            case_collision_strategy: self
+                .case_collision_strategy
+                .unwrap_or_default(),
+            sanitize_file_names: self
+                .sanitize_file_names
+                .unwrap_or(DEFAULT_SANITIZE_FILE_NAMES),
+            write_checksum_manifest: self
+                .write_checksum_manifest
+                .unwrap_or(DEFAULT_WRITE_CHECKSUM_MANIFEST),
+            size_budget_mib: self.size_budget_mib,
+            free_space_margin_mib: self
+                .free_space_margin_mib
+                .unwrap_or(DEFAULT_FREE_SPACE_MARGIN_MIB),
+            cover_art_max_dimension: self.cover_art_max_dimension,
+            cover_art_jpeg_quality,
+            minimum_transcoded_file_size_bytes: self
+                .minimum_transcoded_file_size_bytes
+                .unwrap_or(DEFAULT_MINIMUM_TRANSCODED_FILE_SIZE_BYTES),
+            verify_transcoded_files_decode: self
+                .verify_transcoded_files_decode
+                .unwrap_or(DEFAULT_VERIFY_TRANSCODED_FILES_DECODE),
+            output_template,
         })
     }
 }
@@ -0,0 +1,164 @@
+use miette::miette;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aggregated_library::{CaseCollisionStrategy, OutputFilesystem},
+    paths::PathsConfiguration,
+    tools::ensure_ffmpeg_args_have_placeholders,
+    traits::ResolvableWithPathsConfiguration,
+    utilities::replace_placeholders_in_str,
+};
+
+
+/// Name that refers to the base configuration, i.e. no device profile at all
+/// (see `transcode --profile`). It can't be used as a device profile name.
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+
+
+/// Whether `profile_name` can be used as a device profile name: as it is part of the profile's
+/// state file names (see `state_file_name_for_device_profile`), only ASCII letters, digits,
+/// `-` and `_` are allowed.
+pub fn is_valid_device_profile_name(profile_name: &str) -> bool {
+    !profile_name.is_empty()
+        && profile_name.chars().all(|character| {
+            character.is_ascii_alphanumeric()
+                || character == '-'
+                || character == '_'
+        })
+}
+
+/// Get the name of a state file kept in the source libraries for the given device profile.
+/// Each profile keeps its own source states, so that syncing a device and transcoding
+/// into the aggregated library don't invalidate each other's states.
+///
+/// Without a profile, `state_file_name` is returned unchanged.
+///
+/// # Example
+/// ```
+/// # use euphony_configuration::device_profile::state_file_name_for_device_profile;
+/// assert_eq!(
+///     state_file_name_for_device_profile(".album.source-state.euphony", Some("phone")),
+///     ".album.source-state.phone.euphony"
+/// );
+/// assert_eq!(
+///     state_file_name_for_device_profile(".album.source-state.euphony", None),
+///     ".album.source-state.euphony"
+/// );
+/// ```
+pub fn state_file_name_for_device_profile(
+    state_file_name: &str,
+    device_profile: Option<&str>,
+) -> String {
+    let Some(device_profile) = device_profile else {
+        return state_file_name.to_string();
+    };
+
+    match state_file_name.strip_suffix(".euphony") {
+        Some(file_stem) => format!("{file_stem}.{device_profile}.euphony"),
+        None => format!("{state_file_name}.{device_profile}"),
+    }
+}
+
+
+/// A named set of overrides for the `sync-device` command (`[device_profiles.<name>]`),
+/// bundling the settings of a portable device. Unset options keep their base values.
+#[derive(Serialize, Clone)]
+pub struct DeviceProfileConfiguration {
+    /// Directory to transcode into (e.g. the device's mount point).
+    /// Must be separate from the aggregated library.
+    pub path: String,
+
+    /// Overrides `tools.ffmpeg.audio_transcoding_args`
+    /// (and disables `tools.ffmpeg.audio_transcoding_args_per_extension`
+    /// and the arguments of the libraries' transcoding extension rules).
+    pub audio_transcoding_args: Option<Vec<String>>,
+
+    /// Overrides `tools.ffmpeg.audio_transcoding_output_extension`.
+    pub audio_transcoding_output_extension: Option<String>,
+
+    /// Overrides `aggregated_library.filesystem`.
+    pub filesystem: Option<OutputFilesystem>,
+
+    /// Overrides `aggregated_library.case_collision_strategy`.
+    pub case_collision_strategy: Option<CaseCollisionStrategy>,
+
+    /// Overrides `aggregated_library.sanitize_file_names`.
+    pub sanitize_file_names: Option<bool>,
+
+    /// Overrides `aggregated_library.generate_master_playlist`.
+    pub generate_master_playlist: Option<bool>,
+
+    /// Overrides `aggregated_library.size_budget_mib`.
+    pub size_budget_mib: Option<u64>,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct UnresolvedDeviceProfileConfiguration {
+    path: String,
+
+    audio_transcoding_args: Option<Vec<String>>,
+
+    audio_transcoding_output_extension: Option<String>,
+
+    filesystem: Option<OutputFilesystem>,
+
+    case_collision_strategy: Option<CaseCollisionStrategy>,
+
+    sanitize_file_names: Option<bool>,
+
+    generate_master_playlist: Option<bool>,
+
+    size_budget_mib: Option<u64>,
+}
+
+impl ResolvableWithPathsConfiguration for UnresolvedDeviceProfileConfiguration {
+    type Resolved = DeviceProfileConfiguration;
+
+    fn resolve(
+        self,
+        paths: &PathsConfiguration,
+    ) -> miette::Result<Self::Resolved> {
+        let path = replace_placeholders_in_str(
+            &self.path,
+            "device profile path",
+            &[("{LIBRARY_BASE}", &paths.base_library_path)],
+        );
+
+        if let Some(audio_transcoding_args) = &self.audio_transcoding_args {
+            ensure_ffmpeg_args_have_placeholders(
+                audio_transcoding_args,
+                "audio_transcoding_args",
+            )?;
+        }
+
+        let audio_transcoding_output_extension = self
+            .audio_transcoding_output_extension
+            .map(|extension| extension.to_ascii_lowercase());
+
+        if audio_transcoding_output_extension
+            .as_ref()
+            .is_some_and(|extension| extension.is_empty())
+        {
+            return Err(miette!(
+                "audio_transcoding_output_extension must not be empty."
+            ));
+        }
+
+        if self.size_budget_mib == Some(0) {
+            return Err(miette!(
+                "size_budget_mib is set to 0, but it must be larger than 0."
+            ));
+        }
+
+        Ok(DeviceProfileConfiguration {
+            path,
+            audio_transcoding_args: self.audio_transcoding_args,
+            audio_transcoding_output_extension,
+            filesystem: self.filesystem,
+            case_collision_strategy: self.case_collision_strategy,
+            sanitize_file_names: self.sanitize_file_names,
+            generate_master_playlist: self.generate_master_playlist,
+            size_budget_mib: self.size_budget_mib,
+        })
+    }
+}
@@ -0,0 +1,129 @@
+use miette::miette;
+use serde::{Deserialize, Serialize};
+
+use crate::traits::ResolvableConfiguration;
+
+/// Default for `file_metadata.meta_file_name`.
+pub const DEFAULT_META_FILE_NAME: &str = ".library.state.euphony";
+
+/// Which file metadata is compared between transcodes to decide
+/// whether a source file has changed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ChangeDetectionMode {
+    /// Only a change in file size counts as a change.
+    #[serde(rename = "size")]
+    Size,
+
+    /// Only a change in file creation or modification time counts as a change.
+    #[serde(rename = "mtime")]
+    ModificationTime,
+
+    /// A change in either file size or file creation/modification time counts as a change.
+    #[default]
+    #[serde(rename = "size_and_mtime")]
+    SizeAndModificationTime,
+
+    /// Only a change in file contents counts as a change (compared using a BLAKE3 digest
+    /// of each file). Digests are only recomputed for files whose size or time changed.
+    #[serde(rename = "hash")]
+    ContentHash,
+}
+
+impl ChangeDetectionMode {
+    /// Whether a difference in file size should count as a change.
+    pub fn compares_size(&self) -> bool {
+        matches!(
+            self,
+            Self::Size | Self::SizeAndModificationTime | Self::ContentHash
+        )
+    }
+
+    /// Whether a difference in file creation or modification time should count as a change.
+    pub fn compares_time(&self) -> bool {
+        matches!(
+            self,
+            Self::ModificationTime | Self::SizeAndModificationTime
+        )
+    }
+
+    /// Whether the digests of file contents should be compared.
+    pub fn compares_content_hash(&self) -> bool {
+        matches!(self, Self::ContentHash)
+    }
+
+    /// The name of the mode as used in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Size => "size",
+            Self::ModificationTime => "mtime",
+            Self::SizeAndModificationTime => "size_and_mtime",
+            Self::ContentHash => "hash",
+        }
+    }
+}
+
+
+#[derive(Serialize, Clone)]
+pub struct FileMetadataConfiguration {
+    /// Which file metadata is compared to detect changed source files
+    /// (see `ChangeDetectionMode`).
+    pub change_detection: ChangeDetectionMode,
+
+    /// Name of the library state file kept in the root of each source library
+    /// (see `DEFAULT_META_FILE_NAME`). Device profiles derive the names
+    /// of their own state files from it.
+    pub meta_file_name: String,
+}
+
+/// Used when the `file_metadata` table is omitted.
+impl Default for FileMetadataConfiguration {
+    fn default() -> Self {
+        Self {
+            change_detection: ChangeDetectionMode::default(),
+            meta_file_name: DEFAULT_META_FILE_NAME.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct UnresolvedFileMetadataConfiguration {
+    change_detection: Option<ChangeDetectionMode>,
+
+    meta_file_name: Option<String>,
+}
+
+impl ResolvableConfiguration for UnresolvedFileMetadataConfiguration {
+    type Resolved = FileMetadataConfiguration;
+
+    fn resolve(self) -> miette::Result<Self::Resolved> {
+        let meta_file_name = self
+            .meta_file_name
+            .unwrap_or_else(|| DEFAULT_META_FILE_NAME.to_string());
+
+        if meta_file_name.trim().is_empty() {
+            return Err(miette!("file_metadata.meta_file_name is empty."));
+        }
+
+        if meta_file_name.contains(['/', '\\']) {
+            return Err(miette!(
+                "file_metadata.meta_file_name must be a file name, not a path \
+                (got \"{}\").",
+                meta_file_name
+            ));
+        }
+
+        // Without a leading dot, the file is not hidden and shows up in file managers.
+        #[cfg(unix)]
+        if !meta_file_name.starts_with('.') {
+            tracing::warn!(
+                "file_metadata.meta_file_name (\"{meta_file_name}\") \
+                doesn't start with a dot, so the library state files won't be hidden."
+            );
+        }
+
+        Ok(FileMetadataConfiguration {
+            change_detection: self.change_detection.unwrap_or_default(),
+            meta_file_name,
+        })
+    }
+}
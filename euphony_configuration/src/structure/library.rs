@@ -1,16 +1,26 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Component, Path};
 
-use miette::Result;
-use serde::Deserialize;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use miette::{miette, IntoDiagnostic, Result, WrapErr};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     filesystem::get_path_extension_or_empty,
+    output_template::OutputPathTemplate,
     paths::PathsConfiguration,
+    tools::ensure_ffmpeg_args_have_placeholders,
     traits::{ResolvableConfiguration, ResolvableWithPathsConfiguration},
+    utilities::replace_placeholders_in_str,
 };
 
+/// Default value of `LibraryConfiguration::ignored_names_at_any_depth`:
+/// metadata directories and files created by NAS devices, sync tools and macOS.
+pub const DEFAULT_IGNORED_NAMES_AT_ANY_DEPTH: [&str; 3] =
+    ["@eaDir", ".stfolder", ".DS_Store"];
 
-#[derive(Clone)]
+
+#[derive(Serialize, Clone)]
 pub struct LibraryConfiguration {
     /// Library display name.
     pub name: String,
@@ -19,14 +29,73 @@ pub struct LibraryConfiguration {
     /// which will be dynamically replaced with `essentials.base_library_path` on load).
     pub path: String,
 
+    /// Names of directories in the base of the library that should be ignored.
+    /// Each entry is either an exact directory name or a glob pattern (e.g. `_incoming-*`);
+    /// use `is_directory_ignored_in_base_directory` to check a directory name.
     pub ignored_directories_in_base_directory: Option<Vec<String>>,
 
+    /// Compiled glob patterns from `ignored_directories_in_base_directory`.
+    #[serde(skip)]
+    ignored_directories_in_base_directory_matcher: GlobSet,
+
+    /// Names (or glob patterns) of files and directories that should be ignored
+    /// at any depth of the library (e.g. `@eaDir`), both when validating and transcoding.
+    /// Defaults to `DEFAULT_IGNORED_NAMES_AT_ANY_DEPTH`.
+    pub ignored_names_at_any_depth: Vec<String>,
+
+    /// Compiled glob patterns from `ignored_names_at_any_depth`.
+    #[serde(skip)]
+    ignored_names_at_any_depth_matcher: GlobSet,
+
     /// Validation-related configuration for this library.
     pub validation: LibraryValidationConfiguration,
 
     /// Transcoding-related configuration for this library.
     pub transcoding: LibraryTranscodingConfiguration,
 }
+impl LibraryConfiguration {
+    /// Returns `true` when a directory with the given name in the base of the library
+    /// should be ignored, i.e. when it matches an entry in `ignored_directories_in_base_directory`
+    /// (either exactly or as a glob pattern) or in `ignored_names_at_any_depth`.
+    pub fn is_directory_ignored_in_base_directory<S: AsRef<str>>(
+        &self,
+        directory_name: S,
+    ) -> bool {
+        let directory_name = directory_name.as_ref();
+
+        let is_exact_match = self
+            .ignored_directories_in_base_directory
+            .as_ref()
+            .is_some_and(|ignored| {
+                ignored.iter().any(|ignored| ignored == directory_name)
+            });
+
+        is_exact_match
+            || self
+                .ignored_directories_in_base_directory_matcher
+                .is_match(directory_name)
+            || self.is_name_ignored_at_any_depth(directory_name)
+    }
+
+    /// Returns `true` when a file or directory with the given name should be ignored
+    /// (see `ignored_names_at_any_depth`).
+    pub fn is_name_ignored_at_any_depth<S: AsRef<str>>(&self, name: S) -> bool {
+        self.ignored_names_at_any_depth_matcher
+            .is_match(name.as_ref())
+    }
+
+    /// Returns `true` when any component of the given (relative) path is ignored,
+    /// i.e. when the path is an ignored file or directory or is inside an ignored directory
+    /// (see `ignored_names_at_any_depth`).
+    pub fn is_path_ignored_at_any_depth<P: AsRef<Path>>(&self, path: P) -> bool {
+        path.as_ref().components().any(|component| match component {
+            Component::Normal(name) => {
+                self.is_name_ignored_at_any_depth(name.to_string_lossy())
+            }
+            _ => false,
+        })
+    }
+}
 
 #[derive(Deserialize, Clone)]
 pub(crate) struct UnresolvedLibraryConfiguration {
@@ -36,6 +105,8 @@ pub(crate) struct UnresolvedLibraryConfiguration {
 
     ignored_directories_in_base_directory: Option<Vec<String>>,
 
+    ignored_names_at_any_depth: Option<Vec<String>>,
+
     validation: UnresolvedLibraryValidationConfiguration,
 
     transcoding: UnresolvedLibraryTranscodingConfiguration,
@@ -48,9 +119,11 @@ impl ResolvableWithPathsConfiguration for UnresolvedLibraryConfiguration {
         self,
         paths: &PathsConfiguration,
     ) -> miette::Result<Self::Resolved> {
-        let parsed_path = self
-            .path
-            .replace("{LIBRARY_BASE}", &paths.base_library_path);
+        let parsed_path = replace_placeholders_in_str(
+            &self.path,
+            &format!("libraries.{}.path", self.name),
+            &[("{LIBRARY_BASE}", &paths.base_library_path)],
+        );
 
         let canonicalized_path = dunce::canonicalize(parsed_path)
             .unwrap_or_else(|_| {
@@ -70,21 +143,71 @@ impl ResolvableWithPathsConfiguration for UnresolvedLibraryConfiguration {
 
         let path = canonicalized_path.to_string_lossy().to_string();
 
+        let ignored_directories_in_base_directory_matcher = build_glob_set(
+            self.ignored_directories_in_base_directory.iter().flatten(),
+            &self.name,
+            "ignored_directories_in_base_directory",
+        )?;
+
+        let ignored_names_at_any_depth =
+            self.ignored_names_at_any_depth.unwrap_or_else(|| {
+                DEFAULT_IGNORED_NAMES_AT_ANY_DEPTH
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect()
+            });
+
+        let ignored_names_at_any_depth_matcher = build_glob_set(
+            &ignored_names_at_any_depth,
+            &self.name,
+            "ignored_names_at_any_depth",
+        )?;
+
 
         Ok(LibraryConfiguration {
             name: self.name,
             path,
             ignored_directories_in_base_directory: self
                 .ignored_directories_in_base_directory,
+            ignored_directories_in_base_directory_matcher,
+            ignored_names_at_any_depth,
+            ignored_names_at_any_depth_matcher,
             validation: self.validation.resolve()?,
             transcoding: self.transcoding.resolve()?,
         })
     }
 }
 
+/// Compile the given glob patterns (from the `option_name` option of the library
+/// named `library_name`) into a single `GlobSet`.
+fn build_glob_set<'p, I>(
+    patterns: I,
+    library_name: &str,
+    option_name: &str,
+) -> Result<GlobSet>
+where
+    I: IntoIterator<Item = &'p String>,
+{
+    let mut glob_set_builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let glob = Glob::new(pattern).into_diagnostic().wrap_err_with(|| {
+            miette!(
+                "Library \"{}\" has an invalid pattern in {}: \"{}\".",
+                library_name,
+                option_name,
+                pattern
+            )
+        })?;
+
+        glob_set_builder.add(glob);
+    }
 
+    glob_set_builder.build().into_diagnostic()
+}
 
-#[derive(Clone)]
+
+#[derive(Serialize, Clone)]
 pub struct LibraryValidationConfiguration {
     /// A list of allowed audio extensions. Any not specified here are forbidden
     /// (flagged when running validation), see configuration template for more information.
@@ -131,22 +254,144 @@ impl ResolvableConfiguration for UnresolvedLibraryValidationConfiguration {
 
 
 
-#[derive(Clone)]
+/// How audio files with a specific extension are processed
+/// (see `LibraryTranscodingConfiguration::extension_rules`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ExtensionTranscodingRule {
+    /// Transcode the files using ffmpeg (the default for audio files).
+    /// If `args` are set, they are used instead of the ones from `tools.ffmpeg`.
+    Transcode {
+        #[serde(default)]
+        args: Option<Vec<String>>,
+    },
+
+    /// Copy the files as-is (e.g. to avoid re-encoding already lossy files).
+    Copy,
+}
+
+
+/// Where the artist and album information used for output paths in the aggregated library
+/// comes from (see `LibraryTranscodingConfiguration::album_info_source`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AlbumInfoSource {
+    /// Artist and album directory names (and file names, e.g. `01 - Title.flac`).
+    /// Without an output template, the aggregated library mirrors the source layout.
+    #[default]
+    #[serde(rename = "directories")]
+    Directories,
+
+    /// Output paths are built from the tags embedded in the audio files
+    /// (see `LibraryTranscodingConfiguration::output_template`), falling back
+    /// to the directory names when tags are missing.
+    #[serde(rename = "tags")]
+    Tags,
+}
+
+impl AlbumInfoSource {
+    /// The name of the source as used in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Directories => "directories",
+            Self::Tags => "tags",
+        }
+    }
+}
+
+
+/// How the disc subdirectories of multi-disc albums (e.g. `Album/CD1`, `Album/Disc 2`)
+/// are laid out in the aggregated library
+/// (see `LibraryTranscodingConfiguration::disc_subdirectories`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DiscSubdirectoryLayout {
+    /// Disc subdirectories are kept (e.g. `CD1/01 - Song.mp3`).
+    #[default]
+    #[serde(rename = "preserve")]
+    Preserve,
+
+    /// Files of disc subdirectories are moved into the album directory, prefixed
+    /// with the disc number (e.g. `CD1/01 - Song.flac` becomes `D01 01 - Song.mp3`).
+    #[serde(rename = "flatten")]
+    Flatten,
+}
+
+impl DiscSubdirectoryLayout {
+    /// The name of the layout as used in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Preserve => "preserve",
+            Self::Flatten => "flatten",
+        }
+    }
+}
+
+
+#[derive(Serialize, Clone)]
 pub struct LibraryTranscodingConfiguration {
+    /// Whether the library is transcoded into the aggregated library (defaults to `true`).
+    /// Disabled libraries are still validated and listed, but never transcoded.
+    pub enabled: bool,
+
     /// A list of audio file extensions (e.g. "mp3", "flac" - don't include ".").
     /// Files with these extensions are considered audio files and are transcoded using ffmpeg
-    /// (see `tools.ffmpeg`).
+    /// (see `tools.ffmpeg`), unless `extension_rules` says otherwise.
     pub audio_file_extensions: Vec<String>,
 
     /// A list of other tracked file extensions (e.g. `jpg`, `png` - don't include ".").
     /// Files with these extensions are considered data files and are copied when transcoding.
     pub other_file_extensions: Vec<String>,
 
+    /// Per-extension processing rules for audio files, keyed by the (lowercase) extension.
+    /// Extensions with a rule are audio files (they are added to `audio_file_extensions`
+    /// if missing); audio files without a rule are transcoded with the `tools.ffmpeg` arguments.
+    pub extension_rules: HashMap<String, ExtensionTranscodingRule>,
+
+    /// Where the values of the `output_template` placeholders come from:
+    /// the directory and file names (the default) or the embedded tags.
+    pub album_info_source: AlbumInfoSource,
+
+    /// Template for output paths, relative to the aggregated library and without the extension
+    /// (see `OutputPathTemplate`). If `None`, output paths mirror the source directory structure.
+    ///
+    /// Libraries without their own template use `aggregated_library.output_template`,
+    /// or, if `album_info_source` is `tags`, `DEFAULT_OUTPUT_TEMPLATE`.
+    pub output_template: Option<OutputPathTemplate>,
+
+    /// If enabled, ReplayGain tags (track and album gain and peak) are computed with ffmpeg's
+    /// `replaygain` filter and written into the transcoded audio files after each album
+    /// is processed. Audio files that are copied as-is are not tagged.
+    pub replaygain: bool,
+
+    /// How disc subdirectories of multi-disc albums are laid out in the aggregated library.
+    /// Albums with disc subdirectories are always scanned at least one level deep
+    /// (see `AlbumScanConfiguration::depth`), so all of their discs are tracked.
+    pub disc_subdirectories: DiscSubdirectoryLayout,
+
     /// Dynamically contains extensions from both `audio_file_extensions` and `other_file_extensions`.
+    #[serde(skip)]
     pub all_tracked_extensions: Vec<String>,
 }
 
 impl LibraryTranscodingConfiguration {
+    /// Fall back to the aggregated library's output template (if any) when the library
+    /// doesn't set its own, or to the default template if output paths are built from tags.
+    pub(crate) fn apply_aggregated_output_template(
+        &mut self,
+        aggregated_output_template: Option<&OutputPathTemplate>,
+    ) {
+        if self.output_template.is_some() {
+            return;
+        }
+
+        self.output_template = match aggregated_output_template {
+            Some(template) => Some(template.clone()),
+            None if self.album_info_source == AlbumInfoSource::Tags => {
+                Some(OutputPathTemplate::default())
+            }
+            None => None,
+        };
+    }
+
     /// Returns `Ok(true)` when the given file path's extension is considered an audio file.
     /// Returns `Err` if the extension is invalid UTF-8.
     pub fn is_path_audio_file_by_extension<P: AsRef<Path>>(
@@ -168,19 +413,55 @@ impl LibraryTranscodingConfiguration {
 
         Ok(self.other_file_extensions.contains(&extension))
     }
+
+    /// Returns `Ok(true)` when the given file path is an audio file that should be copied as-is
+    /// instead of transcoded (see `ExtensionTranscodingRule::Copy`).
+    /// Returns `Err` if the extension is invalid UTF-8.
+    pub fn is_path_copied_audio_file_by_extension<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<bool> {
+        let extension = get_path_extension_or_empty(file_path)?;
+
+        Ok(self.extension_rules.get(&extension)
+            == Some(&ExtensionTranscodingRule::Copy))
+    }
+
+    /// Returns the ffmpeg arguments the extension rule of the given file path sets,
+    /// if there is such a rule (see `ExtensionTranscodingRule::Transcode`).
+    /// Returns `Err` if the extension is invalid UTF-8.
+    pub fn audio_transcoding_args_for_path<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+    ) -> Result<Option<&[String]>> {
+        let extension = get_path_extension_or_empty(file_path)?;
+
+        Ok(match self.extension_rules.get(&extension) {
+            Some(ExtensionTranscodingRule::Transcode { args: Some(args) }) => {
+                Some(args)
+            }
+            _ => None,
+        })
+    }
 }
 
 #[derive(Deserialize, Clone)]
 pub(crate) struct UnresolvedLibraryTranscodingConfiguration {
+    enabled: Option<bool>,
     audio_file_extensions: Vec<String>,
     other_file_extensions: Vec<String>,
+    extension_rules: Option<HashMap<String, ExtensionTranscodingRule>>,
+    album_info_source: Option<AlbumInfoSource>,
+    output_template: Option<String>,
+    replaygain: Option<bool>,
+    disc_subdirectories: Option<DiscSubdirectoryLayout>,
 }
 
 impl ResolvableConfiguration for UnresolvedLibraryTranscodingConfiguration {
     type Resolved = LibraryTranscodingConfiguration;
 
     fn resolve(self) -> miette::Result<Self::Resolved> {
-        let audio_file_extensions: Vec<String> = self
+        let mut audio_file_extensions: Vec<String> = self
             .audio_file_extensions
             .into_iter()
             .map(|extention| extention.to_ascii_lowercase())
@@ -192,16 +473,62 @@ impl ResolvableConfiguration for UnresolvedLibraryTranscodingConfiguration {
             .map(|extention| extention.to_ascii_lowercase())
             .collect();
 
+        let extension_rules: HashMap<String, ExtensionTranscodingRule> = self
+            .extension_rules
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(extension, rule)| (extension.to_ascii_lowercase(), rule))
+            .collect();
+
+        // Sorted, so the added audio extensions are in a stable order.
+        let mut rule_extensions: Vec<&String> = extension_rules.keys().collect();
+        rule_extensions.sort_unstable();
+
+        for extension in rule_extensions {
+            if other_file_extensions.contains(extension) {
+                return Err(miette!(
+                    "extension_rules contains a rule for \"{extension}\", \
+                    but it is listed in other_file_extensions: \
+                    rules can only be set for audio files."
+                ));
+            }
+
+            if let ExtensionTranscodingRule::Transcode { args: Some(args) } =
+                &extension_rules[extension]
+            {
+                ensure_ffmpeg_args_have_placeholders(
+                    args,
+                    &format!("extension_rules.{extension}.args"),
+                )?;
+            }
+
+            if !audio_file_extensions.contains(extension) {
+                audio_file_extensions.push(extension.clone());
+            }
+        }
+
         let mut all_tracked_extensions = Vec::with_capacity(
             audio_file_extensions.len() + other_file_extensions.len(),
         );
         all_tracked_extensions.extend(audio_file_extensions.iter().cloned());
         all_tracked_extensions.extend(other_file_extensions.iter().cloned());
 
+        let output_template = self
+            .output_template
+            .as_deref()
+            .map(OutputPathTemplate::parse)
+            .transpose()?;
+
 
         Ok(LibraryTranscodingConfiguration {
+            enabled: self.enabled.unwrap_or(true),
             audio_file_extensions,
             other_file_extensions,
+            extension_rules,
+            album_info_source: self.album_info_source.unwrap_or_default(),
+            output_template,
+            replaygain: self.replaygain.unwrap_or(false),
+            disc_subdirectories: self.disc_subdirectories.unwrap_or_default(),
             all_tracked_extensions,
         })
     }
@@ -1,24 +1,42 @@
 use std::path::PathBuf;
 
+use chrono::format::{Item, StrftimeItems};
 use chrono::Local;
-use serde::Deserialize;
+use miette::miette;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     paths::PathsConfiguration,
     traits::ResolvableWithPathsConfiguration,
-    utilities::get_running_executable_directory,
+    utilities::{get_running_executable_directory, replace_placeholders_in_str},
 };
 
+/// Timestamp format of log file lines, unless `logging.log_file_timestamp_format` is set
+/// (ISO 8601 in UTC, e.g. `2024-01-02T15:04:05Z`).
+pub const DEFAULT_LOG_FILE_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
 
-#[derive(Clone)]
+
+#[derive(Serialize, Clone)]
 pub struct LoggingConfiguration {
     pub default_log_output_path: Option<PathBuf>,
+
+    /// `strftime`-like format (see `chrono::format::strftime`) of the UTC timestamp
+    /// each line of the log file is prefixed with (along with its level).
+    pub log_file_timestamp_format: String,
+
+    /// Whether logs are appended to an existing log file (the default)
+    /// or the file is overwritten (see `--log-append` and `--log-overwrite`).
+    pub append_to_existing_log_file: bool,
 }
 
 
 #[derive(Deserialize, Clone)]
 pub(crate) struct UnresolvedLoggingConfiguration {
     default_log_output_path: Option<PathBuf>,
+
+    log_file_timestamp_format: Option<String>,
+
+    append_to_existing_log_file: Option<bool>,
 }
 
 
@@ -38,18 +56,39 @@ impl ResolvableWithPathsConfiguration for UnresolvedLoggingConfiguration {
 
         let default_log_output_path =
             self.default_log_output_path.as_ref().map(|output_path| {
-                let path_as_string = output_path
-                    .to_string_lossy()
-                    .to_string()
-                    .replace("{LIBRARY_BASE}", &paths.base_library_path)
-                    .replace("{SELF}", &executable_directory)
-                    .replace("{DATETIME}", &formatted_time_now.to_string());
+                let path_as_string = replace_placeholders_in_str(
+                    &output_path.to_string_lossy(),
+                    "logging.default_log_output_path",
+                    &[
+                        ("{LIBRARY_BASE}", &paths.base_library_path),
+                        ("{SELF}", &executable_directory),
+                        ("{DATETIME}", &formatted_time_now.to_string()),
+                    ],
+                );
 
                 PathBuf::from(path_as_string)
             });
 
+        let log_file_timestamp_format = self
+            .log_file_timestamp_format
+            .unwrap_or_else(|| DEFAULT_LOG_FILE_TIMESTAMP_FORMAT.to_string());
+
+        if StrftimeItems::new(&log_file_timestamp_format)
+            .any(|item| matches!(item, Item::Error))
+        {
+            return Err(miette!(
+                "Invalid logging.log_file_timestamp_format: \"{}\" \
+                is not a valid strftime format.",
+                log_file_timestamp_format
+            ));
+        }
+
         Ok(LoggingConfiguration {
             default_log_output_path,
+            log_file_timestamp_format,
+            append_to_existing_log_file: self
+                .append_to_existing_log_file
+                .unwrap_or(true),
         })
     }
 }
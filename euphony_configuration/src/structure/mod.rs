@@ -2,25 +2,45 @@
 //! the main euphony configuration.
 
 pub mod aggregated_library;
+pub mod device_profile;
+pub mod file_metadata;
 pub mod library;
 pub mod logging;
+pub mod output_template;
 pub mod paths;
 pub mod tools;
 pub mod ui;
 pub mod validation;
 
 use std::collections::BTreeMap;
-use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::{env, fs};
 
-use miette::{miette, Context, Result};
-use serde::Deserialize;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::aggregated_library::{
     AggregatedLibraryConfiguration,
+    AggregationMode,
     UnresolvedAggregatedLibraryConfiguration,
 };
-use crate::library::{LibraryConfiguration, UnresolvedLibraryConfiguration};
+use crate::device_profile::{
+    is_valid_device_profile_name,
+    DeviceProfileConfiguration,
+    UnresolvedDeviceProfileConfiguration,
+    DEFAULT_PROFILE_NAME,
+};
+use crate::error::ConfigurationError;
+use crate::file_metadata::{
+    FileMetadataConfiguration,
+    UnresolvedFileMetadataConfiguration,
+};
+use crate::library::{
+    ExtensionTranscodingRule,
+    LibraryConfiguration,
+    UnresolvedLibraryConfiguration,
+};
 use crate::logging::{LoggingConfiguration, UnresolvedLoggingConfiguration};
 use crate::paths::{PathsConfiguration, UnresolvedPathsConfiguration};
 use crate::tools::{ToolsConfiguration, UnresolvedToolsConfiguration};
@@ -36,9 +56,14 @@ use crate::validation::{
     ValidationConfiguration,
 };
 
+/// The configuration schema version this build of euphony supports
+/// (the `schema_version` at the top of the configuration file). It is increased
+/// whenever a change to the configuration would make older configurations misinterpreted.
+pub const CONFIGURATION_SCHEMA_VERSION: u32 = 1;
+
 /// This struct contains the entire `euphony` configuration,
 /// from tool paths to libraries and so forth.
-#[derive(Clone)]
+#[derive(Serialize, Clone)]
 pub struct Configuration {
     pub paths: PathsConfiguration,
 
@@ -48,6 +73,8 @@ pub struct Configuration {
 
     pub validation: ValidationConfiguration,
 
+    pub file_metadata: FileMetadataConfiguration,
+
     pub tools: ToolsConfiguration,
 
     pub libraries: BTreeMap<String, LibraryConfiguration>,
@@ -55,9 +82,22 @@ pub struct Configuration {
     // TODO Should I rename "aggregated library" to something else, like "transcoded library"?
     pub aggregated_library: AggregatedLibraryConfiguration,
 
+    /// Named device profiles for the `sync-device` command (and `transcode --profile`),
+    /// keyed by their names (see `Configuration::into_device_profile_configuration`).
+    /// Can also be configured as `[profiles.<name>]`.
+    pub device_profiles: BTreeMap<String, DeviceProfileConfiguration>,
+
+    /// Path of the loaded configuration file
+    /// (the current working directory if it was read from the standard input).
     pub configuration_file_path: PathBuf,
 }
 
+/// Only the `schema_version` of a configuration (see `ensure_supported_schema_version`).
+#[derive(Deserialize)]
+struct UnresolvedConfigurationSchemaVersion {
+    schema_version: Option<u32>,
+}
+
 #[derive(Deserialize, Clone)]
 struct UnresolvedConfiguration {
     paths: UnresolvedPathsConfiguration,
@@ -68,11 +108,51 @@ struct UnresolvedConfiguration {
 
     validation: UnresolvedValidationConfiguration,
 
+    file_metadata: Option<UnresolvedFileMetadataConfiguration>,
+
     tools: UnresolvedToolsConfiguration,
 
     libraries: BTreeMap<String, UnresolvedLibraryConfiguration>,
 
     aggregated_library: UnresolvedAggregatedLibraryConfiguration,
+
+    #[serde(alias = "profiles")]
+    device_profiles:
+        Option<BTreeMap<String, UnresolvedDeviceProfileConfiguration>>,
+}
+
+/// Ensure the configuration's `schema_version` is the one this build of euphony supports
+/// (see `CONFIGURATION_SCHEMA_VERSION`). This is checked before parsing the rest of the
+/// configuration, so a configuration for a different schema isn't misinterpreted
+/// (or rejected with a confusing parsing error).
+fn ensure_supported_schema_version(
+    configuration_string: &str,
+) -> Result<(), ConfigurationError> {
+    // If even this fails, the full parse will report the error.
+    let Ok(UnresolvedConfigurationSchemaVersion { schema_version }) =
+        toml::from_str(configuration_string)
+    else {
+        return Ok(());
+    };
+
+    match schema_version {
+        None => Err(ConfigurationError::MissingSchemaVersion {
+            expected: CONFIGURATION_SCHEMA_VERSION,
+        }),
+        Some(found) if found > CONFIGURATION_SCHEMA_VERSION => {
+            Err(ConfigurationError::NewerSchemaVersion {
+                expected: CONFIGURATION_SCHEMA_VERSION,
+                found,
+            })
+        }
+        Some(found) if found < CONFIGURATION_SCHEMA_VERSION => {
+            Err(ConfigurationError::OlderSchemaVersion {
+                expected: CONFIGURATION_SCHEMA_VERSION,
+                found,
+            })
+        }
+        Some(_) => Ok(()),
+    }
 }
 
 #[allow(dead_code)]
@@ -84,24 +164,71 @@ impl Configuration {
 
         // Read the configuration file into memory.
         let configuration_string = fs::read_to_string(&configuration_filepath)
-            .expect("Could not read configuration file!");
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not read configuration file {:?}.",
+                    configuration_filepath
+                )
+            })?;
+
+        let configuration_file_path =
+            dunce::canonicalize(&configuration_filepath)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!(
+                        "Could not canonicalize configuration file path {:?}.",
+                        configuration_filepath
+                    )
+                })?;
+
+        Configuration::load_from_str(
+            &configuration_string,
+            configuration_file_path,
+        )
+    }
+
+    /// Parse and resolve the configuration from a TOML string.
+    ///
+    /// As the string doesn't necessarily come from a file, the caller provides
+    /// the `configuration_file_path` to be reported in the resolved configuration.
+    pub fn load_from_str<P: Into<PathBuf>>(
+        configuration_string: &str,
+        configuration_file_path: P,
+    ) -> Result<Configuration> {
+        ensure_supported_schema_version(configuration_string)?;
 
         // Parse the string into the `Config` structure.
         let unresolved_configuration: UnresolvedConfiguration =
-            toml::from_str(&configuration_string)
-                .expect("Could not load configuration file!");
-
-        let configuration_file_path = dunce::canonicalize(configuration_filepath)
-            .expect("Could not canonicalize configuration file path even though it has loaded!");
-
+            toml::from_str(configuration_string)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Could not parse configuration."))?;
 
         // Resolve the configuration into its final state.
         let resolved_configuration =
-            unresolved_configuration.resolve(configuration_file_path)?;
+            unresolved_configuration.resolve(configuration_file_path.into())?;
 
         Ok(resolved_configuration)
     }
 
+    /// Read the entire TOML configuration from `reader` (e.g. the standard input)
+    /// and load it. As there is no configuration file, `configuration_file_path`
+    /// is set to the current working directory.
+    pub fn load_from_reader<R: Read>(mut reader: R) -> Result<Configuration> {
+        let mut configuration_string = String::new();
+        reader
+            .read_to_string(&mut configuration_string)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Could not read configuration."))?;
+
+        let current_directory =
+            env::current_dir().into_diagnostic().wrap_err_with(|| {
+                miette!("Could not get the current working directory.")
+            })?;
+
+        Configuration::load_from_str(&configuration_string, current_directory)
+    }
+
     pub fn load_default_path() -> Result<Configuration> {
         Configuration::load_from_path(
             get_default_configuration_file_path().wrap_err_with(|| {
@@ -135,6 +262,157 @@ impl Configuration {
         None
     }
 
+    /// Resolve the output directory of the `mirror` or `sync-device` command
+    /// into an absolute path, making sure it isn't the aggregated library.
+    fn resolve_separate_output_directory<P: AsRef<Path>>(
+        &self,
+        output_directory_path: P,
+    ) -> Result<String> {
+        // The output directory might not exist yet, so it can't be canonicalized
+        // (joining an absolute path simply returns it unchanged).
+        let output_directory_path = env::current_dir()
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Could not resolve output directory path.")
+            })?
+            .join(output_directory_path);
+        let aggregated_library_path = Path::new(&self.aggregated_library.path);
+
+        let is_aggregated_library = match (
+            dunce::canonicalize(&output_directory_path),
+            dunce::canonicalize(aggregated_library_path),
+        ) {
+            (Ok(output_path), Ok(aggregated_path)) => {
+                output_path == aggregated_path
+            }
+            _ => output_directory_path == aggregated_library_path,
+        };
+
+        if is_aggregated_library {
+            return Err(miette!(
+                "The output directory can't be the aggregated library ({:?}).",
+                aggregated_library_path
+            ));
+        }
+
+        Ok(dunce::simplified(&output_directory_path)
+            .to_string_lossy()
+            .to_string())
+    }
+
+    /// Override `aggregated_library.transcode_threads` (e.g. with the `--threads` option).
+    /// Just like in the configuration file, the minimum value is 1.
+    pub fn with_transcode_threads(
+        mut self,
+        transcode_threads: usize,
+    ) -> Result<Self, ConfigurationError> {
+        if transcode_threads == 0 {
+            return Err(ConfigurationError::ZeroTranscodeThreads);
+        }
+
+        self.aggregated_library.transcode_threads = transcode_threads;
+
+        Ok(self)
+    }
+
+    /// Turn this configuration into one for the `mirror` command: instead of being
+    /// transcoded into the aggregated library, all tracked files (audio files included)
+    /// are copied as-is into `output_directory_path` (see `AggregationMode::Mirror`).
+    ///
+    /// The output directory must not be the aggregated library, as the copies
+    /// would clash with the transcoded files.
+    pub fn into_mirror_configuration<P: AsRef<Path>>(
+        mut self,
+        output_directory_path: P,
+    ) -> Result<Self> {
+        self.aggregated_library.path =
+            self.resolve_separate_output_directory(output_directory_path)?;
+        self.aggregated_library.mode = AggregationMode::Mirror;
+
+        Ok(self)
+    }
+
+    /// Turn this configuration into one for the `sync-device` command: the overrides
+    /// of the given device profile (see `DeviceProfileConfiguration`) are applied
+    /// over the base settings and the profile's directory is used as the aggregated library.
+    ///
+    /// Each profile keeps its own state files in the source libraries
+    /// (see `device_profile::state_file_name_for_device_profile`),
+    /// so syncing a device doesn't interfere with regular transcoding.
+    pub fn into_device_profile_configuration(
+        mut self,
+        profile_name: &str,
+    ) -> Result<Self> {
+        let Some(profile) = self.device_profiles.get(profile_name).cloned()
+        else {
+            let available_profiles = self
+                .device_profiles
+                .keys()
+                .cloned()
+                .collect::<Vec<String>>();
+
+            return Err(miette!(
+                "No such device profile: \"{}\" (available profiles: {}).",
+                profile_name,
+                if available_profiles.is_empty() {
+                    String::from("none")
+                } else {
+                    available_profiles.join(", ")
+                }
+            ));
+        };
+
+        self.aggregated_library.path = self
+            .resolve_separate_output_directory(&profile.path)
+            .wrap_err_with(|| {
+                miette!(
+                    "Invalid path of device profile \"{}\".",
+                    profile_name
+                )
+            })?;
+        self.aggregated_library.device_profile = Some(profile_name.to_string());
+
+        let ffmpeg = &mut self.tools.ffmpeg;
+        if let Some(audio_transcoding_args) = profile.audio_transcoding_args {
+            ffmpeg.audio_transcoding_args = audio_transcoding_args;
+            ffmpeg.audio_transcoding_args_per_extension.clear();
+
+            // The arguments of transcoding extension rules are overridden as well
+            // (copy rules still apply).
+            for library in self.libraries.values_mut() {
+                for rule in library.transcoding.extension_rules.values_mut() {
+                    if let ExtensionTranscodingRule::Transcode { args } = rule {
+                        *args = None;
+                    }
+                }
+            }
+        }
+        if let Some(extension) = profile.audio_transcoding_output_extension {
+            ffmpeg.audio_transcoding_output_extension = extension;
+        }
+
+        let aggregated_library = &mut self.aggregated_library;
+        if let Some(filesystem) = profile.filesystem {
+            aggregated_library.filesystem = filesystem;
+        }
+        if let Some(case_collision_strategy) = profile.case_collision_strategy {
+            aggregated_library.case_collision_strategy = case_collision_strategy;
+        }
+        if let Some(sanitize_file_names) = profile.sanitize_file_names {
+            aggregated_library.sanitize_file_names = sanitize_file_names;
+        }
+        if let Some(generate_master_playlist) = profile.generate_master_playlist
+        {
+            aggregated_library.generate_master_playlist =
+                generate_master_playlist;
+        }
+        if let Some(size_budget_mib) = profile.size_budget_mib {
+            aggregated_library.size_budget_mib = Some(size_budget_mib);
+        }
+
+        Ok(self)
+    }
+
     pub fn get_library_by_full_name<S: AsRef<str>>(
         &self,
         library_name: S,
@@ -156,27 +434,69 @@ impl ResolvableWithContextConfiguration for UnresolvedConfiguration {
         let paths = self.paths.resolve()?;
         let logging = self.logging.resolve(&paths)?;
         let ui = self.ui.resolve()?;
-        let validation = self.validation.resolve()?;
+        let validation = self.validation.resolve(&paths)?;
+        let file_metadata = self
+            .file_metadata
+            .map(|file_metadata| file_metadata.resolve())
+            .transpose()?
+            .unwrap_or_default();
         let tools = self.tools.resolve(&paths)?;
 
+        let aggregated_library = self.aggregated_library.resolve(&paths)?;
+
         let libraries: BTreeMap<String, LibraryConfiguration> = self
             .libraries
             .into_iter()
             .map(|(key, value)| {
-                Ok::<_, miette::Report>((key, value.resolve(&paths)?))
+                let mut library = value.resolve(&paths)?;
+                library.transcoding.apply_aggregated_output_template(
+                    aggregated_library.output_template.as_ref(),
+                );
+
+                Ok::<_, miette::Report>((key, library))
             })
             .collect::<Result<_, _>>()?;
 
-        let aggregated_library = self.aggregated_library.resolve(&paths)?;
+        let device_profiles: BTreeMap<String, DeviceProfileConfiguration> =
+            self.device_profiles
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, profile)| {
+                    if !is_valid_device_profile_name(&name) {
+                        return Err(miette!(
+                            "Invalid device profile name: \"{}\" (only ASCII letters, \
+                            digits, \"-\" and \"_\" are allowed).",
+                            name
+                        ));
+                    }
+
+                    if name == DEFAULT_PROFILE_NAME {
+                        return Err(miette!(
+                            "Invalid device profile name: \"{}\" is reserved \
+                            for the base configuration.",
+                            name
+                        ));
+                    }
+
+                    let profile =
+                        profile.resolve(&paths).wrap_err_with(|| {
+                            miette!("Invalid device profile \"{}\".", name)
+                        })?;
+
+                    Ok((name, profile))
+                })
+                .collect::<Result<_>>()?;
 
         Ok(Configuration {
             paths,
             logging,
             ui,
             validation,
+            file_metadata,
             tools,
             libraries,
             aggregated_library,
+            device_profiles,
             configuration_file_path,
         })
     }
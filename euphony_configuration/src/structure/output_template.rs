@@ -0,0 +1,365 @@
+use std::fmt::{self, Display, Formatter};
+
+use miette::{miette, Result};
+use serde::{Serialize, Serializer};
+
+/// Output template for libraries that build output paths from tags
+/// (see `AlbumInfoSource::Tags`), but don't set one.
+pub const DEFAULT_OUTPUT_TEMPLATE: &str =
+    "{album_artist}/{album}/{track:02} {title}";
+
+
+/// A value that can be substituted into an output path template (see `OutputPathTemplate`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputTemplatePlaceholder {
+    /// `{album_artist}`
+    AlbumArtist,
+
+    /// `{artist}`
+    Artist,
+
+    /// `{album}`
+    Album,
+
+    /// `{year}`
+    Year,
+
+    /// `{disc}`
+    Disc,
+
+    /// `{track}`
+    Track,
+
+    /// `{title}`
+    Title,
+}
+
+impl OutputTemplatePlaceholder {
+    const ALL: [Self; 7] = [
+        Self::AlbumArtist,
+        Self::Artist,
+        Self::Album,
+        Self::Year,
+        Self::Disc,
+        Self::Track,
+        Self::Title,
+    ];
+
+    /// The name of the placeholder as used in templates (without braces).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AlbumArtist => "album_artist",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::Year => "year",
+            Self::Disc => "disc",
+            Self::Track => "track",
+            Self::Title => "title",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|placeholder| placeholder.name() == name)
+    }
+}
+
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum TemplateSegment {
+    Literal(String),
+
+    Placeholder {
+        placeholder: OutputTemplatePlaceholder,
+
+        /// If set (e.g. `{track:02}`), numeric values are padded with zeros to this width.
+        zero_padded_width: Option<usize>,
+    },
+}
+
+/// One `/`-separated component of an output path template.
+type TemplateComponent = Vec<TemplateSegment>;
+
+
+/// A parsed output path template, e.g. `{album_artist}/{year} - {album}/{track:02} {title}`.
+///
+/// The last `/`-separated component is the file name (without the extension),
+/// all the components before it form the album directory (relative to the aggregated library).
+#[derive(Clone, Debug)]
+pub struct OutputPathTemplate {
+    template: String,
+
+    album_directory_components: Vec<TemplateComponent>,
+
+    file_name_component: TemplateComponent,
+}
+
+impl OutputPathTemplate {
+    /// Parse and validate an output path template.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut components = template
+            .split('/')
+            .map(|component| parse_template_component(template, component))
+            .collect::<Result<Vec<TemplateComponent>>>()?;
+
+        if components.len() < 2 {
+            return Err(miette!(
+                "Output template \"{template}\" has no album directory: \
+                it must contain at least one \"/\" (e.g. \"{{album}}/{{title}}\")."
+            ));
+        }
+
+        let file_name_component = components.pop().unwrap_or_default();
+        let has_file_name_placeholder =
+            file_name_component.iter().any(|segment| {
+                matches!(segment, TemplateSegment::Placeholder { .. })
+            });
+
+        if !has_file_name_placeholder {
+            return Err(miette!(
+                "Output template \"{template}\" must contain a placeholder \
+                in its file name (e.g. {{title}}), otherwise all files of an album \
+                would have the same name."
+            ));
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            album_directory_components: components,
+            file_name_component,
+        })
+    }
+
+    /// The template as written in the configuration file.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Render the album directory (relative to the aggregated library).
+    ///
+    /// `value_of` provides the (already sanitized) value of each placeholder.
+    /// Returns `None` if any placeholder in the album directory has no value.
+    pub fn render_album_directory<F>(&self, value_of: F) -> Option<String>
+    where
+        F: Fn(OutputTemplatePlaceholder) -> Option<String>,
+    {
+        self.album_directory_components
+            .iter()
+            .map(|component| render_template_component(component, &value_of))
+            .collect::<Option<Vec<String>>>()
+            .map(|components| components.join("/"))
+    }
+
+    /// Render the file name (without the extension).
+    ///
+    /// `value_of` provides the (already sanitized) value of each placeholder.
+    /// Returns `None` if any placeholder in the file name has no value.
+    pub fn render_file_name<F>(&self, value_of: F) -> Option<String>
+    where
+        F: Fn(OutputTemplatePlaceholder) -> Option<String>,
+    {
+        render_template_component(&self.file_name_component, &value_of)
+    }
+}
+
+impl Default for OutputPathTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_OUTPUT_TEMPLATE)
+            .expect("the default output template is valid")
+    }
+}
+
+impl Display for OutputPathTemplate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+impl Serialize for OutputPathTemplate {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.template)
+    }
+}
+
+
+/// Parse a single `/`-separated component of `template`.
+fn parse_template_component(
+    template: &str,
+    component: &str,
+) -> Result<TemplateComponent> {
+    if component.trim().is_empty() || component == "." || component == ".." {
+        return Err(miette!(
+            "Output template \"{template}\" contains an empty, \".\" or \"..\" path component."
+        ));
+    }
+
+    let mut segments: TemplateComponent = Vec::new();
+    let mut remaining = component;
+
+    while let Some(opening_brace_index) = remaining.find('{') {
+        if opening_brace_index > 0 {
+            segments.push(TemplateSegment::Literal(
+                remaining[..opening_brace_index].to_string(),
+            ));
+        }
+
+        let after_brace = &remaining[opening_brace_index + 1..];
+        let closing_brace_index = after_brace.find('}').ok_or_else(|| {
+            miette!("Output template \"{template}\" has an unclosed \"{{\".")
+        })?;
+
+        segments.push(parse_placeholder(
+            template,
+            &after_brace[..closing_brace_index],
+        )?);
+        remaining = &after_brace[closing_brace_index + 1..];
+    }
+
+    if remaining.contains('}') {
+        return Err(miette!(
+            "Output template \"{template}\" has an unmatched \"}}\"."
+        ));
+    }
+
+    if !remaining.is_empty() {
+        segments.push(TemplateSegment::Literal(remaining.to_string()));
+    }
+
+    Ok(segments)
+}
+
+/// Parse the inside of a placeholder, e.g. `track:02`.
+fn parse_placeholder(
+    template: &str,
+    placeholder: &str,
+) -> Result<TemplateSegment> {
+    let (name, format) = match placeholder.split_once(':') {
+        Some((name, format)) => (name, Some(format)),
+        None => (placeholder, None),
+    };
+
+    let parsed_placeholder = OutputTemplatePlaceholder::from_name(name)
+        .ok_or_else(|| {
+            miette!(
+                "Output template \"{template}\" contains an unknown placeholder \"{{{name}}}\" \
+                (available: {}).",
+                OutputTemplatePlaceholder::ALL
+                    .iter()
+                    .map(|placeholder| format!("{{{}}}", placeholder.name()))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        })?;
+
+    let zero_padded_width = match format {
+        Some(format) => {
+            let width = format
+                .strip_prefix('0')
+                .and_then(|width| width.parse::<usize>().ok())
+                .filter(|width| *width > 0)
+                .ok_or_else(|| {
+                    miette!(
+                        "Output template \"{template}\" has an invalid format in \"{{{placeholder}}}\": \
+                        only zero-padding is supported (e.g. \"{{track:02}}\")."
+                    )
+                })?;
+
+            Some(width)
+        }
+        None => None,
+    };
+
+    Ok(TemplateSegment::Placeholder {
+        placeholder: parsed_placeholder,
+        zero_padded_width,
+    })
+}
+
+/// Render a single template component, returning `None` if any of its placeholders has no value.
+fn render_template_component<F>(
+    component: &TemplateComponent,
+    value_of: &F,
+) -> Option<String>
+where
+    F: Fn(OutputTemplatePlaceholder) -> Option<String>,
+{
+    let mut rendered = String::new();
+
+    for segment in component {
+        match segment {
+            TemplateSegment::Literal(literal) => rendered.push_str(literal),
+            TemplateSegment::Placeholder {
+                placeholder,
+                zero_padded_width,
+            } => {
+                let value = value_of(*placeholder)?;
+
+                match (zero_padded_width, value.parse::<u64>()) {
+                    (Some(width), Ok(number)) => {
+                        rendered.push_str(&format!("{number:0width$}"))
+                    }
+                    _ => rendered.push_str(&value),
+                }
+            }
+        }
+    }
+
+    let rendered = rendered.trim();
+    if rendered.is_empty() {
+        return None;
+    }
+
+    Some(rendered.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value_of(placeholder: OutputTemplatePlaceholder) -> Option<String> {
+        match placeholder {
+            OutputTemplatePlaceholder::AlbumArtist => Some("Artist".to_string()),
+            OutputTemplatePlaceholder::Album => Some("Album".to_string()),
+            OutputTemplatePlaceholder::Year => Some("2001".to_string()),
+            OutputTemplatePlaceholder::Track => Some("3".to_string()),
+            OutputTemplatePlaceholder::Title => Some("Song".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn renders_templates() {
+        let template = OutputPathTemplate::parse(
+            "{album_artist}/{year} - {album}/{track:02} {title}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            template.render_album_directory(value_of).as_deref(),
+            Some("Artist/2001 - Album")
+        );
+        assert_eq!(
+            template.render_file_name(value_of).as_deref(),
+            Some("03 Song")
+        );
+
+        let template = OutputPathTemplate::parse("{artist}/{title}").unwrap();
+        assert_eq!(template.render_album_directory(value_of), None);
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(OutputPathTemplate::parse("{title}").is_err());
+        assert!(OutputPathTemplate::parse("{album}/track").is_err());
+        assert!(OutputPathTemplate::parse("{album}/{name}").is_err());
+        assert!(OutputPathTemplate::parse("{album}/{track:2}").is_err());
+        assert!(OutputPathTemplate::parse("{album}/{title").is_err());
+        assert!(OutputPathTemplate::parse("../{album}/{title}").is_err());
+        assert!(OutputPathTemplate::parse("{album}//{title}").is_err());
+    }
+}
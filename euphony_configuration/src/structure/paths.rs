@@ -1,12 +1,12 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     traits::ResolvableConfiguration,
-    utilities::get_running_executable_directory,
+    utilities::{get_running_executable_directory, replace_placeholders_in_str},
 };
 
 /// Base paths - reusable values such as the base library path and base tools path.
-#[derive(Clone)]
+#[derive(Serialize, Clone)]
 pub struct PathsConfiguration {
     pub base_library_path: String,
     pub base_tools_path: String,
@@ -28,12 +28,16 @@ impl ResolvableConfiguration for UnresolvedPathsConfiguration {
             .to_string_lossy()
             .to_string();
 
-        let base_library_path = self
-            .base_library_path
-            .replace("{SELF}", &executable_directory);
-        let base_tools_path = self
-            .base_tools_path
-            .replace("{SELF}", &executable_directory);
+        let base_library_path = replace_placeholders_in_str(
+            &self.base_library_path,
+            "paths.base_library_path",
+            &[("{SELF}", &executable_directory)],
+        );
+        let base_tools_path = replace_placeholders_in_str(
+            &self.base_tools_path,
+            "paths.base_tools_path",
+            &[("{SELF}", &executable_directory)],
+        );
 
         let base_library_path = dunce::canonicalize(base_library_path)
             .unwrap_or_else(|_| panic!(
@@ -1,17 +1,21 @@
+use std::collections::HashMap;
 use std::path::Path;
+use std::process::Command;
 
-use miette::Result;
-use serde::Deserialize;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    error::ToolsConfigurationError,
     filesystem::get_path_extension_or_empty,
     paths::PathsConfiguration,
     traits::ResolvableWithPathsConfiguration,
+    utilities::replace_placeholders_in_str,
 };
 
 
 
-#[derive(Clone)]
+#[derive(Serialize, Clone)]
 pub struct ToolsConfiguration {
     pub ffmpeg: FfmpegToolsConfiguration,
 }
@@ -36,7 +40,7 @@ impl ResolvableWithPathsConfiguration for UnresolvedToolsConfiguration {
 
 
 
-#[derive(Clone)]
+#[derive(Serialize, Clone)]
 pub struct FfmpegToolsConfiguration {
     /// Configures the ffmpeg binary location.
     /// The {TOOLS_BASE} placeholder is available (see `base_tools_path` in the `essentials` table)
@@ -46,9 +50,21 @@ pub struct FfmpegToolsConfiguration {
     /// The placeholders {INPUT_FILE} and {OUTPUT_FILE} will be replaced with the absolute path to those files.
     pub audio_transcoding_args: Vec<String>,
 
+    /// Optional per-extension overrides of `audio_transcoding_args`, keyed by the
+    /// (lowercase) source file extension. Some source formats (e.g. DSD) need different
+    /// ffmpeg handling; files with other extensions use `audio_transcoding_args`.
+    /// The same placeholders are available.
+    pub audio_transcoding_args_per_extension: HashMap<String, Vec<String>>,
+
     /// This setting should be the extension of the audio files after transcoding.
     /// The default conversion is to MP3, but the user may set any ffmpeg conversion above, which is why this exists.
     pub audio_transcoding_output_extension: String,
+
+    /// Cover art file names (lowercase, in order of preference) to look for next to each
+    /// transcoded file and in its album directory. If one is found, it is embedded into
+    /// the transcoded file (replacing any art embedded in the source file).
+    /// Empty (the default) disables cover art embedding.
+    pub embedded_cover_art_file_names: Vec<String>,
 }
 
 impl FfmpegToolsConfiguration {
@@ -64,6 +80,103 @@ impl FfmpegToolsConfiguration {
 
         Ok(self.audio_transcoding_output_extension.eq(&extension))
     }
+
+    /// Returns the ffmpeg argument template to use when transcoding the given source file:
+    /// the entry in `audio_transcoding_args_per_extension` that matches the file's extension
+    /// or, if there is none, the default `audio_transcoding_args`.
+    ///
+    /// Returns `Err` if the extension is not valid UTF-8.
+    pub fn audio_transcoding_args_for_path<P: AsRef<Path>>(
+        &self,
+        source_file_path: P,
+    ) -> Result<&[String]> {
+        let extension = get_path_extension_or_empty(source_file_path)?;
+
+        Ok(self
+            .audio_transcoding_args_per_extension
+            .get(&extension)
+            .unwrap_or(&self.audio_transcoding_args))
+    }
+
+    /// Run `<binary> -version` to make sure the configured ffmpeg binary can actually
+    /// be executed (e.g. it isn't built for the wrong architecture or missing shared libraries).
+    ///
+    /// Returns the version string (e.g. `6.1.1`) parsed from the first line of the output
+    /// (or the entire first line if it has an unexpected format).
+    /// Returns a descriptive `Err` if ffmpeg can't be run or doesn't exit successfully.
+    pub fn probe_version(&self) -> Result<String> {
+        let output = Command::new(&self.binary)
+            .arg("-version")
+            .output()
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not execute the ffmpeg binary at \"{}\".",
+                    self.binary
+                )
+            })?;
+
+        if !output.status.success() {
+            return Err(miette!(
+                "The ffmpeg binary at \"{}\" exited with {} when asked for its version.\n\
+                Stderr: {}",
+                self.binary,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim(),
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let first_line = stdout
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .ok_or_else(|| {
+                miette!(
+                    "The ffmpeg binary at \"{}\" printed nothing when asked for its version.",
+                    self.binary
+                )
+            })?;
+
+        Ok(parse_ffmpeg_version_line(first_line)
+            .unwrap_or(first_line)
+            .to_string())
+    }
+}
+
+/// Parse the version out of the first line of `ffmpeg -version`,
+/// e.g. `ffmpeg version 6.1.1 Copyright (c) 2000-2023 the FFmpeg developers` gives `6.1.1`.
+fn parse_ffmpeg_version_line(line: &str) -> Option<&str> {
+    line.strip_prefix("ffmpeg version ")?
+        .split_whitespace()
+        .next()
+}
+
+/// Placeholders that every ffmpeg argument list must contain
+/// (they are replaced with the absolute input and output file paths).
+const REQUIRED_FFMPEG_PLACEHOLDERS: [&str; 2] =
+    ["{INPUT_FILE}", "{OUTPUT_FILE}"];
+
+/// Ensure the ffmpeg argument list contains all of `REQUIRED_FFMPEG_PLACEHOLDERS`.
+/// `setting` is the name of the option the arguments come from (used in the error).
+pub(crate) fn ensure_ffmpeg_args_have_placeholders(
+    audio_transcoding_args: &[String],
+    setting: &str,
+) -> Result<(), ToolsConfigurationError> {
+    for placeholder in REQUIRED_FFMPEG_PLACEHOLDERS {
+        let has_placeholder = audio_transcoding_args
+            .iter()
+            .any(|argument| argument.contains(placeholder));
+
+        if !has_placeholder {
+            return Err(ToolsConfigurationError::MissingPlaceholder {
+                placeholder,
+                setting: setting.to_string(),
+            });
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Deserialize, Clone)]
@@ -72,7 +185,11 @@ pub(crate) struct UnresolvedFfmpegToolsConfiguration {
 
     audio_transcoding_args: Vec<String>,
 
+    audio_transcoding_args_per_extension: Option<HashMap<String, Vec<String>>>,
+
     audio_transcoding_output_extension: String,
+
+    embedded_cover_art_file_names: Option<Vec<String>>,
 }
 
 impl ResolvableWithPathsConfiguration for UnresolvedFfmpegToolsConfiguration {
@@ -82,26 +199,166 @@ impl ResolvableWithPathsConfiguration for UnresolvedFfmpegToolsConfiguration {
         self,
         paths: &PathsConfiguration,
     ) -> miette::Result<Self::Resolved> {
-        let ffmpeg = self.binary.replace("{TOOLS_BASE}", &paths.base_tools_path);
+        let ffmpeg = replace_placeholders_in_str(
+            &self.binary,
+            "tools.ffmpeg.binary",
+            &[("{TOOLS_BASE}", &paths.base_tools_path)],
+        );
 
-        let canonicalized_ffmpeg = dunce::canonicalize(ffmpeg.clone())
-            .unwrap_or_else(|_| panic!(
-                "Could not canonicalize ffmpeg binary path: \"{ffmpeg}\", make sure the path is valid.",
-            ));
+        let canonicalized_ffmpeg = dunce::canonicalize(&ffmpeg)
+            .ok()
+            .filter(|canonicalized_ffmpeg| canonicalized_ffmpeg.is_file())
+            .ok_or(ToolsConfigurationError::BinaryNotFound { binary: ffmpeg })?;
 
         let binary = canonicalized_ffmpeg.to_string_lossy().to_string();
 
-        if !canonicalized_ffmpeg.is_file() {
-            panic!("No file exists at this path: {}", self.binary);
-        }
+        ensure_ffmpeg_args_have_placeholders(
+            &self.audio_transcoding_args,
+            "tools.ffmpeg.audio_transcoding_args",
+        )?;
 
         let audio_transcoding_output_extension =
             self.audio_transcoding_output_extension.to_ascii_lowercase();
 
+        let audio_transcoding_args_per_extension: HashMap<_, _> = self
+            .audio_transcoding_args_per_extension
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(extension, args)| (extension.to_ascii_lowercase(), args))
+            .collect();
+
+        for (extension, args) in &audio_transcoding_args_per_extension {
+            ensure_ffmpeg_args_have_placeholders(
+                args,
+                &format!(
+                    "tools.ffmpeg.audio_transcoding_args_per_extension.{extension}"
+                ),
+            )?;
+        }
+
+        let embedded_cover_art_file_names = self
+            .embedded_cover_art_file_names
+            .unwrap_or_default()
+            .into_iter()
+            .map(|file_name| file_name.to_lowercase())
+            .collect();
+
         Ok(FfmpegToolsConfiguration {
             binary,
             audio_transcoding_args: self.audio_transcoding_args,
+            audio_transcoding_args_per_extension,
             audio_transcoding_output_extension,
+            embedded_cover_art_file_names,
         })
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ffmpeg_configuration_with_dsd_override() -> FfmpegToolsConfiguration {
+        FfmpegToolsConfiguration {
+            binary: String::from("ffmpeg"),
+            audio_transcoding_args: vec![
+                String::from("-i"),
+                String::from("{INPUT_FILE}"),
+                String::from("{OUTPUT_FILE}"),
+            ],
+            audio_transcoding_args_per_extension: HashMap::from([(
+                String::from("dff"),
+                vec![
+                    String::from("-i"),
+                    String::from("{INPUT_FILE}"),
+                    String::from("-af"),
+                    String::from("lowpass=24000"),
+                    String::from("{OUTPUT_FILE}"),
+                ],
+            )]),
+            audio_transcoding_output_extension: String::from("mp3"),
+            embedded_cover_art_file_names: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn uses_extension_specific_args_when_configured() {
+        let ffmpeg = ffmpeg_configuration_with_dsd_override();
+
+        assert_eq!(
+            ffmpeg
+                .audio_transcoding_args_for_path("Artist/Album/01 Track.dff")
+                .unwrap(),
+            ffmpeg.audio_transcoding_args_per_extension["dff"].as_slice()
+        );
+    }
+
+    #[test]
+    fn extension_matching_is_case_insensitive() {
+        let ffmpeg = ffmpeg_configuration_with_dsd_override();
+
+        assert_eq!(
+            ffmpeg
+                .audio_transcoding_args_for_path("Artist/Album/01 Track.DFF")
+                .unwrap(),
+            ffmpeg.audio_transcoding_args_per_extension["dff"].as_slice()
+        );
+    }
+
+    #[test]
+    fn args_without_placeholders_are_rejected() {
+        let args_without_output = vec![
+            String::from("-i"),
+            String::from("{INPUT_FILE}"),
+            String::from("out.mp3"),
+        ];
+
+        assert!(matches!(
+            ensure_ffmpeg_args_have_placeholders(&args_without_output, "args"),
+            Err(ToolsConfigurationError::MissingPlaceholder {
+                placeholder: "{OUTPUT_FILE}",
+                ..
+            })
+        ));
+
+        let ffmpeg = ffmpeg_configuration_with_dsd_override();
+        assert!(ensure_ffmpeg_args_have_placeholders(
+            &ffmpeg.audio_transcoding_args,
+            "args"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn parses_ffmpeg_version_line() {
+        assert_eq!(
+            parse_ffmpeg_version_line(
+                "ffmpeg version 6.1.1 Copyright (c) 2000-2023 the FFmpeg developers"
+            ),
+            Some("6.1.1")
+        );
+        assert_eq!(
+            parse_ffmpeg_version_line("ffmpeg version n7.0-12-gabcdef"),
+            Some("n7.0-12-gabcdef")
+        );
+        assert_eq!(parse_ffmpeg_version_line("something else"), None);
+    }
+
+    #[test]
+    fn falls_back_to_default_args() {
+        let ffmpeg = ffmpeg_configuration_with_dsd_override();
+
+        assert_eq!(
+            ffmpeg
+                .audio_transcoding_args_for_path("Artist/Album/01 Track.flac")
+                .unwrap(),
+            ffmpeg.audio_transcoding_args.as_slice()
+        );
+        assert_eq!(
+            ffmpeg
+                .audio_transcoding_args_for_path("Artist/Album/no-extension")
+                .unwrap(),
+            ffmpeg.audio_transcoding_args.as_slice()
+        );
+    }
+}
@@ -1,14 +1,21 @@
-use serde::Deserialize;
+use miette::miette;
+use serde::{Deserialize, Serialize};
 
 use crate::traits::ResolvableConfiguration;
 
-#[derive(Clone)]
+#[derive(Serialize, Clone)]
 pub struct UiConfiguration {
+    /// Whether to show a desktop notification when a transcode finishes or fails
+    /// (requires the `desktop-notifications` feature).
+    pub notify_on_completion: bool,
+
     pub transcoding: TranscodingUiConfiguration,
 }
 
 #[derive(Deserialize, Clone)]
 pub(crate) struct UnresolvedUiConfiguration {
+    notify_on_completion: Option<bool>,
+
     transcoding: UnresolvedTranscodingUiConfiguration,
 }
 
@@ -17,6 +24,7 @@ impl ResolvableConfiguration for UnresolvedUiConfiguration {
 
     fn resolve(self) -> miette::Result<Self::Resolved> {
         Ok(UiConfiguration {
+            notify_on_completion: self.notify_on_completion.unwrap_or(false),
             transcoding: self.transcoding.resolve()?,
         })
     }
@@ -24,23 +32,77 @@ impl ResolvableConfiguration for UnresolvedUiConfiguration {
 
 
 
-#[derive(Clone)]
+/// Default for `ui.transcoding.file_queue_width_percent`.
+const DEFAULT_FILE_QUEUE_WIDTH_PERCENT: u16 = 60;
+
+/// Default for `ui.transcoding.header_view_width_percent`.
+const DEFAULT_HEADER_VIEW_WIDTH_PERCENT: u16 = 67;
+
+/// Minimum (and 100 minus the maximum) value of the layout percentages,
+/// so that neither side of a split can disappear entirely.
+const MIN_LAYOUT_PERCENT: u16 = 10;
+
+
+#[derive(Serialize, Clone)]
 pub struct TranscodingUiConfiguration {
     pub show_logs_tab_on_exit: bool,
+
+    /// Width of the file queue in the transcoding tab as a percentage of the terminal width
+    /// (the album queue takes up the rest).
+    pub file_queue_width_percent: u16,
+
+    /// Width of the view selector in the header as a percentage of the terminal width
+    /// (the help section takes up the rest).
+    pub header_view_width_percent: u16,
 }
 
 
 #[derive(Deserialize, Clone)]
 pub(crate) struct UnresolvedTranscodingUiConfiguration {
     show_logs_tab_on_exit: bool,
+
+    file_queue_width_percent: Option<u16>,
+
+    header_view_width_percent: Option<u16>,
+}
+
+/// Ensure the given layout percentage leaves at least `MIN_LAYOUT_PERCENT`
+/// for both sides of the split.
+fn validate_layout_percent(name: &str, value: u16) -> miette::Result<u16> {
+    let max_layout_percent = 100 - MIN_LAYOUT_PERCENT;
+
+    if !(MIN_LAYOUT_PERCENT..=max_layout_percent).contains(&value) {
+        return Err(miette!(
+            "ui.transcoding.{} is set to {}, but it must be between {} and {}.",
+            name,
+            value,
+            MIN_LAYOUT_PERCENT,
+            max_layout_percent
+        ));
+    }
+
+    Ok(value)
 }
 
 impl ResolvableConfiguration for UnresolvedTranscodingUiConfiguration {
     type Resolved = TranscodingUiConfiguration;
 
     fn resolve(self) -> miette::Result<Self::Resolved> {
+        let file_queue_width_percent = validate_layout_percent(
+            "file_queue_width_percent",
+            self.file_queue_width_percent
+                .unwrap_or(DEFAULT_FILE_QUEUE_WIDTH_PERCENT),
+        )?;
+        let header_view_width_percent = validate_layout_percent(
+            "header_view_width_percent",
+            self.header_view_width_percent
+                .unwrap_or(DEFAULT_HEADER_VIEW_WIDTH_PERCENT),
+        )?;
+
         Ok(TranscodingUiConfiguration {
             show_logs_tab_on_exit: self.show_logs_tab_on_exit,
+            file_queue_width_percent,
+            header_view_width_percent,
         })
     }
 }
@@ -1,21 +1,73 @@
-use serde::Deserialize;
+use std::path::PathBuf;
 
-use crate::traits::ResolvableConfiguration;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+use crate::paths::PathsConfiguration;
+use crate::traits::ResolvableWithPathsConfiguration;
+use crate::utilities::replace_placeholders_in_str;
+
+/// Default for `validation.maximum_album_file_depth`.
+const DEFAULT_MAXIMUM_ALBUM_FILE_DEPTH: u16 = 2;
+
+/// Default for `validation.maximum_album_audio_files`.
+const DEFAULT_MAXIMUM_ALBUM_AUDIO_FILES: usize = 200;
+
+/// Default for `validation.check_case_insensitive_collisions`.
+const DEFAULT_CHECK_CASE_INSENSITIVE_COLLISIONS: bool = true;
+
+/// Default for `validation.check_portable_file_names`.
+const DEFAULT_CHECK_PORTABLE_FILE_NAMES: bool = false;
+
+#[derive(Serialize, Clone)]
 pub struct ValidationConfiguration {
     pub extensions_considered_audio_files: Vec<String>,
+
+    /// The maximum depth of any file inside an album directory
+    /// (zero means the file is directly in the album directory).
+    /// Deeper files cause a validation error, as this usually means
+    /// the wrong directory is being treated as an album.
+    pub maximum_album_file_depth: u16,
+
+    /// The maximum number of audio files in a single album directory.
+    /// Albums with more audio files cause a validation error.
+    pub maximum_album_audio_files: usize,
+
+    /// Whether to report albums with output files (in the same directory of the aggregated
+    /// library) whose names only differ in case, e.g. `Song.mp3` and `song.mp3`.
+    /// These overwrite each other on case-insensitive filesystems (e.g. FAT32, exFAT or NTFS).
+    pub check_case_insensitive_collisions: bool,
+
+    /// Whether to report artist, album and file names that can't be used on FAT32, exFAT
+    /// or NTFS filesystems (e.g. names containing `?` or `:`, or ending with a dot).
+    pub check_portable_file_names: bool,
+
+    /// Where `validate --fix` moves the unexpected files to (into `<library name>/<path
+    /// relative to the library>`). If unset, `validate --fix` deletes them instead.
+    pub quarantine_directory: Option<PathBuf>,
 }
 
 #[derive(Deserialize, Clone)]
 pub(crate) struct UnresolvedValidationConfiguration {
     extensions_considered_audio_files: Vec<String>,
+
+    maximum_album_file_depth: Option<u16>,
+
+    maximum_album_audio_files: Option<usize>,
+
+    check_case_insensitive_collisions: Option<bool>,
+
+    check_portable_file_names: Option<bool>,
+
+    quarantine_directory: Option<String>,
 }
 
-impl ResolvableConfiguration for UnresolvedValidationConfiguration {
+impl ResolvableWithPathsConfiguration for UnresolvedValidationConfiguration {
     type Resolved = ValidationConfiguration;
 
-    fn resolve(self) -> miette::Result<Self::Resolved> {
+    fn resolve(
+        self,
+        paths: &PathsConfiguration,
+    ) -> miette::Result<Self::Resolved> {
         let extensions_considered_audio_files = self
             .extensions_considered_audio_files
             .into_iter()
@@ -25,8 +77,30 @@ impl ResolvableConfiguration for UnresolvedValidationConfiguration {
             })
             .collect();
 
+        let quarantine_directory =
+            self.quarantine_directory.map(|quarantine_directory| {
+                PathBuf::from(replace_placeholders_in_str(
+                    &quarantine_directory,
+                    "validation.quarantine_directory",
+                    &[("{LIBRARY_BASE}", &paths.base_library_path)],
+                ))
+            });
+
         Ok(ValidationConfiguration {
             extensions_considered_audio_files,
+            maximum_album_file_depth: self
+                .maximum_album_file_depth
+                .unwrap_or(DEFAULT_MAXIMUM_ALBUM_FILE_DEPTH),
+            maximum_album_audio_files: self
+                .maximum_album_audio_files
+                .unwrap_or(DEFAULT_MAXIMUM_ALBUM_AUDIO_FILES),
+            check_case_insensitive_collisions: self
+                .check_case_insensitive_collisions
+                .unwrap_or(DEFAULT_CHECK_CASE_INSENSITIVE_COLLISIONS),
+            check_portable_file_names: self
+                .check_portable_file_names
+                .unwrap_or(DEFAULT_CHECK_PORTABLE_FILE_NAMES),
+            quarantine_directory,
         })
     }
 }
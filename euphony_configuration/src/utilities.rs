@@ -1,7 +1,17 @@
 use std::env::args;
 use std::path::{Path, PathBuf};
 
+use directories::BaseDirs;
 use miette::{miette, Context, IntoDiagnostic, Result};
+use tracing::warn;
+
+/// Built-in placeholders that are available in all paths in the configuration
+/// (see `replace_placeholders_in_str`):
+/// - `{HOME}` is the user's home directory (e.g. `/home/alice`),
+/// - `{CONFIG_DIR}` is the user's configuration directory (e.g. `/home/alice/.config`),
+/// - `{DATA_DIR}` is the user's data directory (e.g. `/home/alice/.local/share`).
+const USER_DIRECTORY_PLACEHOLDERS: [&str; 3] =
+    ["{HOME}", "{CONFIG_DIR}", "{DATA_DIR}"];
 
 /// Inspect the first command line argument to find out the directory the program resides in.
 ///
@@ -98,3 +108,85 @@ pub fn get_default_configuration_file_path() -> Result<String> {
 
     Ok(configuration_filepath.to_string_lossy().to_string())
 }
+
+/// Replace placeholders in a configuration value: first the setting-specific `placeholders`
+/// (pairs of placeholder and value, e.g. `("{LIBRARY_BASE}", ...)`), then the built-in
+/// user directory placeholders (see `USER_DIRECTORY_PLACEHOLDERS`).
+///
+/// Any remaining `{...}` placeholder is unknown: it is left as-is, but a warning
+/// mentioning `setting_name` is emitted (as a `tracing` event).
+pub(crate) fn replace_placeholders_in_str(
+    value: &str,
+    setting_name: &str,
+    placeholders: &[(&str, &str)],
+) -> String {
+    let mut replaced_value = value.to_string();
+
+    for (placeholder, placeholder_value) in placeholders {
+        replaced_value = replaced_value.replace(placeholder, placeholder_value);
+    }
+
+    if USER_DIRECTORY_PLACEHOLDERS
+        .iter()
+        .any(|placeholder| replaced_value.contains(placeholder))
+    {
+        match BaseDirs::new() {
+            Some(base_directories) => {
+                for (placeholder, directory) in [
+                    ("{HOME}", base_directories.home_dir()),
+                    ("{CONFIG_DIR}", base_directories.config_dir()),
+                    ("{DATA_DIR}", base_directories.data_dir()),
+                ] {
+                    replaced_value = replaced_value
+                        .replace(placeholder, &directory.to_string_lossy());
+                }
+            }
+            None => warn!(
+                "{setting_name} uses a user directory placeholder, \
+                but the user's directories could not be determined."
+            ),
+        }
+    }
+
+    for unknown_placeholder in find_placeholders(&replaced_value)
+        .into_iter()
+        .filter(|placeholder| !USER_DIRECTORY_PLACEHOLDERS.contains(placeholder))
+    {
+        warn!(
+            "{setting_name} contains an unknown placeholder \
+            {unknown_placeholder} (left as-is)."
+        );
+    }
+
+    replaced_value
+}
+
+/// Find all `{...}` placeholders (uppercase ASCII letters and underscores) in the string.
+fn find_placeholders(value: &str) -> Vec<&str> {
+    let mut placeholders = Vec::new();
+    let mut remaining_value = value;
+
+    while let Some(start_index) = remaining_value.find('{') {
+        let after_start = &remaining_value[start_index + 1..];
+
+        let Some(end_index) = after_start.find('}') else {
+            break;
+        };
+
+        let name = &after_start[..end_index];
+        if !name.is_empty()
+            && name.chars().all(|character| {
+                character.is_ascii_uppercase() || character == '_'
+            })
+        {
+            placeholders.push(
+                &remaining_value[start_index..start_index + end_index + 2],
+            );
+            remaining_value = &after_start[end_index + 1..];
+        } else {
+            remaining_value = after_start;
+        }
+    }
+
+    placeholders
+}
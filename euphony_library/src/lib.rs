@@ -1,3 +1,4 @@
 pub mod state;
+pub mod tags;
 pub mod utilities;
 pub mod view;
@@ -5,6 +5,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use euphony_configuration::file_metadata::ChangeDetectionMode;
 use miette::{miette, Result};
 use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
 
@@ -69,6 +70,11 @@ pub struct AlbumFileChangesV2<'view> {
     ///
     /// Paths are absolute and point to the *transcoded album directory*.
     pub excess_in_transcoded: ExtendedSortedFileList<PathBuf>,
+
+    /// The fresh source album state these changes were generated from (if any).
+    /// Its content hashes are reused when generating the final source album state
+    /// (see `generate_source_album_state`).
+    scanned_source_state: Option<SourceAlbumState>,
 }
 
 impl<'view> AlbumFileChangesV2<'view> {
@@ -81,6 +87,7 @@ impl<'view> AlbumFileChangesV2<'view> {
             removed_from_source_since_last_transcode: SortedFileList::default(),
             missing_in_transcoded: SortedFileList::default(),
             excess_in_transcoded: ExtendedSortedFileList::default(),
+            scanned_source_state: None,
         }
     }
 
@@ -103,14 +110,18 @@ impl<'view> AlbumFileChangesV2<'view> {
         let (
             configuration,
             library_configuration,
+            album_configuration,
             source_album_directory,
             transcoded_album_directory,
         ) = {
             let album_locked = album.read();
 
+            // The configurations outlive the album view, so only the (small)
+            // per-album configuration is cloned out of the lock.
             (
-                album_locked.euphony_configuration().clone(),
-                album_locked.library_configuration().clone(),
+                album_locked.euphony_configuration(),
+                album_locked.library_configuration(),
+                album_locked.configuration.clone(),
                 album_locked.album_directory_in_source_library(),
                 album_locked.album_directory_in_transcoded_library(),
             )
@@ -127,6 +138,29 @@ impl<'view> AlbumFileChangesV2<'view> {
         // **The groups are disjoint.**
 
 
+        // If the change detection mode was switched since the last transcode, the saved
+        // metadata can't be meaningfully compared, so every tracked file counts as changed.
+        let change_detection_mode = fresh_source_state.change_detection_mode;
+        let change_detection_mode_switched =
+            saved_source_state.as_ref().is_some_and(|state| {
+                state.change_detection_mode != change_detection_mode
+            });
+
+        // Similarly, if the transcoding settings (ffmpeg arguments or output extension)
+        // changed since the last transcode, all existing transcodes of audio files are stale.
+        // States saved before the settings hash existed don't have it and are never considered stale.
+        let transcoding_settings_changed = match (
+            saved_source_state
+                .as_ref()
+                .and_then(|state| state.transcoding_settings_hash.as_ref()),
+            fresh_source_state.transcoding_settings_hash.as_ref(),
+        ) {
+            (Some(saved_hash), Some(fresh_hash)) => saved_hash != fresh_hash,
+            _ => false,
+        };
+        let all_audio_files_changed =
+            change_detection_mode_switched || transcoding_settings_changed;
+
         let saved_source_album_file_state = &saved_source_state
             .map(|state| state.tracked_files)
             .unwrap_or_default();
@@ -166,6 +200,20 @@ impl<'view> AlbumFileChangesV2<'view> {
             .map(|state| state.transcoded_files.clone())
             .unwrap_or_default();
 
+        // Maps source file paths back to the transcoded paths they were saved with.
+        // These can differ from freshly computed ones, e.g. for files that were renamed
+        // to avoid a case-insensitive collision (see `aggregated_library.case_collision_strategy`).
+        let saved_source_to_transcoded_file_paths: HashMap<String, String> =
+            saved_transcoded_state
+                .as_ref()
+                .map(|state| {
+                    state
+                        .transcoded_to_original_file_paths
+                        .to_inverted_map()
+                        .into_flattened_map()
+                })
+                .unwrap_or_default();
+
 
         // Relative paths for previously-transcoded audio and data files.
         // Note that audio extensions match the transcode output extension (e.g. MP3),
@@ -235,6 +283,8 @@ impl<'view> AlbumFileChangesV2<'view> {
                     .intersection(&saved_source_file_list_audio),
                 &saved_source_album_file_state.audio_files,
                 &fresh_source_album_file_state.audio_files,
+                change_detection_mode,
+                all_audio_files_changed,
             );
 
             let data_files_changed = Self::filter_to_changed_files(
@@ -242,6 +292,8 @@ impl<'view> AlbumFileChangesV2<'view> {
                     .intersection(&saved_source_file_list_data),
                 &saved_source_album_file_state.data_files,
                 &fresh_source_album_file_state.data_files,
+                change_detection_mode,
+                change_detection_mode_switched,
             );
 
             SortedFileList::new(
@@ -260,61 +312,138 @@ impl<'view> AlbumFileChangesV2<'view> {
         /*
          * Group 3: files that have been removed from the source album directory and whose
          *          transcoded/copied versions are still present in the transcoded album directory.
+         *
+         * Only files that euphony itself produced (i.e. the ones tracked in the saved transcoded
+         * album state) are considered, so we never delete files the user placed there manually.
+         * The source paths are mapped to their transcoded counterparts first
+         * (audio files have the transcoding output extension instead of the source one).
          */
         let removed_from_source_since_last_transcode = {
-            let audio_files_removed = saved_source_file_list_audio
-                .sub(&fresh_source_file_list_audio)
-                .into_iter()
-                .filter_map(|audio_file| {
-                    // We don't need to bother with the file if it doesn't exist
-                    // in the transcoded directory.
-                    let transcoded_file_path =
-                        match SourceAlbumState::get_transcoded_file_path(
-                            &configuration,
-                            &library_configuration,
-                            &audio_file,
-                        ) {
-                            Ok(transcoded_path) => transcoded_path,
-                            Err(error) => {
-                                return Some(Err(error));
-                            }
-                        };
+            let saved_transcoded_file_list_audio_pathbuf =
+                saved_transcoded_file_list_audio
+                    .iter()
+                    .map(PathBuf::from)
+                    .collect::<HashSet<PathBuf>>();
+            let saved_transcoded_file_list_data_pathbuf =
+                saved_transcoded_file_list_data
+                    .iter()
+                    .map(PathBuf::from)
+                    .collect::<HashSet<PathBuf>>();
 
-                    match transcoded_file_path.exists()
-                        && transcoded_file_path.is_file()
-                    {
-                        true => None,
-                        false => Some(Ok(audio_file)),
+            let find_orphaned_transcoded_files =
+                |removed_source_files: HashSet<String>,
+                 saved_transcoded_files: &HashSet<PathBuf>|
+                 -> Result<Vec<PathBuf>> {
+                    let mut orphaned_files: Vec<PathBuf> = Vec::new();
+
+                    for removed_source_file in removed_source_files {
+                        let transcoded_file_path =
+                            match saved_source_to_transcoded_file_paths
+                                .get(&removed_source_file)
+                            {
+                                Some(saved_transcoded_file_path) => {
+                                    PathBuf::from(saved_transcoded_file_path)
+                                }
+                                None => {
+                                    SourceAlbumState::get_transcoded_file_path(
+                                        configuration,
+                                        library_configuration,
+                                        &album_configuration,
+                                        &removed_source_file,
+                                    )?
+                                }
+                            };
+
+                        // We don't need to bother with the file if it doesn't exist
+                        // in the transcoded directory and must not touch it
+                        // if it wasn't produced by us.
+                        if saved_transcoded_files.contains(&transcoded_file_path)
+                            && transcoded_album_directory
+                                .join(&transcoded_file_path)
+                                .is_file()
+                        {
+                            orphaned_files.push(transcoded_file_path);
+                        }
                     }
-                })
-                .collect::<Result<Vec<String>>>()?;
 
-            let data_files_removed = saved_source_file_list_data
-                .sub(&fresh_source_file_list_data)
-                .into_iter()
-                .filter_map(|data_file| {
-                    // We don't need to bother with the file if it doesn't exist
-                    // in the transcoded directory.
-                    let transcoded_file_path =
-                        match SourceAlbumState::get_transcoded_file_path(
-                            &configuration,
-                            &library_configuration,
-                            &data_file,
-                        ) {
-                            Ok(transcoded_path) => transcoded_path,
-                            Err(error) => {
-                                return Some(Err(error));
-                            }
+                    Ok(orphaned_files)
+                };
+
+            // Files whose output path has changed since the last transcode
+            // (e.g. after editing the tags or the template their output path is built from,
+            // see `AlbumSourceFileList::templated_audio_file_names`, or after changing
+            // the disc subdirectory layout) leave their previous output behind.
+            let find_moved_transcoded_files =
+                |kept_source_files: HashSet<&String>,
+                 fresh_source_to_transcoded_file_paths: &HashMap<
+                    PathBuf,
+                    PathBuf,
+                >,
+                 saved_transcoded_files: &HashSet<PathBuf>|
+                 -> Vec<PathBuf> {
+                    let mut moved_files: Vec<PathBuf> = Vec::new();
+
+                    for source_file in kept_source_files {
+                        let Some(saved_transcoded_file_path) =
+                            saved_source_to_transcoded_file_paths
+                                .get(source_file)
+                        else {
+                            continue;
                         };
-
-                    match transcoded_file_path.exists()
-                        && transcoded_file_path.is_file()
-                    {
-                        true => None,
-                        false => Some(Ok(data_file)),
+                        let saved_transcoded_file_path =
+                            PathBuf::from(saved_transcoded_file_path);
+
+                        // Compared case-insensitively, as the paths may refer to the same file
+                        // on a case-insensitive filesystem.
+                        let is_output_path_changed =
+                            fresh_source_to_transcoded_file_paths
+                                .get(&PathBuf::from(source_file))
+                                .is_some_and(|fresh_transcoded_file_path| {
+                                    fresh_transcoded_file_path
+                                        .to_string_lossy()
+                                        .to_lowercase()
+                                        != saved_transcoded_file_path
+                                            .to_string_lossy()
+                                            .to_lowercase()
+                                });
+
+                        if is_output_path_changed
+                            && saved_transcoded_files
+                                .contains(&saved_transcoded_file_path)
+                            && transcoded_album_directory
+                                .join(&saved_transcoded_file_path)
+                                .is_file()
+                        {
+                            moved_files.push(saved_transcoded_file_path);
+                        }
                     }
-                })
-                .collect::<Result<Vec<String>>>()?;
+
+                    moved_files
+                };
+
+            let mut audio_files_removed = find_orphaned_transcoded_files(
+                saved_source_file_list_audio.sub(&fresh_source_file_list_audio),
+                &saved_transcoded_file_list_audio_pathbuf,
+            )?;
+            audio_files_removed.extend(find_moved_transcoded_files(
+                fresh_source_file_list_audio
+                    .intersection(&saved_source_file_list_audio)
+                    .collect(),
+                &source_to_transcode_relative_path_map.audio,
+                &saved_transcoded_file_list_audio_pathbuf,
+            ));
+
+            let mut data_files_removed = find_orphaned_transcoded_files(
+                saved_source_file_list_data.sub(&fresh_source_file_list_data),
+                &saved_transcoded_file_list_data_pathbuf,
+            )?;
+            data_files_removed.extend(find_moved_transcoded_files(
+                fresh_source_file_list_data
+                    .intersection(&saved_source_file_list_data)
+                    .collect(),
+                &source_to_transcode_relative_path_map.data,
+                &saved_transcoded_file_list_data_pathbuf,
+            ));
 
             SortedFileList::new(
                 Self::convert_relative_paths_to_absolute(
@@ -346,6 +475,8 @@ impl<'view> AlbumFileChangesV2<'view> {
                     .intersection(&saved_source_file_list_audio),
                 &saved_source_album_file_state.audio_files,
                 &fresh_source_album_file_state.audio_files,
+                change_detection_mode,
+                all_audio_files_changed,
             )
             .into_iter()
             .map(PathBuf::from)
@@ -369,6 +500,8 @@ impl<'view> AlbumFileChangesV2<'view> {
                     .intersection(&saved_source_file_list_data),
                 &saved_source_album_file_state.data_files,
                 &fresh_source_album_file_state.data_files,
+                change_detection_mode,
+                change_detection_mode_switched,
             )
             .into_iter()
             .map(PathBuf::from)
@@ -482,6 +615,7 @@ impl<'view> AlbumFileChangesV2<'view> {
             removed_from_source_since_last_transcode,
             missing_in_transcoded,
             excess_in_transcoded,
+            scanned_source_state: Some(fresh_source_state),
         })
     }
 
@@ -510,10 +644,11 @@ impl<'view> AlbumFileChangesV2<'view> {
             let artist_view = album_view.read_lock_artist();
             let library_view = artist_view.read_lock_library();
 
-            let configuration = library_view.euphony_configuration.clone();
+            let aggregated_library_path =
+                &library_view.euphony_configuration.aggregated_library.path;
 
             let transcoded_library_directory =
-                Path::new(&configuration.aggregated_library.path)
+                Path::new(aggregated_library_path)
                     .join(album_path_relative_to_library);
 
 
@@ -595,9 +730,52 @@ impl<'view> AlbumFileChangesV2<'view> {
             removed_from_source_since_last_transcode,
             missing_in_transcoded: SortedFileList::default(),
             excess_in_transcoded: ExtendedSortedFileList::default(),
+            scanned_source_state: None,
         })
     }
 
+    /// Generate an `AlbumFileChangesV2` instance that processes exactly the given files,
+    /// ignoring any saved album states (all files are treated as new).
+    ///
+    /// `audio_files` and `data_files` should be paths relative to the source album directory.
+    ///
+    /// This is useful when the user explicitly selects the files to transcode
+    /// (see `transcode --stdin-paths`).
+    pub fn generate_for_explicit_files(
+        album: SharedAlbumView<'view>,
+        audio_files: Vec<PathBuf>,
+        data_files: Vec<PathBuf>,
+    ) -> Self {
+        let source_album_directory =
+            album.read().album_directory_in_source_library();
+
+        let added_in_source_since_last_transcode = SortedFileList::new(
+            Self::convert_relative_paths_to_absolute(
+                &source_album_directory,
+                &audio_files,
+            ),
+            Self::convert_relative_paths_to_absolute(
+                &source_album_directory,
+                &data_files,
+            ),
+        );
+
+        Self {
+            album_view: album.clone(),
+            tracked_source_files: Some(AlbumSourceFileList::from_files(
+                album,
+                audio_files,
+                data_files,
+            )),
+            added_in_source_since_last_transcode,
+            changed_in_source_since_last_transcode: SortedFileList::default(),
+            removed_from_source_since_last_transcode: SortedFileList::default(),
+            missing_in_transcoded: SortedFileList::default(),
+            excess_in_transcoded: ExtendedSortedFileList::default(),
+            scanned_source_state: None,
+        }
+    }
+
     /// Returns `true` if any changes were detected since last transcode
     /// (essentially always `true` if no previous transcoding has been done
     /// and the directory has some audio/data files).
@@ -609,6 +787,20 @@ impl<'view> AlbumFileChangesV2<'view> {
             || !self.excess_in_transcoded.is_empty()
     }
 
+    /// Returns a concise, human-readable summary of the changes,
+    /// e.g. `3 added, 1 changed, 0 removed, 0 missing in transcoded, 0 excess in transcoded`.
+    pub fn describe_changes(&self) -> String {
+        format!(
+            "{} added, {} changed, {} removed, \
+            {} missing in transcoded, {} excess in transcoded",
+            self.added_in_source_since_last_transcode.len(),
+            self.changed_in_source_since_last_transcode.len(),
+            self.removed_from_source_since_last_transcode.len(),
+            self.missing_in_transcoded.len(),
+            self.excess_in_transcoded.len(),
+        )
+    }
+
     /// Return the total number of changed files.
     #[inline]
     pub fn number_of_changed_files(&self) -> usize {
@@ -638,11 +830,23 @@ impl<'view> AlbumFileChangesV2<'view> {
     ///
     /// This method does no further disk lookups, all information is already in the memory.
     pub fn generate_source_album_state(&self) -> Result<SourceAlbumState> {
+        let album_locked = self.read_lock_album();
+
         SourceAlbumState::generate_from_tracked_files(
             self.tracked_source_files.as_ref().ok_or_else(|| {
                 miette!("Can't generate source album state, no tracked files.")
             })?,
-            self.read_lock_album().album_directory_in_source_library(),
+            album_locked.album_directory_in_source_library(),
+            album_locked
+                .euphony_configuration()
+                .file_metadata
+                .change_detection,
+            SourceAlbumState::transcoding_settings_hash(
+                album_locked.euphony_configuration(),
+                album_locked.library_configuration(),
+                &album_locked.configuration,
+            ),
+            self.scanned_source_state.as_ref(),
         )
     }
 
@@ -670,9 +874,15 @@ impl<'view> AlbumFileChangesV2<'view> {
         map_key_iterator: I,
         first_metadata_map: &HashMap<String, FileTrackedMetadata>,
         second_metadata_map: &HashMap<String, FileTrackedMetadata>,
+        change_detection_mode: ChangeDetectionMode,
+        treat_all_as_changed: bool,
     ) -> Vec<String> {
         map_key_iterator
             .filter_map(|file_name| {
+                if treat_all_as_changed {
+                    return Some(file_name.to_string());
+                }
+
                 let first_metadata = first_metadata_map
                     .get(file_name.as_str())
                     .expect("BUG: Could not find intersecting key in first metadata map.");
@@ -681,7 +891,7 @@ impl<'view> AlbumFileChangesV2<'view> {
                     .get(file_name.as_str())
                     .expect("BUG: Could not find intersecting key in second metadata map.");
 
-                match first_metadata.matches(second_metadata) {
+                match first_metadata.matches_with_mode(second_metadata, change_detection_mode) {
                     true => {
                         None
                     }
@@ -695,9 +905,15 @@ impl<'view> AlbumFileChangesV2<'view> {
         map_key_iterator: I,
         first_metadata_map: &HashMap<String, FileTrackedMetadata>,
         second_metadata_map: &HashMap<String, FileTrackedMetadata>,
+        change_detection_mode: ChangeDetectionMode,
+        treat_all_as_changed: bool,
     ) -> Vec<String> {
         map_key_iterator
             .filter_map(|file_name| {
+                if treat_all_as_changed {
+                    return None;
+                }
+
                 let first_metadata = first_metadata_map
                     .get(file_name.as_str())
                     .expect("BUG: Could not find intersecting key in first metadata map.");
@@ -706,7 +922,7 @@ impl<'view> AlbumFileChangesV2<'view> {
                     .get(file_name.as_str())
                     .expect("BUG: Could not find intersecting key in second metadata map.");
 
-                match first_metadata.matches(second_metadata) {
+                match first_metadata.matches_with_mode(second_metadata, change_detection_mode) {
                     true => {
                         Some(file_name.to_string())
                     }
@@ -763,3 +979,208 @@ impl<'a> Debug for AlbumFileChangesV2<'a> {
         )
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use euphony_configuration::Configuration;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::view::LibraryView;
+
+    /// Shared with the end-to-end transcoding tests (`tests/transcode.rs`).
+    const CONFIGURATION_TEMPLATE: &str =
+        include_str!("../../tests/fixtures/configuration.toml");
+
+    /// A temporary library containing a single (possibly empty) album, `Artist/Album`.
+    struct TestAlbum {
+        /// Removes everything once the test is done.
+        directory: TempDir,
+
+        configuration: Configuration,
+    }
+
+    impl TestAlbum {
+        fn new() -> Self {
+            let directory = TempDir::new().unwrap();
+            let directory_path = directory.path();
+
+            fs::create_dir_all(directory_path.join("Lossless/Artist/Album"))
+                .unwrap();
+            fs::create_dir_all(directory_path.join("Aggregated")).unwrap();
+
+            // The configuration only checks that the ffmpeg binary exists.
+            fs::write(directory_path.join("ffmpeg"), "").unwrap();
+
+            let configuration = Configuration::load_from_str(
+                &CONFIGURATION_TEMPLATE
+                    .replace("{ROOT}", &directory_path.to_string_lossy()),
+                directory_path.join("configuration.toml"),
+            )
+            .unwrap();
+
+            Self {
+                directory,
+                configuration,
+            }
+        }
+
+        fn source_album_path(&self) -> PathBuf {
+            self.directory.path().join("Lossless/Artist/Album")
+        }
+
+        fn transcoded_album_path(&self) -> PathBuf {
+            self.directory.path().join("Aggregated/Artist/Album")
+        }
+
+        fn write_source_file(&self, file_name: &str, contents: &str) {
+            fs::write(self.source_album_path().join(file_name), contents)
+                .unwrap();
+        }
+
+        fn remove_source_file(&self, file_name: &str) {
+            fs::remove_file(self.source_album_path().join(file_name)).unwrap();
+        }
+
+        /// Compare the album against its saved states (the same way `transcode` does).
+        fn scan_for_changes(&self) -> AlbumFileChangesV2<'_> {
+            let library = LibraryView::from_library_configuration(
+                &self.configuration,
+                &self.configuration.libraries["lossless"],
+            )
+            .unwrap();
+
+            let artist = library
+                .read()
+                .artist("Artist".to_string())
+                .unwrap()
+                .unwrap();
+            let album =
+                artist.read().album("Album".to_string()).unwrap().unwrap();
+
+            // Bound to a variable so the read lock is released before `album` is dropped.
+            let changes = album.read().scan_for_changes().unwrap();
+            changes
+        }
+
+        /// Create the transcoded files and save both album states,
+        /// as if the album had just been transcoded.
+        fn mark_as_transcoded(&self) {
+            let changes = self.scan_for_changes();
+
+            let transcoded_file_paths = changes
+                .tracked_source_files
+                .as_ref()
+                .unwrap()
+                .map_source_file_paths_to_transcoded_file_paths_relative()
+                .into_flattened_map();
+
+            fs::create_dir_all(self.transcoded_album_path()).unwrap();
+            for transcoded_file_path in transcoded_file_paths.values() {
+                fs::write(
+                    self.transcoded_album_path().join(transcoded_file_path),
+                    "transcoded",
+                )
+                .unwrap();
+            }
+
+            let aggregated_library = &self.configuration.aggregated_library;
+            changes
+                .generate_source_album_state()
+                .unwrap()
+                .save_to_directory(
+                    self.source_album_path(),
+                    true,
+                    aggregated_library.mode,
+                    aggregated_library.device_profile.as_deref(),
+                )
+                .unwrap();
+            changes
+                .generate_transcoded_album_state()
+                .unwrap()
+                .save_to_directory(self.transcoded_album_path(), true)
+                .unwrap();
+        }
+    }
+
+    /// A transcoded album with two audio files and a data file.
+    fn transcoded_album() -> TestAlbum {
+        let album = TestAlbum::new();
+        album.write_source_file("01.flac", "first");
+        album.write_source_file("02.flac", "second");
+        album.write_source_file("cover.jpg", "cover");
+
+        assert!(album.scan_for_changes().has_changes());
+        album.mark_as_transcoded();
+
+        album
+    }
+
+    #[test]
+    fn unchanged_album_has_no_changes() {
+        let album = transcoded_album();
+
+        let changes = album.scan_for_changes();
+        assert!(!changes.has_changes(), "{changes:?}");
+        assert_eq!(changes.number_of_changed_files(), 0);
+    }
+
+    #[test]
+    fn detects_added_files() {
+        let album = transcoded_album();
+        album.write_source_file("03.flac", "third");
+
+        let changes = album.scan_for_changes();
+        assert!(changes.has_changes());
+        assert_eq!(
+            changes.added_in_source_since_last_transcode.audio,
+            vec![album.source_album_path().join("03.flac")]
+        );
+        assert_eq!(changes.number_of_changed_files(), 1);
+    }
+
+    #[test]
+    fn detects_removed_files() {
+        let album = transcoded_album();
+        album.remove_source_file("02.flac");
+        album.remove_source_file("cover.jpg");
+
+        // Removed files point to their (now orphaned) transcoded counterparts.
+        let changes = album.scan_for_changes();
+        assert!(changes.has_changes());
+        assert_eq!(
+            changes.removed_from_source_since_last_transcode.audio,
+            vec![album.transcoded_album_path().join("02.mp3")]
+        );
+        assert_eq!(
+            changes.removed_from_source_since_last_transcode.data,
+            vec![album.transcoded_album_path().join("cover.jpg")]
+        );
+        assert_eq!(changes.number_of_changed_files(), 2);
+    }
+
+    #[test]
+    fn detects_changed_files() {
+        let album = transcoded_album();
+        album.write_source_file("01.flac", "first (remastered)");
+
+        let changes = album.scan_for_changes();
+        assert!(changes.has_changes());
+        assert_eq!(
+            changes.changed_in_source_since_last_transcode.audio,
+            vec![album.source_album_path().join("01.flac")]
+        );
+        assert_eq!(changes.number_of_changed_files(), 1);
+    }
+
+    #[test]
+    fn empty_album_has_no_changes() {
+        let album = TestAlbum::new();
+
+        let changes = album.scan_for_changes();
+        assert!(!changes.has_changes(), "{changes:?}");
+    }
+}
@@ -1,13 +1,39 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::time::UNIX_EPOCH;
 
+use euphony_configuration::file_metadata::ChangeDetectionMode;
 use miette::{miette, Context, IntoDiagnostic, Result};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::utilities::f64_approximate_eq;
 use crate::view::AlbumSourceFileList;
 
+/// Only the `schema_version` of a saved state file (see `read_state_schema_version`).
+#[derive(Deserialize)]
+struct StateSchemaVersion {
+    /// Missing in state files written before the states were versioned (read as 0).
+    #[serde(default)]
+    schema_version: u32,
+}
+
+/// Read only the `schema_version` of a saved (JSON) state file.
+///
+/// This is checked *before* the entire state is parsed, so that a state file written
+/// by an incompatible version of euphony is reported as a schema version mismatch
+/// (and the album is processed again) instead of failing to parse or being misinterpreted.
+pub fn read_state_schema_version(
+    state_file_contents: &str,
+) -> serde_json::Result<u32> {
+    let state: StateSchemaVersion = serde_json::from_str(state_file_contents)?;
+
+    Ok(state.schema_version)
+}
+
+
 /// Represents the filesystem state for the given album.
 /// **This struct is album location-agnostic (meaning you can use it for generating
 /// info about both the source and the transcoded album directory)!**
@@ -28,24 +54,43 @@ impl AlbumFileState {
     /// you got from `AlbumView`. A bit complicated, I know.
     ///
     /// The data in the instance refers to the state in the **source (untranscoded) album directory**.
+    ///
+    /// If `change_detection_mode` compares file contents, the content hash of each file is
+    /// computed as well, unless it can be reused from `previous_state`
+    /// (see `fill_content_hashes`).
     pub fn generate_source_state_from_source_file_list<P: AsRef<Path>>(
         tracked_source_files: &AlbumSourceFileList,
         base_source_album_directory: P,
+        change_detection_mode: ChangeDetectionMode,
+        previous_state: Option<&AlbumFileState>,
     ) -> Result<Self> {
         let base_source_album_directory = base_source_album_directory.as_ref();
 
-        let audio_file_map = Self::build_file_map_from_paths(
+        let mut audio_file_map = Self::build_file_map_from_paths(
             base_source_album_directory,
             &tracked_source_files.audio_files,
             true,
         )?;
 
-        let data_file_map = Self::build_file_map_from_paths(
+        let mut data_file_map = Self::build_file_map_from_paths(
             base_source_album_directory,
             &tracked_source_files.data_files,
             true,
         )?;
 
+        if change_detection_mode.compares_content_hash() {
+            Self::fill_content_hashes(
+                base_source_album_directory,
+                &mut audio_file_map,
+                previous_state.map(|state| &state.audio_files),
+            )?;
+            Self::fill_content_hashes(
+                base_source_album_directory,
+                &mut data_file_map,
+                previous_state.map(|state| &state.data_files),
+            )?;
+        }
+
         Ok(Self {
             audio_files: audio_file_map,
             data_files: data_file_map,
@@ -89,6 +134,136 @@ impl AlbumFileState {
         })
     }
 
+    /// Add a tracked audio file or update its metadata if it is already tracked
+    /// (the path is relative to the album directory). A file is only ever tracked once,
+    /// so if it was tracked as a data file, that entry is removed.
+    ///
+    /// Returns the previous metadata of the file, if it was tracked.
+    pub fn insert_audio_file<S: Into<String>>(
+        &mut self,
+        file_relative_path: S,
+        metadata: FileTrackedMetadata,
+    ) -> Option<FileTrackedMetadata> {
+        let file_relative_path = file_relative_path.into();
+
+        let previous_data_file = self.data_files.remove(&file_relative_path);
+        self.audio_files
+            .insert(file_relative_path, metadata)
+            .or(previous_data_file)
+    }
+
+    /// Add a tracked data file or update its metadata if it is already tracked
+    /// (the path is relative to the album directory). A file is only ever tracked once,
+    /// so if it was tracked as an audio file, that entry is removed.
+    ///
+    /// Returns the previous metadata of the file, if it was tracked.
+    pub fn insert_data_file<S: Into<String>>(
+        &mut self,
+        file_relative_path: S,
+        metadata: FileTrackedMetadata,
+    ) -> Option<FileTrackedMetadata> {
+        let file_relative_path = file_relative_path.into();
+
+        let previous_audio_file = self.audio_files.remove(&file_relative_path);
+        self.data_files
+            .insert(file_relative_path, metadata)
+            .or(previous_audio_file)
+    }
+
+    /// Stop tracking the given file (audio or data, relative to the album directory).
+    ///
+    /// Returns the metadata of the removed file or `None` if it wasn't tracked.
+    pub fn remove_file(
+        &mut self,
+        file_relative_path: &str,
+    ) -> Option<FileTrackedMetadata> {
+        self.audio_files
+            .remove(file_relative_path)
+            .or_else(|| self.data_files.remove(file_relative_path))
+    }
+
+    /// Whether the given file (relative to the album directory) is tracked.
+    pub fn contains_file(&self, file_relative_path: &str) -> bool {
+        self.audio_files.contains_key(file_relative_path)
+            || self.data_files.contains_key(file_relative_path)
+    }
+
+    /// Merge `other` into this state: files tracked in `other` are added,
+    /// or updated if already tracked (see `insert_audio_file` and `insert_data_file`).
+    pub fn merge(&mut self, other: AlbumFileState) {
+        for (file_relative_path, metadata) in other.audio_files {
+            self.insert_audio_file(file_relative_path, metadata);
+        }
+
+        for (file_relative_path, metadata) in other.data_files {
+            self.insert_data_file(file_relative_path, metadata);
+        }
+    }
+
+    /// Stop tracking all the files that no longer exist in `album_directory_path`.
+    ///
+    /// Returns the (sorted) relative paths of the removed files.
+    pub fn prune_missing_files<P: AsRef<Path>>(
+        &mut self,
+        album_directory_path: P,
+    ) -> Vec<String> {
+        let album_directory_path = album_directory_path.as_ref();
+        let mut pruned_file_paths: Vec<String> = Vec::new();
+
+        for file_map in [&mut self.audio_files, &mut self.data_files] {
+            file_map.retain(|file_relative_path, _| {
+                let exists =
+                    album_directory_path.join(file_relative_path).is_file();
+                if !exists {
+                    pruned_file_paths.push(file_relative_path.clone());
+                }
+
+                exists
+            });
+        }
+
+        pruned_file_paths.sort_unstable();
+        pruned_file_paths
+    }
+
+    /// Fill in the content hash (see `FileTrackedMetadata::content_hash`) of each file in `file_map`.
+    ///
+    /// Hashing requires reading entire files, so the hash is reused from `previous_file_map`
+    /// whenever the file's size and times haven't changed. The remaining files are hashed in parallel.
+    fn fill_content_hashes(
+        album_directory_path: &Path,
+        file_map: &mut HashMap<String, FileTrackedMetadata>,
+        previous_file_map: Option<&HashMap<String, FileTrackedMetadata>>,
+    ) -> Result<()> {
+        let mut files_to_hash: Vec<(&String, &mut FileTrackedMetadata)> =
+            Vec::new();
+
+        for (file_relative_path, metadata) in file_map.iter_mut() {
+            let previous_content_hash = previous_file_map
+                .and_then(|previous_map| previous_map.get(file_relative_path))
+                .filter(|previous_metadata| previous_metadata.matches(metadata))
+                .and_then(|previous_metadata| {
+                    previous_metadata.content_hash.clone()
+                });
+
+            match previous_content_hash {
+                Some(content_hash) => metadata.content_hash = Some(content_hash),
+                None => files_to_hash.push((file_relative_path, metadata)),
+            }
+        }
+
+        files_to_hash.into_par_iter().try_for_each(
+            |(file_relative_path, metadata)| {
+                metadata.content_hash =
+                    Some(FileTrackedMetadata::compute_content_hash(
+                        album_directory_path.join(file_relative_path),
+                    )?);
+
+                Ok(())
+            },
+        )
+    }
+
     /// Given a base album path and the list containing paths relative to `album_directory_path`,
     /// this function builds a `HashMap` from relative file paths
     /// to `FileTrackedMetadata` instances containing per-file metadata.
@@ -139,6 +314,11 @@ pub struct FileTrackedMetadata {
     pub size_bytes: u64,
     pub time_modified: f64,
     pub time_created: f64,
+
+    /// BLAKE3 digest of the file contents (hex-encoded).
+    /// Only computed for source files when `file_metadata.change_detection` is set to `hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
 }
 
 impl FileTrackedMetadata {
@@ -149,9 +329,28 @@ impl FileTrackedMetadata {
             size_bytes,
             time_modified,
             time_created,
+            content_hash: None,
         }
     }
 
+    /// Compute the BLAKE3 digest of the file contents (hex-encoded)
+    /// for use as `content_hash`. This reads the entire file.
+    pub fn compute_content_hash<P: AsRef<Path>>(file_path: P) -> Result<String> {
+        let file_path = file_path.as_ref();
+
+        let mut file =
+            File::open(file_path).into_diagnostic().wrap_err_with(|| {
+                miette!("Could not open file for hashing: {:?}", file_path)
+            })?;
+
+        let mut hasher = blake3::Hasher::new();
+        io::copy(&mut file, &mut hasher)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Could not hash file: {:?}", file_path))?;
+
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+
     /// Generate a new `FileTrackedMetadata` instance by getting the relevant values from
     /// the filesystem for the given `file_path`.
     pub fn from_file_path<P: AsRef<Path>>(file_path: P) -> Result<Self> {
@@ -208,10 +407,41 @@ impl FileTrackedMetadata {
     /// - any change in file size will cause it to return `false`,
     /// - any change in file creation/modification time (larger than 0.1) will cause it to return `false`.
     pub fn matches(&self, other: &Self) -> bool {
-        if self.size_bytes != other.size_bytes {
+        self.matches_with_mode(
+            other,
+            ChangeDetectionMode::SizeAndModificationTime,
+        )
+    }
+
+    /// Check whether the `FileTrackedMetadata` pair matches,
+    /// comparing only the metadata selected by `mode`
+    /// (see `file_metadata.change_detection`).
+    ///
+    /// When comparing content hashes and either of them is missing,
+    /// this falls back to comparing the size and times.
+    pub fn matches_with_mode(
+        &self,
+        other: &Self,
+        mode: ChangeDetectionMode,
+    ) -> bool {
+        if mode.compares_content_hash() {
+            return match (&self.content_hash, &other.content_hash) {
+                (Some(content_hash), Some(other_content_hash)) => {
+                    self.size_bytes == other.size_bytes
+                        && content_hash == other_content_hash
+                }
+                _ => self.matches(other),
+            };
+        }
+
+        if mode.compares_size() && self.size_bytes != other.size_bytes {
             return false;
         }
 
+        if !mode.compares_time() {
+            return true;
+        }
+
         static DEFAULT_MAX_TIME_DISTANCE: f64 = 0.1;
 
         if !f64_approximate_eq(
@@ -233,3 +463,193 @@ impl FileTrackedMetadata {
         true
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn metadata(size_bytes: u64) -> FileTrackedMetadata {
+        FileTrackedMetadata::new(size_bytes, 1000.0, 500.0)
+    }
+
+    #[test]
+    fn reads_state_schema_version_only() {
+        assert_eq!(
+            read_state_schema_version(
+                r#"{"schema_version": 3, "some_future_field": [1, 2]}"#
+            )
+            .unwrap(),
+            3
+        );
+        assert_eq!(
+            read_state_schema_version(r#"{"tracked_files": {}}"#).unwrap(),
+            0
+        );
+        assert!(read_state_schema_version("not json").is_err());
+    }
+
+    #[test]
+    fn inserts_and_updates_files() {
+        let mut state = AlbumFileState::default();
+
+        assert!(state.insert_audio_file("01.flac", metadata(10)).is_none());
+        assert!(state.insert_data_file("cover.jpg", metadata(20)).is_none());
+
+        let previous = state.insert_audio_file("01.flac", metadata(11));
+        assert_eq!(previous.unwrap().size_bytes, 10);
+        assert_eq!(state.audio_files["01.flac"].size_bytes, 11);
+        assert_eq!(state.audio_files.len(), 1);
+        assert_eq!(state.data_files.len(), 1);
+    }
+
+    #[test]
+    fn file_is_only_tracked_once() {
+        let mut state = AlbumFileState::default();
+        state.insert_data_file("01.flac", metadata(10));
+
+        let previous = state.insert_audio_file("01.flac", metadata(11));
+
+        assert_eq!(previous.unwrap().size_bytes, 10);
+        assert!(state.data_files.is_empty());
+        assert_eq!(state.audio_files["01.flac"].size_bytes, 11);
+    }
+
+    #[test]
+    fn removes_files() {
+        let mut state = AlbumFileState::default();
+        state.insert_audio_file("01.flac", metadata(10));
+        state.insert_data_file("cover.jpg", metadata(20));
+
+        assert_eq!(
+            state.remove_file("cover.jpg").unwrap().size_bytes,
+            20
+        );
+        assert!(state.remove_file("cover.jpg").is_none());
+        assert!(!state.contains_file("cover.jpg"));
+        assert!(state.contains_file("01.flac"));
+    }
+
+    #[test]
+    fn merges_states() {
+        let mut state = AlbumFileState::default();
+        state.insert_audio_file("01.flac", metadata(10));
+        state.insert_audio_file("02.flac", metadata(20));
+
+        let mut other = AlbumFileState::default();
+        other.insert_audio_file("02.flac", metadata(21));
+        other.insert_data_file("cover.jpg", metadata(30));
+
+        state.merge(other);
+
+        assert_eq!(state.audio_files["01.flac"].size_bytes, 10);
+        assert_eq!(state.audio_files["02.flac"].size_bytes, 21);
+        assert_eq!(state.data_files["cover.jpg"].size_bytes, 30);
+    }
+
+    #[test]
+    fn prunes_missing_files() {
+        let temporary_directory = TempDir::new().unwrap();
+        let album_directory = temporary_directory.path();
+        fs::write(album_directory.join("01.flac"), "audio").unwrap();
+
+        let mut state = AlbumFileState::default();
+        state.insert_audio_file("01.flac", metadata(5));
+        state.insert_audio_file("02.flac", metadata(5));
+        state.insert_data_file("cover.jpg", metadata(5));
+
+        let pruned_file_paths = state.prune_missing_files(album_directory);
+
+        assert_eq!(pruned_file_paths, vec!["02.flac", "cover.jpg"]);
+        assert!(state.contains_file("01.flac"));
+        assert!(!state.contains_file("02.flac"));
+        assert!(state.data_files.is_empty());
+    }
+
+    #[test]
+    fn content_hash_ignores_time_changes() {
+        let mut first = FileTrackedMetadata::new(10, 1000.0, 500.0);
+        first.content_hash = Some("aaaa".to_string());
+        let mut touched = FileTrackedMetadata::new(10, 2000.0, 500.0);
+        touched.content_hash = Some("aaaa".to_string());
+        let mut edited = FileTrackedMetadata::new(10, 1000.0, 500.0);
+        edited.content_hash = Some("bbbb".to_string());
+
+        let mode = ChangeDetectionMode::ContentHash;
+        assert!(first.matches_with_mode(&touched, mode));
+        assert!(!first.matches_with_mode(&edited, mode));
+
+        // Without hashes, size and times are compared instead.
+        assert!(!metadata(10).matches_with_mode(&touched, mode));
+        assert!(metadata(10).matches_with_mode(&metadata(10), mode));
+    }
+
+    #[test]
+    fn reuses_content_hashes_of_unchanged_files() {
+        let temporary_directory = TempDir::new().unwrap();
+        let album_directory = temporary_directory.path();
+        fs::write(album_directory.join("01.flac"), "audio").unwrap();
+        fs::write(album_directory.join("02.flac"), "other audio").unwrap();
+
+        let file_metadata = |file_name: &str| {
+            FileTrackedMetadata::from_file_path(album_directory.join(file_name))
+                .unwrap()
+        };
+
+        let mut previous_file_map = HashMap::new();
+        let mut unchanged_metadata = file_metadata("01.flac");
+        unchanged_metadata.content_hash = Some("previous".to_string());
+        previous_file_map.insert("01.flac".to_string(), unchanged_metadata);
+        let mut changed_metadata = file_metadata("02.flac");
+        changed_metadata.size_bytes += 1;
+        changed_metadata.content_hash = Some("previous".to_string());
+        previous_file_map.insert("02.flac".to_string(), changed_metadata);
+
+        let mut file_map = HashMap::new();
+        file_map.insert("01.flac".to_string(), file_metadata("01.flac"));
+        file_map.insert("02.flac".to_string(), file_metadata("02.flac"));
+
+        AlbumFileState::fill_content_hashes(
+            album_directory,
+            &mut file_map,
+            Some(&previous_file_map),
+        )
+        .unwrap();
+
+        assert_eq!(
+            file_map["01.flac"].content_hash.as_deref(),
+            Some("previous")
+        );
+        assert_eq!(
+            file_map["02.flac"].content_hash,
+            Some(
+                FileTrackedMetadata::compute_content_hash(
+                    album_directory.join("02.flac")
+                )
+                .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn serialization_round_trip() {
+        let mut state = AlbumFileState::default();
+        state.insert_audio_file("01.flac", metadata(10));
+        state.insert_data_file("cover.jpg", metadata(20));
+
+        let serialized_state = serde_json::to_string(&state).unwrap();
+        let deserialized_state: AlbumFileState =
+            serde_json::from_str(&serialized_state).unwrap();
+
+        assert_eq!(deserialized_state.audio_files.len(), 1);
+        assert_eq!(deserialized_state.data_files.len(), 1);
+        assert!(deserialized_state.audio_files["01.flac"]
+            .matches(&state.audio_files["01.flac"]));
+        assert!(deserialized_state.data_files["cover.jpg"]
+            .matches(&state.data_files["cover.jpg"]));
+    }
+}
@@ -3,17 +3,24 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
+use euphony_configuration::aggregated_library::AggregationMode;
+use euphony_configuration::device_profile::state_file_name_for_device_profile;
+use euphony_configuration::file_metadata::ChangeDetectionMode;
 use euphony_configuration::library::LibraryConfiguration;
-use euphony_configuration::Configuration;
+use euphony_configuration::{AlbumConfiguration, Configuration};
 use miette::{miette, Context, Diagnostic, IntoDiagnostic, Result};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use super::common::AlbumFileState;
+use super::common::{read_state_schema_version, AlbumFileState};
 use crate::view::AlbumSourceFileList;
 
 
 pub const SOURCE_ALBUM_STATE_FILE_NAME: &str = ".album.source-state.euphony";
+/// Source album states of the `mirror` command are kept separately, so that mirroring
+/// and transcoding don't invalidate each other's states (see `AggregationMode`).
+pub const MIRROR_SOURCE_ALBUM_STATE_FILE_NAME: &str =
+    ".album.mirror-source-state.euphony";
 const SOURCE_ALBUM_STATE_SCHEMA_VERSION: u32 = 2;
 
 
@@ -56,6 +63,25 @@ pub struct SourceAlbumState {
     /// Keys are file paths relative to the directory for which the state
     /// is being generated for / is saved inside of.
     pub tracked_files: AlbumFileState,
+
+    /// The change detection mode (see `file_metadata.change_detection`)
+    /// that was configured when this state was generated.
+    ///
+    /// States saved before this field existed were always compared using
+    /// both size and time, which is why that is the default.
+    #[serde(default)]
+    pub change_detection_mode: ChangeDetectionMode,
+
+    /// Hash of the effective transcoding settings (ffmpeg arguments and output extension,
+    /// see `AlbumTranscodingConfiguration::transcoding_settings_hash`) that were configured
+    /// when this state was generated. If the settings change, all audio files
+    /// of the album are transcoded again.
+    ///
+    /// `None` when mirroring (audio files are copied as-is) and in states saved before
+    /// this field existed (these are never considered stale, so upgrading euphony
+    /// doesn't re-transcode the entire collection).
+    #[serde(default)]
+    pub transcoding_settings_hash: Option<String>,
 }
 
 impl SourceAlbumState {
@@ -73,14 +99,18 @@ impl SourceAlbumState {
         }
 
         let file_contents = fs::read_to_string(file_path)?;
-        let state: Self = serde_json::from_str(&file_contents)?;
 
-        if state.schema_version != SOURCE_ALBUM_STATE_SCHEMA_VERSION {
+        // The version is checked before parsing the rest of the state,
+        // as the format of another schema version might not even parse.
+        let schema_version = read_state_schema_version(&file_contents)?;
+        if schema_version != SOURCE_ALBUM_STATE_SCHEMA_VERSION {
             return Err(SourceAlbumStateLoadError::SchemaVersionMismatch(
-                state.schema_version,
+                schema_version,
             ));
         }
 
+        let state: Self = serde_json::from_str(&file_contents)?;
+
         Ok(state)
     }
 
@@ -88,12 +118,18 @@ impl SourceAlbumState {
     /// an album state saved, `Ok(None)` will be returned.
     ///
     /// This method will use the `.album.source-state.euphony` file (see `SOURCE_ALBUM_STATE_FILE_NAME`)
-    /// directly inside the directory.
+    /// directly inside the directory (or its mirror or device profile counterpart,
+    /// depending on `mode` and `device_profile`).
     pub fn load_from_directory<P: AsRef<Path>>(
         directory_path: P,
+        mode: AggregationMode,
+        device_profile: Option<&str>,
     ) -> Result<Self, SourceAlbumStateLoadError> {
-        let album_state_file_path =
-            Self::get_state_file_path_for_directory(directory_path);
+        let album_state_file_path = Self::get_state_file_path_for_directory(
+            directory_path,
+            mode,
+            device_profile,
+        );
 
         if !album_state_file_path.is_file() {
             return Err(SourceAlbumStateLoadError::NotFound);
@@ -102,24 +138,49 @@ impl SourceAlbumState {
         Self::load_from_file(album_state_file_path)
     }
 
+    /// Get the name of the state file for the given `AggregationMode`
+    /// (see `SOURCE_ALBUM_STATE_FILE_NAME` and `MIRROR_SOURCE_ALBUM_STATE_FILE_NAME`)
+    /// and device profile (see `state_file_name_for_device_profile`).
+    pub fn get_state_file_name(
+        mode: AggregationMode,
+        device_profile: Option<&str>,
+    ) -> String {
+        let state_file_name = match mode {
+            AggregationMode::Transcode => SOURCE_ALBUM_STATE_FILE_NAME,
+            AggregationMode::Mirror => MIRROR_SOURCE_ALBUM_STATE_FILE_NAME,
+        };
+
+        state_file_name_for_device_profile(state_file_name, device_profile)
+    }
+
     /// Get default path for saving `SourceAlbumState`s inside a directory.
-    /// This is set by `SOURCE_ALBUM_STATE_FILE_NAME`, which is currently `.album.source-state.euphony`.
+    /// This is set by `SOURCE_ALBUM_STATE_FILE_NAME`, which is currently `.album.source-state.euphony`
+    /// (or `MIRROR_SOURCE_ALBUM_STATE_FILE_NAME` when mirroring, see `get_state_file_name`).
     ///
     /// # Example
     /// ```
     /// # use std::path::Path;
+    /// # use euphony_configuration::aggregated_library::AggregationMode;
     /// # use euphony_library::state::source::SourceAlbumState;
     /// let directory_path = Path::new("D:/MusicLibrary/Ed Harrison/Neotokyo");
     ///
     /// assert_eq!(
-    ///     SourceAlbumState::get_state_file_path_for_directory(directory_path),
+    ///     SourceAlbumState::get_state_file_path_for_directory(
+    ///         directory_path,
+    ///         AggregationMode::Transcode,
+    ///         None
+    ///     ),
     ///     Path::new("D:/MusicLibrary/Ed Harrison/Neotokyo/.album.source-state.euphony")
     /// );
     /// ```
     pub fn get_state_file_path_for_directory<P: AsRef<Path>>(
         directory_path: P,
+        mode: AggregationMode,
+        device_profile: Option<&str>,
     ) -> PathBuf {
-        directory_path.as_ref().join(SOURCE_ALBUM_STATE_FILE_NAME)
+        directory_path
+            .as_ref()
+            .join(Self::get_state_file_name(mode, device_profile))
     }
 
     /// Save the source album state into the given file as JSON. If the file exists without
@@ -166,15 +227,19 @@ impl SourceAlbumState {
     /// If the file exists without `allow_overwrite` being `true`, this method will return an `Err`.
     ///
     /// *This method is preferred over `SourceAlbumState::save_to_file` since it automatically uses
-    /// the correct file name (see `SOURCE_ALBUM_STATE_FILE_NAME`).*
+    /// the correct file name for the given `mode` and `device_profile` (see `get_state_file_name`).*
     pub fn save_to_directory<P: AsRef<Path>>(
         &self,
         output_directory_path: P,
         allow_overwrite: bool,
+        mode: AggregationMode,
+        device_profile: Option<&str>,
     ) -> Result<()> {
-        let output_file_path = output_directory_path
-            .as_ref()
-            .join(SOURCE_ALBUM_STATE_FILE_NAME);
+        let output_file_path = Self::get_state_file_path_for_directory(
+            output_directory_path,
+            mode,
+            device_profile,
+        );
 
         self.save_to_file(output_file_path, allow_overwrite)
     }
@@ -183,30 +248,65 @@ impl SourceAlbumState {
     /// `tracked_files`.
     ///
     /// A path to the base of the source directory is also required for consistency with the
-    /// `TranscodedAlbumState` version of this method.
+    /// `TranscodedAlbumState` version of this method. `change_detection_mode` is the currently
+    /// configured `file_metadata.change_detection` mode and `transcoding_settings_hash`
+    /// is the current hash of the transcoding settings (see `Self::transcoding_settings_hash`),
+    /// both of which are stored alongside the files.
+    ///
+    /// When comparing file contents, the content hashes of unchanged files
+    /// are reused from `previous_state` instead of being recomputed.
     pub fn generate_from_tracked_files<P: AsRef<Path>>(
         tracked_album_files: &AlbumSourceFileList,
         base_source_album_directory: P,
+        change_detection_mode: ChangeDetectionMode,
+        transcoding_settings_hash: Option<String>,
+        previous_state: Option<&SourceAlbumState>,
     ) -> Result<Self> {
         let tracked_files =
             AlbumFileState::generate_source_state_from_source_file_list(
                 tracked_album_files,
                 base_source_album_directory,
+                change_detection_mode,
+                previous_state.map(|state| &state.tracked_files),
             )?;
 
         Ok(Self {
             schema_version: SOURCE_ALBUM_STATE_SCHEMA_VERSION,
             tracked_files,
+            change_detection_mode,
+            transcoding_settings_hash,
         })
     }
 
+    /// Returns the hash of the effective transcoding settings for the given album
+    /// (see `AlbumTranscodingConfiguration::transcoding_settings_hash`)
+    /// or `None` when mirroring, as audio files are not transcoded then.
+    pub fn transcoding_settings_hash(
+        configuration: &Configuration,
+        library_configuration: &LibraryConfiguration,
+        album_configuration: &AlbumConfiguration,
+    ) -> Option<String> {
+        match configuration.aggregated_library.mode {
+            AggregationMode::Transcode => Some(
+                album_configuration.transcoding.transcoding_settings_hash(
+                    &configuration.tools.ffmpeg,
+                    &library_configuration.transcoding,
+                    &configuration.aggregated_library,
+                ),
+            ),
+            AggregationMode::Mirror => None,
+        }
+    }
+
     /// Provided a source file path (relative to the source album directory),
     /// get the associated relative file path in the transcoded album directory.
     ///
-    /// This method will do the necessary file extension swapping (e.g. FLAC -> MP3).
+    /// This method will do the necessary file extension swapping (e.g. FLAC -> MP3),
+    /// except for audio files that are copied as-is (when mirroring or due to an extension rule).
     pub fn get_transcoded_file_path<P: AsRef<Path>>(
         configuration: &Configuration,
         library_configuration: &LibraryConfiguration,
+        album_configuration: &AlbumConfiguration,
         source_file_path: P,
     ) -> Result<PathBuf> {
         let source_file_path = source_file_path.as_ref();
@@ -223,12 +323,25 @@ impl SourceAlbumState {
                 )
             })?
         {
-            Ok(source_file_path.with_extension(
-                &configuration
-                    .tools
-                    .ffmpeg
-                    .audio_transcoding_output_extension,
-            ))
+            let is_copied_as_is = library_configuration
+                .transcoding
+                .is_path_copied_audio_file_by_extension(source_file_path)?;
+
+            match configuration.aggregated_library.mode {
+                AggregationMode::Transcode if is_copied_as_is => {
+                    Ok(source_file_path.to_path_buf())
+                }
+                AggregationMode::Transcode => Ok(source_file_path
+                    .with_extension(
+                        album_configuration
+                            .transcoding
+                            .audio_transcoding_output_extension(
+                                &configuration.tools.ffmpeg,
+                            ),
+                    )),
+                // Audio files are copied as-is when mirroring.
+                AggregationMode::Mirror => Ok(source_file_path.to_path_buf()),
+            }
         } else if library_configuration
             .transcoding
             .is_path_data_file_by_extension(source_file_path)
@@ -245,3 +358,51 @@ impl SourceAlbumState {
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::state::common::FileTrackedMetadata;
+
+    #[test]
+    fn file_round_trip_preserves_mutations() {
+        let mut state = SourceAlbumState {
+            schema_version: SOURCE_ALBUM_STATE_SCHEMA_VERSION,
+            tracked_files: AlbumFileState::default(),
+            change_detection_mode: ChangeDetectionMode::Size,
+            transcoding_settings_hash: Some("0123456789abcdef".to_string()),
+        };
+        state.tracked_files.insert_audio_file(
+            "01.flac",
+            FileTrackedMetadata::new(10, 1.0, 1.0),
+        );
+        state.tracked_files.insert_audio_file(
+            "02.flac",
+            FileTrackedMetadata::new(20, 2.0, 2.0),
+        );
+        state.tracked_files.remove_file("02.flac");
+
+        let temporary_directory = TempDir::new().unwrap();
+        let state_file_path = temporary_directory
+            .path()
+            .join(".album.source-state.euphony");
+
+        state.save_to_file(&state_file_path, false).unwrap();
+        let loaded_state =
+            SourceAlbumState::load_from_file(&state_file_path).unwrap();
+
+        assert_eq!(
+            loaded_state.change_detection_mode,
+            state.change_detection_mode
+        );
+        assert_eq!(
+            loaded_state.transcoding_settings_hash,
+            state.transcoding_settings_hash
+        );
+        assert!(loaded_state.tracked_files.contains_file("01.flac"));
+        assert!(!loaded_state.tracked_files.contains_file("02.flac"));
+    }
+}
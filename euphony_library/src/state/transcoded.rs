@@ -8,7 +8,7 @@ use miette::{miette, Context, Diagnostic, IntoDiagnostic, Result};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use super::common::AlbumFileState;
+use super::common::{read_state_schema_version, AlbumFileState};
 use crate::view::common::SortedFileMap;
 use crate::view::AlbumSourceFileList;
 
@@ -66,18 +66,20 @@ impl TranscodedAlbumState {
         }
 
         let file_contents = fs::read_to_string(file_path)?;
-        let transcoded_state: Self = serde_json::from_str(&file_contents)?;
 
-        if transcoded_state.schema_version
-            != TRANSCODED_ALBUM_STATE_SCHEMA_VERSION
-        {
+        // The version is checked before parsing the rest of the state,
+        // as the format of another schema version might not even parse.
+        let schema_version = read_state_schema_version(&file_contents)?;
+        if schema_version != TRANSCODED_ALBUM_STATE_SCHEMA_VERSION {
             return Err(
                 TranscodedAlbumStateLoadError::SchemaVersionMismatch(
-                    transcoded_state.schema_version,
+                    schema_version,
                 ),
             );
         }
 
+        let transcoded_state: Self = serde_json::from_str(&file_contents)?;
+
         Ok(transcoded_state)
     }
 
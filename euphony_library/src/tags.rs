@@ -0,0 +1,377 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use lofty::{ItemKey, ParseOptions, Probe, Tag, TaggedFileExt};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use parking_lot::{const_mutex, Mutex};
+
+/// Tags that have already been read (see `AudioFileTags::read_from_file_cached`),
+/// by absolute audio file path. Entries of files that no longer exist are removed
+/// when their album is rescanned (see `AudioFileTags::evict_cached_missing_files`).
+static TAG_CACHE: Mutex<BTreeMap<PathBuf, CachedAudioFileTags>> =
+    const_mutex(BTreeMap::new());
+
+
+/// Tags of an audio file, along with the size and modification time
+/// the file had when they were read.
+struct CachedAudioFileTags {
+    size_bytes: u64,
+
+    modified_at: Option<SystemTime>,
+
+    /// `None` if the tags couldn't be read.
+    tags: Option<AudioFileTags>,
+}
+
+/// The tags euphony uses to build output paths
+/// (see `euphony_configuration::output_template::OutputPathTemplate`).
+///
+/// Empty tags are treated as missing.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AudioFileTags {
+    pub album_artist: Option<String>,
+
+    pub artist: Option<String>,
+
+    pub album: Option<String>,
+
+    /// Only the year of the release date (e.g. `2001` from `2001-05-14`).
+    pub year: Option<String>,
+
+    pub disc_number: Option<u32>,
+
+    pub track_number: Option<u32>,
+
+    pub title: Option<String>,
+}
+
+impl AudioFileTags {
+    /// Read the tags embedded in the given audio file (e.g. Vorbis comments in FLAC files
+    /// or ID3v2 tags in MP3 files). If a file has multiple tag blocks, the primary tag
+    /// of its format (e.g. Vorbis comments in FLAC files) takes precedence.
+    pub fn read_from_file<P: AsRef<Path>>(file_path: P) -> Result<Self> {
+        let file_path = file_path.as_ref();
+
+        // Only the tags are needed, so the audio properties (e.g. duration) aren't read.
+        let tagged_file = Probe::open(file_path)
+            .and_then(|probe| {
+                probe
+                    .options(ParseOptions::new().read_properties(false))
+                    .read()
+            })
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Failed to read the tags of {:?}.", file_path)
+            })?;
+
+        let mut tags = Self::default();
+
+        // Other tags (e.g. ID3v2 in front of a FLAC stream) come first,
+        // so the primary tag overwrites them.
+        let primary_tag_type = tagged_file.primary_tag_type();
+        for tag in tagged_file
+            .tags()
+            .iter()
+            .filter(|tag| tag.tag_type() != primary_tag_type)
+        {
+            tags.apply_tag(tag);
+        }
+
+        if let Some(primary_tag) = tagged_file.primary_tag() {
+            tags.apply_tag(primary_tag);
+        }
+
+        Ok(tags)
+    }
+
+    /// Same as `read_from_file`, but the tags of a file whose size and modification time
+    /// haven't changed since it was last read are reused instead of probing it again.
+    /// Returns `None` if the tags couldn't be read.
+    ///
+    /// Output paths are rendered from tags every time an album is loaded or scanned,
+    /// so this avoids reading the tags of unchanged albums over and over
+    /// (e.g. in `transcode --watch`).
+    pub fn read_from_file_cached<P: AsRef<Path>>(file_path: P) -> Option<Self> {
+        let file_path = file_path.as_ref();
+
+        let file_metadata = fs::metadata(file_path).ok()?;
+        let size_bytes = file_metadata.len();
+        let modified_at = file_metadata.modified().ok();
+
+        if let Some(cached_tags) = TAG_CACHE.lock().get(file_path) {
+            if cached_tags.size_bytes == size_bytes
+                && cached_tags.modified_at == modified_at
+            {
+                return cached_tags.tags.clone();
+            }
+        }
+
+        let tags = Self::read_from_file(file_path).ok();
+        TAG_CACHE.lock().insert(
+            file_path.to_path_buf(),
+            CachedAudioFileTags {
+                size_bytes,
+                modified_at,
+                tags: tags.clone(),
+            },
+        );
+
+        tags
+    }
+
+    /// Remove the cached tags (see `read_from_file_cached`) of all files inside the given
+    /// directory that no longer exist, so the cache doesn't keep growing as albums
+    /// are changed, moved or removed over a long run (e.g. in `transcode --watch`).
+    pub fn evict_cached_missing_files<P: AsRef<Path>>(directory_path: P) {
+        let directory_path = directory_path.as_ref();
+
+        // Paths are ordered component by component,
+        // so the files inside the directory directly follow it.
+        let cached_file_paths: Vec<PathBuf> = TAG_CACHE
+            .lock()
+            .range::<Path, _>((
+                Bound::Included(directory_path),
+                Bound::Unbounded,
+            ))
+            .map(|(file_path, _)| file_path)
+            .take_while(|file_path| file_path.starts_with(directory_path))
+            .cloned()
+            .collect();
+
+        // The files are checked without holding the lock, as other albums may be scanned in parallel.
+        let missing_file_paths: Vec<PathBuf> = cached_file_paths
+            .into_iter()
+            .filter(|file_path| !file_path.is_file())
+            .collect();
+
+        if missing_file_paths.is_empty() {
+            return;
+        }
+
+        let mut tag_cache = TAG_CACHE.lock();
+        for file_path in missing_file_paths {
+            tag_cache.remove(&file_path);
+        }
+    }
+
+    /// Guess the track number and title from the name of an audio file
+    /// (e.g. `01 - Title.flac`, `01. Title.flac` or `Title.flac`) for libraries
+    /// that build output paths from directory and file names. If the file is inside
+    /// a disc subdirectory (see `parse_disc_directory_name`), the disc number is set as well.
+    pub fn from_file_name<P: AsRef<Path>>(file_path: P) -> Self {
+        let file_path = file_path.as_ref();
+        let disc_number = file_path.parent().and_then(Path::file_name).and_then(
+            |directory_name| {
+                parse_disc_directory_name(&directory_name.to_string_lossy())
+            },
+        );
+
+        let file_stem =
+            file_path.file_stem().unwrap_or_default().to_string_lossy();
+
+        let track_number_length =
+            file_stem.chars().take_while(char::is_ascii_digit).count();
+        let title = file_stem[track_number_length..]
+            .trim_start_matches(|character: char| {
+                character.is_whitespace() || matches!(character, '-' | '.' | '_')
+            })
+            .trim();
+
+        // A file name consisting only of a number (e.g. `01.flac`) is its title.
+        if title.is_empty() {
+            return Self {
+                disc_number,
+                title: Some(file_stem.trim().to_string())
+                    .filter(|title| !title.is_empty()),
+                ..Self::default()
+            };
+        }
+
+        Self {
+            disc_number,
+            track_number: file_stem[..track_number_length].parse::<u32>().ok(),
+            title: Some(title.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Returns `true` if none of the tags are set.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Set the tags that are present (and not empty) in the given tag block.
+    fn apply_tag(&mut self, tag: &Tag) {
+        let value_of = |key: &ItemKey| {
+            tag.get_string(key)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        if let Some(album_artist) = value_of(&ItemKey::AlbumArtist) {
+            self.album_artist = Some(album_artist.to_string());
+        }
+        if let Some(artist) = value_of(&ItemKey::TrackArtist) {
+            self.artist = Some(artist.to_string());
+        }
+        if let Some(album) = value_of(&ItemKey::AlbumTitle) {
+            self.album = Some(album.to_string());
+        }
+
+        // The recording date takes precedence over the original release date.
+        let date_keys = [
+            ItemKey::RecordingDate,
+            ItemKey::Year,
+            ItemKey::OriginalReleaseDate,
+        ];
+        if let Some(year) =
+            date_keys.iter().filter_map(value_of).find_map(parse_year)
+        {
+            self.year = Some(year);
+        }
+
+        if let Some(disc_number) =
+            value_of(&ItemKey::DiscNumber).and_then(parse_position_number)
+        {
+            self.disc_number = Some(disc_number);
+        }
+        if let Some(track_number) =
+            value_of(&ItemKey::TrackNumber).and_then(parse_position_number)
+        {
+            self.track_number = Some(track_number);
+        }
+        if let Some(title) = value_of(&ItemKey::TrackTitle) {
+            self.title = Some(title.to_string());
+        }
+    }
+}
+
+
+/// Parse the disc number from the name of a disc subdirectory of a multi-disc album,
+/// e.g. `CD1`, `CD 2`, `Disc 03`, `disk_4` or `Disc 1 - The Beginning`.
+/// Returns `None` if the name doesn't look like a disc subdirectory.
+pub fn parse_disc_directory_name(directory_name: &str) -> Option<u32> {
+    let lowercase_name = directory_name.trim().to_lowercase();
+
+    let after_prefix = ["disc", "disk", "cd"]
+        .into_iter()
+        .find_map(|prefix| lowercase_name.strip_prefix(prefix))?
+        .trim_start_matches([' ', '_', '-', '.']);
+
+    let number_length = after_prefix
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .count();
+    let after_number = &after_prefix[number_length..];
+
+    // Anything after the number must be separated from it (e.g. not `CD1a`).
+    if !(after_number.is_empty()
+        || after_number.starts_with([' ', '_', '-', '.', '(', '[']))
+    {
+        return None;
+    }
+
+    after_prefix[..number_length].parse::<u32>().ok()
+}
+
+/// Parse a track or disc number, which may include the total (e.g. `3/12`).
+fn parse_position_number(value: &str) -> Option<u32> {
+    value
+        .split('/')
+        .next()
+        .and_then(|number| number.trim().parse::<u32>().ok())
+}
+
+/// Extract the year from a date tag (e.g. `2001`, `2001-05-14` or `2001-05-14T00:00:00`).
+fn parse_year(value: &str) -> Option<String> {
+    let year: String = value.chars().take_while(char::is_ascii_digit).collect();
+
+    (year.len() == 4).then_some(year)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_position_numbers_and_years() {
+        assert_eq!(parse_position_number("3"), Some(3));
+        assert_eq!(parse_position_number("03/12"), Some(3));
+        assert_eq!(parse_position_number("A1"), None);
+
+        assert_eq!(parse_year("2001"), Some("2001".to_string()));
+        assert_eq!(parse_year("2001-05-14"), Some("2001".to_string()));
+        assert_eq!(parse_year("May 2001"), None);
+    }
+
+    #[test]
+    fn guesses_track_numbers_and_titles_from_file_names() {
+        let tags = AudioFileTags::from_file_name("CD1/01 - Song.flac");
+        assert_eq!(tags.track_number, Some(1));
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+
+        let tags = AudioFileTags::from_file_name("7. Song.v2.flac");
+        assert_eq!(tags.track_number, Some(7));
+        assert_eq!(tags.title.as_deref(), Some("Song.v2"));
+
+        let tags = AudioFileTags::from_file_name("Song.flac");
+        assert_eq!(tags.track_number, None);
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+
+        let tags = AudioFileTags::from_file_name("01.flac");
+        assert_eq!(tags.track_number, None);
+        assert_eq!(tags.title.as_deref(), Some("01"));
+        assert_eq!(tags.disc_number, None);
+
+        let tags = AudioFileTags::from_file_name("Disc 2/03 - Song.flac");
+        assert_eq!(tags.disc_number, Some(2));
+        assert_eq!(tags.track_number, Some(3));
+    }
+
+    #[test]
+    fn evicts_cached_tags_of_missing_files() {
+        let temporary_directory = tempfile::tempdir().unwrap();
+        let album_directory = temporary_directory.path().join("Album");
+        fs::create_dir(&album_directory).unwrap();
+
+        let kept_file_path = album_directory.join("01 - Kept.flac");
+        let removed_file_path = album_directory.join("02 - Removed.flac");
+        fs::write(&kept_file_path, "not audio").unwrap();
+        fs::write(&removed_file_path, "not audio").unwrap();
+
+        // Unreadable tags are cached too.
+        assert_eq!(
+            AudioFileTags::read_from_file_cached(&kept_file_path),
+            None
+        );
+        assert_eq!(
+            AudioFileTags::read_from_file_cached(&removed_file_path),
+            None
+        );
+
+        fs::remove_file(&removed_file_path).unwrap();
+        AudioFileTags::evict_cached_missing_files(&album_directory);
+
+        let tag_cache = TAG_CACHE.lock();
+        assert!(tag_cache.contains_key(&kept_file_path));
+        assert!(!tag_cache.contains_key(&removed_file_path));
+    }
+
+    #[test]
+    fn parses_disc_directory_names() {
+        assert_eq!(parse_disc_directory_name("CD1"), Some(1));
+        assert_eq!(parse_disc_directory_name("cd 02"), Some(2));
+        assert_eq!(
+            parse_disc_directory_name("Disc 3 - Live"),
+            Some(3)
+        );
+        assert_eq!(parse_disc_directory_name("Disk_4"), Some(4));
+        assert_eq!(parse_disc_directory_name("CD1a"), None);
+        assert_eq!(parse_disc_directory_name("Discography"), None);
+        assert_eq!(parse_disc_directory_name("Scans"), None);
+    }
+}
@@ -21,6 +21,11 @@ impl<T> SortedFileList<T> {
     pub fn is_empty(&self) -> bool {
         self.audio.is_empty() && self.data.is_empty()
     }
+
+    /// Returns the total number of files in both `audio` and `data` lists.
+    pub fn len(&self) -> usize {
+        self.audio.len() + self.data.len()
+    }
 }
 
 
@@ -51,6 +56,11 @@ impl<T> ExtendedSortedFileList<T> {
     pub fn is_empty(&self) -> bool {
         self.audio.is_empty() && self.data.is_empty() && self.unknown.is_empty()
     }
+
+    /// Returns the total number of files in `audio`, `data` and `unknown` lists.
+    pub fn len(&self) -> usize {
+        self.audio.len() + self.data.len() + self.unknown.len()
+    }
 }
 
 /// We store file creation and modification in 64-bit floats, but we usually compare two times
@@ -62,3 +72,121 @@ impl<T> ExtendedSortedFileList<T> {
 pub fn f64_approximate_eq(first: f64, second: f64, max_distance: f64) -> bool {
     (first - second).abs() < max_distance
 }
+
+/// A lazily computed value (e.g. the result of an expensive scan) that is computed
+/// on first access and reused afterwards, until it is explicitly invalidated
+/// (e.g. after the underlying files were modified externally).
+#[derive(Debug, Clone)]
+pub struct CachedValue<T> {
+    value: Option<T>,
+}
+
+impl<T> Default for CachedValue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> CachedValue<T> {
+    /// Initialize a new, empty `CachedValue` (the first access will compute the value).
+    pub fn new() -> Self {
+        Self { value: None }
+    }
+
+    /// Returns the cached value, if any (this never computes the value).
+    pub fn get(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    /// Returns `true` if a value is currently cached.
+    pub fn is_cached(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// Returns the cached value or, if there is none, computes it with `init` and caches it.
+    pub fn get_or_init<F: FnOnce() -> T>(&mut self, init: F) -> &T {
+        self.value.get_or_insert_with(init)
+    }
+
+    /// Same as `get_or_init`, but the value is computed with a fallible closure.
+    /// If `init` fails, nothing is cached and the error is returned.
+    pub fn get_or_try_init<F, E>(&mut self, init: F) -> Result<&T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let value = match self.value.take() {
+            Some(value) => value,
+            None => init()?,
+        };
+
+        Ok(self.value.insert(value))
+    }
+
+    /// Clear the cached value, so the next `get_or_init`/`get_or_try_init` computes it again.
+    /// Returns the previously cached value, if any.
+    pub fn invalidate(&mut self) -> Option<T> {
+        self.value.take()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn computes_value_only_once() {
+        let num_computations = Cell::new(0);
+        let compute = || {
+            num_computations.set(num_computations.get() + 1);
+            42
+        };
+
+        let mut cached_value = CachedValue::new();
+        assert!(!cached_value.is_cached());
+        assert_eq!(cached_value.get(), None);
+
+        // Miss: the value is computed.
+        assert_eq!(*cached_value.get_or_init(compute), 42);
+        // Hit: the cached value is reused.
+        assert_eq!(*cached_value.get_or_init(compute), 42);
+
+        assert_eq!(num_computations.get(), 1);
+        assert_eq!(cached_value.get(), Some(&42));
+    }
+
+    #[test]
+    fn recomputes_value_after_invalidation() {
+        let mut cached_value = CachedValue::new();
+        cached_value.get_or_init(|| 1);
+
+        assert_eq!(cached_value.invalidate(), Some(1));
+        assert!(!cached_value.is_cached());
+        assert_eq!(cached_value.invalidate(), None);
+
+        assert_eq!(*cached_value.get_or_init(|| 2), 2);
+    }
+
+    #[test]
+    fn does_not_cache_failed_initialization() {
+        let mut cached_value: CachedValue<u32> = CachedValue::new();
+
+        assert_eq!(
+            cached_value.get_or_try_init(|| Err("failed")),
+            Err("failed")
+        );
+        assert!(!cached_value.is_cached());
+
+        assert_eq!(
+            cached_value.get_or_try_init(|| Ok::<_, &str>(3)),
+            Ok(&3)
+        );
+        // Already cached, so the failing closure is not called.
+        assert_eq!(
+            cached_value.get_or_try_init(|| Err("failed")),
+            Ok(&3)
+        );
+    }
+}
@@ -1,9 +1,30 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 
-use euphony_configuration::library::LibraryConfiguration;
-use euphony_configuration::{AlbumConfiguration, Configuration};
+use euphony_configuration::aggregated_library::{
+    AggregationMode,
+    CaseCollisionStrategy,
+    OutputFilesystem,
+};
+use euphony_configuration::library::{
+    AlbumInfoSource,
+    DiscSubdirectoryLayout,
+    LibraryConfiguration,
+};
+use euphony_configuration::output_template::{
+    OutputPathTemplate,
+    OutputTemplatePlaceholder,
+};
+use euphony_configuration::{
+    sanitize_file_name_for_fat,
+    sanitize_relative_path_for_fat,
+    AlbumConfiguration,
+    Configuration,
+    UNPORTABLE_FILE_NAME_CHARACTERS,
+};
 use fs_more::directory::DirectoryScan;
 use miette::{miette, Context, Result};
 use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
@@ -16,6 +37,7 @@ use crate::state::transcoded::{
     TranscodedAlbumStateLoadError,
 };
 use crate::state::AlbumFileChangesV2;
+use crate::tags::{parse_disc_directory_name, AudioFileTags};
 
 pub type SharedAlbumView<'a> = ArcRwLock<AlbumView<'a>>;
 #[allow(dead_code)]
@@ -32,6 +54,14 @@ pub struct AlbumView<'config> {
 
     /// Album name.
     pub title: String,
+
+    /// If the library builds output paths from a template (see `OutputPathTemplate`),
+    /// the album directory in the aggregated library, relative to its root.
+    /// If `None`, the album directory mirrors the source library layout.
+    ///
+    /// For albums that were removed from the source library, this has to be set
+    /// to the directory saved in the library state.
+    pub templated_output_directory: Option<PathBuf>,
 }
 
 impl<'config> AlbumView<'config> {
@@ -55,7 +85,46 @@ impl<'config> AlbumView<'config> {
             ));
         }
 
-        let album_configuration = AlbumConfiguration::load(album_directory)?;
+        let mut album_configuration =
+            AlbumConfiguration::load(&album_directory)?;
+
+        // Multi-disc albums are always scanned deep enough to include all of their discs.
+        if album_configuration.scan.depth == 0
+            && has_disc_subdirectories(&album_directory)
+        {
+            album_configuration.scan.depth = 1;
+        }
+
+        let templated_output_directory = {
+            let artist_lock = artist.read();
+            let library_lock = artist_lock.read_lock_library();
+            let library_configuration = library_lock.library_configuration;
+
+            let templated_output_directory =
+                match &library_configuration.transcoding.output_template {
+                    Some(output_template) if album_directory.is_dir() => {
+                        render_templated_album_directory(
+                            library_configuration,
+                            output_template,
+                            &album_directory,
+                            &album_configuration,
+                            &artist_lock.name,
+                            &album_title,
+                        )?
+                    }
+                    _ => None,
+                };
+
+            if library_lock
+                .euphony_configuration
+                .aggregated_library
+                .sanitize_file_names
+            {
+                templated_output_directory.map(sanitize_relative_path_for_fat)
+            } else {
+                templated_output_directory
+            }
+        };
 
         Ok(Arc::new_cyclic(|weak| {
             RwLock::new(Self {
@@ -63,6 +132,7 @@ impl<'config> AlbumView<'config> {
                 artist,
                 configuration: album_configuration,
                 title: album_title,
+                templated_output_directory,
             })
         }))
     }
@@ -108,7 +178,30 @@ impl<'config> AlbumView<'config> {
     }
 
     /// Get the mapped album directory - an album path inside the transcoded library.
+    ///
+    /// If `aggregated_library.sanitize_file_names` is enabled, the album title is sanitized
+    /// (templated output directories are sanitized when they are rendered).
     pub fn album_directory_in_transcoded_library(&self) -> PathBuf {
+        if let Some(templated_output_directory) =
+            &self.templated_output_directory
+        {
+            return Path::new(
+                &self.euphony_configuration().aggregated_library.path,
+            )
+            .join(templated_output_directory);
+        }
+
+        if self
+            .euphony_configuration()
+            .aggregated_library
+            .sanitize_file_names
+        {
+            return self
+                .read_lock_artist()
+                .artist_directory_in_transcoded_library()
+                .join(sanitize_file_name_for_fat(&self.title));
+        }
+
         self.read_lock_artist()
             .artist_directory_in_transcoded_library()
             .join(self.title.clone())
@@ -116,11 +209,25 @@ impl<'config> AlbumView<'config> {
 
     /// Scan the album directory and return a list of files
     /// that should be validated against the configured validation rules.
+    /// Ignored files (see `ignored_names_at_any_depth`) are not included.
     #[allow(dead_code)]
     pub fn album_validation_files(&self) -> Result<Vec<PathBuf>> {
         let album_scan = self.scan_album_directory()?;
+        let album_directory = self.album_directory_in_source_library();
+        let library_configuration = self.library_configuration();
 
-        Ok(album_scan.files.into_iter().collect())
+        Ok(album_scan
+            .files
+            .into_iter()
+            .filter(|file_path| {
+                !file_path.strip_prefix(&album_directory).is_ok_and(
+                    |relative_path| {
+                        library_configuration
+                            .is_path_ignored_at_any_depth(relative_path)
+                    },
+                )
+            })
+            .collect())
     }
 
     /// Perform a directory scan of the album directory, respecting the depth configuration
@@ -161,7 +268,8 @@ impl<'config> AlbumView<'config> {
     /// **This is a relatively expensive IO operation as it requires quite a bit of disk access.
     /// Reuse the results as much as possible to maintain good performance.**
     pub fn scan_for_changes(&self) -> Result<AlbumFileChangesV2<'config>> {
-        // TODO Implement caching via internal mutability for this costly scan operation.
+        // TODO Implement caching via internal mutability for this costly scan operation
+        //      (see `CachedValue`, which can be invalidated after external changes).
         let source_album_directory_path =
             self.album_directory_in_source_library();
         let transcoded_album_directory_path =
@@ -174,6 +282,11 @@ impl<'config> AlbumView<'config> {
         let saved_source_album_state =
             match SourceAlbumState::load_from_directory(
                 &source_album_directory_path,
+                self.euphony_configuration().aggregated_library.mode,
+                self.euphony_configuration()
+                    .aggregated_library
+                    .device_profile
+                    .as_deref(),
             ) {
                 Ok(state) => Some(state),
                 Err(error) => match error {
@@ -188,6 +301,13 @@ impl<'config> AlbumView<'config> {
             SourceAlbumState::generate_from_tracked_files(
                 &tracked_source_files,
                 &source_album_directory_path,
+                self.euphony_configuration().file_metadata.change_detection,
+                SourceAlbumState::transcoding_settings_hash(
+                    self.euphony_configuration(),
+                    self.library_configuration(),
+                    &self.configuration,
+                ),
+                saved_source_album_state.as_ref(),
             )?;
 
         let saved_transcoded_album_state =
@@ -251,6 +371,14 @@ pub struct AlbumSourceFileList<'config> {
     /// Data file paths associated with the album.
     /// Paths are relative to the album source directory.
     pub data_files: Vec<PathBuf>,
+
+    /// If the library builds output paths from a template (see `OutputPathTemplate`),
+    /// the output file names (without extensions) rendered for each audio file.
+    /// Audio files whose name couldn't be rendered (e.g. due to a missing title tag)
+    /// keep their source paths.
+    ///
+    /// Keys are relative to the album source directory.
+    pub templated_audio_file_names: HashMap<PathBuf, String>,
 }
 
 impl<'config> AlbumSourceFileList<'config> {
@@ -281,6 +409,14 @@ impl<'config> AlbumSourceFileList<'config> {
                     || miette!("Could not generate relative path."),
                 )?;
 
+            // Files in ignored directories (e.g. `@eaDir`) are never tracked.
+            if locked_album_view
+                .library_configuration()
+                .is_path_ignored_at_any_depth(&file_relative_path)
+            {
+                continue;
+            }
+
             if transcoding_configuration
                 .is_path_audio_file_by_extension(&file_relative_path)?
             {
@@ -294,11 +430,44 @@ impl<'config> AlbumSourceFileList<'config> {
 
         drop(locked_album_view);
 
-        Ok(Self {
+        Ok(Self::from_files(
+            album_view,
+            audio_files,
+            data_files,
+        ))
+    }
+
+    /// Build a file list from already known audio and data files
+    /// (paths relative to the album source directory).
+    pub fn from_files(
+        album_view: SharedAlbumView<'config>,
+        audio_files: Vec<PathBuf>,
+        data_files: Vec<PathBuf>,
+    ) -> Self {
+        let templated_audio_file_names = {
+            let locked_album_view = album_view.read();
+
+            match &locked_album_view
+                .library_configuration()
+                .transcoding
+                .output_template
+            {
+                Some(output_template) => render_templated_audio_file_names(
+                    &locked_album_view,
+                    output_template,
+                    &locked_album_view.album_directory_in_source_library(),
+                    &audio_files,
+                ),
+                None => HashMap::new(),
+            }
+        };
+
+        Self {
             album: album_view,
             audio_files,
             data_files,
-        })
+            templated_audio_file_names,
+        }
     }
 
     /// Generate a HashMap that maps from relative paths in the source album directory
@@ -307,16 +476,57 @@ impl<'config> AlbumSourceFileList<'config> {
     /// On the surface it might make sense that the relative paths would stay the same,
     /// *but that isn't always true* (e.g. extension changes when transcoding, etc.).
     ///
+    /// If the aggregated library is on a case-insensitive filesystem and
+    /// `aggregated_library.case_collision_strategy` is `rename`, output paths that would collide
+    /// (see `find_case_insensitive_output_collisions`) are renamed.
+    ///
     /// *Paths are still relative.*
     pub fn map_source_file_paths_to_transcoded_file_paths_relative(
         &self,
+    ) -> SortedFileMap<PathBuf, PathBuf> {
+        let mut file_map = self
+            .map_source_file_paths_to_transcoded_file_paths_before_renaming();
+
+        let aggregated_library =
+            &self.album_read().euphony_configuration().aggregated_library;
+        if aggregated_library.filesystem == OutputFilesystem::CaseInsensitive
+            && aggregated_library.case_collision_strategy
+                == CaseCollisionStrategy::Rename
+        {
+            rename_case_insensitive_collisions(&mut file_map);
+        }
+
+        file_map
+    }
+
+    /// Find groups of tracked files whose output paths only differ in case
+    /// (and would therefore overwrite each other on a case-insensitive filesystem).
+    /// Output paths are compared before any collision renaming.
+    ///
+    /// Each group contains (sorted) source paths, relative to the source album directory.
+    pub fn find_case_insensitive_output_collisions(&self) -> Vec<Vec<PathBuf>> {
+        let file_map = self
+            .map_source_file_paths_to_transcoded_file_paths_before_renaming();
+
+        find_case_insensitive_collisions(
+            file_map.audio.iter().chain(file_map.data.iter()),
+        )
+    }
+
+    /// Generate a HashMap that maps from relative paths in the source album directory
+    /// to the relative paths in the transcoded album directory, without renaming colliding
+    /// paths (see `map_source_file_paths_to_transcoded_file_paths_relative`).
+    fn map_source_file_paths_to_transcoded_file_paths_before_renaming(
+        &self,
     ) -> SortedFileMap<PathBuf, PathBuf> {
         let album = self.album_read();
-        let transcoded_audio_file_extension = &album
-            .euphony_configuration()
-            .tools
-            .ffmpeg
-            .audio_transcoding_output_extension;
+        let configuration = album.euphony_configuration();
+        let transcoded_audio_file_extension = album
+            .configuration
+            .transcoding
+            .audio_transcoding_output_extension(&configuration.tools.ffmpeg);
+        let library_transcoding_configuration =
+            &album.library_configuration().transcoding;
 
         // Transform audio file extensions and create a map from original to transcoded paths.
         // Paths are *still* relative to the album directory.
@@ -324,12 +534,50 @@ impl<'config> AlbumSourceFileList<'config> {
             HashMap::with_capacity(self.audio_files.len());
 
         for source_audio_file_path in &self.audio_files {
-            let relative_transcoded_audio_file_path = source_audio_file_path
-                .with_extension(transcoded_audio_file_extension);
+            // Audio files are copied as-is when mirroring or if their extension rule says so.
+            let is_copied_as_is = configuration.aggregated_library.mode
+                == AggregationMode::Mirror
+                || library_transcoding_configuration
+                    .is_path_copied_audio_file_by_extension(
+                        source_audio_file_path,
+                    )
+                    // The extension is valid, as the file has been classified as audio.
+                    .unwrap_or(false);
+
+            let templated_file_name = self
+                .templated_audio_file_names
+                .get(source_audio_file_path)
+                .map(|templated_file_name| {
+                    // Appended instead of using `with_extension`, as titles may contain dots.
+                    format!(
+                        "{}.{}",
+                        templated_file_name,
+                        source_audio_file_path
+                            .extension()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                    )
+                });
+
+            let relative_output_path = output_path_with_disc_layout(
+                library_transcoding_configuration.disc_subdirectories,
+                source_audio_file_path,
+                templated_file_name,
+            );
+
+            let relative_transcoded_audio_file_path = if is_copied_as_is {
+                relative_output_path
+            } else {
+                relative_output_path
+                    .with_extension(transcoded_audio_file_extension)
+            };
 
             map_original_to_transcoded_audio.insert(
                 source_audio_file_path.clone(),
-                relative_transcoded_audio_file_path,
+                sanitize_output_path_if_enabled(
+                    configuration,
+                    relative_transcoded_audio_file_path,
+                ),
             );
         }
 
@@ -338,10 +586,17 @@ impl<'config> AlbumSourceFileList<'config> {
             HashMap::with_capacity(self.data_files.len());
 
         for source_data_file_path in &self.data_files {
-            // Neither relative path nor the extension changes, so we just insert two copies.
+            // The extension never changes, but the path may (e.g. when flattening discs).
             map_original_to_transcoded_data.insert(
                 source_data_file_path.clone(),
-                source_data_file_path.clone(),
+                sanitize_output_path_if_enabled(
+                    configuration,
+                    output_path_with_disc_layout(
+                        library_transcoding_configuration.disc_subdirectories,
+                        source_data_file_path,
+                        None,
+                    ),
+                ),
             );
         }
 
@@ -416,3 +671,458 @@ impl<'config> AlbumSourceFileList<'config> {
         self.album.write()
     }
 }
+
+
+/// Make a tag (or directory name) usable in an output path: path separators, characters
+/// that are illegal on common filesystems (`:*?"<>|`) and control characters are replaced
+/// with `_`, as are values consisting only of dots (e.g. `..`). Returns `None` for empty values.
+fn sanitize_output_template_value(value: &str) -> Option<String> {
+    let sanitized: String = value
+        .trim()
+        .chars()
+        .map(|character| {
+            if character.is_control()
+                || UNPORTABLE_FILE_NAME_CHARACTERS.contains(&character)
+            {
+                '_'
+            } else {
+                character
+            }
+        })
+        .collect();
+
+    if sanitized.is_empty() {
+        return None;
+    }
+
+    if sanitized.chars().all(|character| character == '.') {
+        return Some("_".repeat(sanitized.len()));
+    }
+
+    Some(sanitized)
+}
+
+/// Returns `true` if the album directory contains any disc subdirectories
+/// (see `parse_disc_directory_name`).
+fn has_disc_subdirectories(album_directory: &Path) -> bool {
+    let Ok(directory_entries) = fs::read_dir(album_directory) else {
+        return false;
+    };
+
+    directory_entries.flatten().any(|entry| {
+        entry.path().is_dir()
+            && parse_disc_directory_name(&entry.file_name().to_string_lossy())
+                .is_some()
+    })
+}
+
+/// If the relative path points to a file directly inside a disc subdirectory
+/// (e.g. `CD1/01 - Song.flac`), return the disc number and the subdirectory name.
+fn disc_subdirectory_of(relative_path: &Path) -> Option<(u32, &OsStr)> {
+    let mut components = relative_path.components();
+    let (Some(Component::Normal(directory_name)), Some(_), None) = (
+        components.next(),
+        components.next(),
+        components.next(),
+    ) else {
+        return None;
+    };
+
+    parse_disc_directory_name(&directory_name.to_string_lossy())
+        .map(|disc_number| (disc_number, directory_name))
+}
+
+/// Build the relative output path of a file (without changing its extension)
+/// according to the library's `disc_subdirectories` layout. If set, `output_file_name`
+/// (e.g. rendered from an output template) replaces the source file name.
+///
+/// Files of disc subdirectories keep their subdirectory (`Preserve`) or are moved into
+/// the album directory with a `D<disc>` prefix (`Flatten`). Other files either keep
+/// their relative source path or, if `output_file_name` is set, are placed
+/// into the album directory.
+fn output_path_with_disc_layout(
+    layout: DiscSubdirectoryLayout,
+    source_relative_path: &Path,
+    output_file_name: Option<String>,
+) -> PathBuf {
+    let Some((disc_number, disc_directory_name)) =
+        disc_subdirectory_of(source_relative_path)
+    else {
+        return output_file_name
+            .map(PathBuf::from)
+            .unwrap_or_else(|| source_relative_path.to_path_buf());
+    };
+
+    let file_name = output_file_name.unwrap_or_else(|| {
+        source_relative_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    });
+
+    match layout {
+        DiscSubdirectoryLayout::Preserve => {
+            Path::new(disc_directory_name).join(file_name)
+        }
+        DiscSubdirectoryLayout::Flatten => {
+            PathBuf::from(format!("D{disc_number:02} {file_name}"))
+        }
+    }
+}
+
+/// Sanitize a relative output path for FAT32 and exFAT (see `sanitize_relative_path_for_fat`)
+/// if `aggregated_library.sanitize_file_names` is enabled.
+fn sanitize_output_path_if_enabled(
+    configuration: &Configuration,
+    relative_output_path: PathBuf,
+) -> PathBuf {
+    if configuration.aggregated_library.sanitize_file_names {
+        sanitize_relative_path_for_fat(relative_output_path)
+    } else {
+        relative_output_path
+    }
+}
+
+/// The value of an output template placeholder for a file with the given tags.
+/// Missing artist and album tags fall back to the artist and album directory names;
+/// other missing tags have no value.
+fn output_template_value(
+    placeholder: OutputTemplatePlaceholder,
+    tags: &AudioFileTags,
+    artist_name: &str,
+    album_title: &str,
+) -> Option<String> {
+    let value = match placeholder {
+        OutputTemplatePlaceholder::AlbumArtist => tags
+            .album_artist
+            .as_deref()
+            .or(tags.artist.as_deref())
+            .unwrap_or(artist_name)
+            .to_string(),
+        OutputTemplatePlaceholder::Artist => tags
+            .artist
+            .as_deref()
+            .or(tags.album_artist.as_deref())
+            .unwrap_or(artist_name)
+            .to_string(),
+        OutputTemplatePlaceholder::Album => {
+            tags.album.as_deref().unwrap_or(album_title).to_string()
+        }
+        OutputTemplatePlaceholder::Year => tags.year.clone()?,
+        OutputTemplatePlaceholder::Disc => tags.disc_number?.to_string(),
+        OutputTemplatePlaceholder::Track => tags.track_number?.to_string(),
+        OutputTemplatePlaceholder::Title => tags.title.clone()?,
+    };
+
+    sanitize_output_template_value(&value)
+}
+
+/// Render the album directory in the aggregated library (see `AlbumView::templated_output_directory`).
+///
+/// If the library builds output paths from tags (see `AlbumInfoSource::Tags`), the tags
+/// of the album's first audio file (in sorted order) that has any tags are used,
+/// otherwise only the artist and album directory names are available.
+/// Returns `None` if the directory can't be rendered (e.g. because the template contains
+/// `{year}`, but there is no date tag), in which case the source layout is mirrored.
+fn render_templated_album_directory(
+    library_configuration: &LibraryConfiguration,
+    output_template: &OutputPathTemplate,
+    album_directory: &Path,
+    album_configuration: &AlbumConfiguration,
+    artist_name: &str,
+    album_title: &str,
+) -> Result<Option<PathBuf>> {
+    let album_tags = if library_configuration.transcoding.album_info_source
+        == AlbumInfoSource::Tags
+    {
+        read_first_album_tags(
+            library_configuration,
+            album_directory,
+            album_configuration,
+        )?
+    } else {
+        AudioFileTags::default()
+    };
+
+    Ok(output_template
+        .render_album_directory(|placeholder| {
+            output_template_value(
+                placeholder,
+                &album_tags,
+                artist_name,
+                album_title,
+            )
+        })
+        .map(PathBuf::from))
+}
+
+/// Read the tags of the album's first audio file (in sorted order) that has any tags.
+fn read_first_album_tags(
+    library_configuration: &LibraryConfiguration,
+    album_directory: &Path,
+    album_configuration: &AlbumConfiguration,
+) -> Result<AudioFileTags> {
+    let album_scan = DirectoryScan::scan_with_options(
+        album_directory,
+        Some(album_configuration.scan.depth as usize),
+        true,
+    )?;
+
+    let mut audio_files: Vec<PathBuf> = album_scan
+        .files
+        .into_iter()
+        .filter(|file_path| {
+            let is_ignored = file_path.strip_prefix(album_directory).is_ok_and(
+                |relative_path| {
+                    library_configuration
+                        .is_path_ignored_at_any_depth(relative_path)
+                },
+            );
+
+            !is_ignored
+                && library_configuration
+                    .transcoding
+                    .is_path_audio_file_by_extension(file_path)
+                    .unwrap_or(false)
+        })
+        .collect();
+    audio_files.sort_unstable();
+
+    Ok(audio_files
+        .iter()
+        .filter_map(AudioFileTags::read_from_file_cached)
+        .find(|tags| !tags.is_empty())
+        .unwrap_or_default())
+}
+
+/// Render the output file names of the given audio files (relative to `album_directory`)
+/// from their tags or, if the library doesn't use tags (see `AlbumInfoSource`), from their
+/// file names (see `AlbumSourceFileList::templated_audio_file_names`).
+///
+/// Files are processed in sorted order and a file whose name is already taken by another one
+/// (compared case-insensitively) keeps its source path, so the names are the same on every run.
+fn render_templated_audio_file_names(
+    album: &AlbumView,
+    output_template: &OutputPathTemplate,
+    album_directory: &Path,
+    audio_files: &[PathBuf],
+) -> HashMap<PathBuf, String> {
+    let uses_tags = album.library_configuration().transcoding.album_info_source
+        == AlbumInfoSource::Tags;
+    let artist_name = album.read_lock_artist().name.clone();
+
+    let mut sorted_audio_files: Vec<&PathBuf> = audio_files.iter().collect();
+    sorted_audio_files.sort_unstable();
+
+    if uses_tags {
+        AudioFileTags::evict_cached_missing_files(album_directory);
+    }
+
+    let mut taken_file_names: HashSet<String> = HashSet::new();
+    let mut templated_file_names: HashMap<PathBuf, String> = HashMap::new();
+
+    for audio_file in sorted_audio_files {
+        let tags = if uses_tags {
+            let Some(tags) = AudioFileTags::read_from_file_cached(
+                album_directory.join(audio_file),
+            ) else {
+                continue;
+            };
+
+            tags
+        } else {
+            AudioFileTags::from_file_name(audio_file)
+        };
+
+        let Some(file_name) = output_template.render_file_name(|placeholder| {
+            output_template_value(placeholder, &tags, &artist_name, &album.title)
+        }) else {
+            continue;
+        };
+
+        // Files in different disc subdirectories may share a name.
+        let disc_directory_name = disc_subdirectory_of(audio_file)
+            .map(|(_, directory_name)| directory_name.to_string_lossy())
+            .unwrap_or_default();
+
+        if taken_file_names
+            .insert(format!("{disc_directory_name}/{file_name}").to_lowercase())
+        {
+            templated_file_names.insert(audio_file.clone(), file_name);
+        }
+    }
+
+    templated_file_names
+}
+
+
+/// Case-fold a path for comparing paths the way a case-insensitive filesystem does.
+fn case_fold_path(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+/// Group source paths by their case-folded output paths and return the groups
+/// with more than one source path (each group and the list of groups are sorted).
+fn find_case_insensitive_collisions<'a, I>(
+    source_to_output_paths: I,
+) -> Vec<Vec<PathBuf>>
+where
+    I: Iterator<Item = (&'a PathBuf, &'a PathBuf)>,
+{
+    let mut source_paths_by_output_path: HashMap<String, Vec<PathBuf>> =
+        HashMap::new();
+
+    for (source_path, output_path) in source_to_output_paths {
+        source_paths_by_output_path
+            .entry(case_fold_path(output_path))
+            .or_default()
+            .push(source_path.clone());
+    }
+
+    let mut collisions: Vec<Vec<PathBuf>> = source_paths_by_output_path
+        .into_values()
+        .filter(|source_paths| source_paths.len() > 1)
+        .map(|mut source_paths| {
+            source_paths.sort_unstable();
+            source_paths
+        })
+        .collect();
+
+    collisions.sort_unstable();
+    collisions
+}
+
+/// Append ` (<number>)` to the file stem of the path, e.g. `song.mp3` -> `song (2).mp3`.
+fn with_collision_suffix(path: &Path, number: usize) -> PathBuf {
+    let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    let file_name = match path.extension() {
+        Some(extension) => format!(
+            "{file_stem} ({number}).{}",
+            extension.to_string_lossy()
+        ),
+        None => format!("{file_stem} ({number})"),
+    };
+
+    path.with_file_name(file_name)
+}
+
+/// Rename the output paths that would collide on a case-insensitive filesystem.
+///
+/// In each group of colliding files, the first source path (in sorted order) keeps its
+/// output path and the others get a ` (2)`, ` (3)`, ... suffix (skipping numbers whose paths
+/// are already taken), so the renaming is the same on every run.
+fn rename_case_insensitive_collisions(
+    file_map: &mut SortedFileMap<PathBuf, PathBuf>,
+) {
+    let collisions = find_case_insensitive_collisions(
+        file_map.audio.iter().chain(file_map.data.iter()),
+    );
+    if collisions.is_empty() {
+        return;
+    }
+
+    let mut taken_output_paths: HashSet<String> = file_map
+        .audio
+        .values()
+        .chain(file_map.data.values())
+        .map(|output_path| case_fold_path(output_path))
+        .collect();
+
+    for colliding_source_paths in collisions {
+        for source_path in colliding_source_paths.iter().skip(1) {
+            let Some(output_path) = file_map
+                .audio
+                .get_mut(source_path)
+                .or_else(|| file_map.data.get_mut(source_path))
+            else {
+                continue;
+            };
+
+            let renamed_output_path = (2..)
+                .map(|number| with_collision_suffix(output_path, number))
+                .find(|candidate_path| {
+                    !taken_output_paths.contains(&case_fold_path(candidate_path))
+                })
+                .expect("there is always a free collision suffix");
+
+            taken_output_paths.insert(case_fold_path(&renamed_output_path));
+            *output_path = renamed_output_path;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_map(
+        audio: &[(&str, &str)],
+        data: &[(&str, &str)],
+    ) -> SortedFileMap<PathBuf, PathBuf> {
+        let to_map = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|(source, output)| {
+                    (PathBuf::from(source), PathBuf::from(output))
+                })
+                .collect::<HashMap<PathBuf, PathBuf>>()
+        };
+
+        SortedFileMap::new(to_map(audio), to_map(data))
+    }
+
+    #[test]
+    fn finds_case_insensitive_collisions() {
+        let map = file_map(
+            &[
+                ("Song.flac", "Song.mp3"),
+                ("song.flac", "song.mp3"),
+                ("other.flac", "other.mp3"),
+            ],
+            &[("SONG.mp3", "SONG.mp3"), ("cover.jpg", "cover.jpg")],
+        );
+
+        let collisions = find_case_insensitive_collisions(
+            map.audio.iter().chain(map.data.iter()),
+        );
+
+        assert_eq!(
+            collisions,
+            vec![vec![
+                PathBuf::from("SONG.mp3"),
+                PathBuf::from("Song.flac"),
+                PathBuf::from("song.flac"),
+            ]]
+        );
+    }
+
+    #[test]
+    fn renames_collisions_deterministically() {
+        let mut map = file_map(
+            &[
+                ("Song.flac", "Song.mp3"),
+                ("song.flac", "song.mp3"),
+                ("song (2).flac", "song (2).mp3"),
+            ],
+            &[],
+        );
+
+        rename_case_insensitive_collisions(&mut map);
+
+        assert_eq!(
+            map.audio[&PathBuf::from("Song.flac")],
+            PathBuf::from("Song.mp3")
+        );
+        assert_eq!(
+            map.audio[&PathBuf::from("song.flac")],
+            PathBuf::from("song (3).mp3")
+        );
+        assert_eq!(
+            map.audio[&PathBuf::from("song (2).flac")],
+            PathBuf::from("song (2).mp3")
+        );
+    }
+}
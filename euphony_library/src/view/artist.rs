@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use euphony_configuration::sanitize_file_name_for_fat;
 use fs_more::directory::DirectoryScan;
 use miette::{miette, Context, Result};
 use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
@@ -73,8 +74,22 @@ impl<'config> ArtistView<'config> {
     }
 
     /// Get the mapped artist directory - an artist directory path inside the transcoded library.
+    ///
+    /// If `aggregated_library.sanitize_file_names` is enabled, the artist name is sanitized.
     pub fn artist_directory_in_transcoded_library(&self) -> PathBuf {
-        self.read_lock_library()
+        let library = self.read_lock_library();
+
+        if library
+            .euphony_configuration
+            .aggregated_library
+            .sanitize_file_names
+        {
+            return library
+                .root_directory_in_transcoded_library()
+                .join(sanitize_file_name_for_fat(&self.name));
+        }
+
+        library
             .root_directory_in_transcoded_library()
             .join(self.name.clone())
     }
@@ -111,12 +126,16 @@ impl<'config> ArtistView<'config> {
     /// NOTE: In euphony, *"album title" is understood as the album's directory name*. This is because
     /// euphony does not scan the album contents and extract the common album title from the tags in the file,
     /// but instead relies on the directory tree to tell artist names and album titles apart.  
+    ///
+    /// Ignored directories (see `ignored_names_at_any_depth`) are not considered albums.
     pub fn albums(&self) -> Result<HashMap<String, SharedAlbumView<'config>>> {
         let self_arc = self.weak_self.upgrade().ok_or_else(|| {
             miette!("Could not upgrade ArtistView weak reference.")
         })?;
 
         let artist_directory_scan = self.scan_artist_directory()?;
+        let library_configuration =
+            self.read_lock_library().library_configuration;
 
         let mut album_map: HashMap<String, SharedAlbumView<'config>> =
             HashMap::with_capacity(artist_directory_scan.directories.len());
@@ -128,6 +147,12 @@ impl<'config> ArtistView<'config> {
                 .to_string_lossy()
                 .to_string();
 
+            if library_configuration
+                .is_name_ignored_at_any_depth(&album_directory_name)
+            {
+                continue;
+            }
+
             album_map.insert(
                 album_directory_name.clone(),
                 AlbumView::new(self_arc.clone(), album_directory_name, false)?,
@@ -145,39 +170,60 @@ impl<'config> ArtistView<'config> {
     pub fn scan_for_albums_with_changes(
         &self,
     ) -> Result<ChangedAlbumsMap<'config>> {
+        let (changed_albums, _) = self.scan_albums_for_changes()?;
+
+        Ok(changed_albums)
+    }
+
+    /// Scan all albums by this artist for changes. Returns a tuple containing:
+    /// - the changed albums (see `scan_for_albums_with_changes`) and
+    /// - titles of albums that are up to date (i.e. have no changes).
+    pub fn scan_albums_for_changes(
+        &self,
+    ) -> Result<(ChangedAlbumsMap<'config>, Vec<String>)> {
         let all_albums: HashMap<String, SharedAlbumView<'config>> =
             self.albums()?;
 
-        all_albums
-            .into_iter()
-            .filter_map(|(title, album)| {
-                let changes = {
-                    let album_locked = album.read();
-
-                    album_locked.scan_for_changes()
-                };
-
-                let changes = match changes {
-                    Ok(changes) => changes,
-                    Err(error) => return Some(Err(error)),
-                };
-
-                if changes.has_changes() {
-                    Some(Ok((title, (album, changes))))
-                } else {
-                    None
-                }
-            })
-            .collect()
+        let mut changed_albums: ChangedAlbumsMap<'config> = HashMap::new();
+        let mut unchanged_album_titles: Vec<String> = Vec::new();
+
+        for (title, album) in all_albums {
+            let changes = {
+                let album_locked = album.read();
+
+                album_locked.scan_for_changes()?
+            };
+
+            if changes.has_changes() {
+                changed_albums.insert(title, (album, changes));
+            } else {
+                unchanged_album_titles.push(title);
+            }
+        }
+
+        Ok((changed_albums, unchanged_album_titles))
     }
 
     /// Scan the artist source directory and return a list of files
     /// that should be validated against the configured validation rules.
+    /// Ignored files (see `ignored_names_at_any_depth`) are not included.
     #[allow(dead_code)]
     pub fn artist_directory_validation_files(&self) -> Result<Vec<PathBuf>> {
         let artist_directory_scan = self.scan_artist_directory()?;
+        let library_configuration =
+            self.read_lock_library().library_configuration;
 
-        Ok(artist_directory_scan.files.into_iter().collect())
+        Ok(artist_directory_scan
+            .files
+            .into_iter()
+            .filter(|file_path| {
+                !file_path.file_name().is_some_and(|file_name| {
+                    library_configuration.is_name_ignored_at_any_depth(
+                        file_name.to_string_lossy(),
+                    )
+                })
+            })
+            .collect())
     }
 
     /*
@@ -125,13 +125,11 @@ impl<'config> LibraryView<'config> {
 
             // If the current directory matches one that should be ignored in the library root,
             // we simply skip it.
-            if let Some(ignored_directory_list) = &self
+            if self
                 .library_configuration
-                .ignored_directories_in_base_directory
+                .is_directory_ignored_in_base_directory(&artist_directory_name)
             {
-                if ignored_directory_list.contains(&artist_directory_name) {
-                    continue;
-                }
+                continue;
             }
 
             artist_map.insert(
@@ -145,11 +143,22 @@ impl<'config> LibraryView<'config> {
 
     /// Scan the root directory of the library and return a list of files at the root
     /// that should be validated against the configured validation rules.
+    /// Ignored files (see `ignored_names_at_any_depth`) are not included.
     #[allow(dead_code)]
     pub fn library_root_validation_files(&self) -> Result<Vec<PathBuf>> {
         let library_directory_scan = self.scan_root_directory()?;
 
-        Ok(library_directory_scan.files.into_iter().collect())
+        Ok(library_directory_scan
+            .files
+            .into_iter()
+            .filter(|file_path| {
+                !file_path.file_name().is_some_and(|file_name| {
+                    self.library_configuration.is_name_ignored_at_any_depth(
+                        file_name.to_string_lossy(),
+                    )
+                })
+            })
+            .collect())
     }
 
     /// Perform a zero-depth directory scan of the root library directory.
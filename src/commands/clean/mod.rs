@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crossterm::style::Stylize;
+use euphony_configuration::Configuration;
+use euphony_library::state::transcoded::TranscodedAlbumState;
+use euphony_library::view::{AlbumSourceFileList, LibraryView};
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use crate::commands::transcode::checksums::get_checksum_manifest_path;
+use crate::commands::transcode::playlist::MASTER_PLAYLIST_FILE_NAME;
+use crate::commands::transcode::scan_fingerprint::SCAN_FINGERPRINTS_FILE_NAME;
+use crate::console::formatting::format_byte_size;
+use crate::console::frontends::SimpleTerminal;
+use crate::console::LogBackend;
+
+
+/// What kind of file a stale file in the aggregated library is.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StaleFileKind {
+    /// A transcoded audio file (detected by its output extension).
+    TranscodedAudio,
+
+    /// A copied data file (e.g. cover art).
+    Data,
+
+    /// One of euphony's own files (e.g. `.album.transcode-state.euphony`)
+    /// in a directory that is no longer part of the aggregated library.
+    EuphonyState,
+}
+
+impl StaleFileKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StaleFileKind::TranscodedAudio => "audio",
+            StaleFileKind::Data => "data",
+            StaleFileKind::EuphonyState => "state",
+        }
+    }
+}
+
+/// A file in the aggregated library that does not correspond
+/// to any current source file.
+pub struct StaleFile {
+    pub path: PathBuf,
+
+    pub kind: StaleFileKind,
+
+    pub size_bytes: u64,
+}
+
+/// Files and directories in the aggregated library that no longer correspond
+/// to any source file (see `find_stale_files`).
+#[derive(Default)]
+pub struct StaleFiles {
+    /// Stale files, sorted by path.
+    pub files: Vec<StaleFile>,
+
+    /// Directories that contain no expected files (not even in their subdirectories).
+    /// Sorted bottom-up, i.e. each directory comes after all of its subdirectories.
+    pub directories: Vec<PathBuf>,
+}
+
+impl StaleFiles {
+    pub fn total_size_bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.size_bytes).sum()
+    }
+
+    pub fn num_files_of_kind(&self, kind: StaleFileKind) -> usize {
+        self.files.iter().filter(|file| file.kind == kind).count()
+    }
+}
+
+
+/// The files euphony expects to find in the aggregated library.
+struct ExpectedFiles {
+    /// Absolute paths of all expected files.
+    file_paths: HashSet<PathBuf>,
+
+    /// Extensions of the transcoded audio files (lowercase),
+    /// used to tell stale audio files apart from stale data files.
+    audio_output_extensions: HashSet<String>,
+}
+
+/// Reconstruct the paths of all the files that the current source libraries
+/// map to in the aggregated library: transcoded audio files, copied data files and
+/// euphony's own files (per-album states and checksum manifests, the master playlist
+/// and the scan fingerprints).
+///
+/// Directories in `ignored_directories_in_base_directory` are skipped.
+fn collect_expected_files(
+    configuration: &Configuration,
+) -> Result<ExpectedFiles> {
+    let aggregated_library_path =
+        PathBuf::from(&configuration.aggregated_library.path);
+
+    let mut file_paths: HashSet<PathBuf> = HashSet::from([
+        aggregated_library_path.join(MASTER_PLAYLIST_FILE_NAME),
+        aggregated_library_path.join(SCAN_FINGERPRINTS_FILE_NAME),
+    ]);
+    let mut audio_output_extensions: HashSet<String> =
+        HashSet::from([configuration
+            .tools
+            .ffmpeg
+            .audio_transcoding_output_extension
+            .clone()]);
+
+    let write_checksum_manifest =
+        configuration.aggregated_library.write_checksum_manifest;
+
+    for library in configuration.libraries.values() {
+        let library_view =
+            LibraryView::from_library_configuration(configuration, library)?;
+        let library_view_locked = library_view.read();
+
+
+        for (artist_name, artist_view) in library_view_locked.artists()? {
+            if library.is_directory_ignored_in_base_directory(&artist_name) {
+                continue;
+            }
+
+            for (album_title, album_view) in artist_view.read().albums()? {
+                let album_directory_in_transcoded_library =
+                    album_view.read().album_directory_in_transcoded_library();
+
+                let source_file_list =
+                    AlbumSourceFileList::from_album_view(album_view)
+                        .wrap_err_with(|| {
+                            miette!(
+                        "Failed to scan album \"{} - {}\" in library {}.",
+                        artist_name,
+                        album_title,
+                        library.name
+                    )
+                        })?;
+
+                let transcoded_file_paths = source_file_list
+                    .map_source_file_paths_to_transcoded_file_paths_absolute();
+
+                for transcoded_audio_path in
+                    transcoded_file_paths.audio.into_values()
+                {
+                    if let Some(extension) = transcoded_audio_path.extension() {
+                        audio_output_extensions.insert(
+                            extension.to_string_lossy().to_ascii_lowercase(),
+                        );
+                    }
+
+                    file_paths.insert(transcoded_audio_path);
+                }
+                file_paths.extend(transcoded_file_paths.data.into_values());
+
+                file_paths.insert(
+                    TranscodedAlbumState::get_state_file_path_for_directory(
+                        &album_directory_in_transcoded_library,
+                    ),
+                );
+                if write_checksum_manifest {
+                    file_paths.insert(get_checksum_manifest_path(
+                        &album_directory_in_transcoded_library,
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(ExpectedFiles {
+        file_paths,
+        audio_output_extensions,
+    })
+}
+
+/// Classify a stale file by its name and extension.
+fn stale_file_kind(
+    file_path: &Path,
+    expected_files: &ExpectedFiles,
+) -> StaleFileKind {
+    let is_euphony_file = file_path.file_name().is_some_and(|file_name| {
+        let file_name = file_name.to_string_lossy();
+        file_name.starts_with('.') && file_name.ends_with(".euphony")
+    });
+    if is_euphony_file {
+        return StaleFileKind::EuphonyState;
+    }
+
+    let is_audio_file = file_path.extension().is_some_and(|extension| {
+        expected_files
+            .audio_output_extensions
+            .contains(&extension.to_string_lossy().to_ascii_lowercase())
+    });
+    if is_audio_file {
+        StaleFileKind::TranscodedAudio
+    } else {
+        StaleFileKind::Data
+    }
+}
+
+/// Recursively walk `directory_path` and collect all the files that aren't expected
+/// into `stale_files`. Symbolic links are never followed (but are reported if stale).
+///
+/// Returns whether the directory contains any expected files (even in its subdirectories).
+fn walk_directory_for_stale_files(
+    directory_path: &Path,
+    expected_files: &ExpectedFiles,
+    stale_files: &mut StaleFiles,
+) -> Result<bool> {
+    let directory_entries = fs::read_dir(directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!("Could not read directory {:?}.", directory_path)
+        })?;
+
+    let mut contains_expected_files = false;
+
+    for entry in directory_entries {
+        let entry = entry.into_diagnostic().wrap_err_with(|| {
+            miette!("Could not read an entry of {:?}.", directory_path)
+        })?;
+        let entry_path = entry.path();
+        let entry_metadata = entry.metadata().into_diagnostic()?;
+
+        if entry_metadata.is_dir() {
+            if walk_directory_for_stale_files(
+                &entry_path,
+                expected_files,
+                stale_files,
+            )? {
+                contains_expected_files = true;
+            } else {
+                stale_files.directories.push(entry_path);
+            }
+        } else if expected_files.file_paths.contains(&entry_path) {
+            contains_expected_files = true;
+        } else {
+            stale_files.files.push(StaleFile {
+                kind: stale_file_kind(&entry_path, expected_files),
+                path: entry_path,
+                size_bytes: entry_metadata.len(),
+            });
+        }
+    }
+
+    Ok(contains_expected_files)
+}
+
+/// Find all the files and directories in the aggregated library that don't correspond
+/// to any current source file (e.g. transcodes of albums that have since been moved or deleted
+/// while euphony wasn't tracking them).
+///
+/// The aggregated library directory itself is never considered stale.
+pub fn find_stale_files(configuration: &Configuration) -> Result<StaleFiles> {
+    let aggregated_library_path =
+        PathBuf::from(&configuration.aggregated_library.path);
+
+    let mut stale_files = StaleFiles::default();
+    if !aggregated_library_path.is_dir() {
+        return Ok(stale_files);
+    }
+
+    let expected_files = collect_expected_files(configuration)?;
+
+    walk_directory_for_stale_files(
+        &aggregated_library_path,
+        &expected_files,
+        &mut stale_files,
+    )?;
+
+    stale_files
+        .files
+        .sort_unstable_by(|first, second| first.path.cmp(&second.path));
+
+    Ok(stale_files)
+}
+
+/// Remove all the stale files, then all the stale directories (bottom-up).
+fn remove_stale_files(stale_files: &StaleFiles) -> Result<()> {
+    for stale_file in &stale_files.files {
+        fs::remove_file(&stale_file.path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not remove stale file {:?}.",
+                    stale_file.path
+                )
+            })?;
+    }
+
+    for stale_directory in &stale_files.directories {
+        fs::remove_dir(stale_directory)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not remove stale directory {:?}.",
+                    stale_directory
+                )
+            })?;
+    }
+
+    Ok(())
+}
+
+
+/// Associated with the `clean` command.
+///
+/// Walks the aggregated library and lists the files and directories that don't
+/// correspond to any current source file (see `find_stale_files`), along with
+/// how much space they take up. If `delete` is set, they are removed as well.
+pub fn cmd_clean(
+    configuration: &Configuration,
+    terminal: &mut SimpleTerminal,
+    delete: bool,
+) -> Result<()> {
+    let stale_files = find_stale_files(configuration)?;
+
+    if stale_files.files.is_empty() && stale_files.directories.is_empty() {
+        terminal.log_println(format!(
+            "{}",
+            "The aggregated library contains no stale files.".green()
+        ));
+        return Ok(());
+    }
+
+    for stale_file in &stale_files.files {
+        terminal.log_println(format!(
+            "  [{}] {} ({})",
+            stale_file.kind.name(),
+            stale_file.path.to_string_lossy(),
+            format_byte_size(stale_file.size_bytes),
+        ));
+    }
+
+    // Only list the topmost stale directories (their contents are stale as well).
+    let stale_directory_set: HashSet<&PathBuf> =
+        stale_files.directories.iter().collect();
+    let mut topmost_stale_directories: Vec<&PathBuf> = stale_files
+        .directories
+        .iter()
+        .filter(|directory| {
+            directory.parent().map_or(true, |parent| {
+                !stale_directory_set.contains(&parent.to_path_buf())
+            })
+        })
+        .collect();
+    topmost_stale_directories.sort_unstable();
+
+    for stale_directory in topmost_stale_directories {
+        terminal.log_println(format!(
+            "  [directory] {}",
+            stale_directory.to_string_lossy()
+        ));
+    }
+
+    let total_size = format_byte_size(stale_files.total_size_bytes());
+
+    terminal.log_newline();
+    terminal.log_println(format!(
+        "Found {} stale files ({} transcoded audio, {} data, {} state files) \
+        and {} stale directories, taking up {}.",
+        stale_files.files.len().to_string().bold(),
+        stale_files.num_files_of_kind(StaleFileKind::TranscodedAudio),
+        stale_files.num_files_of_kind(StaleFileKind::Data),
+        stale_files.num_files_of_kind(StaleFileKind::EuphonyState),
+        stale_files.directories.len().to_string().bold(),
+        total_size.clone().bold(),
+    ));
+
+    if !delete {
+        terminal.log_println(format!(
+            "Run {} to remove them and free up {}.",
+            "euphony clean --delete".bold(),
+            total_size,
+        ));
+        return Ok(());
+    }
+
+    remove_stale_files(&stale_files)?;
+
+    terminal.log_println(format!(
+        "{}",
+        format!(
+            "Removed all stale files, freeing up {}.",
+            total_size
+        )
+        .green()
+    ));
+
+    Ok(())
+}
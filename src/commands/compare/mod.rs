@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+
+use crossterm::style::Stylize;
+use euphony_configuration::library::LibraryConfiguration;
+use euphony_configuration::Configuration;
+use euphony_library::view::LibraryView;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::Serialize;
+
+use crate::console::frontends::SimpleTerminal;
+use crate::console::LogBackend;
+
+
+/// Normalizes an artist name or album title for comparison between libraries:
+/// the name is lowercased, trimmed and any runs of whitespace are collapsed into a single space
+/// (so e.g. `"The  Beatles"` and `"the beatles"` are considered the same artist).
+pub fn normalize_name<S: AsRef<str>>(name: S) -> String {
+    name.as_ref()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+
+/// A single album (as named in its library) found in one library, but not the other.
+#[derive(Serialize, Clone, Debug)]
+pub struct ComparedAlbum {
+    pub artist: String,
+    pub album: String,
+}
+
+/// Result of comparing two libraries by their (normalized) artist and album names.
+#[derive(Serialize, Clone, Debug)]
+pub struct LibraryComparison {
+    pub library_a: String,
+    pub library_b: String,
+
+    /// Albums present in library A, but not in library B.
+    pub missing_from_b: Vec<ComparedAlbum>,
+
+    /// Albums present in library B, but not in library A.
+    pub missing_from_a: Vec<ComparedAlbum>,
+}
+
+
+/// Find the library by its key in the configuration or, if there is no such key,
+/// by its name (case-insensitive).
+fn find_library<'config>(
+    config: &'config Configuration,
+    key_or_name: &str,
+) -> Result<&'config LibraryConfiguration> {
+    if let Some(library) = config.libraries.get(key_or_name) {
+        return Ok(library);
+    }
+
+    config
+        .libraries
+        .values()
+        .find(|library| library.name.eq_ignore_ascii_case(key_or_name))
+        .ok_or_else(|| {
+            miette!(
+                "No such library: \"{}\" (expected a library key or name, \
+                see the list-libraries command).",
+                key_or_name
+            )
+        })
+}
+
+/// Scan the library and return all its albums, keyed by their normalized
+/// artist and album names (see `normalize_name`).
+///
+/// Directories in `ignored_directories_in_base_directory` are skipped.
+fn scan_library_albums(
+    config: &Configuration,
+    library_config: &LibraryConfiguration,
+) -> Result<BTreeMap<(String, String), ComparedAlbum>> {
+    let library_view =
+        LibraryView::from_library_configuration(config, library_config)?;
+    let library_view_locked = library_view.read();
+
+
+    let mut albums: BTreeMap<(String, String), ComparedAlbum> = BTreeMap::new();
+
+    for (artist_name, artist_view) in library_view_locked.artists()? {
+        if library_config.is_directory_ignored_in_base_directory(&artist_name) {
+            continue;
+        }
+
+        for album_title in artist_view.read().albums()?.into_keys() {
+            albums.insert(
+                (
+                    normalize_name(&artist_name),
+                    normalize_name(&album_title),
+                ),
+                ComparedAlbum {
+                    artist: artist_name.clone(),
+                    album: album_title,
+                },
+            );
+        }
+    }
+
+    Ok(albums)
+}
+
+/// Scan both libraries and find the albums that are only present in one of them.
+pub fn compare_libraries(
+    config: &Configuration,
+    library_a: &LibraryConfiguration,
+    library_b: &LibraryConfiguration,
+) -> Result<LibraryComparison> {
+    let albums_a =
+        scan_library_albums(config, library_a).wrap_err_with(|| {
+            miette!("Failed to scan library \"{}\".", library_a.name)
+        })?;
+    let albums_b =
+        scan_library_albums(config, library_b).wrap_err_with(|| {
+            miette!("Failed to scan library \"{}\".", library_b.name)
+        })?;
+
+    let missing_from_b = albums_a
+        .iter()
+        .filter(|(key, _)| !albums_b.contains_key(key))
+        .map(|(_, album)| album.clone())
+        .collect();
+    let missing_from_a = albums_b
+        .iter()
+        .filter(|(key, _)| !albums_a.contains_key(key))
+        .map(|(_, album)| album.clone())
+        .collect();
+
+    Ok(LibraryComparison {
+        library_a: library_a.name.clone(),
+        library_b: library_b.name.clone(),
+        missing_from_b,
+        missing_from_a,
+    })
+}
+
+
+/// Print one direction of the comparison (albums in `present_in`, but not in `missing_from`).
+fn terminal_print_missing_albums(
+    terminal: &SimpleTerminal,
+    present_in: &str,
+    missing_from: &str,
+    albums: &[ComparedAlbum],
+) {
+    terminal.log_println(format!(
+        "{} albums in {} are missing from {}:",
+        albums.len().to_string().bold(),
+        present_in.bold(),
+        missing_from.bold(),
+    ));
+
+    for album in albums {
+        terminal.log_println(format!("  {} - {}", album.artist, album.album));
+    }
+}
+
+/// Associated with the `compare-libraries` command.
+///
+/// Scans both libraries (given by their keys or names) and prints the albums
+/// that are present in one, but not the other. Artist and album names are compared
+/// after normalization (see `normalize_name`).
+///
+/// If `output_json` is set, the comparison is printed as JSON instead.
+pub fn cmd_compare_libraries(
+    config: &Configuration,
+    terminal: &mut SimpleTerminal,
+    library_a_key_or_name: &str,
+    library_b_key_or_name: &str,
+    output_json: bool,
+) -> Result<()> {
+    let library_a = find_library(config, library_a_key_or_name)?;
+    let library_b = find_library(config, library_b_key_or_name)?;
+
+    let comparison = compare_libraries(config, library_a, library_b)?;
+
+    if output_json {
+        terminal.log_println(
+            serde_json::to_string_pretty(&comparison)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!("Failed to serialize library comparison.")
+                })?,
+        );
+
+        return Ok(());
+    }
+
+    terminal_print_missing_albums(
+        terminal,
+        &comparison.library_a,
+        &comparison.library_b,
+        &comparison.missing_from_b,
+    );
+    terminal.log_newline();
+    terminal_print_missing_albums(
+        terminal,
+        &comparison.library_b,
+        &comparison.library_a,
+        &comparison.missing_from_a,
+    );
+
+    Ok(())
+}
@@ -1,8 +1,13 @@
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use crossterm::style::Stylize;
+use euphony_configuration::library::LibraryConfiguration;
 use euphony_configuration::Configuration;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::Serialize;
 
+use super::library_stats::{compute_library_stats, LibraryStats};
 use crate::console::frontends::SimpleTerminal;
 use crate::console::LogBackend;
 
@@ -25,10 +30,42 @@ fn terminal_print_group_header<S: AsRef<str>>(
     ));
 }
 
+/// A library as printed by `list-libraries --format json`.
+#[derive(Serialize)]
+struct ListedLibrary<'config> {
+    #[serde(flatten)]
+    library: &'config LibraryConfiguration,
+
+    /// Only present with `--with-stats`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<LibraryStats>,
+
+    /// Only present with `--with-stats`, if the stats could not be collected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats_error: Option<String>,
+}
+
 /// Associated with the `show-config` command.
 ///
 /// Prints the entire configuration.
-pub fn cmd_show_config(config: &Configuration, terminal: &mut SimpleTerminal) {
+/// If `output_json` is set, the configuration is printed as JSON instead.
+pub fn cmd_show_config(
+    config: &Configuration,
+    terminal: &mut SimpleTerminal,
+    output_json: bool,
+) -> Result<()> {
+    if output_json {
+        terminal.log_println(
+            serde_json::to_string_pretty(config)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!("Failed to serialize configuration.")
+                })?,
+        );
+
+        return Ok(());
+    }
+
     terminal.log_println(format!(
         "Configuration file: {}",
         config.configuration_file_path.to_string_lossy(),
@@ -55,6 +92,37 @@ pub fn cmd_show_config(config: &Configuration, terminal: &mut SimpleTerminal) {
         "    default_log_output_path = {:?}",
         config.logging.default_log_output_path
     ));
+    terminal.log_println(format!(
+        "    log_file_timestamp_format = {:?}",
+        config.logging.log_file_timestamp_format
+    ));
+    terminal.log_println(format!(
+        "    append_to_existing_log_file = {}",
+        config.logging.append_to_existing_log_file
+    ));
+    terminal.log_newline();
+
+
+    // UI
+    terminal_print_group_header(terminal, "ui");
+    terminal.log_println(format!(
+        "    notify_on_completion = {}",
+        config.ui.notify_on_completion,
+    ));
+    terminal.log_println(format!(" => {}", "transcoding".bold()));
+    terminal.log_println(format!(
+        "    show_logs_tab_on_exit = {}",
+        config.ui.transcoding.show_logs_tab_on_exit,
+    ));
+    terminal.log_println(format!(
+        "    file_queue_width_percent = {}",
+        config.ui.transcoding.file_queue_width_percent,
+    ));
+    terminal.log_println(format!(
+        "    header_view_width_percent = {}",
+        config.ui.transcoding.header_view_width_percent,
+    ));
+    terminal.log_newline();
 
 
     // Validation (basics)
@@ -63,6 +131,40 @@ pub fn cmd_show_config(config: &Configuration, terminal: &mut SimpleTerminal) {
         "    extensions_considered_audio_files = {:?}",
         config.validation.extensions_considered_audio_files,
     ));
+    terminal.log_println(format!(
+        "    maximum_album_file_depth = {}",
+        config.validation.maximum_album_file_depth,
+    ));
+    terminal.log_println(format!(
+        "    maximum_album_audio_files = {}",
+        config.validation.maximum_album_audio_files,
+    ));
+    terminal.log_println(format!(
+        "    check_case_insensitive_collisions = {}",
+        config.validation.check_case_insensitive_collisions,
+    ));
+    terminal.log_println(format!(
+        "    check_portable_file_names = {}",
+        config.validation.check_portable_file_names,
+    ));
+    terminal.log_println(format!(
+        "    quarantine_directory = {:?}",
+        config.validation.quarantine_directory,
+    ));
+    terminal.log_newline();
+
+
+    // File metadata
+    terminal_print_group_header(terminal, "file_metadata");
+    terminal.log_println(format!(
+        "    change_detection = {}",
+        config.file_metadata.change_detection.name(),
+    ));
+    terminal.log_println(format!(
+        "    meta_file_name = {}",
+        config.file_metadata.meta_file_name,
+    ));
+    terminal.log_newline();
 
 
     // Tools
@@ -76,6 +178,10 @@ pub fn cmd_show_config(config: &Configuration, terminal: &mut SimpleTerminal) {
         "    audio_transcoding_args = {:?}",
         config.tools.ffmpeg.audio_transcoding_args,
     ));
+    terminal.log_println(format!(
+        "    audio_transcoding_args_per_extension = {:?}",
+        config.tools.ffmpeg.audio_transcoding_args_per_extension,
+    ));
     terminal.log_println(format!(
         "    audio_transcoding_output_extension = {:?}",
         config.tools.ffmpeg.audio_transcoding_output_extension,
@@ -115,6 +221,10 @@ pub fn cmd_show_config(config: &Configuration, terminal: &mut SimpleTerminal) {
                 .as_ref()
                 .unwrap_or(&Vec::new())
         ));
+        terminal.log_println(format!(
+            "    ignored_names_at_any_depth = {:?}",
+            library.ignored_names_at_any_depth
+        ));
 
         // `validation` sub-table
         terminal.log_println(format!("     => {}", "validation".italic()));
@@ -133,6 +243,10 @@ pub fn cmd_show_config(config: &Configuration, terminal: &mut SimpleTerminal) {
 
         // `transcoding` sub-table
         terminal.log_println(format!("     => {}", "transcoding".italic()));
+        terminal.log_println(format!(
+            "        enabled = {}",
+            library.transcoding.enabled,
+        ));
         terminal.log_println(format!(
             "        audio_file_extensions = {:?}",
             library.transcoding.audio_file_extensions,
@@ -141,6 +255,26 @@ pub fn cmd_show_config(config: &Configuration, terminal: &mut SimpleTerminal) {
             "        other_file_extensions = {:?}",
             library.transcoding.other_file_extensions,
         ));
+        terminal.log_println(format!(
+            "        album_info_source = \"{}\"",
+            library.transcoding.album_info_source.name(),
+        ));
+        terminal.log_println(format!(
+            "        output_template = {:?}",
+            library
+                .transcoding
+                .output_template
+                .as_ref()
+                .map(|template| template.as_str()),
+        ));
+        terminal.log_println(format!(
+            "        replaygain = {}",
+            library.transcoding.replaygain,
+        ));
+        terminal.log_println(format!(
+            "        disc_subdirectories = \"{}\"",
+            library.transcoding.disc_subdirectories.name(),
+        ));
 
         terminal.log_newline();
     }
@@ -164,15 +298,167 @@ pub fn cmd_show_config(config: &Configuration, terminal: &mut SimpleTerminal) {
         "  failure_delay_seconds = {}",
         config.aggregated_library.failure_delay_seconds,
     ));
+    terminal.log_println(format!(
+        "  retryable_error_categories = [{}]",
+        config
+            .aggregated_library
+            .retryable_error_categories
+            .iter()
+            .map(|category| format!("\"{}\"", category.name()))
+            .collect::<Vec<String>>()
+            .join(", "),
+    ));
+    terminal.log_println(format!(
+        "  preserve_permissions = {}",
+        config.aggregated_library.preserve_permissions,
+    ));
+    terminal.log_println(format!(
+        "  permissions_umask = {:#05o}",
+        config.aggregated_library.permissions_umask,
+    ));
+    terminal.log_println(format!(
+        "  skip_unchanged_data = {}",
+        config.aggregated_library.skip_unchanged_data,
+    ));
+    terminal.log_println(format!(
+        "  copy_buffer_bytes = {}",
+        config.aggregated_library.copy_buffer_bytes,
+    ));
+    terminal.log_println(format!(
+        "  max_reprocess_percentage = {:?}",
+        config.aggregated_library.max_reprocess_percentage,
+    ));
+    terminal.log_println(format!(
+        "  generate_master_playlist = {}",
+        config.aggregated_library.generate_master_playlist,
+    ));
+    terminal.log_println(format!(
+        "  filesystem = \"{}\"",
+        config.aggregated_library.filesystem.name(),
+    ));
+    terminal.log_println(format!(
+        "  case_collision_strategy = \"{}\"",
+        config.aggregated_library.case_collision_strategy.name(),
+    ));
+    terminal.log_println(format!(
+        "  sanitize_file_names = {}",
+        config.aggregated_library.sanitize_file_names,
+    ));
+    terminal.log_println(format!(
+        "  write_checksum_manifest = {}",
+        config.aggregated_library.write_checksum_manifest,
+    ));
+    terminal.log_println(format!(
+        "  size_budget_mib = {:?}",
+        config.aggregated_library.size_budget_mib,
+    ));
+    terminal.log_println(format!(
+        "  free_space_margin_mib = {}",
+        config.aggregated_library.free_space_margin_mib,
+    ));
+    terminal.log_println(format!(
+        "  output_template = {:?}",
+        config
+            .aggregated_library
+            .output_template
+            .as_ref()
+            .map(|template| template.as_str()),
+    ));
+
+
+    // Device profiles
+    if config.device_profiles.is_empty() {
+        return Ok(());
+    }
+
+    terminal.log_newline();
+    terminal_print_group_header(terminal, "device_profiles");
+
+    for (profile_name, profile) in &config.device_profiles {
+        terminal.log_println(format!(" => {}", profile_name.as_str().bold()));
+        terminal.log_println(format!("    path = \"{}\"", profile.path));
+        terminal.log_println(format!(
+            "    audio_transcoding_args = {:?}",
+            profile.audio_transcoding_args,
+        ));
+        terminal.log_println(format!(
+            "    audio_transcoding_output_extension = {:?}",
+            profile.audio_transcoding_output_extension,
+        ));
+        terminal.log_println(format!(
+            "    filesystem = {:?}",
+            profile.filesystem.map(|filesystem| filesystem.name()),
+        ));
+        terminal.log_println(format!(
+            "    case_collision_strategy = {:?}",
+            profile
+                .case_collision_strategy
+                .map(|strategy| strategy.name()),
+        ));
+        terminal.log_println(format!(
+            "    sanitize_file_names = {:?}",
+            profile.sanitize_file_names,
+        ));
+        terminal.log_println(format!(
+            "    generate_master_playlist = {:?}",
+            profile.generate_master_playlist,
+        ));
+        terminal.log_println(format!(
+            "    size_budget_mib = {:?}",
+            profile.size_budget_mib,
+        ));
+    }
+
+    Ok(())
 }
 
 /// Associated with the `list-libraries` command.
 ///
 /// Prints the registered music libraries from the current configuration.
+/// If `with_stats` is enabled, the number of albums and the most recently modified album
+/// of each library are printed as well (see `library_stats::compute_library_stats`).
+///
+/// If `output_json` is set, the libraries are printed as JSON (keyed by their keys) instead.
 pub fn cmd_list_libraries(
     config: &Configuration,
     terminal: &mut SimpleTerminal,
-) {
+    with_stats: bool,
+    output_json: bool,
+) -> Result<()> {
+    if output_json {
+        let listed_libraries: BTreeMap<&String, ListedLibrary> = config
+            .libraries
+            .iter()
+            .map(|(library_key, library)| {
+                let (stats, stats_error) = if with_stats {
+                    match compute_library_stats(config, library) {
+                        Ok(stats) => (Some(stats), None),
+                        Err(error) => (None, Some(error.to_string())),
+                    }
+                } else {
+                    (None, None)
+                };
+
+                (
+                    library_key,
+                    ListedLibrary {
+                        library,
+                        stats,
+                        stats_error,
+                    },
+                )
+            })
+            .collect();
+
+        terminal.log_println(
+            serde_json::to_string_pretty(&listed_libraries)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("Failed to serialize libraries."))?,
+        );
+
+        return Ok(());
+    }
+
     terminal.log_println(format!(
         "Configuration file: {}",
         config.configuration_file_path.to_string_lossy(),
@@ -192,6 +478,33 @@ pub fn cmd_list_libraries(
         ));
 
         terminal.log_println(format!("    path = \"{}\"", library.path,));
+
+        if with_stats {
+            match compute_library_stats(config, library) {
+                Ok(stats) => {
+                    terminal.log_println(format!(
+                        "    albums = {}",
+                        stats.num_albums
+                    ));
+
+                    if let Some(album) = stats.last_modified_album {
+                        terminal.log_println(format!(
+                            "    last modified = {} ({} - {})",
+                            album.modified_at.format("%Y-%m-%d %H:%M"),
+                            album.artist_name,
+                            album.album_title,
+                        ));
+                    }
+                }
+                Err(error) => {
+                    terminal.log_println(format!(
+                        "    {} {}",
+                        "Could not collect library stats:".yellow(),
+                        error
+                    ));
+                }
+            }
+        }
         terminal.log_println(format!(
             "    ignored_directories_in_base_directory = {:?}",
             library
@@ -199,6 +512,10 @@ pub fn cmd_list_libraries(
                 .as_ref()
                 .unwrap_or(&Vec::new())
         ));
+        terminal.log_println(format!(
+            "    ignored_names_at_any_depth = {:?}",
+            library.ignored_names_at_any_depth
+        ));
 
         // `validation` sub-table
         terminal.log_println(format!("     => {}", "validation".italic()));
@@ -217,6 +534,10 @@ pub fn cmd_list_libraries(
 
         // `transcoding` sub-table
         terminal.log_println(format!("     => {}", "transcoding".italic()));
+        terminal.log_println(format!(
+            "        enabled = {}",
+            library.transcoding.enabled,
+        ));
         terminal.log_println(format!(
             "        audio_file_extensions = {:?}",
             library.transcoding.audio_file_extensions,
@@ -225,7 +546,29 @@ pub fn cmd_list_libraries(
             "        other_file_extensions = {:?}",
             library.transcoding.other_file_extensions,
         ));
+        terminal.log_println(format!(
+            "        album_info_source = \"{}\"",
+            library.transcoding.album_info_source.name(),
+        ));
+        terminal.log_println(format!(
+            "        output_template = {:?}",
+            library
+                .transcoding
+                .output_template
+                .as_ref()
+                .map(|template| template.as_str()),
+        ));
+        terminal.log_println(format!(
+            "        replaygain = {}",
+            library.transcoding.replaygain,
+        ));
+        terminal.log_println(format!(
+            "        disc_subdirectories = \"{}\"",
+            library.transcoding.disc_subdirectories.name(),
+        ));
 
         terminal.log_newline();
     }
+
+    Ok(())
 }
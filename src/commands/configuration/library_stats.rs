@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+use euphony_configuration::library::LibraryConfiguration;
+use euphony_configuration::Configuration;
+use euphony_library::view::LibraryView;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::Serialize;
+
+
+/// The most recently modified album of a library (see `LibraryStats`).
+#[derive(Serialize)]
+pub struct LastModifiedAlbum {
+    pub artist_name: String,
+
+    pub album_title: String,
+
+    pub modified_at: DateTime<Local>,
+}
+
+/// A quick summary of a library's contents (see `list-libraries --with-stats`).
+#[derive(Serialize)]
+pub struct LibraryStats {
+    /// Number of albums in the library.
+    pub num_albums: usize,
+
+    /// The album that was modified most recently, if the library has any albums.
+    pub last_modified_album: Option<LastModifiedAlbum>,
+}
+
+
+/// Returns the most recent modification time of the album directory itself
+/// and the files and directories directly inside it.
+///
+/// This is intentionally a shallow pass: files in subdirectories are not checked
+/// (their modification usually touches the subdirectory anyway).
+fn album_directory_modification_time(
+    album_directory_path: &Path,
+) -> Result<SystemTime> {
+    let mut latest_modification_time = fs::metadata(album_directory_path)
+        .and_then(|metadata| metadata.modified())
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Could not read modification time of {:?}.",
+                album_directory_path
+            )
+        })?;
+
+    let directory_entries = fs::read_dir(album_directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Could not read directory {:?}.",
+                album_directory_path
+            )
+        })?;
+
+    for entry in directory_entries {
+        let Ok(modification_time) = entry
+            .and_then(|entry| entry.metadata())
+            .and_then(|metadata| metadata.modified())
+        else {
+            continue;
+        };
+
+        latest_modification_time =
+            latest_modification_time.max(modification_time);
+    }
+
+    Ok(latest_modification_time)
+}
+
+/// Count the library's albums and find the most recently modified one.
+///
+/// Only modification times are checked (see `album_directory_modification_time`),
+/// so this is much faster than a full scan for changes.
+/// Directories in `ignored_directories_in_base_directory` are skipped.
+pub fn compute_library_stats(
+    config: &Configuration,
+    library_config: &LibraryConfiguration,
+) -> Result<LibraryStats> {
+    let library_view =
+        LibraryView::from_library_configuration(config, library_config)?;
+    let library_view_locked = library_view.read();
+
+
+    let mut num_albums: usize = 0;
+    let mut last_modified_album: Option<(String, String, SystemTime)> = None;
+
+    for (artist_name, artist_view) in library_view_locked.artists()? {
+        if library_config.is_directory_ignored_in_base_directory(&artist_name) {
+            continue;
+        }
+
+        for (album_title, album_view) in artist_view.read().albums()? {
+            num_albums += 1;
+
+            let modification_time = album_directory_modification_time(
+                &album_view.read().album_directory_in_source_library(),
+            )?;
+
+            let is_most_recent = match &last_modified_album {
+                Some((_, _, latest_time)) => modification_time > *latest_time,
+                None => true,
+            };
+
+            if is_most_recent {
+                last_modified_album = Some((
+                    artist_name.clone(),
+                    album_title,
+                    modification_time,
+                ));
+            }
+        }
+    }
+
+    Ok(LibraryStats {
+        num_albums,
+        last_modified_album: last_modified_album.map(
+            |(artist_name, album_title, modification_time)| LastModifiedAlbum {
+                artist_name,
+                album_title,
+                modified_at: DateTime::from(modification_time),
+            },
+        ),
+    })
+}
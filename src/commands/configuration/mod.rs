@@ -1,3 +1,4 @@
 pub use commands::*;
 
 mod commands;
+pub mod library_stats;
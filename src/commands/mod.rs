@@ -1,8 +1,30 @@
+pub use clean::cmd_clean;
+pub use compare::cmd_compare_libraries;
 pub use configuration::cmd_list_libraries;
 pub use configuration::cmd_show_config;
-pub use transcode::cmd_transcode_all;
-pub use validation::cmd_validate;
+pub use transcode::explicit_paths::{
+    cmd_transcode_paths,
+    read_paths_from_stdin,
+};
+pub use transcode::notification::notify_transcode_completion;
+pub use transcode::shard::Shard;
+pub use transcode::watch::cmd_transcode_watch;
+pub use transcode::{
+    cmd_mirror_all,
+    cmd_sync_device,
+    cmd_transcode_album,
+    cmd_transcode_all,
+    cmd_transcode_library,
+};
+pub use transcode::{
+    transcode_collection,
+    TranscodeOptions,
+    TranscodeRunSummary,
+};
+pub use validation::{cmd_validate, cmd_validate_album, ValidateOptions};
 
+pub mod clean;
+pub mod compare;
 pub mod configuration;
 pub mod transcode;
 pub mod validation;
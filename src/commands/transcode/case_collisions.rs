@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use crossterm::style::Stylize;
+use euphony_configuration::aggregated_library::{
+    CaseCollisionStrategy,
+    OutputFilesystem,
+};
+use euphony_configuration::Configuration;
+
+use super::LibraryWithChanges;
+use crate::console::LogBackend;
+
+
+/// Format a group of colliding source paths as a single comma-separated line.
+fn format_colliding_paths(colliding_source_paths: &[PathBuf]) -> String {
+    colliding_source_paths
+        .iter()
+        .map(|source_path| source_path.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Drop the changed albums with output files that would overwrite each other
+/// on a case-insensitive filesystem, i.e. whose output paths only differ in case
+/// (e.g. `Song.flac` and `song.flac`).
+///
+/// This only has an effect when `aggregated_library.filesystem` is `case_insensitive`
+/// and `aggregated_library.case_collision_strategy` is `skip` (with `rename`, the colliding
+/// output files are renamed instead). Each skipped album and its collisions are logged.
+/// As their states are not saved, skipped albums are checked again on the next run.
+///
+/// Returns the remaining libraries and the number of skipped albums.
+pub fn retain_albums_without_case_collisions<'config>(
+    configuration: &Configuration,
+    terminal: &impl LogBackend,
+    libraries_with_changes: Vec<LibraryWithChanges<'config>>,
+) -> (Vec<LibraryWithChanges<'config>>, usize) {
+    let aggregated_library = &configuration.aggregated_library;
+    if aggregated_library.filesystem != OutputFilesystem::CaseInsensitive
+        || aggregated_library.case_collision_strategy
+            != CaseCollisionStrategy::Skip
+    {
+        return (libraries_with_changes, 0);
+    }
+
+    let mut num_skipped_albums: usize = 0;
+
+    let remaining_libraries = libraries_with_changes
+        .into_iter()
+        .filter_map(|mut library| {
+            for artist in &mut library.sorted_changed_artists {
+                let artist_name = artist.artist_name.clone();
+
+                artist.sorted_changed_albums.retain(|changed_album| {
+                    let Some(tracked_source_files) =
+                        changed_album.changes.tracked_source_files.as_ref()
+                    else {
+                        return true;
+                    };
+
+                    let collisions = tracked_source_files
+                        .find_case_insensitive_output_collisions();
+                    if collisions.is_empty() {
+                        return true;
+                    }
+
+                    terminal.log_println(format!(
+                        "{} Skipping album \"{artist_name} - {}\": its output files would \
+                        collide on a case-insensitive filesystem. Rename the source files \
+                        or set aggregated_library.case_collision_strategy to \"rename\".",
+                        "WARNING:".yellow(),
+                        changed_album.album_title,
+                    ));
+                    for colliding_source_paths in &collisions {
+                        terminal.log_println(format!(
+                            "  - {}",
+                            format_colliding_paths(colliding_source_paths)
+                        ));
+                    }
+
+                    num_skipped_albums += 1;
+                    false
+                });
+            }
+
+            library.sorted_changed_artists.retain(|artist| {
+                !artist.sorted_changed_albums.is_empty()
+                    || !artist.sorted_removed_albums.is_empty()
+            });
+
+            if library.sorted_changed_artists.is_empty()
+                && library.fully_removed_artists.is_empty()
+            {
+                None
+            } else {
+                Some(library)
+            }
+        })
+        .collect();
+
+    (remaining_libraries, num_skipped_albums)
+}
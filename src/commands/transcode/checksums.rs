@@ -0,0 +1,96 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use sha2::{Digest, Sha256};
+
+/// File name of the per-album checksum manifest
+/// (see `aggregated_library.write_checksum_manifest`).
+pub const CHECKSUM_MANIFEST_FILE_NAME: &str = "checksums.sha256";
+
+
+/// Get the path of the checksum manifest inside a transcoded album directory.
+pub fn get_checksum_manifest_path<P: AsRef<Path>>(
+    album_directory_path: P,
+) -> PathBuf {
+    album_directory_path
+        .as_ref()
+        .join(CHECKSUM_MANIFEST_FILE_NAME)
+}
+
+/// Compute the SHA-256 hash of the file's contents as a lowercase hex string.
+fn compute_file_sha256(file_path: &Path) -> Result<String> {
+    let file = File::open(file_path).into_diagnostic().wrap_err_with(|| {
+        miette!("Could not open {:?} for hashing.", file_path)
+    })?;
+
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(file), &mut hasher)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!("Could not read {:?} for hashing.", file_path)
+        })?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Write the checksum manifest (see `CHECKSUM_MANIFEST_FILE_NAME`) into the transcoded
+/// album directory, replacing any previous one.
+///
+/// `output_file_paths` are relative to the album directory. The manifest uses the same format
+/// as `sha256sum` (one `<hash>  <path>` line per file, sorted by path, with forward slashes),
+/// so it can be verified with standard tools. If the album has no output files,
+/// any previous manifest is removed instead.
+pub fn write_checksum_manifest<P: AsRef<Path>>(
+    album_directory_path: P,
+    output_file_paths: &[PathBuf],
+) -> Result<()> {
+    let album_directory_path = album_directory_path.as_ref();
+    if output_file_paths.is_empty() {
+        return remove_checksum_manifest(album_directory_path);
+    }
+
+    let mut sorted_output_file_paths: Vec<String> = output_file_paths
+        .iter()
+        .map(|file_path| file_path.to_string_lossy().replace('\\', "/"))
+        .collect();
+    sorted_output_file_paths.sort_unstable();
+
+    let mut manifest = String::new();
+    for output_file_path in sorted_output_file_paths {
+        let file_hash =
+            compute_file_sha256(&album_directory_path.join(&output_file_path))?;
+
+        manifest.push_str(&format!("{file_hash}  {output_file_path}\n"));
+    }
+
+    let manifest_path = get_checksum_manifest_path(album_directory_path);
+    fs::write(&manifest_path, manifest)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Could not write checksum manifest {:?}.",
+                manifest_path
+            )
+        })
+}
+
+/// Remove the checksum manifest from the transcoded album directory, if it exists.
+pub fn remove_checksum_manifest<P: AsRef<Path>>(
+    album_directory_path: P,
+) -> Result<()> {
+    let manifest_path = get_checksum_manifest_path(album_directory_path);
+    if !manifest_path.is_file() {
+        return Ok(());
+    }
+
+    fs::remove_file(&manifest_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Could not remove checksum manifest {:?}.",
+                manifest_path
+            )
+        })
+}
@@ -0,0 +1,180 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use crossbeam::channel::Sender;
+use crossterm::style::Stylize;
+use euphony_configuration::Configuration;
+use euphony_library::state::AlbumFileChangesV2;
+use miette::Result;
+
+use super::jobs::FileJobMessage;
+use super::state::changes::{FileJobContext, FileProcessingAction};
+use super::state::generate_jobs::GenerateChanges;
+use super::LibraryWithChanges;
+use crate::console::frontends::shared::queue::QueueItemID;
+use crate::console::LogBackend;
+
+
+/// Number of files per action that a dry run would process.
+#[derive(Default)]
+struct DryRunFileCounts {
+    num_transcoded: usize,
+    num_copied: usize,
+    num_deleted: usize,
+}
+
+impl DryRunFileCounts {
+    fn add_action(&mut self, action: &FileProcessingAction) {
+        match action {
+            FileProcessingAction::Transcode { .. } => self.num_transcoded += 1,
+            FileProcessingAction::Copy { .. } => self.num_copied += 1,
+            FileProcessingAction::DeleteInTranscoded { .. } => {
+                self.num_deleted += 1
+            }
+        }
+    }
+
+    fn add_counts(&mut self, other: &DryRunFileCounts) {
+        self.num_transcoded += other.num_transcoded;
+        self.num_copied += other.num_copied;
+        self.num_deleted += other.num_deleted;
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} to transcode, {} to copy, {} to delete",
+            self.num_transcoded, self.num_copied, self.num_deleted
+        )
+    }
+}
+
+
+/// Collect the file actions the album's changes would result in.
+///
+/// This generates the file jobs exactly like a real transcode would (so the preview is accurate),
+/// but only records their `FileJobContext`s - the jobs themselves are dropped without running.
+pub(super) fn collect_album_file_actions(
+    configuration: &Arc<Configuration>,
+    changes: &AlbumFileChangesV2,
+) -> Result<Vec<FileProcessingAction>> {
+    let actions: RefCell<Vec<FileProcessingAction>> = RefCell::new(Vec::new());
+
+    changes.generate_file_jobs::<Sender<FileJobMessage>, _>(
+        configuration,
+        |context: FileJobContext| {
+            actions.borrow_mut().push(context.action);
+            Ok(QueueItemID::new_random())
+        },
+    )?;
+
+    Ok(actions.into_inner())
+}
+
+/// Describe a single file action (e.g. `transcode a.flac -> a.mp3`).
+fn describe_file_action(action: &FileProcessingAction) -> String {
+    match action {
+        FileProcessingAction::Transcode {
+            source_path,
+            target_path,
+            ..
+        } => format!(
+            "transcode {} -> {}",
+            source_path.to_string_lossy(),
+            target_path.to_string_lossy()
+        ),
+        FileProcessingAction::Copy {
+            source_path,
+            target_path,
+            ..
+        } => format!(
+            "copy {} -> {}",
+            source_path.to_string_lossy(),
+            target_path.to_string_lossy()
+        ),
+        FileProcessingAction::DeleteInTranscoded { target_path, .. } => {
+            format!("delete {}", target_path.to_string_lossy())
+        }
+    }
+}
+
+
+/// Print everything a transcode would do (see `transcode --dry-run`): per-library counts
+/// and the full list of files that would be transcoded, copied or deleted, per album.
+///
+/// Nothing is processed and no state files are saved.
+pub fn log_dry_run_preview(
+    configuration: &Arc<Configuration>,
+    terminal: &impl LogBackend,
+    libraries_with_changes: &[LibraryWithChanges],
+) -> Result<()> {
+    let mut total_counts = DryRunFileCounts::default();
+    let mut total_num_albums: usize = 0;
+
+    for library in libraries_with_changes {
+        let mut library_counts = DryRunFileCounts::default();
+        let mut library_lines: Vec<String> = Vec::new();
+        let mut library_num_albums: usize = 0;
+
+        for artist in &library.sorted_changed_artists {
+            let changed_albums = artist
+                .sorted_changed_albums
+                .iter()
+                .map(|album| (&album.album_title, &album.changes, false));
+            let removed_albums = artist
+                .sorted_removed_albums
+                .iter()
+                .map(|album| (&album.album_title, &album.changes, true));
+
+            for (album_title, changes, is_removed) in
+                changed_albums.chain(removed_albums)
+            {
+                library_num_albums += 1;
+
+                library_lines.push(format!(
+                    "  {} - {}{}",
+                    artist.artist_name,
+                    album_title,
+                    if is_removed {
+                        " (removed from the source library)"
+                    } else {
+                        ""
+                    }
+                ));
+
+                for action in collect_album_file_actions(configuration, changes)?
+                {
+                    library_counts.add_action(&action);
+                    library_lines
+                        .push(format!("    {}", describe_file_action(&action)));
+                }
+            }
+        }
+
+        terminal.log_println(format!(
+            "Library {}: {} albums ({}).",
+            library.library_name.clone().bold(),
+            library_num_albums,
+            library_counts.describe()
+        ));
+        for line in library_lines {
+            terminal.log_println(line);
+        }
+
+        total_counts.add_counts(&library_counts);
+        total_num_albums += library_num_albums;
+    }
+
+    terminal.log_newline();
+    terminal.log_println(
+        format!(
+            "Dry run: {} albums would be processed ({}). \
+            Nothing was transcoded, copied or deleted and no state files were saved.",
+            total_num_albums,
+            total_counts.describe()
+        )
+        .green()
+        .bold(),
+    );
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use miette::Result;
+use tokio::sync::broadcast;
+
+use super::GlobalProgress;
+use crate::console::frontends::shared::queue::{
+    AlbumQueueItem,
+    AlbumQueueItemFinishedResult,
+    FileQueueItem,
+    FileQueueItemFinishedResult,
+    QueueItemID,
+};
+use crate::console::frontends::TranscodeTerminal;
+use crate::console::{
+    LogBackend,
+    TranscodeBackend,
+    UserControlMessage,
+    UserControllableBackend,
+};
+
+/// Receives the events of a transcoding run (see `transcode_collection`).
+///
+/// This decouples the transcoding engine from the terminal: all terminal backends
+/// (through `TranscodeTerminal`) are one implementation, but callers that embed euphony
+/// can provide their own (e.g. to report progress in their own UI, or to collect the events in tests).
+///
+/// Albums and files are identified by the `QueueItemID`s the sink returns when they are queued.
+/// Log messages (which may contain ANSI colours) are received through `LogBackend`.
+pub trait TranscodeEventSink<'config>: LogBackend {
+    /// Scanning `num_albums` albums for changes has started.
+    fn on_scan_started(&self, num_albums: usize);
+
+    /// `num_scanned` albums have been scanned so far.
+    fn on_scan_progress(&self, num_scanned: usize) -> Result<()>;
+
+    /// Scanning has finished.
+    fn on_scan_finished(&self);
+
+    /// Processing is about to start. This is called before any albums are queued.
+    fn on_processing_started(&self);
+
+    /// An album will be processed. Returns the ID the album is referred to by from now on.
+    fn on_album_queued(
+        &self,
+        album: AlbumQueueItem<'config>,
+    ) -> Result<QueueItemID>;
+
+    /// Processing of the album has started (its files are queued next).
+    fn on_album_started(&self, album_id: QueueItemID) -> Result<()>;
+
+    /// The album has been processed.
+    fn on_album_finished(
+        &self,
+        album_id: QueueItemID,
+        result: AlbumQueueItemFinishedResult,
+    ) -> Result<()>;
+
+    /// A file of an album that has started will be processed.
+    /// Returns the ID the file is referred to by from now on.
+    fn on_file_queued(
+        &self,
+        file: FileQueueItem<'config>,
+    ) -> Result<QueueItemID>;
+
+    /// Processing (i.e. transcoding or copying) of the file has started.
+    fn on_file_started(&self, file_id: QueueItemID) -> Result<()>;
+
+    /// How far along the file is (`progress` is between 0 and 1).
+    /// Only sent for files whose progress can be determined (i.e. transcodes).
+    fn on_file_progress(
+        &self,
+        file_id: QueueItemID,
+        progress: f64,
+    ) -> Result<()>;
+
+    /// The file has been processed (successfully or not) or was cancelled.
+    fn on_file_finished(
+        &self,
+        file_id: QueueItemID,
+        result: FileQueueItemFinishedResult,
+    ) -> Result<()>;
+
+    /// The file's album has finished, so the file will not be referred to anymore.
+    fn on_file_removed(&self, file_id: QueueItemID) -> Result<()>;
+
+    /// The overall progress of the run (the file counters) has changed.
+    fn on_progress(&self, progress: &GlobalProgress) -> Result<()>;
+
+    /// Get a receiver for user control messages (such as an abort message)
+    /// that should be handled while processing.
+    fn user_control_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<UserControlMessage>>;
+}
+
+
+impl<'config, 'scope> TranscodeEventSink<'config>
+    for TranscodeTerminal<'config, 'scope>
+{
+    fn on_scan_started(&self, num_albums: usize) {
+        self.scan_progress_enable(num_albums);
+    }
+
+    fn on_scan_progress(&self, num_scanned: usize) -> Result<()> {
+        self.scan_progress_set_scanned(num_scanned)
+    }
+
+    fn on_scan_finished(&self) {
+        self.scan_progress_disable();
+    }
+
+    fn on_processing_started(&self) {
+        self.queue_album_enable();
+        self.queue_file_enable();
+        self.progress_enable();
+    }
+
+    fn on_album_queued(
+        &self,
+        album: AlbumQueueItem<'config>,
+    ) -> Result<QueueItemID> {
+        self.queue_album_item_add(album)
+    }
+
+    fn on_album_started(&self, album_id: QueueItemID) -> Result<()> {
+        self.queue_album_item_start(album_id)
+    }
+
+    fn on_album_finished(
+        &self,
+        album_id: QueueItemID,
+        result: AlbumQueueItemFinishedResult,
+    ) -> Result<()> {
+        self.queue_album_item_finish(album_id, result)
+    }
+
+    fn on_file_queued(
+        &self,
+        file: FileQueueItem<'config>,
+    ) -> Result<QueueItemID> {
+        self.queue_file_item_add(file)
+    }
+
+    fn on_file_started(&self, file_id: QueueItemID) -> Result<()> {
+        self.queue_file_item_start(file_id)
+    }
+
+    fn on_file_progress(
+        &self,
+        file_id: QueueItemID,
+        progress: f64,
+    ) -> Result<()> {
+        self.queue_file_item_set_progress(file_id, progress)
+    }
+
+    fn on_file_finished(
+        &self,
+        file_id: QueueItemID,
+        result: FileQueueItemFinishedResult,
+    ) -> Result<()> {
+        self.queue_file_item_finish(file_id, result)
+    }
+
+    fn on_file_removed(&self, file_id: QueueItemID) -> Result<()> {
+        self.queue_file_item_remove(file_id).map(|_| ())
+    }
+
+    fn on_progress(&self, progress: &GlobalProgress) -> Result<()> {
+        // Backends ignore (or don't emit) counters that haven't changed.
+        self.progress_set_total(progress.num_total_files)?;
+        self.progress_set_audio_files_currently_processing(
+            progress.audio_files_currently_processing,
+        )?;
+        self.progress_set_data_files_currently_processing(
+            progress.data_files_currently_processing,
+        )?;
+        self.progress_set_audio_files_finished_ok(
+            progress.audio_files_finished_ok,
+        )?;
+        self.progress_set_data_files_finished_ok(
+            progress.data_files_finished_ok,
+        )?;
+        self.progress_set_audio_files_errored(progress.audio_files_errored)?;
+        self.progress_set_data_files_errored(progress.data_files_errored)
+    }
+
+    fn user_control_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<UserControlMessage>> {
+        self.get_user_control_receiver()
+    }
+}
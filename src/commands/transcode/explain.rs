@@ -0,0 +1,375 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crossterm::style::Stylize;
+use euphony_configuration::aggregated_library::AggregationMode;
+use euphony_library::state::AlbumFileChangesV2;
+use euphony_library::view::SharedLibraryView;
+
+use super::state::changes::{
+    CopyProcessingReason,
+    DeleteInTranscodedProcessingReason,
+    FileProcessingAction,
+    TranscodeProcessingReason,
+};
+use super::{LibraryWithChanges, QueuedAlbumJobType, UnchangedAlbum};
+use crate::console::LogBackend;
+use crate::globals::is_explain_enabled;
+
+
+/// Returns `singular` if `count` is one and `plural` otherwise.
+fn pluralize<'a>(count: usize, singular: &'a str, plural: &'a str) -> &'a str {
+    if count == 1 {
+        singular
+    } else {
+        plural
+    }
+}
+
+/// Returns the file name of `path` (or the whole path if it has none).
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+/// Returns a human-readable list of the (uppercase) extensions of the given files,
+/// e.g. `FLAC` or `FLAC/WAV`.
+fn describe_extensions(paths: &[PathBuf]) -> String {
+    let extensions = paths
+        .iter()
+        .filter_map(|path| path.extension())
+        .map(|extension| extension.to_string_lossy().to_uppercase())
+        .collect::<BTreeSet<String>>();
+
+    if extensions.is_empty() {
+        "audio".to_string()
+    } else {
+        extensions.into_iter().collect::<Vec<String>>().join("/")
+    }
+}
+
+
+/// Narrate (see `transcode --explain`) why each library needs (or doesn't need) processing.
+pub fn explain_library_changes(
+    terminal: &impl LogBackend,
+    libraries: &[SharedLibraryView],
+    libraries_with_changes: &[LibraryWithChanges],
+    unchanged_albums: &[UnchangedAlbum],
+) {
+    if !is_explain_enabled() {
+        return;
+    }
+
+    let mut num_unchanged_albums_per_library: BTreeMap<&str, usize> =
+        BTreeMap::new();
+    for album in unchanged_albums {
+        *num_unchanged_albums_per_library
+            .entry(&album.library_name)
+            .or_default() += 1;
+    }
+
+    terminal.log_newline();
+
+    for library in libraries {
+        let library_name = library.read().name();
+        let num_unchanged_albums = num_unchanged_albums_per_library
+            .get(library_name.as_str())
+            .copied()
+            .unwrap_or_default();
+
+        let Some(library_changes) = libraries_with_changes
+            .iter()
+            .find(|changes| changes.library_name == library_name)
+        else {
+            terminal.log_println(format!(
+                "Library {} has no changes: all of its {} {} already up to date, \
+                so there is nothing to do.",
+                library_name.clone().bold(),
+                num_unchanged_albums,
+                pluralize(num_unchanged_albums, "album is", "albums are"),
+            ));
+            continue;
+        };
+
+        let num_changed_albums = library_changes
+            .sorted_changed_artists
+            .iter()
+            .map(|artist| artist.sorted_changed_albums.len())
+            .sum::<usize>();
+        let num_removed_albums = library_changes
+            .sorted_changed_artists
+            .iter()
+            .map(|artist| artist.sorted_removed_albums.len())
+            .sum::<usize>();
+
+        terminal.log_println(format!("Library {}:", library_name.bold()));
+
+        if num_changed_albums > 0 {
+            terminal.log_println(format!(
+                "  {} {} will be processed because {} new or changed files \
+                since the last transcode (or {} never been transcoded).",
+                num_changed_albums,
+                pluralize(num_changed_albums, "album", "albums"),
+                pluralize(num_changed_albums, "it has", "they have"),
+                pluralize(num_changed_albums, "has", "have"),
+            ));
+        }
+
+        if num_removed_albums > 0 {
+            terminal.log_println(format!(
+                "  {} transcoded {} will be deleted because {} been \
+                removed from the source library.",
+                num_removed_albums,
+                pluralize(num_removed_albums, "album", "albums"),
+                pluralize(
+                    num_removed_albums,
+                    "its source album has",
+                    "their source albums have"
+                ),
+            ));
+        }
+
+        if num_unchanged_albums > 0 {
+            terminal.log_println(format!(
+                "  {} {} already up to date and will be skipped.",
+                num_unchanged_albums,
+                pluralize(num_unchanged_albums, "album is", "albums are"),
+            ));
+        }
+    }
+
+    terminal.log_newline();
+}
+
+
+/// Narrate (see `transcode --explain`) what is about to happen to the album and why.
+pub fn explain_album_changes(
+    terminal: &impl LogBackend,
+    changes: &AlbumFileChangesV2,
+    job_type: QueuedAlbumJobType,
+) {
+    if !is_explain_enabled() {
+        return;
+    }
+
+    match job_type {
+        QueuedAlbumJobType::FullyRemoving => {
+            terminal.log_println(
+                "  The source album was removed, so its transcoded version \
+                will be deleted as well.",
+            );
+            return;
+        }
+        QueuedAlbumJobType::ExplicitFiles => {
+            terminal.log_println(
+                "  Only the files that were explicitly requested will be processed.",
+            );
+            return;
+        }
+        QueuedAlbumJobType::NormalProcessing => {}
+    }
+
+    let (aggregation_mode, output_extension) = {
+        let album_locked = changes.read_lock_album();
+        let configuration = album_locked.euphony_configuration();
+
+        (
+            configuration.aggregated_library.mode,
+            album_locked
+                .configuration
+                .transcoding
+                .audio_transcoding_output_extension(&configuration.tools.ffmpeg)
+                .to_uppercase(),
+        )
+    };
+
+    let audio_not_yet_processed_reason = match aggregation_mode {
+        AggregationMode::Transcode => "they haven't been transcoded yet",
+        AggregationMode::Mirror => "they haven't been copied yet",
+    };
+
+    let audio_groups: [(&Vec<PathBuf>, &str); 3] = [
+        (
+            &changes.added_in_source_since_last_transcode.audio,
+            audio_not_yet_processed_reason,
+        ),
+        (
+            &changes.changed_in_source_since_last_transcode.audio,
+            "their source files changed since the last transcode",
+        ),
+        (
+            &changes.missing_in_transcoded.audio,
+            "their transcoded versions are missing from the transcoded library",
+        ),
+    ];
+
+    for (files, reason) in audio_groups {
+        if files.is_empty() {
+            continue;
+        }
+
+        let explanation = match aggregation_mode {
+            AggregationMode::Transcode => format!(
+                "  Transcoding {} {} {} to {} because {}.",
+                files.len(),
+                describe_extensions(files),
+                pluralize(files.len(), "file", "files"),
+                output_extension,
+                reason,
+            ),
+            AggregationMode::Mirror => format!(
+                "  Copying {} {} {} (mirroring, no transcoding) because {}.",
+                files.len(),
+                describe_extensions(files),
+                pluralize(files.len(), "file", "files"),
+                reason,
+            ),
+        };
+
+        terminal.log_println(explanation);
+    }
+
+    let data_groups: [(&Vec<PathBuf>, &str); 3] = [
+        (
+            &changes.added_in_source_since_last_transcode.data,
+            "they haven't been copied yet",
+        ),
+        (
+            &changes.changed_in_source_since_last_transcode.data,
+            "their source files changed since the last transcode",
+        ),
+        (
+            &changes.missing_in_transcoded.data,
+            "their copies are missing from the transcoded library",
+        ),
+    ];
+
+    for (files, reason) in data_groups {
+        if files.is_empty() {
+            continue;
+        }
+
+        terminal.log_println(format!(
+            "  Copying {} data {} because {}.",
+            files.len(),
+            pluralize(files.len(), "file", "files"),
+            reason,
+        ));
+    }
+
+    let num_removed_files =
+        changes.removed_from_source_since_last_transcode.audio.len()
+            + changes.removed_from_source_since_last_transcode.data.len();
+    if num_removed_files > 0 {
+        terminal.log_println(format!(
+            "  Deleting {} transcoded {} because {} removed from the source album.",
+            num_removed_files,
+            pluralize(num_removed_files, "file", "files"),
+            pluralize(
+                num_removed_files,
+                "its source file was",
+                "their source files were"
+            ),
+        ));
+    }
+
+    let num_excess_files = changes.excess_in_transcoded.audio.len()
+        + changes.excess_in_transcoded.data.len()
+        + changes.excess_in_transcoded.unknown.len();
+    if num_excess_files > 0 {
+        terminal.log_println(format!(
+            "  Deleting {} unexpected {} in the transcoded album directory \
+            because {} to any source file.",
+            num_excess_files,
+            pluralize(num_excess_files, "file", "files"),
+            pluralize(
+                num_excess_files,
+                "it doesn't belong",
+                "they don't belong"
+            ),
+        ));
+    }
+}
+
+
+/// Narrate (see `transcode --explain`) a single file action and the reason for it.
+pub fn explain_file_action(
+    terminal: &impl LogBackend,
+    action: &FileProcessingAction,
+) {
+    if !is_explain_enabled() {
+        return;
+    }
+
+    let explanation = match action {
+        FileProcessingAction::Transcode {
+            source_path,
+            target_path,
+            reason,
+        } => {
+            let reason = match reason {
+                TranscodeProcessingReason::AddedInSourceLibrary => {
+                    "it hasn't been transcoded yet"
+                }
+                TranscodeProcessingReason::ChangedInSourceLibrary => {
+                    "the source file changed"
+                }
+                TranscodeProcessingReason::MissingInTranscodedLibrary => {
+                    "the transcoded file is missing"
+                }
+            };
+
+            format!(
+                "    Transcoding {} to {} because {}.",
+                file_name(source_path),
+                file_name(target_path),
+                reason
+            )
+        }
+        FileProcessingAction::Copy {
+            source_path,
+            reason,
+            ..
+        } => {
+            let reason = match reason {
+                CopyProcessingReason::AddedInSourceLibrary => {
+                    "it hasn't been copied yet"
+                }
+                CopyProcessingReason::ChangedInSourceLibrary => {
+                    "the source file changed"
+                }
+                CopyProcessingReason::MissingInTranscodedLibrary => {
+                    "the copy is missing"
+                }
+            };
+
+            format!(
+                "    Copying {} because {}.",
+                file_name(source_path),
+                reason
+            )
+        }
+        FileProcessingAction::DeleteInTranscoded {
+            target_path,
+            reason,
+        } => {
+            let reason = match reason {
+                DeleteInTranscodedProcessingReason::RemovedFromSourceLibrary => {
+                    "its source file was removed"
+                }
+                DeleteInTranscodedProcessingReason::ExcessInTranscodedLibrary => {
+                    "it doesn't belong to any source file"
+                }
+            };
+
+            format!(
+                "    Deleting {} because {}.",
+                file_name(target_path),
+                reason
+            )
+        }
+    };
+
+    terminal.log_println(explanation.dark_grey());
+}
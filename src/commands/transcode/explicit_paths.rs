@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::path::{Component, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crossterm::style::Stylize;
+use euphony_configuration::Configuration;
+use euphony_library::state::AlbumFileChangesV2;
+use euphony_library::view::{AlbumView, ArtistView, SharedLibraryView};
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::events::TranscodeEventSink;
+use super::interrupt::install_interrupt_handler;
+use super::{
+    collect_libraries_sorted,
+    ensure_ffmpeg_is_available,
+    initialize_progress,
+    log_run_summary,
+    process_queued_albums,
+    QueuedAlbum,
+    QueuedAlbumJobType,
+    TranscodeRunSummary,
+};
+use crate::console::frontends::shared::queue::AlbumQueueItem;
+use crate::console::frontends::TranscodeTerminal;
+use crate::console::LogBackend;
+
+
+/// Read newline-delimited file paths from the standard input until EOF.
+/// Empty lines are ignored.
+pub fn read_paths_from_stdin() -> Result<Vec<PathBuf>> {
+    io::stdin()
+        .lock()
+        .lines()
+        .filter(|line| {
+            line.as_ref()
+                .map(|line| !line.trim().is_empty())
+                .unwrap_or(true)
+        })
+        .map(|line| line.map(|line| PathBuf::from(line.trim())))
+        .collect::<Result<Vec<PathBuf>, io::Error>>()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Failed to read file paths from stdin."))
+}
+
+
+/// Audio and data files (relative to the album directory) that were explicitly
+/// requested for a single album.
+#[derive(Default)]
+struct ExplicitAlbumFiles {
+    audio_files: Vec<PathBuf>,
+    data_files: Vec<PathBuf>,
+}
+
+/// Identifies an album by its library (index into the sorted library list),
+/// artist name and album title.
+type ExplicitAlbumKey = (usize, String, String);
+
+
+/// Find the library that owns `source_file_path` and sort the file into its album.
+///
+/// Returns `Ok(Some(reason))` if the path can't be transcoded
+/// (e.g. it's not inside any registered library); the caller should report it and skip it.
+fn sort_explicit_path_into_album(
+    source_file_path: &PathBuf,
+    libraries: &[(SharedLibraryView, PathBuf)],
+    albums: &mut BTreeMap<ExplicitAlbumKey, ExplicitAlbumFiles>,
+) -> Result<Option<&'static str>> {
+    let Ok(source_file_path) = dunce::canonicalize(source_file_path) else {
+        return Ok(Some("file does not exist"));
+    };
+
+    if !source_file_path.is_file() {
+        return Ok(Some("not a file"));
+    }
+
+    let Some((library_index, (library, relative_file_path))) = libraries
+        .iter()
+        .map(|(library, library_root)| {
+            (
+                library,
+                source_file_path.strip_prefix(library_root).ok(),
+            )
+        })
+        .enumerate()
+        .find_map(|(index, (library, relative_path))| {
+            relative_path.map(|path| (index, (library, path.to_path_buf())))
+        })
+    else {
+        return Ok(Some("not inside any registered library"));
+    };
+
+    // The expected structure is <library>/<artist>/<album>/<file>,
+    // where the file can be nested deeper if the album's scan depth allows it.
+    let mut components = relative_file_path.components();
+    let (
+        Some(Component::Normal(artist_name)),
+        Some(Component::Normal(album_title)),
+    ) = (components.next(), components.next())
+    else {
+        return Ok(Some("not inside an album directory"));
+    };
+
+    let path_relative_to_album: PathBuf = components.collect();
+    if path_relative_to_album.as_os_str().is_empty() {
+        return Ok(Some("not inside an album directory"));
+    }
+
+    let artist_name = artist_name.to_string_lossy().to_string();
+    let album_title = album_title.to_string_lossy().to_string();
+
+    let library_locked = library.read();
+
+    if library_locked
+        .library_configuration
+        .is_directory_ignored_in_base_directory(&artist_name)
+    {
+        return Ok(Some("inside an ignored directory"));
+    }
+
+    if library_locked
+        .library_configuration
+        .is_path_ignored_at_any_depth(&path_relative_to_album)
+    {
+        return Ok(Some("ignored (see ignored_names_at_any_depth)"));
+    }
+
+    let transcoding_configuration =
+        &library_locked.library_configuration.transcoding;
+
+    let is_audio_file = transcoding_configuration
+        .is_path_audio_file_by_extension(&path_relative_to_album)?;
+    let is_data_file = transcoding_configuration
+        .is_path_data_file_by_extension(&path_relative_to_album)?;
+
+    if !is_audio_file && !is_data_file {
+        return Ok(Some("not a tracked audio or data file"));
+    }
+
+    let album_files = albums
+        .entry((library_index, artist_name, album_title))
+        .or_default();
+
+    if is_audio_file {
+        album_files.audio_files.push(path_relative_to_album);
+    } else {
+        album_files.data_files.push(path_relative_to_album);
+    }
+
+    Ok(None)
+}
+
+
+/// Transcode (or copy) exactly the given source files into the aggregated library.
+///
+/// Each path is resolved to its owning library and album, and the output paths are computed
+/// the same way as when transcoding normally. Saved album states are ignored when deciding
+/// what to process, but the processed files are merged into them afterwards (the library
+/// states are not updated). Paths that are not inside any registered library
+/// (or are otherwise invalid) are reported and skipped.
+pub fn cmd_transcode_paths<'config: 'scope, 'scope>(
+    configuration: &'config Arc<Configuration>,
+    terminal: &TranscodeTerminal<'config, 'scope>,
+    source_file_paths: Vec<PathBuf>,
+) -> Result<TranscodeRunSummary> {
+    let time_full_processing_start = Instant::now();
+
+    terminal.log_println(
+        "Command: transcode explicit file list (from stdin)."
+            .cyan()
+            .bold(),
+    );
+
+    ensure_ffmpeg_is_available(configuration, terminal)?;
+
+    let mut terminal_user_input = terminal.user_control_receiver()?;
+    install_interrupt_handler()?;
+
+    let libraries = collect_libraries_sorted(configuration, terminal)?
+        .into_iter()
+        .map(|library| {
+            let library_root = library.read().root_directory_in_source_library();
+            let library_root = dunce::canonicalize(&library_root)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!("Failed to canonicalize {:?}", library_root)
+                })?;
+
+            Ok((library, library_root))
+        })
+        .collect::<Result<Vec<(SharedLibraryView, PathBuf)>>>()?;
+
+    let mut albums: BTreeMap<ExplicitAlbumKey, ExplicitAlbumFiles> =
+        BTreeMap::new();
+
+    for source_file_path in &source_file_paths {
+        if let Some(skip_reason) = sort_explicit_path_into_album(
+            source_file_path,
+            &libraries,
+            &mut albums,
+        )? {
+            terminal.log_println(format!(
+                "{} {:?} ({skip_reason}).",
+                "Skipping:".yellow(),
+                source_file_path,
+            ));
+        }
+    }
+
+    if albums.is_empty() {
+        terminal.log_println("No valid file paths given, nothing to do.".bold());
+        return Ok(TranscodeRunSummary::default());
+    }
+
+
+    terminal.on_processing_started();
+
+    let mut queued_albums: Vec<QueuedAlbum> = Vec::with_capacity(albums.len());
+    let mut num_total_files: usize = 0;
+
+    for ((library_index, artist_name, album_title), album_files) in albums {
+        let (library, _) = &libraries[library_index];
+
+        let artist = ArtistView::new(library.clone(), artist_name, false)?;
+        let album = AlbumView::new(artist, album_title, false)?;
+
+        let changes = AlbumFileChangesV2::generate_for_explicit_files(
+            album.clone(),
+            album_files.audio_files,
+            album_files.data_files,
+        );
+        num_total_files += changes.number_of_changed_files();
+
+        let queue_id = terminal.on_album_queued(AlbumQueueItem::new(
+            album.clone(),
+            changes.number_of_changed_audio_files(),
+            changes.number_of_changed_data_files(),
+        ))?;
+
+        queued_albums.push(QueuedAlbum {
+            album,
+            queue_id,
+            changes,
+            job_type: QueuedAlbumJobType::ExplicitFiles,
+        });
+    }
+
+    terminal.log_println(format!(
+        "{} files will be processed.",
+        num_total_files.to_string().bold()
+    ));
+
+    let mut global_progress = initialize_progress(terminal, num_total_files)?;
+
+    let num_errored_albums = process_queued_albums(
+        configuration,
+        &queued_albums,
+        &mut global_progress,
+        terminal,
+        &mut terminal_user_input,
+        |_, _, _| Ok(()),
+    )?;
+
+    let time_full_processing_elapsed =
+        time_full_processing_start.elapsed().as_secs_f64();
+
+    if num_errored_albums > 0 {
+        terminal.log_println(format!(
+            "{} Files processed in {time_full_processing_elapsed:.2} seconds, \
+            but {num_errored_albums} albums had failed files.",
+            "WARNING:".yellow()
+        ));
+    } else {
+        terminal.log_println(format!(
+            "All files successfully processed in {time_full_processing_elapsed:.2} seconds."
+        ));
+    }
+
+    log_run_summary(terminal, &global_progress, num_errored_albums);
+
+    Ok(TranscodeRunSummary {
+        num_processed_albums: global_progress.num_finished_albums,
+        num_errored_albums,
+    })
+}
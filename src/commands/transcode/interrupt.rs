@@ -0,0 +1,43 @@
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use miette::{miette, IntoDiagnostic, Result, WrapErr};
+
+/// Exit code used when a second interrupt forces euphony to exit immediately
+/// (the conventional `128 + SIGINT`).
+const FORCED_EXIT_CODE: i32 = 130;
+
+/// Set once the process has received an interrupt (Ctrl-C).
+static INTERRUPT_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Set once the interrupt handler has been installed (it can only be installed once per process).
+static INTERRUPT_HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+
+/// Install a Ctrl-C (interrupt) handler for the rest of the process.
+///
+/// Instead of killing the process, the first interrupt only marks the run as interrupted
+/// (see `is_interrupted`): the current album is then cancelled like when the user exits
+/// the terminal UI, its partially processed files are deleted and the terminal backend
+/// is cleanly destroyed. A second interrupt exits immediately.
+///
+/// Calling this more than once is a no-op.
+pub fn install_interrupt_handler() -> Result<()> {
+    if INTERRUPT_HANDLER_INSTALLED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    ctrlc::set_handler(|| {
+        if INTERRUPT_RECEIVED.swap(true, Ordering::SeqCst) {
+            process::exit(FORCED_EXIT_CODE);
+        }
+    })
+    .into_diagnostic()
+    .wrap_err_with(|| miette!("Could not install the interrupt handler."))
+}
+
+/// Whether the process has received an interrupt (Ctrl-C) since the handler was installed.
+#[inline]
+pub fn is_interrupted() -> bool {
+    INTERRUPT_RECEIVED.load(Ordering::SeqCst)
+}
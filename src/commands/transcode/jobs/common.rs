@@ -1,53 +1,82 @@
-use std::sync::atomic::AtomicBool;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{fs, io};
 
 use crossbeam::channel::Sender;
-use miette::Result;
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
+use euphony_configuration::aggregated_library::FileJobErrorCategory;
+use miette::{miette, Context, IntoDiagnostic, Result};
 
 use crate::commands::transcode::state::changes::FileType;
 use crate::console::frontends::shared::queue::QueueItemID;
 
-pub struct CancellableTask<C: Send> {
-    #[allow(dead_code)]
-    id: String,
+pub struct CancellableTask<S: FileProgressSink> {
+    /// Path of the file the task processes (used to report the task's failure).
+    file_path: PathBuf,
 
     #[allow(clippy::type_complexity)]
-    task_closure: Box<dyn FnOnce(&AtomicBool, &Sender<C>) + Send>,
+    task_closure: Box<dyn FnOnce(&AtomicBool, &S) -> Result<()> + Send>,
 }
 
-impl<C: Send> CancellableTask<C> {
+impl<S: FileProgressSink> CancellableTask<S> {
     #[allow(clippy::type_complexity)]
     pub fn new(
-        task_id: String,
-        boxed_closure: Box<dyn FnOnce(&AtomicBool, &Sender<C>) + Send>,
+        file_path: PathBuf,
+        boxed_closure: Box<dyn FnOnce(&AtomicBool, &S) -> Result<()> + Send>,
     ) -> Self {
         Self {
-            id: task_id,
+            file_path,
             task_closure: boxed_closure,
         }
     }
 
+    pub fn file_path(&self) -> &Path {
+        &self.file_path
+    }
+
     pub fn execute_task(
         self,
         cancellation_flag: &AtomicBool,
-        message_sender: &Sender<C>,
-    ) {
-        (self.task_closure)(cancellation_flag, message_sender)
+        progress_sink: &S,
+    ) -> Result<()> {
+        (self.task_closure)(cancellation_flag, progress_sink)
     }
 }
 
-pub trait IntoCancellableTask<C: Send> {
-    fn into_cancellable_task(self) -> CancellableTask<C>;
+pub trait IntoCancellableTask<S: FileProgressSink> {
+    fn into_cancellable_task(self) -> CancellableTask<S>;
+}
+/// What a successfully finished `FileJob` did (counted in the end-of-run summary).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileJobAction {
+    /// The audio file was transcoded into a file of `bytes_written` bytes.
+    Transcoded { bytes_written: u64 },
+
+    /// The file was copied as-is (`bytes_written` bytes).
+    Copied { bytes_written: u64 },
+
+    /// Nothing needed to be done (e.g. the target file already matched the source file
+    /// or the file to delete was already missing).
+    Skipped,
+
+    /// The file was deleted from the aggregated library.
+    Deleted,
 }
+
 /// Task state for completed `FileJob`s.
 #[derive(Debug)]
 pub enum FileJobResult {
     Okay {
+        action: FileJobAction,
         verbose_info: Option<String>,
     },
     Errored {
         error: String,
+        /// Decides whether the job is retried
+        /// (see `aggregated_library.retryable_error_categories`).
+        category: FileJobErrorCategory,
         verbose_info: Option<String>,
     },
 }
@@ -59,12 +88,30 @@ pub enum FileJobMessage {
         file_type: FileType,
         file_path: String,
     },
-    // TODO Some sort of progress message?
+    /// How far along the file is, between 0 and 1 (only sent by transcoding jobs,
+    /// and only if ffmpeg's progress could be parsed).
+    Progress {
+        queue_item: QueueItemID,
+        progress: f64,
+    },
     Finished {
         queue_item: QueueItemID,
         file_type: FileType,
         file_path: String,
         processing_result: FileJobResult,
+        /// How long ffmpeg ran for this file (only set for transcoding jobs).
+        ffmpeg_duration: Option<Duration>,
+    },
+    /// The job failed, but will be tried again after a delay
+    /// (see `aggregated_library.failure_max_retries`).
+    Retrying {
+        file_type: FileType,
+        file_path: String,
+        error: String,
+        category: FileJobErrorCategory,
+        /// Number of the attempt that just failed (starting at 1).
+        failed_attempt: usize,
+        max_attempts: usize,
     },
     Cancelled {
         queue_item: QueueItemID,
@@ -74,6 +121,12 @@ pub enum FileJobMessage {
     Log {
         content: String,
     },
+    /// The ReplayGain tags of a finished album have been written (or failed to be,
+    /// which the task logs as a warning beforehand).
+    ReplayGainFinished {
+        /// Index of the album in the list of queued albums.
+        album_index: usize,
+    },
 }
 
 impl FileJobMessage {
@@ -89,17 +142,44 @@ impl FileJobMessage {
         }
     }
 
+    pub fn new_progress(queue_item: QueueItemID, progress: f64) -> Self {
+        Self::Progress {
+            queue_item,
+            progress,
+        }
+    }
+
     pub fn new_finished<P: Into<String>>(
         queue_item: QueueItemID,
         file_type: FileType,
         file_path: P,
         result: FileJobResult,
+        ffmpeg_duration: Option<Duration>,
     ) -> Self {
         Self::Finished {
             queue_item,
             file_type,
             file_path: file_path.into(),
             processing_result: result,
+            ffmpeg_duration,
+        }
+    }
+
+    pub fn new_retrying<P: Into<String>>(
+        file_type: FileType,
+        file_path: P,
+        error: String,
+        category: FileJobErrorCategory,
+        failed_attempt: usize,
+        max_attempts: usize,
+    ) -> Self {
+        Self::Retrying {
+            file_type,
+            file_path: file_path.into(),
+            error,
+            category,
+            failed_attempt,
+            max_attempts,
         }
     }
 
@@ -120,39 +200,318 @@ impl FileJobMessage {
             content: log_string.into(),
         }
     }
+
+    pub fn new_replaygain_finished(album_index: usize) -> Self {
+        Self::ReplayGainFinished { album_index }
+    }
+}
+
+
+/// Receives the progress of file jobs as they run (in the thread pool's worker threads).
+///
+/// The transcode command uses `Sender<FileJobMessage>`, which relays everything
+/// back to the main thread as `FileJobMessage`s, where it is reported to the
+/// `TranscodeEventSink` (i.e. the terminal's file queue and progress).
+/// Other implementations can e.g. collect the events in tests.
+///
+/// An error means the events can't be delivered anymore (e.g. the receiving end is gone).
+pub trait FileProgressSink: Send + Sync + 'static {
+    /// The job has started processing the file.
+    fn file_started(
+        &self,
+        queue_item: QueueItemID,
+        file_type: FileType,
+        file_path: &str,
+    ) -> Result<()>;
+
+    /// How far along the file is, between 0 and 1 (see `FileJobMessage::Progress`).
+    fn file_progress(
+        &self,
+        queue_item: QueueItemID,
+        progress: f64,
+    ) -> Result<()>;
+
+    /// The job has finished processing the file (successfully or not).
+    fn file_finished(
+        &self,
+        queue_item: QueueItemID,
+        file_type: FileType,
+        file_path: &str,
+        result: FileJobResult,
+        ffmpeg_duration: Option<Duration>,
+    ) -> Result<()>;
+
+    /// The job failed, but will be tried again (see `FileJobMessage::Retrying`).
+    fn file_retrying(
+        &self,
+        file_type: FileType,
+        file_path: &str,
+        error: String,
+        category: FileJobErrorCategory,
+        failed_attempt: usize,
+        max_attempts: usize,
+    ) -> Result<()>;
+
+    /// The job was cancelled before the file was finished.
+    fn file_cancelled(
+        &self,
+        queue_item: QueueItemID,
+        file_type: FileType,
+        file_path: &str,
+    ) -> Result<()>;
+
+    /// A log message (e.g. verbose information from the thread pool).
+    fn log(&self, content: String) -> Result<()>;
+
+    /// The ReplayGain tags of the album with the given index have been written
+    /// (see `FileJobMessage::ReplayGainFinished`).
+    fn replaygain_finished(&self, album_index: usize) -> Result<()>;
+}
+
+impl FileProgressSink for Sender<FileJobMessage> {
+    fn file_started(
+        &self,
+        queue_item: QueueItemID,
+        file_type: FileType,
+        file_path: &str,
+    ) -> Result<()> {
+        self.send(FileJobMessage::new_starting(
+            queue_item, file_type, file_path,
+        ))
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Could not send FileJobMessage::Starting."))
+    }
+
+    fn file_progress(
+        &self,
+        queue_item: QueueItemID,
+        progress: f64,
+    ) -> Result<()> {
+        self.send(FileJobMessage::new_progress(queue_item, progress))
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Could not send FileJobMessage::Progress.")
+            })
+    }
+
+    fn file_finished(
+        &self,
+        queue_item: QueueItemID,
+        file_type: FileType,
+        file_path: &str,
+        result: FileJobResult,
+        ffmpeg_duration: Option<Duration>,
+    ) -> Result<()> {
+        self.send(FileJobMessage::new_finished(
+            queue_item,
+            file_type,
+            file_path,
+            result,
+            ffmpeg_duration,
+        ))
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Could not send FileJobMessage::Finished."))
+    }
+
+    fn file_retrying(
+        &self,
+        file_type: FileType,
+        file_path: &str,
+        error: String,
+        category: FileJobErrorCategory,
+        failed_attempt: usize,
+        max_attempts: usize,
+    ) -> Result<()> {
+        self.send(FileJobMessage::new_retrying(
+            file_type,
+            file_path,
+            error,
+            category,
+            failed_attempt,
+            max_attempts,
+        ))
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Could not send FileJobMessage::Retrying."))
+    }
+
+    fn file_cancelled(
+        &self,
+        queue_item: QueueItemID,
+        file_type: FileType,
+        file_path: &str,
+    ) -> Result<()> {
+        self.send(FileJobMessage::new_cancelled(
+            queue_item, file_type, file_path,
+        ))
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Could not send FileJobMessage::Cancelled."))
+    }
+
+    fn log(&self, content: String) -> Result<()> {
+        self.send(FileJobMessage::new_log(content))
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Could not send FileJobMessage::Log."))
+    }
+
+    fn replaygain_finished(&self, album_index: usize) -> Result<()> {
+        self.send(FileJobMessage::new_replaygain_finished(
+            album_index,
+        ))
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!("Could not send FileJobMessage::ReplayGainFinished.")
+        })
+    }
+}
+
+
+/// Messages in ffmpeg's output that indicate a problem with the input file itself
+/// (retrying such a file won't help, see `FileJobErrorCategory::InvalidData`).
+const FFMPEG_INVALID_DATA_MESSAGES: [&str; 6] = [
+    "Invalid data found when processing input",
+    "could not find codec parameters",
+    "Unsupported codec",
+    "does not contain any stream",
+    "Decoding error",
+    "Error while decoding stream",
+];
+
+/// Messages in ffmpeg's output that indicate a filesystem problem.
+const FFMPEG_IO_ERROR_MESSAGES: [&str; 6] = [
+    "Input/output error",
+    "No space left on device",
+    "Permission denied",
+    "No such file or directory",
+    "Resource temporarily unavailable",
+    "Device or resource busy",
+];
+
+/// Messages in ffmpeg's output that indicate an operation timed out.
+const FFMPEG_TIMEOUT_MESSAGES: [&str; 2] =
+    ["Connection timed out", "Operation timed out"];
+
+
+/// Classify a filesystem error for the purposes of retrying
+/// (see `aggregated_library.retryable_error_categories`).
+pub fn categorize_io_error(error: &io::Error) -> FileJobErrorCategory {
+    match error.kind() {
+        io::ErrorKind::TimedOut => FileJobErrorCategory::Timeout,
+        _ => FileJobErrorCategory::Io,
+    }
+}
+
+/// Classify a failed ffmpeg run (non-zero exit code) by looking at its standard error output.
+/// Unrecognized failures are categorized as `FileJobErrorCategory::Other`.
+pub fn categorize_ffmpeg_failure(ffmpeg_stderr: &str) -> FileJobErrorCategory {
+    let contains_any = |messages: &[&str]| {
+        messages
+            .iter()
+            .any(|message| ffmpeg_stderr.contains(message))
+    };
+
+    if contains_any(&FFMPEG_INVALID_DATA_MESSAGES) {
+        FileJobErrorCategory::InvalidData
+    } else if contains_any(&FFMPEG_TIMEOUT_MESSAGES) {
+        FileJobErrorCategory::Timeout
+    } else if contains_any(&FFMPEG_IO_ERROR_MESSAGES) {
+        FileJobErrorCategory::Io
+    } else {
+        FileJobErrorCategory::Other
+    }
+}
+
+
+/// Marker in the names of temporary output files (see `get_temporary_output_file_path`).
+pub const TEMPORARY_OUTPUT_FILE_MARKER: &str = "euphony-tmp";
+
+/// Get the path of the temporary file that a job writes its output into before renaming it
+/// to `target_file_path` (only once the output is complete). This way an interrupted job
+/// never leaves a partial file at `target_file_path` that would look valid to the next scan.
+///
+/// The temporary file is in the same directory (so the rename is atomic) and keeps
+/// the extension (so ffmpeg can still infer the output format from it),
+/// e.g. `01 Intro.mp3` becomes `01 Intro.euphony-tmp.mp3`.
+pub fn get_temporary_output_file_path(target_file_path: &Path) -> PathBuf {
+    let mut temporary_file_name: OsString = target_file_path
+        .file_stem()
+        .map(|file_stem| file_stem.to_os_string())
+        .unwrap_or_default();
+    temporary_file_name.push(".");
+    temporary_file_name.push(TEMPORARY_OUTPUT_FILE_MARKER);
+
+    if let Some(extension) = target_file_path.extension() {
+        temporary_file_name.push(".");
+        temporary_file_name.push(extension);
+    }
+
+    target_file_path.with_file_name(temporary_file_name)
+}
+
+/// Remove a temporary output file (see `get_temporary_output_file_path`), if it exists.
+pub fn remove_temporary_output_file(
+    temporary_file_path: &Path,
+) -> io::Result<()> {
+    match fs::remove_file(temporary_file_path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+
+/// How often `sleep_unless_cancelled` checks the cancellation flag.
+const SLEEP_CANCELLATION_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Sleep for `duration`, waking up early if the cancellation flag is set.
+/// Returns `false` if the sleep was cut short by a cancellation.
+pub fn sleep_unless_cancelled(
+    duration: Duration,
+    cancellation_flag: &AtomicBool,
+) -> bool {
+    let time_sleep_start = Instant::now();
+
+    loop {
+        if cancellation_flag.load(Ordering::SeqCst) {
+            return false;
+        }
+
+        let elapsed = time_sleep_start.elapsed();
+        if elapsed >= duration {
+            return true;
+        }
+
+        thread::sleep(SLEEP_CANCELLATION_CHECK_INTERVAL.min(duration - elapsed));
+    }
 }
 
 
 /// A simple file job abstraction.
 ///
 /// All implementors must have a `run` method that will execute the task.
+/// Failures to process the file are reported to the progress sink as a failed file,
+/// `run` itself only returns an error if the job can't continue at all
+/// (e.g. because the progress sink is gone).
 pub trait FileJob {
+    /// Path of the file this job produces (or removes).
+    fn target_file_path(&self) -> &Path;
+
     fn run(
         &mut self,
         cancellation_flag: &AtomicBool,
-        message_sender: &Sender<FileJobMessage>,
+        progress_sink: &dyn FileProgressSink,
     ) -> Result<()>;
 }
 
 /// Blanket implementation of the `into_cancellable_task` method for all `FileJob`s.
-/// The generated `task_id` is 8 random ASCII characters.
-impl<Job> IntoCancellableTask<FileJobMessage> for Job
+impl<Job, S> IntoCancellableTask<S> for Job
 where
     Job: FileJob + Send + 'static,
+    S: FileProgressSink,
 {
-    fn into_cancellable_task(mut self) -> CancellableTask<FileJobMessage> {
-        // Random 8-character ASCII id.
-        let random_task_id = thread_rng()
-            .sample_iter(Alphanumeric)
-            .take(8)
-            .map(char::from)
-            .collect();
-
+    fn into_cancellable_task(mut self) -> CancellableTask<S> {
         CancellableTask::new(
-            random_task_id,
-            Box::new(move |cancellation_flag, sender| {
-                self.run(cancellation_flag, sender)
-                    .expect("Task errored while running.");
+            self.target_file_path().to_path_buf(),
+            Box::new(move |cancellation_flag, progress_sink| {
+                self.run(cancellation_flag, progress_sink)
             }),
         )
     }
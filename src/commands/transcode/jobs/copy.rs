@@ -1,17 +1,31 @@
-use std::fs;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crossbeam::channel::Sender;
-use euphony_configuration::get_path_extension_or_empty;
+use euphony_configuration::aggregated_library::AggregationMode;
+use euphony_configuration::file_metadata::ChangeDetectionMode;
+use euphony_configuration::library::ExtensionTranscodingRule;
+use euphony_configuration::{get_path_extension_or_empty, Configuration};
 use euphony_library::view::SharedAlbumView;
-use miette::{miette, Context, IntoDiagnostic, Result};
+use miette::{miette, Result};
 
 use crate::commands::transcode::jobs::common::{
+    categorize_io_error,
+    get_temporary_output_file_path,
+    remove_temporary_output_file,
+    sleep_unless_cancelled,
     FileJob,
-    FileJobMessage,
+    FileJobAction,
     FileJobResult,
+    FileProgressSink,
 };
+use crate::commands::transcode::jobs::cover_art::CoverArtDownscaling;
+use crate::commands::transcode::jobs::permissions::copy_permissions_from_source_file;
+use crate::commands::transcode::jobs::playlist_rewrite::PlaylistRewriting;
 use crate::commands::transcode::state::changes::FileType;
 use crate::console::frontends::shared::queue::QueueItemID;
 use crate::globals::is_verbose_enabled;
@@ -19,7 +33,9 @@ use crate::globals::is_verbose_enabled;
 /// One of multiple file jobs.
 ///
 /// `CopyFileJob` simply copies a file (usually data/other files, not audio files) into the
-/// album directory in the aggregated library.
+/// album directory in the aggregated library. Audio files are only copied when mirroring
+/// (see `AggregationMode::Mirror`) or if their extension rule says so
+/// (see `ExtensionTranscodingRule::Copy`).
 pub struct CopyFileJob {
     /// File to copy from.
     source_file_path: PathBuf,
@@ -27,37 +43,79 @@ pub struct CopyFileJob {
     /// File to copy to.
     target_file_path: PathBuf,
 
+    /// Temporary file the copy is written to, which is renamed to `target_file_path`
+    /// once complete (see `get_temporary_output_file_path`).
+    temporary_target_file_path: PathBuf,
+
     /// For missing directory creation purposes, the directory `target_file_path` is in.
     target_file_directory_path: PathBuf,
 
+    /// If set, the source file's permissions are copied onto the target file
+    /// after copying, with these (umask) bits cleared.
+    preserved_permissions_umask: Option<u32>,
+
+    /// If set, the copy is skipped when the target file already matches the source file
+    /// according to this mode (see `aggregated_library.skip_unchanged_data`).
+    skip_if_unchanged: Option<ChangeDetectionMode>,
+
+    /// If set, the file is an image that is downscaled while copying
+    /// (see `aggregated_library.cover_art_max_dimension`).
+    cover_art_downscaling: Option<CoverArtDownscaling>,
+
+    /// If set, the file is a playlist whose references to transcoded audio files
+    /// are rewritten while copying (see `PlaylistRewriting`).
+    playlist_rewriting: Option<PlaylistRewriting>,
+
+    /// Configuration shared with the worker threads: the copy buffer size
+    /// (see `copy_with_progress`) and the retry settings are read
+    /// from its `aggregated_library`.
+    configuration: Arc<Configuration>,
+
+    /// Type of the copied file (data, or audio when mirroring).
+    file_type: FileType,
+
     /// `QueueItemID` this job belongs to.
     queue_item: QueueItemID,
 }
 
 impl CopyFileJob {
     /// Initialize a new `CopyFileJob`.
+    ///
+    /// `audio_source_to_target_paths` maps the album's audio files to their output paths
+    /// (both absolute), so copied playlists can refer to renamed outputs.
     pub fn new(
+        configuration: &Arc<Configuration>,
         album: SharedAlbumView,
         source_file_path: PathBuf,
         target_file_path: PathBuf,
+        audio_source_to_target_paths: &HashMap<PathBuf, PathBuf>,
+        file_type: FileType,
         queue_item: QueueItemID,
     ) -> Result<Self> {
         let album_locked = album.read();
 
         let transcoding_config =
             &album_locked.library_configuration().transcoding;
+        let is_mirroring =
+            configuration.aggregated_library.mode == AggregationMode::Mirror;
 
         /*
          * 1. Sanity checks
          */
-        if !transcoding_config
+        let is_copyable_file = transcoding_config
             .is_path_data_file_by_extension(&source_file_path)?
-        {
+            || transcoding_config
+                .is_path_copied_audio_file_by_extension(&source_file_path)?
+            || (is_mirroring
+                && transcoding_config
+                    .is_path_audio_file_by_extension(&source_file_path)?);
+
+        if !is_copyable_file {
             return Err(miette!(
                 "Invalid source file extension: \"{}\": \
                 expected a tracked data file extension for this library (one of \"{:?}\").",
                 get_path_extension_or_empty(source_file_path)?,
-                transcoding_config.audio_file_extensions,
+                transcoding_config.other_file_extensions,
             ));
         }
 
@@ -66,31 +124,228 @@ impl CopyFileJob {
             .parent()
             .ok_or_else(|| miette!("Could not get target file directory."))?;
 
+        let aggregated_library_config = &configuration.aggregated_library;
+        let preserved_permissions_umask = aggregated_library_config
+            .preserve_permissions
+            .then_some(aggregated_library_config.permissions_umask);
+
+        // Images are only downscaled when transcoding (mirrors are exact copies).
+        let cover_art_downscaling =
+            CoverArtDownscaling::from_configuration(aggregated_library_config)
+                .filter(|_| {
+                    !is_mirroring
+                        && file_type == FileType::Data
+                        && CoverArtDownscaling::is_downscalable_image(
+                            &source_file_path,
+                        )
+                });
+
+        // Playlists are only rewritten when transcoding as well (mirrors keep the audio files).
+        let playlist_rewriting = (!is_mirroring
+            && file_type == FileType::Data
+            && PlaylistRewriting::is_rewritten_playlist(&source_file_path))
+        .then(|| {
+            let transcoded_audio_extensions = transcoding_config
+                .audio_file_extensions
+                .iter()
+                .filter(|extension| {
+                    !matches!(
+                        transcoding_config.extension_rules.get(*extension),
+                        Some(ExtensionTranscodingRule::Copy)
+                    )
+                })
+                .cloned()
+                .collect();
+
+            PlaylistRewriting::new(
+                transcoded_audio_extensions,
+                album_locked
+                    .configuration
+                    .transcoding
+                    .audio_transcoding_output_extension(
+                        &configuration.tools.ffmpeg,
+                    )
+                    .to_string(),
+                PlaylistRewriting::renamed_audio_references(
+                    &source_file_path,
+                    &target_file_path,
+                    audio_source_to_target_paths,
+                ),
+            )
+        });
+
+        // Downscaled images and rewritten playlists never match the source file's size,
+        // so they can't be skipped.
+        let skip_if_unchanged = (aggregated_library_config.skip_unchanged_data
+            && file_type == FileType::Data
+            && cover_art_downscaling.is_none()
+            && playlist_rewriting.is_none())
+        .then_some(configuration.file_metadata.change_detection);
+
         Ok(Self {
             target_file_directory_path: target_file_directory.to_path_buf(),
             source_file_path,
+            temporary_target_file_path: get_temporary_output_file_path(
+                &target_file_path,
+            ),
             target_file_path,
+            preserved_permissions_umask,
+            skip_if_unchanged,
+            cover_art_downscaling,
+            playlist_rewriting,
+            configuration: configuration.clone(),
+            file_type,
             queue_item,
         })
     }
 }
 
+impl CopyFileJob {
+    /// Copy the file once (including its permissions, if configured),
+    /// downscaling it if it is an image that is too large (see `CoverArtDownscaling`)
+    /// or rewriting it if it is a playlist (see `PlaylistRewriting`).
+    ///
+    /// The file is copied into a temporary file first and only renamed into place once
+    /// the copy is complete, so a failed or interrupted copy never leaves a partial file behind.
+    /// The progress of plain copies is reported to `progress_sink` (see `copy_with_progress`).
+    fn copy_file(&self, progress_sink: &dyn FileProgressSink) -> FileJobResult {
+        // TODO Find out a way to create cancellable file copies.
+        let copy_result = match (
+            &self.cover_art_downscaling,
+            &self.playlist_rewriting,
+        ) {
+            (Some(cover_art_downscaling), _) => cover_art_downscaling
+                .copy_downscaled_image(
+                    &self.source_file_path,
+                    &self.temporary_target_file_path,
+                ),
+            (None, Some(playlist_rewriting)) => playlist_rewriting
+                .copy_rewritten_playlist(
+                    &self.source_file_path,
+                    &self.temporary_target_file_path,
+                ),
+            (None, None) => copy_with_progress(
+                &self.source_file_path,
+                &self.temporary_target_file_path,
+                self.configuration.aggregated_library.copy_buffer_bytes,
+                |progress| {
+                    // Progress reports are best-effort, so a failed report is not an error here
+                    // (the final job message will report it).
+                    let _ =
+                        progress_sink.file_progress(self.queue_item, progress);
+                },
+            ),
+        }
+        .and_then(|bytes_copied| {
+            /*
+             * Step 3: (optionally) copy the source file's permissions.
+             */
+            if let Some(umask) = self.preserved_permissions_umask {
+                copy_permissions_from_source_file(
+                    &self.source_file_path,
+                    &self.temporary_target_file_path,
+                    umask,
+                )?;
+            }
+
+            /*
+             * Step 4: move the complete copy into place.
+             */
+            fs::rename(
+                &self.temporary_target_file_path,
+                &self.target_file_path,
+            )?;
+
+            Ok(bytes_copied)
+        });
+
+        match copy_result {
+            Ok(bytes_copied) => {
+                let verbose_info = is_verbose_enabled().then(|| {
+                    format!(
+                        "Copy operation OK. Copied {} bytes.",
+                        bytes_copied
+                    )
+                });
+
+                FileJobResult::Okay {
+                    action: FileJobAction::Copied {
+                        bytes_written: bytes_copied,
+                    },
+                    verbose_info,
+                }
+            }
+            Err(error) => {
+                // Don't leave the partial copy behind (it is overwritten on retries anyway).
+                let _ = remove_temporary_output_file(
+                    &self.temporary_target_file_path,
+                );
+
+                let verbose_info = is_verbose_enabled().then(|| {
+                    format!(
+                        "Copy operation from {:?} to {:?} failed.",
+                        &self.source_file_path, &self.target_file_path
+                    )
+                });
+
+                FileJobResult::Errored {
+                    error: error.to_string(),
+                    category: categorize_io_error(&error),
+                    verbose_info,
+                }
+            }
+        }
+    }
+}
+
 impl FileJob for CopyFileJob {
+    fn target_file_path(&self) -> &Path {
+        &self.target_file_path
+    }
+
     fn run(
         &mut self,
-        _cancellation_flag: &AtomicBool,
-        message_sender: &Sender<FileJobMessage>,
+        cancellation_flag: &AtomicBool,
+        progress_sink: &dyn FileProgressSink,
     ) -> Result<()> {
-        message_sender
-            .send(FileJobMessage::new_starting(
-                self.queue_item,
-                FileType::Data,
-                self.target_file_path.to_string_lossy(),
-            ))
-            .into_diagnostic()
-            .wrap_err_with(|| {
-                miette!("Could not send FileJobMessage::Starting.")
-            })?;
+        let target_file_path = self.target_file_path.to_string_lossy();
+
+        progress_sink.file_started(
+            self.queue_item,
+            self.file_type,
+            &target_file_path,
+        )?;
+
+        /*
+         * Step 0: (optionally) skip the copy if the target file already matches the source.
+         */
+        if let Some(mode) = self.skip_if_unchanged {
+            if target_file_matches_source_file(
+                &self.source_file_path,
+                &self.target_file_path,
+                mode,
+            ) {
+                let verbose_info = is_verbose_enabled().then(|| {
+                    format!(
+                        "Copy skipped: {:?} already matches the source file.",
+                        &self.target_file_path
+                    )
+                });
+
+                progress_sink.file_finished(
+                    self.queue_item,
+                    self.file_type,
+                    &target_file_path,
+                    FileJobResult::Okay {
+                        action: FileJobAction::Skipped,
+                        verbose_info,
+                    },
+                    None,
+                )?;
+
+                return Ok(());
+            }
+        }
 
         /*
          * Step 1: create parent directories if missing.
@@ -102,62 +357,202 @@ impl FileJob for CopyFileJob {
             let verbose_info = is_verbose_enabled()
                 .then(|| format!("fs::create_dir_all error: {error}"));
 
-            message_sender.send(FileJobMessage::new_finished(self.queue_item, FileType::Data, self.target_file_path.to_string_lossy(), FileJobResult::Errored {
+            progress_sink.file_finished(self.queue_item, self.file_type, &target_file_path, FileJobResult::Errored {
                 error: "Could not create target file's missing parent directory.".to_string(),
+                category: categorize_io_error(&error),
                 verbose_info
-            }))
-                .into_diagnostic()
-                .wrap_err_with(|| miette!("Could not send FileJobMessage::Finished"))?;
+            }, None)?;
 
             return Ok(());
         }
 
         /*
-         * Step 2: copy the file.
+         * Step 2: copy the file, retrying failed attempts as configured.
          */
-        // TODO Find out a way to create cancellable file copies.
-        //      (Make sure to handle the half-copied edge-case - we should delete such a file)
-        let copy_result =
-            fs::copy(&self.source_file_path, &self.target_file_path);
+        let aggregated_library_config = &self.configuration.aggregated_library;
+        let max_attempts =
+            aggregated_library_config.failure_max_retries as usize + 1;
+        let mut attempt: usize = 1;
 
-        let processing_result = match copy_result {
-            Ok(bytes_copied) => {
-                let verbose_info = is_verbose_enabled().then(|| {
-                    format!(
-                        "Copy operation OK. Copied {} bytes.",
-                        bytes_copied
-                    )
-                });
+        let processing_result = loop {
+            let processing_result = self.copy_file(progress_sink);
 
-                FileJobResult::Okay { verbose_info }
-            }
-            Err(error) => {
-                let verbose_info = is_verbose_enabled().then(|| {
-                    format!(
-                        "Copy operation from {:?} to {:?} failed.",
-                        &self.source_file_path, &self.target_file_path
-                    )
-                });
+            if let FileJobResult::Errored {
+                error, category, ..
+            } = &processing_result
+            {
+                if attempt < max_attempts
+                    && aggregated_library_config
+                        .retryable_error_categories
+                        .contains(category)
+                {
+                    progress_sink.file_retrying(
+                        self.file_type,
+                        &target_file_path,
+                        error.clone(),
+                        *category,
+                        attempt,
+                        max_attempts,
+                    )?;
 
-                FileJobResult::Errored {
-                    error: error.to_string(),
-                    verbose_info,
+                    if !sleep_unless_cancelled(
+                        Duration::from_secs(
+                            aggregated_library_config.failure_delay_seconds
+                                as u64,
+                        ),
+                        cancellation_flag,
+                    ) {
+                        progress_sink.file_cancelled(
+                            self.queue_item,
+                            self.file_type,
+                            &target_file_path,
+                        )?;
+
+                        return Ok(());
+                    }
+
+                    attempt += 1;
+                    continue;
                 }
             }
+
+            break processing_result;
         };
 
-        message_sender
-            .send(FileJobMessage::new_finished(
-                self.queue_item,
-                FileType::Data,
-                self.target_file_path.to_string_lossy(),
-                processing_result,
-            ))
-            .into_diagnostic()
-            .wrap_err_with(|| {
-                miette!("Could not send FileJobMessage::Finished.")
-            })?;
+        progress_sink.file_finished(
+            self.queue_item,
+            self.file_type,
+            &target_file_path,
+            processing_result,
+            None,
+        )?;
 
         Ok(())
     }
 }
+
+
+/// Copy `source_file_path` to `target_file_path` (overwriting it) using a buffer
+/// of `buffer_size` bytes and return the number of bytes copied.
+///
+/// After each chunk, `on_progress` is called with how far along the copy is (between 0 and 1).
+/// Files that fit into a single buffer don't need progress reports, so they are copied
+/// with `fs::copy` instead (which can use faster, platform-specific copy methods).
+/// Unlike `fs::copy`, the buffered copy doesn't copy the source file's permissions.
+fn copy_with_progress<F: FnMut(f64)>(
+    source_file_path: &Path,
+    target_file_path: &Path,
+    buffer_size: usize,
+    mut on_progress: F,
+) -> io::Result<u64> {
+    let mut source_file = File::open(source_file_path)?;
+    let total_bytes = source_file.metadata()?.len();
+
+    if total_bytes <= buffer_size as u64 {
+        return fs::copy(source_file_path, target_file_path);
+    }
+
+    let mut target_file = File::create(target_file_path)?;
+    let mut buffer = vec![0u8; buffer_size];
+    let mut bytes_copied: u64 = 0;
+
+    loop {
+        let bytes_read = match source_file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(bytes_read) => bytes_read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        target_file.write_all(&buffer[..bytes_read])?;
+        bytes_copied += bytes_read as u64;
+
+        on_progress((bytes_copied as f64 / total_bytes as f64).min(1.0));
+    }
+
+    target_file.flush()?;
+
+    Ok(bytes_copied)
+}
+
+/// Whether `target_file_path` exists and has the same size as `source_file_path`.
+/// If `mode` compares times, the target file must also not be older than the source file
+/// (copies get a fresh modification time, so they can't be compared for equality).
+///
+/// Any error while reading the metadata counts as a mismatch (i.e. the file is copied).
+fn target_file_matches_source_file(
+    source_file_path: &Path,
+    target_file_path: &Path,
+    mode: ChangeDetectionMode,
+) -> bool {
+    let (Ok(source_metadata), Ok(target_metadata)) = (
+        fs::metadata(source_file_path),
+        fs::metadata(target_file_path),
+    ) else {
+        return false;
+    };
+
+    if !target_metadata.is_file()
+        || source_metadata.len() != target_metadata.len()
+    {
+        return false;
+    }
+
+    if mode.compares_time() {
+        return match (
+            source_metadata.modified(),
+            target_metadata.modified(),
+        ) {
+            (Ok(source_modified), Ok(target_modified)) => {
+                target_modified >= source_modified
+            }
+            _ => false,
+        };
+    }
+
+    true
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn copies_files_in_chunks_and_reports_progress() {
+        let directory = TempDir::new().unwrap();
+        let source_file_path = directory.path().join("source.bin");
+        let target_file_path = directory.path().join("target.bin");
+
+        let contents: Vec<u8> =
+            (0..10_000u32).map(|index| index as u8).collect();
+        fs::write(&source_file_path, &contents).unwrap();
+
+        let mut reported_progress: Vec<f64> = Vec::new();
+        let bytes_copied = copy_with_progress(
+            &source_file_path,
+            &target_file_path,
+            4096,
+            |progress| reported_progress.push(progress),
+        )
+        .unwrap();
+
+        assert_eq!(bytes_copied, 10_000);
+        assert_eq!(fs::read(&target_file_path).unwrap(), contents);
+        assert_eq!(reported_progress.len(), 3);
+        assert_eq!(reported_progress.last(), Some(&1.0));
+
+        // Files that fit into the buffer are copied without progress reports.
+        reported_progress.clear();
+        copy_with_progress(
+            &source_file_path,
+            &target_file_path,
+            16_384,
+            |progress| reported_progress.push(progress),
+        )
+        .unwrap();
+        assert!(reported_progress.is_empty());
+    }
+}
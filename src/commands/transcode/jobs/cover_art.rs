@@ -0,0 +1,127 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use euphony_configuration::aggregated_library::AggregatedLibraryConfiguration;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::io::Reader as ImageReader;
+use image::{DynamicImage, ImageError, ImageFormat, ImageOutputFormat};
+
+/// Extensions (lowercase) of the images that can be downscaled
+/// (see `aggregated_library.cover_art_max_dimension`).
+const DOWNSCALABLE_IMAGE_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+
+/// How cover art images are downscaled
+/// (see `aggregated_library.cover_art_max_dimension` and `cover_art_jpeg_quality`).
+#[derive(Copy, Clone, Debug)]
+pub struct CoverArtDownscaling {
+    /// Images larger than this many pixels in width or height are downscaled to fit.
+    pub max_dimension: u32,
+
+    /// Quality (1 to 100) of downscaled JPEG images.
+    pub jpeg_quality: u8,
+}
+
+impl CoverArtDownscaling {
+    /// Returns `None` if downscaling is disabled in the given configuration.
+    pub fn from_configuration(
+        aggregated_library: &AggregatedLibraryConfiguration,
+    ) -> Option<Self> {
+        aggregated_library
+            .cover_art_max_dimension
+            .map(|max_dimension| Self {
+                max_dimension,
+                jpeg_quality: aggregated_library.cover_art_jpeg_quality,
+            })
+    }
+
+    /// Whether the file is an image that can be downscaled (by its extension).
+    pub fn is_downscalable_image(file_path: &Path) -> bool {
+        file_path.extension().is_some_and(|extension| {
+            DOWNSCALABLE_IMAGE_EXTENSIONS.contains(
+                &extension.to_string_lossy().to_ascii_lowercase().as_str(),
+            )
+        })
+    }
+
+    /// Whether the image is larger than `max_dimension` in width or height
+    /// (only the image header is read).
+    pub fn exceeds_max_dimension(
+        &self,
+        image_file_path: &Path,
+    ) -> io::Result<bool> {
+        let (width, height) = image::image_dimensions(image_file_path)
+            .map_err(image_error_to_io_error)?;
+
+        Ok(width.max(height) > self.max_dimension)
+    }
+
+    /// Write the image at `source_file_path` into `target_file_path`, downscaled to fit
+    /// `max_dimension` if it is larger. Otherwise (or if the image's size can't be read,
+    /// e.g. because it isn't actually an image), the file is copied as-is.
+    ///
+    /// The downscaled image keeps the source image's format (JPEG images are encoded
+    /// with `jpeg_quality`). Returns the number of bytes written.
+    pub fn copy_downscaled_image(
+        &self,
+        source_file_path: &Path,
+        target_file_path: &Path,
+    ) -> io::Result<u64> {
+        if !self
+            .exceeds_max_dimension(source_file_path)
+            .unwrap_or(false)
+        {
+            return fs::copy(source_file_path, target_file_path);
+        }
+
+        let image_reader =
+            ImageReader::open(source_file_path)?.with_guessed_format()?;
+        let image_format = image_reader.format();
+
+        let downscaled_image = image_reader
+            .decode()
+            .map_err(image_error_to_io_error)?
+            .resize(
+                self.max_dimension,
+                self.max_dimension,
+                FilterType::Lanczos3,
+            );
+
+        let mut target_file = BufWriter::new(File::create(target_file_path)?);
+
+        match image_format {
+            Some(ImageFormat::Png) => downscaled_image
+                .write_to(&mut target_file, ImageOutputFormat::Png)
+                .map_err(image_error_to_io_error)?,
+            _ => {
+                // JPEG has no alpha channel.
+                let downscaled_image =
+                    DynamicImage::ImageRgb8(downscaled_image.to_rgb8());
+
+                JpegEncoder::new_with_quality(
+                    &mut target_file,
+                    self.jpeg_quality,
+                )
+                .encode_image(&downscaled_image)
+                .map_err(image_error_to_io_error)?
+            }
+        };
+
+        target_file
+            .into_inner()
+            .map_err(|error| error.into_error())?;
+
+        Ok(fs::metadata(target_file_path)?.len())
+    }
+}
+
+/// Image decoding errors are reported as `InvalidData` I/O errors
+/// (so that failed copies can be categorized like any other I/O error).
+fn image_error_to_io_error(error: ImageError) -> io::Error {
+    match error {
+        ImageError::IoError(error) => error,
+        error => io::Error::new(io::ErrorKind::InvalidData, error),
+    }
+}
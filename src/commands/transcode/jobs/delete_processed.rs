@@ -1,15 +1,17 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicBool;
 
-use crossbeam::channel::Sender;
+use euphony_configuration::aggregated_library::FileJobErrorCategory;
 use euphony_configuration::Configuration;
-use miette::{miette, Context, IntoDiagnostic, Result};
+use miette::{miette, Result};
 
 use crate::commands::transcode::jobs::common::{
+    categorize_io_error,
     FileJob,
-    FileJobMessage,
+    FileJobAction,
     FileJobResult,
+    FileProgressSink,
 };
 use crate::commands::transcode::state::changes::FileType;
 use crate::console::frontends::shared::queue::QueueItemID;
@@ -71,32 +73,37 @@ impl DeleteProcessedFileJob {
 }
 
 impl FileJob for DeleteProcessedFileJob {
+    fn target_file_path(&self) -> &Path {
+        &self.target_file_path
+    }
+
     fn run(
         &mut self,
         _cancellation_flag: &AtomicBool,
-        message_sender: &Sender<FileJobMessage>,
+        progress_sink: &dyn FileProgressSink,
     ) -> Result<()> {
-        message_sender
-            .send(FileJobMessage::new_starting(
-                self.queue_item,
-                self.file_type,
-                self.target_file_path.to_string_lossy(),
-            ))
-            .into_diagnostic()
-            .wrap_err_with(|| {
-                miette!("Could not send FileJobMessage::Starting.")
-            })?;
+        let target_file_path = self.target_file_path.to_string_lossy();
+
+        progress_sink.file_started(
+            self.queue_item,
+            self.file_type,
+            &target_file_path,
+        )?;
 
         let processing_result = if !self.target_file_path.is_file() {
             if self.ignore_if_missing {
                 let verbose_info = is_verbose_enabled()
                     .then(|| "File did not exist, but ignore_if_missing==true - skipping.".to_string());
 
-                FileJobResult::Okay { verbose_info }
+                FileJobResult::Okay {
+                    action: FileJobAction::Skipped,
+                    verbose_info,
+                }
             } else {
                 FileJobResult::Errored {
                     error: "File did not exist and ignore_if_missing != true!"
                         .to_string(),
+                    category: FileJobErrorCategory::Io,
                     verbose_info: None,
                 }
             }
@@ -104,25 +111,25 @@ impl FileJob for DeleteProcessedFileJob {
             let removal_result = fs::remove_file(&self.target_file_path);
 
             match removal_result {
-                Ok(_) => FileJobResult::Okay { verbose_info: None },
+                Ok(_) => FileJobResult::Okay {
+                    action: FileJobAction::Deleted,
+                    verbose_info: None,
+                },
                 Err(error) => FileJobResult::Errored {
                     error: error.to_string(),
+                    category: categorize_io_error(&error),
                     verbose_info: None,
                 },
             }
         };
 
-        message_sender
-            .send(FileJobMessage::new_finished(
-                self.queue_item,
-                self.file_type,
-                self.target_file_path.to_string_lossy(),
-                processing_result,
-            ))
-            .into_diagnostic()
-            .wrap_err_with(|| {
-                miette!("Could not send FileJobMessage::Finished.")
-            })?;
+        progress_sink.file_finished(
+            self.queue_item,
+            self.file_type,
+            &target_file_path,
+            processing_result,
+            None,
+        )?;
 
         Ok(())
     }
@@ -1,6 +1,9 @@
 pub mod common;
 pub mod copy;
+pub mod cover_art;
 pub mod delete_processed;
+pub mod permissions;
+pub mod playlist_rewrite;
 pub mod thread_pool;
 pub mod transcode;
 
@@ -0,0 +1,34 @@
+use std::io;
+use std::path::Path;
+
+/// Copy the permission (mode) bits of `source_file_path` onto `target_file_path`,
+/// clearing any bits that are set in `umask`
+/// (see `aggregated_library.preserve_permissions`).
+#[cfg(unix)]
+pub fn copy_permissions_from_source_file(
+    source_file_path: &Path,
+    target_file_path: &Path,
+    umask: u32,
+) -> io::Result<()> {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let source_mode = fs::metadata(source_file_path)?.permissions().mode();
+    let target_mode = source_mode & 0o7777 & !umask;
+
+    fs::set_permissions(
+        target_file_path,
+        fs::Permissions::from_mode(target_mode),
+    )
+}
+
+/// Permission bits are a Unix concept, so this is a no-op on other platforms
+/// (see `aggregated_library.preserve_permissions`).
+#[cfg(not(unix))]
+pub fn copy_permissions_from_source_file(
+    _source_file_path: &Path,
+    _target_file_path: &Path,
+    _umask: u32,
+) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Extensions (lowercase) of the playlist files whose references to audio files
+/// are rewritten when they are copied (see `PlaylistRewriting`).
+const REWRITTEN_PLAYLIST_EXTENSIONS: [&str; 2] = ["m3u", "m3u8"];
+
+
+/// How the references in copied playlists (`.m3u`/`.m3u8`) are rewritten: references to
+/// audio files that are transcoded get the transcoded file extension, so the playlists
+/// keep working in the aggregated library. References to audio files whose output
+/// is renamed (e.g. by `aggregated_library.output_template`) point to the renamed file.
+///
+/// Comments (`#EXTM3U`, `#EXTINF`, ...), URLs and references to other files are left alone
+/// and paths otherwise stay exactly as they were (e.g. relative paths remain relative).
+#[derive(Clone, Debug)]
+pub struct PlaylistRewriting {
+    /// Extensions (lowercase) of the audio files that are transcoded.
+    transcoded_audio_extensions: HashSet<String>,
+
+    /// Extension of the transcoded audio files.
+    output_extension: String,
+
+    /// References (relative to the playlist, with `/` separators) to audio files whose output
+    /// is renamed, mapped to the references to their output (see `renamed_audio_references`).
+    renamed_audio_references: HashMap<String, String>,
+}
+
+impl PlaylistRewriting {
+    pub fn new(
+        transcoded_audio_extensions: HashSet<String>,
+        output_extension: String,
+        renamed_audio_references: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            transcoded_audio_extensions,
+            output_extension,
+            renamed_audio_references,
+        }
+    }
+
+    /// Find the audio files (absolute source paths mapped to absolute output paths)
+    /// whose output isn't just the source path with another extension
+    /// and map their references relative to the playlist in the source library
+    /// to references relative to the playlist in the aggregated library.
+    pub fn renamed_audio_references(
+        playlist_source_path: &Path,
+        playlist_target_path: &Path,
+        audio_source_to_target_paths: &HashMap<PathBuf, PathBuf>,
+    ) -> HashMap<String, String> {
+        let (Some(source_directory), Some(target_directory)) = (
+            playlist_source_path.parent(),
+            playlist_target_path.parent(),
+        ) else {
+            return HashMap::new();
+        };
+
+        audio_source_to_target_paths
+            .iter()
+            .filter_map(|(source_path, target_path)| {
+                let source_reference =
+                    relative_reference(source_directory, source_path)?;
+                let target_reference =
+                    relative_reference(target_directory, target_path)?;
+
+                let is_renamed = Path::new(&source_reference).with_extension("")
+                    != Path::new(&target_reference).with_extension("");
+
+                is_renamed.then_some((source_reference, target_reference))
+            })
+            .collect()
+    }
+
+    /// Whether the file is a playlist whose references are rewritten (by its extension).
+    pub fn is_rewritten_playlist(file_path: &Path) -> bool {
+        file_path.extension().is_some_and(|extension| {
+            REWRITTEN_PLAYLIST_EXTENSIONS.contains(
+                &extension.to_string_lossy().to_ascii_lowercase().as_str(),
+            )
+        })
+    }
+
+    /// Rewrite a single playlist line (without its line ending).
+    /// Playlists aren't necessarily UTF-8, so lines are handled as raw bytes.
+    fn rewrite_line(&self, line: &[u8]) -> Vec<u8> {
+        // Keep any trailing whitespace (e.g. `\r` of Windows line endings) as it is.
+        let path_length = line
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(0, |last_index| last_index + 1);
+        let (path, trailing_whitespace) = line.split_at(path_length);
+
+        let is_reference = path
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .is_some_and(|first_byte| *first_byte != b'#');
+        let is_url = path.windows(3).any(|bytes| bytes == b"://");
+        if !is_reference || is_url {
+            return line.to_vec();
+        }
+
+        if let Some(renamed_reference) =
+            std::str::from_utf8(path).ok().and_then(|path| {
+                self.renamed_audio_references
+                    .get(&normalize_reference(path))
+            })
+        {
+            let mut rewritten_line = renamed_reference.as_bytes().to_vec();
+            rewritten_line.extend_from_slice(trailing_whitespace);
+
+            return rewritten_line;
+        }
+
+        let file_name_start = path
+            .iter()
+            .rposition(|byte| *byte == b'/' || *byte == b'\\')
+            .map_or(0, |separator_index| separator_index + 1);
+        let Some(extension_start) = path[file_name_start..]
+            .iter()
+            .rposition(|byte| *byte == b'.')
+            .map(|dot_index| file_name_start + dot_index + 1)
+        else {
+            return line.to_vec();
+        };
+
+        let extension = String::from_utf8_lossy(&path[extension_start..])
+            .to_ascii_lowercase();
+        if !self.transcoded_audio_extensions.contains(&extension) {
+            return line.to_vec();
+        }
+
+        let mut rewritten_line = path[..extension_start].to_vec();
+        rewritten_line.extend_from_slice(self.output_extension.as_bytes());
+        rewritten_line.extend_from_slice(trailing_whitespace);
+
+        rewritten_line
+    }
+
+    /// Rewrite the contents of a playlist (see `PlaylistRewriting`).
+    pub fn rewrite_playlist(&self, playlist: &[u8]) -> Vec<u8> {
+        playlist
+            .split(|byte| *byte == b'\n')
+            .map(|line| self.rewrite_line(line))
+            .collect::<Vec<_>>()
+            .join(&b'\n')
+    }
+
+    /// Write the playlist at `source_file_path` into `target_file_path`
+    /// with its references rewritten. Returns the number of bytes written.
+    pub fn copy_rewritten_playlist(
+        &self,
+        source_file_path: &Path,
+        target_file_path: &Path,
+    ) -> io::Result<u64> {
+        let rewritten_playlist =
+            self.rewrite_playlist(&fs::read(source_file_path)?);
+
+        fs::write(target_file_path, &rewritten_playlist)?;
+
+        Ok(rewritten_playlist.len() as u64)
+    }
+}
+
+/// Normalize a playlist reference for looking it up in
+/// `PlaylistRewriting::renamed_audio_references`: `/` separators and no leading `./`.
+fn normalize_reference(reference: &str) -> String {
+    let reference = reference.trim().replace('\\', "/");
+
+    reference
+        .strip_prefix("./")
+        .map(str::to_string)
+        .unwrap_or(reference)
+}
+
+/// Build the reference from `directory` to `path` (both absolute), using `..` where needed
+/// and `/` as the separator. Returns `None` if `path` can't be expressed as valid UTF-8.
+fn relative_reference(directory: &Path, path: &Path) -> Option<String> {
+    let directory_components: Vec<Component> = directory.components().collect();
+    let path_components: Vec<Component> = path.components().collect();
+
+    let common_length = directory_components
+        .iter()
+        .zip(&path_components)
+        .take_while(|(first, second)| first == second)
+        .count();
+
+    let mut reference_components: Vec<&str> =
+        vec![".."; directory_components.len() - common_length];
+    for component in &path_components[common_length..] {
+        reference_components.push(component.as_os_str().to_str()?);
+    }
+
+    Some(reference_components.join("/"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::path::{Path, PathBuf};
+
+    use super::PlaylistRewriting;
+
+    #[test]
+    fn rewrites_transcoded_audio_references() {
+        let rewriting = PlaylistRewriting::new(
+            HashSet::from(["flac".to_string(), "wav".to_string()]),
+            "mp3".to_string(),
+            HashMap::new(),
+        );
+
+        let playlist = b"#EXTM3U\r\n\
+            #EXTINF:123,Artist - Song.flac\r\n\
+            01 - Song.FLAC\r\n\
+            CD 2/02 - Other.wav\r\n\
+            ..\\Booklet\\scan.jpg\r\n\
+            https://example.com/stream.flac\r\n";
+
+        assert_eq!(
+            String::from_utf8(rewriting.rewrite_playlist(playlist)).unwrap(),
+            "#EXTM3U\r\n\
+            #EXTINF:123,Artist - Song.flac\r\n\
+            01 - Song.mp3\r\n\
+            CD 2/02 - Other.mp3\r\n\
+            ..\\Booklet\\scan.jpg\r\n\
+            https://example.com/stream.flac\r\n"
+        );
+    }
+
+    #[test]
+    fn rewrites_references_to_renamed_audio_files() {
+        let audio_source_to_target_paths = HashMap::from([
+            (
+                PathBuf::from("/source/Artist/Album/01 Song.flac"),
+                PathBuf::from("/target/Artist - Album/01. Song.mp3"),
+            ),
+            (
+                PathBuf::from("/source/Artist/Album/CD 2/02 Other.flac"),
+                PathBuf::from("/target/Artist - Album/CD 2/02 Other.mp3"),
+            ),
+        ]);
+
+        let rewriting = PlaylistRewriting::new(
+            HashSet::from(["flac".to_string()]),
+            "mp3".to_string(),
+            PlaylistRewriting::renamed_audio_references(
+                Path::new("/source/Artist/Album/list.m3u8"),
+                Path::new("/target/Artist - Album/list.m3u8"),
+                &audio_source_to_target_paths,
+            ),
+        );
+
+        assert_eq!(
+            String::from_utf8(
+                rewriting.rewrite_playlist(
+                    b"./01 Song.flac\r\nCD 2\\02 Other.flac\r\n"
+                )
+            )
+            .unwrap(),
+            "01. Song.mp3\r\nCD 2\\02 Other.mp3\r\n"
+        );
+    }
+}
@@ -1,16 +1,17 @@
+use std::any::Any;
 use std::cmp::min;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
 use std::time::Duration;
 
-use crossbeam::channel::Sender;
-use miette::{miette, IntoDiagnostic, Result};
+use miette::{miette, Report, Result};
 use parking_lot::{Mutex, MutexGuard};
+use tracing::{debug, trace};
 
-use crate::commands::transcode::jobs::{CancellableTask, FileJobMessage};
-use crate::globals::is_verbose_enabled;
+use crate::commands::transcode::jobs::{CancellableTask, FileProgressSink};
 
 // How fast the thread pool's coordinator cleans up and creates new tasks ("ticks", if you will).
 const THREAD_POOL_COORDINATOR_TICK_DURATION: Duration =
@@ -21,6 +22,51 @@ pub enum ThreadPoolStopReason {
     CancellationFlagSet,
 }
 
+/// A task that returned an error or panicked (instead of reporting its result
+/// to the progress sink), see `CancellableThreadPool::take_failed_tasks`.
+#[derive(Debug)]
+pub struct FailedTask {
+    /// Path of the file the task was processing.
+    pub file_path: PathBuf,
+
+    pub error: Report,
+}
+
+/// A task that is currently running in its own thread.
+struct RunningTask {
+    file_path: PathBuf,
+    thread_handle: JoinHandle<Result<()>>,
+}
+
+impl RunningTask {
+    /// Wait for the task's thread to exit. Errors and panics are both reported as a `FailedTask`.
+    fn join(self) -> Option<FailedTask> {
+        let error = match self.thread_handle.join() {
+            Ok(Ok(())) => return None,
+            Ok(Err(error)) => error,
+            Err(panic_payload) => miette!(
+                "Worker panicked: {}",
+                panic_payload_to_string(panic_payload.as_ref())
+            ),
+        };
+
+        Some(FailedTask {
+            file_path: self.file_path,
+            error,
+        })
+    }
+}
+
+fn panic_payload_to_string(panic_payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic_payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic_payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "(unknown panic payload)".to_string()
+    }
+}
+
 /// This is an implementation of a cancellable thread pool.
 /// There can be up to `max_num_threads` tasks running at once, each in its own thread.
 /// New tasks are added from the queue automatically (once `start()` is called).
@@ -28,9 +74,9 @@ pub enum ThreadPoolStopReason {
 /// Each queued cancellable task receives two arguments:
 /// - An AtomicBool with which it can check for task cancellation
 ///   (when `true` the task has been cancelled).
-/// - A message sender (`Sender<C>`) that the worker can use to relay messages back to the main
-///   thread. The message type is generic (`C`), but it must be `Send`.
-pub struct CancellableThreadPool {
+/// - A progress sink (`S`, see `FileProgressSink`) that the worker reports its progress to.
+///   The transcode command uses a `Sender<FileJobMessage>` that relays it back to the main thread.
+pub struct CancellableThreadPool<S: FileProgressSink + Clone> {
     /// Maximum amount of tasks (threads) that can be running concurrently.
     max_num_threads: usize,
 
@@ -39,36 +85,36 @@ pub struct CancellableThreadPool {
     /// (how and when depends entirely on their implementation).
     task_cancellation_flag: Arc<AtomicBool>,
 
-    /// A multi-producer single-consumer Sender. Distributed across workers who can send
-    /// messages back to the user-provided channel's `Receiver`. The data sent can be anything
-    /// that can be safely sent across threads (`Send`).
-    worker_message_sender: Sender<FileJobMessage>,
+    /// Progress sink that is distributed across workers (each gets its own clone).
+    /// The thread pool also logs to it when verbose output is enabled.
+    progress_sink: S,
 
     /// If `Some`, a handle to the pool coordinator (handles spawning and cleaning up tasks).
     pool_coordination_thread: Option<JoinHandle<Result<ThreadPoolStopReason>>>,
 
     /// A vector of pending tasks.
-    pending_tasks: Arc<Mutex<Vec<CancellableTask<FileJobMessage>>>>,
+    pending_tasks: Arc<Mutex<Vec<CancellableTask<S>>>>,
 
     /// A vector of currently-running tasks. Never larger than `max_num_threads`.
-    running_tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    running_tasks: Arc<Mutex<Vec<RunningTask>>>,
+
+    /// Tasks that failed and haven't been taken yet (see `take_failed_tasks`).
+    failed_tasks: Arc<Mutex<Vec<FailedTask>>>,
 }
 
-impl CancellableThreadPool {
+impl<S: FileProgressSink + Clone> CancellableThreadPool<S> {
     /// Create a new cancellable thread pool.
-    pub fn new(
-        thread_pool_size: usize,
-        worker_message_sender: Sender<FileJobMessage>,
-    ) -> Self {
+    pub fn new(thread_pool_size: usize, progress_sink: S) -> Self {
         Self {
             max_num_threads: thread_pool_size,
             task_cancellation_flag: Arc::new(AtomicBool::new(false)),
-            worker_message_sender,
+            progress_sink,
             pool_coordination_thread: None,
             pending_tasks: Arc::new(Mutex::new(Vec::new())),
             running_tasks: Arc::new(Mutex::new(Vec::with_capacity(
                 thread_pool_size,
             ))),
+            failed_tasks: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -81,28 +127,22 @@ impl CancellableThreadPool {
 
         let max_num_threads = self.max_num_threads;
         let cancellation_flag = self.task_cancellation_flag.clone();
-        let worker_message_sender = self.worker_message_sender.clone();
+        let progress_sink = self.progress_sink.clone();
         let pending_tasks_copy = self.pending_tasks.clone();
         let running_tasks_copy = self.running_tasks.clone();
+        let failed_tasks_copy = self.failed_tasks.clone();
 
         let coordinator_thread_handle = thread::spawn(move || {
-            let out_of_loop_sender = worker_message_sender.clone();
-
             let coordinator_result = CancellableThreadPool::run_coordinator(
                 max_num_threads,
                 cancellation_flag,
-                worker_message_sender,
+                &progress_sink,
                 pending_tasks_copy,
                 running_tasks_copy,
+                failed_tasks_copy,
             );
 
-            if is_verbose_enabled() {
-                out_of_loop_sender
-                    .send(FileJobMessage::new_log(
-                        "ThreadPool: coordinator thread has stopped.",
-                    ))
-                    .into_diagnostic()?;
-            }
+            debug!("ThreadPool: coordinator thread has stopped.");
 
             coordinator_result
         });
@@ -114,25 +154,15 @@ impl CancellableThreadPool {
 
     /// Enter the given cancellable task into the thread-pool task queue.
     ///
-    /// The cancellable task's message sender type must match the thread-pool's message sender.
-    pub fn queue_task(
-        &mut self,
-        cancellable_task: CancellableTask<FileJobMessage>,
-    ) {
+    /// The cancellable task's progress sink type must match the thread-pool's progress sink.
+    pub fn queue_task(&mut self, cancellable_task: CancellableTask<S>) {
         let mut exclusive_queue_lock = self.get_locked_pending_tasks();
         exclusive_queue_lock.push(cancellable_task);
     }
 
-    /// Checks whether there are any running or pending tasks in this thread pool.
-    pub fn has_tasks_left(&self) -> bool {
-        let (pending_vec_empty, running_vec_empty) = {
-            let running_tasks = self.get_locked_running_tasks();
-            let pending_tasks = self.get_locked_pending_tasks();
-
-            (pending_tasks.is_empty(), running_tasks.is_empty())
-        };
-
-        !pending_vec_empty || !running_vec_empty
+    /// Returns the number of queued tasks that haven't been started yet.
+    pub fn num_pending_tasks(&self) -> usize {
+        self.get_locked_pending_tasks().len()
     }
 
     /// Returns `true` when the coordinator thread of the thread pool is running
@@ -144,49 +174,49 @@ impl CancellableThreadPool {
         }
     }
 
-    /// Get the cancellation flag of this thread pool. This is useful for
-    /// triggering cancellation externally (by just setting this `AtomicBool` to `true`).
-    #[allow(dead_code)]
-    pub fn cancellation_flag(&self) -> Arc<AtomicBool> {
-        self.task_cancellation_flag.clone()
+    /// Remove and return the tasks that failed (returned an error or panicked) so far.
+    ///
+    /// A failed task did not report its file as finished, so callers waiting
+    /// for all files to finish should check this regularly.
+    pub fn take_failed_tasks(&self) -> Vec<FailedTask> {
+        self.failed_tasks.lock().drain(..).collect()
     }
 
     /// This method will set the cancellation flag and wait for the thread pool to finish.
-    pub fn set_cancellation_and_join(self) -> Result<ThreadPoolStopReason> {
+    ///
+    /// Returns the tasks that failed and haven't been taken yet (see `take_failed_tasks`).
+    pub fn set_cancellation_and_join(self) -> Result<Vec<FailedTask>> {
         self.task_cancellation_flag.store(true, Ordering::SeqCst);
         self.join()
     }
 
     /// This method will wait for the thread pool to finish.
     /// Note that this method does **not** set the cancellation flag.
-    pub fn join(self) -> Result<ThreadPoolStopReason> {
-        if self.pool_coordination_thread.is_none() {
+    ///
+    /// Returns the tasks that failed and haven't been taken yet (see `take_failed_tasks`).
+    /// As all running tasks are joined before the coordinator exits, no failure is lost.
+    pub fn join(mut self) -> Result<Vec<FailedTask>> {
+        let Some(coordinator_thread_handle) =
+            self.pool_coordination_thread.take()
+        else {
             return Err(miette!("Thread pool is not running."));
-        }
-
-        // Checked above.
-        let coordinator_thread_handle = self.pool_coordination_thread.unwrap();
+        };
 
         coordinator_thread_handle.join().map_err(|error| {
             miette!(
                 "ThreadPool coordinator thread exited abnormally. {:?}",
                 error
             )
-        })?
+        })??;
+
+        Ok(self.take_failed_tasks())
     }
 
     /// Lock and return the list of pending tasks.
-    fn get_locked_pending_tasks(
-        &self,
-    ) -> MutexGuard<Vec<CancellableTask<FileJobMessage>>> {
+    fn get_locked_pending_tasks(&self) -> MutexGuard<Vec<CancellableTask<S>>> {
         self.pending_tasks.lock()
     }
 
-    /// Lock and return the list of currently-running task handles.
-    fn get_locked_running_tasks(&self) -> MutexGuard<Vec<JoinHandle<()>>> {
-        self.running_tasks.lock()
-    }
-
     /// This method is the coordinator function. It should be executed in its own thread.
     ///
     /// The goal of this method is to manage pending and active threads by cleaning up finished
@@ -195,9 +225,10 @@ impl CancellableThreadPool {
     fn run_coordinator(
         max_num_threads: usize,
         cancellation_flag: Arc<AtomicBool>,
-        worker_message_sender: Sender<FileJobMessage>,
-        pending_tasks: Arc<Mutex<Vec<CancellableTask<FileJobMessage>>>>,
-        running_tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+        progress_sink: &S,
+        pending_tasks: Arc<Mutex<Vec<CancellableTask<S>>>>,
+        running_tasks: Arc<Mutex<Vec<RunningTask>>>,
+        failed_tasks: Arc<Mutex<Vec<FailedTask>>>,
     ) -> Result<ThreadPoolStopReason> {
         loop {
             let cancellation_flag_value =
@@ -207,28 +238,22 @@ impl CancellableThreadPool {
                 // We should wait for all active threads first though - the threads will, if
                 // properly implemented, soon see the cancellation flag and exit accordingly.
 
-                if is_verbose_enabled() {
-                    worker_message_sender.send(
-                        FileJobMessage::new_log("ThreadPool: cancellation flag set, waiting for active workers, clearing pending tasks and joining.")
-                    )
-                        .into_diagnostic()?;
-                }
+                debug!(
+                    "ThreadPool: cancellation flag set, waiting for active workers, \
+                    clearing pending tasks and joining."
+                );
 
                 let mut running_tasks_locked = running_tasks.lock();
                 for task in running_tasks_locked.drain(..) {
-                    task.join().expect("Thread pool worker panicked!");
+                    if let Some(failed_task) = task.join() {
+                        failed_tasks.lock().push(failed_task);
+                    }
                 }
 
                 let mut pending_tasks_locked = pending_tasks.lock();
                 pending_tasks_locked.clear();
 
-                if is_verbose_enabled() {
-                    worker_message_sender
-                        .send(FileJobMessage::new_log(
-                            "ThreadPool: exiting coordinator thread.",
-                        ))
-                        .into_diagnostic()?;
-                }
+                debug!("ThreadPool: exiting coordinator thread.");
 
 
                 return Ok(ThreadPoolStopReason::CancellationFlagSet);
@@ -243,18 +268,16 @@ impl CancellableThreadPool {
                 let mut finished_tasks_indices: Vec<usize> =
                     Vec::with_capacity(running_tasks_locked.len());
                 for (index, task) in running_tasks_locked.iter().enumerate() {
-                    if task.is_finished() {
+                    if task.thread_handle.is_finished() {
                         finished_tasks_indices.push(index);
                     }
                 }
 
-                if !finished_tasks_indices.is_empty() && is_verbose_enabled() {
-                    worker_message_sender
-                        .send(FileJobMessage::new_log(format!(
-                            "ThreadPool: {} tasks finished since last tick.",
-                            finished_tasks_indices.len()
-                        )))
-                        .into_diagnostic()?;
+                if !finished_tasks_indices.is_empty() {
+                    trace!(
+                        num_finished_tasks = finished_tasks_indices.len(),
+                        "ThreadPool: tasks finished since last tick."
+                    );
                 }
 
                 if !finished_tasks_indices.is_empty() {
@@ -263,9 +286,9 @@ impl CancellableThreadPool {
                     // the vector is reversed.
                     finished_tasks_indices.iter().rev().for_each(|index| {
                         let finished_task = running_tasks_locked.remove(*index);
-                        finished_task
-                            .join()
-                            .expect("Thread pool worker panicked!");
+                        if let Some(failed_task) = finished_task.join() {
+                            failed_tasks.lock().push(failed_task);
+                        }
                     });
                 }
 
@@ -273,7 +296,7 @@ impl CancellableThreadPool {
                 let threads_to_limit =
                     max_num_threads - running_tasks_locked.len();
                 if threads_to_limit > 0 {
-                    let tasks_to_run: Vec<CancellableTask<FileJobMessage>> = {
+                    let tasks_to_run: Vec<CancellableTask<S>> = {
                         let mut pending_tasks_locked = pending_tasks.lock();
 
                         let pending_tasks_num = pending_tasks_locked.len();
@@ -286,25 +309,27 @@ impl CancellableThreadPool {
                     // Create new threads for each new task.
                     for new_task in tasks_to_run {
                         let cancellation_flag_copy = cancellation_flag.clone();
-                        let message_sender_copy = worker_message_sender.clone();
-
-                        let task_thread_handle = thread::spawn(move || {
+                        let progress_sink_copy = progress_sink.clone();
+                        let file_path = new_task.file_path().to_path_buf();
+                        trace!(
+                            file = ?file_path,
+                            "ThreadPool: spawning worker."
+                        );
+
+                        let thread_handle = thread::spawn(move || {
                             new_task.execute_task(
                                 &cancellation_flag_copy,
-                                &message_sender_copy,
+                                &progress_sink_copy,
                             )
                         });
 
-                        running_tasks_locked.push(task_thread_handle);
+                        running_tasks_locked.push(RunningTask {
+                            file_path,
+                            thread_handle,
+                        });
                     }
-                } else if !finished_tasks_indices.is_empty()
-                    && is_verbose_enabled()
-                {
-                    worker_message_sender
-                        .send(FileJobMessage::new_log(
-                            "ThreadPool: no pending tasks to spawn right now",
-                        ))
-                        .into_diagnostic()?;
+                } else if !finished_tasks_indices.is_empty() {
+                    trace!("ThreadPool: no pending tasks to spawn right now.");
                 }
             }
 
@@ -312,3 +337,64 @@ impl CancellableThreadPool {
         }
     }
 }
+
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crossbeam::channel::{self, Sender};
+    use miette::miette;
+
+    use super::CancellableThreadPool;
+    use crate::commands::transcode::jobs::{CancellableTask, FileJobMessage};
+    use crate::globals::VERBOSE;
+
+    #[test]
+    fn reports_errored_and_panicked_tasks() {
+        VERBOSE.set(false);
+
+        let (sender, _receiver) = channel::unbounded::<FileJobMessage>();
+        let mut thread_pool = CancellableThreadPool::new(2, sender);
+
+        let ok_task: CancellableTask<Sender<FileJobMessage>> =
+            CancellableTask::new(
+                PathBuf::from("ok.mp3"),
+                Box::new(|_, _| Ok(())),
+            );
+        thread_pool.queue_task(ok_task);
+        thread_pool.queue_task(CancellableTask::new(
+            PathBuf::from("errored.mp3"),
+            Box::new(|_, _| Err(miette!("sink is gone"))),
+        ));
+        thread_pool.queue_task(CancellableTask::new(
+            PathBuf::from("panicked.mp3"),
+            Box::new(|_, _| panic!("worker bug")),
+        ));
+
+        thread_pool.start().unwrap();
+        // Running tasks are joined (not cancelled) once the pool is cancelled.
+        while thread_pool.num_pending_tasks() > 0 {
+            std::thread::yield_now();
+        }
+
+        let mut failed_tasks = thread_pool.set_cancellation_and_join().unwrap();
+        failed_tasks
+            .sort_by(|first, second| first.file_path.cmp(&second.file_path));
+
+        assert_eq!(failed_tasks.len(), 2);
+        assert_eq!(
+            failed_tasks[0].file_path,
+            PathBuf::from("errored.mp3")
+        );
+        assert_eq!(failed_tasks[0].error.to_string(), "sink is gone");
+        assert_eq!(
+            failed_tasks[1].file_path,
+            PathBuf::from("panicked.mp3")
+        );
+        assert_eq!(
+            failed_tasks[1].error.to_string(),
+            "Worker panicked: worker bug"
+        );
+    }
+}
@@ -1,19 +1,30 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{fs, thread};
 
-use crossbeam::channel::Sender;
-use euphony_configuration::get_path_extension_or_empty;
+use euphony_configuration::aggregated_library::FileJobErrorCategory;
+use euphony_configuration::{get_path_extension_or_empty, Configuration};
 use euphony_library::view::SharedAlbumView;
 use miette::{miette, Context, IntoDiagnostic, Result};
 
 use crate::commands::transcode::jobs::common::{
+    categorize_ffmpeg_failure,
+    categorize_io_error,
+    get_temporary_output_file_path,
+    remove_temporary_output_file,
+    sleep_unless_cancelled,
     FileJob,
-    FileJobMessage,
+    FileJobAction,
     FileJobResult,
+    FileProgressSink,
 };
+use crate::commands::transcode::jobs::cover_art::CoverArtDownscaling;
+use crate::commands::transcode::jobs::permissions::copy_permissions_from_source_file;
 use crate::commands::transcode::state::changes::FileType;
 use crate::console::frontends::shared::queue::QueueItemID;
 use crate::globals::is_verbose_enabled;
@@ -27,23 +38,63 @@ const PARTIAL_TRANSCODED_FILE_DELETE_ATTEMPT_INTERVAL: Duration =
  * Specific job implementations
  */
 
+/// Outcome of a single ffmpeg run (see `TranscodeAudioFileJob::run_ffmpeg`).
+enum FfmpegRunOutcome {
+    /// ffmpeg was killed because the job was cancelled (the partial file has been removed).
+    Cancelled,
+
+    /// ffmpeg exited on its own, either successfully or not.
+    Finished {
+        processing_result: FileJobResult,
+        ffmpeg_duration: Duration,
+    },
+}
+
+/// Cover art that is downscaled before being embedded into the transcoded file
+/// (see `aggregated_library.cover_art_max_dimension`).
+struct DownscaledCoverArt {
+    /// Path to the cover art file in the source library.
+    source_file_path: PathBuf,
+
+    /// Path to the temporary file the downscaled cover art is written to and embedded from.
+    /// It is removed once the job finishes.
+    temporary_file_path: PathBuf,
+
+    downscaling: CoverArtDownscaling,
+}
+
 /// One of multiple file jobs.
 ///
 /// `TranscodeAudioFileJob` uses ffmpeg to transcode an audio file. The resulting file location
 /// is in the album directory of the aggregated library.
 pub struct TranscodeAudioFileJob {
+    /// Path to the source file that will be transcoded.
+    source_file_path: PathBuf,
+
     /// Path to the target file's directory (for missing directory creation purposes).
     target_file_directory_path: PathBuf,
 
     /// Path to the target file that will be created.
     target_file_path: PathBuf,
 
-    /// Path to the ffmpeg binary.
-    ffmpeg_binary_path: String,
+    /// Path to the temporary file ffmpeg writes into, which is renamed to `target_file_path`
+    /// once transcoding succeeds (see `get_temporary_output_file_path`).
+    temporary_target_file_path: PathBuf,
 
     /// List of arguments to ffmpeg that will transcode the audio as configured.
     ffmpeg_arguments: Vec<String>,
 
+    /// If set, the embedded cover art must be downscaled before running ffmpeg.
+    downscaled_cover_art: Option<DownscaledCoverArt>,
+
+    /// If set, the source file's permissions are copied onto the target file
+    /// after transcoding, with these (umask) bits cleared.
+    preserved_permissions_umask: Option<u32>,
+
+    /// Configuration shared with the worker threads: the ffmpeg binary (`tools.ffmpeg`),
+    /// the output checks and the retry settings (`aggregated_library`) are read from it.
+    configuration: Arc<Configuration>,
+
     /// `QueueItemID` this job belongs to.
     queue_item: QueueItemID,
 }
@@ -51,6 +102,7 @@ pub struct TranscodeAudioFileJob {
 impl TranscodeAudioFileJob {
     /// Initialize a new `TranscodeAudioFileJob`.
     pub fn new(
+        configuration: &Arc<Configuration>,
         album: SharedAlbumView,
         source_file_path: PathBuf,
         target_file_path: PathBuf,
@@ -58,15 +110,14 @@ impl TranscodeAudioFileJob {
     ) -> Result<Self> {
         let album_locked = album.read();
 
-        let config = album_locked.euphony_configuration();
-
         /*
          * 1. Sanity and error checking before we begin, as these jobs should not operate on
          *    unusual cases that are not matching the configuration.
          */
         let transcoding_config =
             &album_locked.library_configuration().transcoding;
-        let ffmpeg_config = &config.tools.ffmpeg;
+        let ffmpeg_config = &configuration.tools.ffmpeg;
+        let album_transcoding_config = &album_locked.configuration.transcoding;
 
         if !transcoding_config
             .is_path_audio_file_by_extension(&source_file_path)?
@@ -79,12 +130,12 @@ impl TranscodeAudioFileJob {
             ));
         }
 
-        if !ffmpeg_config
-            .is_path_transcoding_output_by_extension(&target_file_path)?
-        {
-            let ffmpeg_output_extension =
-                &config.tools.ffmpeg.audio_transcoding_output_extension;
+        let ffmpeg_output_extension = album_transcoding_config
+            .audio_transcoding_output_extension(ffmpeg_config);
 
+        if get_path_extension_or_empty(&target_file_path)?
+            != ffmpeg_output_extension
+        {
             return Err(miette!(
                 "Invalid ffmpeg output file extension \"{}\": expected \"{}\".",
                 get_path_extension_or_empty(target_file_path)?,
@@ -103,107 +154,233 @@ impl TranscodeAudioFileJob {
             .to_str()
             .ok_or_else(|| miette!("Target file path is not valid UTF-8."))?;
 
-        let ffmpeg_arguments: Vec<String> = config
-            .tools
-            .ffmpeg
-            .audio_transcoding_args
+        let temporary_target_file_path =
+            get_temporary_output_file_path(&target_file_path);
+        let temporary_target_file_path_str =
+            temporary_target_file_path.to_str().ok_or_else(|| {
+                miette!("Temporary target file path is not valid UTF-8.")
+            })?;
+
+        let audio_transcoding_args = album_transcoding_config
+            .audio_transcoding_args_for_path(
+                ffmpeg_config,
+                transcoding_config,
+                &source_file_path,
+            )?;
+
+        // If enabled and the album has a cover art file, embed it (otherwise transcode normally).
+        let cover_art_file_path = find_cover_art_file(
+            &source_file_path,
+            &album_locked.album_directory_in_source_library(),
+            &ffmpeg_config.embedded_cover_art_file_names,
+        );
+
+        // Cover art that is too large is downscaled into a temporary file next to the
+        // transcoded file. If its size can't be read, it is embedded as-is.
+        let downscaled_cover_art =
+            cover_art_file_path
+                .as_deref()
+                .and_then(|cover_art_file_path| {
+                    let downscaling = CoverArtDownscaling::from_configuration(
+                        &configuration.aggregated_library,
+                    )?;
+
+                    let needs_downscaling =
+                        CoverArtDownscaling::is_downscalable_image(
+                            cover_art_file_path,
+                        ) && downscaling
+                            .exceeds_max_dimension(cover_art_file_path)
+                            .unwrap_or(false);
+
+                    needs_downscaling.then(|| DownscaledCoverArt {
+                        source_file_path: cover_art_file_path.to_path_buf(),
+                        temporary_file_path: get_temporary_cover_art_file_path(
+                            &temporary_target_file_path,
+                            cover_art_file_path,
+                        ),
+                        downscaling,
+                    })
+                });
+
+        let embedded_cover_art_file_path = match &downscaled_cover_art {
+            Some(downscaled_cover_art) => {
+                Some(downscaled_cover_art.temporary_file_path.clone())
+            }
+            None => cover_art_file_path,
+        };
+
+        let audio_transcoding_args = match embedded_cover_art_file_path
+            .as_deref()
+            .and_then(|cover_art_file_path| cover_art_file_path.to_str())
+        {
+            Some(cover_art_file_path) => add_cover_art_to_ffmpeg_args(
+                audio_transcoding_args,
+                cover_art_file_path,
+            ),
+            None => audio_transcoding_args.to_vec(),
+        };
+
+        let ffmpeg_arguments: Vec<String> = audio_transcoding_args
             .iter()
             .map(|arg| {
                 arg.replace("{INPUT_FILE}", source_file_path_str)
-                    .replace("{OUTPUT_FILE}", target_file_path_str)
+                    .replace("{OUTPUT_FILE}", temporary_target_file_path_str)
             })
             .collect();
 
+        let preserved_permissions_umask = configuration
+            .aggregated_library
+            .preserve_permissions
+            .then_some(configuration.aggregated_library.permissions_umask);
+
 
         // We have owned versions of data here because we want to be able to send this
         // job across threads easily.
         Ok(Self {
+            source_file_path: PathBuf::from(source_file_path_str),
             target_file_directory_path: target_file_directory.to_path_buf(),
             target_file_path: PathBuf::from(target_file_path_str),
-            ffmpeg_binary_path: config.tools.ffmpeg.binary.clone(),
+            temporary_target_file_path: PathBuf::from(
+                temporary_target_file_path_str,
+            ),
             ffmpeg_arguments,
+            downscaled_cover_art,
+            preserved_permissions_umask,
+            configuration: configuration.clone(),
             queue_item,
         })
     }
 }
 
-impl FileJob for TranscodeAudioFileJob {
-    fn run(
-        &mut self,
+impl TranscodeAudioFileJob {
+    /// Run ffmpeg once, killing it if the job is cancelled in the meantime.
+    ///
+    /// ffmpeg is asked to report its progress on standard output (`-progress pipe:1`),
+    /// which is forwarded to the progress sink (`FileProgressSink::file_progress`).
+    /// If the input duration or the progress can't be parsed, no progress is reported.
+    fn run_ffmpeg(
+        &self,
         cancellation_flag: &AtomicBool,
-        message_sender: &Sender<FileJobMessage>,
-    ) -> Result<()> {
-        message_sender
-            .send(FileJobMessage::new_starting(
-                self.queue_item,
-                FileType::Audio,
-                self.target_file_path.to_string_lossy(),
-            ))
-            .into_diagnostic()
-            .wrap_err_with(|| {
-                miette!("Could not send FileJobMessage::Starting.")
-            })?;
+        progress_sink: &dyn FileProgressSink,
+    ) -> Result<FfmpegRunOutcome> {
+        let time_ffmpeg_start = Instant::now();
 
-        /*
-         * Step 1: create missing directories
-         */
-        let create_dir_result =
-            fs::create_dir_all(&self.target_file_directory_path);
+        let spawn_result = Command::new(&self.configuration.tools.ffmpeg.binary)
+            .args(["-progress", "pipe:1"])
+            .args(&self.ffmpeg_arguments)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
 
-        if let Err(error) = create_dir_result {
-            let verbose_info = is_verbose_enabled()
-                .then(|| format!("fs::create_dir_all error: {error}"));
+        // A failure to spawn ffmpeg is reported as a regular (possibly retried) job failure.
+        let mut ffmpeg_child_process = match spawn_result {
+            Ok(child_process) => child_process,
+            Err(error) => {
+                let verbose_info: Option<String> =
+                    is_verbose_enabled().then(|| {
+                        format!(
+                            "Could not spawn ffmpeg. Binary={:?} Arguments={:?}",
+                            &self.configuration.tools.ffmpeg.binary,
+                            &self.ffmpeg_arguments
+                        )
+                    });
 
-            message_sender.send(FileJobMessage::new_finished(self.queue_item, FileType::Audio, self.target_file_path.to_string_lossy(), FileJobResult::Errored {
-                error: "Could not create target file's missing parent directory.".to_string(),
-                verbose_info
-            }))
-                .into_diagnostic()
-                .wrap_err_with(|| miette!("Could not send FileJobMessage::Finished"))?;
+                return Ok(FfmpegRunOutcome::Finished {
+                    processing_result: FileJobResult::Errored {
+                        error: format!(
+                            "Could not spawn ffmpeg for transcoding: {error}"
+                        ),
+                        category: FileJobErrorCategory::ProcessSpawn,
+                        verbose_info,
+                    },
+                    ffmpeg_duration: time_ffmpeg_start.elapsed(),
+                });
+            }
+        };
 
-            return Ok(());
-        }
+        let ffmpeg_stdout = ffmpeg_child_process
+            .stdout
+            .take()
+            .ok_or_else(|| miette!("Could not capture ffmpeg stdout."))?;
+        let ffmpeg_stderr = ffmpeg_child_process
+            .stderr
+            .take()
+            .ok_or_else(|| miette!("Could not capture ffmpeg stderr."))?;
 
-        /*
-         * Step 2: run ffmpeg (transcodes audio)
-         */
-        let mut ffmpeg_child_process = Command::new(&self.ffmpeg_binary_path)
-            .args(&self.ffmpeg_arguments)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .into_diagnostic()
-            .wrap_err_with(|| {
-                miette!("Could not spawn ffmpeg for transcoding.")
-            })?;
+        // Parsed from stderr, in microseconds (0 until known).
+        let input_duration_micros = AtomicU64::new(0);
 
-        // Keep checking for cancellation
-        while ffmpeg_child_process
-            .try_wait()
-            .into_diagnostic()
-            .wrap_err_with(|| {
-                miette!("Could not wait or get process exit code.")
-            })?
-            .is_none()
-        {
-            let cancellation_flag_value =
-                cancellation_flag.load(Ordering::SeqCst);
-            if cancellation_flag_value {
-                // Cancellation flag is set to true, we should kill ffmpeg and exit as soon as possible.
-                ffmpeg_child_process
-                    .kill()
+        // Both outputs must be read while ffmpeg is running,
+        // otherwise ffmpeg could block on a full pipe.
+        let (ffmpeg_exit_status, ffmpeg_stdout, ffmpeg_stderr) =
+            thread::scope(|scope| {
+                let stdout_reader = scope.spawn(|| {
+                    read_ffmpeg_progress_output(
+                        ffmpeg_stdout,
+                        &input_duration_micros,
+                        progress_sink,
+                        self.queue_item,
+                    )
+                });
+                let stderr_reader = scope.spawn(|| {
+                    read_ffmpeg_error_output(
+                        ffmpeg_stderr,
+                        &input_duration_micros,
+                    )
+                });
+
+                // Keep checking for cancellation
+                while ffmpeg_child_process
+                    .try_wait()
                     .into_diagnostic()
                     .wrap_err_with(|| {
-                        miette!("Could not kill ffmpeg process.")
-                    })?;
+                        miette!("Could not wait or get process exit code.")
+                    })?
+                    .is_none()
+                {
+                    let cancellation_flag_value =
+                        cancellation_flag.load(Ordering::SeqCst);
+                    if cancellation_flag_value {
+                        // Cancellation flag is set to true, we should kill ffmpeg and exit as soon as possible.
+                        ffmpeg_child_process
+                            .kill()
+                            .into_diagnostic()
+                            .wrap_err_with(|| {
+                                miette!("Could not kill ffmpeg process.")
+                            })?;
 
-                break;
-            }
+                        break;
+                    }
 
-            thread::sleep(FFMPEG_TASK_CANCELLATION_CHECK_INTERVAL);
-        }
+                    thread::sleep(FFMPEG_TASK_CANCELLATION_CHECK_INTERVAL);
+                }
 
-        ffmpeg_child_process.wait().into_diagnostic()?;
+                let ffmpeg_exit_status =
+                    ffmpeg_child_process.wait().into_diagnostic()?;
+
+                let ffmpeg_stdout = stdout_reader
+                    .join()
+                    .map_err(|_| miette!("ffmpeg stdout reader panicked."))?
+                    .into_diagnostic()
+                    .wrap_err_with(|| {
+                        miette!("Could not read ffmpeg stdout.")
+                    })?;
+                let ffmpeg_stderr = stderr_reader
+                    .join()
+                    .map_err(|_| miette!("ffmpeg stderr reader panicked."))?
+                    .into_diagnostic()
+                    .wrap_err_with(|| {
+                        miette!("Could not read ffmpeg stderr.")
+                    })?;
+
+                Ok::<_, miette::Report>((
+                    ffmpeg_exit_status,
+                    ffmpeg_stdout,
+                    ffmpeg_stderr,
+                ))
+            })?;
+
+        let ffmpeg_duration = time_ffmpeg_start.elapsed();
 
         // ffmpeg process is finished at this point, we should just check what the reason was.
         let final_cancellation_flag = cancellation_flag.load(Ordering::SeqCst);
@@ -211,11 +388,10 @@ impl FileJob for TranscodeAudioFileJob {
             // Process was killed because of cancellation.
 
             // Delete the partial file.
-            if self.target_file_path.exists() && self.target_file_path.is_file()
-            {
+            if self.temporary_target_file_path.is_file() {
                 let mut retries: usize = 0;
                 while retries <= 4 {
-                    match fs::remove_file(&self.target_file_path) {
+                    match fs::remove_file(&self.temporary_target_file_path) {
                         Ok(_) => {
                             break;
                         }
@@ -233,87 +409,760 @@ impl FileJob for TranscodeAudioFileJob {
                 }
             }
 
-            message_sender
-                .send(FileJobMessage::new_cancelled(
-                    self.queue_item,
-                    FileType::Audio,
-                    self.target_file_path.to_string_lossy(),
-                ))
-                .into_diagnostic()
-                .wrap_err_with(|| {
-                    miette!("Could not send FileJobMessage::Cancelled.")
-                })?;
+            return Ok(FfmpegRunOutcome::Cancelled);
+        }
+
+        // Everything was normal.
+        let ffmpeg_exit_code = ffmpeg_exit_status
+            .code()
+            .ok_or_else(|| miette!("No ffmpeg exit code?!"))?;
+
+        // Extract ffmpeg stdout/stderr/exit code if necessary.
+        let processing_result = if ffmpeg_exit_code == 0 {
+            let verbose_info: Option<String> = is_verbose_enabled().then(|| {
+                format!(
+                    "ffmpeg exited (exit code 0). Binary={:?} Arguments={:?}",
+                    &self.configuration.tools.ffmpeg.binary,
+                    &self.ffmpeg_arguments
+                )
+            });
+
+            // Finalize the transcoded file by verifying it, (optionally) copying the source
+            // file's permissions and moving it into place.
+            let finalization_result = self
+                .verify_transcoded_file()
+                .map_err(|error| ("Transcoded file failed verification", error))
+                .and_then(|_| {
+                    match self.preserved_permissions_umask {
+                        Some(umask) => copy_permissions_from_source_file(
+                            &self.source_file_path,
+                            &self.temporary_target_file_path,
+                            umask,
+                        ),
+                        None => Ok(()),
+                    }
+                    .map_err(|error| {
+                        ("Could not copy source file permissions", error)
+                    })
+                })
+                .and_then(|_| {
+                    fs::rename(
+                        &self.temporary_target_file_path,
+                        &self.target_file_path,
+                    )
+                    .map_err(|error| {
+                        (
+                            "Could not move the transcoded file into place",
+                            error,
+                        )
+                    })
+                });
 
-            Ok(())
+            match finalization_result {
+                Ok(_) => FileJobResult::Okay {
+                    action: FileJobAction::Transcoded {
+                        bytes_written: fs::metadata(&self.target_file_path)
+                            .map(|metadata| metadata.len())
+                            .unwrap_or_default(),
+                    },
+                    verbose_info,
+                },
+                Err((context, error)) => {
+                    // The file is incomplete without its finalization, so it must not be kept.
+                    let _ = remove_temporary_output_file(
+                        &self.temporary_target_file_path,
+                    );
+
+                    FileJobResult::Errored {
+                        error: format!("{context}: {error}"),
+                        category: categorize_io_error(&error),
+                        verbose_info,
+                    }
+                }
+            }
         } else {
-            // Everything was normal.
-            let ffmpeg_output = ffmpeg_child_process
-                .wait_with_output()
+            // ffmpeg might have left a partial file behind.
+            let _ =
+                remove_temporary_output_file(&self.temporary_target_file_path);
+
+            let ffmpeg_stdout = String::from_utf8(ffmpeg_stdout)
                 .into_diagnostic()
-                .wrap_err_with(|| miette!("Could not get ffmpeg output."))?;
+                .wrap_err_with(|| miette!("Could not parse ffmpeg stdout."))?;
 
-            let ffmpeg_exit_code = ffmpeg_output
-                .status
-                .code()
-                .ok_or_else(|| miette!("No ffmpeg exit code?!"))?;
+            let ffmpeg_stderr = String::from_utf8(ffmpeg_stderr)
+                .into_diagnostic()
+                .wrap_err_with(|| miette!("could not parse ffmpeg stderr."))?;
 
-            // Extract ffmpeg stdout/stderr/exit code if necessary.
-            let processing_result = if ffmpeg_exit_code == 0 {
-                let verbose_info: Option<String> = is_verbose_enabled()
-                    .then(|| {
-                        format!(
-                            "ffmpeg exited (exit code 0). Binary={:?} Arguments={:?}",
-                            &self.ffmpeg_binary_path, &self.ffmpeg_arguments
-                        )
-                    });
+            let category = categorize_ffmpeg_failure(&ffmpeg_stderr);
 
-                FileJobResult::Okay { verbose_info }
-            } else {
-                let ffmpeg_stdout = String::from_utf8(ffmpeg_output.stdout)
-                    .into_diagnostic()
-                    .wrap_err_with(|| {
-                        miette!("Could not parse ffmpeg stdout.")
-                    })?;
+            let error = format!(
+                "ffmpeg exited with non-zero exit code.\nStdout: {}\nStderr: {}",
+                ffmpeg_stdout, ffmpeg_stderr
+            );
 
-                let ffmpeg_stderr = String::from_utf8(ffmpeg_output.stderr)
-                    .into_diagnostic()
-                    .wrap_err_with(|| {
-                        miette!("could not parse ffmpeg stderr.")
-                    })?;
+            let verbose_info: Option<String> = is_verbose_enabled().then(|| {
+                format!(
+                    "ffmpeg exited (exit code {}). Binary={:?} Arguments={:?}",
+                    ffmpeg_exit_code,
+                    &self.configuration.tools.ffmpeg.binary,
+                    &self.ffmpeg_arguments
+                )
+            });
 
-                let error = format!(
-                    "ffmpeg exited with non-zero exit code.\nStdout: {}\nStderr: {}",
-                    ffmpeg_stdout, ffmpeg_stderr
-                );
+            FileJobResult::Errored {
+                error,
+                category,
+                verbose_info,
+            }
+        };
 
-                let verbose_info: Option<String> = is_verbose_enabled()
-                    .then(|| {
-                        format!(
-                            "ffmpeg exited (exit code {}). Binary={:?} Arguments={:?}",
-                            ffmpeg_exit_code,
-                            &self.ffmpeg_binary_path, &self.ffmpeg_arguments
-                        )
-                    });
+        Ok(FfmpegRunOutcome::Finished {
+            processing_result,
+            ffmpeg_duration,
+        })
+    }
 
-                FileJobResult::Errored {
-                    error,
-                    verbose_info,
+    /// Run ffmpeg (see `run_ffmpeg`), retrying failed attempts as configured,
+    /// and report the file as finished (or cancelled) to the progress sink.
+    fn transcode_with_retries(
+        &self,
+        cancellation_flag: &AtomicBool,
+        progress_sink: &dyn FileProgressSink,
+    ) -> Result<()> {
+        /*
+         * Step 3: run ffmpeg (transcodes audio), retrying failed attempts as configured
+         */
+        // Remove any temporary file left behind by an interrupted run
+        // (if it can't be removed, ffmpeg will report the problem).
+        let _ = remove_temporary_output_file(&self.temporary_target_file_path);
+
+        let aggregated_library_config = &self.configuration.aggregated_library;
+        let max_attempts =
+            aggregated_library_config.failure_max_retries as usize + 1;
+        let mut attempt: usize = 1;
+        // Includes the time spent in failed attempts.
+        let mut total_ffmpeg_duration = Duration::ZERO;
+
+        loop {
+            let (processing_result, ffmpeg_duration) =
+                match self.run_ffmpeg(cancellation_flag, progress_sink)? {
+                    FfmpegRunOutcome::Cancelled => {
+                        return self.report_cancelled(progress_sink);
+                    }
+                    FfmpegRunOutcome::Finished {
+                        processing_result,
+                        ffmpeg_duration,
+                    } => (processing_result, ffmpeg_duration),
+                };
+
+            total_ffmpeg_duration += ffmpeg_duration;
+
+            if let FileJobResult::Errored {
+                error, category, ..
+            } = &processing_result
+            {
+                if attempt < max_attempts
+                    && aggregated_library_config
+                        .retryable_error_categories
+                        .contains(category)
+                {
+                    progress_sink.file_retrying(
+                        FileType::Audio,
+                        &self.target_file_path.to_string_lossy(),
+                        error.clone(),
+                        *category,
+                        attempt,
+                        max_attempts,
+                    )?;
+
+                    if !sleep_unless_cancelled(
+                        Duration::from_secs(
+                            aggregated_library_config.failure_delay_seconds
+                                as u64,
+                        ),
+                        cancellation_flag,
+                    ) {
+                        return self.report_cancelled(progress_sink);
+                    }
+
+                    attempt += 1;
+                    continue;
                 }
-            };
+            }
+
+            return progress_sink.file_finished(
+                self.queue_item,
+                FileType::Audio,
+                &self.target_file_path.to_string_lossy(),
+                processing_result,
+                Some(total_ffmpeg_duration),
+            );
+        }
+    }
+
+    /// Verify the (temporary) transcoded file after ffmpeg reported success: it must be at least
+    /// `aggregated_library.minimum_transcoded_file_size_bytes` large and, if enabled
+    /// (`aggregated_library.verify_transcoded_files_decode`), decode without errors.
+    ///
+    /// Failures are reported as `InvalidData` I/O errors (as the source file was decoded
+    /// successfully, a broken output file usually points to a storage problem worth retrying).
+    fn verify_transcoded_file(&self) -> io::Result<()> {
+        let aggregated_library_config = &self.configuration.aggregated_library;
+
+        let output_file_size =
+            fs::metadata(&self.temporary_target_file_path)?.len();
+        if output_file_size
+            < aggregated_library_config.minimum_transcoded_file_size_bytes
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the transcoded file only has {} bytes (expected at least {})",
+                    output_file_size,
+                    aggregated_library_config.minimum_transcoded_file_size_bytes
+                ),
+            ));
+        }
+
+        if !aggregated_library_config.verify_transcoded_files_decode {
+            return Ok(());
+        }
+
+        let decode_output =
+            Command::new(&self.configuration.tools.ffmpeg.binary)
+                .args(["-v", "error", "-i"])
+                .arg(&self.temporary_target_file_path)
+                .args(["-f", "null", "-"])
+                .stdin(Stdio::null())
+                .output()?;
+
+        let decode_errors = String::from_utf8_lossy(&decode_output.stderr);
+        if !decode_output.status.success() || !decode_errors.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the transcoded file could not be decoded: {}",
+                    decode_errors.trim()
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn report_cancelled(
+        &self,
+        progress_sink: &dyn FileProgressSink,
+    ) -> Result<()> {
+        progress_sink.file_cancelled(
+            self.queue_item,
+            FileType::Audio,
+            &self.target_file_path.to_string_lossy(),
+        )
+    }
+}
 
-            message_sender
-                .send(FileJobMessage::new_finished(
+impl FileJob for TranscodeAudioFileJob {
+    fn target_file_path(&self) -> &Path {
+        &self.target_file_path
+    }
+
+    fn run(
+        &mut self,
+        cancellation_flag: &AtomicBool,
+        progress_sink: &dyn FileProgressSink,
+    ) -> Result<()> {
+        progress_sink.file_started(
+            self.queue_item,
+            FileType::Audio,
+            &self.target_file_path.to_string_lossy(),
+        )?;
+
+        /*
+         * Step 1: create missing directories
+         */
+        let create_dir_result =
+            fs::create_dir_all(&self.target_file_directory_path);
+
+        if let Err(error) = create_dir_result {
+            let verbose_info = is_verbose_enabled()
+                .then(|| format!("fs::create_dir_all error: {error}"));
+
+            progress_sink.file_finished(self.queue_item, FileType::Audio, &self.target_file_path.to_string_lossy(), FileJobResult::Errored {
+                error: "Could not create target file's missing parent directory.".to_string(),
+                category: categorize_io_error(&error),
+                verbose_info
+            }, None)?;
+
+            return Ok(());
+        }
+
+        /*
+         * Step 2: (optionally) downscale the embedded cover art
+         */
+        if let Some(downscaled_cover_art) = &self.downscaled_cover_art {
+            let downscaling_result =
+                downscaled_cover_art.downscaling.copy_downscaled_image(
+                    &downscaled_cover_art.source_file_path,
+                    &downscaled_cover_art.temporary_file_path,
+                );
+
+            if let Err(error) = downscaling_result {
+                let _ = remove_temporary_output_file(
+                    &downscaled_cover_art.temporary_file_path,
+                );
+
+                progress_sink.file_finished(
                     self.queue_item,
                     FileType::Audio,
-                    self.target_file_path.to_string_lossy(),
-                    processing_result,
-                ))
-                .into_diagnostic()
-                .wrap_err_with(|| {
-                    miette!("Could not send FileJobMessage::Finished.")
-                })?;
+                    &self.target_file_path.to_string_lossy(),
+                    FileJobResult::Errored {
+                        error: format!(
+                            "Could not downscale cover art {:?}: {error}",
+                            downscaled_cover_art.source_file_path
+                        ),
+                        category: categorize_io_error(&error),
+                        verbose_info: None,
+                    },
+                    None,
+                )?;
 
-            Ok(())
+                return Ok(());
+            }
         }
+
+        let transcoding_result =
+            self.transcode_with_retries(cancellation_flag, progress_sink);
+
+        if let Some(downscaled_cover_art) = &self.downscaled_cover_art {
+            let _ = remove_temporary_output_file(
+                &downscaled_cover_art.temporary_file_path,
+            );
+        }
+
+        transcoding_result
+    }
+}
+
+
+/*
+ * Cover art embedding
+ */
+
+/// Find the cover art file to embed into the transcode of `source_file_path`: the first
+/// of `cover_art_file_names` (lowercase, in order of preference) that exists next to the file
+/// or, if there is none, in the album directory. File names are compared case-insensitively.
+///
+/// Returns `None` if no names are configured (i.e. embedding is disabled) or no file matches.
+fn find_cover_art_file(
+    source_file_path: &Path,
+    album_directory_path: &Path,
+    cover_art_file_names: &[String],
+) -> Option<PathBuf> {
+    if cover_art_file_names.is_empty() {
+        return None;
+    }
+
+    let mut candidate_directories: Vec<&Path> = Vec::with_capacity(2);
+    if let Some(source_file_directory) = source_file_path.parent() {
+        candidate_directories.push(source_file_directory);
+    }
+    if !candidate_directories.contains(&album_directory_path) {
+        candidate_directories.push(album_directory_path);
+    }
+
+    for directory in candidate_directories {
+        let Ok(directory_entries) = fs::read_dir(directory) else {
+            continue;
+        };
+
+        let files_by_lowercase_name: HashMap<String, PathBuf> =
+            directory_entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| {
+                    (
+                        entry.file_name().to_string_lossy().to_lowercase(),
+                        entry.path(),
+                    )
+                })
+                .collect();
+
+        let cover_art_file = cover_art_file_names
+            .iter()
+            .find_map(|file_name| files_by_lowercase_name.get(file_name));
+
+        if let Some(cover_art_file) = cover_art_file {
+            return Some(cover_art_file.clone());
+        }
+    }
+
+    None
+}
+
+/// Get the path of the temporary file downscaled cover art is written to: next to the
+/// temporary transcoded file, with the cover art's extension
+/// (e.g. `01.euphony-tmp.cover.jpg` for `01.euphony-tmp.mp3`).
+fn get_temporary_cover_art_file_path(
+    temporary_target_file_path: &Path,
+    cover_art_file_path: &Path,
+) -> PathBuf {
+    let cover_art_extension = cover_art_file_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    temporary_target_file_path
+        .with_extension(format!("cover.{cover_art_extension}"))
+}
+
+/// Stream mapping and options added in front of the output file when embedding cover art:
+/// audio from the source file, the picture from the cover art file (copied as-is).
+/// Any picture embedded in the source file is therefore replaced instead of duplicated.
+const FFMPEG_COVER_ART_OUTPUT_ARGS: [&str; 8] = [
+    "-map",
+    "0:a",
+    "-map",
+    "1:v",
+    "-c:v",
+    "copy",
+    "-disposition:v",
+    "attached_pic",
+];
+
+/// Add a cover art file to an ffmpeg argument template (see `FFMPEG_COVER_ART_OUTPUT_ARGS`):
+/// it is added as the second input right after `-i {INPUT_FILE}` and mapped into the output.
+/// `-vn` is removed, as it would drop the picture.
+///
+/// If the template doesn't have the usual `-i {INPUT_FILE}` and `{OUTPUT_FILE}` arguments,
+/// it is returned unchanged (i.e. the file is transcoded without cover art).
+fn add_cover_art_to_ffmpeg_args(
+    audio_transcoding_args: &[String],
+    cover_art_file_path: &str,
+) -> Vec<String> {
+    let input_index = audio_transcoding_args.windows(2).position(|arguments| {
+        arguments[0] == "-i" && arguments[1].contains("{INPUT_FILE}")
+    });
+    let output_index = audio_transcoding_args
+        .iter()
+        .position(|argument| argument.contains("{OUTPUT_FILE}"));
+
+    let (Some(input_index), Some(output_index)) = (input_index, output_index)
+    else {
+        return audio_transcoding_args.to_vec();
+    };
+    if output_index <= input_index + 1 {
+        return audio_transcoding_args.to_vec();
+    }
+
+    let mut arguments: Vec<String> =
+        Vec::with_capacity(audio_transcoding_args.len() + 10);
+
+    for (index, argument) in audio_transcoding_args.iter().enumerate() {
+        if index == output_index {
+            arguments.extend(FFMPEG_COVER_ART_OUTPUT_ARGS.map(String::from));
+        }
+
+        if argument != "-vn" {
+            arguments.push(argument.clone());
+        }
+
+        if index == input_index + 1 {
+            arguments.push("-i".to_string());
+            arguments.push(cover_art_file_path.to_string());
+        }
+    }
+
+    arguments
+}
+
+
+/*
+ * ffmpeg output parsing
+ */
+
+/// Parse an ffmpeg timestamp (`HH:MM:SS.xx`, as in `Duration: 00:03:25.47`).
+fn parse_ffmpeg_timestamp(timestamp: &str) -> Option<Duration> {
+    let mut components = timestamp.trim().split(':');
+
+    let hours: u64 = components.next()?.parse().ok()?;
+    let minutes: u64 = components.next()?.parse().ok()?;
+    let seconds: f64 = components.next()?.parse().ok()?;
+
+    if components.next().is_some() || !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+
+    Some(
+        Duration::from_secs(hours * 3600 + minutes * 60)
+            + Duration::from_secs_f64(seconds),
+    )
+}
+
+/// Parse the input duration from a line of ffmpeg's standard error output
+/// (e.g. `  Duration: 00:03:25.47, start: 0.000000, bitrate: 1024 kb/s`).
+///
+/// Returns `None` for any other line and for unknown durations (`Duration: N/A`).
+fn parse_ffmpeg_input_duration(stderr_line: &str) -> Option<Duration> {
+    let duration_and_rest =
+        stderr_line.trim_start().strip_prefix("Duration:")?;
+
+    parse_ffmpeg_timestamp(duration_and_rest.split(',').next()?)
+}
+
+/// Parse the current output position from a line of ffmpeg's `-progress` output
+/// (`out_time_us=...`, or `out_time_ms=...` in older versions, both in microseconds).
+///
+/// Returns `None` for any other line and for unknown positions (e.g. `out_time_us=N/A`).
+fn parse_ffmpeg_progress_position(progress_line: &str) -> Option<Duration> {
+    let (key, value) = progress_line.trim().split_once('=')?;
+
+    match key {
+        "out_time_us" | "out_time_ms" => {
+            value.parse::<u64>().ok().map(Duration::from_micros)
+        }
+        _ => None,
+    }
+}
+
+/// Read ffmpeg's standard error output until it is closed, storing the input duration
+/// into `input_duration_micros` as soon as it is printed. Returns the entire output.
+fn read_ffmpeg_error_output<R: Read>(
+    ffmpeg_stderr: R,
+    input_duration_micros: &AtomicU64,
+) -> io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(ffmpeg_stderr);
+    let mut output: Vec<u8> = Vec::new();
+
+    loop {
+        let line_start = output.len();
+        if reader.read_until(b'\n', &mut output)? == 0 {
+            return Ok(output);
+        }
+
+        // Only the first (i.e. input) duration is relevant.
+        if input_duration_micros.load(Ordering::SeqCst) == 0 {
+            let line = String::from_utf8_lossy(&output[line_start..]);
+
+            if let Some(duration) = parse_ffmpeg_input_duration(&line) {
+                input_duration_micros
+                    .store(duration.as_micros() as u64, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+/// Read ffmpeg's `-progress` output (on its standard output) until it is closed,
+/// reporting the file's progress to `progress_sink` every time the position advances
+/// (as long as the input duration is known, see `read_ffmpeg_error_output`).
+///
+/// Returns the lines of the output that are not progress reports.
+fn read_ffmpeg_progress_output<R: Read>(
+    ffmpeg_stdout: R,
+    input_duration_micros: &AtomicU64,
+    progress_sink: &dyn FileProgressSink,
+    queue_item: QueueItemID,
+) -> io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(ffmpeg_stdout);
+    let mut line: Vec<u8> = Vec::new();
+    let mut output: Vec<u8> = Vec::new();
+    let mut last_reported_position = Duration::ZERO;
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(output);
+        }
+
+        let line_str = String::from_utf8_lossy(&line);
+        if !line_str.contains('=') {
+            output.extend_from_slice(&line);
+            continue;
+        }
+
+        let Some(position) = parse_ffmpeg_progress_position(&line_str) else {
+            continue;
+        };
+
+        let input_duration_micros = input_duration_micros.load(Ordering::SeqCst);
+        if input_duration_micros == 0 || position <= last_reported_position {
+            continue;
+        }
+        last_reported_position = position;
+
+        let progress = (position.as_micros() as f64
+            / input_duration_micros as f64)
+            .min(1.0);
+
+        // Progress reports are best-effort, so a failed report is not an error here
+        // (the final job message will report it).
+        let _ = progress_sink.file_progress(queue_item, progress);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::AtomicU64;
+    use std::time::Duration;
+
+    use crossbeam::channel;
+
+    use super::{
+        add_cover_art_to_ffmpeg_args,
+        get_temporary_cover_art_file_path,
+        parse_ffmpeg_input_duration,
+        parse_ffmpeg_progress_position,
+        read_ffmpeg_progress_output,
+    };
+    use crate::commands::transcode::jobs::FileJobMessage;
+    use crate::console::frontends::shared::queue::QueueItemID;
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn adds_cover_art_to_ffmpeg_args() {
+        let template = arguments(&[
+            "-i",
+            "{INPUT_FILE}",
+            "-vn",
+            "-codec:a",
+            "libmp3lame",
+            "-y",
+            "{OUTPUT_FILE}",
+        ]);
+
+        assert_eq!(
+            add_cover_art_to_ffmpeg_args(&template, "/music/cover.jpg"),
+            arguments(&[
+                "-i",
+                "{INPUT_FILE}",
+                "-i",
+                "/music/cover.jpg",
+                "-codec:a",
+                "libmp3lame",
+                "-y",
+                "-map",
+                "0:a",
+                "-map",
+                "1:v",
+                "-c:v",
+                "copy",
+                "-disposition:v",
+                "attached_pic",
+                "{OUTPUT_FILE}",
+            ])
+        );
+    }
+
+    #[test]
+    fn places_temporary_cover_art_next_to_temporary_file() {
+        assert_eq!(
+            get_temporary_cover_art_file_path(
+                Path::new("/music/Album/01.euphony-tmp.mp3"),
+                Path::new("/source/Album/Cover.JPG"),
+            ),
+            PathBuf::from("/music/Album/01.euphony-tmp.cover.jpg")
+        );
+    }
+
+    #[test]
+    fn keeps_unusual_ffmpeg_args_without_cover_art() {
+        let template = arguments(&["-i", "-", "-f", "mp3", "{OUTPUT_FILE}"]);
+
+        assert_eq!(
+            add_cover_art_to_ffmpeg_args(&template, "/music/cover.jpg"),
+            template
+        );
+    }
+
+    #[test]
+    fn parses_ffmpeg_input_duration() {
+        assert_eq!(
+            parse_ffmpeg_input_duration(
+                "  Duration: 00:03:25.47, start: 0.000000, bitrate: 1024 kb/s"
+            ),
+            Some(Duration::from_millis(205_470))
+        );
+        assert_eq!(
+            parse_ffmpeg_input_duration(
+                "  Duration: 01:00:00.00, start: 0.000000, bitrate: N/A"
+            ),
+            Some(Duration::from_secs(3600))
+        );
+
+        assert_eq!(
+            parse_ffmpeg_input_duration("  Duration: N/A, bitrate: N/A"),
+            None
+        );
+        assert_eq!(
+            parse_ffmpeg_input_duration("  Stream #0:0: Audio: flac, 44100 Hz"),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_ffmpeg_progress_position() {
+        assert_eq!(
+            parse_ffmpeg_progress_position("out_time_us=12500000\n"),
+            Some(Duration::from_micros(12_500_000))
+        );
+        assert_eq!(
+            parse_ffmpeg_progress_position("out_time_ms=12500000"),
+            Some(Duration::from_micros(12_500_000))
+        );
+
+        assert_eq!(
+            parse_ffmpeg_progress_position("out_time_us=N/A"),
+            None
+        );
+        assert_eq!(
+            parse_ffmpeg_progress_position("out_time=00:00:12.500000"),
+            None
+        );
+        assert_eq!(
+            parse_ffmpeg_progress_position("progress=continue"),
+            None
+        );
+    }
+
+    #[test]
+    fn reports_ffmpeg_progress_to_sink() {
+        let (sender, receiver) = channel::unbounded::<FileJobMessage>();
+        let queue_item = QueueItemID::new_random();
+
+        let ffmpeg_stdout = "out_time_us=2500000\n\
+            progress=continue\n\
+            out_time_us=2500000\n\
+            out_time_us=20000000\n\
+            some other output\n";
+
+        let other_output = read_ffmpeg_progress_output(
+            ffmpeg_stdout.as_bytes(),
+            &AtomicU64::new(10_000_000),
+            &sender,
+            queue_item,
+        )
+        .unwrap();
+        assert_eq!(other_output, b"some other output\n");
+
+        // Positions that don't advance are not reported and progress is capped at 1.
+        let reported_progress: Vec<f64> = receiver
+            .try_iter()
+            .map(|message| match message {
+                FileJobMessage::Progress {
+                    queue_item: reported_queue_item,
+                    progress,
+                } if reported_queue_item == queue_item => progress,
+                _ => panic!("unexpected message"),
+            })
+            .collect();
+        assert_eq!(reported_progress, vec![0.25, 1.0]);
     }
 }
@@ -5,11 +5,14 @@ use std::io::Write;
 use std::path::Path;
 use std::{fs, io};
 
+use euphony_configuration::aggregated_library::AggregationMode;
+use euphony_configuration::device_profile::state_file_name_for_device_profile;
 use miette::{miette, Context, Diagnostic, IntoDiagnostic, Result};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-pub const LIBRARY_STATE_FILE_NAME: &str = ".library.state.euphony";
+/// Library states of the `mirror` command are kept separately (see `AggregationMode`).
+pub const MIRROR_LIBRARY_STATE_FILE_NAME: &str = ".library.mirror-state.euphony";
 const LIBRARY_STATE_SCHEMA_VERSION: u32 = 2;
 
 #[derive(Error, Debug, Diagnostic)]
@@ -31,14 +34,54 @@ pub enum LibraryStateLoadError {
 }
 
 
-#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TrackedAlbum {
     pub album_title: String,
 
     /// Relative path from the library root to the album.
     pub album_source_relative_path: String,
+
+    /// Relative path from the aggregated library root to the transcoded album,
+    /// if it differs from `album_source_relative_path`, i.e. if it was built from a template
+    /// (see `AlbumView::templated_output_directory`) or sanitized
+    /// (see `aggregated_library.sanitize_file_names`).
+    /// If the path changes (e.g. after editing the album tag), the album
+    /// is no longer equal to its saved counterpart, so the old directory is removed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album_transcoded_relative_path: Option<String>,
+}
+
+impl TrackedAlbum {
+    /// Relative path from the aggregated library root to the transcoded album.
+    pub fn album_output_relative_path(&self) -> &str {
+        self.album_transcoded_relative_path
+            .as_deref()
+            .unwrap_or(&self.album_source_relative_path)
+    }
+
+    fn normalized_output_relative_path(&self) -> String {
+        self.album_output_relative_path()
+            .replace('\\', "/")
+            .to_lowercase()
+    }
 }
 
+/// Albums are equal if both their source and their output directories are
+/// (the latter compared case-insensitively and regardless of path separators,
+/// as they may refer to the same directory). An album without a transcoded relative
+/// path is equal to one whose transcoded relative path happens to be its source path.
+impl PartialEq for TrackedAlbum {
+    fn eq(&self, other: &Self) -> bool {
+        self.album_title == other.album_title
+            && self.album_source_relative_path
+                == other.album_source_relative_path
+            && self.normalized_output_relative_path()
+                == other.normalized_output_relative_path()
+    }
+}
+
+impl Eq for TrackedAlbum {}
+
 impl Hash for TrackedAlbum {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.album_source_relative_path.hash(state)
@@ -90,11 +133,31 @@ impl LibraryState {
         Ok(state)
     }
 
+    /// Get the name of the state file for the given `AggregationMode`
+    /// (`meta_file_name`, i.e. `file_metadata.meta_file_name`, or `MIRROR_LIBRARY_STATE_FILE_NAME`)
+    /// and device profile (see `state_file_name_for_device_profile`).
+    pub fn get_state_file_name(
+        meta_file_name: &str,
+        mode: AggregationMode,
+        device_profile: Option<&str>,
+    ) -> String {
+        let state_file_name = match mode {
+            AggregationMode::Transcode => meta_file_name,
+            AggregationMode::Mirror => MIRROR_LIBRARY_STATE_FILE_NAME,
+        };
+
+        state_file_name_for_device_profile(state_file_name, device_profile)
+    }
+
     pub fn load_from_directory<P: AsRef<Path>>(
         directory_path: P,
+        meta_file_name: &str,
+        mode: AggregationMode,
+        device_profile: Option<&str>,
     ) -> Result<Self, LibraryStateLoadError> {
-        let library_state_file_path =
-            directory_path.as_ref().join(LIBRARY_STATE_FILE_NAME);
+        let library_state_file_path = directory_path.as_ref().join(
+            Self::get_state_file_name(meta_file_name, mode, device_profile),
+        );
 
         if !library_state_file_path.is_file() {
             return Err(LibraryStateLoadError::NotFound);
@@ -146,9 +209,18 @@ impl LibraryState {
         &self,
         output_directory_path: P,
         allow_overwrite: bool,
+        meta_file_name: &str,
+        mode: AggregationMode,
+        device_profile: Option<&str>,
     ) -> Result<()> {
         let output_file_path =
-            output_directory_path.as_ref().join(LIBRARY_STATE_FILE_NAME);
+            output_directory_path
+                .as_ref()
+                .join(Self::get_state_file_name(
+                    meta_file_name,
+                    mode,
+                    device_profile,
+                ));
 
         self.save_to_file(output_file_path, allow_overwrite)
     }
@@ -1,16 +1,28 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::ops::Sub;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use std::{fs, thread};
 
 use crossbeam::channel;
 use crossbeam::channel::{Receiver, RecvTimeoutError, Sender};
 use crossterm::style::Stylize;
+use euphony_configuration::aggregated_library::AggregationMode;
 use euphony_configuration::Configuration;
-use euphony_library::state::transcoded::TranscodedAlbumState;
+use euphony_library::state::source::{
+    SourceAlbumState,
+    SourceAlbumStateLoadError,
+};
+use euphony_library::state::transcoded::{
+    TranscodedAlbumState,
+    TranscodedAlbumStateLoadError,
+};
 use euphony_library::state::AlbumFileChangesV2;
 use euphony_library::view::library::LibraryViewError;
 use euphony_library::view::{
+    AlbumSourceFileList,
     AlbumView,
     ArtistView,
     LibraryView,
@@ -18,19 +30,63 @@ use euphony_library::view::{
     SharedArtistView,
     SharedLibraryView,
 };
-use miette::{miette, Context, IntoDiagnostic, Result};
-
+use miette::{miette, Context, Diagnostic, IntoDiagnostic, Result};
+use rayon::prelude::*;
+use thiserror::Error;
+use tracing::debug;
+
+use self::case_collisions::retain_albums_without_case_collisions;
+use self::checksums::{remove_checksum_manifest, write_checksum_manifest};
+use self::dry_run::{collect_album_file_actions, log_dry_run_preview};
+use self::events::TranscodeEventSink;
+use self::explain::{
+    explain_album_changes,
+    explain_file_action,
+    explain_library_changes,
+};
+use self::interrupt::{install_interrupt_handler, is_interrupted};
 use self::library_state::{
     LibraryState,
     LibraryStateLoadError,
     TrackedAlbum,
     TrackedArtistAlbums,
-    LIBRARY_STATE_FILE_NAME,
 };
-use self::state::changes::FileType;
+use self::playlist::{write_master_playlist, MASTER_PLAYLIST_FILE_NAME};
+use self::prune::prune_empty_directories;
+use self::replaygain::apply_replaygain_to_album;
+use self::scan::{
+    build_scan_thread_pool,
+    scan_all_albums_for_changes,
+    ScannedArtistAlbums,
+};
+use self::scan_fingerprint::{
+    compute_library_fingerprint,
+    ScanFingerprints,
+    ScanFingerprintsLoadError,
+};
+use self::shard::{retain_albums_in_shard, Shard};
+use self::size_budget::{compute_directory_size, BYTES_PER_MIB};
+use self::size_estimate::{check_free_space, log_output_size_estimate};
+use self::state::changes::{FileProcessingAction, FileType};
 use self::state::generate_jobs::GenerateChanges;
+use self::status_file::{
+    FileProgress,
+    ItemProgress,
+    RunPhase,
+    RunStatus,
+    StatusFile,
+};
+use self::target::{retain_albums_in_target, TranscodeTarget};
 use crate::commands::transcode::jobs::common::FileJobMessage;
-use crate::commands::transcode::jobs::{CancellableThreadPool, FileJobResult};
+use crate::commands::transcode::jobs::{
+    CancellableTask,
+    CancellableThreadPool,
+    FailedTask,
+    FileJobAction,
+    FileJobResult,
+    FileProgressSink,
+};
+use crate::console::formatting::format_byte_size;
 use crate::console::frontends::shared::queue::{
     AlbumQueueItem,
     AlbumQueueItemFinishedResult,
@@ -40,20 +96,162 @@ use crate::console::frontends::shared::queue::{
     QueueItemID,
 };
 use crate::console::frontends::TranscodeTerminal;
-use crate::console::{
-    LogBackend,
-    TranscodeBackend,
-    UserControlMessage,
-    UserControllableBackend,
-};
+use crate::console::{LogBackend, UserControlMessage};
 use crate::globals::is_verbose_enabled;
 
+pub mod case_collisions;
+pub mod checksums;
+pub mod dry_run;
+pub mod events;
+pub mod explain;
+pub mod explicit_paths;
+pub mod interrupt;
 pub mod jobs;
 pub mod library_state;
+pub mod notification;
+pub mod playlist;
+pub mod prune;
+pub mod replaygain;
+pub mod scan;
+pub mod scan_fingerprint;
+pub mod shard;
+pub mod size_budget;
+pub mod size_estimate;
 pub mod state;
+pub mod status_file;
+pub mod target;
+pub mod watch;
+
+
+/// Minimum number of transcoded files for the parallelism summary to be printed
+/// (see `log_parallelism_summary`).
+const MIN_FILES_FOR_PARALLELISM_SUMMARY: usize = 5;
+
+/// Minimum number of previously processed albums for the reprocessing safeguard
+/// to apply (see `check_reprocessing_safeguard`), so that small collections aren't affected.
+const MIN_ALBUMS_FOR_REPROCESSING_SAFEGUARD: usize = 10;
+
+
+/// Options that influence the behaviour of the `transcode` command.
+#[derive(Clone, Default)]
+pub struct TranscodeOptions {
+    /// If enabled, libraries whose scan fingerprint matches the one saved
+    /// during the last transcode are skipped without a full scan.
+    /// See `scan_fingerprint::compute_library_fingerprint` for more information.
+    pub use_cached_scan: bool,
+
+    /// If enabled, albums that are up to date (and are therefore skipped)
+    /// are listed as well, not only the ones that need to be processed.
+    pub print_unchanged: bool,
+
+    /// If enabled, albums are scanned for changes in parallel
+    /// (using `aggregated_library.transcode_threads` threads).
+    pub parallel_scan: bool,
+
+    /// If set, only the changed albums that belong to this shard are processed
+    /// (see `shard::Shard`). As other shards are processed separately,
+    /// library states and scan fingerprints are not saved in this case.
+    pub shard: Option<Shard>,
+
+    /// If enabled, the libraries are scanned as usual, but instead of processing the changes,
+    /// the albums and files that would be processed are only listed (see `dry_run`).
+    /// Nothing in the aggregated library is touched and no state files are saved.
+    pub dry_run: bool,
+
+    /// If set, a small JSON file describing the progress of the run is periodically
+    /// written to this path (see `status_file::StatusFile`).
+    pub status_file_path: Option<PathBuf>,
+
+    /// If enabled, the run proceeds even if more albums than allowed by
+    /// `aggregated_library.max_reprocess_percentage` need to be processed again.
+    pub allow_mass_reprocessing: bool,
+
+    /// If enabled, the run proceeds even if the estimated output would not leave
+    /// `aggregated_library.free_space_margin_mib` of free space on the aggregated library's
+    /// volume (see `size_estimate::check_free_space`).
+    pub ignore_free_space: bool,
+
+    /// If enabled, empty directories in the aggregated library are removed
+    /// at the end of the run (see `prune::prune_empty_directories`).
+    pub prune_empty_directories: bool,
+
+    /// If enabled (along with `prune_empty_directories`), directories that only contain
+    /// hidden files are pruned as well.
+    pub prune_directories_with_hidden_files: bool,
+}
+
+/// Returned (as the outermost cause) when ffmpeg can't be run before transcoding
+/// (see `ensure_ffmpeg_is_available`), so callers can tell it apart from other errors.
+#[derive(Error, Debug, Diagnostic)]
+#[error(
+    "ffmpeg is not available, aborting before processing anything \
+    (check tools.ffmpeg.binary in the configuration)."
+)]
+pub struct FfmpegUnavailableError;
+
+/// What a finished `transcode_collection` (or `cmd_transcode_paths`) run has processed.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TranscodeRunSummary {
+    /// Number of processed albums (including the ones with failed files).
+    /// Zero if everything was already up to date or this was a dry run.
+    pub num_processed_albums: usize,
+
+    /// Number of processed albums with at least one failed file.
+    pub num_errored_albums: usize,
+}
+
+
+/// Set up the terminal progress bar with zero progress and the given total
+/// and return the matching `GlobalProgress`.
+///
+/// The library and album totals and the status file (if any) are left for the caller to set.
+fn initialize_progress<'config>(
+    terminal: &impl TranscodeEventSink<'config>,
+    num_total_files: usize,
+) -> Result<GlobalProgress> {
+    let global_progress = GlobalProgress {
+        num_total_files,
+        num_total_libraries: 0,
+        num_finished_libraries: 0,
+        num_total_albums: 0,
+        num_finished_albums: 0,
+        status_file: None,
+        audio_files_currently_processing: 0,
+        data_files_currently_processing: 0,
+        audio_files_finished_ok: 0,
+        data_files_finished_ok: 0,
+        audio_files_errored: 0,
+        data_files_errored: 0,
+        ffmpeg_total_duration: Duration::ZERO,
+        ffmpeg_num_files: 0,
+        num_transcoded_files: 0,
+        num_copied_files: 0,
+        num_skipped_files: 0,
+        num_deleted_files: 0,
+        num_bytes_written: 0,
+    };
+
+    terminal.on_progress(&global_progress)?;
+
+    Ok(global_progress)
+}
 
 
 pub struct GlobalProgress {
+    pub num_total_files: usize,
+
+    pub num_total_libraries: usize,
+
+    pub num_finished_libraries: usize,
+
+    pub num_total_albums: usize,
+
+    pub num_finished_albums: usize,
+
+    /// If set, the progress is periodically written into this file
+    /// (see `transcode --status-file`).
+    pub status_file: Option<StatusFile>,
+
     pub audio_files_currently_processing: usize,
 
     pub data_files_currently_processing: usize,
@@ -65,18 +263,218 @@ pub struct GlobalProgress {
     pub audio_files_errored: usize,
 
     pub data_files_errored: usize,
+
+    /// Sum of the durations of all ffmpeg runs (see `log_parallelism_summary`).
+    pub ffmpeg_total_duration: Duration,
+
+    /// Number of ffmpeg runs included in `ffmpeg_total_duration`.
+    pub ffmpeg_num_files: usize,
+
+    /// Number of successfully transcoded audio files (see `FileJobAction`).
+    pub num_transcoded_files: usize,
+
+    /// Number of files successfully copied as-is.
+    pub num_copied_files: usize,
+
+    /// Number of files that turned out not to need any work.
+    pub num_skipped_files: usize,
+
+    /// Number of files successfully deleted from the aggregated library.
+    pub num_deleted_files: usize,
+
+    /// Total size of the transcoded and copied files written into the aggregated library.
+    pub num_bytes_written: u64,
 }
 
+impl GlobalProgress {
+    /// Count a successfully finished file in the end-of-run summary (see `log_run_summary`).
+    fn record_file_action(&mut self, action: FileJobAction) {
+        match action {
+            FileJobAction::Transcoded { bytes_written } => {
+                self.num_transcoded_files += 1;
+                self.num_bytes_written += bytes_written;
+            }
+            FileJobAction::Copied { bytes_written } => {
+                self.num_copied_files += 1;
+                self.num_bytes_written += bytes_written;
+            }
+            FileJobAction::Skipped => self.num_skipped_files += 1,
+            FileJobAction::Deleted => self.num_deleted_files += 1,
+        }
+    }
 
-fn process_album<'config>(
-    queued_album: QueuedAlbum<'config>,
-    progress: &mut GlobalProgress,
-    terminal: &TranscodeTerminal<'config, '_>,
-    terminal_user_input_receiver: &mut tokio::sync::broadcast::Receiver<
-        UserControlMessage,
-    >,
-) -> Result<()> {
-    // TODO A percentage of storage saved after each file finishes would be cool.
+    /// Describe the current progress as a `RunStatus` (see `status_file`).
+    fn to_run_status(&self, phase: RunPhase) -> RunStatus {
+        let files_finished_ok =
+            self.audio_files_finished_ok + self.data_files_finished_ok;
+        let files_errored = self.audio_files_errored + self.data_files_errored;
+
+        RunStatus::new(
+            phase,
+            ItemProgress::new(
+                self.num_finished_libraries,
+                self.num_total_libraries,
+            ),
+            ItemProgress::new(self.num_finished_albums, self.num_total_albums),
+            FileProgress {
+                progress: ItemProgress::new(
+                    files_finished_ok + files_errored,
+                    self.num_total_files,
+                ),
+                finished_ok: files_finished_ok,
+                errored: files_errored,
+                currently_processing: self.audio_files_currently_processing
+                    + self.data_files_currently_processing,
+            },
+        )
+    }
+
+    /// Write the current progress into the status file, if there is one.
+    /// Unless `force` is set, this is throttled and does nothing if the file was written recently.
+    pub fn update_status_file(
+        &mut self,
+        terminal: &impl LogBackend,
+        phase: RunPhase,
+        force: bool,
+    ) {
+        let run_status = self.to_run_status(phase);
+
+        if let Some(status_file) = &mut self.status_file {
+            if force || status_file.is_write_due() {
+                write_status_file(terminal, status_file, &run_status);
+            }
+        }
+    }
+}
+
+
+/// Write a status without any progress into `status_file` (if there is one), e.g. while
+/// scanning or when there turned out to be nothing to process.
+fn write_status_without_progress(
+    terminal: &impl LogBackend,
+    status_file: Option<&mut StatusFile>,
+    phase: RunPhase,
+) {
+    let Some(status_file) = status_file else {
+        return;
+    };
+
+    // With nothing to process, a finished run is fully (100%) done.
+    let empty_progress = if phase == RunPhase::Finished {
+        ItemProgress::new(0, 0)
+    } else {
+        ItemProgress::default()
+    };
+
+    let run_status = RunStatus::new(
+        phase,
+        empty_progress.clone(),
+        empty_progress.clone(),
+        FileProgress {
+            progress: empty_progress,
+            ..FileProgress::default()
+        },
+    );
+
+    write_status_file(terminal, status_file, &run_status);
+}
+
+/// Write `run_status` into `status_file`. The status file is only a monitoring aid,
+/// so a failed write is logged as a warning instead of aborting the run.
+fn write_status_file(
+    terminal: &impl LogBackend,
+    status_file: &mut StatusFile,
+    run_status: &RunStatus,
+) {
+    if let Err(error) = status_file.write(run_status) {
+        terminal.log_println(format!(
+            "{} Failed to write status file: {error}",
+            "WARNING:".yellow()
+        ));
+    }
+}
+
+
+/// Delete the output files of a cancelled album (see `process_queued_albums`).
+/// Failed deletions are only logged, as there is nothing more we can do about them.
+fn delete_partially_processed_files(
+    terminal: &impl LogBackend,
+    album_artist_name: &str,
+    album_title: &str,
+    started_target_file_paths: &[PathBuf],
+) {
+    let mut num_deleted_files: usize = 0;
+
+    for target_file_path in started_target_file_paths {
+        if !target_file_path.is_file() {
+            continue;
+        }
+
+        match fs::remove_file(target_file_path) {
+            Ok(()) => num_deleted_files += 1,
+            Err(error) => {
+                terminal.log_println(format!(
+                    "{} Could not delete partially processed file {:?}: {error}",
+                    "WARNING:".red(),
+                    target_file_path,
+                ));
+            }
+        }
+    }
+
+    if num_deleted_files > 0 {
+        terminal.log_println(format!(
+            "Deleted {num_deleted_files} partially processed files \
+            of the cancelled album \"{album_artist_name} - {album_title}\"."
+        ));
+    }
+}
+
+
+/// Processing progress of a single album whose file jobs have been queued
+/// onto the thread pool (see `process_queued_albums`).
+struct AlbumInProgress {
+    time_start: Instant,
+
+    /// Queue items of all the album's files.
+    file_queue_items: Vec<QueueItemID>,
+
+    /// Number of the album's file jobs that haven't finished yet.
+    num_remaining_files: usize,
+
+    /// Number of the album's file jobs that finished with an error.
+    num_errored_files: usize,
+
+    /// Target paths of all the file jobs that have started, so their (potentially partial)
+    /// output can be deleted if the run is cancelled.
+    started_target_file_paths: Vec<PathBuf>,
+
+    /// Whether the task writing the album's ReplayGain tags has finished
+    /// (see `finish_album_or_queue_replaygain`).
+    has_written_replaygain_tags: bool,
+}
+
+/// Albums (and their files) that are currently being processed (see `process_queued_albums`).
+#[derive(Default)]
+struct AlbumsInProgress {
+    /// Albums whose file jobs have been queued, keyed by their index in the queued album list.
+    albums: HashMap<usize, AlbumInProgress>,
+
+    /// Index of the album (in the queued album list) each queued file belongs to.
+    file_item_albums: HashMap<QueueItemID, usize>,
+
+    /// Number of finished albums that had at least one failed file.
+    num_errored_albums: usize,
+}
+
+/// Start processing an album: generate its file jobs, add the files to the file queue
+/// and queue the jobs onto the (shared) thread pool.
+fn start_album<'config>(
+    configuration: &Arc<Configuration>,
+    queued_album: &QueuedAlbum<'config>,
+    terminal: &impl TranscodeEventSink<'config>,
+    thread_pool: &mut CancellableThreadPool<impl FileProgressSink + Clone>,
+) -> Result<AlbumInProgress> {
     let time_album_start = Instant::now();
 
     let (album_artist_name, album_title, album_library_name) = {
@@ -91,255 +489,563 @@ fn process_album<'config>(
         )
     };
 
-    terminal.queue_album_item_start(queued_album.queue_id)?;
+    terminal.on_album_started(queued_album.queue_id)?;
     terminal.log_println(format!(
         "↳ Transcoding album \"{album_artist_name} - {album_title}\" (library: {album_library_name})"
     ));
 
-    if is_verbose_enabled() {
-        terminal.log_println(format!(
-            "Album changes: {:?}",
-            queued_album.changes
-        ));
+    explain_album_changes(
+        terminal,
+        &queued_album.changes,
+        queued_album.job_type,
+    );
+
+    debug!(
+        library = %album_library_name,
+        artist = %album_artist_name,
+        album = %album_title,
+        changes = ?queued_album.changes,
+        "Album changes."
+    );
+    debug!(
+        library = %album_library_name,
+        artist = %album_artist_name,
+        album = %album_title,
+        file_map = ?queued_album
+            .changes
+            .tracked_source_files
+            .as_ref()
+            .map(|files| files
+                .map_source_file_paths_to_transcoded_file_paths_absolute())
+            .unwrap_or_default(),
+        "Absolute source to transcoded file paths."
+    );
+
+    let file_queue_items: RefCell<Vec<QueueItemID>> = RefCell::new(Vec::new());
+
+    // Generate all file jobs.
+    let changes = &queued_album.changes;
+    let jobs = changes.generate_file_jobs(configuration, |context| {
+        // Parse queue item details.
+        let target_path = context.action.target_path();
+        let file_name = target_path
+            .file_name()
+            .ok_or_else(|| {
+                miette!("Invalid path: no file name: {:?}.", target_path)
+            })?
+            .to_string_lossy();
+
+        explain_file_action(terminal, &context.action);
+
+        // Instantiate `FileItem` and add to queue.
+        let file_item = FileQueueItem::<'config>::new(
+            queued_album.album.clone(),
+            file_name.to_string(),
+            context,
+        );
+
+        let queued_file_item_id = terminal.on_file_queued(file_item)?;
+        file_queue_items.borrow_mut().push(queued_file_item_id);
+
+        Ok(queued_file_item_id)
+    })?;
+
+    let num_jobs = jobs.len();
+    for job in jobs {
+        // This does not block! The thread pool has an internal job queue.
+        thread_pool.queue_task(job);
     }
 
-    let (worker_tx, worker_rx) = channel::unbounded::<FileJobMessage>();
-    let (processing_control_tx, processing_control_rx) =
-        channel::unbounded::<MainThreadMessage>();
-
-    let mut user_requested_cancellation = false;
-
-    thread::scope::<'_, _, Result<()>>(|scope| {
-        // Spawn a thread that will manage the following:
-        // - initialize the thread pool
-        // - spawn workers that will work on jobs generated from `queued_album.changes`,
-        // - fill up the file queue on the terminal frontend.
-        let processing_thread_handle = scope.spawn(|| {
-            process_changes(
-                &queued_album.changes,
-                queued_album.album.clone(),
-                terminal,
-                worker_tx,
-                processing_control_rx,
-            )
-        });
+    Ok(AlbumInProgress {
+        time_start: time_album_start,
+        file_queue_items: file_queue_items.into_inner(),
+        num_remaining_files: num_jobs,
+        num_errored_files: 0,
+        started_target_file_paths: Vec::new(),
+        has_written_replaygain_tags: false,
+    })
+}
 
-        // We didn't block on `process_album_changes` above because we want to be able to receive two things:
-        // - user input messages through `terminal_user_input_receiver` and
-        // - album transcode/copy/delete progress through `worker_rx`.
-        // Not blocking above means we keep doing the above things until the processing thread says
-        // it's done with the album or until some other fail state (such as cancellation).
-        loop {
-            // Check and handle job progress.
-            let worker_job_message =
-                worker_rx.recv_timeout(Duration::from_millis(1));
-
-            match worker_job_message {
-                Ok(job_message) => match job_message {
-                    FileJobMessage::Starting {
-                        queue_item,
-                        file_type,
-                        file_path,
-                    } => {
+/// Handle a single message from the file job workers: update the progress,
+/// the file queue and the processing progress of the file's album.
+///
+/// Returns the index of the file's album if this was its last unfinished file.
+fn handle_file_job_message<'config>(
+    job_message: FileJobMessage,
+    albums_in_progress: &mut AlbumsInProgress,
+    progress: &mut GlobalProgress,
+    terminal: &impl TranscodeEventSink<'config>,
+) -> Result<Option<usize>> {
+    match job_message {
+        FileJobMessage::Starting {
+            queue_item,
+            file_type,
+            file_path,
+        } => {
+            debug!(file = %file_path, ?file_type, "File starting.");
+
+            if let Some(album) = albums_in_progress
+                .file_item_albums
+                .get(&queue_item)
+                .and_then(|album_index| {
+                    albums_in_progress.albums.get_mut(album_index)
+                })
+            {
+                album
+                    .started_target_file_paths
+                    .push(PathBuf::from(&file_path));
+            }
+
+            terminal.on_file_started(queue_item)?;
+
+            match file_type {
+                FileType::Audio => {
+                    progress.audio_files_currently_processing += 1;
+                }
+                FileType::Data | FileType::Unknown => {
+                    progress.data_files_currently_processing += 1;
+                }
+            }
+
+            terminal.on_progress(progress)?;
+        }
+        FileJobMessage::Progress {
+            queue_item,
+            progress,
+        } => {
+            terminal.on_file_progress(queue_item, progress)?;
+        }
+        FileJobMessage::Finished {
+            queue_item,
+            file_type,
+            processing_result,
+            file_path,
+            ffmpeg_duration,
+        } => {
+            debug!(
+                file = %file_path,
+                ?file_type,
+                result = ?processing_result,
+                "File finished."
+            );
+
+            match file_type {
+                FileType::Audio => {
+                    progress.audio_files_currently_processing -= 1;
+                }
+                FileType::Data | FileType::Unknown => {
+                    progress.data_files_currently_processing -= 1;
+                }
+            }
+
+            if let Some(ffmpeg_duration) = ffmpeg_duration {
+                progress.ffmpeg_total_duration += ffmpeg_duration;
+                progress.ffmpeg_num_files += 1;
+            }
+
+            terminal.on_progress(progress)?;
+
+            let is_ok = matches!(processing_result, FileJobResult::Okay { .. });
+
+            let item_result = match processing_result {
+                FileJobResult::Okay {
+                    action,
+                    verbose_info,
+                } => {
+                    if let Some(verbose_info) = verbose_info {
                         if is_verbose_enabled() {
-                            terminal.log_println(format!(
-                                "File starting: {file_path} ({file_type:?})"
-                            ));
+                            terminal.log_println(verbose_info);
                         }
+                    }
 
-                        terminal.queue_file_item_start(queue_item)?;
+                    progress.record_file_action(action);
 
-                        match file_type {
-                            FileType::Audio => {
-                                progress.audio_files_currently_processing += 1;
-                            }
-                            FileType::Data | FileType::Unknown => {
-                                progress.data_files_currently_processing += 1;
-                            }
+                    match file_type {
+                        FileType::Audio => {
+                            progress.audio_files_finished_ok += 1;
+                        }
+                        FileType::Data => {
+                            progress.data_files_finished_ok += 1;
+                        }
+                        FileType::Unknown => {
+                            terminal.log_println("REPORT THIS BUG: Unexpected OK FileType::Unknown!");
                         }
-
-                        terminal.progress_set_audio_files_currently_processing(
-                            progress.audio_files_currently_processing,
-                        )?;
-                        terminal.progress_set_data_files_currently_processing(
-                            progress.data_files_currently_processing,
-                        )?;
                     }
-                    FileJobMessage::Finished {
-                        queue_item,
-                        file_type,
-                        processing_result,
-                        file_path,
-                    } => {
+
+                    FileQueueItemFinishedResult::Ok
+                }
+                FileJobResult::Errored {
+                    error,
+                    verbose_info,
+                    ..
+                } => {
+                    if let Some(verbose_info) = verbose_info {
                         if is_verbose_enabled() {
-                            terminal.log_println(format!(
-                                "File finished: {file_path} ({file_type:?}) result={processing_result:?}"
-                            ));
+                            terminal.log_println(verbose_info);
                         }
+                    }
 
-                        match file_type {
-                            FileType::Audio => {
-                                progress.audio_files_currently_processing -= 1;
-                            }
-                            FileType::Data | FileType::Unknown => {
-                                progress.data_files_currently_processing -= 1;
-                            }
+                    match file_type {
+                        FileType::Audio => {
+                            progress.audio_files_errored += 1;
+                        }
+                        FileType::Data => {
+                            progress.data_files_errored += 1;
                         }
+                        FileType::Unknown => {
+                            terminal.log_println("REPORT THIS BUG: Unexpected ERR FileType::Unknown!");
+                        }
+                    };
 
-                        terminal.progress_set_audio_files_currently_processing(
-                            progress.audio_files_currently_processing,
-                        )?;
-                        terminal.progress_set_data_files_currently_processing(
-                            progress.data_files_currently_processing,
-                        )?;
-
-                        let item_result = match processing_result {
-                            FileJobResult::Okay { verbose_info } => {
-                                if let Some(verbose_info) = verbose_info {
-                                    if is_verbose_enabled() {
-                                        terminal.log_println(verbose_info);
-                                    }
-                                }
-
-                                match file_type {
-                                    FileType::Audio => {
-                                        progress.audio_files_finished_ok += 1;
-                                        terminal.progress_set_audio_files_finished_ok(progress.audio_files_finished_ok)?;
-                                    }
-                                    FileType::Data => {
-                                        progress.data_files_finished_ok += 1;
-                                        terminal
-                                            .progress_set_data_files_finished_ok(
-                                                progress.data_files_finished_ok,
-                                            )?;
-                                    }
-                                    FileType::Unknown => {
-                                        terminal.log_println("REPORT THIS BUG: Unexpected OK FileType::Unknown!");
-                                    }
-                                }
-
-                                FileQueueItemFinishedResult::Ok
-                            }
-                            FileJobResult::Errored {
-                                error,
-                                verbose_info,
-                            } => {
-                                if let Some(verbose_info) = verbose_info {
-                                    if is_verbose_enabled() {
-                                        terminal.log_println(verbose_info);
-                                    }
-                                }
-
-                                match file_type {
-                                    FileType::Audio => {
-                                        progress.audio_files_errored += 1;
-                                        terminal
-                                            .progress_set_audio_files_errored(
-                                                progress.audio_files_errored,
-                                            )?;
-                                    }
-                                    FileType::Data => {
-                                        progress.data_files_errored += 1;
-                                        terminal
-                                            .progress_set_data_files_errored(
-                                                progress.data_files_errored,
-                                            )?;
-                                    }
-                                    FileType::Unknown => {
-                                        terminal.log_println("REPORT THIS BUG: Unexpected ERR FileType::Unknown!");
-                                    }
-                                };
-
-                                FileQueueItemFinishedResult::Failed(
-                                    FileQueueItemErrorType::Errored { error },
-                                )
-                            }
-                        };
+                    FileQueueItemFinishedResult::Failed(
+                        FileQueueItemErrorType::Errored { error },
+                    )
+                }
+            };
 
-                        // TODO File that fail once should retry (see the configuration).
-                        // TODO Errored files should stop the transcode.
-                        terminal
-                            .queue_file_item_finish(queue_item, item_result)?;
-                    }
-                    FileJobMessage::Cancelled { queue_item, .. } => {
-                        let item_result = FileQueueItemFinishedResult::Failed(
-                            FileQueueItemErrorType::Cancelled,
-                        );
+            terminal.on_progress(progress)?;
+            terminal.on_file_finished(queue_item, item_result)?;
 
-                        terminal
-                            .queue_file_item_finish(queue_item, item_result)?;
-                    }
-                    FileJobMessage::Log { content } => {
-                        terminal.log_println(content);
-                    }
-                },
-                Err(error) => {
-                    if error == RecvTimeoutError::Disconnected {
-                        // This happens when the sender (i.e. the processing thread) drops the
-                        // sender handle (i.e. stops).
-                        // This error indicates that processing has (or is about to) finish.
-                        break;
-                    }
-                }
+            progress.update_status_file(terminal, RunPhase::Processing, false);
+
+            let Some(album_index) =
+                albums_in_progress.file_item_albums.remove(&queue_item)
+            else {
+                return Ok(None);
+            };
+            let Some(album) = albums_in_progress.albums.get_mut(&album_index)
+            else {
+                return Ok(None);
+            };
+
+            album.num_remaining_files -= 1;
+            if !is_ok {
+                album.num_errored_files += 1;
             }
 
+            if album.num_remaining_files == 0 {
+                return Ok(Some(album_index));
+            }
+        }
+        FileJobMessage::Retrying {
+            file_type,
+            file_path,
+            error,
+            category,
+            failed_attempt,
+            max_attempts,
+        } => {
+            terminal.log_println(format!(
+                "{} Attempt {failed_attempt} of {max_attempts} failed for \
+                {file_path} ({file_type:?}), retrying. Error ({}): {error}",
+                "WARNING:".yellow(),
+                category.name(),
+            ));
+        }
+        FileJobMessage::Cancelled { queue_item, .. } => {
+            let item_result = FileQueueItemFinishedResult::Failed(
+                FileQueueItemErrorType::Cancelled,
+            );
 
-            // Check and handle user input from the terminal frontend.
-            let user_input = terminal_user_input_receiver.try_recv();
+            terminal.on_file_finished(queue_item, item_result)?;
+        }
+        FileJobMessage::Log { content } => {
+            terminal.log_println(content);
+        }
+        FileJobMessage::ReplayGainFinished { album_index } => {
+            let Some(album) = albums_in_progress.albums.get_mut(&album_index)
+            else {
+                return Ok(None);
+            };
 
-            if let Ok(user_input) = user_input {
-                match user_input {
-                    UserControlMessage::Exit if !user_requested_cancellation => {
-                        user_requested_cancellation = true;
+            album.has_written_replaygain_tags = true;
+            return Ok(Some(album_index));
+        }
+    }
 
-                        terminal.log_println(
-                            "User wants to exit, cancelling transcode.",
-                        );
+    Ok(None)
+}
 
-                        processing_control_tx
-                            .send(MainThreadMessage::StopProcessing)
-                            .into_diagnostic()?;
-                    }
-                    _ => {}
-                }
-            }
+/// Regenerate the checksum manifest of an album whose files were processed explicitly
+/// (see `QueuedAlbumJobType::ExplicitFiles`). As the album states are not updated in that case,
+/// the output files are listed from the current source files (only existing ones are included).
+fn write_explicit_album_checksum_manifest(
+    queued_album: &QueuedAlbum,
+) -> Result<()> {
+    let album_transcoded_directory_path = queued_album
+        .album
+        .read()
+        .album_directory_in_transcoded_library();
+
+    let source_file_list =
+        AlbumSourceFileList::from_album_view(queued_album.album.clone())?;
+    let source_to_transcoded_file_paths = source_file_list
+        .map_source_file_paths_to_transcoded_file_paths_relative();
+
+    let output_file_paths: Vec<PathBuf> = source_to_transcoded_file_paths
+        .audio
+        .into_values()
+        .chain(source_to_transcoded_file_paths.data.into_values())
+        .filter(|output_file_path| {
+            album_transcoded_directory_path
+                .join(output_file_path)
+                .is_file()
+        })
+        .collect();
 
+    write_checksum_manifest(
+        album_transcoded_directory_path,
+        &output_file_paths,
+    )
+}
 
-            // Finally, if the processing thread has finished, we should stop.
-            if processing_thread_handle.is_finished() {
-                break;
+/// Merge the states of the files processed from an explicit file list (see `cmd_transcode_paths`)
+/// into the album's saved source and transcoded states, so the next full transcode doesn't
+/// process those files again. Transcoded files that no longer exist are pruned from
+/// the transcoded state along the way.
+///
+/// Albums without (current) saved states are left as they are, as all of their files
+/// are processed on the next full transcode anyway.
+fn merge_explicit_files_into_saved_album_states(
+    queued_album: &QueuedAlbum,
+) -> Result<()> {
+    let explicit_source_state =
+        queued_album.changes.generate_source_album_state()?;
+    let explicit_transcoded_state =
+        queued_album.changes.generate_transcoded_album_state()?;
+
+    let album_view = queued_album.album.read();
+    let aggregated_library =
+        &album_view.euphony_configuration().aggregated_library;
+    let source_album_directory = album_view.album_directory_in_source_library();
+    let transcoded_album_directory =
+        album_view.album_directory_in_transcoded_library();
+
+    let mut source_album_state = match SourceAlbumState::load_from_directory(
+        &source_album_directory,
+        aggregated_library.mode,
+        aggregated_library.device_profile.as_deref(),
+    ) {
+        Ok(state) => state,
+        Err(error) => match error {
+            SourceAlbumStateLoadError::NotFound
+            | SourceAlbumStateLoadError::SchemaVersionMismatch(_) => {
+                return Ok(());
             }
-        }
+            _ => return Err(error.into()),
+        },
+    };
 
+    let mut transcoded_album_state =
+        match TranscodedAlbumState::load_from_directory(
+            &transcoded_album_directory,
+        ) {
+            Ok(state) => state,
+            Err(error) => match error {
+                TranscodedAlbumStateLoadError::NotFound
+                | TranscodedAlbumStateLoadError::SchemaVersionMismatch(_) => {
+                    return Ok(());
+                }
+                _ => return Err(error.into()),
+            },
+        };
 
-        // Wait for processing thread to fully finish.
-        processing_thread_handle
-            .join()
-            .expect("Album processing thread panicked.")
-    })?;
+    // Source files are not pruned: the next full transcode must still see removed
+    // source files as removed, so their transcoded counterparts are deleted.
+    source_album_state
+        .tracked_files
+        .merge(explicit_source_state.tracked_files);
 
+    let transcoded_files = &mut transcoded_album_state.transcoded_files;
+    transcoded_files.merge(explicit_transcoded_state.transcoded_files);
+    transcoded_files.prune_missing_files(&transcoded_album_directory);
 
-    if user_requested_cancellation {
-        let album_view = queued_album.album.read();
+    let transcoded_to_original_file_paths =
+        &mut transcoded_album_state.transcoded_to_original_file_paths;
+    for (file_map, explicit_file_map) in [
+        (
+            &mut transcoded_to_original_file_paths.audio,
+            explicit_transcoded_state
+                .transcoded_to_original_file_paths
+                .audio,
+        ),
+        (
+            &mut transcoded_to_original_file_paths.data,
+            explicit_transcoded_state
+                .transcoded_to_original_file_paths
+                .data,
+        ),
+    ] {
+        file_map.extend(explicit_file_map);
+        file_map.retain(|transcoded_file_path, _| {
+            transcoded_files.contains_file(transcoded_file_path)
+        });
+    }
 
-        terminal.log_println(format!(
-            "{} A partially-transcoded album ({} - {}) has been potentially left behind \
-            in the transcoded library.",
-            "WARNING:".red(),
-            album_view.read_lock_artist().name,
-            album_view.title,
-        ));
+    source_album_state.save_to_directory(
+        &source_album_directory,
+        true,
+        aggregated_library.mode,
+        aggregated_library.device_profile.as_deref(),
+    )?;
+    transcoded_album_state.save_to_directory(&transcoded_album_directory, true)
+}
 
-        return Err(miette!("User aborted transcoding."));
+/// List the transcoded audio files of a finished album whose ReplayGain tags need
+/// to be written (see `libraries.<key>.transcoding.replaygain`). Returns `None` if the tags
+/// are disabled or if the album has no transcoded or deleted audio files,
+/// as its gains can't have changed in that case.
+fn replaygain_audio_file_paths(
+    configuration: &Arc<Configuration>,
+    queued_album: &QueuedAlbum,
+) -> Result<Option<Vec<PathBuf>>> {
+    if !queued_album
+        .album
+        .read()
+        .library_configuration()
+        .transcoding
+        .replaygain
+        || configuration.aggregated_library.mode == AggregationMode::Mirror
+    {
+        return Ok(None);
     }
 
+    let ffmpeg = &configuration.tools.ffmpeg;
+    let has_audio_changes =
+        collect_album_file_actions(configuration, &queued_album.changes)?
+            .iter()
+            .any(|action| match action {
+                FileProcessingAction::Transcode { .. } => true,
+                FileProcessingAction::Copy { .. } => false,
+                FileProcessingAction::DeleteInTranscoded {
+                    target_path, ..
+                } => ffmpeg
+                    .is_path_transcoding_output_by_extension(target_path)
+                    .unwrap_or(false),
+            });
+
+    if !has_audio_changes {
+        return Ok(None);
+    }
+
+    let source_file_list =
+        AlbumSourceFileList::from_album_view(queued_album.album.clone())?;
+    let library_transcoding_configuration = &queued_album
+        .album
+        .read()
+        .library_configuration()
+        .transcoding;
+
+    // Audio files copied as-is (see `ExtensionTranscodingRule::Copy`) are left untouched.
+    let transcoded_audio_file_paths: Vec<PathBuf> = source_file_list
+        .map_source_file_paths_to_transcoded_file_paths_absolute()
+        .audio
+        .into_iter()
+        .filter(|(source_file_path, transcoded_file_path)| {
+            !library_transcoding_configuration
+                .is_path_copied_audio_file_by_extension(source_file_path)
+                .unwrap_or(false)
+                && transcoded_file_path.is_file()
+        })
+        .map(|(_, transcoded_file_path)| transcoded_file_path)
+        .collect();
+
+    Ok(Some(transcoded_audio_file_paths))
+}
+
+/// Queue a task that writes the ReplayGain tags into the given transcoded audio files
+/// of a finished album onto the (shared) thread pool, so the two ffmpeg passes
+/// don't block the album processing loop. Once done, the task reports back
+/// with `FileJobMessage::ReplayGainFinished`.
+///
+/// As the files are perfectly usable without the tags, failures are only logged as warnings.
+fn queue_replaygain_task(
+    configuration: &Arc<Configuration>,
+    queued_album: &QueuedAlbum,
+    album_index: usize,
+    transcoded_audio_file_paths: Vec<PathBuf>,
+    thread_pool: &mut CancellableThreadPool<impl FileProgressSink + Clone>,
+) {
+    let configuration = configuration.clone();
+
+    let album_view = queued_album.album.read();
+    let album_directory_path =
+        album_view.album_directory_in_transcoded_library();
+    let album_artist_name = album_view.read_lock_artist().name.clone();
+    let album_title = album_view.title.clone();
+    drop(album_view);
+
+    debug!(
+        artist = %album_artist_name,
+        album = %album_title,
+        num_files = transcoded_audio_file_paths.len(),
+        "Writing ReplayGain tags."
+    );
+
+    thread_pool.queue_task(CancellableTask::new(
+        album_directory_path,
+        Box::new(move |_, progress_sink| {
+            if let Err(error) = apply_replaygain_to_album(
+                &configuration.tools.ffmpeg.binary,
+                &transcoded_audio_file_paths,
+            ) {
+                progress_sink.log(format!(
+                    "{} Could not write the ReplayGain tags of album \
+                    \"{album_artist_name} - {album_title}\": {error}",
+                    "WARNING:".yellow()
+                ))?;
+            }
+
+            progress_sink.replaygain_finished(album_index)
+        }),
+    ));
+}
 
-    // There are now two possibilities:
+/// Finish an album whose file jobs have all completed (and whose ReplayGain tags
+/// have been written, see `finish_album_or_queue_replaygain`): save (or remove) its states,
+/// mark it as finished in the album queue and remove its files from the file queue.
+///
+/// If any of the album's files failed, its states are *not* saved, so the entire album
+/// is processed again on the next run. Returns whether all of the album's files succeeded.
+fn finish_album<'config>(
+    queued_album: &QueuedAlbum,
+    album_in_progress: AlbumInProgress,
+    progress: &mut GlobalProgress,
+    terminal: &impl TranscodeEventSink<'config>,
+) -> Result<bool> {
+    for file_queue_item in album_in_progress.file_queue_items {
+        terminal.on_file_removed(file_queue_item)?;
+    }
+
+    let (album_artist_name, album_title) = {
+        let album_view = queued_album.album.read();
+        let artist_view = album_view.read_lock_artist();
+
+        (artist_view.name.clone(), album_view.title.clone())
+    };
+
+    let time_album_elapsed =
+        album_in_progress.time_start.elapsed().as_secs_f64();
+    let all_files_ok = album_in_progress.num_errored_files == 0;
+
+    // There are now three possibilities:
+    // - if any of the files failed, we must not save the states, so the album is processed again next time,
     // - if the album was being processed normally, we should save the states (see below - `.album.source-state.euphony`, ...)
     // - but if the transcoded album was being deleted (e.g. when the source album is fully deleted),
     //   we need to remove those state files and possibly delete the empty directory that has now been left behind
 
-    if queued_album.job_type == QueuedAlbumJobType::NormalProcessing {
+    if !all_files_ok {
+        terminal.on_album_finished(
+            queued_album.queue_id,
+            AlbumQueueItemFinishedResult::new_failed(),
+        )?;
+        terminal.log_println(format!(
+            "{} {} files of album \"{album_artist_name} - {album_title}\" failed, \
+            album state has not been saved (the album will be processed again on the next run).",
+            "WARNING:".yellow(),
+            album_in_progress.num_errored_files,
+        ));
+    } else if queued_album.job_type == QueuedAlbumJobType::NormalProcessing {
         // The entire album is not up-to-date, so we generate two state structs that are then
         // saved as JSON:
         // - `.album.source-state.euphony` is saved in the source album directory
@@ -347,7 +1053,6 @@ fn process_album<'config>(
         // - `.album.transcode-state.euphony` is saved in the transcoded album directory
         //   and contains a mapping from transcoded files back to their originals
         //   as well as metadata of the tracked *transcoded* files.
-
         let source_album_state =
             queued_album.changes.generate_source_album_state()?;
         let transcoded_album_state =
@@ -355,28 +1060,75 @@ fn process_album<'config>(
 
         {
             let album_view = queued_album.album.read();
+            let aggregated_library =
+                &album_view.euphony_configuration().aggregated_library;
 
             source_album_state.save_to_directory(
                 album_view.album_directory_in_source_library(),
                 true,
+                aggregated_library.mode,
+                aggregated_library.device_profile.as_deref(),
             )?;
 
             transcoded_album_state.save_to_directory(
                 album_view.album_directory_in_transcoded_library(),
                 true,
             )?;
-        }
 
-        // Mark the album as finished in the album queue and clear the file queue.
-        terminal.queue_album_item_finish(
+            if album_view
+                .euphony_configuration()
+                .aggregated_library
+                .write_checksum_manifest
+            {
+                let transcoded_files = &transcoded_album_state.transcoded_files;
+                let output_file_paths: Vec<PathBuf> = transcoded_files
+                    .audio_files
+                    .keys()
+                    .chain(transcoded_files.data_files.keys())
+                    .map(PathBuf::from)
+                    .collect();
+
+                write_checksum_manifest(
+                    album_view.album_directory_in_transcoded_library(),
+                    &output_file_paths,
+                )?;
+            }
+        }
+
+        // Mark the album as finished in the album queue.
+        terminal.on_album_finished(
+            queued_album.queue_id,
+            AlbumQueueItemFinishedResult::new_ok(),
+        )?;
+
+        terminal.log_println(format!(
+            "  Album \"{album_artist_name} - {album_title}\" transcoded \
+            in {time_album_elapsed:.2} seconds."
+        ));
+    } else if queued_album.job_type == QueuedAlbumJobType::ExplicitFiles {
+        // Only some files were processed, so they are merged into the saved album states.
+        // The checksum manifest (like the album gain), however, covers all output files
+        // and must be regenerated.
+        merge_explicit_files_into_saved_album_states(queued_album)?;
+
+        let write_manifest = queued_album
+            .album
+            .read()
+            .euphony_configuration()
+            .aggregated_library
+            .write_checksum_manifest;
+        if write_manifest {
+            write_explicit_album_checksum_manifest(queued_album)?;
+        }
+
+        terminal.on_album_finished(
             queued_album.queue_id,
             AlbumQueueItemFinishedResult::new_ok(),
         )?;
-        terminal.queue_file_clear()?;
 
-        let time_album_elapsed = time_album_start.elapsed().as_secs_f64();
         terminal.log_println(format!(
-            "  Album transcoded in {time_album_elapsed:.2} seconds."
+            "  Files of album \"{album_artist_name} - {album_title}\" processed \
+            in {time_album_elapsed:.2} seconds."
         ));
     } else if queued_album.job_type == QueuedAlbumJobType::FullyRemoving {
         // The transcoded album was fully deleted, meaning we need to delete the state (`.*.euphony`) files
@@ -391,6 +1143,8 @@ fn process_album<'config>(
                 &album_transcoded_directory_path,
             );
 
+        remove_checksum_manifest(&album_transcoded_directory_path)?;
+
         if transcoded_album_state_file_path.exists()
             && transcoded_album_state_file_path.is_file()
         {
@@ -441,38 +1195,478 @@ fn process_album<'config>(
                 ));
             }
         }
+
+        // Removed albums (including ones whose output directory changed, e.g. due to
+        // an output template or file name sanitization) may leave empty parent directories
+        // (e.g. of the album artist) behind. Removal stops at the first directory that isn't empty.
+        let aggregated_library_path = Path::new(
+            &album_view.euphony_configuration().aggregated_library.path,
+        );
+
+        for parent_directory in album_transcoded_directory_path
+            .ancestors()
+            .skip(1)
+            .take_while(|path| {
+                path.starts_with(aggregated_library_path)
+                    && *path != aggregated_library_path
+            })
+        {
+            if fs::remove_dir(parent_directory).is_err() {
+                break;
+            }
+        }
+
+        terminal.on_album_finished(
+            queued_album.queue_id,
+            AlbumQueueItemFinishedResult::new_ok(),
+        )?;
     }
 
-    Ok(())
+    progress.num_finished_albums += 1;
+    progress.update_status_file(terminal, RunPhase::Processing, false);
+
+    Ok(all_files_ok)
+}
+
+/// The main loop of `process_queued_albums` and everything it works with: it keeps
+/// the thread pool fed with the file jobs of the following albums, handles worker messages
+/// and user input and finishes each album as soon as its last file job completes.
+struct AlbumProcessingLoop<'a, 'config, T> {
+    configuration: &'a Arc<Configuration>,
+
+    queued_albums: &'a [QueuedAlbum<'config>],
+
+    /// Runs the file jobs of all albums (and their ReplayGain tasks).
+    thread_pool: CancellableThreadPool<Sender<FileJobMessage>>,
+
+    /// Receives the messages the file jobs send to their `FileProgressSink`.
+    worker_rx: Receiver<FileJobMessage>,
+
+    albums_in_progress: AlbumsInProgress,
+
+    progress: &'a mut GlobalProgress,
+
+    terminal: &'a T,
+
+    terminal_user_input_receiver:
+        &'a mut tokio::sync::broadcast::Receiver<UserControlMessage>,
+}
+
+impl<'a, 'config, T> AlbumProcessingLoop<'a, 'config, T>
+where
+    T: TranscodeEventSink<'config>,
+{
+    /// Run the loop until all queued albums have been finished, calling `on_album_finished`
+    /// with the index of each finished album and whether all of its files succeeded.
+    ///
+    /// Returns `Ok(true)` if the user requested cancellation.
+    fn run<F>(&mut self, on_album_finished: &mut F) -> Result<bool>
+    where
+        F: FnMut(usize, bool, &mut GlobalProgress) -> Result<()>,
+    {
+        let thread_pool_size =
+            self.configuration.aggregated_library.transcode_threads;
+        let mut next_album_index: usize = 0;
+
+        loop {
+            // Queue the jobs of the following album(s) before the pool runs out of pending jobs,
+            // so the workers never wait for an album to finish.
+            while next_album_index < self.queued_albums.len()
+                && self.thread_pool.num_pending_tasks() < thread_pool_size
+            {
+                let album_index = next_album_index;
+                next_album_index += 1;
+
+                let album = start_album(
+                    self.configuration,
+                    &self.queued_albums[album_index],
+                    self.terminal,
+                    &mut self.thread_pool,
+                )?;
+
+                if album.num_remaining_files == 0 {
+                    if let Some(album_ok) = self
+                        .finish_album_or_queue_replaygain(album_index, album)?
+                    {
+                        on_album_finished(album_index, album_ok, self.progress)?;
+                    }
+
+                    continue;
+                }
+
+                for file_queue_item in &album.file_queue_items {
+                    self.albums_in_progress
+                        .file_item_albums
+                        .insert(*file_queue_item, album_index);
+                }
+                self.albums_in_progress.albums.insert(album_index, album);
+            }
+
+            if next_album_index >= self.queued_albums.len()
+                && self.albums_in_progress.albums.is_empty()
+            {
+                return Ok(false);
+            }
+
+            // A failed task never reports its file as finished, so its album would never finish.
+            let failed_tasks = self.thread_pool.take_failed_tasks();
+            if !failed_tasks.is_empty() {
+                return Err(failed_tasks_error(&failed_tasks));
+            }
+
+            if !self.thread_pool.is_running() {
+                return Err(miette!(
+                    "Thread pool stopped before all albums were processed."
+                ));
+            }
+
+            // Check and handle job progress.
+            match self.worker_rx.recv_timeout(Duration::from_millis(1)) {
+                Ok(job_message) => {
+                    let finished_album_index = handle_file_job_message(
+                        job_message,
+                        &mut self.albums_in_progress,
+                        self.progress,
+                        self.terminal,
+                    )?;
+
+                    if let Some(album_index) = finished_album_index {
+                        let album = self
+                            .albums_in_progress
+                            .albums
+                            .remove(&album_index)
+                            .ok_or_else(|| {
+                                miette!(
+                                    "BUG: Finished album is not in progress."
+                                )
+                            })?;
+
+                        if let Some(album_ok) = self
+                            .finish_album_or_queue_replaygain(
+                                album_index,
+                                album,
+                            )?
+                        {
+                            on_album_finished(
+                                album_index,
+                                album_ok,
+                                self.progress,
+                            )?;
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(miette!(
+                        "Worker message channel disconnected unexpectedly."
+                    ));
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+
+            // Check and handle user input from the terminal frontend.
+            if let Ok(UserControlMessage::Exit) =
+                self.terminal_user_input_receiver.try_recv()
+            {
+                self.terminal
+                    .log_println("User wants to exit, cancelling transcode.");
+                return Ok(true);
+            }
+
+            // An interrupt (Ctrl-C) cancels the run just like the exit keybind does.
+            if is_interrupted() {
+                self.terminal
+                    .log_println("Interrupted, cancelling transcode.");
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Finish an album whose file jobs have all completed (see `finish_album`), unless its
+    /// ReplayGain tags still need to be written: in that case, they are written by a task
+    /// on the thread pool (see `queue_replaygain_task`) and the album is kept
+    /// in `albums_in_progress` until the task reports back.
+    ///
+    /// Returns `Some` with whether all of the album's files succeeded if the album was finished.
+    fn finish_album_or_queue_replaygain(
+        &mut self,
+        album_index: usize,
+        album_in_progress: AlbumInProgress,
+    ) -> Result<Option<bool>> {
+        let queued_album = &self.queued_albums[album_index];
+
+        // ReplayGain tags modify the transcoded files, so they must be written
+        // before the album states are saved.
+        let needs_replaygain_tags = !album_in_progress
+            .has_written_replaygain_tags
+            && album_in_progress.num_errored_files == 0
+            && queued_album.job_type != QueuedAlbumJobType::FullyRemoving;
+
+        if needs_replaygain_tags {
+            if let Some(transcoded_audio_file_paths) =
+                replaygain_audio_file_paths(self.configuration, queued_album)?
+            {
+                queue_replaygain_task(
+                    self.configuration,
+                    queued_album,
+                    album_index,
+                    transcoded_audio_file_paths,
+                    &mut self.thread_pool,
+                );
+                self.albums_in_progress
+                    .albums
+                    .insert(album_index, album_in_progress);
+
+                return Ok(None);
+            }
+        }
+
+        let album_ok = finish_album(
+            queued_album,
+            album_in_progress,
+            self.progress,
+            self.terminal,
+        )?;
+        if !album_ok {
+            self.albums_in_progress.num_errored_albums += 1;
+        }
+
+        Ok(Some(album_ok))
+    }
 }
 
-fn process_library<'config>(
-    queued_library: QueuedLibrary<'config>,
+/// Build an error that lists the files of the failed thread pool tasks
+/// (i.e. file jobs that returned an error or panicked instead of finishing their file).
+fn failed_tasks_error(failed_tasks: &[FailedTask]) -> miette::Report {
+    let failed_file_list = failed_tasks
+        .iter()
+        .map(|failed_task| {
+            format!(
+                "  - {}: {}",
+                failed_task.file_path.to_string_lossy(),
+                failed_task.error
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    miette!(
+        "{} file job(s) failed unexpectedly:\n{}",
+        failed_tasks.len(),
+        failed_file_list
+    )
+}
+
+/// Process all the given albums with a single thread pool
+/// (with `aggregated_library.transcode_threads` workers).
+///
+/// The file jobs of all albums are fed into the same pool (the next album's jobs are queued
+/// before the pool runs dry), so the workers stay busy across album boundaries.
+/// Each album is finished (see `finish_album`) as soon as its last file job completes,
+/// after which `on_album_finished` is called with the album's index in `queued_albums`
+/// and whether all of its files succeeded.
+///
+/// Returns the number of albums that had at least one failed file.
+fn process_queued_albums<'config, F>(
+    configuration: &Arc<Configuration>,
+    queued_albums: &[QueuedAlbum<'config>],
     progress: &mut GlobalProgress,
-    terminal: &TranscodeTerminal<'config, '_>,
+    terminal: &impl TranscodeEventSink<'config>,
     terminal_user_input_receiver: &mut tokio::sync::broadcast::Receiver<
         UserControlMessage,
     >,
-) -> Result<()> {
-    for album in queued_library.queued_albums {
-        process_album(
-            album,
-            progress,
-            terminal,
-            terminal_user_input_receiver,
-        )?;
+    mut on_album_finished: F,
+) -> Result<usize>
+where
+    F: FnMut(usize, bool, &mut GlobalProgress) -> Result<()>,
+{
+    let (worker_tx, worker_rx) = channel::unbounded::<FileJobMessage>();
+
+    let mut thread_pool = CancellableThreadPool::new(
+        configuration.aggregated_library.transcode_threads,
+        worker_tx,
+    );
+    thread_pool.start()?;
+
+    let mut processing_loop = AlbumProcessingLoop {
+        configuration,
+        queued_albums,
+        thread_pool,
+        worker_rx,
+        albums_in_progress: AlbumsInProgress::default(),
+        progress,
+        terminal,
+        terminal_user_input_receiver,
+    };
+
+    let loop_result = processing_loop.run(&mut on_album_finished);
+
+    // This point is reached on three occasions:
+    // - all jobs finished normally, in which case the following will barely block,
+    // - the user requested cancellation or something failed, in which case
+    //   we're probably going to have to wait for the workers a bit.
+    let failed_tasks =
+        processing_loop
+            .thread_pool
+            .set_cancellation_and_join()
+            .wrap_err_with(|| miette!("Thread pool exited abnormally."))?;
+
+    // Tasks can also fail while the thread pool is being joined.
+    if !failed_tasks.is_empty() {
+        let failed_tasks_error = failed_tasks_error(&failed_tasks);
+
+        return Err(match loop_result {
+            Ok(_) => failed_tasks_error,
+            Err(error) => error.wrap_err(failed_tasks_error),
+        });
+    }
+
+    let user_requested_cancellation = loop_result?;
+
+    if user_requested_cancellation {
+        // Handle the messages the workers sent while being cancelled
+        // (the channel disconnects once the thread pool has been dropped).
+        for job_message in processing_loop.worker_rx.try_iter() {
+            handle_file_job_message(
+                job_message,
+                &mut processing_loop.albums_in_progress,
+                processing_loop.progress,
+                terminal,
+            )?;
+        }
+
+        // The states of unfinished albums are not saved, so the files that were processed
+        // (or only partially written) before the cancellation are orphaned.
+        // They are deleted, so the next transcode processes them from scratch.
+        for (album_index, album) in processing_loop.albums_in_progress.albums {
+            let album_view = queued_albums[album_index].album.read();
+            let artist_name = album_view.read_lock_artist().name.clone();
+
+            delete_partially_processed_files(
+                terminal,
+                &artist_name,
+                &album_view.title,
+                &album.started_target_file_paths,
+            );
+        }
+
+        return Err(miette!("User aborted transcoding."));
+    }
+
+    Ok(processing_loop.albums_in_progress.num_errored_albums)
+}
+
+/// Process all queued albums of all queued libraries (see `process_queued_albums`).
+///
+/// Once all albums of a library have been processed, empty artist directories
+/// of fully removed artists are cleaned up and (if `save_library_state` is set)
+/// the library state is saved.
+///
+/// Returns the number of albums that had at least one failed file.
+fn process_queued_libraries<'config>(
+    configuration: &Arc<Configuration>,
+    queued_libraries: Vec<QueuedLibrary<'config>>,
+    progress: &mut GlobalProgress,
+    terminal: &impl TranscodeEventSink<'config>,
+    terminal_user_input_receiver: &mut tokio::sync::broadcast::Receiver<
+        UserControlMessage,
+    >,
+    save_library_state: bool,
+) -> Result<usize> {
+    let mut queued_albums: Vec<QueuedAlbum> = Vec::new();
+    let mut album_library_indices: Vec<usize> = Vec::new();
+    let mut libraries: Vec<(LibraryToFinish, usize)> =
+        Vec::with_capacity(queued_libraries.len());
+
+    for (library_index, queued_library) in
+        queued_libraries.into_iter().enumerate()
+    {
+        let num_albums = queued_library.queued_albums.len();
+
+        album_library_indices
+            .extend(std::iter::repeat(library_index).take(num_albums));
+        queued_albums.extend(queued_library.queued_albums);
+
+        libraries.push((
+            LibraryToFinish {
+                library: queued_library.library,
+                fresh_artist_album_list_state: queued_library
+                    .fresh_artist_album_list_state,
+                fully_removed_artists: queued_library.fully_removed_artists,
+                has_errored_albums: false,
+            },
+            num_albums,
+        ));
     }
 
+    for (library, num_remaining_albums) in &libraries {
+        if *num_remaining_albums == 0 {
+            finish_library(library, progress, terminal, save_library_state)?;
+        }
+    }
+
+    process_queued_albums(
+        configuration,
+        &queued_albums,
+        progress,
+        terminal,
+        terminal_user_input_receiver,
+        |album_index, album_ok, progress| {
+            let (library, num_remaining_albums) =
+                &mut libraries[album_library_indices[album_index]];
+
+            if !album_ok {
+                library.has_errored_albums = true;
+            }
+
+            *num_remaining_albums -= 1;
+            if *num_remaining_albums == 0 {
+                finish_library(library, progress, terminal, save_library_state)?;
+            }
+
+            Ok(())
+        },
+    )
+}
+
+/// A library whose albums are being processed (see `process_queued_libraries`).
+struct LibraryToFinish<'view> {
+    library: SharedLibraryView<'view>,
+
+    fresh_artist_album_list_state: LibraryState,
+
+    fully_removed_artists: Vec<SharedArtistView<'view>>,
+
+    /// Whether any of the library's albums had a failed file.
+    has_errored_albums: bool,
+}
+
+/// Finish a library after all of its albums have been processed: remove the now-empty
+/// artist directories of fully removed artists and save the library state.
+fn finish_library(
+    library: &LibraryToFinish,
+    progress: &mut GlobalProgress,
+    terminal: &impl LogBackend,
+    save_library_state: bool,
+) -> Result<()> {
+    progress.num_finished_libraries += 1;
+    progress.update_status_file(terminal, RunPhase::Processing, false);
 
     // There might be some artists whose transcoded albums we just deleted (because they were
     // completely removed from the source library). In that case, it's a good idea to check
     // whether the artist directory is now empty - in that case we should delete the now-empty artist directory
     // (in the transcoded directory; we never touch the source directory).
-    for fully_removed_artist in queued_library.fully_removed_artists {
+    for fully_removed_artist in &library.fully_removed_artists {
         let artist_transcoded_directory_path = fully_removed_artist
             .read()
             .artist_directory_in_transcoded_library();
 
+        // The albums of libraries that build output paths from a template
+        // are not necessarily inside the artist directory.
+        if !artist_transcoded_directory_path.is_dir() {
+            continue;
+        }
+
         // Now remove the artist directory if it is empty.
         // `std::fs::remove_dir` already guarantees that it will only remove empty directories.
         if artist_transcoded_directory_path
@@ -506,159 +1700,798 @@ fn process_library<'config>(
     }
 
 
-    let library_view = queued_library.library.read();
+    // When processing only a single shard, the remaining albums (including any removed ones)
+    // are processed by other shards, so the library state must not be updated yet.
+    // The same goes for libraries with failed albums: otherwise a removed album whose
+    // transcoded files could not be deleted would not be detected as removed again.
+    if !save_library_state || library.has_errored_albums {
+        return Ok(());
+    }
+
+    let library_view = library.library.read();
     let library_directory = library_view.root_directory_in_source_library();
+    let aggregated_library =
+        &library_view.euphony_configuration.aggregated_library;
+    let device_profile = aggregated_library.device_profile.as_deref();
+    let meta_file_name = &library_view
+        .euphony_configuration
+        .file_metadata
+        .meta_file_name;
+
+    library.fresh_artist_album_list_state.save_to_directory(
+        library_directory,
+        true,
+        meta_file_name,
+        aggregated_library.mode,
+        device_profile,
+    )?;
+
+    if is_verbose_enabled() {
+        terminal.log_println(format!(
+            "Saved library state into {} for library {} ({:?})",
+            LibraryState::get_state_file_name(
+                meta_file_name,
+                aggregated_library.mode,
+                device_profile
+            ),
+            library_view.name(),
+            library_view.root_directory_in_source_library()
+        ));
+    }
+
+    Ok(())
+}
+
+
+/// Transcode all libraries (or, if `library_names_or_paths` is not empty, only the given ones),
+/// skipping unchanged albums.
+pub fn cmd_transcode_all<'config: 'scope, 'scope, 'scope_env: 'scope_env>(
+    configuration: &'config Arc<Configuration>,
+    terminal: &TranscodeTerminal<'config, 'scope>,
+    library_names_or_paths: &[String],
+    options: TranscodeOptions,
+) -> Result<TranscodeRunSummary> {
+    let target = TranscodeTarget::resolve_libraries(
+        configuration,
+        library_names_or_paths,
+    )?;
+
+    terminal.log_println(
+        format!("Command: transcode {target} (skip unchanged).")
+            .cyan()
+            .bold(),
+    );
+
+    transcode_collection(configuration, terminal, options, &target)
+}
+
+/// Transcode a single library (given by its full name or directory path),
+/// skipping unchanged albums.
+pub fn cmd_transcode_library<'config: 'scope, 'scope, 'scope_env: 'scope_env>(
+    configuration: &'config Arc<Configuration>,
+    terminal: &TranscodeTerminal<'config, 'scope>,
+    library_name_or_path: &str,
+    options: TranscodeOptions,
+) -> Result<TranscodeRunSummary> {
+    let target =
+        TranscodeTarget::resolve_library(configuration, library_name_or_path)?;
+
+    terminal.log_println(
+        format!("Command: transcode {target} (skip unchanged).")
+            .cyan()
+            .bold(),
+    );
+
+    transcode_collection(configuration, terminal, options, &target)
+}
+
+/// Transcode a single album (given by its source directory path), skipping it if unchanged.
+///
+/// Only the album is scanned, so this is much faster than transcoding the entire collection
+/// (e.g. after fixing a tag). As the rest of the library is not processed,
+/// the library state and scan fingerprints are not updated.
+pub fn cmd_transcode_album<'config: 'scope, 'scope, 'scope_env: 'scope_env>(
+    configuration: &'config Arc<Configuration>,
+    terminal: &TranscodeTerminal<'config, 'scope>,
+    album_directory_path: &Path,
+    options: TranscodeOptions,
+) -> Result<TranscodeRunSummary> {
+    let target =
+        TranscodeTarget::resolve_album(configuration, album_directory_path)?;
+
+    terminal.log_println(
+        format!("Command: transcode {target} (skip unchanged).")
+            .cyan()
+            .bold(),
+    );
+
+    transcode_collection(configuration, terminal, options, &target)
+}
+
+/// Copy all tracked files (audio files included, without transcoding) into the mirror
+/// output directory, skipping unchanged albums. This uses the same scanning and processing
+/// as `cmd_transcode_all`, but `configuration` must be a mirror configuration
+/// (see `Configuration::into_mirror_configuration`).
+pub fn cmd_mirror_all<'config: 'scope, 'scope, 'scope_env: 'scope_env>(
+    configuration: &'config Arc<Configuration>,
+    terminal: &TranscodeTerminal<'config, 'scope>,
+    options: TranscodeOptions,
+) -> Result<TranscodeRunSummary> {
+    if configuration.aggregated_library.mode != AggregationMode::Mirror {
+        return Err(miette!(
+            "BUG: cmd_mirror_all called without a mirror configuration."
+        ));
+    }
+
+    terminal.log_println(
+        format!(
+            "Command: mirror entire collection into {} (copy without transcoding, skip unchanged).",
+            configuration.aggregated_library.path
+        )
+        .cyan()
+        .bold(),
+    );
+
+    transcode_collection(
+        configuration,
+        terminal,
+        options,
+        &TranscodeTarget::EntireCollection,
+    )
+}
+
+/// Transcode all libraries (or, if `library_names_or_paths` is not empty, only the given ones)
+/// into the directory of a device profile, skipping unchanged albums. This uses the same
+/// scanning and processing as `cmd_transcode_all`, but `configuration` must be
+/// a device profile configuration (see `Configuration::into_device_profile_configuration`).
+pub fn cmd_sync_device<'config: 'scope, 'scope, 'scope_env: 'scope_env>(
+    configuration: &'config Arc<Configuration>,
+    terminal: &TranscodeTerminal<'config, 'scope>,
+    library_names_or_paths: &[String],
+    options: TranscodeOptions,
+) -> Result<TranscodeRunSummary> {
+    let Some(device_profile) =
+        configuration.aggregated_library.device_profile.as_deref()
+    else {
+        return Err(miette!(
+            "BUG: cmd_sync_device called without a device profile configuration."
+        ));
+    };
+
+    let target = TranscodeTarget::resolve_libraries(
+        configuration,
+        library_names_or_paths,
+    )?;
+
+    terminal.log_println(
+        format!(
+            "Command: sync {target} into device profile \"{device_profile}\" at {} (skip unchanged).",
+            configuration.aggregated_library.path
+        )
+        .cyan()
+        .bold(),
+    );
+
+    transcode_collection(configuration, terminal, options, &target)
+}
+
+/// Make sure the configured ffmpeg binary can be executed before doing any work
+/// (see `FfmpegToolsConfiguration::probe_version`), so a broken ffmpeg installation
+/// fails the run right away instead of failing every file deep into it.
+///
+/// Nothing is checked when mirroring, as ffmpeg is not used then.
+fn ensure_ffmpeg_is_available(
+    configuration: &Configuration,
+    terminal: &impl LogBackend,
+) -> Result<()> {
+    if configuration.aggregated_library.mode != AggregationMode::Transcode {
+        return Ok(());
+    }
+
+    let ffmpeg_version = configuration
+        .tools
+        .ffmpeg
+        .probe_version()
+        .wrap_err(FfmpegUnavailableError)?;
+
+    if is_verbose_enabled() {
+        terminal.log_println(format!("Using ffmpeg {ffmpeg_version}."));
+    }
+
+    Ok(())
+}
+
+/// Scan all libraries (or only the ones in `target`) for changes and process them
+/// (shared by `transcode`, `transcode-library`, `transcode-album`, `mirror` and `sync-device`).
+///
+/// This is the transcoding engine without any terminal attached: everything that happens
+/// is reported to `terminal` (see `TranscodeEventSink`), so other programs can embed it
+/// with their own sink. Processing stops early when the sink sends `UserControlMessage::Exit`
+/// or on Ctrl-C (see `interrupt::install_interrupt_handler`).
+///
+/// The configuration is shared with the worker threads (each file job holds a clone
+/// of the `Arc`), so it doesn't have to outlive them.
+///
+/// Returns the number of processed albums and how many of them had failed files.
+pub fn transcode_collection<'config>(
+    configuration: &'config Arc<Configuration>,
+    terminal: &impl TranscodeEventSink<'config>,
+    options: TranscodeOptions,
+    target: &TranscodeTarget,
+) -> Result<TranscodeRunSummary> {
+    let time_full_processing_start = Instant::now();
+
+    let mut status_file = options.status_file_path.clone().map(StatusFile::new);
+    write_status_without_progress(
+        terminal,
+        status_file.as_mut(),
+        RunPhase::Scanning,
+    );
+
+    if !options.dry_run {
+        ensure_ffmpeg_is_available(configuration, terminal)?;
+    }
+
+    terminal.log_println("Scanning albums for changes...");
+
+    // The user may send control messages via the selected backend (such as an abort message).
+    // We can receive such messages through this receiver.
+    // The terminal UI backend for example implements the "q" keybind that sends UserControlMessage::Exit.
+    let mut terminal_user_input = terminal.user_control_receiver()?;
+
+    // Interrupts (Ctrl-C) are handled similarly: they cancel the current album
+    // instead of killing the process (see `interrupt::install_interrupt_handler`).
+    install_interrupt_handler()?;
+
+
+    let mut libraries: Vec<SharedLibraryView<'config>> =
+        collect_libraries_sorted(configuration, terminal)?;
+    libraries.retain(|library| target.includes_library(&library.read().name()));
+
+    // Fingerprints are computed before scanning: if a library changes while it is being
+    // transcoded, the saved fingerprint won't match on the next run and it will be scanned again.
+    let fresh_fingerprints = if options.use_cached_scan {
+        let fresh_fingerprints = compute_library_fingerprints(&libraries)?;
+
+        libraries = filter_out_libraries_with_matching_fingerprints(
+            configuration,
+            libraries,
+            &fresh_fingerprints,
+            terminal,
+        )?;
+
+        fresh_fingerprints
+    } else {
+        HashMap::new()
+    };
+
+    let num_scan_threads = if options.parallel_scan {
+        configuration.aggregated_library.transcode_threads
+    } else {
+        1
+    };
+    let scan_thread_pool = build_scan_thread_pool(num_scan_threads)?;
+
+    let fresh_library_states =
+        collect_full_library_states(&libraries, &scan_thread_pool)?;
+
+    let (libraries_with_changes, unchanged_albums) = collect_changes(
+        &fresh_library_states,
+        terminal,
+        &scan_thread_pool,
+        target,
+    )?;
+    let libraries_with_changes =
+        retain_albums_in_target(libraries_with_changes, target);
+    let (libraries_with_changes, num_albums_with_case_collisions) =
+        retain_albums_without_case_collisions(
+            configuration,
+            terminal,
+            libraries_with_changes,
+        );
+
+    explain_library_changes(
+        terminal,
+        &libraries,
+        &libraries_with_changes,
+        &unchanged_albums,
+    );
+
+    if !matches!(target, TranscodeTarget::Album { .. }) {
+        check_reprocessing_safeguard(
+            configuration,
+            terminal,
+            &libraries_with_changes,
+            unchanged_albums.len(),
+            &options,
+        )?;
+    }
+
+    if options.print_unchanged {
+        log_unchanged_albums(terminal, unchanged_albums);
+    }
+
+    let libraries_with_changes = match options.shard {
+        Some(shard) => {
+            let (libraries_in_shard, num_skipped_albums) =
+                retain_albums_in_shard(libraries_with_changes, shard);
+
+            terminal.log_println(format!(
+                "Shard {}: skipping {} changed albums that belong to other shards.",
+                shard.to_string().bold(),
+                num_skipped_albums
+            ));
+
+            libraries_in_shard
+        }
+        None => libraries_with_changes,
+    };
+
+    // Fingerprints must only be saved once all libraries are fully up to date.
+    // Similarly, the library states must not be updated when processing only a part of them.
+    let is_processing_entire_libraries = options.shard.is_none()
+        && !matches!(target, TranscodeTarget::Album { .. });
+    // Albums skipped because of case collisions must be checked again on the next run.
+    let should_save_fingerprints = options.use_cached_scan
+        && is_processing_entire_libraries
+        && !options.dry_run
+        && num_albums_with_case_collisions == 0;
+
+    // It is possible that no changes have been detected, in which case we should just exit.
+    if libraries_with_changes.is_empty() {
+        if should_save_fingerprints {
+            save_library_fingerprints(configuration, fresh_fingerprints)?;
+        }
+
+        if !options.dry_run {
+            prune_empty_directories_if_requested(
+                configuration,
+                terminal,
+                &options,
+            )?;
+            generate_master_playlist_if_enabled(configuration, terminal)?;
+        }
+
+        let message = if num_albums_with_case_collisions > 0 {
+            "All other albums are up to date, no transcoding needed."
+        } else if options.shard.is_some() {
+            "All albums in this shard are up to date, no transcoding needed."
+        } else {
+            "All albums are up to date, no transcoding needed."
+        };
+
+        terminal.log_println(message.green().bold());
+
+        write_status_without_progress(
+            terminal,
+            status_file.as_mut(),
+            RunPhase::Finished,
+        );
+        return Ok(TranscodeRunSummary::default());
+    }
+
+    let num_total_changed_files = libraries_with_changes
+        .iter()
+        .flat_map(|library| &library.sorted_changed_artists)
+        .map(|artist| {
+            let num_files_a = artist
+                .sorted_changed_albums
+                .iter()
+                .map(|album| album.changes.number_of_changed_files())
+                .sum::<usize>();
+
+            let num_files_b = artist
+                .sorted_removed_albums
+                .iter()
+                .map(|album| album.changes.number_of_changed_files())
+                .sum::<usize>();
+
+            num_files_a + num_files_b
+        })
+        .sum::<usize>();
+
+    terminal.log_println(format!(
+        "{} files are new, have changed or otherwise need to be processed.",
+        num_total_changed_files.to_string().bold()
+    ));
+    if let Some(output_size_estimate) = log_output_size_estimate(
+        configuration,
+        terminal,
+        &libraries_with_changes,
+    ) {
+        check_free_space(
+            configuration,
+            terminal,
+            &output_size_estimate,
+            &options,
+        )?;
+    }
+
+    if options.dry_run {
+        log_dry_run_preview(configuration, terminal, &libraries_with_changes)?;
+
+        write_status_without_progress(
+            terminal,
+            status_file.as_mut(),
+            RunPhase::Finished,
+        );
+        return Ok(TranscodeRunSummary::default());
+    }
+
+
+    // Queue the entire workload - this way we'll generate `QueueItemID`s
+    // for each item, enabling us to interact with the terminal backend
+    // and display individual album and file progress.
+    terminal.on_processing_started();
+
+    let queued_libraries =
+        queue_all_changed_albums(terminal, libraries_with_changes)?;
+
+    // Set up progress bar tracking.
+    let mut global_progress =
+        initialize_progress(terminal, num_total_changed_files)?;
+
+    global_progress.num_total_libraries = queued_libraries.len();
+    global_progress.num_total_albums = queued_libraries
+        .iter()
+        .map(|library| library.queued_albums.len())
+        .sum::<usize>();
+    global_progress.status_file = status_file;
+    global_progress.update_status_file(terminal, RunPhase::Processing, true);
+
+    let time_processing_start = Instant::now();
+
+    let processing_result = process_queued_libraries(
+        configuration,
+        queued_libraries,
+        &mut global_progress,
+        terminal,
+        &mut terminal_user_input,
+        is_processing_entire_libraries,
+    );
+
+    let num_errored_albums = match processing_result {
+        Ok(num_errored_albums) => num_errored_albums,
+        Err(error) => {
+            global_progress.update_status_file(terminal, RunPhase::Failed, true);
+            return Err(error);
+        }
+    };
+
+    global_progress.update_status_file(terminal, RunPhase::Finished, true);
+
+    // Fingerprints are only saved after everything has been processed successfully:
+    // albums with failed files must be scanned (and processed) again, so in that case
+    // the fingerprints are not saved.
+    if should_save_fingerprints && num_errored_albums == 0 {
+        save_library_fingerprints(configuration, fresh_fingerprints)?;
+    }
+
+    prune_empty_directories_if_requested(configuration, terminal, &options)?;
+    generate_master_playlist_if_enabled(configuration, terminal)?;
+    warn_if_over_size_budget(configuration, terminal)?;
+
+    let time_full_processing_elapsed =
+        time_full_processing_start.elapsed().as_secs_f64();
+
+    if num_errored_albums > 0 {
+        terminal.log_println(format!(
+            "{} Changes processed in {time_full_processing_elapsed:.2} seconds, \
+            but {num_errored_albums} albums had failed files.",
+            "WARNING:".yellow()
+        ));
+    } else {
+        terminal.log_println(format!(
+            "All changes successfully processed in {time_full_processing_elapsed:.2} seconds."
+        ));
+    }
+
+    log_run_summary(terminal, &global_progress, num_errored_albums);
+    log_parallelism_summary(
+        terminal,
+        &global_progress,
+        time_processing_start.elapsed(),
+        configuration.aggregated_library.transcode_threads,
+    );
+
+    Ok(TranscodeRunSummary {
+        num_processed_albums: global_progress.num_finished_albums,
+        num_errored_albums,
+    })
+}
+
+
+/*
+ * Utility functions
+ */
+
+/// Count the changed albums that have been processed before (i.e. have a saved source
+/// album state) and therefore need to be processed *again*. Newly added albums are not counted.
+fn count_reprocessed_albums(
+    libraries_with_changes: &[LibraryWithChanges],
+) -> usize {
+    libraries_with_changes
+        .iter()
+        .flat_map(|library| &library.sorted_changed_artists)
+        .flat_map(|artist| &artist.sorted_changed_albums)
+        .filter(|changed_album| {
+            let album_locked = changed_album.album.read();
+            let aggregated_library =
+                &album_locked.euphony_configuration().aggregated_library;
+
+            SourceAlbumState::get_state_file_path_for_directory(
+                album_locked.album_directory_in_source_library(),
+                aggregated_library.mode,
+                aggregated_library.device_profile.as_deref(),
+            )
+            .is_file()
+        })
+        .count()
+}
+
+/// Guard against accidentally reprocessing most of the collection (e.g. after switching
+/// `file_metadata.change_detection` by mistake): if more than
+/// `aggregated_library.max_reprocess_percentage` percent of the previously processed albums
+/// would be processed again, the run is aborted, unless `allow_mass_reprocessing` is set.
+///
+/// During a dry run, only a warning is printed.
+fn check_reprocessing_safeguard(
+    configuration: &Configuration,
+    terminal: &impl LogBackend,
+    libraries_with_changes: &[LibraryWithChanges],
+    num_unchanged_albums: usize,
+    options: &TranscodeOptions,
+) -> Result<()> {
+    let Some(max_reprocess_percentage) =
+        configuration.aggregated_library.max_reprocess_percentage
+    else {
+        return Ok(());
+    };
+
+    // Unchanged albums have necessarily been processed before.
+    let num_reprocessed_albums =
+        count_reprocessed_albums(libraries_with_changes);
+    let num_previously_processed_albums =
+        num_reprocessed_albums + num_unchanged_albums;
+
+    if num_previously_processed_albums < MIN_ALBUMS_FOR_REPROCESSING_SAFEGUARD {
+        return Ok(());
+    }
+
+    let reprocess_percentage = num_reprocessed_albums as f64
+        / num_previously_processed_albums as f64
+        * 100.0;
 
-    queued_library
-        .fresh_artist_album_list_state
-        .save_to_directory(library_directory, true)?;
+    if reprocess_percentage <= max_reprocess_percentage {
+        return Ok(());
+    }
+
+    let message = format!(
+        "{} of {} previously processed albums ({:.0}%) would be processed again, which is \
+        more than aggregated_library.max_reprocess_percentage ({}%). This usually means \
+        the configuration has changed (e.g. file_metadata.change_detection).",
+        num_reprocessed_albums,
+        num_previously_processed_albums,
+        reprocess_percentage,
+        max_reprocess_percentage
+    );
+
+    if options.allow_mass_reprocessing || options.dry_run {
+        terminal.log_println(format!("{} {}", "Warning:".yellow(), message));
+        return Ok(());
+    }
+
+    Err(miette!(
+        "{} If this is expected, run the command again with --yes.",
+        message
+    ))
+}
+
+/// If `aggregated_library.generate_master_playlist` is enabled, rebuild the master playlist
+/// in the root of the aggregated library (see `playlist::write_master_playlist`).
+fn generate_master_playlist_if_enabled(
+    configuration: &Configuration,
+    terminal: &impl LogBackend,
+) -> Result<()> {
+    if !configuration.aggregated_library.generate_master_playlist {
+        return Ok(());
+    }
+
+    let num_tracks = write_master_playlist(configuration)
+        .wrap_err_with(|| miette!("Failed to generate the master playlist."))?;
+
+    terminal.log_println(format!(
+        "Master playlist {} generated ({} tracks).",
+        MASTER_PLAYLIST_FILE_NAME.bold(),
+        num_tracks
+    ));
+
+    Ok(())
+}
+
+/// If `aggregated_library.size_budget_mib` is set, print a warning when the aggregated library
+/// takes up more space than that (e.g. more than fits onto the device of a `sync-device` profile).
+fn warn_if_over_size_budget(
+    configuration: &Configuration,
+    terminal: &impl LogBackend,
+) -> Result<()> {
+    let Some(size_budget_mib) = configuration.aggregated_library.size_budget_mib
+    else {
+        return Ok(());
+    };
+
+    let total_size_bytes =
+        compute_directory_size(&configuration.aggregated_library.path)
+            .wrap_err_with(|| {
+                miette!("Failed to compute the size of the aggregated library.")
+            })?;
+    let size_budget_bytes = size_budget_mib.saturating_mul(BYTES_PER_MIB);
 
-    if is_verbose_enabled() {
+    if total_size_bytes > size_budget_bytes {
         terminal.log_println(format!(
-            "Saved library state into {} for library {} ({:?})",
-            LIBRARY_STATE_FILE_NAME,
-            library_view.name(),
-            library_view.root_directory_in_source_library()
+            "{} The aggregated library takes up {}, which is over its size budget of {} \
+            (aggregated_library.size_budget_mib).",
+            "WARNING:".yellow(),
+            format_byte_size(total_size_bytes).bold(),
+            format_byte_size(size_budget_bytes),
         ));
     }
 
     Ok(())
 }
 
-pub fn cmd_transcode_all<'config: 'scope, 'scope, 'scope_env: 'scope_env>(
-    configuration: &'config Configuration,
-    terminal: &TranscodeTerminal<'config, 'scope>,
+/// If requested with `--prune-empty-dirs`, remove the empty directories in the aggregated library
+/// (see `prune::prune_empty_directories`).
+fn prune_empty_directories_if_requested(
+    configuration: &Configuration,
+    terminal: &impl LogBackend,
+    options: &TranscodeOptions,
 ) -> Result<()> {
-    let time_full_processing_start = Instant::now();
-
-    terminal.log_println(
-        "Command: transcode entire collection (skip unchanged)."
-            .cyan()
-            .bold(),
-    );
-    terminal.log_println("Scanning albums for changes...");
-
-    // The user may send control messages via the selected backend (such as an abort message).
-    // We can receive such messages through this receiver.
-    // The terminal UI backend for example implements the "q" keybind that sends UserControlMessage::Exit.
-    let mut terminal_user_input = terminal.get_user_control_receiver()?;
-
-
-    let libraries: Vec<SharedLibraryView<'config>> =
-        collect_libraries_sorted(configuration, terminal)?;
-
-    let fresh_library_states = collect_full_library_states(&libraries)?;
-    let libraries_with_changes =
-        collect_changes(&fresh_library_states, terminal)?;
-
-    // It is possible that no changes have been detected, in which case we should just exit.
-    if libraries_with_changes.is_empty() {
-        terminal.log_println(
-            "All albums are up to date, no transcoding needed."
-                .green()
-                .bold(),
-        );
+    if !options.prune_empty_directories {
         return Ok(());
     }
 
-    let num_total_changed_files = libraries_with_changes
-        .iter()
-        .flat_map(|library| &library.sorted_changed_artists)
-        .map(|artist| {
-            let num_files_a = artist
-                .sorted_changed_albums
-                .iter()
-                .map(|album| album.changes.number_of_changed_files())
-                .sum::<usize>();
-
-            let num_files_b = artist
-                .sorted_removed_albums
-                .iter()
-                .map(|album| album.changes.number_of_changed_files())
-                .sum::<usize>();
+    let removed_directories = prune_empty_directories(
+        &configuration.aggregated_library.path,
+        options.prune_directories_with_hidden_files,
+    )
+    .wrap_err_with(|| miette!("Failed to prune empty directories."))?;
 
-            num_files_a + num_files_b
-        })
-        .sum::<usize>();
+    if is_verbose_enabled() {
+        for removed_directory in &removed_directories {
+            terminal.log_println(format!(
+                "Removed empty directory {:?}.",
+                removed_directory
+            ));
+        }
+    }
 
     terminal.log_println(format!(
-        "{} files are new, have changed or otherwise need to be processed.",
-        num_total_changed_files.to_string().bold()
+        "Pruned {} empty directories from the aggregated library.",
+        removed_directories.len().to_string().bold()
     ));
 
+    Ok(())
+}
 
-    // Queue the entire workload - this way we'll generate `QueueItemID`s
-    // for each item, enabling us to interact with the terminal backend
-    // and display individual album and file progress.
-    terminal.queue_album_enable();
-    terminal.queue_file_enable();
-    terminal.progress_enable();
-
-    let queued_libraries =
-        queue_all_changed_albums(terminal, libraries_with_changes)?;
-
-    // Set up progress bar tracking.
-    let mut global_progress = GlobalProgress {
-        audio_files_currently_processing: 0,
-        data_files_currently_processing: 0,
-        audio_files_finished_ok: 0,
-        data_files_finished_ok: 0,
-        audio_files_errored: 0,
-        data_files_errored: 0,
-    };
+/// Print the total time spent in ffmpeg (summed over all transcoded files) compared to
+/// the wall-clock processing time. Their ratio is the effective parallelism factor: if it is
+/// well below the number of transcode threads, adding more threads is unlikely to help.
+///
+/// Nothing is printed if too few files were transcoded for the numbers to be meaningful.
+fn log_parallelism_summary(
+    terminal: &impl LogBackend,
+    progress: &GlobalProgress,
+    processing_wall_time: Duration,
+    num_transcode_threads: usize,
+) {
+    if progress.ffmpeg_num_files < MIN_FILES_FOR_PARALLELISM_SUMMARY {
+        return;
+    }
 
-    terminal.progress_set_audio_files_currently_processing(
-        global_progress.audio_files_currently_processing,
-    )?;
-    terminal.progress_set_data_files_currently_processing(
-        global_progress.data_files_currently_processing,
-    )?;
-    terminal.progress_set_audio_files_finished_ok(
-        global_progress.audio_files_finished_ok,
-    )?;
-    terminal.progress_set_data_files_finished_ok(
-        global_progress.data_files_finished_ok,
-    )?;
-    terminal
-        .progress_set_audio_files_errored(global_progress.audio_files_errored)?;
-    terminal
-        .progress_set_data_files_errored(global_progress.data_files_errored)?;
+    let ffmpeg_total_seconds = progress.ffmpeg_total_duration.as_secs_f64();
+    let wall_time_seconds = processing_wall_time.as_secs_f64();
+    if wall_time_seconds <= 0.0 {
+        return;
+    }
 
-    terminal.progress_set_total(num_total_changed_files)?;
+    terminal.log_println(format!(
+        "Total ffmpeg time: {:.2} seconds across {} files, wall time: {:.2} seconds \
+        (effective parallelism: {:.2}x with {} transcode threads).",
+        ffmpeg_total_seconds,
+        progress.ffmpeg_num_files,
+        wall_time_seconds,
+        ffmpeg_total_seconds / wall_time_seconds,
+        num_transcode_threads,
+    ));
+}
 
+/// Print a summary of what the run did: how many libraries, albums and files were processed
+/// (and how), how many files failed and how much was written into the aggregated library.
+fn log_run_summary(
+    terminal: &impl LogBackend,
+    progress: &GlobalProgress,
+    num_errored_albums: usize,
+) {
+    let num_failed_files =
+        progress.audio_files_errored + progress.data_files_errored;
+
+    terminal.log_newline();
+    terminal.log_println("Summary:".bold());
+    terminal.log_println(format!(
+        "  Libraries: {}, albums: {} ({} with failed files).",
+        progress.num_finished_libraries,
+        progress.num_finished_albums,
+        num_errored_albums
+    ));
+    terminal.log_println(format!(
+        "  Files: {} transcoded, {} copied, {} skipped (already up to date), {} deleted.",
+        progress.num_transcoded_files,
+        progress.num_copied_files,
+        progress.num_skipped_files,
+        progress.num_deleted_files
+    ));
 
-    for queued_library in queued_libraries {
-        process_library(
-            queued_library,
-            &mut global_progress,
-            terminal,
-            &mut terminal_user_input,
-        )?;
+    let failed_files = format!("  Failed files: {num_failed_files}.");
+    if num_failed_files > 0 {
+        terminal.log_println(failed_files.red());
+    } else {
+        terminal.log_println(failed_files);
     }
 
-    let time_full_processing_elapsed =
-        time_full_processing_start.elapsed().as_secs_f64();
-
     terminal.log_println(format!(
-        "All changes successfully processed in {time_full_processing_elapsed:.2} seconds."
+        "  Written to the aggregated library: {}.",
+        format_byte_size(progress.num_bytes_written)
     ));
-
-    Ok(())
 }
 
+/// Print a clearly-separated list of albums that are up to date
+/// and will be skipped (see `transcode --print-unchanged`).
+fn log_unchanged_albums(
+    terminal: &impl LogBackend,
+    mut unchanged_albums: Vec<UnchangedAlbum>,
+) {
+    unchanged_albums.sort_unstable();
 
-/*
- * Utility functions
- */
+    terminal.log_newline();
+    terminal.log_println(
+        format!(
+            "{} albums are up to date and will be skipped:",
+            unchanged_albums.len()
+        )
+        .bold(),
+    );
+
+    for album in &unchanged_albums {
+        terminal.log_println(format!(
+            "  = {} - {} (library: {})",
+            album.artist_name, album.album_title, album.library_name,
+        ));
+    }
+
+    terminal.log_println("(end of up-to-date albums)".dark_grey());
+    terminal.log_newline();
+}
 
 fn collect_libraries_sorted<'config>(
     configuration: &'config Configuration,
-    terminal: &TranscodeTerminal<'config, '_>,
+    terminal: &impl TranscodeEventSink<'config>,
 ) -> Result<Vec<SharedLibraryView<'config>>> {
+    // Libraries with transcoding disabled are still validated, but never transcoded.
+    for library in configuration.libraries.values() {
+        if !library.transcoding.enabled {
+            terminal.log_println(format!(
+                "Skipping library \"{}\" (transcoding.enabled is false).",
+                library.name
+            ));
+        }
+    }
+
     // `LibraryView` is the root abstraction here - we use it to discover artists and their albums.
     let mut libraries = configuration
         .libraries
         .values()
+        .filter(|library| library.transcoding.enabled)
         .map(|library| {
             LibraryView::from_library_configuration(configuration, library)
         })
@@ -680,50 +2513,167 @@ fn collect_libraries_sorted<'config>(
 }
 
 
-fn collect_full_library_states<'config>(
-    sorted_libraries: &[SharedLibraryView<'config>],
-) -> Result<Vec<(SharedLibraryView<'config>, LibraryState)>> {
-    sorted_libraries
+/// Compute the scan fingerprints of the given libraries, keyed by library name
+/// (see `scan_fingerprint::compute_library_fingerprint`).
+fn compute_library_fingerprints(
+    libraries: &[SharedLibraryView],
+) -> Result<HashMap<String, String>> {
+    libraries
         .iter()
         .map(|library| {
-            let library = library.clone();
+            let library_locked = library.read();
 
-            let tracked_artists_and_albums = library
-                .read()
-                .artists()?
-                .iter()
-                .map(|(artist_name, artist_view)| {
-                    let mut tracked_albums = Vec::new();
-                    for (album_title, album_view) in
-                        artist_view.read().albums()?
-                    {
-                        let album_path = album_view
-                            .read()
-                            .directory_path_relative_to_library_root();
-
-                        tracked_albums.push(TrackedAlbum {
-                            album_title,
-                            album_source_relative_path: dunce::simplified(
-                                &album_path,
-                            )
-                            .to_string_lossy()
-                            .to_string(),
-                        })
-                    }
+            Ok((
+                library_locked.name(),
+                compute_library_fingerprint(&library_locked)?,
+            ))
+        })
+        .collect()
+}
+
+/// Load the scan fingerprints saved during the last transcode and remove
+/// all libraries whose fresh fingerprint matches the saved one.
+///
+/// If no fingerprints have been saved yet, all libraries are returned.
+fn filter_out_libraries_with_matching_fingerprints<'config>(
+    configuration: &'config Configuration,
+    libraries: Vec<SharedLibraryView<'config>>,
+    fresh_fingerprints: &HashMap<String, String>,
+    terminal: &impl TranscodeEventSink<'config>,
+) -> Result<Vec<SharedLibraryView<'config>>> {
+    let saved_fingerprints = match ScanFingerprints::load_from_directory(
+        &configuration.aggregated_library.path,
+    ) {
+        Ok(fingerprints) => fingerprints,
+        Err(error) => match error {
+            ScanFingerprintsLoadError::NotFound
+            | ScanFingerprintsLoadError::SchemaVersionMismatch(_) => {
+                return Ok(libraries);
+            }
+            _ => return Err(error.into()),
+        },
+    };
+
+    let mut libraries_to_scan = Vec::with_capacity(libraries.len());
+
+    for library in libraries {
+        let library_name = library.read().name();
 
-                    Ok((
-                        artist_name.clone(),
-                        TrackedArtistAlbums { tracked_albums },
-                    ))
+        let is_unchanged = saved_fingerprints
+            .library_fingerprints
+            .get(&library_name)
+            .is_some_and(|saved_fingerprint| {
+                fresh_fingerprints.get(&library_name) == Some(saved_fingerprint)
+            });
+
+        if is_unchanged {
+            terminal.log_println(format!(
+                "Library {} is unchanged since the last transcode (cached scan), skipping.",
+                library_name.bold()
+            ));
+            continue;
+        }
+
+        libraries_to_scan.push(library);
+    }
+
+    Ok(libraries_to_scan)
+}
+
+/// Save the given (pre-scan) fingerprints into the aggregated library,
+/// keeping any other fingerprints that are already saved.
+fn save_library_fingerprints(
+    configuration: &Configuration,
+    fresh_fingerprints: HashMap<String, String>,
+) -> Result<()> {
+    let aggregated_library_path = &configuration.aggregated_library.path;
+
+    let mut fingerprints =
+        match ScanFingerprints::load_from_directory(aggregated_library_path) {
+            Ok(fingerprints) => fingerprints,
+            Err(error) => match error {
+                ScanFingerprintsLoadError::NotFound
+                | ScanFingerprintsLoadError::SchemaVersionMismatch(_) => {
+                    ScanFingerprints::new(HashMap::new())
+                }
+                _ => return Err(error.into()),
+            },
+        };
+
+    fingerprints.library_fingerprints.extend(fresh_fingerprints);
+
+    fingerprints
+        .save_to_directory(aggregated_library_path)
+        .wrap_err_with(|| miette!("Failed to save library scan fingerprints."))
+}
+
+
+/// Generate fresh library states (artist and album lists) of the given libraries.
+/// Libraries and their artists are listed in parallel on the given thread pool
+/// (see `scan::build_scan_thread_pool`), the order of the libraries is preserved.
+fn collect_full_library_states<'config>(
+    sorted_libraries: &[SharedLibraryView<'config>],
+    thread_pool: &rayon::ThreadPool,
+) -> Result<Vec<(SharedLibraryView<'config>, LibraryState)>> {
+    thread_pool.install(|| {
+        sorted_libraries
+            .par_iter()
+            .map(|library| collect_full_library_state(library.clone()))
+            .collect()
+    })
+}
+
+/// Generate the fresh library state of a single library (see `collect_full_library_states`).
+fn collect_full_library_state(
+    library: SharedLibraryView,
+) -> Result<(SharedLibraryView, LibraryState)> {
+    let tracked_artists_and_albums = library
+        .read()
+        .artists()?
+        .par_iter()
+        .map(|(artist_name, artist_view)| {
+            let mut tracked_albums = Vec::new();
+            for (album_title, album_view) in artist_view.read().albums()? {
+                let album_view = album_view.read();
+                let album_path =
+                    album_view.directory_path_relative_to_library_root();
+
+                // Only recorded if the album isn't transcoded into the same relative path.
+                let album_transcoded_path =
+                    album_view.album_directory_in_transcoded_library();
+                let album_transcoded_relative_path = album_transcoded_path
+                    .strip_prefix(
+                        &album_view
+                            .euphony_configuration()
+                            .aggregated_library
+                            .path,
+                    )
+                    .ok()
+                    .filter(|relative_path| *relative_path != album_path)
+                    .map(|relative_path| {
+                        relative_path.to_string_lossy().to_string()
+                    });
+
+                tracked_albums.push(TrackedAlbum {
+                    album_title,
+                    album_source_relative_path: dunce::simplified(&album_path)
+                        .to_string_lossy()
+                        .to_string(),
+                    album_transcoded_relative_path,
                 })
-                .collect::<Result<HashMap<String, TrackedArtistAlbums>>>()?;
+            }
 
             Ok((
-                library,
-                LibraryState::new(tracked_artists_and_albums),
+                artist_name.clone(),
+                TrackedArtistAlbums { tracked_albums },
             ))
         })
-        .collect()
+        .collect::<Result<HashMap<String, TrackedArtistAlbums>>>()?;
+
+    Ok((
+        library,
+        LibraryState::new(tracked_artists_and_albums),
+    ))
 }
 
 
@@ -736,6 +2686,8 @@ pub struct ChangedAlbum<'view> {
 }
 
 pub struct FullyRemovedAlbum<'view> {
+    pub album: SharedAlbumView<'view>,
+
     pub album_title: String,
 
     pub changes: AlbumFileChangesV2<'view>,
@@ -751,6 +2703,16 @@ pub struct ArtistWithChanges<'view> {
     pub sorted_removed_albums: Vec<FullyRemovedAlbum<'view>>,
 }
 
+/// An album that has no changes since the last transcode (and is therefore skipped).
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct UnchangedAlbum {
+    pub library_name: String,
+
+    pub artist_name: String,
+
+    pub album_title: String,
+}
+
 pub struct LibraryWithChanges<'view> {
     pub library: SharedLibraryView<'view>,
 
@@ -764,39 +2726,30 @@ pub struct LibraryWithChanges<'view> {
 }
 
 
+/// Collect the artist's changes, given the results of scanning their albums
+/// (see `scan::scan_all_albums_for_changes`). Returns a tuple containing the artist's changes
+/// (or `None` if there are none) and titles of the artist's albums that are up to date.
 fn collect_artist_changes<'config>(
     artist: SharedArtistView<'config>,
+    scanned_albums: ScannedArtistAlbums<'config>,
     saved_tracked_album_list: Option<&TrackedArtistAlbums>,
     fresh_tracked_album_list: &TrackedArtistAlbums,
-    terminal: &TranscodeTerminal<'config, '_>,
-) -> Result<Option<ArtistWithChanges<'config>>> {
+    terminal: &impl TranscodeEventSink<'config>,
+) -> Result<(Option<ArtistWithChanges<'config>>, Vec<String>)> {
     let artist_locked = artist.read();
 
-    let mut changed_albums: Vec<ChangedAlbum> = artist_locked
-        .scan_for_albums_with_changes()?
-        .into_iter()
-        .map(
-            |(album_title, (album_view, album_changes))| ChangedAlbum {
-                album: album_view,
-                album_title,
-                changes: album_changes,
-            },
-        )
-        .collect::<Vec<ChangedAlbum>>();
-
-    if is_verbose_enabled() {
-        terminal.log_println(format!(
-            "Changes for artist {}:\n{}",
-            artist_locked.name,
-            changed_albums
-                .iter()
-                .map(|album| format!(
-                    "album_title={},changes={:?}",
-                    album.album_title, album.changes
-                ))
-                .collect::<Vec<String>>()
-                .join("\n"),
-        ));
+    let ScannedArtistAlbums {
+        mut changed_albums,
+        unchanged_album_titles,
+    } = scanned_albums;
+
+    for album in &changed_albums {
+        debug!(
+            artist = %artist_locked.name,
+            album = %album.album_title,
+            changes = %album.changes.describe_changes(),
+            "Album has changed."
+        );
     }
 
     let mut removed_albums = if let Some(saved_album_list) =
@@ -819,11 +2772,7 @@ fn collect_artist_changes<'config>(
         fully_removed_album_set
             .into_iter()
             .filter_map(|album| {
-                let album_view = match AlbumView::new(
-                    artist.clone(),
-                    album.album_title.clone(),
-                    true,
-                ) {
+                let album_view = match removed_album_view(artist.clone(), album) {
                     Ok(view) => view,
                     Err(error) => return Some(Err(error))
                 };
@@ -834,14 +2783,15 @@ fn collect_artist_changes<'config>(
                 }
 
                 let changes = match AlbumFileChangesV2::generate_entire_transcoded_album_deletion(
-                    album_view,
-                    &album.album_source_relative_path
+                    album_view.clone(),
+                    album.album_output_relative_path()
                 ) {
                     Ok(changes) => changes,
                     Err(error) => return Some(Err(error))
                 };
 
                 Some(Ok(FullyRemovedAlbum {
+                    album: album_view,
                     album_title: album.album_title.clone(),
                     changes,
                 }))
@@ -859,24 +2809,51 @@ fn collect_artist_changes<'config>(
             first.album_title.cmp(&second.album_title)
         });
 
-        Ok(Some(ArtistWithChanges {
-            artist: artist.clone(),
-            artist_name: artist_locked.name.clone(),
-            sorted_changed_albums: changed_albums,
-            sorted_removed_albums: removed_albums,
-        }))
+        Ok((
+            Some(ArtistWithChanges {
+                artist: artist.clone(),
+                artist_name: artist_locked.name.clone(),
+                sorted_changed_albums: changed_albums,
+                sorted_removed_albums: removed_albums,
+            }),
+            unchanged_album_titles,
+        ))
     } else {
-        Ok(None)
+        Ok((None, unchanged_album_titles))
     }
 }
 
+/// Create the view of an album that has been removed since the last transcode (or whose
+/// output directory has changed, see `TrackedAlbum::album_transcoded_relative_path`),
+/// pointing at the transcoded album directory saved in the library state
+/// (regardless of the current output template or file name sanitization).
+fn removed_album_view<'config>(
+    artist: SharedArtistView<'config>,
+    album: &TrackedAlbum,
+) -> Result<SharedAlbumView<'config>> {
+    let album_view = AlbumView::new(artist, album.album_title.clone(), true)?;
+
+    album_view.write().templated_output_directory =
+        Some(PathBuf::from(album.album_output_relative_path()));
+
+    Ok(album_view)
+}
+
+/// Scan all given libraries for changes (on the given scanning thread pool).
+/// Returns a tuple containing the libraries with changes
+/// and a list of albums that are up to date (i.e. will be skipped).
 fn collect_changes<'config>(
     sorted_libraries_with_fresh_states: &Vec<(
         SharedLibraryView<'config>,
         LibraryState,
     )>,
-    terminal: &TranscodeTerminal<'config, '_>,
-) -> Result<Vec<LibraryWithChanges<'config>>> {
+    terminal: &impl TranscodeEventSink<'config>,
+    scan_thread_pool: &rayon::ThreadPool,
+    target: &TranscodeTarget,
+) -> Result<(
+    Vec<LibraryWithChanges<'config>>,
+    Vec<UnchangedAlbum>,
+)> {
     // We perform a scan on each library: for each artist in the library, we scan each
     // of their albums for changes (this includes untranscoded albums in addition to
     // albums changed since last transcode).
@@ -884,24 +2861,34 @@ fn collect_changes<'config>(
     // This is a relatively expensive step (a lot of disk accesses),
     // but at the end we'll have all the work we need to perform.
 
+    let mut scanned_albums = scan_all_albums_for_changes(
+        sorted_libraries_with_fresh_states,
+        terminal,
+        scan_thread_pool,
+        target,
+    )?;
+
     let mut libraries_with_changes: Vec<LibraryWithChanges> =
         Vec::with_capacity(sorted_libraries_with_fresh_states.len());
+    let mut unchanged_albums: Vec<UnchangedAlbum> = Vec::new();
 
     for (library_view, fresh_tracked_artist_album_list) in
         sorted_libraries_with_fresh_states
     {
         let library = library_view.read();
 
-        if is_verbose_enabled() {
-            terminal.log_println(format!(
-                "Scanning changes in library: {}",
-                library.name(),
-            ));
-        }
+        debug!(library = %library.name(), "Scanning library for changes.");
 
         let saved_tracked_artist_album_list =
             match LibraryState::load_from_directory(
                 library.root_directory_in_source_library(),
+                &library.euphony_configuration.file_metadata.meta_file_name,
+                library.euphony_configuration.aggregated_library.mode,
+                library
+                    .euphony_configuration
+                    .aggregated_library
+                    .device_profile
+                    .as_deref(),
             ) {
                 Ok(state) => Some(state),
                 Err(error) => match error {
@@ -955,8 +2942,13 @@ fn collect_changes<'config>(
                     )
                 })?;
 
-            let changes = collect_artist_changes(
+            let scanned_artist_albums = scanned_albums
+                .remove(&(library.name(), artist_name.clone()))
+                .unwrap_or_default();
+
+            let (changes, unchanged_album_titles) = collect_artist_changes(
                 artist_view.clone(),
+                scanned_artist_albums,
                 saved_artist_album_list,
                 fresh_artist_album_list,
                 terminal,
@@ -965,6 +2957,14 @@ fn collect_changes<'config>(
             if let Some(changes) = changes {
                 artists_with_changes.push(changes);
             }
+
+            unchanged_albums.extend(unchanged_album_titles.into_iter().map(
+                |album_title| UnchangedAlbum {
+                    library_name: library.name(),
+                    artist_name: artist_name.clone(),
+                    album_title,
+                },
+            ));
         }
 
         // Any artists left in `remaining_saved_tracked_artists` are those that were entirely removed
@@ -979,39 +2979,46 @@ fn collect_changes<'config>(
                 true,
             )?;
 
-            let artist_transcoded_directory_path =
-                artist_view.read().artist_directory_in_transcoded_library();
-            if !artist_transcoded_directory_path.exists() {
-                continue;
-            }
-
             let saved_tracked_artist_album_list = saved_tracked_artist_album_list.as_ref().expect("BUG: remaining_saved_tracked_artists was non-empty even though saved_tracked_artist_album_list was None.");
 
             let artist_albums = saved_tracked_artist_album_list.tracked_artists.get(fully_removed_artist)
                 .expect("BUG: Artist is missing even though the set was generated from it.");
 
+            // Albums whose output directory was built from a template don't need to be
+            // inside the artist directory, so each album is checked on its own.
             let sorted_removed_albums = artist_albums
                 .tracked_albums
                 .iter()
-                .map(|album| {
-                    let album_view = AlbumView::new(
-                        artist_view.clone(),
-                        album.album_title.clone(),
-                        true,
-                    )?;
+                .filter_map(|album| {
+                    let album_view = match removed_album_view(artist_view.clone(), album) {
+                        Ok(view) => view,
+                        Err(error) => return Some(Err(error))
+                    };
+
+                    if !album_view.read().album_directory_in_transcoded_library().exists() {
+                        return None;
+                    }
 
-                    let album_changes = AlbumFileChangesV2::generate_entire_transcoded_album_deletion(
-                        album_view,
-                        &album.album_source_relative_path
-                    )?;
+                    let album_changes = match AlbumFileChangesV2::generate_entire_transcoded_album_deletion(
+                        album_view.clone(),
+                        album.album_output_relative_path()
+                    ) {
+                        Ok(changes) => changes,
+                        Err(error) => return Some(Err(error))
+                    };
 
-                    Ok(FullyRemovedAlbum {
+                    Some(Ok(FullyRemovedAlbum {
+                        album: album_view,
                         album_title: album.album_title.clone(),
                         changes: album_changes,
-                    })
+                    }))
                 })
                 .collect::<Result<Vec<FullyRemovedAlbum>>>()?;
 
+            if sorted_removed_albums.is_empty() {
+                continue;
+            }
+
 
             if is_verbose_enabled() {
                 terminal.log_println(format!(
@@ -1055,7 +3062,7 @@ fn collect_changes<'config>(
         first.library_name.cmp(&second.library_name)
     });
 
-    Ok(libraries_with_changes)
+    Ok((libraries_with_changes, unchanged_albums))
 }
 
 
@@ -1063,6 +3070,9 @@ fn collect_changes<'config>(
 pub enum QueuedAlbumJobType {
     NormalProcessing,
     FullyRemoving,
+    /// Only the explicitly requested files are processed and album states are left untouched
+    /// (see `explicit_paths::cmd_transcode_paths`).
+    ExplicitFiles,
 }
 
 pub struct QueuedAlbum<'view> {
@@ -1086,8 +3096,8 @@ pub struct QueuedLibrary<'view> {
 }
 
 
-fn queue_all_changed_albums<'config: 'scope, 'scope>(
-    terminal: &TranscodeTerminal<'config, 'scope>,
+fn queue_all_changed_albums<'config>(
+    terminal: &impl TranscodeEventSink<'config>,
     libraries_with_changes: Vec<LibraryWithChanges<'config>>,
 ) -> Result<Vec<QueuedLibrary<'config>>> {
     let mut queued_libraries: Vec<QueuedLibrary> =
@@ -1111,7 +3121,7 @@ fn queue_all_changed_albums<'config: 'scope, 'scope>(
         for artist in changed_library.sorted_changed_artists {
             for changed_album in artist.sorted_changed_albums {
                 let album_queue_id =
-                    terminal.queue_album_item_add(AlbumQueueItem::new(
+                    terminal.on_album_queued(AlbumQueueItem::new(
                         changed_album.album.clone(),
                         changed_album.changes.number_of_changed_audio_files(),
                         changed_album.changes.number_of_changed_data_files(),
@@ -1126,21 +3136,15 @@ fn queue_all_changed_albums<'config: 'scope, 'scope>(
             }
 
             for removed_album in artist.sorted_removed_albums {
-                let removed_album_view = AlbumView::new(
-                    artist.artist.clone(),
-                    removed_album.album_title.clone(),
-                    true,
-                )?;
-
                 let album_queue_id =
-                    terminal.queue_album_item_add(AlbumQueueItem::new(
-                        removed_album_view.clone(),
+                    terminal.on_album_queued(AlbumQueueItem::new(
+                        removed_album.album.clone(),
                         removed_album.changes.number_of_changed_audio_files(),
                         removed_album.changes.number_of_changed_data_files(),
                     ))?;
 
                 queued_albums.push(QueuedAlbum {
-                    album: removed_album_view,
+                    album: removed_album.album,
                     queue_id: album_queue_id,
                     changes: removed_album.changes,
                     job_type: QueuedAlbumJobType::FullyRemoving,
@@ -1159,112 +3163,3 @@ fn queue_all_changed_albums<'config: 'scope, 'scope>(
 
     Ok(queued_libraries)
 }
-
-
-/// A message type to send from the main processing thread to `process_album_changes`.
-/// Currently the only possible message is for the worker threads to stop.
-enum MainThreadMessage {
-    StopProcessing,
-}
-
-/// Process an entire album (given its `AlbumFileChangesV2`).
-///
-/// `worker_progress_sender` is the `Sender` part of a channel that individual file workers
-/// can use to send `FileJobMessage`s back to the main thread.
-///
-/// `main_thread_receiver` is the `Receiver` part of a channel that the main thread can use
-/// to signal `MainThreadMessage`s (currently just an "abort processing" message).
-///
-/// This function returns with `Ok(())` when the album has been processed.
-fn process_changes<'config>(
-    album_changes: &AlbumFileChangesV2,
-    album: SharedAlbumView<'config>,
-    terminal: &TranscodeTerminal<'config, '_>,
-    worker_progress_sender: Sender<FileJobMessage>,
-    main_thread_receiver: Receiver<MainThreadMessage>,
-) -> Result<()> {
-    let thread_pool_size = {
-        let album_locked = album.read();
-
-        album_locked
-            .euphony_configuration()
-            .aggregated_library
-            .transcode_threads
-    };
-
-    let mut thread_pool =
-        CancellableThreadPool::new(thread_pool_size, worker_progress_sender);
-    thread_pool.start()?;
-
-    if is_verbose_enabled() {
-        terminal.log_println(format!(
-            "absolute_source_file_paths_to_transcoded_file_paths_map={:?}",
-            album_changes
-                .tracked_source_files
-                .as_ref()
-                .map(|files| files
-                    .map_source_file_paths_to_transcoded_file_paths_absolute())
-                .unwrap_or_default()
-        ));
-    }
-
-    // Generate and queue all file jobs.
-    let jobs = album_changes.generate_file_jobs(|context| {
-        // Parse queue item details.
-        let target_path = context.action.target_path();
-        let file_name = target_path
-            .file_name()
-            .ok_or_else(|| {
-                miette!("Invalid path: no file name: {:?}.", target_path)
-            })?
-            .to_string_lossy();
-
-        // Instantiate `FileItem` and add to queue.
-        let file_item = FileQueueItem::<'config>::new(
-            album.clone(),
-            file_name.to_string(),
-            context,
-        );
-
-        let queued_file_item_id = terminal.queue_file_item_add(file_item)?;
-
-        Ok(queued_file_item_id)
-    })?;
-
-    // Could flatten this into `generate_file_jobs`, but this is cleaner.
-    for job in jobs {
-        // This does not block! The thread pool has an internal job queue.
-        thread_pool.queue_task(job);
-    }
-
-    // All jobs have been queued, now we wait for tasks to complete.
-    while thread_pool.has_tasks_left() && thread_pool.is_running() {
-        // Keep checking for a user exit message.
-        let potential_main_thread_message =
-            main_thread_receiver.recv_timeout(Duration::from_millis(20));
-
-        match potential_main_thread_message {
-            Ok(message) => match message {
-                MainThreadMessage::StopProcessing => {
-                    // Any exit from this while loop will mean the
-                    // cancellation flag will be set to true, so a break is enough.
-                    break;
-                }
-            },
-            Err(error) => {
-                if error == RecvTimeoutError::Disconnected {
-                    panic!("Main thread receiver disconnected unexpectedly!?");
-                }
-            }
-        }
-    }
-
-    // This point is reached on two occasions:
-    // - thread pool jobs finished normally, in which case the following will barely block,
-    // - main thread requested cancellation, in which case we're probably going to have to wait for the workers a bit.
-    thread_pool
-        .set_cancellation_and_join()
-        .wrap_err_with(|| miette!("Thread pool exited abnormally."))?;
-
-    Ok(())
-}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use euphony_configuration::Configuration;
+use miette::Result;
+#[cfg(not(feature = "desktop-notifications"))]
+use tracing::warn;
+
+use super::TranscodeRunSummary;
+use crate::console::formatting::format_duration;
+
+
+/// Show a desktop notification with the outcome of a transcode
+/// (if `ui.notify_on_completion` is enabled): a summary of the processed albums
+/// or, if the run failed or some files failed to process, a distinct error notification.
+///
+/// Showing the notification is best-effort: if it can't be shown (e.g. there is
+/// no notification service), this does nothing.
+pub fn notify_transcode_completion(
+    configuration: &Configuration,
+    result: &Result<TranscodeRunSummary>,
+    elapsed: Duration,
+) {
+    if !configuration.ui.notify_on_completion {
+        return;
+    }
+
+    let elapsed = format_duration(elapsed);
+
+    match result {
+        Ok(summary) if summary.num_errored_albums > 0 => show_notification(
+            "Transcoding finished with failures",
+            &format!(
+                "{} albums processed, {} of them with failed files, in {}.",
+                summary.num_processed_albums,
+                summary.num_errored_albums,
+                elapsed
+            ),
+            true,
+        ),
+        Ok(summary) => show_notification(
+            "Transcoding finished",
+            &format!(
+                "{} albums processed, no failures, in {}.",
+                summary.num_processed_albums, elapsed
+            ),
+            false,
+        ),
+        Err(error) => show_notification(
+            "Transcoding failed",
+            &format!("{} (after {}).", error.root_cause(), elapsed),
+            true,
+        ),
+    }
+}
+
+
+#[cfg(feature = "desktop-notifications")]
+fn show_notification(summary: &str, body: &str, is_error: bool) {
+    let mut notification = notify_rust::Notification::new();
+    notification
+        .appname("euphony")
+        .summary(summary)
+        .body(body)
+        .icon(
+            if is_error {
+                "dialog-error"
+            } else {
+                "dialog-information"
+            },
+        );
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if is_error {
+        notification.urgency(notify_rust::Urgency::Critical);
+    }
+
+    if let Err(error) = notification.show() {
+        tracing::debug!(%error, "Could not show the desktop notification.");
+    }
+}
+
+#[cfg(not(feature = "desktop-notifications"))]
+fn show_notification(_summary: &str, _body: &str, _is_error: bool) {
+    warn!(
+        "ui.notify_on_completion is enabled, but this build of euphony \
+        doesn't support desktop notifications (see the desktop-notifications feature)."
+    );
+}
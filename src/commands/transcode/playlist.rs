@@ -0,0 +1,180 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use euphony_configuration::Configuration;
+use euphony_library::state::transcoded::{
+    TranscodedAlbumState,
+    TranscodedAlbumStateLoadError,
+};
+use euphony_library::view::LibraryView;
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+/// File name of the master playlist in the root of the aggregated library
+/// (see `aggregated_library.generate_master_playlist`).
+pub const MASTER_PLAYLIST_FILE_NAME: &str = "euphony.m3u";
+
+
+/// A single album in the aggregated library that should appear in the master playlist.
+struct PlaylistAlbum {
+    artist_name: String,
+
+    album_title: String,
+
+    /// Absolute path to the album directory in the aggregated library.
+    transcoded_album_directory: PathBuf,
+}
+
+
+/// Convert a path relative to the aggregated library root into an M3U entry.
+/// Forward slashes are used on all platforms, so the playlist works on portable players as well.
+fn path_to_playlist_entry(relative_path: &Path) -> String {
+    relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Collect all albums of all libraries, sorted by artist name and album title.
+///
+/// Directories in `ignored_directories_in_base_directory` are skipped.
+fn collect_playlist_albums(
+    configuration: &Configuration,
+) -> Result<Vec<PlaylistAlbum>> {
+    let mut albums: Vec<PlaylistAlbum> = Vec::new();
+
+    for library_config in configuration.libraries.values() {
+        let library_view = LibraryView::from_library_configuration(
+            configuration,
+            library_config,
+        )?;
+        let library_view_locked = library_view.read();
+
+
+        for (artist_name, artist_view) in library_view_locked.artists()? {
+            if library_config
+                .is_directory_ignored_in_base_directory(&artist_name)
+            {
+                continue;
+            }
+
+            for (album_title, album_view) in artist_view.read().albums()? {
+                albums.push(PlaylistAlbum {
+                    artist_name: artist_name.clone(),
+                    album_title,
+                    transcoded_album_directory: album_view
+                        .read()
+                        .album_directory_in_transcoded_library(),
+                });
+            }
+        }
+    }
+
+    albums.sort_unstable_by(|first, second| {
+        (&first.artist_name, &first.album_title)
+            .cmp(&(&second.artist_name, &second.album_title))
+    });
+
+    Ok(albums)
+}
+
+/// Generate the master playlist (see `MASTER_PLAYLIST_FILE_NAME`) in the root
+/// of the aggregated library, listing all transcoded (or copied) audio files,
+/// grouped by artist and album. Paths in the playlist are relative to the aggregated library root,
+/// so the playlist keeps working when the library is moved (e.g. onto a portable player).
+///
+/// The playlist is always rebuilt from scratch from the saved transcoded album states,
+/// which means it only contains files that are currently present in the aggregated library.
+/// Albums that haven't been transcoded yet are skipped.
+///
+/// Returns the number of tracks in the playlist.
+pub fn write_master_playlist(configuration: &Configuration) -> Result<usize> {
+    let aggregated_library_path =
+        Path::new(&configuration.aggregated_library.path);
+
+    let mut playlist = String::from("#EXTM3U\n");
+    let mut num_tracks: usize = 0;
+
+    for album in collect_playlist_albums(configuration)? {
+        let transcoded_state = match TranscodedAlbumState::load_from_directory(
+            &album.transcoded_album_directory,
+        ) {
+            Ok(state) => state,
+            Err(TranscodedAlbumStateLoadError::NotFound) => continue,
+            Err(error) => {
+                return Err(error).into_diagnostic().wrap_err_with(|| {
+                    miette!(
+                        "Could not load transcoded album state for {} - {}.",
+                        album.artist_name,
+                        album.album_title
+                    )
+                });
+            }
+        };
+
+        let relative_album_directory = album
+            .transcoded_album_directory
+            .strip_prefix(aggregated_library_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "BUG: Transcoded album directory is not in the aggregated library: {:?}",
+                    album.transcoded_album_directory
+                )
+            })?;
+
+        let mut audio_files = transcoded_state
+            .transcoded_files
+            .audio_files
+            .keys()
+            .map(PathBuf::from)
+            .filter(|relative_file_path| {
+                album
+                    .transcoded_album_directory
+                    .join(relative_file_path)
+                    .is_file()
+            })
+            .collect::<Vec<PathBuf>>();
+
+        if audio_files.is_empty() {
+            continue;
+        }
+
+        audio_files.sort_unstable();
+
+        writeln!(
+            playlist,
+            "# {} - {}",
+            album.artist_name, album.album_title
+        )
+        .into_diagnostic()?;
+
+        for relative_file_path in audio_files {
+            writeln!(
+                playlist,
+                "{}",
+                path_to_playlist_entry(
+                    &relative_album_directory.join(relative_file_path)
+                )
+            )
+            .into_diagnostic()?;
+
+            num_tracks += 1;
+        }
+    }
+
+    let playlist_file_path =
+        aggregated_library_path.join(MASTER_PLAYLIST_FILE_NAME);
+
+    fs::write(&playlist_file_path, playlist)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Could not write master playlist: {:?}",
+                playlist_file_path
+            )
+        })?;
+
+    Ok(num_tracks)
+}
@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+
+/// Whether the file or directory name starts with a dot (e.g. `.album.transcode-state.euphony`).
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|file_name| file_name.to_string_lossy().starts_with('.'))
+}
+
+/// Recursively prune empty directories inside `directory_path` (bottom-up),
+/// then remove `directory_path` itself if it ended up empty (unless `is_root` is set).
+///
+/// If `remove_hidden_files` is set, directories that contain nothing but hidden files
+/// (and empty directories) count as empty as well and the hidden files are removed with them.
+///
+/// Removed directories are appended to `removed_directories`.
+/// Returns whether `directory_path` is (now) empty.
+fn prune_directory(
+    directory_path: &Path,
+    is_root: bool,
+    remove_hidden_files: bool,
+    removed_directories: &mut Vec<PathBuf>,
+) -> Result<bool> {
+    let directory_entries = fs::read_dir(directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!("Could not read directory {:?}.", directory_path)
+        })?;
+
+    let mut is_empty = true;
+    let mut hidden_files: Vec<PathBuf> = Vec::new();
+
+    for entry in directory_entries {
+        let entry = entry.into_diagnostic().wrap_err_with(|| {
+            miette!("Could not read an entry of {:?}.", directory_path)
+        })?;
+        let entry_path = entry.path();
+
+        // Symbolic links are never followed (nor removed).
+        let entry_type = entry.file_type().into_diagnostic()?;
+        if entry_type.is_dir() {
+            if !prune_directory(
+                &entry_path,
+                false,
+                remove_hidden_files,
+                removed_directories,
+            )? {
+                is_empty = false;
+            }
+        } else if remove_hidden_files
+            && entry_type.is_file()
+            && is_hidden(&entry_path)
+        {
+            hidden_files.push(entry_path);
+        } else {
+            is_empty = false;
+        }
+    }
+
+    if !is_empty || is_root {
+        return Ok(is_empty);
+    }
+
+    for hidden_file_path in hidden_files {
+        fs::remove_file(&hidden_file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not remove hidden file {:?}.",
+                    hidden_file_path
+                )
+            })?;
+    }
+
+    fs::remove_dir(directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Could not remove empty directory {:?}.",
+                directory_path
+            )
+        })?;
+    removed_directories.push(directory_path.to_path_buf());
+
+    Ok(true)
+}
+
+/// Remove all empty directories in the aggregated library (see `transcode --prune-empty-dirs`),
+/// e.g. the ones left behind by albums that produced no output files.
+/// The aggregated library directory itself is never removed.
+///
+/// Directories containing hidden files (e.g. euphony's state files) are not considered empty,
+/// unless `remove_hidden_files` is set, in which case such directories are removed
+/// along with their hidden files.
+///
+/// Returns the (sorted) removed directories.
+pub fn prune_empty_directories<P: AsRef<Path>>(
+    aggregated_library_path: P,
+    remove_hidden_files: bool,
+) -> Result<Vec<PathBuf>> {
+    let aggregated_library_path = aggregated_library_path.as_ref();
+    let mut removed_directories: Vec<PathBuf> = Vec::new();
+
+    if !aggregated_library_path.is_dir() {
+        return Ok(removed_directories);
+    }
+
+    prune_directory(
+        aggregated_library_path,
+        true,
+        remove_hidden_files,
+        &mut removed_directories,
+    )?;
+
+    removed_directories.sort_unstable();
+    Ok(removed_directories)
+}
@@ -0,0 +1,244 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+/// Marker inserted into the names of temporary files while their tags are being written
+/// (e.g. `01 Song.replaygain-tmp.mp3`). The extension is kept, so ffmpeg picks the same format.
+const TEMPORARY_FILE_MARKER: &str = "replaygain-tmp";
+
+
+/// Gain and peak of a track or an entire album, as reported by ffmpeg's `replaygain` filter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ReplayGainValues {
+    /// Gain in decibels.
+    gain: f64,
+
+    /// Peak sample amplitude (1.0 is full scale).
+    peak: f64,
+}
+
+impl ReplayGainValues {
+    fn formatted_gain(&self) -> String {
+        format!("{:+.2} dB", self.gain)
+    }
+
+    fn formatted_peak(&self) -> String {
+        format!("{:.6}", self.peak)
+    }
+}
+
+
+/// Parse the gain and peak the `replaygain` filter logs when it finishes, e.g.:
+/// ```text
+/// [Parsed_replaygain_0 @ 0x5581f8a0c2c0] track_gain = -7.43 dB
+/// [Parsed_replaygain_0 @ 0x5581f8a0c2c0] track_peak = 0.988525
+/// ```
+fn parse_replaygain_output(ffmpeg_output: &str) -> Option<ReplayGainValues> {
+    let value_after = |key: &str| -> Option<f64> {
+        ffmpeg_output.lines().rev().find_map(|line| {
+            let (_, value) = line.split_once(key)?;
+            value
+                .trim_start()
+                .strip_prefix('=')?
+                .trim()
+                .trim_end_matches("dB")
+                .trim()
+                .parse::<f64>()
+                .ok()
+        })
+    };
+
+    Some(ReplayGainValues {
+        gain: value_after("track_gain")?,
+        peak: value_after("track_peak")?,
+    })
+}
+
+/// Analyze the given audio files with ffmpeg's `replaygain` filter as if they were
+/// a single stream (i.e. a single file gives its track gain, all files of an album
+/// concatenated give the album gain).
+fn analyze_audio_files(
+    ffmpeg_binary_path: &str,
+    audio_file_paths: &[PathBuf],
+) -> Result<ReplayGainValues> {
+    let mut arguments: Vec<OsString> =
+        vec!["-hide_banner".into(), "-nostats".into()];
+
+    for audio_file_path in audio_file_paths {
+        arguments.push("-i".into());
+        arguments.push(audio_file_path.into());
+    }
+
+    let filter = if audio_file_paths.len() == 1 {
+        String::from("[0:a:0]replaygain")
+    } else {
+        let inputs: String = (0..audio_file_paths.len())
+            .map(|input_index| format!("[{input_index}:a:0]"))
+            .collect();
+
+        format!(
+            "{inputs}concat=n={}:v=0:a=1,replaygain",
+            audio_file_paths.len()
+        )
+    };
+
+    arguments.extend(["-filter_complex".into(), filter.into()]);
+    arguments.extend(["-f".into(), "null".into(), "-".into()]);
+
+    let output = Command::new(ffmpeg_binary_path)
+        .args(&arguments)
+        .stdin(Stdio::null())
+        .output()
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!("Could not spawn ffmpeg for ReplayGain analysis.")
+        })?;
+
+    let ffmpeg_output = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(miette!(
+            "ffmpeg exited with {} while analyzing {:?}: {}",
+            output.status,
+            audio_file_paths,
+            ffmpeg_output.lines().last().unwrap_or_default()
+        ));
+    }
+
+    parse_replaygain_output(&ffmpeg_output).ok_or_else(|| {
+        miette!(
+            "Could not find the ReplayGain values in ffmpeg's output for {:?}.",
+            audio_file_paths
+        )
+    })
+}
+
+/// Write the ReplayGain tags into the given audio file by remuxing it (without re-encoding)
+/// into a temporary file next to it, which then replaces the original.
+fn write_replaygain_tags(
+    ffmpeg_binary_path: &str,
+    audio_file_path: &Path,
+    track: ReplayGainValues,
+    album: ReplayGainValues,
+) -> Result<()> {
+    let temporary_file_path = audio_file_path.with_extension(format!(
+        "{TEMPORARY_FILE_MARKER}.{}",
+        audio_file_path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+    ));
+
+    let output = Command::new(ffmpeg_binary_path)
+        .args(["-hide_banner", "-nostats", "-v", "error", "-y", "-i"])
+        .arg(audio_file_path)
+        .args(["-map", "0", "-c", "copy", "-map_metadata", "0"])
+        .args([
+            "-metadata",
+            &format!("REPLAYGAIN_TRACK_GAIN={}", track.formatted_gain()),
+            "-metadata",
+            &format!("REPLAYGAIN_TRACK_PEAK={}", track.formatted_peak()),
+            "-metadata",
+            &format!("REPLAYGAIN_ALBUM_GAIN={}", album.formatted_gain()),
+            "-metadata",
+            &format!("REPLAYGAIN_ALBUM_PEAK={}", album.formatted_peak()),
+        ])
+        .arg(&temporary_file_path)
+        .stdin(Stdio::null())
+        .output()
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!("Could not spawn ffmpeg for ReplayGain tagging.")
+        })?;
+
+    if !output.status.success() || !temporary_file_path.is_file() {
+        let _ = fs::remove_file(&temporary_file_path);
+
+        return Err(miette!(
+            "ffmpeg exited with {} while writing the ReplayGain tags of {:?}: {}",
+            output.status,
+            audio_file_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    fs::rename(&temporary_file_path, audio_file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Could not replace {:?} with its tagged copy.",
+                audio_file_path
+            )
+        })
+}
+
+/// Compute the track and album ReplayGain values of the given transcoded audio files
+/// (all files of a single album) and write them into the files as
+/// `REPLAYGAIN_{TRACK,ALBUM}_{GAIN,PEAK}` tags (see `libraries.<key>.transcoding.replaygain`).
+///
+/// All files are analyzed before any of them is tagged, so if the analysis fails,
+/// the files are left as they are.
+pub fn apply_replaygain_to_album(
+    ffmpeg_binary_path: &str,
+    audio_file_paths: &[PathBuf],
+) -> Result<()> {
+    if audio_file_paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut sorted_audio_file_paths = audio_file_paths.to_vec();
+    sorted_audio_file_paths.sort_unstable();
+
+    let track_values = sorted_audio_file_paths
+        .iter()
+        .map(|audio_file_path| {
+            analyze_audio_files(
+                ffmpeg_binary_path,
+                std::slice::from_ref(audio_file_path),
+            )
+        })
+        .collect::<Result<Vec<ReplayGainValues>>>()?;
+
+    // The album gain of a single-track album is its track gain.
+    let album_values = match track_values.as_slice() {
+        [single_track_values] => *single_track_values,
+        _ => analyze_audio_files(ffmpeg_binary_path, &sorted_audio_file_paths)?,
+    };
+
+    for (audio_file_path, track_values) in
+        sorted_audio_file_paths.iter().zip(track_values)
+    {
+        write_replaygain_tags(
+            ffmpeg_binary_path,
+            audio_file_path,
+            track_values,
+            album_values,
+        )?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_replaygain_filter_output() {
+        let output = "Input #0, mp3, from 'song.mp3':\n\
+            [Parsed_replaygain_1 @ 0x5581f8a0c2c0] track_gain = -7.43 dB\n\
+            [Parsed_replaygain_1 @ 0x5581f8a0c2c0] track_peak = 0.988525\n";
+
+        let values = parse_replaygain_output(output).unwrap();
+        assert_eq!(values.formatted_gain(), "-7.43 dB");
+        assert_eq!(values.formatted_peak(), "0.988525");
+
+        assert_eq!(
+            parse_replaygain_output("track_gain = +1.20 dB"),
+            None
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::thread;
+
+use crossbeam::channel;
+use euphony_library::state::AlbumFileChangesV2;
+use euphony_library::view::{SharedAlbumView, SharedLibraryView};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use super::events::TranscodeEventSink;
+use super::library_state::LibraryState;
+use super::target::TranscodeTarget;
+use super::ChangedAlbum;
+
+
+/// Build the thread pool that is used for scanning libraries and albums for changes
+/// (see `collect_full_library_states` and `scan_all_albums_for_changes`).
+pub fn build_scan_thread_pool(num_scan_threads: usize) -> Result<ThreadPool> {
+    ThreadPoolBuilder::new()
+        .num_threads(num_scan_threads.max(1))
+        .thread_name(|thread_index| format!("euphony-scan-{thread_index}"))
+        .build()
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Could not create the scanning thread pool."))
+}
+
+/// Run `operation` on each item on the given thread pool.
+///
+/// `on_item_finished` is called on the calling thread with the number of finished items
+/// each time an item finishes (e.g. to update a progress indicator).
+///
+/// Results are returned in the same order as the input items.
+fn map_in_parallel<T, R, F, P>(
+    thread_pool: &ThreadPool,
+    items: &[T],
+    operation: F,
+    mut on_item_finished: P,
+) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+    P: FnMut(usize) -> Result<()>,
+{
+    let (finished_sender, finished_receiver) = channel::unbounded::<()>();
+
+    thread::scope(|scope| {
+        let operation = &operation;
+
+        // The sender is moved into the parallel iterator (and cloned for each of its splits),
+        // so the progress loop below ends once all items have been processed.
+        let parallel_map_handle = scope.spawn(move || {
+            thread_pool.install(|| {
+                items
+                    .par_iter()
+                    .map_with(finished_sender, |finished_sender, item| {
+                        let result = operation(item);
+                        let _ = finished_sender.send(());
+
+                        result
+                    })
+                    .collect::<Vec<R>>()
+            })
+        });
+
+        for (num_finished, _) in finished_receiver.iter().enumerate() {
+            on_item_finished(num_finished + 1)?;
+        }
+
+        parallel_map_handle
+            .join()
+            .map_err(|_| miette!("Parallel scanning thread panicked."))
+    })
+}
+
+/// Collect the artists and albums of all given libraries that are part of `target`,
+/// listing the libraries and artists in parallel.
+fn collect_albums_to_scan<'config>(
+    sorted_libraries_with_fresh_states: &[(
+        SharedLibraryView<'config>,
+        LibraryState,
+    )],
+    thread_pool: &ThreadPool,
+    target: &TranscodeTarget,
+) -> Result<Vec<(String, String, String, SharedAlbumView<'config>)>> {
+    thread_pool.install(|| {
+        let artists = sorted_libraries_with_fresh_states
+            .par_iter()
+            .map(|(library, _)| {
+                let library_locked = library.read();
+                let library_name = library_locked.name();
+
+                Ok(library_locked
+                    .artists()?
+                    .into_iter()
+                    .map(|(artist_name, artist)| {
+                        (library_name.clone(), artist_name, artist)
+                    })
+                    .collect::<Vec<_>>())
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let albums = artists
+            .par_iter()
+            .map(|(library_name, artist_name, artist)| {
+                Ok(artist
+                    .read()
+                    .albums()?
+                    .into_iter()
+                    .filter(|(album_title, _)| {
+                        target.includes_album(
+                            library_name,
+                            artist_name,
+                            album_title,
+                        )
+                    })
+                    .map(|(album_title, album)| {
+                        (
+                            library_name.clone(),
+                            artist_name.clone(),
+                            album_title,
+                            album,
+                        )
+                    })
+                    .collect::<Vec<_>>())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(albums.into_iter().flatten().collect())
+    })
+}
+
+
+/// Results of scanning a single artist's albums for changes.
+#[derive(Default)]
+pub struct ScannedArtistAlbums<'config> {
+    /// Albums that have changed (or haven't been transcoded at all yet).
+    pub changed_albums: Vec<ChangedAlbum<'config>>,
+
+    /// Titles of albums that are up to date.
+    pub unchanged_album_titles: Vec<String>,
+}
+
+/// Maps a library name and an artist name to the results of scanning the artist's albums.
+pub type ScannedAlbums<'config> =
+    HashMap<(String, String), ScannedArtistAlbums<'config>>;
+
+
+/// Scan all albums in the given libraries (that are part of `target`) for changes
+/// on the given thread pool (see `build_scan_thread_pool`).
+///
+/// Scanning reads the metadata of every file in each album, which can take a while for
+/// large collections, so this also displays a scan progress indicator.
+pub fn scan_all_albums_for_changes<'config>(
+    sorted_libraries_with_fresh_states: &[(
+        SharedLibraryView<'config>,
+        LibraryState,
+    )],
+    terminal: &impl TranscodeEventSink<'config>,
+    thread_pool: &ThreadPool,
+    target: &TranscodeTarget,
+) -> Result<ScannedAlbums<'config>> {
+    let albums_to_scan = collect_albums_to_scan(
+        sorted_libraries_with_fresh_states,
+        thread_pool,
+        target,
+    )?;
+
+    terminal.on_scan_started(albums_to_scan.len());
+
+    let scan_results = map_in_parallel(
+        thread_pool,
+        &albums_to_scan,
+        |(_, _, _, album)| album.read().scan_for_changes(),
+        |num_scanned| terminal.on_scan_progress(num_scanned),
+    );
+
+    terminal.on_scan_finished();
+
+    let mut scanned_albums: ScannedAlbums = HashMap::new();
+
+    for ((library_name, artist_name, album_title, album), changes) in
+        albums_to_scan.into_iter().zip(scan_results?)
+    {
+        let changes: AlbumFileChangesV2 = changes?;
+
+        let scanned_artist_albums = scanned_albums
+            .entry((library_name, artist_name))
+            .or_default();
+
+        if changes.has_changes() {
+            scanned_artist_albums.changed_albums.push(ChangedAlbum {
+                album,
+                album_title,
+                changes,
+            });
+        } else {
+            scanned_artist_albums
+                .unchanged_album_titles
+                .push(album_title);
+        }
+    }
+
+    Ok(scanned_albums)
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use std::{fs, io};
+
+use euphony_library::view::LibraryView;
+use miette::{miette, Context, Diagnostic, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const SCAN_FINGERPRINTS_FILE_NAME: &str =
+    ".library.scan-fingerprints.euphony";
+const SCAN_FINGERPRINTS_SCHEMA_VERSION: u32 = 2;
+
+/// Files with this suffix are written by euphony itself and are not part of the fingerprint.
+const FINGERPRINT_EXCLUDED_FILE_SUFFIX: &str = ".euphony";
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum ScanFingerprintsLoadError {
+    #[error("no scan fingerprints found on disk")]
+    NotFound,
+
+    #[error(
+        "schema version mismatch: {0} (current is {})",
+        SCAN_FINGERPRINTS_SCHEMA_VERSION
+    )]
+    SchemaVersionMismatch(u32),
+
+    #[error("io::Error encountered while loading scan fingerprints")]
+    IoError(#[from] io::Error),
+
+    #[error("serde_json::Error encountered while loading scan fingerprints")]
+    JSONError(#[from] serde_json::Error),
+}
+
+
+/// Compute a cheap fingerprint of the entire source library directory tree.
+///
+/// The fingerprint is a BLAKE3 hash of the (sorted) list of all files in the library,
+/// along with the size and modification time of each file. This means that adding, removing,
+/// renaming or rewriting any file will change the fingerprint, but modifying a file in-place
+/// without changing its size or modification time *will not* (that is why using the cached scan
+/// is opt-in).
+///
+/// Euphony's own `*.euphony` files (e.g. album source states) are skipped, as they are written
+/// into the source library by the transcode itself. Directories from
+/// `ignored_directories_in_base_directory` and files and directories
+/// from `ignored_names_at_any_depth` are skipped as well, just like when scanning.
+///
+/// The fingerprint is hex-encoded.
+pub fn compute_library_fingerprint(library: &LibraryView) -> Result<String> {
+    let library_root = library.root_directory_in_source_library();
+
+    let mut hasher = blake3::Hasher::new();
+    let mut pending_directories = vec![library_root.clone()];
+
+    while let Some(directory_path) = pending_directories.pop() {
+        let mut directory_entries = fs::read_dir(&directory_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Failed to read directory: {:?}", directory_path)
+            })?
+            .collect::<Result<Vec<_>, io::Error>>()
+            .into_diagnostic()?;
+
+        // Directory listing order is platform-dependent, so we sort to keep the hash deterministic.
+        directory_entries.sort_unstable_by_key(|entry| entry.file_name());
+
+        for entry in directory_entries {
+            let entry_path = entry.path();
+            let entry_name = entry.file_name().to_string_lossy().to_string();
+
+            if library
+                .library_configuration
+                .is_name_ignored_at_any_depth(&entry_name)
+            {
+                continue;
+            }
+
+            if entry_path.is_dir() {
+                let is_ignored = directory_path == library_root
+                    && library
+                        .library_configuration
+                        .is_directory_ignored_in_base_directory(&entry_name);
+
+                if !is_ignored {
+                    pending_directories.push(entry_path);
+                }
+
+                continue;
+            }
+
+            if entry_name.ends_with(FINGERPRINT_EXCLUDED_FILE_SUFFIX) {
+                continue;
+            }
+
+            let file_metadata =
+                entry.metadata().into_diagnostic().wrap_err_with(|| {
+                    miette!("Failed to get metadata of {:?}", entry_path)
+                })?;
+            let file_modification_time = file_metadata
+                .modified()
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!(
+                        "Failed to get modification time of {:?}",
+                        entry_path
+                    )
+                })?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+
+            let relative_file_path = entry_path
+                .strip_prefix(&library_root)
+                .into_diagnostic()?
+                .to_string_lossy()
+                .to_string();
+
+            // The path is terminated so that it can't run into the numbers that follow it.
+            hasher.update(relative_file_path.as_bytes());
+            hasher.update(&[0]);
+            hasher.update(&file_metadata.len().to_le_bytes());
+            hasher.update(&file_modification_time.as_secs().to_le_bytes());
+            hasher.update(&file_modification_time.subsec_nanos().to_le_bytes());
+        }
+    }
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+
+/// Per-library scan fingerprints, saved in the root of the aggregated (transcoded) library.
+///
+/// See `compute_library_fingerprint` for more information.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScanFingerprints {
+    pub schema_version: u32,
+
+    /// Maps library names to their fingerprints from the last successful transcode.
+    pub library_fingerprints: HashMap<String, String>,
+}
+
+impl ScanFingerprints {
+    pub fn new(library_fingerprints: HashMap<String, String>) -> Self {
+        Self {
+            schema_version: SCAN_FINGERPRINTS_SCHEMA_VERSION,
+            library_fingerprints,
+        }
+    }
+
+    pub fn load_from_directory<P: AsRef<Path>>(
+        directory_path: P,
+    ) -> Result<Self, ScanFingerprintsLoadError> {
+        let file_path =
+            directory_path.as_ref().join(SCAN_FINGERPRINTS_FILE_NAME);
+
+        if !file_path.is_file() {
+            return Err(ScanFingerprintsLoadError::NotFound);
+        }
+
+        let file_contents = fs::read_to_string(file_path)?;
+        let fingerprints: Self = serde_json::from_str(&file_contents)?;
+
+        if fingerprints.schema_version != SCAN_FINGERPRINTS_SCHEMA_VERSION {
+            return Err(ScanFingerprintsLoadError::SchemaVersionMismatch(
+                fingerprints.schema_version,
+            ));
+        }
+
+        Ok(fingerprints)
+    }
+
+    pub fn save_to_directory<P: AsRef<Path>>(
+        &self,
+        output_directory_path: P,
+    ) -> Result<()> {
+        let output_directory_path = output_directory_path.as_ref();
+
+        fs::create_dir_all(output_directory_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not create directory {:?}.",
+                    output_directory_path
+                )
+            })?;
+
+        let serialized_fingerprints = serde_json::to_string(self)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Could not serialize scan fingerprints to string.")
+            })?;
+
+        let mut output_file = File::create(
+            output_directory_path.join(SCAN_FINGERPRINTS_FILE_NAME),
+        )
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Could not open output file for writing."))?;
+
+        output_file
+            .write_all(serialized_fingerprints.as_bytes())
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Could not write serialized scan fingerprints to file.")
+            })?;
+
+        Ok(())
+    }
+}
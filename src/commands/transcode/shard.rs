@@ -0,0 +1,162 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use super::LibraryWithChanges;
+
+/// A single shard of the collection (see `transcode --shard`).
+///
+/// Splitting a transcode into `count` shards and running each of them (e.g. on separate machines)
+/// covers every album exactly once: each album is assigned to a shard by a stable hash
+/// of its library name and its path relative to the library root.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Shard {
+    /// One-based index of this shard.
+    pub index: usize,
+
+    /// Total number of shards.
+    pub count: usize,
+}
+
+impl Shard {
+    /// Whether the given album (identified by its library name and its path
+    /// relative to the library root) belongs to this shard.
+    pub fn contains_album(
+        &self,
+        library_name: &str,
+        album_path_relative_to_library_root: &Path,
+    ) -> bool {
+        let hash =
+            stable_album_hash(library_name, album_path_relative_to_library_root);
+
+        (hash % self.count as u64) as usize == self.index - 1
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid_format_error = || {
+            format!("invalid shard \"{value}\": expected INDEX/COUNT, e.g. 1/3")
+        };
+
+        let (index, count) =
+            value.split_once('/').ok_or_else(invalid_format_error)?;
+
+        let index = index
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| invalid_format_error())?;
+        let count = count
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| invalid_format_error())?;
+
+        if count == 0 || index == 0 || index > count {
+            return Err(format!(
+                "invalid shard \"{value}\": the index must be between 1 and the shard count"
+            ));
+        }
+
+        Ok(Self { index, count })
+    }
+}
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+
+/// Hash the library name and the album path using 64-bit FNV-1a.
+///
+/// Unlike `DefaultHasher`, the result is stable across platforms, euphony and Rust versions,
+/// which is required for multiple machines to agree on the shard assignments.
+/// Path separators are normalized, so Windows and Unix machines agree as well.
+fn stable_album_hash(
+    library_name: &str,
+    album_path_relative_to_library_root: &Path,
+) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x00000100000001b3;
+
+    let album_path = album_path_relative_to_library_root
+        .to_string_lossy()
+        .replace('\\', "/");
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for byte in library_name
+        .bytes()
+        .chain(std::iter::once(b'/'))
+        .chain(album_path.bytes())
+    {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
+}
+
+
+/// Retain only the (changed or removed) albums that belong to the given shard.
+/// Artists and libraries that end up without any albums to process are dropped.
+///
+/// Returns the remaining libraries and the number of albums that were skipped
+/// because they belong to other shards.
+pub fn retain_albums_in_shard<'config>(
+    libraries_with_changes: Vec<LibraryWithChanges<'config>>,
+    shard: Shard,
+) -> (Vec<LibraryWithChanges<'config>>, usize) {
+    let mut num_skipped_albums: usize = 0;
+
+    let libraries_in_shard = libraries_with_changes
+        .into_iter()
+        .filter_map(|mut library| {
+            let library_name = library.library_name.clone();
+
+            for artist in &mut library.sorted_changed_artists {
+                let num_albums_before = artist.sorted_changed_albums.len()
+                    + artist.sorted_removed_albums.len();
+
+                let artist_path = artist
+                    .artist
+                    .read()
+                    .directory_path_relative_to_library_root();
+
+                artist.sorted_changed_albums.retain(|changed_album| {
+                    let album_path = changed_album
+                        .album
+                        .read()
+                        .directory_path_relative_to_library_root();
+
+                    shard.contains_album(&library_name, &album_path)
+                });
+                artist.sorted_removed_albums.retain(|album| {
+                    shard.contains_album(
+                        &library_name,
+                        &artist_path.join(&album.album_title),
+                    )
+                });
+
+                num_skipped_albums += num_albums_before
+                    - artist.sorted_changed_albums.len()
+                    - artist.sorted_removed_albums.len();
+            }
+
+            library.sorted_changed_artists.retain(|artist| {
+                !artist.sorted_changed_albums.is_empty()
+                    || !artist.sorted_removed_albums.is_empty()
+            });
+
+            if library.sorted_changed_artists.is_empty() {
+                None
+            } else {
+                Some(library)
+            }
+        })
+        .collect();
+
+    (libraries_in_shard, num_skipped_albums)
+}
@@ -0,0 +1,37 @@
+use std::fs;
+use std::path::Path;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+/// Number of bytes in a mebibyte (see `aggregated_library.size_budget_mib`).
+pub const BYTES_PER_MIB: u64 = 1024 * 1024;
+
+
+/// Recursively sum up the sizes of all files inside `directory_path`.
+/// Symbolic links are never followed.
+pub fn compute_directory_size<P: AsRef<Path>>(directory_path: P) -> Result<u64> {
+    let directory_path = directory_path.as_ref();
+
+    let directory_entries = fs::read_dir(directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!("Could not read directory {:?}.", directory_path)
+        })?;
+
+    let mut total_size_bytes: u64 = 0;
+
+    for entry in directory_entries {
+        let entry = entry.into_diagnostic().wrap_err_with(|| {
+            miette!("Could not read an entry of {:?}.", directory_path)
+        })?;
+        let entry_metadata = entry.metadata().into_diagnostic()?;
+
+        if entry_metadata.is_dir() {
+            total_size_bytes += compute_directory_size(entry.path())?;
+        } else {
+            total_size_bytes += entry_metadata.len();
+        }
+    }
+
+    Ok(total_size_bytes)
+}
@@ -0,0 +1,388 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use crossterm::style::Stylize;
+use euphony_configuration::Configuration;
+use euphony_library::state::AlbumFileChangesV2;
+use euphony_library::view::AlbumView;
+use miette::{miette, Result};
+
+use super::dry_run::collect_album_file_actions;
+use super::size_budget::BYTES_PER_MIB;
+use super::state::changes::FileProcessingAction;
+use super::{LibraryWithChanges, TranscodeOptions};
+use crate::console::formatting::format_byte_size;
+use crate::console::LogBackend;
+
+/// Bitrate (in bits per second) assumed for transcoded files when it can't be read
+/// from the ffmpeg arguments (roughly MP3 V0, euphony's default output).
+const DEFAULT_ESTIMATED_BITRATE: u64 = 245_000;
+
+/// Approximate average bitrates (in bits per second) of LAME's VBR presets,
+/// indexed by the quality (`-q:a 0` is V0).
+const LAME_VBR_PRESET_BITRATES: [u64; 10] = [
+    245_000, 225_000, 190_000, 175_000, 165_000, 130_000, 115_000, 100_000,
+    85_000, 65_000,
+];
+
+
+/// A rough estimate of how the aggregated library will change in size after processing
+/// the pending changes (see `estimate_output_size`).
+#[derive(Default, Clone, Copy, Debug)]
+pub struct OutputSizeEstimate {
+    /// Estimated total size of the files that will be transcoded or copied.
+    pub bytes_written: u64,
+
+    /// Total size of the existing files in the aggregated library
+    /// that will be overwritten or deleted.
+    pub bytes_freed: u64,
+}
+
+impl OutputSizeEstimate {
+    /// Estimated growth of the aggregated library (negative if it shrinks).
+    pub fn net_growth_bytes(&self) -> i128 {
+        self.bytes_written as i128 - self.bytes_freed as i128
+    }
+}
+
+
+/// Parse an ffmpeg bitrate such as `320k`, `1M` or `192000` into bits per second.
+fn parse_ffmpeg_bitrate(bitrate: &str) -> Option<u64> {
+    let bitrate = bitrate.trim();
+
+    let (number, multiplier) = match bitrate.chars().last()? {
+        'k' | 'K' => (&bitrate[..bitrate.len() - 1], 1_000),
+        'M' => (&bitrate[..bitrate.len() - 1], 1_000_000),
+        _ => (bitrate, 1),
+    };
+
+    let number: f64 = number.parse().ok()?;
+    if number <= 0.0 {
+        return None;
+    }
+
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Guess the bitrate (in bits per second) of files transcoded with the given ffmpeg
+/// arguments: an explicit audio bitrate (`-b:a`) or a LAME VBR quality (`-q:a`).
+/// Returns `None` if neither is set.
+fn estimate_target_bitrate(ffmpeg_arguments: &[String]) -> Option<u64> {
+    ffmpeg_arguments
+        .windows(2)
+        .find_map(|pair| match pair[0].as_str() {
+            "-b:a" | "-ab" => parse_ffmpeg_bitrate(&pair[1]),
+            "-q:a" | "-qscale:a" | "-aq" => {
+                let quality: f64 = pair[1].parse().ok()?;
+                LAME_VBR_PRESET_BITRATES
+                    .get(quality.round().max(0.0) as usize)
+                    .copied()
+            }
+            _ => None,
+        })
+}
+
+/// Read the duration of a FLAC file (in seconds) from its `STREAMINFO` block.
+/// Returns `None` if the file is not a FLAC file or its header can't be read.
+fn read_flac_duration<P: AsRef<Path>>(file_path: P) -> Option<f64> {
+    // "fLaC", the metadata block header and the first 18 bytes of STREAMINFO
+    // (which always comes first).
+    let mut header = [0u8; 26];
+    File::open(file_path).ok()?.read_exact(&mut header).ok()?;
+
+    if &header[0..4] != b"fLaC" || header[4] & 0x7F != 0 {
+        return None;
+    }
+
+    // 20 bits of sample rate, 3 bits of channels, 5 bits of bits per sample
+    // and 36 bits of total samples.
+    let packed = u64::from_be_bytes(header[18..26].try_into().ok()?);
+    let sample_rate = packed >> 44;
+    let total_samples = packed & 0xF_FFFF_FFFF;
+
+    if sample_rate == 0 || total_samples == 0 {
+        return None;
+    }
+
+    Some(total_samples as f64 / sample_rate as f64)
+}
+
+/// Estimate the size of the file the given audio file will be transcoded into:
+/// its duration times the target bitrate. If the duration is unknown
+/// (only FLAC files are supported), the size of the source file is used instead.
+fn estimate_transcoded_file_size(
+    album: Option<&AlbumView>,
+    source_file_path: &Path,
+    source_file_size: u64,
+) -> u64 {
+    let Some(duration) = read_flac_duration(source_file_path) else {
+        return source_file_size;
+    };
+
+    let target_bitrate = album
+        .and_then(|album| {
+            album
+                .configuration
+                .transcoding
+                .audio_transcoding_args_for_path(
+                    &album.euphony_configuration().tools.ffmpeg,
+                    &album.library_configuration().transcoding,
+                    source_file_path,
+                )
+                .ok()
+        })
+        .and_then(estimate_target_bitrate)
+        .unwrap_or(DEFAULT_ESTIMATED_BITRATE);
+
+    (duration * target_bitrate as f64 / 8.0) as u64
+}
+
+/// Size of the file, or zero if it doesn't exist (or can't be read).
+fn file_size_or_zero<P: AsRef<Path>>(file_path: P) -> u64 {
+    fs::metadata(file_path)
+        .map(|metadata| metadata.len())
+        .unwrap_or_default()
+}
+
+/// Add the file actions of a single album to the estimate.
+fn add_album_to_estimate(
+    configuration: &Arc<Configuration>,
+    estimate: &mut OutputSizeEstimate,
+    album: Option<&AlbumView>,
+    changes: &AlbumFileChangesV2,
+) -> Result<()> {
+    for action in collect_album_file_actions(configuration, changes)? {
+        let bytes_written = match &action {
+            FileProcessingAction::Transcode { source_path, .. } => {
+                estimate_transcoded_file_size(
+                    album,
+                    source_path,
+                    file_size_or_zero(source_path),
+                )
+            }
+            FileProcessingAction::Copy { source_path, .. } => {
+                file_size_or_zero(source_path)
+            }
+            FileProcessingAction::DeleteInTranscoded { .. } => 0,
+        };
+
+        estimate.bytes_written += bytes_written;
+        estimate.bytes_freed += file_size_or_zero(action.target_path());
+    }
+
+    Ok(())
+}
+
+/// Roughly estimate how much will be written into (and freed in) the aggregated library
+/// when processing the given changes. Data files are copied as-is, while the size
+/// of transcoded audio files is estimated from their duration and the target bitrate.
+pub fn estimate_output_size(
+    configuration: &Arc<Configuration>,
+    libraries_with_changes: &[LibraryWithChanges],
+) -> Result<OutputSizeEstimate> {
+    let mut estimate = OutputSizeEstimate::default();
+
+    for artist in libraries_with_changes
+        .iter()
+        .flat_map(|library| &library.sorted_changed_artists)
+    {
+        for changed_album in &artist.sorted_changed_albums {
+            let album = changed_album.album.read();
+            add_album_to_estimate(
+                configuration,
+                &mut estimate,
+                Some(&album),
+                &changed_album.changes,
+            )?;
+        }
+
+        for removed_album in &artist.sorted_removed_albums {
+            add_album_to_estimate(
+                configuration,
+                &mut estimate,
+                None,
+                &removed_album.changes,
+            )?;
+        }
+    }
+
+    Ok(estimate)
+}
+
+/// Find the free space on the volume of the aggregated library (or, if it doesn't
+/// exist yet, of its closest existing parent directory).
+pub fn available_space_for_aggregated_library(
+    configuration: &Configuration,
+) -> Option<u64> {
+    Path::new(&configuration.aggregated_library.path)
+        .ancestors()
+        .find(|path| path.is_dir())
+        .and_then(|existing_path| fs2::available_space(existing_path).ok())
+}
+
+/// Log the estimated output size of the pending changes along with the free space
+/// on the aggregated library's volume and return the estimate. As this is only an aid,
+/// failures are logged as warnings (and `None` is returned) instead of aborting the run.
+pub fn log_output_size_estimate(
+    configuration: &Arc<Configuration>,
+    terminal: &impl LogBackend,
+    libraries_with_changes: &[LibraryWithChanges],
+) -> Option<OutputSizeEstimate> {
+    let estimate =
+        match estimate_output_size(configuration, libraries_with_changes) {
+            Ok(estimate) => estimate,
+            Err(error) => {
+                terminal.log_println(format!(
+                    "{} Could not estimate the output size: {error}",
+                    "WARNING:".yellow()
+                ));
+                return None;
+            }
+        };
+
+    let net_growth_bytes = estimate.net_growth_bytes();
+    let net_growth = if net_growth_bytes < 0 {
+        format!(
+            "shrinks by ~{}",
+            format_byte_size(net_growth_bytes.unsigned_abs() as u64)
+        )
+    } else {
+        format!(
+            "grows by ~{}",
+            format_byte_size(net_growth_bytes as u64)
+        )
+    };
+
+    let available_space =
+        match available_space_for_aggregated_library(configuration) {
+            Some(available_space) => {
+                format!("{} free", format_byte_size(available_space))
+            }
+            None => String::from("free space unknown"),
+        };
+
+    terminal.log_println(format!(
+        "Estimated output: ~{} written, the aggregated library {} ({}).",
+        format_byte_size(estimate.bytes_written),
+        net_growth,
+        available_space
+    ));
+
+    Some(estimate)
+}
+
+/// Make sure the aggregated library's volume has enough free space for the estimated
+/// output plus `aggregated_library.free_space_margin_mib`, so a full disk doesn't surface
+/// as a long list of failed files halfway through the run. If there isn't enough space,
+/// the run is aborted, unless `ignore_free_space` is set.
+///
+/// During a dry run (or if the free space can't be determined), only a warning is printed.
+pub fn check_free_space(
+    configuration: &Configuration,
+    terminal: &impl LogBackend,
+    estimate: &OutputSizeEstimate,
+    options: &TranscodeOptions,
+) -> Result<()> {
+    // Runs that don't grow the aggregated library never need more space.
+    let net_growth_bytes = estimate.net_growth_bytes();
+    if net_growth_bytes <= 0 {
+        return Ok(());
+    }
+
+    let Some(available_space) =
+        available_space_for_aggregated_library(configuration)
+    else {
+        terminal.log_println(format!(
+            "{} Could not determine the free space on the aggregated library's volume, \
+            skipping the free space check.",
+            "Warning:".yellow()
+        ));
+        return Ok(());
+    };
+
+    let margin_bytes = configuration
+        .aggregated_library
+        .free_space_margin_mib
+        .saturating_mul(BYTES_PER_MIB);
+    let required_space = (net_growth_bytes as u64).saturating_add(margin_bytes);
+
+    if available_space >= required_space {
+        return Ok(());
+    }
+
+    let message = format!(
+        "Not enough free space on the aggregated library's volume: the aggregated library \
+        is estimated to grow by ~{} and aggregated_library.free_space_margin_mib requires \
+        another {} to remain free, but only {} is available.",
+        format_byte_size(net_growth_bytes as u64),
+        format_byte_size(margin_bytes),
+        format_byte_size(available_space)
+    );
+
+    if options.ignore_free_space || options.dry_run {
+        terminal.log_println(format!("{} {}", "Warning:".yellow(), message));
+        return Ok(());
+    }
+
+    Err(miette!(
+        "{} Free up some space or run the command again with --ignore-free-space.",
+        message
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parses_ffmpeg_bitrates() {
+        assert_eq!(parse_ffmpeg_bitrate("320k"), Some(320_000));
+        assert_eq!(parse_ffmpeg_bitrate("1M"), Some(1_000_000));
+        assert_eq!(parse_ffmpeg_bitrate("192000"), Some(192_000));
+        assert_eq!(parse_ffmpeg_bitrate("fast"), None);
+    }
+
+    #[test]
+    fn estimates_target_bitrate_from_ffmpeg_arguments() {
+        assert_eq!(
+            estimate_target_bitrate(&arguments(&[
+                "-i",
+                "{INPUT_FILE}",
+                "-codec:a",
+                "libmp3lame",
+                "-q:a",
+                "0",
+                "{OUTPUT_FILE}"
+            ])),
+            Some(245_000)
+        );
+        assert_eq!(
+            estimate_target_bitrate(&arguments(&[
+                "-i",
+                "{INPUT_FILE}",
+                "-b:a",
+                "64k",
+                "{OUTPUT_FILE}"
+            ])),
+            Some(64_000)
+        );
+        assert_eq!(
+            estimate_target_bitrate(&arguments(&[
+                "-i",
+                "{INPUT_FILE}",
+                "{OUTPUT_FILE}"
+            ])),
+            None
+        );
+    }
+}
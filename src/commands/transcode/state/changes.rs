@@ -1,6 +1,9 @@
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use euphony_configuration::aggregated_library::AggregationMode;
+use euphony_configuration::Configuration;
 use euphony_library::view::common::SortedFileMap;
 use euphony_library::view::SharedAlbumView;
 use miette::{miette, Context, Result};
@@ -11,7 +14,7 @@ use crate::commands::transcode::jobs::{
     CancellableTask,
     CopyFileJob,
     DeleteProcessedFileJob,
-    FileJobMessage,
+    FileProgressSink,
     IntoCancellableTask,
     TranscodeAudioFileJob,
 };
@@ -53,6 +56,23 @@ pub enum CopyProcessingReason {
 }
 
 
+impl From<TranscodeProcessingReason> for CopyProcessingReason {
+    fn from(reason: TranscodeProcessingReason) -> Self {
+        match reason {
+            TranscodeProcessingReason::AddedInSourceLibrary => {
+                CopyProcessingReason::AddedInSourceLibrary
+            }
+            TranscodeProcessingReason::ChangedInSourceLibrary => {
+                CopyProcessingReason::ChangedInSourceLibrary
+            }
+            TranscodeProcessingReason::MissingInTranscodedLibrary => {
+                CopyProcessingReason::MissingInTranscodedLibrary
+            }
+        }
+    }
+}
+
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Copy, Clone)]
 pub enum DeleteInTranscodedProcessingReason {
@@ -103,13 +123,22 @@ pub struct FileJobContext {
 }
 
 
+/// What every file job of an album is created with: the configuration (shared with
+/// the worker threads), the album and the closure that generates the job's queue item.
+pub struct AlbumJobContext<'a, 'view, F> {
+    pub configuration: &'a Arc<Configuration>,
+    pub album_view: &'a SharedAlbumView<'view>,
+    pub queue_item_id_generator: &'a F,
+}
+
+
 pub fn add_transcode_job<
+    S: FileProgressSink,
     F: Fn(FileJobContext) -> Result<QueueItemID>,
     P: Into<PathBuf>,
 >(
-    global_job_array: &mut Vec<CancellableTask<FileJobMessage>>,
-    album_view: &SharedAlbumView,
-    queue_item_id_generator: &F,
+    global_job_array: &mut Vec<CancellableTask<S>>,
+    context: &AlbumJobContext<F>,
     absolute_source_to_target_path_map: &SortedFileMap<PathBuf, PathBuf>,
     source_path: P,
     file_type: FileType,
@@ -117,6 +146,28 @@ pub fn add_transcode_job<
 ) -> Result<()> {
     let source_path = source_path.into();
 
+    let is_copied_as_is = context.configuration.aggregated_library.mode
+        == AggregationMode::Mirror
+        || context
+            .album_view
+            .read()
+            .library_configuration()
+            .transcoding
+            .is_path_copied_audio_file_by_extension(&source_path)?;
+
+    // When mirroring (or if the extension rule says so),
+    // audio files are copied as-is instead of being transcoded.
+    if is_copied_as_is {
+        return add_file_copy_job(
+            global_job_array,
+            context,
+            absolute_source_to_target_path_map,
+            source_path,
+            file_type,
+            transcode_reason.into(),
+        );
+    }
+
     let target_path = absolute_source_to_target_path_map
         .get(&source_path)
         .ok_or_else(|| {
@@ -126,7 +177,7 @@ pub fn add_transcode_job<
             )
         })?;
 
-    let queue_item_id = queue_item_id_generator(FileJobContext {
+    let queue_item_id = (context.queue_item_id_generator)(FileJobContext {
         file_type,
         action: FileProcessingAction::Transcode {
             source_path: source_path.clone(),
@@ -136,7 +187,8 @@ pub fn add_transcode_job<
     })?;
 
     let transcoding_job = TranscodeAudioFileJob::new(
-        album_view.clone(),
+        context.configuration,
+        context.album_view.clone(),
         source_path,
         target_path.to_path_buf(),
         queue_item_id,
@@ -149,12 +201,12 @@ pub fn add_transcode_job<
 }
 
 pub fn add_file_copy_job<
+    S: FileProgressSink,
     F: Fn(FileJobContext) -> Result<QueueItemID>,
     P: Into<PathBuf>,
 >(
-    global_job_array: &mut Vec<CancellableTask<FileJobMessage>>,
-    album_view: &SharedAlbumView,
-    queue_item_id_generator: &F,
+    global_job_array: &mut Vec<CancellableTask<S>>,
+    context: &AlbumJobContext<F>,
     absolute_source_to_target_path_map: &SortedFileMap<PathBuf, PathBuf>,
     source_path: P,
     file_type: FileType,
@@ -171,7 +223,7 @@ pub fn add_file_copy_job<
             )
         })?;
 
-    let queue_item_id = queue_item_id_generator(FileJobContext {
+    let queue_item_id = (context.queue_item_id_generator)(FileJobContext {
         file_type,
         action: FileProcessingAction::Copy {
             source_path: source_path.clone(),
@@ -181,9 +233,12 @@ pub fn add_file_copy_job<
     })?;
 
     let copy_job = CopyFileJob::new(
-        album_view.clone(),
+        context.configuration,
+        context.album_view.clone(),
         source_path,
         target_path.to_path_buf(),
+        &absolute_source_to_target_path_map.audio,
+        file_type,
         queue_item_id,
     )
     .wrap_err_with(|| miette!("Could not create CopyFileJob."))?;
@@ -194,19 +249,19 @@ pub fn add_file_copy_job<
 }
 
 pub fn add_aggregated_file_deletion_job<
+    S: FileProgressSink,
     F: Fn(FileJobContext) -> Result<QueueItemID>,
     P: Into<PathBuf>,
 >(
-    global_job_array: &mut Vec<CancellableTask<FileJobMessage>>,
-    album_view: &SharedAlbumView,
-    queue_item_id_generator: &F,
+    global_job_array: &mut Vec<CancellableTask<S>>,
+    context: &AlbumJobContext<F>,
     target_path: P,
     file_type: FileType,
     deletion_reason: DeleteInTranscodedProcessingReason,
 ) -> Result<()> {
     let target_path = target_path.into();
 
-    let queue_item_id = queue_item_id_generator(FileJobContext {
+    let queue_item_id = (context.queue_item_id_generator)(FileJobContext {
         file_type,
         action: FileProcessingAction::DeleteInTranscoded {
             target_path: target_path.clone(),
@@ -214,15 +269,17 @@ pub fn add_aggregated_file_deletion_job<
         },
     })?;
 
-    let transcoded_album_directory =
-        album_view.read().album_directory_in_transcoded_library();
+    let transcoded_album_directory = context
+        .album_view
+        .read()
+        .album_directory_in_transcoded_library();
 
     if !target_path.starts_with(transcoded_album_directory) {
         return Err(miette!("Suspicious file deletion job (doesn't match transcoded directory): {:?}", target_path));
     }
 
     let copy_job = DeleteProcessedFileJob::new(
-        album_view.read().euphony_configuration(),
+        context.configuration,
         target_path,
         file_type,
         true,
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use euphony_configuration::Configuration;
 use euphony_library::state::AlbumFileChangesV2;
 use miette::{miette, Result};
 
@@ -7,6 +9,7 @@ use super::changes::{
     add_aggregated_file_deletion_job,
     add_file_copy_job,
     add_transcode_job,
+    AlbumJobContext,
     CopyProcessingReason,
     DeleteInTranscodedProcessingReason,
     FileJobContext,
@@ -14,7 +17,7 @@ use super::changes::{
     TranscodeProcessingReason,
 };
 use crate::{
-    commands::transcode::jobs::{CancellableTask, FileJobMessage},
+    commands::transcode::jobs::{CancellableTask, FileProgressSink},
     console::frontends::shared::queue::QueueItemID,
 };
 
@@ -30,14 +33,19 @@ fn sort_pathbuf_iterator<'a, I: IntoIterator<Item = &'a PathBuf>>(
 
 
 pub trait GenerateChanges {
-    fn generate_file_jobs<F: Fn(FileJobContext) -> Result<QueueItemID>>(
+    fn generate_file_jobs<
+        S: FileProgressSink,
+        F: Fn(FileJobContext) -> Result<QueueItemID>,
+    >(
         &self,
+        configuration: &Arc<Configuration>,
         queue_item_id_generator: F,
-    ) -> Result<Vec<CancellableTask<FileJobMessage>>>;
+    ) -> Result<Vec<CancellableTask<S>>>;
 }
 
 impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
     /// This method will generate and return a list of cancellable tasks.
+    /// Each of them holds a clone of `configuration` (it is shared with the worker threads).
     ///
     /// The `queue_item_id_generator` parameter should be a closure that will take two parameters:
     /// - `FileType`, which is the type of the file (audio or data) and
@@ -45,13 +53,23 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
     ///
     /// The closure should return an `Ok(QueueItemID)`.
     /// If `Err` is returned, this method will exit early, propagating the error.
-    fn generate_file_jobs<F: Fn(FileJobContext) -> Result<QueueItemID>>(
+    fn generate_file_jobs<
+        S: FileProgressSink,
+        F: Fn(FileJobContext) -> Result<QueueItemID>,
+    >(
         &self,
+        configuration: &Arc<Configuration>,
         queue_item_id_generator: F,
-    ) -> Result<Vec<CancellableTask<FileJobMessage>>> {
-        let mut jobs: Vec<CancellableTask<FileJobMessage>> =
+    ) -> Result<Vec<CancellableTask<S>>> {
+        let mut jobs: Vec<CancellableTask<S>> =
             Vec::with_capacity(self.number_of_changed_files());
 
+        let context = AlbumJobContext {
+            configuration,
+            album_view: &self.album_view,
+            queue_item_id_generator: &queue_item_id_generator,
+        };
+
         let absolute_source_to_target_path_map =
             self.tracked_source_files.as_ref().map(|files| {
                 files.map_source_file_paths_to_transcoded_file_paths_absolute()
@@ -69,8 +87,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
 
             add_transcode_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 source_to_target_path_map,
                 path,
                 FileType::Audio,
@@ -89,8 +106,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
 
             add_transcode_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 source_to_target_path_map,
                 path,
                 FileType::Audio,
@@ -107,8 +123,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
 
             add_transcode_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 source_to_target_path_map,
                 path,
                 FileType::Audio,
@@ -129,8 +144,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
 
             add_file_copy_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 source_to_target_path_map,
                 path,
                 FileType::Data,
@@ -149,8 +163,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
 
             add_file_copy_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 source_to_target_path_map,
                 path,
                 FileType::Data,
@@ -167,8 +180,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
 
             add_file_copy_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 source_to_target_path_map,
                 path,
                 FileType::Data,
@@ -183,8 +195,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
         ) {
             add_aggregated_file_deletion_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 target_path,
                 FileType::Audio,
                 DeleteInTranscodedProcessingReason::RemovedFromSourceLibrary,
@@ -196,8 +207,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
         ) {
             add_aggregated_file_deletion_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 target_path,
                 FileType::Data,
                 DeleteInTranscodedProcessingReason::RemovedFromSourceLibrary,
@@ -208,8 +218,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
         for path in sort_pathbuf_iterator(&self.excess_in_transcoded.audio) {
             add_aggregated_file_deletion_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 path,
                 FileType::Audio,
                 DeleteInTranscodedProcessingReason::ExcessInTranscodedLibrary,
@@ -219,8 +228,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
         for path in sort_pathbuf_iterator(&self.excess_in_transcoded.data) {
             add_aggregated_file_deletion_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 path,
                 FileType::Data,
                 DeleteInTranscodedProcessingReason::ExcessInTranscodedLibrary,
@@ -230,8 +238,7 @@ impl<'view> GenerateChanges for AlbumFileChangesV2<'view> {
         for path in sort_pathbuf_iterator(&self.excess_in_transcoded.unknown) {
             add_aggregated_file_deletion_job(
                 &mut jobs,
-                &self.album_view,
-                &queue_item_id_generator,
+                &context,
                 path,
                 FileType::Unknown,
                 DeleteInTranscodedProcessingReason::ExcessInTranscodedLibrary,
@@ -0,0 +1,160 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+use serde::Serialize;
+
+const STATUS_FILE_SCHEMA_VERSION: u32 = 1;
+
+/// Minimum time between two (non-forced) writes of the status file.
+const STATUS_FILE_WRITE_INTERVAL: Duration = Duration::from_secs(2);
+
+
+/// Which phase the run is currently in.
+#[derive(Serialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunPhase {
+    Scanning,
+    Processing,
+    Finished,
+    Failed,
+}
+
+/// Progress of a single kind of item (libraries, albums or files).
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ItemProgress {
+    pub total: usize,
+
+    pub finished: usize,
+
+    /// `finished` as a percentage of `total` (100 if there is nothing to do).
+    pub percent: f64,
+}
+
+impl ItemProgress {
+    pub fn new(finished: usize, total: usize) -> Self {
+        let percent = if total == 0 {
+            100.0
+        } else {
+            (finished as f64 / total as f64 * 100.0).min(100.0)
+        };
+
+        Self {
+            total,
+            finished,
+            percent,
+        }
+    }
+}
+
+/// Progress of the files in more detail (`finished` includes the errored files).
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FileProgress {
+    #[serde(flatten)]
+    pub progress: ItemProgress,
+
+    pub finished_ok: usize,
+
+    pub errored: usize,
+
+    pub currently_processing: usize,
+}
+
+/// Contents of the status file (see `transcode --status-file`).
+#[derive(Serialize, Clone, Debug)]
+pub struct RunStatus {
+    pub schema_version: u32,
+
+    pub phase: RunPhase,
+
+    pub updated_at_unix_seconds: u64,
+
+    pub libraries: ItemProgress,
+
+    pub albums: ItemProgress,
+
+    pub files: FileProgress,
+}
+
+impl RunStatus {
+    pub fn new(
+        phase: RunPhase,
+        libraries: ItemProgress,
+        albums: ItemProgress,
+        files: FileProgress,
+    ) -> Self {
+        let updated_at_unix_seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            schema_version: STATUS_FILE_SCHEMA_VERSION,
+            phase,
+            updated_at_unix_seconds,
+            libraries,
+            albums,
+            files,
+        }
+    }
+}
+
+
+/// A small JSON file describing the progress of the current run,
+/// intended to be polled by other processes (e.g. a monitoring dashboard).
+///
+/// Writes are throttled (see `STATUS_FILE_WRITE_INTERVAL`) and atomic: the status is written
+/// into a temporary file next to the status file, which then replaces the status file,
+/// so readers never see a partially-written file.
+pub struct StatusFile {
+    file_path: PathBuf,
+
+    last_write: Option<Instant>,
+}
+
+impl StatusFile {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self {
+            file_path,
+            last_write: None,
+        }
+    }
+
+    /// Whether enough time has passed since the last write for a non-forced write.
+    pub fn is_write_due(&self) -> bool {
+        match self.last_write {
+            Some(last_write) => {
+                last_write.elapsed() >= STATUS_FILE_WRITE_INTERVAL
+            }
+            None => true,
+        }
+    }
+
+    /// Atomically overwrite the status file with the given status.
+    pub fn write(&mut self, status: &RunStatus) -> io::Result<()> {
+        let mut temporary_file_name: OsString = self
+            .file_path
+            .file_name()
+            .map(|name| name.to_os_string())
+            .unwrap_or_else(|| OsString::from("status"));
+        temporary_file_name.push(".tmp");
+        let temporary_file_path =
+            self.file_path.with_file_name(temporary_file_name);
+
+        let serialized_status = serde_json::to_string_pretty(status)?;
+
+        {
+            let mut temporary_file = File::create(&temporary_file_path)?;
+            temporary_file.write_all(serialized_status.as_bytes())?;
+            temporary_file.flush()?;
+        }
+
+        fs::rename(&temporary_file_path, &self.file_path)?;
+        self.last_write = Some(Instant::now());
+
+        Ok(())
+    }
+}
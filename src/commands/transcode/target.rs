@@ -0,0 +1,288 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Component, Path};
+
+use euphony_configuration::Configuration;
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use super::LibraryWithChanges;
+
+
+/// Which part of the collection a transcode run scans and processes
+/// (see the `transcode`, `transcode-library` and `transcode-album` commands
+/// and `transcode --library`).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum TranscodeTarget {
+    /// All albums in all libraries.
+    #[default]
+    EntireCollection,
+
+    /// All albums in a single library.
+    Library { library_name: String },
+
+    /// All albums in several libraries (see `TranscodeTarget::resolve_libraries`).
+    Libraries { library_names: Vec<String> },
+
+    /// A single album.
+    Album {
+        library_name: String,
+        artist_name: String,
+        album_title: String,
+    },
+}
+
+impl TranscodeTarget {
+    /// Resolve a library given either by its (full) name or by its directory path.
+    pub fn resolve_library(
+        configuration: &Configuration,
+        library_name_or_path: &str,
+    ) -> Result<Self> {
+        Ok(Self::Library {
+            library_name: resolve_library_name(
+                configuration,
+                library_name_or_path,
+            )?,
+        })
+    }
+
+    /// Resolve several libraries, each given either by its (full) name or by its directory path
+    /// (see `resolve_library`). No libraries means the entire collection.
+    pub fn resolve_libraries<S: AsRef<str>>(
+        configuration: &Configuration,
+        library_names_or_paths: &[S],
+    ) -> Result<Self> {
+        let mut library_names: Vec<String> =
+            Vec::with_capacity(library_names_or_paths.len());
+
+        for library_name_or_path in library_names_or_paths {
+            let library_name = resolve_library_name(
+                configuration,
+                library_name_or_path.as_ref(),
+            )?;
+
+            if !library_names.contains(&library_name) {
+                library_names.push(library_name);
+            }
+        }
+
+        Ok(match library_names.len() {
+            0 => Self::EntireCollection,
+            1 => Self::Library {
+                library_name: library_names.remove(0),
+            },
+            _ => Self::Libraries { library_names },
+        })
+    }
+
+    /// Resolve an album given by its source directory path,
+    /// which must be at `<library>/<artist>/<album>`.
+    pub fn resolve_album<P: AsRef<Path>>(
+        configuration: &Configuration,
+        album_directory_path: P,
+    ) -> Result<Self> {
+        let album_directory_path = album_directory_path.as_ref();
+        let album_directory_path = dunce::canonicalize(album_directory_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Album directory does not exist: {:?}",
+                    album_directory_path
+                )
+            })?;
+
+        if !album_directory_path.is_dir() {
+            return Err(miette!(
+                "Album path is not a directory: {:?}",
+                album_directory_path
+            ));
+        }
+
+        for library in configuration.libraries.values() {
+            let Ok(library_root) = dunce::canonicalize(&library.path) else {
+                continue;
+            };
+
+            let Ok(relative_album_path) =
+                album_directory_path.strip_prefix(&library_root)
+            else {
+                continue;
+            };
+
+            let mut components = relative_album_path.components();
+            let (
+                Some(Component::Normal(artist_name)),
+                Some(Component::Normal(album_title)),
+                None,
+            ) = (
+                components.next(),
+                components.next(),
+                components.next(),
+            )
+            else {
+                return Err(miette!(
+                    "Path {:?} is not an album directory: expected <library>/<artist>/<album>.",
+                    album_directory_path
+                ));
+            };
+
+            let artist_name = artist_name.to_string_lossy().to_string();
+            if library.is_directory_ignored_in_base_directory(&artist_name) {
+                return Err(miette!(
+                    "Album directory {:?} is inside an ignored directory.",
+                    album_directory_path
+                ));
+            }
+
+            return Ok(Self::Album {
+                library_name: library.name.clone(),
+                artist_name,
+                album_title: album_title.to_string_lossy().to_string(),
+            });
+        }
+
+        Err(miette!(
+            "Album directory {:?} is not inside any registered library.",
+            album_directory_path
+        ))
+    }
+
+    /// Whether this target covers the entire library with the given name.
+    pub fn includes_library(&self, library_name: &str) -> bool {
+        match self {
+            TranscodeTarget::EntireCollection => true,
+            TranscodeTarget::Library {
+                library_name: target_library_name,
+            }
+            | TranscodeTarget::Album {
+                library_name: target_library_name,
+                ..
+            } => target_library_name == library_name,
+            TranscodeTarget::Libraries { library_names } => {
+                library_names.iter().any(|name| name == library_name)
+            }
+        }
+    }
+
+    /// Whether this target covers the given album.
+    pub fn includes_album(
+        &self,
+        library_name: &str,
+        artist_name: &str,
+        album_title: &str,
+    ) -> bool {
+        match self {
+            TranscodeTarget::Album {
+                library_name: target_library_name,
+                artist_name: target_artist_name,
+                album_title: target_album_title,
+            } => {
+                target_library_name == library_name
+                    && target_artist_name == artist_name
+                    && target_album_title == album_title
+            }
+            _ => self.includes_library(library_name),
+        }
+    }
+}
+
+impl Display for TranscodeTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranscodeTarget::EntireCollection => write!(f, "entire collection"),
+            TranscodeTarget::Library { library_name } => {
+                write!(f, "library {library_name}")
+            }
+            TranscodeTarget::Libraries { library_names } => {
+                write!(f, "libraries {}", library_names.join(", "))
+            }
+            TranscodeTarget::Album {
+                artist_name,
+                album_title,
+                ..
+            } => write!(f, "album {artist_name} - {album_title}"),
+        }
+    }
+}
+
+
+/// Resolve the name of a library given either by its (full) name or by its directory path.
+fn resolve_library_name(
+    configuration: &Configuration,
+    library_name_or_path: &str,
+) -> Result<String> {
+    if let Some(library) =
+        configuration.get_library_by_full_name(library_name_or_path)
+    {
+        return Ok(library.name.clone());
+    }
+
+    if configuration.is_library(library_name_or_path) {
+        if let Some(library_name) =
+            configuration.get_library_name_from_path(library_name_or_path)
+        {
+            return Ok(library_name);
+        }
+    }
+
+    // The path may also be relative or non-canonical (e.g. have a trailing slash).
+    if let Ok(canonical_path) = dunce::canonicalize(library_name_or_path) {
+        for library in configuration.libraries.values() {
+            if dunce::canonicalize(&library.path)
+                .is_ok_and(|library_path| library_path == canonical_path)
+            {
+                return Ok(library.name.clone());
+            }
+        }
+    }
+
+    Err(miette!(
+        "\"{}\" is neither the name nor the path of a registered library \
+        (see the list-libraries command).",
+        library_name_or_path
+    ))
+}
+
+
+/// Keep only the changed albums that belong to the given target.
+///
+/// Only needed for `TranscodeTarget::Album`, as libraries outside the target are never scanned.
+/// Removed albums and artists of the target's library are dropped as well,
+/// as they are only processed when transcoding the entire library.
+pub fn retain_albums_in_target<'config>(
+    libraries_with_changes: Vec<LibraryWithChanges<'config>>,
+    target: &TranscodeTarget,
+) -> Vec<LibraryWithChanges<'config>> {
+    let TranscodeTarget::Album { .. } = target else {
+        return libraries_with_changes;
+    };
+
+    libraries_with_changes
+        .into_iter()
+        .filter_map(|mut library| {
+            let library_name = library.library_name.clone();
+
+            for artist in &mut library.sorted_changed_artists {
+                let artist_name = artist.artist_name.clone();
+
+                artist.sorted_changed_albums.retain(|changed_album| {
+                    target.includes_album(
+                        &library_name,
+                        &artist_name,
+                        &changed_album.album_title,
+                    )
+                });
+                artist.sorted_removed_albums.clear();
+            }
+
+            library
+                .sorted_changed_artists
+                .retain(|artist| !artist.sorted_changed_albums.is_empty());
+            library.fully_removed_artists.clear();
+
+            if library.sorted_changed_artists.is_empty() {
+                None
+            } else {
+                Some(library)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,312 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crossterm::style::Stylize;
+use euphony_configuration::library::LibraryConfiguration;
+use euphony_configuration::{Configuration, ALBUM_OVERRIDE_FILE_NAME};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tracing::debug;
+
+use super::events::TranscodeEventSink;
+use super::interrupt::{install_interrupt_handler, is_interrupted};
+use super::target::TranscodeTarget;
+use super::{transcode_collection, TranscodeOptions};
+use crate::console::frontends::TranscodeTerminal;
+use crate::console::{LogBackend, UserControlMessage};
+
+/// How long the libraries must be quiet (i.e. without new filesystem events)
+/// before the changed albums are processed. This coalesces bursts of events,
+/// e.g. editors writing temporary files or a tagger rewriting an entire album.
+const WATCH_DEBOUNCE_DURATION: Duration = Duration::from_secs(2);
+
+/// How often the exit keybind and interrupts are checked while waiting for events.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+
+/// A library that is being watched for changes.
+struct WatchedLibrary<'config> {
+    configuration: &'config LibraryConfiguration,
+
+    /// Canonical path to the library's root directory
+    /// (the paths in filesystem events are inside it).
+    root_directory: PathBuf,
+}
+
+/// Returns `true` if the file is one of the files euphony itself writes into
+/// the source libraries (album and library state files), so changes to them must not
+/// trigger another transcode. Album overrides are edited by the user and are not ignored.
+fn is_written_by_euphony(
+    configuration: &Configuration,
+    file_name: &str,
+) -> bool {
+    if file_name == ALBUM_OVERRIDE_FILE_NAME {
+        return false;
+    }
+
+    file_name.ends_with(".euphony")
+        || file_name == configuration.file_metadata.meta_file_name
+}
+
+/// Find out what needs to be transcoded again after `changed_path` has been changed,
+/// added or removed: the album the path belongs to or, if an album or artist directory
+/// itself has been removed (or added), the entire library (which also handles removals).
+///
+/// Returns `None` for paths that are ignored (see `ignored_directories_in_base_directory`
+/// and `ignored_names_at_any_depth`), that aren't inside an album directory
+/// or that are written by euphony itself.
+fn resolve_watch_target(
+    configuration: &Configuration,
+    libraries: &[WatchedLibrary],
+    changed_path: &Path,
+) -> Option<TranscodeTarget> {
+    let library = libraries
+        .iter()
+        .find(|library| changed_path.starts_with(&library.root_directory))?;
+    let library_configuration = library.configuration;
+
+    let relative_path =
+        changed_path.strip_prefix(&library.root_directory).ok()?;
+    if library_configuration.is_path_ignored_at_any_depth(relative_path) {
+        return None;
+    }
+
+    let file_name = relative_path.file_name()?.to_string_lossy();
+    if is_written_by_euphony(configuration, &file_name) {
+        return None;
+    }
+
+    let components = relative_path
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Option<Vec<String>>>()?;
+
+    let artist_name = components.first()?;
+    if library_configuration.is_directory_ignored_in_base_directory(artist_name)
+    {
+        return None;
+    }
+
+    let library_target = TranscodeTarget::Library {
+        library_name: library_configuration.name.clone(),
+    };
+
+    // Files in the library and artist directories are never transcoded, but added, removed
+    // or renamed artist and album directories affect the entire library, as only transcoding
+    // the entire library updates its state (i.e. the list of albums to detect removals with).
+    if components.len() <= 2 {
+        return (!changed_path.is_file()).then_some(library_target);
+    }
+
+    let album_title = &components[1];
+    let album_directory_path =
+        library.root_directory.join(artist_name).join(album_title);
+
+    if !album_directory_path.is_dir() {
+        return Some(library_target);
+    }
+
+    Some(TranscodeTarget::Album {
+        library_name: library_configuration.name.clone(),
+        artist_name: artist_name.clone(),
+        album_title: album_title.clone(),
+    })
+}
+
+/// Add `target` to the pending targets, unless it is already covered by one of them
+/// (an album is covered by a pending transcode of its entire library).
+fn add_pending_target(
+    pending_targets: &mut Vec<TranscodeTarget>,
+    target: TranscodeTarget,
+) {
+    if pending_targets.contains(&target) {
+        return;
+    }
+
+    if let TranscodeTarget::Library { library_name } = &target {
+        pending_targets.retain(|pending_target| {
+            !matches!(
+                pending_target,
+                TranscodeTarget::Album { library_name: album_library_name, .. }
+                    if album_library_name == library_name
+            )
+        });
+    } else if let TranscodeTarget::Album { library_name, .. } = &target {
+        let is_library_pending = pending_targets.iter().any(|pending_target| {
+            matches!(
+                pending_target,
+                TranscodeTarget::Library { library_name: pending_library_name }
+                    if pending_library_name == library_name
+            )
+        });
+
+        if is_library_pending {
+            return;
+        }
+    }
+
+    pending_targets.push(target);
+}
+
+/// Transcode all libraries (or, if `library_names_or_paths` is not empty, only the given ones)
+/// and keep watching them for changes afterwards (`transcode --watch`).
+///
+/// Whenever files change, are added or removed, only the affected albums are scanned
+/// and transcoded again, once the libraries have been quiet for `WATCH_DEBOUNCE_DURATION`.
+/// Watching stops when the user exits the terminal UI or on Ctrl-C.
+pub fn cmd_transcode_watch<'config: 'scope, 'scope>(
+    configuration: &'config Arc<Configuration>,
+    terminal: &TranscodeTerminal<'config, 'scope>,
+    library_names_or_paths: &[String],
+    options: TranscodeOptions,
+) -> Result<()> {
+    let target = TranscodeTarget::resolve_libraries(
+        configuration,
+        library_names_or_paths,
+    )?;
+
+    terminal.log_println(
+        format!(
+            "Command: transcode {target} and watch for changes (skip unchanged)."
+        )
+        .cyan()
+        .bold(),
+    );
+
+    // The receiver must exist before the initial transcode, so exiting the terminal UI
+    // during it stops the watch as well.
+    let mut terminal_user_input = terminal.user_control_receiver()?;
+    install_interrupt_handler()?;
+
+    transcode_collection(configuration, terminal, options.clone(), &target)?;
+
+    let libraries = configuration
+        .libraries
+        .values()
+        .filter(|library| {
+            library.transcoding.enabled && target.includes_library(&library.name)
+        })
+        .map(|library| {
+            let root_directory = dunce::canonicalize(&library.path)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!("Failed to canonicalize {:?}", library.path)
+                })?;
+
+            Ok(WatchedLibrary {
+                configuration: library,
+                root_directory,
+            })
+        })
+        .collect::<Result<Vec<WatchedLibrary>>>()?;
+
+    let (event_sender, event_receiver) = mpsc::channel();
+    let mut watcher: RecommendedWatcher =
+        notify::recommended_watcher(move |event| {
+            // The receiver is only dropped once watching has stopped.
+            let _ = event_sender.send(event);
+        })
+        .into_diagnostic()
+        .wrap_err_with(|| miette!("Could not set up the filesystem watcher."))?;
+
+    for library in &libraries {
+        watcher
+            .watch(&library.root_directory, RecursiveMode::Recursive)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not watch library {:?}.",
+                    library.root_directory
+                )
+            })?;
+    }
+
+    terminal.log_println(
+        format!(
+            "Watching {} libraries for changes (press Ctrl-C to stop).",
+            libraries.len()
+        )
+        .bold(),
+    );
+
+    let mut pending_targets: Vec<TranscodeTarget> = Vec::new();
+    let mut time_last_event = Instant::now();
+
+    loop {
+        if let Ok(UserControlMessage::Exit) = terminal_user_input.try_recv() {
+            terminal.log_println("User wants to exit, no longer watching.");
+            return Ok(());
+        }
+
+        if is_interrupted() {
+            terminal.log_println("Interrupted, no longer watching.");
+            return Ok(());
+        }
+
+        match event_receiver.recv_timeout(WATCH_POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                for changed_path in &event.paths {
+                    let Some(target) = resolve_watch_target(
+                        configuration,
+                        &libraries,
+                        changed_path,
+                    ) else {
+                        continue;
+                    };
+
+                    debug!(path = ?changed_path, %target, "Detected change.");
+
+                    add_pending_target(&mut pending_targets, target);
+                    time_last_event = Instant::now();
+                }
+            }
+            Ok(Err(error)) => {
+                terminal.log_println(format!(
+                    "{} Filesystem watcher error: {error}",
+                    "WARNING:".yellow()
+                ));
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(miette!(
+                    "Filesystem watcher channel disconnected unexpectedly."
+                ));
+            }
+        }
+
+        if pending_targets.is_empty()
+            || time_last_event.elapsed() < WATCH_DEBOUNCE_DURATION
+        {
+            continue;
+        }
+
+        for target in pending_targets.drain(..) {
+            if is_interrupted() {
+                break;
+            }
+
+            terminal.log_println(
+                format!("Detected changes, transcoding {target}.")
+                    .cyan()
+                    .bold(),
+            );
+
+            // A failed incremental transcode must not stop the watch.
+            if let Err(error) = transcode_collection(
+                configuration,
+                terminal,
+                options.clone(),
+                &target,
+            ) {
+                terminal.log_println(format!(
+                    "{} Failed to transcode {target}: {error:?}",
+                    "ERROR:".red()
+                ));
+            }
+        }
+    }
+}
@@ -1,18 +1,71 @@
 use std::collections::{HashMap, HashSet};
-use std::hash::{Hash, Hasher};
-use std::path::{Path, PathBuf};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
 
 use crossterm::style::Stylize;
+use euphony_configuration::aggregated_library::AggregationMode;
 use euphony_configuration::library::LibraryConfiguration;
-use euphony_configuration::{Configuration, ALBUM_OVERRIDE_FILE_NAME};
-use euphony_library::state::source::SOURCE_ALBUM_STATE_FILE_NAME;
-use euphony_library::view::LibraryView;
-use miette::{miette, Context, Result};
-
-use crate::commands::transcode::library_state::LIBRARY_STATE_FILE_NAME;
+use euphony_configuration::{
+    Configuration,
+    ALBUM_OVERRIDE_FILE_NAME,
+    UNPORTABLE_FILE_NAME_CHARACTERS,
+};
+use euphony_library::state::source::{
+    SourceAlbumState,
+    MIRROR_SOURCE_ALBUM_STATE_FILE_NAME,
+    SOURCE_ALBUM_STATE_FILE_NAME,
+};
+use euphony_library::view::{
+    AlbumSourceFileList,
+    AlbumView,
+    ArtistView,
+    LibraryView,
+    SharedAlbumView,
+};
+use miette::{miette, Context, IntoDiagnostic, Result};
+
+use self::report::{
+    ValidationFinding,
+    ValidationJsonReport,
+    ValidationReport,
+    ValidationViolation,
+    ValidationViolationCategory,
+};
+use crate::commands::transcode::library_state::{
+    LibraryState,
+    MIRROR_LIBRARY_STATE_FILE_NAME,
+};
 use crate::console::frontends::ValidationTerminal;
 use crate::console::{LogBackend, ValidationBackend, ValidationErrorInfo};
 
+pub mod report;
+
+
+/// Options that influence the behaviour of the `validate` command.
+#[derive(Clone, Default)]
+pub struct ValidateOptions {
+    /// If set, findings that are already present in this validation report are not displayed
+    /// (see `validate --baseline`).
+    pub baseline_file_path: Option<PathBuf>,
+
+    /// If set, all current findings are saved into a validation report at this path
+    /// (see `validate --report`).
+    pub report_file_path: Option<PathBuf>,
+
+    /// If set, the displayed unexpected files (i.e. files that are not allowed in their library)
+    /// are deleted or moved into `validation.quarantine_directory` (see `validate --fix`).
+    pub fix_unexpected_files: bool,
+
+    /// If set, `fix_unexpected_files` doesn't ask for confirmation first (see `validate --yes`).
+    pub skip_fix_confirmation: bool,
+
+    /// If set, the displayed validation errors are printed as a JSON validation report
+    /// instead of human-readable text (see `validate --format json`).
+    /// Other output (e.g. the command header) is omitted, so only the report is printed.
+    pub output_json: bool,
+}
+
 /// Implemented by concrete validation errors to allow a standardised way of displaying the error.
 pub trait ValidationErrorDisplay {
     /// This method should format and return the complete string that
@@ -23,7 +76,11 @@ pub trait ValidationErrorDisplay {
 /// Describes all possible validation errors.
 pub enum ValidationError<'a> {
     UnexpectedFile(UnexpectedFile<'a>),
+    UnexpectedAlbumFiles(UnexpectedAlbumFiles<'a>),
     AlbumCollision(AlbumCollision<'a>),
+    SuspiciousAlbumDirectory(SuspiciousAlbumDirectory<'a>),
+    CaseInsensitiveCollision(CaseInsensitiveCollision<'a>),
+    UnportableFileName(UnportableFileName<'a>),
 }
 
 impl<'a> ValidationError<'a> {
@@ -36,6 +93,19 @@ impl<'a> ValidationError<'a> {
         Self::UnexpectedFile(UnexpectedFile::new(file_path, library, reason))
     }
 
+    /// Initialize a new validation error: unexpected files in an album directory.
+    pub fn new_unexpected_album_files<P: Into<PathBuf>>(
+        album_directory_path: P,
+        library: &'a LibraryConfiguration,
+        unexpected_files: Vec<(PathBuf, UnexpectedFileLocation)>,
+    ) -> Self {
+        Self::UnexpectedAlbumFiles(UnexpectedAlbumFiles::new(
+            album_directory_path,
+            library,
+            unexpected_files,
+        ))
+    }
+
     /// Initialize a new validation error: an album collision.
     #[allow(dead_code)]
     pub fn new_album_collision(
@@ -46,15 +116,229 @@ impl<'a> ValidationError<'a> {
         )?))
     }
 
+    /// Initialize a new validation error: a suspiciously deep or large album directory.
+    pub fn new_suspicious_album_directory<P: Into<PathBuf>>(
+        album_directory_path: P,
+        library: &'a LibraryConfiguration,
+        deepest_file_depth: usize,
+        num_audio_files: usize,
+    ) -> Self {
+        Self::SuspiciousAlbumDirectory(SuspiciousAlbumDirectory::new(
+            album_directory_path,
+            library,
+            deepest_file_depth,
+            num_audio_files,
+        ))
+    }
+
+    /// Initialize a new validation error: album files whose output names only differ in case.
+    pub fn new_case_insensitive_collision<P: Into<PathBuf>>(
+        album_directory_path: P,
+        library: &'a LibraryConfiguration,
+        colliding_file_groups: Vec<Vec<PathBuf>>,
+    ) -> Self {
+        Self::CaseInsensitiveCollision(CaseInsensitiveCollision::new(
+            album_directory_path,
+            library,
+            colliding_file_groups,
+        ))
+    }
+
+    /// Initialize a new validation error: a name that can't be used on FAT32, exFAT or NTFS.
+    pub fn new_unportable_file_name<P: Into<PathBuf>>(
+        path: P,
+        library: &'a LibraryConfiguration,
+        reason: &'static str,
+    ) -> Self {
+        Self::UnportableFileName(UnportableFileName::new(path, library, reason))
+    }
+
+    /// Return the stable `ValidationFinding` identifiers of this validation error.
+    /// Most errors have a single finding, but grouped errors (e.g. `UnexpectedAlbumFiles`)
+    /// have one for each of their files.
+    pub fn findings(&self) -> Result<Vec<ValidationFinding>> {
+        match self {
+            ValidationError::UnexpectedFile(unexpected_file) => {
+                Ok(vec![unexpected_file.finding()?])
+            }
+            ValidationError::UnexpectedAlbumFiles(unexpected_album_files) => {
+                unexpected_album_files.findings()
+            }
+            ValidationError::AlbumCollision(album_collision) => {
+                Ok(vec![album_collision.finding()])
+            }
+            ValidationError::SuspiciousAlbumDirectory(suspicious_album) => {
+                Ok(vec![suspicious_album.finding()?])
+            }
+            ValidationError::CaseInsensitiveCollision(collision) => {
+                Ok(vec![collision.finding()?])
+            }
+            ValidationError::UnportableFileName(unportable_name) => {
+                Ok(vec![unportable_name.finding()?])
+            }
+        }
+    }
+
+    /// Return the violations of this validation error for the JSON validation report
+    /// (see `validate --format json`), each with the name of the library it is in.
+    pub fn violations(&self) -> Result<Vec<(String, ValidationViolation)>> {
+        match self {
+            ValidationError::UnexpectedFile(unexpected_file) => {
+                Ok(vec![(
+                    unexpected_file.library.name.clone(),
+                    ValidationViolation::new(
+                        ValidationViolationCategory::ForbiddenExtension,
+                        unexpected_file.relative_file_path()?.to_string_lossy(),
+                        unexpected_file.location.description(),
+                    ),
+                )])
+            }
+            ValidationError::UnexpectedAlbumFiles(unexpected_album_files) => {
+                unexpected_album_files
+                    .unexpected_files
+                    .iter()
+                    .map(|(file_path, location)| {
+                        Ok((
+                            unexpected_album_files.library.name.clone(),
+                            ValidationViolation::new(
+                                ValidationViolationCategory::ForbiddenExtension,
+                                unexpected_album_files
+                                    .relative_path(file_path)?
+                                    .to_string_lossy(),
+                                location.description(),
+                            ),
+                        ))
+                    })
+                    .collect()
+            }
+            ValidationError::AlbumCollision(album_collision) => {
+                // The collision is reported in each of the colliding libraries.
+                let colliding_library_names =
+                    album_collision.colliding_library_names();
+                let message = format!(
+                    "Inter-library album collision (colliding libraries: {}).",
+                    colliding_library_names.join(", ")
+                );
+                let album_path = Path::new(&album_collision.artist_name())
+                    .join(album_collision.album_title())
+                    .to_string_lossy()
+                    .to_string();
+
+                Ok(colliding_library_names
+                    .into_iter()
+                    .map(|library_name| {
+                        (
+                            library_name,
+                            ValidationViolation::new(
+                                ValidationViolationCategory::Collision,
+                                &album_path,
+                                &message,
+                            ),
+                        )
+                    })
+                    .collect())
+            }
+            ValidationError::SuspiciousAlbumDirectory(suspicious_album) => {
+                Ok(vec![(
+                    suspicious_album.library.name.clone(),
+                    ValidationViolation::new(
+                        ValidationViolationCategory::SuspiciousAlbumDirectory,
+                        suspicious_album.relative_album_path()?.to_string_lossy(),
+                        format!(
+                            "Suspicious album directory (deepest file depth: {}, audio files: {}).",
+                            suspicious_album.deepest_file_depth,
+                            suspicious_album.num_audio_files,
+                        ),
+                    ),
+                )])
+            }
+            ValidationError::CaseInsensitiveCollision(collision) => {
+                let colliding_files = collision
+                    .colliding_file_groups
+                    .iter()
+                    .map(|colliding_file_group| {
+                        colliding_file_group
+                            .iter()
+                            .map(|file_path| file_path.to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    })
+                    .collect::<Vec<_>>()
+                    .join("; ");
+
+                Ok(vec![(
+                    collision.library.name.clone(),
+                    ValidationViolation::new(
+                        ValidationViolationCategory::CaseCollision,
+                        collision.relative_album_path()?.to_string_lossy(),
+                        format!(
+                            "Files whose names only differ in case: {}.",
+                            colliding_files
+                        ),
+                    ),
+                )])
+            }
+            ValidationError::UnportableFileName(unportable_name) => {
+                Ok(vec![(
+                    unportable_name.library.name.clone(),
+                    ValidationViolation::new(
+                        ValidationViolationCategory::UnportableName,
+                        unportable_name.relative_path()?.to_string_lossy(),
+                        format!(
+                            "Name can't be used on FAT32, exFAT or NTFS filesystems: it {}.",
+                            unportable_name.reason
+                        ),
+                    ),
+                )])
+            }
+        }
+    }
+
+    /// Return the files `validate --fix` would remove to resolve this validation error
+    /// (only unexpected files can be fixed this way).
+    pub fn fixable_files(&self) -> Vec<FixableFile<'a>> {
+        match self {
+            ValidationError::UnexpectedFile(unexpected_file) => {
+                vec![FixableFile {
+                    file_path: unexpected_file.file_path.clone(),
+                    library: unexpected_file.library,
+                }]
+            }
+            ValidationError::UnexpectedAlbumFiles(unexpected_album_files) => {
+                unexpected_album_files
+                    .unexpected_files
+                    .iter()
+                    .map(|(file_path, _)| FixableFile {
+                        file_path: file_path.clone(),
+                        library: unexpected_album_files.library,
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
     /// Consume the enum instance and return the `ValidationErrorInfo` that its variant returns.
     pub fn into_validation_error_info(self) -> Result<ValidationErrorInfo> {
         match self {
             ValidationError::UnexpectedFile(unexpected_file) => {
                 unexpected_file.get_error_info()
             }
+            ValidationError::UnexpectedAlbumFiles(unexpected_album_files) => {
+                unexpected_album_files.get_error_info()
+            }
             ValidationError::AlbumCollision(album_collision) => {
                 album_collision.get_error_info()
             }
+            ValidationError::SuspiciousAlbumDirectory(suspicious_album) => {
+                suspicious_album.get_error_info()
+            }
+            ValidationError::CaseInsensitiveCollision(collision) => {
+                collision.get_error_info()
+            }
+            ValidationError::UnportableFileName(unportable_name) => {
+                unportable_name.get_error_info()
+            }
         }
     }
 }
@@ -67,6 +351,44 @@ pub enum UnexpectedFileLocation {
     AlbumDirectoryOther,
 }
 
+impl UnexpectedFileLocation {
+    /// Human-readable description of an unexpected file in this location.
+    fn description(&self) -> &'static str {
+        match self {
+            UnexpectedFileLocation::LibraryRoot => {
+                "Unexpected file in library root."
+            }
+            UnexpectedFileLocation::ArtistDirectory => {
+                "Unexpected file in artist directory."
+            }
+            UnexpectedFileLocation::AlbumDirectoryAudio => {
+                "Unexpected audio file in album directory."
+            }
+            UnexpectedFileLocation::AlbumDirectoryOther => {
+                "Unexpected data file in album directory."
+            }
+        }
+    }
+
+    /// Category of the `ValidationFinding`s of unexpected files in this location.
+    fn finding_category(&self) -> &'static str {
+        match self {
+            UnexpectedFileLocation::LibraryRoot => {
+                "unexpected-file-in-library-root"
+            }
+            UnexpectedFileLocation::ArtistDirectory => {
+                "unexpected-file-in-artist-directory"
+            }
+            UnexpectedFileLocation::AlbumDirectoryAudio => {
+                "unexpected-audio-file-in-album-directory"
+            }
+            UnexpectedFileLocation::AlbumDirectoryOther => {
+                "unexpected-data-file-in-album-directory"
+            }
+        }
+    }
+}
+
 /// This validation error happens when the contents of a library do not match what is configured
 /// in the library configuration table in `configuration.toml`.
 pub struct UnexpectedFile<'a> {
@@ -92,6 +414,21 @@ impl<'a> UnexpectedFile<'a> {
             location: reason,
         }
     }
+
+    /// Returns the unexpected file's path, relative to the library root.
+    fn relative_file_path(&self) -> Result<PathBuf> {
+        pathdiff::diff_paths(&self.file_path, &self.library.path).ok_or_else(
+            || miette!("Could not make file path relative to library base!"),
+        )
+    }
+
+    pub fn finding(&self) -> Result<ValidationFinding> {
+        Ok(ValidationFinding::new(
+            self.location.finding_category(),
+            &self.library.name,
+            self.relative_file_path()?.to_string_lossy(),
+        ))
+    }
 }
 
 impl<'a> ValidationErrorDisplay for UnexpectedFile<'a> {
@@ -109,11 +446,7 @@ impl<'a> ValidationErrorDisplay for UnexpectedFile<'a> {
 
         // TODO Render a shortened file tree as in the example above.
 
-        let relative_file_path =
-            pathdiff::diff_paths(&self.file_path, &self.library.path)
-                .ok_or_else(|| {
-                    miette!("Could not make file path relative to library base!")
-                })?;
+        let relative_file_path = self.relative_file_path()?;
 
         let attributes = vec![
             ("Library".to_string(), self.library.name.clone()),
@@ -124,20 +457,371 @@ impl<'a> ValidationErrorDisplay for UnexpectedFile<'a> {
         ];
 
         Ok(ValidationErrorInfo::new(
-            match self.location {
-                UnexpectedFileLocation::LibraryRoot => {
-                    "Unexpected file in library root."
-                }
-                UnexpectedFileLocation::ArtistDirectory => {
-                    "Unexpected file in artist directory."
-                }
-                UnexpectedFileLocation::AlbumDirectoryAudio => {
-                    "Unexpected audio file in album directory."
-                }
-                UnexpectedFileLocation::AlbumDirectoryOther => {
-                    "Unexpected data file in album directory."
+            self.location.description(),
+            attributes,
+        ))
+    }
+}
+
+
+/// This validation error happens when an album directory contains files that are not allowed
+/// in its library (see `allowed_audio_file_extensions`, `allowed_other_file_extensions`
+/// and `allowed_other_files_by_name` in the library's validation configuration table).
+///
+/// Unlike `UnexpectedFile`, all the unexpected files of an album are grouped into a single error.
+pub struct UnexpectedAlbumFiles<'a> {
+    /// Album directory path.
+    album_directory_path: PathBuf,
+
+    /// What library the album is part of.
+    library: &'a LibraryConfiguration,
+
+    /// Unexpected file paths (absolute) and why they are unexpected
+    /// (either `AlbumDirectoryAudio` or `AlbumDirectoryOther`).
+    unexpected_files: Vec<(PathBuf, UnexpectedFileLocation)>,
+}
+
+impl<'a> UnexpectedAlbumFiles<'a> {
+    pub fn new<P: Into<PathBuf>>(
+        album_directory_path: P,
+        library: &'a LibraryConfiguration,
+        unexpected_files: Vec<(PathBuf, UnexpectedFileLocation)>,
+    ) -> Self {
+        Self {
+            album_directory_path: album_directory_path.into(),
+            library,
+            unexpected_files,
+        }
+    }
+
+    /// Returns the path, relative to the library root.
+    fn relative_path(&self, path: &Path) -> Result<PathBuf> {
+        pathdiff::diff_paths(path, &self.library.path).ok_or_else(|| {
+            miette!("Could not make path relative to library base!")
+        })
+    }
+
+    /// Returns a finding for each unexpected file (the same ones `UnexpectedFile` would return,
+    /// so baselines are unaffected by the grouping).
+    pub fn findings(&self) -> Result<Vec<ValidationFinding>> {
+        self.unexpected_files
+            .iter()
+            .map(|(file_path, location)| {
+                Ok(ValidationFinding::new(
+                    location.finding_category(),
+                    &self.library.name,
+                    self.relative_path(file_path)?.to_string_lossy(),
+                ))
+            })
+            .collect()
+    }
+}
+
+impl<'a> ValidationErrorDisplay for UnexpectedAlbumFiles<'a> {
+    fn get_error_info(&self) -> Result<ValidationErrorInfo> {
+        // (UnexpectedAlbumFiles validation error display example)
+        //
+        // # Unexpected files in album directory.
+        //
+        // Library: Standard
+        // Album: Aindulmedir/The Lunar Lexicon
+        // Audio file: 01 Aindulmedir - Wind-Bitten.wav
+        // Data file: Thumbs.db
+
+        let mut attributes = vec![
+            ("Library".to_string(), self.library.name.clone()),
+            (
+                "Album".to_string(),
+                self.relative_path(&self.album_directory_path)?
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+        ];
+
+        for (file_path, location) in &self.unexpected_files {
+            let attribute_name = match location {
+                UnexpectedFileLocation::AlbumDirectoryAudio => "Audio file",
+                _ => "Data file",
+            };
+
+            let relative_file_path =
+                pathdiff::diff_paths(file_path, &self.album_directory_path)
+                    .ok_or_else(|| {
+                        miette!("Could not make file path relative to album!")
+                    })?;
+
+            attributes.push((
+                attribute_name.to_string(),
+                relative_file_path.to_string_lossy().to_string(),
+            ));
+        }
+
+        Ok(ValidationErrorInfo::new(
+            "Unexpected files in album directory.",
+            attributes,
+        ))
+    }
+}
+
+
+/// This validation error happens when an album directory contains files that are nested
+/// too deeply or an improbably large number of audio files (see `maximum_album_file_depth` and
+/// `maximum_album_audio_files` in the validation configuration table).
+///
+/// This usually means that a wrong directory is being treated as an album
+/// (e.g. because the album scan depth has been increased).
+pub struct SuspiciousAlbumDirectory<'a> {
+    /// Album directory path.
+    album_directory_path: PathBuf,
+
+    /// What library the album is part of.
+    library: &'a LibraryConfiguration,
+
+    /// Depth of the most deeply nested file in the album directory
+    /// (zero means the file is directly in the album directory).
+    deepest_file_depth: usize,
+
+    /// Number of audio files in the album directory.
+    num_audio_files: usize,
+}
+
+impl<'a> SuspiciousAlbumDirectory<'a> {
+    pub fn new<P: Into<PathBuf>>(
+        album_directory_path: P,
+        library: &'a LibraryConfiguration,
+        deepest_file_depth: usize,
+        num_audio_files: usize,
+    ) -> Self {
+        Self {
+            album_directory_path: album_directory_path.into(),
+            library,
+            deepest_file_depth,
+            num_audio_files,
+        }
+    }
+
+    /// Returns the album directory path, relative to the library root.
+    fn relative_album_path(&self) -> Result<PathBuf> {
+        pathdiff::diff_paths(&self.album_directory_path, &self.library.path)
+            .ok_or_else(|| {
+                miette!("Could not make album path relative to library base!")
+            })
+    }
+
+    pub fn finding(&self) -> Result<ValidationFinding> {
+        Ok(ValidationFinding::new(
+            "suspicious-album-directory",
+            &self.library.name,
+            self.relative_album_path()?.to_string_lossy(),
+        ))
+    }
+}
+
+impl<'a> ValidationErrorDisplay for SuspiciousAlbumDirectory<'a> {
+    fn get_error_info(&self) -> Result<ValidationErrorInfo> {
+        // (SuspiciousAlbumDirectory validation error display example)
+        //
+        // # Suspicious album directory (is this really an album?)
+        //
+        // Library: Standard
+        // Album: Aindulmedir/The Lunar Lexicon
+        // Deepest file depth: 4
+        // Audio files: 311
+
+        let relative_album_path = self.relative_album_path()?;
+
+        let attributes = vec![
+            ("Library".to_string(), self.library.name.clone()),
+            (
+                "Album".to_string(),
+                relative_album_path.to_string_lossy().to_string(),
+            ),
+            (
+                "Deepest file depth".to_string(),
+                self.deepest_file_depth.to_string(),
+            ),
+            (
+                "Audio files".to_string(),
+                self.num_audio_files.to_string(),
+            ),
+        ];
+
+        Ok(ValidationErrorInfo::new(
+            "Suspicious album directory (is this really an album?)",
+            attributes,
+        ))
+    }
+}
+
+
+/// This validation error happens when an album has tracked files whose output paths
+/// (in the aggregated library) only differ in case, e.g. `Song.mp3` and `song.mp3`
+/// (see `check_case_insensitive_collisions` in the validation configuration table).
+///
+/// These files would overwrite each other on a case-insensitive filesystem (e.g. FAT32 or exFAT).
+pub struct CaseInsensitiveCollision<'a> {
+    /// Album directory path.
+    album_directory_path: PathBuf,
+
+    /// What library the album is part of.
+    library: &'a LibraryConfiguration,
+
+    /// Groups of colliding files.
+    /// Paths are relative to the album directory.
+    colliding_file_groups: Vec<Vec<PathBuf>>,
+}
+
+impl<'a> CaseInsensitiveCollision<'a> {
+    pub fn new<P: Into<PathBuf>>(
+        album_directory_path: P,
+        library: &'a LibraryConfiguration,
+        colliding_file_groups: Vec<Vec<PathBuf>>,
+    ) -> Self {
+        Self {
+            album_directory_path: album_directory_path.into(),
+            library,
+            colliding_file_groups,
+        }
+    }
+
+    /// Returns the album directory path, relative to the library root.
+    fn relative_album_path(&self) -> Result<PathBuf> {
+        pathdiff::diff_paths(&self.album_directory_path, &self.library.path)
+            .ok_or_else(|| {
+                miette!("Could not make album path relative to library base!")
+            })
+    }
+
+    pub fn finding(&self) -> Result<ValidationFinding> {
+        Ok(ValidationFinding::new(
+            "case-insensitive-file-name-collision",
+            &self.library.name,
+            self.relative_album_path()?.to_string_lossy(),
+        ))
+    }
+}
+
+impl<'a> ValidationErrorDisplay for CaseInsensitiveCollision<'a> {
+    fn get_error_info(&self) -> Result<ValidationErrorInfo> {
+        // (CaseInsensitiveCollision validation error display example)
+        //
+        // # Files whose names only differ in case (they collide on case-insensitive filesystems).
+        //
+        // Library: Standard
+        // Album: Aindulmedir/The Lunar Lexicon
+        // Colliding files (in CD 1): 01 Wind-Bitten.flac, 01 wind-bitten.flac
+
+        let relative_album_path = self.relative_album_path()?;
+
+        let mut attributes = vec![
+            ("Library".to_string(), self.library.name.clone()),
+            (
+                "Album".to_string(),
+                relative_album_path.to_string_lossy().to_string(),
+            ),
+        ];
+
+        for colliding_files in &self.colliding_file_groups {
+            let directory = colliding_files
+                .first()
+                .and_then(|file_path| file_path.parent())
+                .filter(|directory| !directory.as_os_str().is_empty());
+
+            let attribute_name = match directory {
+                Some(directory) => {
+                    format!(
+                        "Colliding files (in {})",
+                        directory.to_string_lossy()
+                    )
                 }
-            },
+                None => "Colliding files".to_string(),
+            };
+
+            let file_names = colliding_files
+                .iter()
+                .map(|file_path| {
+                    file_path.file_name().unwrap_or_default().to_string_lossy()
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            attributes.push((attribute_name, file_names));
+        }
+
+        Ok(ValidationErrorInfo::new(
+            "Files whose names only differ in case (they collide on case-insensitive filesystems).",
+            attributes,
+        ))
+    }
+}
+
+
+/// This validation error happens when an artist, album or file name can't be used
+/// on FAT32, exFAT or NTFS filesystems (see `check_portable_file_names`
+/// in the validation configuration table and `find_unportable_file_name_reason`).
+pub struct UnportableFileName<'a> {
+    /// Path of the directory or file with the unportable name.
+    path: PathBuf,
+
+    /// What library the directory or file is part of.
+    library: &'a LibraryConfiguration,
+
+    /// Why the name can't be used.
+    reason: &'static str,
+}
+
+impl<'a> UnportableFileName<'a> {
+    pub fn new<P: Into<PathBuf>>(
+        path: P,
+        library: &'a LibraryConfiguration,
+        reason: &'static str,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            library,
+            reason,
+        }
+    }
+
+    /// Returns the path, relative to the library root.
+    fn relative_path(&self) -> Result<PathBuf> {
+        pathdiff::diff_paths(&self.path, &self.library.path).ok_or_else(|| {
+            miette!("Could not make path relative to library base!")
+        })
+    }
+
+    pub fn finding(&self) -> Result<ValidationFinding> {
+        Ok(ValidationFinding::new(
+            "unportable-file-name",
+            &self.library.name,
+            self.relative_path()?.to_string_lossy(),
+        ))
+    }
+}
+
+impl<'a> ValidationErrorDisplay for UnportableFileName<'a> {
+    fn get_error_info(&self) -> Result<ValidationErrorInfo> {
+        // (UnportableFileName validation error display example)
+        //
+        // # Name can't be used on FAT32, exFAT or NTFS filesystems.
+        //
+        // Library: Standard
+        // Path: Aindulmedir/The Lunar Lexicon?
+        // Reason: contains a character that isn't allowed (<>:"/\|?* or a control character)
+
+        let relative_path = self.relative_path()?;
+
+        let attributes = vec![
+            ("Library".to_string(), self.library.name.clone()),
+            (
+                "Path".to_string(),
+                relative_path.to_string_lossy().to_string(),
+            ),
+            ("Reason".to_string(), self.reason.to_string()),
+        ];
+
+        Ok(ValidationErrorInfo::new(
+            "Name can't be used on FAT32, exFAT or NTFS filesystems.",
             attributes,
         ))
     }
@@ -170,23 +854,6 @@ impl<'a> ValidationAlbumEntry<'a> {
     }
 }
 
-impl<'a> PartialEq for ValidationAlbumEntry<'a> {
-    fn eq(&self, other: &Self) -> bool {
-        self.artist_name.eq(&other.artist_name)
-            && self.album_title.eq(&other.album_title)
-    }
-}
-
-impl<'a> Eq for ValidationAlbumEntry<'a> {}
-
-impl<'a> Hash for ValidationAlbumEntry<'a> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.artist_name.hash(state);
-        self.album_title.hash(state);
-        self.library.name.hash(state);
-    }
-}
-
 
 /// Represents a single album collision containing two or more colliding album entries
 /// (each from a different library) - were the user to try and `transcode`, this would cause issues
@@ -239,6 +906,20 @@ impl<'a> AlbumCollision<'a> {
             .map(|entry| entry.library.name.clone())
             .collect()
     }
+
+    pub fn finding(&self) -> ValidationFinding {
+        // Library names are sorted to keep the identifier stable between runs.
+        let mut colliding_library_names = self.colliding_library_names();
+        colliding_library_names.sort_unstable();
+
+        ValidationFinding::new(
+            "album-collision",
+            colliding_library_names.join("+"),
+            Path::new(&self.artist_name())
+                .join(self.album_title())
+                .to_string_lossy(),
+        )
+    }
 }
 
 impl<'a> ValidationErrorDisplay for AlbumCollision<'a> {
@@ -277,9 +958,12 @@ impl<'a> ValidationErrorDisplay for AlbumCollision<'a> {
 /// - call `add_album_entry` with all your albums from all your libraries,
 /// - when finished, call `find_collisions` to receive information about potential collisions.
 struct CollectionCollisionValidator<'a> {
-    /// A nested map from artist names to album names to sets of individual (colliding) albums.
-    artist_to_albums:
-        HashMap<String, HashMap<String, HashSet<ValidationAlbumEntry<'a>>>>,
+    /// A nested map from artist names to album names to individual (colliding) albums,
+    /// keyed by the name of the library they are in.
+    artist_to_albums: HashMap<
+        String,
+        HashMap<String, HashMap<String, ValidationAlbumEntry<'a>>>,
+    >,
 }
 
 impl<'a> CollectionCollisionValidator<'a> {
@@ -309,10 +993,11 @@ impl<'a> CollectionCollisionValidator<'a> {
             .entry(entry.artist_name.clone())
             .or_default();
 
-        let album_set =
+        let album_entries =
             artist_albums.entry(entry.album_title.clone()).or_default();
 
-        let exact_entry_already_existed = !album_set.insert(entry);
+        let exact_entry_already_existed =
+            album_entries.insert(library.name.clone(), entry).is_some();
 
         // It is possible (but would be a bug) that the exact same entry from the same library
         // would be inserted multiple times. In that case we return early.
@@ -330,12 +1015,12 @@ impl<'a> CollectionCollisionValidator<'a> {
         self.artist_to_albums
             .values()
             .flatten()
-            .filter_map(|(_, album_set)| {
-                if album_set.len() > 1 {
+            .filter_map(|(_, album_entries)| {
+                if album_entries.len() > 1 {
                     // This album has a collision, generate it.
                     Some(AlbumCollision::new(
-                        album_set
-                            .iter()
+                        album_entries
+                            .values()
                             .collect::<Vec<&'a ValidationAlbumEntry<'a>>>(),
                     ))
                 } else {
@@ -347,11 +1032,349 @@ impl<'a> CollectionCollisionValidator<'a> {
     }
 }
 
+/// Returns `true` if the file is any kind of audio file
+/// (see `validation.extensions_considered_audio_files`).
+fn is_any_audio_file(config: &Configuration, file_path: &Path) -> bool {
+    let file_extension = file_path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+
+    config
+        .validation
+        .extensions_considered_audio_files
+        .contains(&file_extension)
+}
+
+/// Returns `true` if the file is an audio file that is allowed in the given library.
+fn is_valid_library_audio_file(
+    library_config: &LibraryConfiguration,
+    file_path: &Path,
+) -> bool {
+    let file_extension = file_path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+
+    library_config
+        .validation
+        .allowed_audio_file_extensions
+        .contains(&file_extension)
+}
+
+/// Returns `true` if the file is a non-audio (data) file that is allowed in the given library
+/// (either by its extension or by its full name).
+fn is_valid_library_non_audio_file(
+    library_config: &LibraryConfiguration,
+    file_path: &Path,
+) -> bool {
+    let file_name = file_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let file_extension = file_path
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+
+    library_config
+        .validation
+        .allowed_other_file_extensions
+        .contains(&file_extension)
+        || library_config
+            .validation
+            .allowed_other_files_by_name
+            .contains(&file_name)
+}
+
+/// Returns `true` if the file name is one of the source album state files: the `transcode`
+/// and `mirror` ones or the one of any device profile (see `SourceAlbumState::get_state_file_name`).
+fn is_source_album_state_file_name(
+    config: &Configuration,
+    file_name: &str,
+) -> bool {
+    file_name == SOURCE_ALBUM_STATE_FILE_NAME
+        || file_name == MIRROR_SOURCE_ALBUM_STATE_FILE_NAME
+        || config.device_profiles.keys().any(|profile_name| {
+            file_name
+                == SourceAlbumState::get_state_file_name(
+                    AggregationMode::Transcode,
+                    Some(profile_name),
+                )
+        })
+}
+
+/// Returns `true` if the file name is one of the library state files: the `transcode`
+/// and `mirror` ones or the one of any device profile (see `LibraryState::get_state_file_name`).
+fn is_library_state_file_name(config: &Configuration, file_name: &str) -> bool {
+    let meta_file_name = &config.file_metadata.meta_file_name;
+
+    file_name == meta_file_name
+        || file_name == MIRROR_LIBRARY_STATE_FILE_NAME
+        || config.device_profiles.keys().any(|profile_name| {
+            file_name
+                == LibraryState::get_state_file_name(
+                    meta_file_name,
+                    AggregationMode::Transcode,
+                    Some(profile_name),
+                )
+        })
+}
+
+
+/// Names (with or without an extension) reserved on Windows filesystems.
+const RESERVED_FILE_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6",
+    "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+    "LPT7", "LPT8", "LPT9",
+];
+
+/// Returns why the given file or directory name can't be used on FAT32, exFAT or NTFS
+/// filesystems, or `None` if it can (see `validation.check_portable_file_names`).
+fn find_unportable_file_name_reason(name: &str) -> Option<&'static str> {
+    if name.chars().any(|character| {
+        character.is_control()
+            || UNPORTABLE_FILE_NAME_CHARACTERS.contains(&character)
+    }) {
+        return Some(
+            "contains a character that isn't allowed (<>:\"/\\|?* or a control character)",
+        );
+    }
+
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Some("ends with a dot or a space");
+    }
+
+    let name_before_extension = name.split('.').next().unwrap_or_default();
+    if RESERVED_FILE_NAMES.iter().any(|reserved_name| {
+        reserved_name.eq_ignore_ascii_case(name_before_extension)
+    }) {
+        return Some("is a reserved name (e.g. CON or NUL)");
+    }
+
+    None
+}
+
+/// Check the tracked files of an album for names that would be a problem when copying
+/// the aggregated library to another filesystem: output files whose names only differ in case
+/// and (including the album directory) names that can't be used on FAT32, exFAT or NTFS
+/// (see `check_case_insensitive_collisions` and `check_portable_file_names`
+/// in the validation configuration table).
+fn validate_album_file_names<'a>(
+    config: &Configuration,
+    library_config: &'a LibraryConfiguration,
+    album_view: SharedAlbumView,
+) -> Result<Vec<ValidationError<'a>>> {
+    let mut validation_errors: Vec<ValidationError> = Vec::new();
+
+    if !config.validation.check_case_insensitive_collisions
+        && !config.validation.check_portable_file_names
+    {
+        return Ok(validation_errors);
+    }
+
+    let album_directory_path =
+        album_view.read().album_directory_in_source_library();
+    let tracked_source_files = AlbumSourceFileList::from_album_view(album_view)?;
+
+    if config.validation.check_case_insensitive_collisions {
+        let colliding_file_groups =
+            tracked_source_files.find_case_insensitive_output_collisions();
+
+        if !colliding_file_groups.is_empty() {
+            validation_errors.push(
+                ValidationError::new_case_insensitive_collision(
+                    &album_directory_path,
+                    library_config,
+                    colliding_file_groups,
+                ),
+            );
+        }
+    }
+
+    if config.validation.check_portable_file_names {
+        if let Some(reason) = album_directory_path.file_name().and_then(|name| {
+            find_unportable_file_name_reason(&name.to_string_lossy())
+        }) {
+            validation_errors.push(ValidationError::new_unportable_file_name(
+                &album_directory_path,
+                library_config,
+                reason,
+            ));
+        }
+
+        // Directories inside the album are reported only once, not for each of their files.
+        let mut reported_relative_paths: HashSet<PathBuf> = HashSet::new();
+
+        for relative_file_path in tracked_source_files
+            .audio_files
+            .iter()
+            .chain(tracked_source_files.data_files.iter())
+        {
+            let mut relative_path = PathBuf::new();
+
+            for component in relative_file_path.components() {
+                relative_path.push(component);
+
+                let Some(reason) = find_unportable_file_name_reason(
+                    &component.as_os_str().to_string_lossy(),
+                ) else {
+                    continue;
+                };
+
+                if reported_relative_paths.insert(relative_path.clone()) {
+                    validation_errors.push(
+                        ValidationError::new_unportable_file_name(
+                            album_directory_path.join(&relative_path),
+                            library_config,
+                            reason,
+                        ),
+                    );
+                }
+
+                break;
+            }
+        }
+    }
+
+    Ok(validation_errors)
+}
+
+/// Check the name of an artist directory (see `check_portable_file_names`
+/// in the validation configuration table).
+fn validate_artist_directory_name<'a>(
+    config: &Configuration,
+    library_config: &'a LibraryConfiguration,
+    artist_name: &str,
+) -> Option<ValidationError<'a>> {
+    if !config.validation.check_portable_file_names {
+        return None;
+    }
+
+    find_unportable_file_name_reason(artist_name).map(|reason| {
+        ValidationError::new_unportable_file_name(
+            Path::new(&library_config.path).join(artist_name),
+            library_config,
+            reason,
+        )
+    })
+}
+
+
+/// Validate a single album directory: checks whether the directory looks like an actual album
+/// and whether it contains any unexpected files.
+///
+/// This does not include the collision check, which needs information about all the libraries.
+fn validate_album<'a>(
+    config: &Configuration,
+    library_config: &'a LibraryConfiguration,
+    album_view_locked: &AlbumView,
+) -> Result<Vec<ValidationError<'a>>> {
+    let mut validation_errors: Vec<ValidationError> = Vec::new();
+
+    let album_directory_path =
+        album_view_locked.album_directory_in_source_library();
+    let album_files = album_view_locked.album_validation_files()?;
+
+    // Check whether the album directory looks like an actual album.
+    let deepest_file_depth = album_files
+        .iter()
+        .filter_map(|file_path| {
+            file_path.strip_prefix(&album_directory_path).ok()
+        })
+        .map(|relative_path| {
+            relative_path.components().count().saturating_sub(1)
+        })
+        .max()
+        .unwrap_or_default();
+    let num_audio_files = album_files
+        .iter()
+        .filter(|file_path| is_any_audio_file(config, file_path))
+        .count();
+
+    if deepest_file_depth > config.validation.maximum_album_file_depth as usize
+        || num_audio_files > config.validation.maximum_album_audio_files
+    {
+        validation_errors.push(ValidationError::new_suspicious_album_directory(
+            &album_directory_path,
+            library_config,
+            deepest_file_depth,
+            num_audio_files,
+        ));
+    }
+
+    let mut unexpected_files: Vec<(PathBuf, UnexpectedFileLocation)> =
+        Vec::new();
+
+    for album_dir_file_path in album_files {
+        let album_dir_file_name = album_dir_file_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        if is_source_album_state_file_name(config, &album_dir_file_name)
+            || album_dir_file_name.eq(ALBUM_OVERRIDE_FILE_NAME)
+        {
+            continue;
+        }
+
+        let is_any_audio =
+            is_any_audio_file(config, album_dir_file_path.as_path());
+        let is_valid_audio = is_valid_library_audio_file(
+            library_config,
+            album_dir_file_path.as_path(),
+        );
+        let is_valid_non_audio = is_valid_library_non_audio_file(
+            library_config,
+            album_dir_file_path.as_path(),
+        );
+
+        if is_any_audio && !is_valid_audio {
+            // File was an audio file, but not the kind that we allow in this library.
+            unexpected_files.push((
+                album_dir_file_path,
+                UnexpectedFileLocation::AlbumDirectoryAudio,
+            ));
+        } else if !is_any_audio && !is_valid_non_audio {
+            // File was not an audio file nor a valid non-audio (data) file in this library.
+            unexpected_files.push((
+                album_dir_file_path,
+                UnexpectedFileLocation::AlbumDirectoryOther,
+            ));
+        }
+    }
+
+    if !unexpected_files.is_empty() {
+        unexpected_files.sort_unstable_by(
+            |(first_path, _), (second_path, _)| first_path.cmp(second_path),
+        );
+
+        validation_errors.push(ValidationError::new_unexpected_album_files(
+            &album_directory_path,
+            library_config,
+            unexpected_files,
+        ));
+    }
+
+    Ok(validation_errors)
+}
+
+
 /// Runs the validation process over the entire collection (all registered libraries).
+///
+/// Returns the number of displayed validation errors
+/// (when using a baseline, only the new ones are displayed).
 fn validate_entire_collection(
     config: &Configuration,
     terminal: &mut ValidationTerminal,
-) -> Result<()> {
+    options: &ValidateOptions,
+) -> Result<usize> {
     // As explained in the README and configuration template, library structure
     // is expected to be the following:
     //
@@ -433,64 +1456,11 @@ fn validate_entire_collection(
     // As we're validating albums we're also performing an artist-album collision check
     // between all registered libraries.
 
-    let is_any_audio_file = |file_path: &Path| {
-        let file_extension = file_path
-            .extension()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_ascii_lowercase();
-
-        config
-            .validation
-            .extensions_considered_audio_files
-            .contains(&file_extension)
-    };
-
     for library_config in config.libraries.values() {
         let library_view =
             LibraryView::from_library_configuration(config, library_config)?;
         let library_view_locked = library_view.read();
 
-        let ignored_directories_in_base_directory: HashSet<&String> =
-            match &library_config.ignored_directories_in_base_directory {
-                Some(ignored_dirs) => HashSet::from_iter(ignored_dirs.iter()),
-                None => HashSet::new(),
-            };
-
-        let allowed_audio_file_extensions =
-            &library_config.validation.allowed_audio_file_extensions;
-        let allowed_other_file_extensions =
-            &library_config.validation.allowed_other_file_extensions;
-        let allowed_other_files_by_name =
-            &library_config.validation.allowed_other_files_by_name;
-
-        // Handy closures for repeated file validity checks.
-        let is_valid_library_audio_file = |file_path: &Path| {
-            let file_extension = file_path
-                .extension()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_ascii_lowercase();
-
-            allowed_audio_file_extensions.contains(&file_extension)
-        };
-
-        let is_valid_library_non_audio_file = |file_path: &Path| {
-            let file_name = file_path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string();
-
-            let file_extension = file_path
-                .extension()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_ascii_lowercase();
-
-            allowed_other_file_extensions.contains(&file_extension)
-                || allowed_other_files_by_name.contains(&file_name)
-        };
 
         // Check for unexpected files in the root library directory.
         let root_library_files_to_check =
@@ -502,12 +1472,15 @@ fn validate_entire_collection(
                 .to_string_lossy()
                 .to_string();
 
-            // Allow `.library.state.euphony`.
-            if root_file_name.eq(LIBRARY_STATE_FILE_NAME) {
+            // Allow `.library.state.euphony` (and its `mirror` and device profile counterparts).
+            if is_library_state_file_name(config, &root_file_name) {
                 continue;
             }
 
-            if !is_valid_library_non_audio_file(root_file.as_path()) {
+            if !is_valid_library_non_audio_file(
+                library_config,
+                root_file.as_path(),
+            ) {
                 validation_errors.push(ValidationError::new_unexpected_file(
                     root_file,
                     library_config,
@@ -518,16 +1491,25 @@ fn validate_entire_collection(
 
         // Check for unexpected files in each artist directory.
         for (artist_name, artist_view) in library_view_locked.artists()? {
-            if ignored_directories_in_base_directory.contains(&artist_name) {
+            if library_config
+                .is_directory_ignored_in_base_directory(&artist_name)
+            {
                 continue;
             }
 
+            validation_errors.extend(validate_artist_directory_name(
+                config,
+                library_config,
+                &artist_name,
+            ));
+
             let artist_view_locked = artist_view.read();
 
             let artist_files =
                 artist_view_locked.artist_directory_validation_files()?;
             for artist_dir_file_path in artist_files {
                 if !is_valid_library_non_audio_file(
+                    library_config,
                     artist_dir_file_path.as_path(),
                 ) {
                     validation_errors.push(ValidationError::new_unexpected_file(
@@ -544,51 +1526,16 @@ fn validate_entire_collection(
                     .add_album_entry(&artist_name, &album_title, library_config)
                     .wrap_err_with(|| miette!("BUG: Duplicate album entry."))?;
 
-                let album_view_locked = album_view.read();
-
-                let album_files = album_view_locked.album_validation_files()?;
-                for album_dir_file_path in album_files {
-                    let album_dir_file_name = album_dir_file_path
-                        .file_name()
-                        .unwrap_or_default()
-                        .to_string_lossy()
-                        .to_string();
-
-                    if album_dir_file_name.eq(SOURCE_ALBUM_STATE_FILE_NAME)
-                        || album_dir_file_name.eq(ALBUM_OVERRIDE_FILE_NAME)
-                    {
-                        continue;
-                    }
-
-                    let is_any_audio =
-                        is_any_audio_file(album_dir_file_path.as_path());
-                    let is_valid_audio = is_valid_library_audio_file(
-                        album_dir_file_path.as_path(),
-                    );
-                    let is_valid_non_audio = is_valid_library_non_audio_file(
-                        album_dir_file_path.as_path(),
-                    );
-
-                    if is_any_audio && !is_valid_audio {
-                        // File was an audio file, but not the kind that we allow in this library.
-                        validation_errors.push(
-                            ValidationError::new_unexpected_file(
-                                &album_dir_file_path,
-                                library_config,
-                                UnexpectedFileLocation::AlbumDirectoryAudio,
-                            ),
-                        );
-                    } else if !is_any_audio && !is_valid_non_audio {
-                        // File was not an audio file nor a valid non-audio (data) file in this library.
-                        validation_errors.push(
-                            ValidationError::new_unexpected_file(
-                                &album_dir_file_path,
-                                library_config,
-                                UnexpectedFileLocation::AlbumDirectoryOther,
-                            ),
-                        );
-                    }
-                }
+                validation_errors.extend(validate_album(
+                    config,
+                    library_config,
+                    &album_view.read(),
+                )?);
+                validation_errors.extend(validate_album_file_names(
+                    config,
+                    library_config,
+                    album_view,
+                )?);
             }
         }
     }
@@ -602,40 +1549,478 @@ fn validate_entire_collection(
     );
 
 
-    // We've completed the validation process, we'll now display the results.
-    let validation_errors_vec: Vec<ValidationErrorInfo> = validation_errors
+    report_validation_errors(config, terminal, validation_errors, options)
+}
+
+
+/// Runs the validation process over a single album directory.
+///
+/// The album's owning library is resolved from its path (the album directory must be
+/// at `<library>/<artist>/<album>`), then the same album checks as in
+/// `validate_entire_collection` are performed. Additionally, the album is checked for
+/// collisions with same-named albums in other libraries.
+///
+/// Returns the number of displayed validation errors
+/// (when using a baseline, only the new ones are displayed).
+fn validate_single_album(
+    config: &Configuration,
+    terminal: &mut ValidationTerminal,
+    album_directory_path: &Path,
+    options: &ValidateOptions,
+) -> Result<usize> {
+    let album_directory_path = dunce::canonicalize(album_directory_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            miette!(
+                "Album directory does not exist: {:?}",
+                album_directory_path
+            )
+        })?;
+
+    if !album_directory_path.is_dir() {
+        return Err(miette!(
+            "Album path is not a directory: {:?}",
+            album_directory_path
+        ));
+    }
+
+    // Find the library that contains the album directory.
+    let mut owning_library: Option<(&LibraryConfiguration, PathBuf)> = None;
+    for library_config in config.libraries.values() {
+        let Ok(library_root) = dunce::canonicalize(&library_config.path) else {
+            continue;
+        };
+
+        if let Ok(relative_album_path) =
+            album_directory_path.strip_prefix(&library_root)
+        {
+            owning_library =
+                Some((library_config, relative_album_path.to_path_buf()));
+            break;
+        }
+    }
+
+    let Some((library_config, relative_album_path)) = owning_library else {
+        return Err(miette!(
+            "Album directory {:?} is not inside any registered library.",
+            album_directory_path
+        ));
+    };
+
+    // The album directory is expected to be at <library>/<artist>/<album>.
+    let mut components = relative_album_path.components();
+    let (
+        Some(Component::Normal(artist_name)),
+        Some(Component::Normal(album_title)),
+        None,
+    ) = (
+        components.next(),
+        components.next(),
+        components.next(),
+    )
+    else {
+        return Err(miette!(
+            "Path {:?} is not an album directory: expected <library>/<artist>/<album>.",
+            album_directory_path
+        ));
+    };
+
+    let artist_name = artist_name.to_string_lossy().to_string();
+    let album_title = album_title.to_string_lossy().to_string();
+
+    if library_config.is_directory_ignored_in_base_directory(&artist_name) {
+        return Err(miette!(
+            "Album directory {:?} is inside an ignored directory.",
+            album_directory_path
+        ));
+    }
+
+    if !options.output_json {
+        terminal.log_println(format!(
+            "Validating album \"{artist_name} - {album_title}\" (library: {}).",
+            library_config.name
+        ));
+    }
+
+    let library_view =
+        LibraryView::from_library_configuration(config, library_config)?;
+    let artist_view = ArtistView::new(library_view, artist_name.clone(), false)?;
+    let album_view = AlbumView::new(artist_view, album_title.clone(), false)?;
+
+    // Check for collisions with same-named albums in other libraries.
+    let mut collision_validator = CollectionCollisionValidator::new();
+    collision_validator
+        .add_album_entry(&artist_name, &album_title, library_config)
+        .wrap_err_with(|| miette!("BUG: Duplicate album entry."))?;
+
+    for other_library_config in config.libraries.values() {
+        if std::ptr::eq(other_library_config, library_config) {
+            continue;
+        }
+
+        let is_ignored_in_other_library = other_library_config
+            .is_directory_ignored_in_base_directory(&artist_name);
+
+        let other_album_directory_path = Path::new(&other_library_config.path)
+            .join(&artist_name)
+            .join(&album_title);
+
+        if !is_ignored_in_other_library && other_album_directory_path.is_dir() {
+            collision_validator
+                .add_album_entry(
+                    &artist_name,
+                    &album_title,
+                    other_library_config,
+                )
+                .wrap_err_with(|| miette!("BUG: Duplicate album entry."))?;
+        }
+    }
+
+    let mut validation_errors =
+        validate_album(config, library_config, &album_view.read())?;
+    validation_errors.extend(validate_artist_directory_name(
+        config,
+        library_config,
+        &artist_name,
+    ));
+    validation_errors.extend(validate_album_file_names(
+        config,
+        library_config,
+        album_view,
+    )?);
+    validation_errors.extend(
+        collision_validator
+            .find_collisions()?
+            .into_iter()
+            .map(ValidationError::AlbumCollision),
+    );
+
+    report_validation_errors(config, terminal, validation_errors, options)
+}
+
+
+/// A file that is not allowed in its library, which `validate --fix` can remove.
+pub struct FixableFile<'a> {
+    /// Absolute path of the file.
+    file_path: PathBuf,
+
+    /// What library the file is part of.
+    library: &'a LibraryConfiguration,
+}
+
+/// A validation error, prepared for saving and displaying (see `report_validation_errors`).
+struct ReportedValidationError<'a> {
+    findings: Vec<ValidationFinding>,
+
+    violations: Vec<(String, ValidationViolation)>,
+
+    fixable_files: Vec<FixableFile<'a>>,
+
+    error_info: ValidationErrorInfo,
+}
+
+
+/// Save and display the results of a finished validation process
+/// (optionally saving a report, comparing against a baseline
+/// or fixing unexpected files, see `ValidateOptions`).
+///
+/// Returns the number of displayed validation errors
+/// (when using a baseline, only the new ones are displayed).
+fn report_validation_errors(
+    config: &Configuration,
+    terminal: &mut ValidationTerminal,
+    validation_errors: Vec<ValidationError>,
+    options: &ValidateOptions,
+) -> Result<usize> {
+    let reported_errors: Vec<ReportedValidationError> = validation_errors
         .into_iter()
-        .map(|error| error.into_validation_error_info())
-        .collect::<Result<Vec<ValidationErrorInfo>>>()?;
+        .map(|error| {
+            Ok(ReportedValidationError {
+                findings: error.findings()?,
+                violations: error.violations()?,
+                fixable_files: error.fixable_files(),
+                error_info: error.into_validation_error_info()?,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    if let Some(report_file_path) = &options.report_file_path {
+        ValidationReport::new(
+            reported_errors
+                .iter()
+                .flat_map(|error| error.findings.iter().cloned())
+                .collect(),
+        )
+        .save_to_file(report_file_path)?;
+
+        if !options.output_json {
+            terminal.log_println(format!(
+                "Saved validation report to {:?}.",
+                report_file_path
+            ));
+        }
+    }
+
+    let displayed_errors: Vec<ReportedValidationError> = match &options
+        .baseline_file_path
+    {
+        Some(baseline_file_path) => {
+            let baseline = ValidationReport::load_from_file(baseline_file_path)?;
+            let baseline_findings = baseline.finding_set();
+
+            let num_total_findings = reported_errors.len();
+            let new_errors: Vec<ReportedValidationError> = reported_errors
+                    .into_iter()
+                    // Grouped errors are displayed if any of their findings is new.
+                    .filter(|error| {
+                        !error.findings.iter().all(|finding| {
+                            baseline_findings.contains(finding)
+                        })
+                    })
+                    .collect();
+
+            if !options.output_json {
+                terminal.log_println(format!(
+                        "Compared against baseline {:?}: {} of {} validation errors are already in the baseline.",
+                        baseline_file_path,
+                        num_total_findings - new_errors.len(),
+                        num_total_findings,
+                    ));
+            }
+
+            new_errors
+        }
+        None => reported_errors,
+    };
+
+    let num_validation_errors = displayed_errors.len();
+    let mut fixable_files: Vec<FixableFile> = Vec::new();
 
-    if validation_errors_vec.is_empty() {
-        terminal.log_println("All libraries validated, no errors.".green());
+    if options.output_json {
+        let mut json_report = ValidationJsonReport::default();
+
+        for error in displayed_errors {
+            for (library_name, violation) in error.violations {
+                json_report.add_violation(library_name, violation);
+            }
+
+            fixable_files.extend(error.fixable_files);
+        }
+
+        terminal.log_println(
+            serde_json::to_string_pretty(&json_report)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    miette!("Failed to serialize validation report.")
+                })?,
+        );
+    } else if displayed_errors.is_empty() {
+        if options.baseline_file_path.is_some() {
+            terminal
+                .log_println("All libraries validated, no new errors.".green());
+        } else {
+            terminal.log_println("All libraries validated, no errors.".green());
+        }
     } else {
         terminal.log_println(
             format!(
-                "{} validation errors!",
-                validation_errors_vec.len()
+                "{} {}validation errors!",
+                num_validation_errors,
+                if options.baseline_file_path.is_some() {
+                    "new "
+                } else {
+                    ""
+                }
             )
             .red(),
         );
 
-        for error in validation_errors_vec {
-            terminal.validation_add_error(error);
+        for error in displayed_errors {
+            terminal.validation_add_error(error.error_info);
+            fixable_files.extend(error.fixable_files);
+        }
+    }
+
+    if options.fix_unexpected_files {
+        fix_unexpected_files(config, terminal, fixable_files, options)?;
+    }
+
+    Ok(num_validation_errors)
+}
+
+/// Delete the given unexpected files or, if `validation.quarantine_directory` is set,
+/// move them into `<quarantine directory>/<library name>/<path relative to the library>`
+/// (see `validate --fix`). Each deleted or moved file is logged.
+///
+/// Unless `skip_fix_confirmation` is set, the user is asked for confirmation first.
+fn fix_unexpected_files(
+    config: &Configuration,
+    terminal: &mut ValidationTerminal,
+    mut fixable_files: Vec<FixableFile>,
+    options: &ValidateOptions,
+) -> Result<()> {
+    // Make sure we never touch files that are allowed in their library
+    // or euphony's own files (e.g. the state files of a since removed device profile).
+    fixable_files.retain(|fixable_file| {
+        let file_path = &fixable_file.file_path;
+
+        let is_euphony_file = file_path
+            .extension()
+            .is_some_and(|extension| extension == "euphony");
+
+        let is_allowed_file =
+            is_valid_library_non_audio_file(fixable_file.library, file_path)
+                || (is_any_audio_file(config, file_path)
+                    && is_valid_library_audio_file(
+                        fixable_file.library,
+                        file_path,
+                    ));
+
+        if is_euphony_file || is_allowed_file {
+            terminal.log_println(format!("Not fixing {:?}.", file_path));
+        }
+
+        !is_euphony_file && !is_allowed_file
+    });
+
+    if fixable_files.is_empty() {
+        terminal.log_println("No unexpected files to fix.");
+        return Ok(());
+    }
+
+    let quarantine_directory = config.validation.quarantine_directory.as_ref();
+
+    if !options.skip_fix_confirmation {
+        let action_description = match quarantine_directory {
+            Some(quarantine_directory) => format!(
+                "move {} unexpected files into {:?}",
+                fixable_files.len(),
+                quarantine_directory
+            ),
+            None => format!("delete {} unexpected files", fixable_files.len()),
+        };
+
+        terminal.log_println(format!(
+            "About to {action_description}. Continue? [y/N]"
+        ));
+
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Could not read confirmation."))?;
+
+        if !matches!(
+            answer.trim().to_ascii_lowercase().as_str(),
+            "y" | "yes"
+        ) {
+            terminal.log_println("Not fixing any files.");
+            return Ok(());
         }
     }
 
+    let num_fixed_files = fixable_files.len();
+
+    for fixable_file in fixable_files {
+        let file_path = &fixable_file.file_path;
+
+        match quarantine_directory {
+            Some(quarantine_directory) => {
+                let relative_file_path = pathdiff::diff_paths(
+                    file_path,
+                    &fixable_file.library.path,
+                )
+                .ok_or_else(|| {
+                    miette!("Could not make file path relative to library base!")
+                })?;
+                let quarantined_file_path = quarantine_directory
+                    .join(&fixable_file.library.name)
+                    .join(relative_file_path);
+
+                move_file(file_path, &quarantined_file_path).wrap_err_with(
+                    || {
+                        miette!(
+                            "Could not move {:?} into quarantine.",
+                            file_path
+                        )
+                    },
+                )?;
+
+                terminal.log_println(format!(
+                    "Moved {:?} to {:?}.",
+                    file_path, quarantined_file_path
+                ));
+            }
+            None => {
+                fs::remove_file(file_path).into_diagnostic().wrap_err_with(
+                    || miette!("Could not delete {:?}.", file_path),
+                )?;
+
+                terminal.log_println(format!("Deleted {:?}.", file_path));
+            }
+        }
+    }
+
+    terminal.log_println(
+        format!("Fixed {} unexpected files.", num_fixed_files).green(),
+    );
+
+    Ok(())
+}
+
+/// Move the file to the target path, creating any missing parent directories.
+/// Existing files are never overwritten.
+fn move_file(source_file_path: &Path, target_file_path: &Path) -> Result<()> {
+    if target_file_path.exists() {
+        return Err(miette!(
+            "Target file {:?} already exists.",
+            target_file_path
+        ));
+    }
+
+    if let Some(target_directory) = target_file_path.parent() {
+        fs::create_dir_all(target_directory).into_diagnostic()?;
+    }
+
+    // Renaming doesn't work across filesystems, in which case the file is copied instead.
+    if fs::rename(source_file_path, target_file_path).is_err() {
+        fs::copy(source_file_path, target_file_path).into_diagnostic()?;
+        fs::remove_file(source_file_path).into_diagnostic()?;
+    }
+
     Ok(())
 }
 
 /// Associated with the `validate` command.
 ///
 /// Validates the entire collection for unexpected files and album collisions.
+/// Returns the number of displayed validation errors (see `validate_entire_collection`).
 pub fn cmd_validate(
     config: &Configuration,
     terminal: &mut ValidationTerminal,
-) -> Result<()> {
-    terminal.log_println("Command: validate entire collection.".cyan().bold());
+    options: ValidateOptions,
+) -> Result<usize> {
+    if !options.output_json {
+        terminal
+            .log_println("Command: validate entire collection.".cyan().bold());
+    }
 
-    validate_entire_collection(config, terminal)?;
-    Ok(())
+    validate_entire_collection(config, terminal, &options)
+}
+
+/// Associated with the `validate-album` command.
+///
+/// Validates a single album directory for unexpected files and album collisions.
+/// Returns the number of displayed validation errors (see `validate_single_album`).
+pub fn cmd_validate_album(
+    config: &Configuration,
+    terminal: &mut ValidationTerminal,
+    album_directory_path: &Path,
+    options: ValidateOptions,
+) -> Result<usize> {
+    if !options.output_json {
+        terminal.log_println("Command: validate single album.".cyan().bold());
+    }
+
+    validate_single_album(config, terminal, album_directory_path, &options)
 }
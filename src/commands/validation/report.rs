@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+
+const VALIDATION_REPORT_SCHEMA_VERSION: u32 = 1;
+
+
+/// A stable identifier of a single validation finding.
+///
+/// Findings are identified by their category and a path that is independent of where
+/// the libraries are on disk (i.e. relative to the library root),
+/// so they can be compared between validation runs (see `validate --baseline`).
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ValidationFinding {
+    /// Kind of the validation error, e.g. `unexpected-file-in-album-directory`.
+    pub category: String,
+
+    /// Name of the library (or libraries, separated by `+`) the finding is in.
+    pub library: String,
+
+    /// Path of the offending file or directory, relative to the library root.
+    pub path: String,
+}
+
+impl ValidationFinding {
+    pub fn new<C: Into<String>, L: Into<String>, P: Into<String>>(
+        category: C,
+        library: L,
+        path: P,
+    ) -> Self {
+        Self {
+            category: category.into(),
+            library: library.into(),
+            path: path.into(),
+        }
+    }
+}
+
+
+/// A saved list of validation findings (see `validate --report` and `validate --baseline`).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ValidationReport {
+    pub schema_version: u32,
+
+    pub findings: Vec<ValidationFinding>,
+}
+
+impl ValidationReport {
+    pub fn new(findings: Vec<ValidationFinding>) -> Self {
+        Self {
+            schema_version: VALIDATION_REPORT_SCHEMA_VERSION,
+            findings,
+        }
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(file_path: P) -> Result<Self> {
+        let file_path = file_path.as_ref();
+
+        let file_contents = fs::read_to_string(file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not read validation report: {:?}",
+                    file_path
+                )
+            })?;
+
+        let report: Self = serde_json::from_str(&file_contents)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not parse validation report: {:?}",
+                    file_path
+                )
+            })?;
+
+        if report.schema_version != VALIDATION_REPORT_SCHEMA_VERSION {
+            return Err(miette!(
+                "Validation report schema version mismatch: {} (current is {}).",
+                report.schema_version,
+                VALIDATION_REPORT_SCHEMA_VERSION
+            ));
+        }
+
+        Ok(report)
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(
+        &self,
+        output_file_path: P,
+    ) -> Result<()> {
+        let output_file_path = output_file_path.as_ref();
+
+        let serialized_report = serde_json::to_string_pretty(self)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!("Could not serialize validation report to string.")
+            })?;
+
+        fs::write(output_file_path, serialized_report)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Could not write validation report to {:?}.",
+                    output_file_path
+                )
+            })
+    }
+
+    /// Returns a set of all the findings in this report.
+    pub fn finding_set(&self) -> HashSet<&ValidationFinding> {
+        self.findings.iter().collect()
+    }
+}
+
+
+/// Category of a violation in a JSON validation report (see `validate --format json`).
+#[derive(Serialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationViolationCategory {
+    /// A file that is not allowed in its library, i.e. neither by its extension
+    /// nor by its name (see `UnexpectedFile` and `UnexpectedAlbumFiles`).
+    ForbiddenExtension,
+
+    /// An album that exists in more than one library (see `AlbumCollision`).
+    Collision,
+
+    /// Album files whose output names only differ in case (see `CaseInsensitiveCollision`).
+    CaseCollision,
+
+    /// A name that can't be used on FAT32, exFAT or NTFS (see `UnportableFileName`).
+    UnportableName,
+
+    /// An album directory that probably isn't an album (see `SuspiciousAlbumDirectory`).
+    SuspiciousAlbumDirectory,
+}
+
+/// A single violation in a JSON validation report.
+#[derive(Serialize, Clone, Debug)]
+pub struct ValidationViolation {
+    pub category: ValidationViolationCategory,
+
+    /// Path of the offending file or directory, relative to the library root.
+    pub path: String,
+
+    /// Human-readable description of the violation.
+    pub message: String,
+}
+
+impl ValidationViolation {
+    pub fn new<P: Into<String>, M: Into<String>>(
+        category: ValidationViolationCategory,
+        path: P,
+        message: M,
+    ) -> Self {
+        Self {
+            category,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// The JSON validation report that `validate --format json` prints.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ValidationJsonReport {
+    /// Total number of violations (in all libraries).
+    pub num_violations: usize,
+
+    /// Violations in each library, keyed by library name.
+    pub libraries: BTreeMap<String, Vec<ValidationViolation>>,
+}
+
+impl ValidationJsonReport {
+    pub fn add_violation<L: Into<String>>(
+        &mut self,
+        library_name: L,
+        violation: ValidationViolation,
+    ) {
+        self.libraries
+            .entry(library_name.into())
+            .or_default()
+            .push(violation);
+
+        self.num_violations += 1;
+    }
+}
@@ -0,0 +1,140 @@
+use std::borrow::Cow;
+use std::time::Duration;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const BYTE_SIZE_UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+
+/// Format the given amount of bytes into a human-friendly string
+/// using binary (1024-based) units, e.g. `512 B`, `1.50 KiB` or `3.21 GiB`.
+///
+/// Use this everywhere a file or directory size is printed so the output stays consistent.
+pub fn format_byte_size(size_bytes: u64) -> String {
+    if size_bytes < 1024 {
+        return format!("{} B", size_bytes);
+    }
+
+    let mut size = size_bytes as f64 / 1024f64;
+    let mut unit_index = 0;
+
+    // Values that would round up to 1024.00 are moved to the next unit
+    // to avoid outputs such as "1024.00 KiB".
+    while size >= 1023.995 && unit_index < BYTE_SIZE_UNITS.len() - 1 {
+        size /= 1024f64;
+        unit_index += 1;
+    }
+
+    format!("{:.2} {}", size, BYTE_SIZE_UNITS[unit_index])
+}
+
+/// Format the given duration into a short human-friendly string with a whole number
+/// of seconds, e.g. `45s`, `3m 07s` or `1h 02m 09s`.
+pub fn format_duration(duration: Duration) -> String {
+    let total_seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (
+        total_seconds / 3600,
+        (total_seconds % 3600) / 60,
+        total_seconds % 60,
+    );
+
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m {seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+
+/// Truncate the given (plain, unstyled) text to fit into `max_width` terminal columns,
+/// ending it with `…` if anything was cut off.
+///
+/// Widths are display widths, not byte or character counts: wide characters
+/// (e.g. CJK) take up two columns. Truncate before styling the text, so ANSI codes are never cut.
+pub fn truncate_to_display_width(text: &str, max_width: usize) -> Cow<'_, str> {
+    if text.width() <= max_width {
+        return Cow::Borrowed(text);
+    }
+
+    if max_width == 0 {
+        return Cow::Borrowed("");
+    }
+
+    // One column is reserved for the ellipsis.
+    let mut truncated_text = String::with_capacity(max_width + 2);
+    let mut truncated_width: usize = 0;
+
+    for character in text.chars() {
+        let character_width = character.width().unwrap_or(0);
+        if truncated_width + character_width > max_width - 1 {
+            break;
+        }
+
+        truncated_text.push(character);
+        truncated_width += character_width;
+    }
+
+    truncated_text.push('…');
+
+    Cow::Owned(truncated_text)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_bytes_below_one_kibibyte_without_decimals() {
+        assert_eq!(format_byte_size(0), "0 B");
+        assert_eq!(format_byte_size(1023), "1023 B");
+    }
+
+    #[test]
+    fn formats_unit_boundaries() {
+        assert_eq!(format_byte_size(1024), "1.00 KiB");
+        assert_eq!(format_byte_size(1536), "1.50 KiB");
+        assert_eq!(format_byte_size(1024 * 1024), "1.00 MiB");
+        assert_eq!(format_byte_size(1024 * 1024 - 1), "1.00 MiB");
+        assert_eq!(format_byte_size(1024 * 1024 * 1024), "1.00 GiB");
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(
+            format_duration(Duration::from_millis(45_900)),
+            "45s"
+        );
+        assert_eq!(
+            format_duration(Duration::from_secs(187)),
+            "3m 07s"
+        );
+        assert_eq!(
+            format_duration(Duration::from_secs(3729)),
+            "1h 02m 09s"
+        );
+    }
+
+    #[test]
+    fn keeps_text_that_fits() {
+        assert_eq!(truncate_to_display_width("Song", 4), "Song");
+        assert_eq!(truncate_to_display_width("東京", 4), "東京");
+    }
+
+    #[test]
+    fn truncates_to_display_width() {
+        assert_eq!(
+            truncate_to_display_width("Song title", 6),
+            "Song …"
+        );
+        assert_eq!(
+            truncate_to_display_width("東京事変 - 教育", 7),
+            "東京事…"
+        );
+        // A wide character that doesn't fit entirely is left out.
+        assert_eq!(truncate_to_display_width("東京事変", 6), "東京…");
+        assert_eq!(truncate_to_display_width("Song", 0), "");
+    }
+}
@@ -1,26 +1,39 @@
+use std::collections::HashMap;
 use std::fmt::Display;
-use std::fs::File;
-use std::io::{BufWriter, Write};
 use std::path::Path;
+use std::sync::Arc;
 use std::thread::Scope;
 
 use crossterm::style::{Color, Stylize};
-use miette::{miette, Context, IntoDiagnostic, Result};
+use euphony_library::view::SharedAlbumView;
+use miette::{miette, Context, Result};
 use parking_lot::{Mutex, RwLock};
 use tokio::sync::broadcast;
 
-use super::shared::logging::initialize_log_file_for_log_output;
+use super::shared::logging::{
+    initialize_log_file_for_log_output,
+    write_line_to_log_file,
+    LogFileOptions,
+    LogLevel,
+    SharedLogFileWriter,
+};
 use crate::console::frontends::shared::queue::{
     AlbumQueueItem,
     AlbumQueueItemFinishedResult,
     FileQueueItem,
+    FileQueueItemErrorType,
     FileQueueItemFinishedResult,
     Queue,
     QueueItem,
     QueueItemID,
     RenderableQueueItem,
 };
-use crate::console::frontends::shared::Progress;
+use crate::console::frontends::shared::{Progress, ScanProgress};
+use crate::console::tracing_output::{
+    clear_tracing_log_sink,
+    format_tracing_line,
+    set_tracing_log_sink,
+};
 use crate::console::traits::{
     LogToFileBackend,
     UserControllableBackend,
@@ -46,6 +59,16 @@ pub struct QueueAndProgressState<'config> {
 
     /// When the progress bar is active, this contains the progress bar state.
     progress: Option<Progress>,
+
+    /// When scanning, this contains the scan progress state.
+    scan_progress: Option<ScanProgress>,
+
+    /// How many albums have been started so far (used to number the album log lines).
+    num_started_albums: usize,
+
+    /// Per-album file counters of the albums that are currently in progress,
+    /// keyed by their album view (see `album_view_key`).
+    albums_in_progress: HashMap<usize, AlbumInProgressCounters>,
 }
 
 impl<'config> QueueAndProgressState<'config> {
@@ -54,20 +77,55 @@ impl<'config> QueueAndProgressState<'config> {
             album_queue: None,
             file_queue: None,
             progress: None,
+            scan_progress: None,
+            num_started_albums: 0,
+            albums_in_progress: HashMap::new(),
         }
     }
 }
 
 
+/// The number of an album in progress (in the order albums were started)
+/// and how many of its files have finished so far.
+struct AlbumInProgressCounters {
+    album_number: usize,
+
+    num_files_finished_ok: usize,
+
+    num_files_failed: usize,
+}
+
+/// File queue items only know which album view they belong to, so albums in progress
+/// are tracked by the address of their (shared) album view.
+fn album_view_key(album_view: &SharedAlbumView) -> usize {
+    Arc::as_ptr(album_view) as usize
+}
+
+/// Format the result of a finished file as shown at the end of its log line.
+fn format_file_result(result: &FileQueueItemFinishedResult) -> String {
+    match result {
+        FileQueueItemFinishedResult::Ok => "ok".green().to_string(),
+        FileQueueItemFinishedResult::Failed(
+            FileQueueItemErrorType::Cancelled,
+        ) => "cancelled".yellow().to_string(),
+        FileQueueItemFinishedResult::Failed(
+            FileQueueItemErrorType::Errored { error },
+        ) => format!("{}: {error}", "failed".red()),
+    }
+}
+
+
 /// A simple non-dynamic terminal backend implementation.
 ///
 /// Any log output simply goes to stdout. More complex features, such as queues, are not displayed
-/// dynamically as a UI, but with simple one-line status updates about the queue (e.g. "New item in queue: ...").
+/// dynamically as a UI, but as a linear log with one line per event, e.g. `[album 3/12] Artist - Title`
+/// when an album is started and `  [file] track01.flac -> ok` when one of its files finishes.
+/// This makes it suitable for piping the output into a file or CI logs.
 pub struct BareTerminalBackend<'config> {
     state: RwLock<QueueAndProgressState<'config>>,
 
     /// If log file output is enabled, this contains the mutex in front of the file writer.
-    log_file_output: Mutex<Option<BufWriter<strip_ansi_escapes::Writer<File>>>>,
+    log_file_output: SharedLogFileWriter,
 
     broadcast_sender: Mutex<broadcast::Sender<UserControlMessage>>,
 }
@@ -78,7 +136,7 @@ impl<'config> BareTerminalBackend<'config> {
 
         Self {
             state: RwLock::new(QueueAndProgressState::new()),
-            log_file_output: Mutex::new(None),
+            log_file_output: Arc::new(Mutex::new(None)),
             broadcast_sender: Mutex::new(broadcast_sender),
         }
     }
@@ -88,10 +146,19 @@ impl<'config, 'scope, 'scope_env: 'scope> TerminalBackend<'scope, 'scope_env>
     for BareTerminalBackend<'config>
 {
     fn setup(&self, _scope: &'scope Scope<'scope, 'scope_env>) -> Result<()> {
+        // `tracing` events are logged just like the rest of the output.
+        let log_file_output = self.log_file_output.clone();
+        set_tracing_log_sink(Box::new(move |level, line| {
+            println!("{}", format_tracing_line(level, &line));
+            write_line_to_log_file(&log_file_output, level, &line);
+        }));
+
         Ok(())
     }
 
     fn destroy(self) -> Result<()> {
+        clear_tracing_log_sink();
+
         // If logging to file was enabled, we should disable it before this backend is dropped,
         // otherwise we risk failing to flush to file.
         self.disable_saving_logs_to_file()?;
@@ -105,9 +172,7 @@ impl<'config> LogBackend for BareTerminalBackend<'config> {
         println!();
 
         if let Some(writer) = self.log_file_output.lock().as_mut() {
-            writer
-                .write_all("\n".as_bytes())
-                .expect("Could not write to logfile.");
+            writer.write_newline().expect("Could not write to logfile.");
         }
     }
 
@@ -116,14 +181,11 @@ impl<'config> LogBackend for BareTerminalBackend<'config> {
 
         println!("{content_string}");
 
-        if let Some(writer) = self.log_file_output.lock().as_mut() {
-            writer
-                .write_all(content_string.as_bytes())
-                .expect("Could not write to logfile.");
-            writer
-                .write_all("\n".as_bytes())
-                .expect("Could not write to logfile (newline).");
-        }
+        write_line_to_log_file(
+            &self.log_file_output,
+            LogLevel::from_log_message(&content_string),
+            &content_string,
+        );
     }
 }
 
@@ -132,23 +194,19 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
      * Album queue
      */
     fn queue_album_enable(&self) {
-        self.log_println("Album queue enabled.");
-
         let mut locked_state = self.state.write();
         locked_state.album_queue = Some(Queue::new());
+        locked_state.num_started_albums = 0;
+        locked_state.albums_in_progress.clear();
     }
 
     fn queue_album_disable(&self) {
-        self.log_println("Album queue disabled.");
-
         let mut locked_state = self.state.write();
         locked_state.album_queue = None;
+        locked_state.albums_in_progress.clear();
     }
 
     fn queue_album_clear(&self) -> Result<()> {
-        self.log_println("Album queue cleared.");
-
-
         let mut locked_state = self.state.write();
         locked_state
             .album_queue
@@ -156,6 +214,9 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
             .ok_or_else(|| miette!("Album queue is disabled, can't clear."))?
             .clear();
 
+        locked_state.num_started_albums = 0;
+        locked_state.albums_in_progress.clear();
+
         Ok(())
     }
 
@@ -165,16 +226,11 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
     ) -> Result<QueueItemID> {
         let item_id = item.get_id();
 
-        self.log_println(format!(
-            "Album queue item added: {}",
-            item.render()
-        ));
-
         let mut locked_state = self.state.write();
         locked_state
             .album_queue
             .as_mut()
-            .ok_or_else(|| miette!("Album queue is disabled, can't clear."))?
+            .ok_or_else(|| miette!("Album queue is disabled, can't add item."))?
             .queue_item(item)?;
 
         Ok(item_id)
@@ -182,21 +238,38 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
 
     fn queue_album_item_start(&self, item_id: QueueItemID) -> Result<()> {
         let mut locked_state = self.state.write();
+        let locked_state = &mut *locked_state;
+
         let album_queue =
             locked_state.album_queue.as_mut().ok_or_else(|| {
                 miette!("Album queue is disabled, can't start item.")
             })?;
 
-
         album_queue.start_item(item_id)?;
+        let num_albums = album_queue.items().len();
 
         let item = album_queue
             .item(item_id)
             .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
-        let item_rendered = item.render();
+
+        locked_state.num_started_albums += 1;
+        let album_number = locked_state.num_started_albums;
+
+        locked_state.albums_in_progress.insert(
+            album_view_key(&item.album_view),
+            AlbumInProgressCounters {
+                album_number,
+                num_files_finished_ok: 0,
+                num_files_failed: 0,
+            },
+        );
 
         self.log_println(format!(
-            "Album queue item started: {item_rendered}"
+            "{} {} ({} audio, {} data files)",
+            format!("[album {album_number}/{num_albums}]").bold(),
+            item.render(),
+            item.num_changed_audio_files,
+            item.num_changed_data_files,
         ));
 
         Ok(())
@@ -208,22 +281,56 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
         result: AlbumQueueItemFinishedResult,
     ) -> Result<()> {
         let mut locked_state = self.state.write();
+        let locked_state = &mut *locked_state;
+
         let album_queue =
             locked_state.album_queue.as_mut().ok_or_else(|| {
                 miette!("Album queue is disabled, can't finish item.")
             })?;
 
         album_queue.finish_item(item_id, result)?;
+        let num_albums = album_queue.items().len();
 
         let item = album_queue
             .item(item_id)
             .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
-        let item_rendered = item.render();
+
+        let counters = locked_state
+            .albums_in_progress
+            .remove(&album_view_key(&item.album_view));
+
+        let (album_number, num_files_finished_ok, num_files_failed) =
+            match counters {
+                Some(counters) => (
+                    counters.album_number.to_string(),
+                    counters.num_files_finished_ok,
+                    counters.num_files_failed,
+                ),
+                None => ("?".to_string(), 0, 0),
+            };
+
+        let result_string = if result.ok {
+            "ok".green().to_string()
+        } else {
+            "failed".red().to_string()
+        };
 
         self.log_println(format!(
-            "Album queue item finished: {item_rendered} (result: {result:?})"
+            "{} {} -> {result_string} ({num_files_finished_ok} files finished, \
+            {num_files_failed} failed)",
+            format!("[album {album_number}/{num_albums}]").bold(),
+            item.render(),
         ));
 
+        if let Some(progress) = locked_state.progress.as_ref() {
+            self.log_println(format!(
+                "[progress] {}/{} files ({:.0}%)",
+                progress.total_finished_or_errored(),
+                progress.total_files,
+                progress.completion_ratio() * 100f64,
+            ));
+        }
+
         Ok(())
     }
 
@@ -244,22 +351,16 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
      * File queue
      */
     fn queue_file_enable(&self) {
-        self.log_println("File queue enabled.");
-
         let mut locked_state = self.state.write();
         locked_state.file_queue = Some(Queue::new());
     }
 
     fn queue_file_disable(&self) {
-        self.log_println("File queue disabled.");
-
         let mut locked_state = self.state.write();
         locked_state.file_queue = None;
     }
 
     fn queue_file_clear(&self) -> Result<()> {
-        self.log_println("File queue cleared.");
-
         let mut locked_state = self.state.write();
         locked_state
             .file_queue
@@ -276,11 +377,6 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
     ) -> Result<QueueItemID> {
         let item_id = item.get_id();
 
-        self.log_println(format!(
-            "File queue item added: {}",
-            item.render()
-        ));
-
         let mut locked_state = self.state.write();
         locked_state
             .file_queue
@@ -293,49 +389,65 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
 
     fn queue_file_item_start(&self, item_id: QueueItemID) -> Result<()> {
         let mut locked_state = self.state.write();
-        let file_queue = locked_state
-            .file_queue
-            .as_mut()
-            .ok_or_else(|| miette!("File queue is disabled, can't add item."))?;
-
+        let file_queue = locked_state.file_queue.as_mut().ok_or_else(|| {
+            miette!("File queue is disabled, can't start item.")
+        })?;
 
         file_queue.start_item(item_id)?;
 
         let item = file_queue
             .item(item_id)
             .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
-        let item_rendered = item.render();
 
-        self.log_println(format!(
-            "File queue item started: {item_rendered}"
-        ));
+        self.log_println(format!("  [file] {} -> started", item.file_name));
 
         Ok(())
     }
 
+    fn queue_file_item_set_progress(
+        &self,
+        _item_id: QueueItemID,
+        _progress: f64,
+    ) -> Result<()> {
+        // Intra-file progress is not logged, as it would flood the linear output.
+        Ok(())
+    }
+
     fn queue_file_item_finish(
         &self,
         item_id: QueueItemID,
         result: FileQueueItemFinishedResult,
     ) -> Result<()> {
         let mut locked_state = self.state.write();
-        let file_queue = locked_state
-            .file_queue
-            .as_mut()
-            .ok_or_else(|| miette!("File queue is disabled, can't add item."))?;
+        let locked_state = &mut *locked_state;
 
+        let file_queue = locked_state.file_queue.as_mut().ok_or_else(|| {
+            miette!("File queue is disabled, can't finish item.")
+        })?;
 
-        let result_string = format!("{result:?}");
+        let result_string = format_file_result(&result);
+        let is_ok = result == FileQueueItemFinishedResult::Ok;
 
         file_queue.finish_item(item_id, result)?;
 
         let item = file_queue
             .item(item_id)
             .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
-        let item_rendered = item.render();
+
+        if let Some(counters) = locked_state
+            .albums_in_progress
+            .get_mut(&album_view_key(&item.album_view))
+        {
+            if is_ok {
+                counters.num_files_finished_ok += 1;
+            } else {
+                counters.num_files_failed += 1;
+            }
+        }
 
         self.log_println(format!(
-            "File queue item finished: {item_rendered} (result: {result_string})"
+            "  [file] {} -> {result_string}",
+            item.file_name
         ));
 
         Ok(())
@@ -346,10 +458,9 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
         item_id: QueueItemID,
     ) -> Result<FileQueueItem<'config>> {
         let mut locked_state = self.state.write();
-        let file_queue = locked_state
-            .file_queue
-            .as_mut()
-            .ok_or_else(|| miette!("File queue is disabled, can't add item."))?;
+        let file_queue = locked_state.file_queue.as_mut().ok_or_else(|| {
+            miette!("File queue is disabled, can't remove item.")
+        })?;
 
         file_queue.remove_item(item_id)
     }
@@ -358,15 +469,11 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
      * Progress
      */
     fn progress_enable(&self) {
-        println!("Progress bar enabled.");
-
         let mut locked_state = self.state.write();
         locked_state.progress = Some(Progress::default());
     }
 
     fn progress_disable(&self) {
-        println!("Progress bar disabled.");
-
         let mut locked_state = self.state.write();
         locked_state.progress = None;
     }
@@ -488,6 +595,46 @@ impl<'config> TranscodeBackend<'config> for BareTerminalBackend<'config> {
             )),
         }
     }
+
+    /*
+     * Scan progress
+     */
+    fn scan_progress_enable(&self, num_total: usize) {
+        self.log_println(format!("Scanning {num_total} items."));
+
+        let mut locked_state = self.state.write();
+        locked_state.scan_progress = Some(ScanProgress::new(num_total));
+    }
+
+    fn scan_progress_disable(&self) {
+        let mut locked_state = self.state.write();
+        locked_state.scan_progress = None;
+    }
+
+    fn scan_progress_set_scanned(&self, num_scanned: usize) -> Result<()> {
+        let mut locked_state = self.state.write();
+
+        let scan_progress =
+            locked_state.scan_progress.as_mut().ok_or_else(|| {
+                miette!("Scan progress is disabled, can't set scanned items.")
+            })?;
+
+        // To avoid flooding the output, we only print a line for every 10% of progress.
+        let previous_step = (scan_progress.completion_ratio() * 10f64) as usize;
+        scan_progress.items_scanned = num_scanned;
+        let current_step = (scan_progress.completion_ratio() * 10f64) as usize;
+
+        if current_step != previous_step {
+            self.log_println(format!(
+                "Scanning progress: {}/{} ({:.0}%)",
+                scan_progress.items_scanned,
+                scan_progress.total_items,
+                scan_progress.completion_ratio() * 100f64
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl<'config> ValidationBackend for BareTerminalBackend<'config> {
@@ -529,16 +676,19 @@ impl<'config, 'scope, 'scope_env: 'scope> LogToFileBackend<'scope, 'scope_env>
     fn enable_saving_logs_to_file<P: AsRef<Path>>(
         &self,
         log_output_file_path: P,
+        options: &LogFileOptions,
         _scope: &'scope Scope<'scope, 'scope_env>,
     ) -> Result<()> {
-        let buf_writer =
-            initialize_log_file_for_log_output(log_output_file_path.as_ref())
-                .wrap_err_with(|| {
-                    miette!("Failed to initialize log file for log output.")
-                })?;
+        let log_file_writer = initialize_log_file_for_log_output(
+            log_output_file_path.as_ref(),
+            options,
+        )
+        .wrap_err_with(|| {
+            miette!("Failed to initialize log file for log output.")
+        })?;
 
         let mut locked_self_log_output = self.log_file_output.lock();
-        *locked_self_log_output = Some(buf_writer);
+        *locked_self_log_output = Some(log_file_writer);
 
         Ok(())
     }
@@ -547,17 +697,7 @@ impl<'config, 'scope, 'scope_env: 'scope> LogToFileBackend<'scope, 'scope_env>
         let mut locked_log_output = self.log_file_output.lock();
 
         if let Some(writer) = locked_log_output.take() {
-            let mut inner_writer = writer
-                .into_inner()
-                .map_err(|_| miette!("Failed to unwrap the BufWriter."))?
-                .into_inner()
-                .map_err(|_| {
-                    miette!("Failed to unwrap the ansi escape writer.")
-                })?;
-
-            inner_writer.flush().into_diagnostic().wrap_err_with(|| {
-                miette!("Failed to perform final flush on the File.")
-            })?;
+            writer.finish()?;
         }
 
         Ok(())
@@ -15,7 +15,7 @@
 ///
 /// Let's say we have the following enum:
 ///
-/// ```
+/// ```ignore
 /// enum SimpleTerminal {
 ///     Bare(BareTerminalBackend),
 ///     Fancy(TUITerminalBackend),
@@ -25,7 +25,7 @@
 /// And we want to use this macro to be able to call `.into()` on a `BareTerminalBackend` and have it
 /// convert into a `SimpleTerminal`. We would call the macro like this:
 ///
-/// ```
+/// ```ignore
 /// terminal_impl_direct_from_with_lifetime!(
 ///     on
 ///         SimpleTerminal<'config, 'scope>,
@@ -37,7 +37,7 @@
 ///
 /// which would expand to the following simple (but repetitive if we'd done it by hand) implementation:
 ///
-/// ```
+/// ```ignore
 /// impl<'config, 'scope> From<BareTerminalBackend<'config>> for SimpleTerminal<'config, 'scope> {
 ///     fn from(item: BareTerminalBackend<'config>) -> Self {
 ///         SimpleTerminal::Bare(item)
@@ -53,7 +53,7 @@
 ///
 /// We can now perform simple `.into()`s in our code instead of manual conversion:
 ///
-/// ```
+/// ```ignore
 /// let simple_terminal: SimpleTerminal = BareTerminalBackend::new().into();
 /// ```
 #[macro_export]
@@ -147,10 +147,11 @@ macro_rules! enumdispatch_impl_log_to_file {
             fn enable_saving_logs_to_file<P: AsRef<Path>>(
                 &self,
                 log_file_path: P,
+                options: &LogFileOptions,
                 scope: &'scope Scope<'scope, 'scope_env>
             ) -> miette::Result<()> {
                 match self {
-                    $($variant(terminal) => terminal.enable_saving_logs_to_file(log_file_path, scope)),+
+                    $($variant(terminal) => terminal.enable_saving_logs_to_file(log_file_path, options, scope)),+
                 }
             }
 
@@ -279,6 +280,16 @@ macro_rules! enumdispatch_impl_transcode {
                 }
             }
 
+            fn queue_file_item_set_progress(
+                &self,
+                item_id: QueueItemID,
+                progress: f64,
+            ) -> miette::Result<()> {
+                match self {
+                    $($variant(terminal) => terminal.queue_file_item_set_progress(item_id, progress)),+
+                }
+            }
+
             fn queue_file_item_finish(
                 &self,
                 item_id: QueueItemID,
@@ -375,6 +386,27 @@ macro_rules! enumdispatch_impl_transcode {
                         terminal.progress_set_data_files_errored(num_data_files_errored)),+
                 }
             }
+
+            /*
+             * Scan progress
+             */
+            fn scan_progress_enable(&self, num_total: usize) {
+                match self {
+                    $($variant(terminal) => terminal.scan_progress_enable(num_total)),+
+                }
+            }
+
+            fn scan_progress_disable(&self) {
+                match self {
+                    $($variant(terminal) => terminal.scan_progress_disable()),+
+                }
+            }
+
+            fn scan_progress_set_scanned(&self, num_scanned: usize) -> miette::Result<()> {
+                match self {
+                    $($variant(terminal) => terminal.scan_progress_set_scanned(num_scanned)),+
+                }
+            }
         }
     };
 }
@@ -37,7 +37,8 @@
 //! are added a variant to `TranscodeTerminal` can be used for the following commands:
 //! - `transcode`
 //!
-//! Both `BareTerminalBackend` and `TUITerminalBackend` are available here.
+//! `BareTerminalBackend`, `TUITerminalBackend` and `NdjsonTerminalBackend` (machine-readable events
+//! for external tooling) are available here.
 //!
 //!
 //!
@@ -58,7 +59,7 @@
 //!
 //! Let's say we have the following enum:
 //!
-//! ```
+//! ```ignore
 //! enum MyEnum {
 //!     VariantOne(SomeBackend),
 //!     VariantTwo(SomeBackendTwo),
@@ -77,7 +78,7 @@
 //! Calling `enumdispatch_impl_terminal!(MyEnum, MyEnum::VariantOne, MyEnum::VariantTwo)` will
 //! expand to the following:
 //!
-//! ```
+//! ```ignore
 //! impl TerminalBackend for MyEnum {
 //!     fn setup(&mut self) -> Result<()> {
 //!         match self {
@@ -97,7 +98,7 @@
 //!
 //! And that's it! Now we can simply do:
 //!
-//! ```
+//! ```ignore
 //! // Puts `SomeBackend` into the enum (in practice this could be one of many backend implementations
 //! // being put into one of many enum variants). See `terminal_impl_direct_from` for a better approach.
 //! let backend = MyEnum::VariantOne(some_backend_instance);
@@ -120,7 +121,9 @@ use std::path::Path;
 use std::thread::Scope;
 
 pub use bare::*;
+pub use ndjson::*;
 
+use crate::console::frontends::shared::logging::LogFileOptions;
 use crate::console::frontends::shared::queue::{
     AlbumQueueItem,
     AlbumQueueItemFinishedResult,
@@ -151,6 +154,7 @@ use crate::{
 
 mod bare;
 mod macro_impls;
+mod ndjson;
 pub mod shared;
 pub mod terminal_ui;
 
@@ -246,6 +250,7 @@ enumdispatch_impl_validation!(
 pub enum TranscodeTerminal<'config, 'scope> {
     Bare(BareTerminalBackend<'config>),
     Fancy(FancyTerminalBackend<'scope, 'config>),
+    Ndjson(NdjsonTerminalBackend<'config>),
 }
 
 impl<'config: 'scope, 'scope> Debug for TranscodeTerminal<'config, 'scope> {
@@ -259,7 +264,8 @@ terminal_impl_direct_from!(
         TranscodeTerminal<'config, 'scope>,
     do conversions
         BareTerminalBackend<'config> => TranscodeTerminal::Bare,
-        FancyTerminalBackend<'scope, 'config> => TranscodeTerminal::Fancy
+        FancyTerminalBackend<'scope, 'config> => TranscodeTerminal::Fancy,
+        NdjsonTerminalBackend<'config> => TranscodeTerminal::Ndjson
 );
 
 enumdispatch_impl_terminal!(
@@ -269,7 +275,8 @@ enumdispatch_impl_terminal!(
         TranscodeTerminal<'config, 'scope>,
     implement variants
         TranscodeTerminal::Bare,
-        TranscodeTerminal::Fancy
+        TranscodeTerminal::Fancy,
+        TranscodeTerminal::Ndjson
 );
 enumdispatch_impl_log!(
     lifetimes: 'config, 'scope,
@@ -277,7 +284,8 @@ enumdispatch_impl_log!(
         TranscodeTerminal<'config, 'scope>,
     implement variants
         TranscodeTerminal::Bare,
-        TranscodeTerminal::Fancy
+        TranscodeTerminal::Fancy,
+        TranscodeTerminal::Ndjson
 );
 enumdispatch_impl_log_to_file!(
     lifetimes: 'config: 'scope, 'scope, 'scope_env: 'scope,
@@ -286,7 +294,8 @@ enumdispatch_impl_log_to_file!(
         TranscodeTerminal<'config, 'scope>,
     implement variants
         TranscodeTerminal::Bare,
-        TranscodeTerminal::Fancy
+        TranscodeTerminal::Fancy,
+        TranscodeTerminal::Ndjson
 );
 enumdispatch_impl_user_controllable!(
     lifetimes: 'config, 'scope,
@@ -294,7 +303,8 @@ enumdispatch_impl_user_controllable!(
         TranscodeTerminal<'config, 'scope>,
     implement variants
         TranscodeTerminal::Bare,
-        TranscodeTerminal::Fancy
+        TranscodeTerminal::Fancy,
+        TranscodeTerminal::Ndjson
 );
 enumdispatch_impl_transcode!(
     lifetimes: 'config, 'scope,
@@ -303,5 +313,6 @@ enumdispatch_impl_transcode!(
         TranscodeTerminal<'config, 'scope>,
     implement variants
         TranscodeTerminal::Bare,
-        TranscodeTerminal::Fancy
+        TranscodeTerminal::Fancy,
+        TranscodeTerminal::Ndjson
 );
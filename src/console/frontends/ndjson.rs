@@ -0,0 +1,679 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::Scope;
+
+use chrono::Local;
+use miette::{miette, Context, IntoDiagnostic, Result};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use super::shared::logging::{
+    initialize_log_file_for_log_output,
+    write_line_to_log_file,
+    LogFileOptions,
+    LogLevel,
+    SharedLogFileWriter,
+};
+use crate::console::frontends::shared::queue::{
+    AlbumQueueItem,
+    AlbumQueueItemFinishedResult,
+    FileQueueItem,
+    FileQueueItemErrorType,
+    FileQueueItemFinishedResult,
+    Queue,
+    QueueItem,
+    QueueItemID,
+    RenderableQueueItem,
+};
+use crate::console::frontends::shared::{Progress, ScanProgress};
+use crate::console::tracing_output::{
+    clear_tracing_log_sink,
+    format_tracing_line,
+    set_tracing_log_sink,
+};
+use crate::console::traits::{LogToFileBackend, UserControllableBackend};
+use crate::console::{
+    LogBackend,
+    TerminalBackend,
+    TranscodeBackend,
+    UserControlMessage,
+};
+
+
+/// State of a queue item as reported in NDJSON events.
+#[derive(Serialize, Copy, Clone)]
+#[serde(rename_all = "snake_case")]
+enum NdjsonQueueItemState {
+    Queued,
+    InProgress,
+    Finished,
+}
+
+/// A single machine-readable event, serialized as one line of JSON.
+/// The `event` field contains the event type (`album`, `file`, `progress` or `scan_progress`).
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum NdjsonEvent<'a> {
+    /// An album was queued, started or finished.
+    Album {
+        queue_item_id: u32,
+        album: &'a str,
+        num_audio_files: usize,
+        num_data_files: usize,
+        state: NdjsonQueueItemState,
+        /// Only set when `state` is `finished`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ok: Option<bool>,
+    },
+
+    /// A file was queued, started or finished.
+    File {
+        queue_item_id: u32,
+        file: &'a str,
+        album: &'a str,
+        state: NdjsonQueueItemState,
+        /// Only set when `state` is `finished`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ok: Option<bool>,
+        /// Only set when the file failed or was cancelled.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+        /// How far along an in-progress transcode is (between 0 and 1).
+        /// Only set on progress updates, which are emitted if ffmpeg reports its progress.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        progress: Option<f64>,
+    },
+
+    /// Overall file progress changed.
+    Progress {
+        total_files: usize,
+        audio_files_currently_processing: usize,
+        data_files_currently_processing: usize,
+        audio_files_finished_ok: usize,
+        data_files_finished_ok: usize,
+        audio_files_errored: usize,
+        data_files_errored: usize,
+    },
+
+    /// Scanning progress changed.
+    ScanProgress {
+        items_scanned: usize,
+        total_items: usize,
+    },
+}
+
+impl<'a> From<&'a Progress> for NdjsonEvent<'a> {
+    fn from(progress: &'a Progress) -> Self {
+        NdjsonEvent::Progress {
+            total_files: progress.total_files,
+            audio_files_currently_processing: progress
+                .audio_files_currently_processing,
+            data_files_currently_processing: progress
+                .data_files_currently_processing,
+            audio_files_finished_ok: progress.audio_files_finished_ok,
+            data_files_finished_ok: progress.data_files_finished_ok,
+            audio_files_errored: progress.audio_files_errored,
+            data_files_errored: progress.data_files_errored,
+        }
+    }
+}
+
+/// An `NdjsonEvent` along with the time it was emitted (RFC 3339).
+#[derive(Serialize)]
+struct NdjsonEventLine<'a> {
+    timestamp: String,
+
+    #[serde(flatten)]
+    event: NdjsonEvent<'a>,
+}
+
+
+struct NdjsonState<'config> {
+    album_queue:
+        Option<Queue<AlbumQueueItem<'config>, AlbumQueueItemFinishedResult>>,
+
+    file_queue:
+        Option<Queue<FileQueueItem<'config>, FileQueueItemFinishedResult>>,
+
+    progress: Option<Progress>,
+
+    scan_progress: Option<ScanProgress>,
+}
+
+/// A terminal backend for external tooling (`--progress-format ndjson`).
+///
+/// Every queue and progress transition is written to stdout as a single line of JSON
+/// (newline-delimited JSON, see `NdjsonEvent`), while the human-readable log output
+/// goes to stderr (and the log file, if enabled), so the stdout stream stays pure JSON.
+pub struct NdjsonTerminalBackend<'config> {
+    state: RwLock<NdjsonState<'config>>,
+
+    /// If log file output is enabled, this contains the mutex in front of the file writer.
+    log_file_output: SharedLogFileWriter,
+
+    broadcast_sender: Mutex<broadcast::Sender<UserControlMessage>>,
+}
+
+impl<'config> NdjsonTerminalBackend<'config> {
+    pub fn new() -> Self {
+        let (broadcast_sender, _) = broadcast::channel(1);
+
+        Self {
+            state: RwLock::new(NdjsonState {
+                album_queue: None,
+                file_queue: None,
+                progress: None,
+                scan_progress: None,
+            }),
+            log_file_output: Arc::new(Mutex::new(None)),
+            broadcast_sender: Mutex::new(broadcast_sender),
+        }
+    }
+
+    /// Write a single event to stdout as one line of JSON.
+    fn emit_event(&self, event: NdjsonEvent) -> Result<()> {
+        let event_line = NdjsonEventLine {
+            timestamp: Local::now().to_rfc3339(),
+            event,
+        };
+
+        let serialized_event = serde_json::to_string(&event_line)
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to serialize NDJSON event."))?;
+
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{serialized_event}")
+            .and_then(|_| stdout.flush())
+            .into_diagnostic()
+            .wrap_err_with(|| miette!("Failed to write NDJSON event to stdout."))
+    }
+
+    /// Apply `update` to the progress and emit a `progress` event if anything changed.
+    fn update_progress<F: FnOnce(&mut Progress)>(
+        &self,
+        update: F,
+    ) -> Result<()> {
+        let mut locked_state = self.state.write();
+        let progress = locked_state.progress.as_mut().ok_or_else(|| {
+            miette!("Progress bar is disabled, can't update progress.")
+        })?;
+
+        let previous_progress = *progress;
+        update(progress);
+
+        if *progress != previous_progress {
+            self.emit_event(NdjsonEvent::from(&*progress))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'config, 'scope, 'scope_env: 'scope> TerminalBackend<'scope, 'scope_env>
+    for NdjsonTerminalBackend<'config>
+{
+    fn setup(&self, _scope: &'scope Scope<'scope, 'scope_env>) -> Result<()> {
+        // `tracing` events are logged just like the rest of the output.
+        let log_file_output = self.log_file_output.clone();
+        set_tracing_log_sink(Box::new(move |level, line| {
+            eprintln!("{}", format_tracing_line(level, &line));
+            write_line_to_log_file(&log_file_output, level, &line);
+        }));
+
+        Ok(())
+    }
+
+    fn destroy(self) -> Result<()> {
+        clear_tracing_log_sink();
+
+        // If logging to file was enabled, we should disable it before this backend is dropped,
+        // otherwise we risk failing to flush to file.
+        self.disable_saving_logs_to_file()?;
+
+        Ok(())
+    }
+}
+
+impl<'config> LogBackend for NdjsonTerminalBackend<'config> {
+    fn log_newline(&self) {
+        eprintln!();
+
+        if let Some(writer) = self.log_file_output.lock().as_mut() {
+            writer.write_newline().expect("Could not write to logfile.");
+        }
+    }
+
+    fn log_println<D: Display>(&self, content: D) {
+        let content_string = content.to_string();
+
+        eprintln!("{content_string}");
+
+        write_line_to_log_file(
+            &self.log_file_output,
+            LogLevel::from_log_message(&content_string),
+            &content_string,
+        );
+    }
+}
+
+impl<'config> TranscodeBackend<'config> for NdjsonTerminalBackend<'config> {
+    /*
+     * Album queue
+     */
+    fn queue_album_enable(&self) {
+        let mut locked_state = self.state.write();
+        locked_state.album_queue = Some(Queue::new());
+    }
+
+    fn queue_album_disable(&self) {
+        let mut locked_state = self.state.write();
+        locked_state.album_queue = None;
+    }
+
+    fn queue_album_clear(&self) -> Result<()> {
+        let mut locked_state = self.state.write();
+        locked_state
+            .album_queue
+            .as_mut()
+            .ok_or_else(|| miette!("Album queue is disabled, can't clear."))?
+            .clear();
+
+        Ok(())
+    }
+
+    fn queue_album_item_add(
+        &self,
+        item: AlbumQueueItem<'config>,
+    ) -> Result<QueueItemID> {
+        let item_id = item.get_id();
+
+        self.emit_event(NdjsonEvent::Album {
+            queue_item_id: *item_id,
+            album: &item.render(),
+            num_audio_files: item.num_changed_audio_files,
+            num_data_files: item.num_changed_data_files,
+            state: NdjsonQueueItemState::Queued,
+            ok: None,
+        })?;
+
+        let mut locked_state = self.state.write();
+        locked_state
+            .album_queue
+            .as_mut()
+            .ok_or_else(|| miette!("Album queue is disabled, can't add item."))?
+            .queue_item(item)?;
+
+        Ok(item_id)
+    }
+
+    fn queue_album_item_start(&self, item_id: QueueItemID) -> Result<()> {
+        let mut locked_state = self.state.write();
+        let album_queue =
+            locked_state.album_queue.as_mut().ok_or_else(|| {
+                miette!("Album queue is disabled, can't start item.")
+            })?;
+
+        album_queue.start_item(item_id)?;
+
+        let item = album_queue
+            .item(item_id)
+            .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
+
+        self.emit_event(NdjsonEvent::Album {
+            queue_item_id: *item_id,
+            album: &item.render(),
+            num_audio_files: item.num_changed_audio_files,
+            num_data_files: item.num_changed_data_files,
+            state: NdjsonQueueItemState::InProgress,
+            ok: None,
+        })
+    }
+
+    fn queue_album_item_finish(
+        &self,
+        item_id: QueueItemID,
+        result: AlbumQueueItemFinishedResult,
+    ) -> Result<()> {
+        let mut locked_state = self.state.write();
+        let album_queue =
+            locked_state.album_queue.as_mut().ok_or_else(|| {
+                miette!("Album queue is disabled, can't finish item.")
+            })?;
+
+        album_queue.finish_item(item_id, result)?;
+
+        let item = album_queue
+            .item(item_id)
+            .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
+
+        self.emit_event(NdjsonEvent::Album {
+            queue_item_id: *item_id,
+            album: &item.render(),
+            num_audio_files: item.num_changed_audio_files,
+            num_data_files: item.num_changed_data_files,
+            state: NdjsonQueueItemState::Finished,
+            ok: Some(result.ok),
+        })
+    }
+
+    fn queue_album_item_remove(
+        &self,
+        item_id: QueueItemID,
+    ) -> Result<AlbumQueueItem<'config>> {
+        let mut locked_state = self.state.write();
+        let album_queue =
+            locked_state.album_queue.as_mut().ok_or_else(|| {
+                miette!("Album queue is disabled, can't remove item.")
+            })?;
+
+        album_queue.remove_item(item_id)
+    }
+
+    /*
+     * File queue
+     */
+    fn queue_file_enable(&self) {
+        let mut locked_state = self.state.write();
+        locked_state.file_queue = Some(Queue::new());
+    }
+
+    fn queue_file_disable(&self) {
+        let mut locked_state = self.state.write();
+        locked_state.file_queue = None;
+    }
+
+    fn queue_file_clear(&self) -> Result<()> {
+        let mut locked_state = self.state.write();
+        locked_state
+            .file_queue
+            .as_mut()
+            .ok_or_else(|| miette!("File queue is disabled, can't clear."))?
+            .clear();
+
+        Ok(())
+    }
+
+    fn queue_file_item_add(
+        &self,
+        item: FileQueueItem<'config>,
+    ) -> Result<QueueItemID> {
+        let item_id = item.get_id();
+
+        self.emit_event(NdjsonEvent::File {
+            queue_item_id: *item_id,
+            file: &item.file_name,
+            album: &render_album_of_file(&item),
+            state: NdjsonQueueItemState::Queued,
+            ok: None,
+            error: None,
+            progress: None,
+        })?;
+
+        let mut locked_state = self.state.write();
+        locked_state
+            .file_queue
+            .as_mut()
+            .ok_or_else(|| miette!("File queue is disabled, can't add item."))?
+            .queue_item(item)?;
+
+        Ok(item_id)
+    }
+
+    fn queue_file_item_start(&self, item_id: QueueItemID) -> Result<()> {
+        let mut locked_state = self.state.write();
+        let file_queue = locked_state.file_queue.as_mut().ok_or_else(|| {
+            miette!("File queue is disabled, can't start item.")
+        })?;
+
+        file_queue.start_item(item_id)?;
+
+        let item = file_queue
+            .item(item_id)
+            .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
+
+        self.emit_event(NdjsonEvent::File {
+            queue_item_id: *item_id,
+            file: &item.file_name,
+            album: &render_album_of_file(item),
+            state: NdjsonQueueItemState::InProgress,
+            ok: None,
+            error: None,
+            progress: None,
+        })
+    }
+
+    fn queue_file_item_set_progress(
+        &self,
+        item_id: QueueItemID,
+        progress: f64,
+    ) -> Result<()> {
+        let mut locked_state = self.state.write();
+        let file_queue = locked_state.file_queue.as_mut().ok_or_else(|| {
+            miette!("File queue is disabled, can't update item progress.")
+        })?;
+
+        let item = file_queue
+            .item(item_id)
+            .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
+
+        self.emit_event(NdjsonEvent::File {
+            queue_item_id: *item_id,
+            file: &item.file_name,
+            album: &render_album_of_file(item),
+            state: NdjsonQueueItemState::InProgress,
+            ok: None,
+            error: None,
+            progress: Some(progress),
+        })
+    }
+
+    fn queue_file_item_finish(
+        &self,
+        item_id: QueueItemID,
+        result: FileQueueItemFinishedResult,
+    ) -> Result<()> {
+        let mut locked_state = self.state.write();
+        let file_queue = locked_state.file_queue.as_mut().ok_or_else(|| {
+            miette!("File queue is disabled, can't finish item.")
+        })?;
+
+        let error = match &result {
+            FileQueueItemFinishedResult::Ok => None,
+            FileQueueItemFinishedResult::Failed(
+                FileQueueItemErrorType::Cancelled,
+            ) => Some("cancelled".to_string()),
+            FileQueueItemFinishedResult::Failed(
+                FileQueueItemErrorType::Errored { error },
+            ) => Some(error.clone()),
+        };
+
+        file_queue.finish_item(item_id, result)?;
+
+        let item = file_queue
+            .item(item_id)
+            .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
+
+        self.emit_event(NdjsonEvent::File {
+            queue_item_id: *item_id,
+            file: &item.file_name,
+            album: &render_album_of_file(item),
+            state: NdjsonQueueItemState::Finished,
+            ok: Some(error.is_none()),
+            error: error.as_deref(),
+            progress: None,
+        })
+    }
+
+    fn queue_file_item_remove(
+        &self,
+        item_id: QueueItemID,
+    ) -> Result<FileQueueItem<'config>> {
+        let mut locked_state = self.state.write();
+        let file_queue = locked_state.file_queue.as_mut().ok_or_else(|| {
+            miette!("File queue is disabled, can't remove item.")
+        })?;
+
+        file_queue.remove_item(item_id)
+    }
+
+    /*
+     * Progress
+     */
+    fn progress_enable(&self) {
+        let mut locked_state = self.state.write();
+        locked_state.progress = Some(Progress::default());
+    }
+
+    fn progress_disable(&self) {
+        let mut locked_state = self.state.write();
+        locked_state.progress = None;
+    }
+
+    fn progress_set_total(&self, num_total: usize) -> Result<()> {
+        self.update_progress(|progress| progress.total_files = num_total)
+    }
+
+    fn progress_set_audio_files_currently_processing(
+        &self,
+        num_audio_files_currently_processing: usize,
+    ) -> Result<()> {
+        self.update_progress(|progress| {
+            progress.audio_files_currently_processing =
+                num_audio_files_currently_processing
+        })
+    }
+
+    fn progress_set_data_files_currently_processing(
+        &self,
+        num_data_files_currently_processing: usize,
+    ) -> Result<()> {
+        self.update_progress(|progress| {
+            progress.data_files_currently_processing =
+                num_data_files_currently_processing
+        })
+    }
+
+    fn progress_set_audio_files_finished_ok(
+        &self,
+        num_audio_files_finished_ok: usize,
+    ) -> Result<()> {
+        self.update_progress(|progress| {
+            progress.audio_files_finished_ok = num_audio_files_finished_ok
+        })
+    }
+
+    fn progress_set_data_files_finished_ok(
+        &self,
+        num_data_files_finished_ok: usize,
+    ) -> Result<()> {
+        self.update_progress(|progress| {
+            progress.data_files_finished_ok = num_data_files_finished_ok
+        })
+    }
+
+    fn progress_set_audio_files_errored(
+        &self,
+        num_audio_files_errored: usize,
+    ) -> Result<()> {
+        self.update_progress(|progress| {
+            progress.audio_files_errored = num_audio_files_errored
+        })
+    }
+
+    fn progress_set_data_files_errored(
+        &self,
+        num_data_files_errored: usize,
+    ) -> Result<()> {
+        self.update_progress(|progress| {
+            progress.data_files_errored = num_data_files_errored
+        })
+    }
+
+    /*
+     * Scan progress
+     */
+    fn scan_progress_enable(&self, num_total: usize) {
+        let mut locked_state = self.state.write();
+        locked_state.scan_progress = Some(ScanProgress::new(num_total));
+    }
+
+    fn scan_progress_disable(&self) {
+        let mut locked_state = self.state.write();
+        locked_state.scan_progress = None;
+    }
+
+    fn scan_progress_set_scanned(&self, num_scanned: usize) -> Result<()> {
+        let mut locked_state = self.state.write();
+
+        let scan_progress =
+            locked_state.scan_progress.as_mut().ok_or_else(|| {
+                miette!("Scan progress is disabled, can't set scanned items.")
+            })?;
+
+        if scan_progress.items_scanned == num_scanned {
+            return Ok(());
+        }
+        scan_progress.items_scanned = num_scanned;
+
+        self.emit_event(NdjsonEvent::ScanProgress {
+            items_scanned: scan_progress.items_scanned,
+            total_items: scan_progress.total_items,
+        })
+    }
+}
+
+/// Render the album a file queue item belongs to (as `Artist - Album`).
+fn render_album_of_file(item: &FileQueueItem) -> String {
+    let album_locked = item.album_view.read();
+
+    format!(
+        "{} - {}",
+        album_locked.read_lock_artist().name,
+        album_locked.title,
+    )
+}
+
+impl<'config> UserControllableBackend for NdjsonTerminalBackend<'config> {
+    fn get_user_control_receiver(
+        &self,
+    ) -> Result<broadcast::Receiver<UserControlMessage>> {
+        Ok(self.broadcast_sender.lock().subscribe())
+    }
+}
+
+impl<'config, 'scope, 'scope_env: 'scope> LogToFileBackend<'scope, 'scope_env>
+    for NdjsonTerminalBackend<'config>
+{
+    fn enable_saving_logs_to_file<P: AsRef<Path>>(
+        &self,
+        log_output_file_path: P,
+        options: &LogFileOptions,
+        _scope: &'scope Scope<'scope, 'scope_env>,
+    ) -> Result<()> {
+        let log_file_writer = initialize_log_file_for_log_output(
+            log_output_file_path.as_ref(),
+            options,
+        )
+        .wrap_err_with(|| {
+            miette!("Failed to initialize log file for log output.")
+        })?;
+
+        let mut locked_self_log_output = self.log_file_output.lock();
+        *locked_self_log_output = Some(log_file_writer);
+
+        Ok(())
+    }
+
+    fn disable_saving_logs_to_file(&self) -> Result<()> {
+        let mut locked_log_output = self.log_file_output.lock();
+
+        if let Some(writer) = locked_log_output.take() {
+            writer.finish()?;
+        }
+
+        Ok(())
+    }
+}
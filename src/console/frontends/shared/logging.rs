@@ -1,27 +1,192 @@
 use std::{
     env::args,
     fs::{self, File, OpenOptions},
-    io::{BufWriter, Write},
+    io::{self, BufWriter, Write},
     path::Path,
+    sync::Arc,
 };
 
-use chrono::Local;
+use chrono::Utc;
+use euphony_configuration::logging::LoggingConfiguration;
 use miette::{miette, Context, IntoDiagnostic, Result};
+use parking_lot::Mutex;
 use strip_ansi_escapes::Writer as StripAnsiWriter;
 
 use crate::EUPHONY_VERSION;
 
+/// Log messages starting with one of these (after their ANSI styling is stripped)
+/// are treated as warnings or errors (see `LogLevel::from_log_message`).
+const WARNING_LOG_PREFIXES: [&str; 2] = ["WARNING", "WARN:"];
+const ERROR_LOG_PREFIXES: [&str; 2] = ["ERROR", "REPORT THIS BUG"];
+
+
+/// Log file writer shared between a terminal backend and its tracing log sink
+/// (see `set_tracing_log_sink`). `None` while saving logs to a file is disabled.
+pub type SharedLogFileWriter = Arc<Mutex<Option<LogFileWriter>>>;
+
+
+/// Level of a log line, written into the log file along with its timestamp.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    /// Classify a plain log message (i.e. not a `tracing` event) by its (unstyled) prefix:
+    /// e.g. `WARNING: ...` is a warning. Messages without such a prefix are informational.
+    pub fn from_log_message(message: &str) -> Self {
+        let unstyled_message = strip_ansi_escapes::strip_str(message);
+        let unstyled_message = unstyled_message.trim_start();
+
+        if ERROR_LOG_PREFIXES
+            .iter()
+            .any(|prefix| unstyled_message.starts_with(prefix))
+        {
+            Self::Error
+        } else if WARNING_LOG_PREFIXES
+            .iter()
+            .any(|prefix| unstyled_message.starts_with(prefix))
+        {
+            Self::Warning
+        } else {
+            Self::Info
+        }
+    }
+
+    /// The tag written into the log file, e.g. `INFO`.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Self::Trace => "TRACE",
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warning => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+}
+
+impl From<tracing::Level> for LogLevel {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::TRACE => Self::Trace,
+            tracing::Level::DEBUG => Self::Debug,
+            tracing::Level::INFO => Self::Info,
+            tracing::Level::WARN => Self::Warning,
+            tracing::Level::ERROR => Self::Error,
+        }
+    }
+}
+
+
+/// How the log file is written (see `initialize_log_file_for_log_output`).
+#[derive(Clone, Debug)]
+pub struct LogFileOptions {
+    /// Format of the UTC timestamp each line is prefixed with
+    /// (see `logging.log_file_timestamp_format`).
+    pub timestamp_format: String,
+
+    /// Whether to append to an existing log file instead of overwriting it
+    /// (see `logging.append_to_existing_log_file`).
+    pub append: bool,
+}
+
+impl LogFileOptions {
+    pub fn from_configuration(configuration: &LoggingConfiguration) -> Self {
+        Self {
+            timestamp_format: configuration.log_file_timestamp_format.clone(),
+            append: configuration.append_to_existing_log_file,
+        }
+    }
+}
+
+
+/// Writes log lines into the log file, prefixing each of them with its timestamp
+/// and level, e.g. `[2024-01-02T15:04:05Z] [INFO] Scanning libraries...`.
+/// ANSI styling is stripped.
+pub struct LogFileWriter {
+    writer: BufWriter<StripAnsiWriter<File>>,
+
+    timestamp_format: String,
+}
+
+impl LogFileWriter {
+    fn new(file: File, options: &LogFileOptions) -> Self {
+        Self {
+            writer: BufWriter::with_capacity(
+                1024,
+                strip_ansi_escapes::Writer::new(file),
+            ),
+            timestamp_format: options.timestamp_format.clone(),
+        }
+    }
+
+    /// Write a single (prefixed) line.
+    pub fn write_line(&mut self, level: LogLevel, line: &str) -> io::Result<()> {
+        let formatted_time_now = Utc::now().format(&self.timestamp_format);
+
+        writeln!(
+            self.writer,
+            "[{}] [{}] {}",
+            formatted_time_now,
+            level.tag(),
+            line
+        )
+    }
+
+    /// Write an empty line (without a prefix).
+    pub fn write_newline(&mut self) -> io::Result<()> {
+        self.writer.write_all("\n".as_bytes())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flush all buffered lines and then the file itself.
+    pub fn finish(self) -> Result<()> {
+        let mut file = self
+            .writer
+            .into_inner()
+            .map_err(|_| miette!("Failed to unwrap the BufWriter."))?
+            .into_inner()
+            .map_err(|_| miette!("Failed to unwrap the ansi escape writer."))?;
+
+        file.flush().into_diagnostic().wrap_err_with(|| {
+            miette!("Failed to perform final flush on the File.")
+        })
+    }
+}
+
+
+/// Write a single line into the log file, if saving logs to a file is enabled.
+pub fn write_line_to_log_file(
+    log_file_output: &SharedLogFileWriter,
+    level: LogLevel,
+    line: &str,
+) {
+    if let Some(writer) = log_file_output.lock().as_mut() {
+        writer
+            .write_line(level, line)
+            .expect("Could not write to logfile.");
+    }
+}
+
 // TODO Extract code from enable_saving_logs_to_file.
 /// Prepares the log file for log output.
 /// This involves opening the file for writing
 /// (creating it if necessary). If the file already exists,
-/// is is opened in append mode.
+/// is is opened in append mode or truncated, depending on `options.append`.
 ///
 /// A small invocation header is written to the log file before the writer
 /// handle is returned.
 pub fn initialize_log_file_for_log_output(
     log_output_file_path: &Path,
-) -> Result<BufWriter<StripAnsiWriter<File>>> {
+    options: &LogFileOptions,
+) -> Result<LogFileWriter> {
     let log_output_directory_path = log_output_file_path
         .parent()
         .ok_or_else(|| miette!("No log file parent directory?!"))?;
@@ -39,7 +204,7 @@ pub fn initialize_log_file_for_log_output(
     }
 
     let output_file = match log_output_file_path.exists() {
-        true => OpenOptions::new()
+        true if options.append => OpenOptions::new()
             .append(true)
             .open(log_output_file_path)
             .into_diagnostic()
@@ -52,6 +217,17 @@ pub fn initialize_log_file_for_log_output(
             .wrap_err_with(|| {
                 miette!("Failed to open existing log file for writing.")
             })?,
+        true => File::create(log_output_file_path)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                miette!(
+                    "Failed to open log output file for overwriting: {:?}",
+                    log_output_file_path
+                )
+            })
+            .wrap_err_with(|| {
+                miette!("Failed to open existing log file for writing.")
+            })?,
         false => OpenOptions::new()
             .create_new(true)
             .write(true)
@@ -66,27 +242,49 @@ pub fn initialize_log_file_for_log_output(
             .wrap_err_with(|| miette!("Failed to create and open log file."))?,
     };
 
-    let ansi_escaping_writer = strip_ansi_escapes::Writer::new(output_file);
-    let mut buf_writer = BufWriter::with_capacity(1024, ansi_escaping_writer);
+    let mut log_file_writer = LogFileWriter::new(output_file, options);
 
     // Write an "invocation header", marking the start of euphony.
-    let time_now = Local::now();
-    let formatted_time_now = time_now.format("%Y-%m-%d %H:%M:%S%.3f");
-
-    buf_writer
-        .write_all(
-            format!(
-                "{} Hello from euphony {}. Started with arguments: {:?}",
-                formatted_time_now,
+    log_file_writer
+        .write_line(
+            LogLevel::Info,
+            &format!(
+                "Hello from euphony {}. Started with arguments: {:?}",
                 EUPHONY_VERSION,
                 args()
-            )
-            .as_bytes(),
+            ),
         )
         .into_diagnostic()
         .wrap_err_with(|| {
             miette!("Could not write invocation header to file.")
         })?;
 
-    Ok(buf_writer)
+    Ok(log_file_writer)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+
+    #[test]
+    fn classifies_log_messages_by_their_prefix() {
+        assert_eq!(
+            LogLevel::from_log_message(&format!(
+                "{} Something is off.",
+                "WARNING:".yellow()
+            )),
+            LogLevel::Warning
+        );
+        assert_eq!(
+            LogLevel::from_log_message("  ERROR: Could not copy."),
+            LogLevel::Error
+        );
+        assert_eq!(
+            LogLevel::from_log_message("Warnings are fine."),
+            LogLevel::Info
+        );
+    }
 }
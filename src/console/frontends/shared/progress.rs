@@ -1,3 +1,10 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How many of the most recent completions `RollingRemainingTimeEstimator` averages over.
+const ROLLING_ESTIMATE_WINDOW: usize = 8;
+
+
 /// A small progress bar abstraction that contains just two fields: `current` out of `total` progress.
 #[derive(Default, Copy, Clone, Eq, PartialEq)]
 pub struct Progress {
@@ -37,13 +44,201 @@ impl Progress {
         self.total_files.saturating_sub(self.total_not_pending())
     }
 
-    /// Get progress percentage.
+    /// Get progress as a ratio between `0.0` and `1.0`.
+    ///
+    /// An empty run (`total_files == 0`) has a ratio of `0.0`. The ratio is also clamped to `1.0`,
+    /// as the progress gauge panics on ratios outside that range.
     #[inline]
     pub fn completion_ratio(&self) -> f64 {
         if self.total_files == 0 {
             0f64
         } else {
-            self.total_finished_or_errored() as f64 / self.total_files as f64
+            (self.total_finished_or_errored() as f64 / self.total_files as f64)
+                .min(1f64)
+        }
+    }
+
+    /// Estimate the remaining time by extrapolating the rate at which files have been
+    /// finished so far (`elapsed` is the time since processing started).
+    ///
+    /// Returns `None` until at least one file has finished.
+    pub fn estimate_remaining_time(
+        &self,
+        elapsed: Duration,
+    ) -> Option<Duration> {
+        let num_finished = self.total_finished_or_errored();
+        if num_finished == 0 {
+            return None;
+        }
+
+        let num_remaining = self.total_files.saturating_sub(num_finished);
+
+        Some(elapsed.mul_f64(num_remaining as f64 / num_finished as f64))
+    }
+}
+
+
+/// Estimates the remaining time of a run from the intervals between its most recently
+/// completed items (e.g. albums).
+///
+/// Unlike extrapolating from the entire run, the rolling average adapts when the items
+/// get larger or smaller (e.g. a library of lossless albums following one of MP3 albums).
+/// As items are processed in parallel, intervals between completions are used instead
+/// of the durations of individual items.
+#[derive(Clone, Debug)]
+pub struct RollingRemainingTimeEstimator {
+    last_completion_at: Instant,
+
+    recent_intervals: VecDeque<Duration>,
+}
+
+impl RollingRemainingTimeEstimator {
+    /// Start estimating; the first completion is measured from `started_at`.
+    pub fn new(started_at: Instant) -> Self {
+        Self {
+            last_completion_at: started_at,
+            recent_intervals: VecDeque::with_capacity(ROLLING_ESTIMATE_WINDOW),
+        }
+    }
+
+    /// Record an item that has been completed at `completed_at`.
+    pub fn record_completion(&mut self, completed_at: Instant) {
+        if self.recent_intervals.len() == ROLLING_ESTIMATE_WINDOW {
+            self.recent_intervals.pop_front();
+        }
+
+        self.recent_intervals.push_back(
+            completed_at.saturating_duration_since(self.last_completion_at),
+        );
+        self.last_completion_at = completed_at;
+    }
+
+    /// Estimate how long the `num_remaining` items will take.
+    ///
+    /// Returns `None` until at least one item has been completed.
+    pub fn estimate_remaining_time(
+        &self,
+        num_remaining: usize,
+    ) -> Option<Duration> {
+        if self.recent_intervals.is_empty() {
+            return None;
         }
+
+        let average_interval = self.recent_intervals.iter().sum::<Duration>()
+            / self.recent_intervals.len() as u32;
+
+        Some(average_interval * num_remaining as u32)
+    }
+}
+
+
+/// Progress of a scanning phase (e.g. scanning albums for changes) that happens
+/// before any files are processed: `items_scanned` out of `total_items`.
+#[derive(Default, Copy, Clone, Eq, PartialEq)]
+pub struct ScanProgress {
+    pub total_items: usize,
+
+    pub items_scanned: usize,
+}
+
+impl ScanProgress {
+    pub fn new(total_items: usize) -> Self {
+        Self {
+            total_items,
+            items_scanned: 0,
+        }
+    }
+
+    /// Get progress as a ratio between `0.0` and `1.0`
+    /// (see `Progress::completion_ratio` for edge cases).
+    #[inline]
+    pub fn completion_ratio(&self) -> f64 {
+        if self.total_items == 0 {
+            0f64
+        } else {
+            (self.items_scanned as f64 / self.total_items as f64).min(1f64)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completion_ratio_is_zero_when_total_is_zero() {
+        let progress = Progress::default();
+        assert_eq!(progress.completion_ratio(), 0f64);
+
+        let progress = Progress {
+            audio_files_finished_ok: 3,
+            ..Default::default()
+        };
+        assert_eq!(progress.completion_ratio(), 0f64);
+    }
+
+    #[test]
+    fn completion_ratio_is_clamped_to_one() {
+        let progress = Progress {
+            total_files: 2,
+            audio_files_finished_ok: 2,
+            data_files_errored: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(progress.completion_ratio(), 1f64);
+    }
+
+    #[test]
+    fn remaining_time_is_extrapolated_from_finished_files() {
+        let progress = Progress {
+            total_files: 10,
+            audio_files_finished_ok: 3,
+            data_files_errored: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            progress.estimate_remaining_time(Duration::from_secs(20)),
+            Some(Duration::from_secs(30))
+        );
+
+        let progress = Progress {
+            total_files: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            progress.estimate_remaining_time(Duration::from_secs(20)),
+            None
+        );
+    }
+
+    #[test]
+    fn rolling_estimate_only_averages_recent_completions() {
+        let started_at = Instant::now();
+        let mut estimator = RollingRemainingTimeEstimator::new(started_at);
+        assert_eq!(estimator.estimate_remaining_time(5), None);
+
+        // One slow album, followed by a full window of fast ones.
+        let mut completed_at = started_at + Duration::from_secs(100);
+        estimator.record_completion(completed_at);
+        assert_eq!(
+            estimator.estimate_remaining_time(2),
+            Some(Duration::from_secs(200))
+        );
+
+        for _ in 0..ROLLING_ESTIMATE_WINDOW {
+            completed_at += Duration::from_secs(4);
+            estimator.record_completion(completed_at);
+        }
+
+        assert_eq!(
+            estimator.estimate_remaining_time(5),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            estimator.estimate_remaining_time(0),
+            Some(Duration::ZERO)
+        );
     }
 }
@@ -134,6 +134,10 @@ impl AlbumQueueItemFinishedResult {
     pub fn new_ok() -> Self {
         Self { ok: true }
     }
+
+    pub fn new_failed() -> Self {
+        Self { ok: false }
+    }
 }
 
 
@@ -14,19 +14,14 @@ use crate::console::colours::{
     X060_MEDIUM_PURPLE4,
     X064_CHARTREUSE4,
     X065_DARK_SEA_GREEN4,
-    X095_LIGHT_PINK4,
-    X106_YELLOW4,
     X107_DARK_OLIVE_GREEN3,
     X147_LIGHT_STEEL_BLUE,
     X188_GREY84,
     X209_SALMON1,
-    X237_GREY23,
     X242_GREY42,
-    X244_GREY50,
     X245_GREY54,
-    X246_GREY58,
-    X248_GREY66,
 };
+use crate::console::formatting::truncate_to_display_width;
 use crate::console::frontends::shared::queue::{
     AlbumQueueItem,
     AlbumQueueItemFinishedResult,
@@ -42,6 +37,76 @@ use crate::console::frontends::shared::queue::{
 use crate::console::frontends::shared::{AnimatedSpinner, SpinnerStyle};
 
 
+/// Maximum display widths (in terminal columns) of the names in rendered queue items.
+/// Longer names are truncated with an ellipsis (see `truncate_to_display_width`).
+const ALBUM_ITEM_MAXIMUM_ARTIST_NAME_WIDTH: usize = 32;
+const ALBUM_ITEM_MAXIMUM_ALBUM_TITLE_WIDTH: usize = 42;
+const FILE_ITEM_MAXIMUM_FILE_NAME_WIDTH: usize = 42;
+
+
+/// Styles of a rendered queue item, depending on its state (see `QueueItemStyleRules::for_state`).
+/// Album and file items share these, so that a state looks the same in both queues.
+struct QueueItemStyleRules {
+    /// Style of the spinner / state prefix (and the file action, e.g. `[t]`).
+    prefix: Style,
+
+    /// Style of the main content (the album's artist and title or the file name).
+    primary: Style,
+
+    /// Style of the secondary information (album changes, file progress and result).
+    secondary: Style,
+}
+
+impl QueueItemStyleRules {
+    fn for_state(state: GenericQueueItemState) -> &'static Self {
+        match state {
+            GenericQueueItemState::Pending | GenericQueueItemState::Queued => {
+                &QUEUE_ITEM_PENDING_STYLE_RULES
+            }
+            GenericQueueItemState::InProgress => {
+                &QUEUE_ITEM_IN_PROGRESS_STYLE_RULES
+            }
+            GenericQueueItemState::Finished { ok: true } => {
+                &QUEUE_ITEM_FINISHED_OK_STYLE_RULES
+            }
+            GenericQueueItemState::Finished { ok: false } => {
+                &QUEUE_ITEM_FINISHED_FAILED_STYLE_RULES
+            }
+        }
+    }
+}
+
+const QUEUE_ITEM_PENDING_STYLE_RULES: QueueItemStyleRules =
+    QueueItemStyleRules {
+        prefix: X242_GREY42,
+        primary: X245_GREY54,
+        secondary: X242_GREY42,
+    };
+
+const QUEUE_ITEM_IN_PROGRESS_STYLE_RULES: QueueItemStyleRules =
+    QueueItemStyleRules {
+        prefix: X147_LIGHT_STEEL_BLUE,
+        primary: X188_GREY84,
+        secondary: X060_MEDIUM_PURPLE4,
+    };
+
+const QUEUE_ITEM_FINISHED_OK_STYLE_RULES: QueueItemStyleRules =
+    QueueItemStyleRules {
+        prefix: X107_DARK_OLIVE_GREEN3,
+        primary: X064_CHARTREUSE4,
+        secondary: X065_DARK_SEA_GREEN4,
+    };
+
+// Failed items should stand out (so problems can be spotted without reading the logs).
+const QUEUE_ITEM_FINISHED_FAILED_STYLE_RULES: QueueItemStyleRules =
+    QueueItemStyleRules {
+        prefix: X209_SALMON1.add_modifier(Modifier::BOLD),
+        primary: X009_RED.add_modifier(Modifier::BOLD),
+        secondary: X209_SALMON1,
+    };
+
+
+
 pub struct FancyAlbumQueueItem<'config> {
     pub item: AlbumQueueItem<'config>,
 
@@ -98,20 +163,6 @@ impl<'config> QueueItem<AlbumQueueItemFinishedResult>
     }
 }
 
-const ALBUM_ITEM_HEADER_PENDING_STYLE: Style = X248_GREY66;
-const ALBUM_ITEM_PREFIX_PENDING_STYLE: Style = ALBUM_ITEM_HEADER_PENDING_STYLE;
-const ALBUM_ITEM_CHANGES_PENDING_STYLE: Style = X245_GREY54;
-
-const ALBUM_ITEM_HEADER_IN_PROGRESS_STYLE: Style = X147_LIGHT_STEEL_BLUE;
-const ALBUM_ITEM_PREFIX_IN_PROGRESS_STYLE: Style =
-    ALBUM_ITEM_HEADER_IN_PROGRESS_STYLE;
-const ALBUM_ITEM_CHANGES_IN_PROGRESS_STYLE: Style = X060_MEDIUM_PURPLE4;
-
-const ALBUM_ITEM_HEADER_FINISHED_STYLE: Style = X064_CHARTREUSE4;
-const ALBUM_ITEM_PREFIX_FINISHED_STYLE: Style = ALBUM_ITEM_HEADER_FINISHED_STYLE;
-const ALBUM_ITEM_CHANGES_FINISHED_STYLE: Style = X065_DARK_SEA_GREEN4;
-
-
 impl<'config, 'text> RenderableQueueItem<Text<'text>>
     for FancyAlbumQueueItem<'config>
 {
@@ -124,7 +175,8 @@ impl<'config, 'text> RenderableQueueItem<Text<'text>>
                 AlbumQueueItemState::Pending => "   ",
                 AlbumQueueItemState::Queued => "   ",
                 AlbumQueueItemState::InProgress => " R ",
-                AlbumQueueItemState::Finished { .. } => " F ",
+                AlbumQueueItemState::Finished { ok: true } => " F ",
+                AlbumQueueItemState::Finished { ok: false } => " E ",
             }
             .to_string(),
         };
@@ -132,39 +184,28 @@ impl<'config, 'text> RenderableQueueItem<Text<'text>>
         let locked_album_view = self.item.album_view.read();
         let locked_artist_view = locked_album_view.read_lock_artist();
 
-        let (prefix_style, header_style, changes_style) = match self.item.state {
-            AlbumQueueItemState::Pending => (
-                ALBUM_ITEM_PREFIX_PENDING_STYLE,
-                ALBUM_ITEM_HEADER_PENDING_STYLE,
-                ALBUM_ITEM_CHANGES_PENDING_STYLE,
-            ),
-            AlbumQueueItemState::Queued => (
-                ALBUM_ITEM_PREFIX_PENDING_STYLE,
-                ALBUM_ITEM_HEADER_PENDING_STYLE,
-                ALBUM_ITEM_CHANGES_PENDING_STYLE,
-            ),
-            AlbumQueueItemState::InProgress => (
-                ALBUM_ITEM_PREFIX_IN_PROGRESS_STYLE,
-                ALBUM_ITEM_HEADER_IN_PROGRESS_STYLE,
-                ALBUM_ITEM_CHANGES_IN_PROGRESS_STYLE,
-            ),
-            AlbumQueueItemState::Finished { .. } => (
-                ALBUM_ITEM_PREFIX_FINISHED_STYLE,
-                ALBUM_ITEM_HEADER_FINISHED_STYLE,
-                ALBUM_ITEM_CHANGES_FINISHED_STYLE,
-            ),
-        };
-
+        let style_rules = QueueItemStyleRules::for_state(self.get_state());
 
         Text::from(vec![
             Line::from(vec![
-                Span::styled(potential_spinner_prefix, prefix_style),
+                Span::styled(potential_spinner_prefix, style_rules.prefix),
                 // TODO Wrap onto new line automatically (with maximum wrap of two lines)
-                Span::styled(locked_artist_view.name.to_string(), header_style),
-                Span::styled(" - ", header_style),
                 Span::styled(
-                    locked_album_view.title.to_string(),
-                    header_style.add_modifier(Modifier::BOLD),
+                    truncate_to_display_width(
+                        &locked_artist_view.name,
+                        ALBUM_ITEM_MAXIMUM_ARTIST_NAME_WIDTH,
+                    )
+                    .into_owned(),
+                    style_rules.primary,
+                ),
+                Span::styled(" - ", style_rules.primary),
+                Span::styled(
+                    truncate_to_display_width(
+                        &locked_album_view.title,
+                        ALBUM_ITEM_MAXIMUM_ALBUM_TITLE_WIDTH,
+                    )
+                    .into_owned(),
+                    style_rules.primary.add_modifier(Modifier::BOLD),
                 ),
             ]),
             Line::from(vec![Span::styled(
@@ -173,7 +214,7 @@ impl<'config, 'text> RenderableQueueItem<Text<'text>>
                     self.item.num_changed_audio_files,
                     self.item.num_changed_data_files,
                 ),
-                changes_style,
+                style_rules.secondary,
             )]),
         ])
     }
@@ -185,6 +226,10 @@ pub struct FancyFileQueueItem<'config> {
     pub item: FileQueueItem<'config>,
 
     pub spinner: Option<AnimatedSpinner>,
+
+    /// How far along the file is (between 0 and 1), if known.
+    /// Only transcodes report their progress, and only while in progress.
+    pub progress: Option<f64>,
 }
 
 impl<'config> FancyFileQueueItem<'config> {
@@ -192,6 +237,7 @@ impl<'config> FancyFileQueueItem<'config> {
         Self {
             item: queue_item,
             spinner: None,
+            progress: None,
         }
     }
 
@@ -233,34 +279,11 @@ impl<'config> QueueItem<FileQueueItemFinishedResult>
     fn on_item_finished(&mut self, result: FileQueueItemFinishedResult) {
         self.item.on_item_finished(result);
         self.disable_spinner();
+        self.progress = None;
     }
 }
 
 
-const FILE_ITEM_CONTENT_PENDING_STYLE: Style = X244_GREY50;
-const FILE_ITEM_PREFIX_PENDING_STYLE: Style = X242_GREY42;
-const FILE_ITEM_ACTION_PENDING_STYLE: Style = X242_GREY42;
-const FILE_ITEM_EXPLAINER_PENDING_STYLE: Style = X242_GREY42;
-
-
-const FILE_ITEM_CONTENT_IN_PROGRESS_STYLE: Style = X188_GREY84;
-const FILE_ITEM_PREFIX_IN_PROGRESS_STYLE: Style = X248_GREY66;
-const FILE_ITEM_ACTION_IN_PROGRESS_STYLE: Style = X248_GREY66;
-const FILE_ITEM_EXPLAINER_IN_PROGRESS_STYLE: Style = X246_GREY58;
-
-
-const FILE_ITEM_CONTENT_FINISHED_OK_STYLE: Style = X106_YELLOW4;
-const FILE_ITEM_PREFIX_FINISHED_OK_STYLE: Style = X107_DARK_OLIVE_GREEN3;
-const FILE_ITEM_ACTION_FINISHED_OK_STYLE: Style = X107_DARK_OLIVE_GREEN3;
-const FILE_ITEM_EXPLAINER_FINISHED_OK_STYLE: Style = X237_GREY23;
-
-
-const FILE_ITEM_CONTENT_FINISHED_ERROR_STYLE: Style = X009_RED;
-const FILE_ITEM_PREFIX_FINISHED_ERROR_STYLE: Style = X209_SALMON1;
-const FILE_ITEM_ACTION_FINISHED_ERROR_STYLE: Style = X209_SALMON1;
-const FILE_ITEM_EXPLAINER_FINISHED_ERROR_STYLE: Style = X095_LIGHT_PINK4;
-
-
 impl<'config, 'text> RenderableQueueItem<Text<'text>>
     for FancyFileQueueItem<'config>
 {
@@ -287,49 +310,21 @@ impl<'config, 'text> RenderableQueueItem<Text<'text>>
             FileProcessingAction::DeleteInTranscoded { .. } => "[d]",
         };
 
-        let (prefix_style, action_style, content_style, explainer_style) =
-            match self.item.state {
-                FileQueueItemState::Pending => (
-                    FILE_ITEM_PREFIX_PENDING_STYLE,
-                    FILE_ITEM_ACTION_PENDING_STYLE,
-                    FILE_ITEM_CONTENT_PENDING_STYLE,
-                    FILE_ITEM_EXPLAINER_PENDING_STYLE,
-                ),
-                FileQueueItemState::Queued => (
-                    FILE_ITEM_PREFIX_PENDING_STYLE,
-                    FILE_ITEM_ACTION_PENDING_STYLE,
-                    FILE_ITEM_CONTENT_PENDING_STYLE,
-                    FILE_ITEM_EXPLAINER_PENDING_STYLE,
-                ),
-                FileQueueItemState::InProgress => (
-                    FILE_ITEM_PREFIX_IN_PROGRESS_STYLE,
-                    FILE_ITEM_ACTION_IN_PROGRESS_STYLE,
-                    FILE_ITEM_CONTENT_IN_PROGRESS_STYLE,
-                    FILE_ITEM_EXPLAINER_IN_PROGRESS_STYLE,
-                ),
-                FileQueueItemState::Finished { ref result } => match result {
-                    FileQueueItemFinishedResult::Ok => (
-                        FILE_ITEM_PREFIX_FINISHED_OK_STYLE,
-                        FILE_ITEM_ACTION_FINISHED_OK_STYLE,
-                        FILE_ITEM_CONTENT_FINISHED_OK_STYLE,
-                        FILE_ITEM_EXPLAINER_FINISHED_OK_STYLE,
-                    ),
-                    FileQueueItemFinishedResult::Failed(_) => (
-                        FILE_ITEM_PREFIX_FINISHED_ERROR_STYLE,
-                        FILE_ITEM_ACTION_FINISHED_ERROR_STYLE,
-                        FILE_ITEM_CONTENT_FINISHED_ERROR_STYLE,
-                        FILE_ITEM_EXPLAINER_FINISHED_ERROR_STYLE,
-                    ),
-                },
-            };
+        let style_rules = QueueItemStyleRules::for_state(self.get_state());
 
         let mut primary_line = vec![
-            Span::styled(potential_spinner_prefix, prefix_style),
-            Span::styled(action_str, action_style),
+            Span::styled(potential_spinner_prefix, style_rules.prefix),
+            Span::styled(action_str, style_rules.prefix),
             Span::raw(" "),
             Span::styled(
-                format!("\"{}\"", self.item.file_name),
-                content_style,
+                format!(
+                    "\"{}\"",
+                    truncate_to_display_width(
+                        &self.item.file_name,
+                        FILE_ITEM_MAXIMUM_FILE_NAME_WIDTH,
+                    )
+                ),
+                style_rules.primary,
             ),
         ];
 
@@ -343,8 +338,16 @@ impl<'config, 'text> RenderableQueueItem<Text<'text>>
             };
 
             primary_line.push(Span::raw(" "));
-            primary_line.push(Span::styled(result_str, explainer_style));
+            primary_line.push(Span::styled(result_str, style_rules.secondary));
         } else {
+            if let Some(progress) = self.progress {
+                primary_line.push(Span::raw(" "));
+                primary_line.push(Span::styled(
+                    format!("{:.0}%", progress * 100.0),
+                    style_rules.secondary,
+                ));
+            }
+
             let reason_str: &'static str = match self.item.context.action {
                 FileProcessingAction::Transcode { reason, .. } => match reason {
                     TranscodeProcessingReason::AddedInSourceLibrary => "(why: newly added)",
@@ -363,7 +366,7 @@ impl<'config, 'text> RenderableQueueItem<Text<'text>>
             };
 
             primary_line.push(Span::raw(" "));
-            primary_line.push(Span::styled(reason_str, explainer_style));
+            primary_line.push(Span::styled(reason_str, style_rules.secondary));
         }
 
         Text::from(vec![Line::from(primary_line)])
@@ -3,7 +3,8 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use ansi_to_tui::IntoText;
-use crossterm::event::{Event, KeyCode};
+use crossbeam::channel::Receiver;
+use crossterm::event::{Event, KeyCode, KeyModifiers};
 use euphony_configuration::ui::TranscodingUiConfiguration;
 use miette::Result;
 use miette::{miette, IntoDiagnostic, WrapErr};
@@ -39,8 +40,16 @@ use crate::console::colours::{
     X244_GREY50,
     X245_GREY54,
 };
+use crate::console::formatting::format_duration;
+use crate::console::frontends::shared::logging::LogLevel;
+use crate::console::frontends::shared::queue::QueueItemStateQuery;
 use crate::console::frontends::terminal_ui::queue_display::generate_smart_collapsible_queue;
-use crate::console::frontends::terminal_ui::state::{LogState, UIPage, UIState};
+use crate::console::frontends::terminal_ui::state::{
+    LogState,
+    ProblemSeverity,
+    UIPage,
+    UIState,
+};
 use crate::console::UserControlMessage;
 use crate::EUPHONY_VERSION;
 
@@ -56,6 +65,17 @@ const LOGS_TAB_TITLE_STYLE: Style = X143_DARK_KHAKI;
 
 const LOGS_TAB_LOG_TIME_STYLE: Style = X244_GREY50;
 
+/// Width of the time in front of each log entry (`HH:MM:SS `).
+const LOG_TIME_WIDTH: usize = 9;
+
+const PROBLEMS_PANEL_BORDER_STYLE: Style = X160_RED3;
+const PROBLEMS_PANEL_TITLE_STYLE: Style = X172_ORANGE3;
+const PROBLEMS_PANEL_WARNING_STYLE: Style = X136_DARK_GOLDENROD;
+const PROBLEMS_PANEL_ERROR_STYLE: Style = X160_RED3;
+
+/// Maximum number of entries the warnings and errors panel shows at once (most recent first).
+const PROBLEMS_PANEL_MAXIMUM_VISIBLE_ENTRIES: usize = 6;
+
 const HEADER_TRANSCODING_TAB_TEXT_STYLE: Style = TRANSCODING_TAB_TITLE_STYLE;
 const HEADER_LOGS_TAB_TEXT_STYLE: Style = LOGS_TAB_TITLE_STYLE;
 
@@ -77,9 +97,14 @@ fn render_header(
     terminal_frame: &mut Frame,
     header_rect: Rect,
     ui_state: &UIState,
+    transcoding_ui_config: &TranscodingUiConfiguration,
 ) {
-    let header_constraints =
-        vec![Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)];
+    let header_view_width_percent =
+        transcoding_ui_config.header_view_width_percent;
+    let header_constraints = vec![
+        Constraint::Percentage(header_view_width_percent),
+        Constraint::Percentage(100 - header_view_width_percent),
+    ];
 
     let header_layout = Layout::default()
         .direction(Direction::Horizontal)
@@ -167,6 +192,20 @@ fn render_header(
             "<q>",
             MUTED_TEXT_STYLE.add_modifier(Modifier::BOLD),
         ),
+        Span::styled(" | ", MUTED_TEXT_STYLE),
+        Span::styled(
+            "scroll logs <PgUp/PgDn/Home/End>",
+            MUTED_TEXT_STYLE,
+        ),
+        Span::styled(" | ", MUTED_TEXT_STYLE),
+        Span::styled(
+            if ui_state.show_problems_panel {
+                "hide problems <e>"
+            } else {
+                "show problems <e>"
+            },
+            MUTED_TEXT_STYLE,
+        ),
     ]))
     .block(help_block)
     .alignment(Alignment::Left);
@@ -175,14 +214,30 @@ fn render_header(
 }
 
 
+/// Number of lines a log entry takes up in the logs tab (see `render_logs_tab`).
+fn log_entry_line_count(log_content: &str, max_line_width: usize) -> usize {
+    if (LOG_TIME_WIDTH + log_content.len()) <= max_line_width {
+        1
+    } else {
+        textwrap::wrap(
+            log_content,
+            textwrap::Options::new(max_line_width - LOG_TIME_WIDTH)
+                .break_words(false),
+        )
+        .len()
+    }
+}
+
+/// Render the logs tab: the most recent log entries (or, when scrolled back,
+/// the ones before the scrolled-out entries), newest at the bottom.
 fn render_logs_tab(
     terminal_frame: &mut Frame,
     body_rect: Rect,
-    log_state: &LogState,
+    log_state: &mut LogState,
 ) -> Result<()> {
+    let log_journal = &mut log_state.log_journal;
+
     let logs_block = Block::default()
-        .title(Span::styled(" Logs ", LOGS_TAB_TITLE_STYLE))
-        .title_alignment(Alignment::Left)
         .borders(Borders::ALL)
         .border_type(BorderType::Thick)
         .border_style(LOGS_TAB_BORDER_STYLE);
@@ -191,9 +246,45 @@ fn render_logs_tab(
     let max_line_width = logs_inner_rect.width as usize;
     let max_lines = logs_inner_rect.height as usize;
 
+    // Don't allow scrolling further back than the point where the oldest entries fill the tab.
+    {
+        let num_entries = log_journal.iter_oldest_first().len();
+
+        let mut num_oldest_lines: usize = 0;
+        let mut num_oldest_fitting_entries: usize = 0;
+        for (log_content, _) in log_journal.iter_oldest_first() {
+            num_oldest_lines +=
+                log_entry_line_count(log_content, max_line_width);
+            if num_oldest_lines > max_lines {
+                break;
+            }
+
+            num_oldest_fitting_entries += 1;
+        }
+
+        log_journal
+            .limit_scroll_offset(num_entries - num_oldest_fitting_entries);
+    }
+
+    let scroll_offset = log_journal.scroll_offset();
+    let logs_block_title = if scroll_offset > 0 {
+        format!(" Logs (scrolled back, {scroll_offset} newer entries) ")
+    } else {
+        " Logs ".to_string()
+    };
+
+    let logs_block = logs_block
+        .title(Span::styled(
+            logs_block_title,
+            LOGS_TAB_TITLE_STYLE,
+        ))
+        .title_alignment(Alignment::Left);
+
     let mut log_lines: Vec<Line> = Vec::with_capacity(max_lines);
+    let mut num_visible_entries: usize = 0;
 
-    let mut log_iterator = log_state.log_journal.iter_most_recent_first();
+    let mut log_iterator =
+        log_journal.iter_most_recent_first().skip(scroll_offset);
     while log_lines.len() < max_lines {
         let Some((log_content, log_time)) = log_iterator.next() else {
             break;
@@ -206,6 +297,8 @@ fn render_logs_tab(
         let formatted_log_time =
             Span::styled(formatted_log_time, LOGS_TAB_LOG_TIME_STYLE);
 
+        num_visible_entries += 1;
+
 
         if (formatted_log_time_length + log_content_length) <= max_line_width {
             // Log entry fits in one line, no need to wrap.
@@ -232,6 +325,7 @@ fn render_logs_tab(
             );
 
             if wrapped_text.len() + log_lines.len() > max_lines {
+                num_visible_entries -= 1;
                 break;
             }
 
@@ -270,6 +364,8 @@ fn render_logs_tab(
         log_lines.insert(0, Line::default());
     }
 
+    log_journal.set_num_visible_entries(num_visible_entries);
+
     let logs_paragraph = Paragraph::new(log_lines);
 
     terminal_frame.render_widget(logs_block, body_rect);
@@ -279,10 +375,71 @@ fn render_logs_tab(
 }
 
 
+/// Render the warnings and errors panel: the most recent problems, one (truncated) line each.
+fn render_problems_panel(
+    terminal_frame: &mut Frame,
+    panel_rect: Rect,
+    log_state: &LogState,
+) -> Result<()> {
+    let problem_journal = &log_state.problem_journal;
+
+    let problems_block = Block::default()
+        .title(Span::styled(
+            format!(
+                " Warnings ({}) and errors ({}) ",
+                problem_journal.num_with_severity(ProblemSeverity::Warning),
+                problem_journal.num_with_severity(ProblemSeverity::Error),
+            ),
+            PROBLEMS_PANEL_TITLE_STYLE,
+        ))
+        .title_alignment(Alignment::Left)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain)
+        .border_style(PROBLEMS_PANEL_BORDER_STYLE);
+    let problems_inner_rect = problems_block.inner(panel_rect);
+
+    let max_lines = problems_inner_rect.height as usize;
+
+    let mut problem_lines: Vec<Line> = Vec::with_capacity(max_lines);
+    for problem in problem_journal.iter_most_recent_first().take(max_lines) {
+        let (severity_label, severity_style) = match problem.severity {
+            ProblemSeverity::Warning => {
+                ("warning ", PROBLEMS_PANEL_WARNING_STYLE)
+            }
+            ProblemSeverity::Error => ("error   ", PROBLEMS_PANEL_ERROR_STYLE),
+        };
+
+        let message_as_text =
+            problem.message.as_bytes().into_text().into_diagnostic()?;
+
+        let mut line_spans: Vec<Span> = vec![
+            Span::styled(
+                format!("{} ", problem.time.format("%H:%M:%S")),
+                LOGS_TAB_LOG_TIME_STYLE,
+            ),
+            Span::styled(severity_label, severity_style),
+        ];
+        if let Some(first_message_line) = message_as_text.lines.first() {
+            line_spans.extend(first_message_line.spans.clone());
+        }
+
+        problem_lines.push(Line::from(line_spans));
+    }
+
+    let problems_paragraph = Paragraph::new(problem_lines);
+
+    terminal_frame.render_widget(problems_block, panel_rect);
+    terminal_frame.render_widget(problems_paragraph, problems_inner_rect);
+
+    Ok(())
+}
+
+
 fn render_transcoding_tab(
     terminal_frame: &mut Frame,
     body_rect: Rect,
     ui_state: &UIState,
+    transcoding_ui_config: &TranscodingUiConfiguration,
 ) {
     if ui_state.album_queue.is_none() || ui_state.file_queue.is_none() {
         // This if statement shouldn't ever trigger, but if it does for some reason, we
@@ -301,8 +458,12 @@ fn render_transcoding_tab(
         .expect("BUG: Just checked that album queue is Some?!");
 
 
-    let transcoding_tab_constraints =
-        vec![Constraint::Ratio(2, 5), Constraint::Ratio(3, 5)];
+    let file_queue_width_percent =
+        transcoding_ui_config.file_queue_width_percent;
+    let transcoding_tab_constraints = vec![
+        Constraint::Percentage(100 - file_queue_width_percent),
+        Constraint::Percentage(file_queue_width_percent),
+    ];
 
     let transcoding_tab_layout = Layout::default()
         .direction(Direction::Horizontal)
@@ -311,9 +472,26 @@ fn render_transcoding_tab(
 
 
     // Album queue
+    let num_remaining_albums = album_queue
+        .items()
+        .filter(|(_, album)| !album.is_finished())
+        .count();
+    let albums_queue_title = match ui_state
+        .album_remaining_time
+        .as_ref()
+        .and_then(|estimator| {
+            estimator.estimate_remaining_time(num_remaining_albums)
+        }) {
+        Some(remaining_time) if num_remaining_albums > 0 => format!(
+            " Album list (ETA {}) ",
+            format_duration(remaining_time)
+        ),
+        _ => String::from(" Album list "),
+    };
+
     let albums_queue_block = Block::default()
         .title(Span::styled(
-            " Album list ",
+            albums_queue_title,
             TRANSCODING_TAB_TITLE_STYLE,
         ))
         .title_alignment(Alignment::Left)
@@ -358,6 +536,64 @@ fn render_transcoding_tab(
 }
 
 
+fn render_scan_progress_footer(
+    terminal_frame: &mut Frame,
+    footer_rect: Rect,
+    ui_state: &UIState,
+) {
+    let Some(scan_progress) = ui_state.scan_progress else {
+        terminal_frame.render_widget(Clear, footer_rect);
+        return;
+    };
+
+    let footer_block = Block::default()
+        .title(Span::styled(
+            format!(
+                " Scanning ({:.1}%) ",
+                scan_progress.completion_ratio() * 100f64
+            ),
+            PROGRESS_BAR_BLOCK_TITLE_STYLE,
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Plain)
+        .border_style(PROGRESS_BAR_BLOCK_BORDER_STYLE);
+    let footer_inner_rect = footer_block.inner(footer_rect);
+
+    terminal_frame.render_widget(footer_block, footer_rect);
+
+
+    let footer_constraints =
+        vec![Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)];
+
+    let footer_inner_layout = Layout::default()
+        .constraints(footer_constraints)
+        .split(footer_inner_rect);
+
+
+    let scan_progress_gauge = LineGauge::default()
+        .gauge_style(PROGRESS_GAUGE_STYLE)
+        .line_set(ratatui::symbols::line::THICK)
+        .ratio(scan_progress.completion_ratio())
+        .label(Span::raw(""));
+
+    terminal_frame.render_widget(scan_progress_gauge, footer_inner_layout[0]);
+
+    let scan_status_line = Paragraph::new(Line::from(vec![
+        Span::styled("SCANNED: ", MUTED_TEXT_STYLE),
+        Span::styled(
+            format!(
+                "{} / {}",
+                scan_progress.items_scanned, scan_progress.total_items
+            ),
+            PROGRESS_DESCRIPTION_PROCESSING_FILES_VALUES_STYLE,
+        ),
+    ]))
+    .alignment(Alignment::Center);
+
+    terminal_frame.render_widget(scan_status_line, footer_inner_layout[1]);
+}
+
+
 fn render_progress_footer(
     terminal_frame: &mut Frame,
     footer_rect: Rect,
@@ -372,12 +608,27 @@ fn render_progress_footer(
 
     let progress = ui_state.progress.expect("BUG: progress shouldn't be None.");
 
+    let remaining_time = ui_state
+        .progress_started_at
+        .and_then(|started_at| {
+            progress.estimate_remaining_time(started_at.elapsed())
+        })
+        .filter(|remaining_time| !remaining_time.is_zero());
+    let footer_title = match remaining_time {
+        Some(remaining_time) => format!(
+            " Overall file progress ({:.1}%, ETA {}) ",
+            progress.completion_ratio() * 100f64,
+            format_duration(remaining_time)
+        ),
+        None => format!(
+            " Overall file progress ({:.1}%) ",
+            progress.completion_ratio() * 100f64
+        ),
+    };
+
     let footer_block = Block::default()
         .title(Span::styled(
-            format!(
-                " Overall file progress ({:.1}%) ",
-                progress.completion_ratio() * 100f64
-            ),
+            footer_title,
             PROGRESS_BAR_BLOCK_TITLE_STYLE,
         ))
         .borders(Borders::ALL)
@@ -461,8 +712,9 @@ fn render_progress_footer(
 
 
 fn render_ui(
-    log_state: &LogState,
+    log_state: &mut LogState,
     ui_state: &UIState,
+    transcoding_ui_config: &TranscodingUiConfiguration,
     terminal_frame: &mut Frame,
     is_final_render: bool,
 ) -> Result<()> {
@@ -528,8 +780,8 @@ fn render_ui(
         Constraint::Length(3),
         // Body of the app (either transcoding queue or log view)
         Constraint::Min(5),
-        // Footer containing the progress bar and additional info.
-        if ui_state.progress.is_some() {
+        // Footer containing the progress bar (or scan progress) and additional info.
+        if ui_state.progress.is_some() || ui_state.scan_progress.is_some() {
             Constraint::Length(4)
         } else {
             Constraint::Length(0)
@@ -541,26 +793,68 @@ fn render_ui(
         .constraints(main_constraints)
         .split(frame_size);
 
-    render_header(terminal_frame, main_layout[0], ui_state);
+    // The warnings and errors panel takes space at the bottom of the body,
+    // but only when it is enabled and there is something to show.
+    let num_visible_problems = if ui_state.show_problems_panel {
+        log_state
+            .problem_journal
+            .iter_most_recent_first()
+            .take(PROBLEMS_PANEL_MAXIMUM_VISIBLE_ENTRIES)
+            .count()
+    } else {
+        0
+    };
+
+    let body_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Min(5),
+            if num_visible_problems > 0 {
+                // Two additional lines for the panel borders.
+                Constraint::Length(num_visible_problems as u16 + 2)
+            } else {
+                Constraint::Length(0)
+            },
+        ])
+        .split(main_layout[1]);
+    let body_rect = body_layout[0];
+
+    render_header(
+        terminal_frame,
+        main_layout[0],
+        ui_state,
+        transcoding_ui_config,
+    );
 
     // If any of the queues are disabled, always render the log view instead.
     if ui_state.file_queue.is_none() || ui_state.album_queue.is_none() {
-        render_logs_tab(terminal_frame, main_layout[1], log_state)?;
+        render_logs_tab(terminal_frame, body_rect, log_state)?;
     } else {
         match ui_state.current_page {
             UIPage::Transcoding => {
-                render_transcoding_tab(terminal_frame, main_layout[1], ui_state);
+                render_transcoding_tab(
+                    terminal_frame,
+                    body_rect,
+                    ui_state,
+                    transcoding_ui_config,
+                );
             }
             UIPage::Logs => {
-                render_logs_tab(terminal_frame, main_layout[1], log_state)?;
+                render_logs_tab(terminal_frame, body_rect, log_state)?;
             }
         };
     }
 
+    if num_visible_problems > 0 {
+        render_problems_panel(terminal_frame, body_layout[1], log_state)?;
+    }
+
     // Prevents the function from being called when the progress bar is disabled
     // (the Rect will have 0 height anyway).
     if ui_state.progress.is_some() {
         render_progress_footer(terminal_frame, main_layout[2], ui_state);
+    } else if ui_state.scan_progress.is_some() {
+        render_scan_progress_footer(terminal_frame, main_layout[2], ui_state);
     }
 
     Ok(())
@@ -573,6 +867,7 @@ pub fn run_render_loop(
     transcoding_ui_config: TranscodingUiConfiguration,
     log_state: Arc<Mutex<LogState>>,
     ui_state: Arc<RwLock<UIState>>,
+    tracing_log_receiver: Receiver<(LogLevel, String)>,
     user_control_sender: &broadcast::Sender<UserControlMessage>,
     cancellation_token: CancellationToken,
 ) -> Result<()> {
@@ -582,6 +877,15 @@ pub fn run_render_loop(
     loop {
         let render_time_start = Instant::now();
 
+        // Log the `tracing` events received since the last render
+        // (see `set_tracing_log_sink`), including the last ones before stopping.
+        {
+            let mut locked_log_state = log_state.lock();
+            for (level, line) in tracing_log_receiver.try_iter() {
+                locked_log_state.log_tracing_event(level, line);
+            }
+        }
+
         if cancellation_token.is_cancelled() {
             // Main thread signalled us to stop, simply exit by returning early.
             break;
@@ -591,12 +895,18 @@ pub fn run_render_loop(
         {
             let mut locked_terminal = terminal.lock();
             let locked_ui_state = ui_state.read();
-            let locked_log_state = log_state.lock();
+            let mut locked_log_state = log_state.lock();
 
             locked_terminal
                 .draw(|frame| {
-                    render_ui(&locked_log_state, &locked_ui_state, frame, false)
-                        .expect("Failed to render terminal UI.")
+                    render_ui(
+                        &mut locked_log_state,
+                        &locked_ui_state,
+                        &transcoding_ui_config,
+                        frame,
+                        false,
+                    )
+                    .expect("Failed to render terminal UI.")
                 })
                 .into_diagnostic()
                 .wrap_err_with(|| miette!("Failed to render terminal UI."))?;
@@ -634,8 +944,30 @@ pub fn run_render_loop(
                         || miette!("Failed to read keyboard event."),
                     )?
                 {
+                    // In raw mode, Ctrl-C doesn't raise an interrupt signal,
+                    // so we treat it the same as the exit keybind.
+                    let is_ctrl_c = key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL);
+
+                    // Scrolling the logs tab (see `LogJournal`).
+                    match key.code {
+                        KeyCode::PageUp => {
+                            log_state.lock().log_journal.scroll_page_up();
+                        }
+                        KeyCode::PageDown => {
+                            log_state.lock().log_journal.scroll_page_down();
+                        }
+                        KeyCode::Home => {
+                            log_state.lock().log_journal.scroll_to_oldest();
+                        }
+                        KeyCode::End => {
+                            log_state.lock().log_journal.scroll_to_newest();
+                        }
+                        _ => {}
+                    }
+
                     if let KeyCode::Char(char) = key.code {
-                        if char == 'q' {
+                        if char == 'q' || is_ctrl_c {
                             if transcoding_ui_config.show_logs_tab_on_exit {
                                 let mut locked_ui_state = ui_state.write();
                                 locked_ui_state.current_page = UIPage::Logs;
@@ -654,6 +986,10 @@ pub fn run_render_loop(
                         } else if char == 'l' {
                             let mut locked_ui_state = ui_state.write();
                             locked_ui_state.current_page = UIPage::Logs;
+                        } else if char == 'e' {
+                            let mut locked_ui_state = ui_state.write();
+                            locked_ui_state.show_problems_panel =
+                                !locked_ui_state.show_problems_panel;
                         }
                     }
                 }
@@ -667,12 +1003,18 @@ pub fn run_render_loop(
     {
         let mut locked_terminal = terminal.lock();
         let locked_ui_state = ui_state.read();
-        let locked_log_state = log_state.lock();
+        let mut locked_log_state = log_state.lock();
 
         locked_terminal
             .draw(|frame| {
-                render_ui(&locked_log_state, &locked_ui_state, frame, true)
-                    .expect("Failed to render terminal UI.")
+                render_ui(
+                    &mut locked_log_state,
+                    &locked_ui_state,
+                    &transcoding_ui_config,
+                    frame,
+                    true,
+                )
+                .expect("Failed to render terminal UI.")
             })
             .into_diagnostic()
             .wrap_err_with(|| {
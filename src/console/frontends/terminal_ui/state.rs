@@ -1,9 +1,11 @@
 use std::collections::vec_deque::Iter;
 use std::collections::VecDeque;
-use std::fs::File;
-use std::io::{BufWriter, Stdout};
+use std::io::Stdout;
+use std::iter::Rev;
+use std::slice;
 use std::sync::Arc;
 use std::thread::ScopedJoinHandle;
+use std::time::Instant;
 
 use chrono::{DateTime, Local};
 use miette::Result;
@@ -13,20 +15,28 @@ use ratatui::Terminal;
 use tokio::sync::broadcast;
 
 use crate::cancellation::CancellationToken;
+use crate::console::frontends::shared::logging::{LogFileWriter, LogLevel};
 use crate::console::frontends::shared::queue::{
     AlbumQueueItemFinishedResult,
     FileQueueItemFinishedResult,
     Queue,
 };
-use crate::console::frontends::shared::Progress;
+use crate::console::frontends::shared::{
+    Progress,
+    RollingRemainingTimeEstimator,
+    ScanProgress,
+};
 use crate::console::frontends::terminal_ui::queue_items::{
     FancyAlbumQueueItem,
     FancyFileQueueItem,
 };
+use crate::console::tracing_output::format_tracing_line;
 use crate::console::UserControlMessage;
 
 
-const LOG_JOURNAL_DEFAULT_MAXIMUM_HISTORY: usize = 40;
+/// Number of log entries kept for the logs tab (older ones are dropped). This is much more
+/// than fits on the screen, so that earlier entries can be scrolled back to.
+const LOG_JOURNAL_DEFAULT_MAXIMUM_HISTORY: usize = 2000;
 
 
 pub struct TerminalState<'thread_scope> {
@@ -46,7 +56,7 @@ pub struct TerminalState<'thread_scope> {
 pub enum LogOutputMode<'thread_scope> {
     None,
     ToFile {
-        buf_writer: Arc<Mutex<BufWriter<strip_ansi_escapes::Writer<File>>>>,
+        log_file_writer: Arc<Mutex<LogFileWriter>>,
         writer_flushing_thread_handle:
             ScopedJoinHandle<'thread_scope, Result<()>>,
         writer_flushing_thread_cancellation_token: CancellationToken,
@@ -62,6 +72,14 @@ impl<'thread_scope> Default for LogOutputMode<'thread_scope> {
 pub struct LogJournal {
     journal: VecDeque<(String, DateTime<Local>)>,
     maximum_history: usize,
+
+    /// Number of the most recent entries that are scrolled out of view (below the logs tab).
+    /// When 0, the logs tab follows the newest entries.
+    scroll_offset: usize,
+
+    /// Number of entries the logs tab showed when it was last rendered
+    /// (scrolling by a page moves by this many entries).
+    num_visible_entries: usize,
 }
 
 impl LogJournal {
@@ -69,6 +87,8 @@ impl LogJournal {
         Self {
             journal: VecDeque::with_capacity(maximum_history),
             maximum_history,
+            scroll_offset: 0,
+            num_visible_entries: 1,
         }
     }
 
@@ -80,17 +100,138 @@ impl LogJournal {
         }
 
         self.journal.push_front((entry.into(), Local::now()));
+
+        // When scrolled up, hold the position instead of following the new entry.
+        if self.scroll_offset > 0 {
+            self.scroll_offset =
+                (self.scroll_offset + 1).min(self.journal.len() - 1);
+        }
     }
 
     pub fn iter_most_recent_first(&self) -> Iter<'_, (String, DateTime<Local>)> {
         self.journal.iter()
     }
+
+    pub fn iter_oldest_first(&self) -> Rev<Iter<'_, (String, DateTime<Local>)>> {
+        self.journal.iter().rev()
+    }
+
+    pub fn scroll_offset(&self) -> usize {
+        self.scroll_offset
+    }
+
+    /// Limit the scroll offset, so the logs tab can't be scrolled past the point
+    /// where the oldest entries fill it (called when rendering).
+    pub fn limit_scroll_offset(&mut self, maximum_scroll_offset: usize) {
+        self.scroll_offset = self.scroll_offset.min(maximum_scroll_offset);
+    }
+
+    /// Record how many entries the logs tab showed (called when rendering).
+    pub fn set_num_visible_entries(&mut self, num_visible_entries: usize) {
+        self.num_visible_entries = num_visible_entries.max(1);
+    }
+
+    /// Scroll one page towards older entries (PageUp).
+    pub fn scroll_page_up(&mut self) {
+        self.scroll_offset = (self.scroll_offset + self.num_visible_entries)
+            .min(self.journal.len().saturating_sub(1));
+    }
+
+    /// Scroll one page towards newer entries (PageDown).
+    /// Reaching the bottom resumes following the newest entries.
+    pub fn scroll_page_down(&mut self) {
+        self.scroll_offset =
+            self.scroll_offset.saturating_sub(self.num_visible_entries);
+    }
+
+    /// Scroll to the oldest entries (Home).
+    pub fn scroll_to_oldest(&mut self) {
+        self.scroll_offset = self.journal.len().saturating_sub(1);
+    }
+
+    /// Scroll to the newest entries and follow them again (End).
+    pub fn scroll_to_newest(&mut self) {
+        self.scroll_offset = 0;
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ProblemSeverity {
+    Warning,
+    Error,
+}
+
+impl ProblemSeverity {
+    /// Classify a log entry as a warning or error by its level.
+    /// Returns `None` for ordinary log entries.
+    pub fn from_log_level(level: LogLevel) -> Option<Self> {
+        match level {
+            LogLevel::Warning => Some(Self::Warning),
+            LogLevel::Error => Some(Self::Error),
+            LogLevel::Trace | LogLevel::Debug | LogLevel::Info => None,
+        }
+    }
+}
+
+pub struct Problem {
+    pub severity: ProblemSeverity,
+
+    pub message: String,
+
+    pub time: DateTime<Local>,
+}
+
+/// All warnings and errors of the current run. Unlike `LogJournal`,
+/// this keeps every entry, so nothing is lost during long runs.
+pub struct ProblemJournal {
+    problems: Vec<Problem>,
+}
+
+impl ProblemJournal {
+    pub fn new() -> Self {
+        Self {
+            problems: Vec::new(),
+        }
+    }
+
+    pub fn insert_entry<S: Into<String>>(
+        &mut self,
+        severity: ProblemSeverity,
+        message: S,
+    ) {
+        self.problems.push(Problem {
+            severity,
+            message: message.into(),
+            time: Local::now(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn num_with_severity(&self, severity: ProblemSeverity) -> usize {
+        self.problems
+            .iter()
+            .filter(|problem| problem.severity == severity)
+            .count()
+    }
+
+    pub fn iter_most_recent_first(&self) -> Rev<slice::Iter<'_, Problem>> {
+        self.problems.iter().rev()
+    }
+
+    pub fn iter_oldest_first(&self) -> slice::Iter<'_, Problem> {
+        self.problems.iter()
+    }
 }
 
 pub struct LogState<'thread_scope> {
     pub log_output: LogOutputMode<'thread_scope>,
 
     pub log_journal: LogJournal,
+
+    pub problem_journal: ProblemJournal,
 }
 
 impl<'thread_scope> LogState<'thread_scope> {
@@ -98,7 +239,50 @@ impl<'thread_scope> LogState<'thread_scope> {
         Self {
             log_output: LogOutputMode::default(),
             log_journal: LogJournal::new(LOG_JOURNAL_DEFAULT_MAXIMUM_HISTORY),
+            problem_journal: ProblemJournal::new(),
+        }
+    }
+
+    /// Add a message to the log journal (and, if it is a warning or an error,
+    /// to the problem journal) and, if enabled, write it into the log file.
+    pub fn log_message(&mut self, message: String) {
+        let level = LogLevel::from_log_message(&message);
+        self.log_entry(level, &message, message.clone());
+    }
+
+    /// Like `log_message`, but for a formatted `tracing` event (see `set_tracing_log_sink`).
+    pub fn log_tracing_event(&mut self, level: LogLevel, line: String) {
+        self.log_entry(level, &line, format_tracing_line(level, &line));
+    }
+
+    fn log_entry(
+        &mut self,
+        level: LogLevel,
+        log_file_line: &str,
+        journal_entry: String,
+    ) {
+        // If enabled, write the line into the log file (its BufWriter, to be precise).
+        match &self.log_output {
+            LogOutputMode::ToFile {
+                log_file_writer, ..
+            } => {
+                log_file_writer
+                    .lock()
+                    .write_line(level, log_file_line)
+                    .expect("Failed to write line to log file output.");
+            }
+            LogOutputMode::None => {}
+        }
+
+        // Warnings and errors are additionally kept in the problem journal,
+        // so they remain visible after scrolling out of the log journal.
+        if let Some(severity) = ProblemSeverity::from_log_level(level) {
+            self.problem_journal
+                .insert_entry(severity, journal_entry.clone());
         }
+
+        // Add entry to log journal.
+        self.log_journal.insert_entry(journal_entry);
     }
 }
 
@@ -120,7 +304,18 @@ pub struct UIState<'config> {
 
     pub progress: Option<Progress>,
 
+    /// When the progress bar was enabled (i.e. when processing started), for estimating the remaining time.
+    pub progress_started_at: Option<Instant>,
+
+    /// Estimates the remaining time from recently finished albums (see the album queue).
+    pub album_remaining_time: Option<RollingRemainingTimeEstimator>,
+
+    pub scan_progress: Option<ScanProgress>,
+
     pub current_page: UIPage,
+
+    /// Whether the warnings and errors panel is shown (when there are any problems).
+    pub show_problems_panel: bool,
 }
 
 impl<'config> UIState<'config> {
@@ -129,7 +324,11 @@ impl<'config> UIState<'config> {
             album_queue: None,
             file_queue: None,
             progress: None,
+            progress_started_at: None,
+            album_remaining_time: None,
+            scan_progress: None,
             current_page: UIPage::Logs,
+            show_problems_panel: true,
         }
     }
 }
@@ -1,13 +1,12 @@
 use std::fmt::Display;
-use std::fs::File;
-use std::io::{stdout, BufWriter, Write};
+use std::io::stdout;
 use std::path::Path;
 use std::sync::Arc;
 use std::thread;
 use std::thread::Scope;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use chrono::Local;
+use crossterm::style::Stylize;
 use crossterm::ExecutableCommand;
 use euphony_configuration::Configuration;
 use miette::{miette, Context, IntoDiagnostic, Result};
@@ -17,17 +16,27 @@ use ratatui::Terminal;
 use tokio::sync::broadcast;
 
 use crate::cancellation::CancellationToken;
-use crate::console::frontends::shared::logging::initialize_log_file_for_log_output;
+use crate::console::frontends::shared::logging::{
+    initialize_log_file_for_log_output,
+    LogFileOptions,
+    LogFileWriter,
+    LogLevel,
+};
 use crate::console::frontends::shared::queue::{
     AlbumQueueItem,
     AlbumQueueItemFinishedResult,
     FileQueueItem,
+    FileQueueItemErrorType,
     FileQueueItemFinishedResult,
     Queue,
     QueueItem,
     QueueItemID,
 };
-use crate::console::frontends::shared::Progress;
+use crate::console::frontends::shared::{
+    Progress,
+    RollingRemainingTimeEstimator,
+    ScanProgress,
+};
 use crate::console::frontends::terminal_ui::queue_items::{
     FancyAlbumQueueItem,
     FancyFileQueueItem,
@@ -36,10 +45,16 @@ use crate::console::frontends::terminal_ui::rendering;
 use crate::console::frontends::terminal_ui::state::{
     LogOutputMode,
     LogState,
+    ProblemJournal,
+    ProblemSeverity,
     TerminalState,
     UIPage,
     UIState,
 };
+use crate::console::tracing_output::{
+    clear_tracing_log_sink,
+    set_tracing_log_sink,
+};
 use crate::console::{
     LogBackend,
     LogToFileBackend,
@@ -55,7 +70,7 @@ const FLUSHING_THREAD_CANCELLATION_CHECK_INTERVAL: Duration =
 
 
 fn run_log_output_flushing_loop(
-    log_file_writer: Arc<Mutex<BufWriter<strip_ansi_escapes::Writer<File>>>>,
+    log_file_writer: Arc<Mutex<LogFileWriter>>,
     cancellation_token: CancellationToken,
 ) -> Result<()> {
     let mut time_accumulator = Duration::from_secs(0);
@@ -95,6 +110,39 @@ fn run_log_output_flushing_loop(
 }
 
 
+/// Print all warnings and errors of the run to stdout (oldest first).
+fn print_problem_summary(problem_journal: &ProblemJournal) {
+    if problem_journal.is_empty() {
+        return;
+    }
+
+    println!();
+    println!(
+        "{}",
+        format!(
+            "Warnings and errors during this run ({} warnings, {} errors):",
+            problem_journal.num_with_severity(ProblemSeverity::Warning),
+            problem_journal.num_with_severity(ProblemSeverity::Error),
+        )
+        .bold()
+    );
+
+    for problem in problem_journal.iter_oldest_first() {
+        let severity = match problem.severity {
+            ProblemSeverity::Warning => "warning".yellow(),
+            ProblemSeverity::Error => "error".red(),
+        };
+
+        println!(
+            "{} [{}] {}",
+            problem.time.format("%H:%M:%S"),
+            severity,
+            problem.message
+        );
+    }
+}
+
+
 pub struct FancyTerminalBackend<'thread_scope, 'config> {
     terminal_state: Arc<Mutex<Option<TerminalState<'thread_scope>>>>,
 
@@ -175,12 +223,22 @@ impl<'scope, 'scope_env: 'scope, 'config: 'scope>
 
         let transcoding_ui_config = self.config.ui.transcoding.clone();
 
+        // `tracing` events can come from any thread, so they are passed to the render thread,
+        // which adds them to the log state like the rest of the log output.
+        let (tracing_log_sender, tracing_log_receiver) =
+            crossbeam::channel::unbounded::<(LogLevel, String)>();
+        set_tracing_log_sink(Box::new(move |level, line| {
+            // The receiver is only dropped once the render thread stops.
+            let _ = tracing_log_sender.send((level, line));
+        }));
+
         let render_thread_join_handle = scope.spawn(move || {
             rendering::run_render_loop(
                 terminal_arc_mutex_clone,
                 transcoding_ui_config,
                 log_state_arc_clone,
                 ui_state_arc_clone,
+                tracing_log_receiver,
                 &user_control_sender_clone,
                 render_cancellation_token_clone,
             )
@@ -207,7 +265,8 @@ impl<'scope, 'scope_env: 'scope, 'config: 'scope>
                 miette!("Cannot destroy terminal, hasn't been set up yet.")
             })?;
 
-        // Wait for render thread to stop.
+        // Wait for render thread to stop (it logs the remaining `tracing` events first).
+        clear_tracing_log_sink();
         terminal_state.render_thread_cancellation_token.cancel();
         terminal_state
             .render_thread_join_handle
@@ -237,6 +296,11 @@ impl<'scope, 'scope_env: 'scope, 'config: 'scope>
             .wrap_err_with(|| miette!("Failed to disable terminal raw mode."))?;
 
 
+        // The terminal UI is gone, so print the warnings and errors once more
+        // for them to remain reviewable after exiting.
+        print_problem_summary(&self.log_state.lock().problem_journal);
+
+
         // Join log output flushing thread.
         let log_state = self.log_state.lock();
         if matches!(log_state.log_output, LogOutputMode::ToFile { .. }) {
@@ -257,11 +321,12 @@ impl<'thread_scope, 'config> LogBackend
 
         // If enabled, write newline into the log file (its BufWriter, to be precise).
         match &state.log_output {
-            LogOutputMode::ToFile { buf_writer, .. } => {
-                let mut locked_buf_writer = buf_writer.lock();
-
-                locked_buf_writer
-                    .write_all("\n".as_bytes())
+            LogOutputMode::ToFile {
+                log_file_writer, ..
+            } => {
+                log_file_writer
+                    .lock()
+                    .write_newline()
                     .expect("Failed to write newline to log file output.");
             }
             LogOutputMode::None => {}
@@ -272,33 +337,7 @@ impl<'thread_scope, 'config> LogBackend
     }
 
     fn log_println<D: Display>(&self, content: D) {
-        let message = content.to_string();
-        let mut state = self.log_state.lock();
-
-        // If enabled, write message into the log file (its BufWriter, to be precise).
-        match &state.log_output {
-            LogOutputMode::ToFile { buf_writer, .. } => {
-                let time_now = Local::now();
-                let formatted_time_now =
-                    time_now.format("%Y-%m-%d %H:%M:%S%.3f ");
-
-                let mut locked_buf_writer = buf_writer.lock();
-
-                locked_buf_writer
-                    .write_all(formatted_time_now.to_string().as_bytes())
-                    .expect("Failed to write formatted time to log file output");
-                locked_buf_writer.write_all(message.as_bytes()).expect(
-                    "Failed to write println contents to log file output.",
-                );
-                locked_buf_writer
-                    .write_all("\n".as_bytes())
-                    .expect("Failed to write newline to log file output.");
-            }
-            LogOutputMode::None => {}
-        }
-
-        // Add message to log journal.
-        state.log_journal.insert_entry(message);
+        self.log_state.lock().log_message(content.to_string());
     }
 }
 
@@ -309,17 +348,20 @@ impl<'scope, 'scope_env: 'scope, 'config: 'scope>
     fn enable_saving_logs_to_file<P: AsRef<Path>>(
         &self,
         log_output_file_path: P,
+        options: &LogFileOptions,
         scope: &'scope Scope<'scope, 'scope_env>,
     ) -> Result<()> {
-        let buf_writer =
-            initialize_log_file_for_log_output(log_output_file_path.as_ref())
-                .wrap_err_with(|| {
-                    miette!("Failed to initialize log file for log output.")
-                })?;
+        let log_file_writer = initialize_log_file_for_log_output(
+            log_output_file_path.as_ref(),
+            options,
+        )
+        .wrap_err_with(|| {
+            miette!("Failed to initialize log file for log output.")
+        })?;
 
 
-        let buf_writer_arc_mutex = Arc::new(Mutex::new(buf_writer));
-        let buf_writer_arc_mutex_clone = buf_writer_arc_mutex.clone();
+        let log_file_writer_arc_mutex = Arc::new(Mutex::new(log_file_writer));
+        let log_file_writer_arc_mutex_clone = log_file_writer_arc_mutex.clone();
 
 
         let flushing_thread_cancellation_token = CancellationToken::new();
@@ -329,7 +371,7 @@ impl<'scope, 'scope_env: 'scope, 'config: 'scope>
 
         let flushing_thread_handle = scope.spawn(move || {
             run_log_output_flushing_loop(
-                buf_writer_arc_mutex_clone,
+                log_file_writer_arc_mutex_clone,
                 flushing_thread_cancellation_token_clone,
             )
         });
@@ -338,7 +380,7 @@ impl<'scope, 'scope_env: 'scope, 'config: 'scope>
             let mut locked_state = self.log_state.lock();
 
             locked_state.log_output = LogOutputMode::ToFile {
-                buf_writer: buf_writer_arc_mutex,
+                log_file_writer: log_file_writer_arc_mutex,
                 writer_flushing_thread_handle: flushing_thread_handle,
                 writer_flushing_thread_cancellation_token:
                     flushing_thread_cancellation_token,
@@ -457,7 +499,15 @@ impl<'thread_scope, 'config> TranscodeBackend<'config>
             .ok_or_else(|| {
                 miette!("Album queue is disabled, can't finish item.")
             })?
-            .finish_item(item_id, result)
+            .finish_item(item_id, result)?;
+
+        if let Some(album_remaining_time) =
+            locked_state.album_remaining_time.as_mut()
+        {
+            album_remaining_time.record_completion(Instant::now());
+        }
+
+        Ok(())
     }
 
     fn queue_album_item_remove(
@@ -535,20 +585,55 @@ impl<'thread_scope, 'config> TranscodeBackend<'config>
             .start_item(item_id)
     }
 
-    fn queue_file_item_finish(
+    fn queue_file_item_set_progress(
         &self,
         item_id: QueueItemID,
-        result: FileQueueItemFinishedResult,
+        progress: f64,
     ) -> Result<()> {
         let mut locked_state = self.ui_state.write();
 
-        locked_state
+        let fancy_item = locked_state
             .file_queue
             .as_mut()
             .ok_or_else(|| {
-                miette!("File queue is disabled, can't finish item.")
+                miette!("File queue is disabled, can't update item progress.")
             })?
-            .finish_item(item_id, result)
+            .item_mut(item_id)
+            .ok_or_else(|| miette!("Invalid item_id, no such item."))?;
+
+        fancy_item.progress = Some(progress);
+
+        Ok(())
+    }
+
+    fn queue_file_item_finish(
+        &self,
+        item_id: QueueItemID,
+        result: FileQueueItemFinishedResult,
+    ) -> Result<()> {
+        let mut locked_state = self.ui_state.write();
+
+        let file_queue = locked_state.file_queue.as_mut().ok_or_else(|| {
+            miette!("File queue is disabled, can't finish item.")
+        })?;
+
+        if let FileQueueItemFinishedResult::Failed(
+            FileQueueItemErrorType::Errored { error },
+        ) = &result
+        {
+            let file_name = file_queue
+                .item(item_id)
+                .map(|fancy_item| fancy_item.item.file_name.clone())
+                .unwrap_or_default();
+            let first_error_line = error.lines().next().unwrap_or_default();
+
+            self.log_state.lock().problem_journal.insert_entry(
+                ProblemSeverity::Error,
+                format!("Failed to process {file_name}: {first_error_line}"),
+            );
+        }
+
+        file_queue.finish_item(item_id, result)
     }
 
     fn queue_file_item_remove(
@@ -574,13 +659,20 @@ impl<'thread_scope, 'config> TranscodeBackend<'config>
 
     fn progress_enable(&self) {
         let mut locked_state = self.ui_state.write();
+        let now = Instant::now();
+
         locked_state.progress = Some(Progress::default());
+        locked_state.progress_started_at = Some(now);
+        locked_state.album_remaining_time =
+            Some(RollingRemainingTimeEstimator::new(now));
         locked_state.current_page = UIPage::Transcoding;
     }
 
     fn progress_disable(&self) {
         let mut locked_state = self.ui_state.write();
         locked_state.progress = None;
+        locked_state.progress_started_at = None;
+        locked_state.album_remaining_time = None;
         locked_state.current_page = UIPage::Logs;
     }
 
@@ -699,6 +791,35 @@ impl<'thread_scope, 'config> TranscodeBackend<'config>
 
         Ok(())
     }
+
+
+    /*
+     * Scan progress
+     */
+
+    fn scan_progress_enable(&self, num_total: usize) {
+        let mut locked_state = self.ui_state.write();
+        locked_state.scan_progress = Some(ScanProgress::new(num_total));
+    }
+
+    fn scan_progress_disable(&self) {
+        let mut locked_state = self.ui_state.write();
+        locked_state.scan_progress = None;
+    }
+
+    fn scan_progress_set_scanned(&self, num_scanned: usize) -> Result<()> {
+        let mut locked_state = self.ui_state.write();
+
+        locked_state
+            .scan_progress
+            .as_mut()
+            .ok_or_else(|| {
+                miette!("Scan progress is disabled, can't set scanned items.")
+            })?
+            .items_scanned = num_scanned;
+
+        Ok(())
+    }
 }
 
 impl<'thread_scope, 'config> UserControllableBackend
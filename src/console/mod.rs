@@ -1,5 +1,7 @@
 pub use traits::*;
 
 pub mod colours;
+pub mod formatting;
 pub mod frontends;
+pub mod tracing_output;
 mod traits;
@@ -0,0 +1,114 @@
+use std::io::{self, Write};
+
+use miette::{miette, Result};
+use parking_lot::{const_rwlock, RwLock};
+use tracing::level_filters::LevelFilter;
+use tracing::Metadata;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::console::frontends::shared::logging::LogLevel;
+
+/// Receives the level and the formatted line (without the level and a trailing newline)
+/// of each `tracing` event.
+pub type TracingLogSink = Box<dyn Fn(LogLevel, String) + Send + Sync>;
+
+/// The sink of the terminal backend that is currently set up (see `set_tracing_log_sink`).
+/// While no backend is set up, events are written to the standard error output instead.
+static TRACING_LOG_SINK: RwLock<Option<TracingLogSink>> = const_rwlock(None);
+
+
+/// Forward all `tracing` events to `sink` (replacing the previous sink, if any).
+///
+/// Terminal backends call this when they are set up, so events are shown (and saved
+/// into the log file) the same way as the rest of their log output.
+pub fn set_tracing_log_sink(sink: TracingLogSink) {
+    *TRACING_LOG_SINK.write() = Some(sink);
+}
+
+/// Stop forwarding `tracing` events to the current sink (see `set_tracing_log_sink`).
+pub fn clear_tracing_log_sink() {
+    TRACING_LOG_SINK.write().take();
+}
+
+/// Format an event for the terminal: its level (padded to a common width) and the line,
+/// e.g. ` INFO Scanning libraries...`.
+pub fn format_tracing_line(level: LogLevel, line: &str) -> String {
+    format!("{:>5} {line}", level.tag())
+}
+
+/// Install the global `tracing` subscriber: events up to `max_level` are formatted
+/// as single lines (e.g. `Album finished. library="Lossless" album="Title"`)
+/// and forwarded, along with their level, to the active terminal backend.
+///
+/// At the `TRACE` level, the ID of the thread each event was emitted on is included as well.
+pub fn install_tracing_subscriber(max_level: LevelFilter) -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_max_level(max_level)
+        .with_writer(MakeTracingLogWriter)
+        .with_target(false)
+        .with_level(false)
+        .with_thread_ids(max_level == LevelFilter::TRACE)
+        .without_time()
+        .try_init()
+        .map_err(|error| {
+            miette!("Could not install the tracing subscriber: {error}")
+        })
+}
+
+
+/// Creates a `TracingLogWriter` for each event.
+struct MakeTracingLogWriter;
+
+impl<'a> MakeWriter<'a> for MakeTracingLogWriter {
+    type Writer = TracingLogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        TracingLogWriter::new(LogLevel::Info)
+    }
+
+    fn make_writer_for(&'a self, metadata: &Metadata<'_>) -> Self::Writer {
+        TracingLogWriter::new(LogLevel::from(*metadata.level()))
+    }
+}
+
+
+/// Collects a single formatted event and hands it to the tracing log sink once dropped.
+struct TracingLogWriter {
+    level: LogLevel,
+
+    buffer: Vec<u8>,
+}
+
+impl TracingLogWriter {
+    fn new(level: LogLevel) -> Self {
+        Self {
+            level,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Write for TracingLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for TracingLogWriter {
+    fn drop(&mut self) {
+        let line = String::from_utf8_lossy(&self.buffer).trim_end().to_string();
+        if line.is_empty() {
+            return;
+        }
+
+        match TRACING_LOG_SINK.read().as_ref() {
+            Some(sink) => sink(self.level, line),
+            None => eprintln!("{}", format_tracing_line(self.level, &line)),
+        }
+    }
+}
@@ -5,6 +5,7 @@ use std::thread::Scope;
 use miette::Result;
 use tokio::sync::broadcast;
 
+use crate::console::frontends::shared::logging::LogFileOptions;
 use crate::console::frontends::shared::queue::{
     AlbumQueueItem,
     AlbumQueueItemFinishedResult,
@@ -39,6 +40,7 @@ pub trait LogToFileBackend<'scope, 'scope_env: 'scope> {
     fn enable_saving_logs_to_file<P: AsRef<Path>>(
         &self,
         log_file_path: P,
+        options: &LogFileOptions,
         scope: &'scope Scope<'scope, 'scope_env>,
     ) -> Result<()>;
     fn disable_saving_logs_to_file(&self) -> Result<()>;
@@ -107,6 +109,14 @@ pub trait TranscodeBackend<'config> {
     /// This will give it the `FileItemState::InProgress` state.
     fn queue_file_item_start(&self, item_id: QueueItemID) -> Result<()>;
 
+    /// Update how far along the given in-progress file is (`progress` is between 0 and 1).
+    /// This is only called for files whose progress can be determined (i.e. transcodes).
+    fn queue_file_item_set_progress(
+        &self,
+        item_id: QueueItemID,
+        progress: f64,
+    ) -> Result<()>;
+
     /// Mark the given file in the file queue as "finished".
     /// This will give it the `FileItemState:Finished` state and the given `result`.
     fn queue_file_item_finish(
@@ -162,6 +172,19 @@ pub trait TranscodeBackend<'config> {
         &self,
         num_data_files_errored: usize,
     ) -> Result<()>;
+
+    /*
+     * Scan progress
+     */
+    /// Enable the scan progress indicator, which is shown while scanning (before any files are processed).
+    /// This must be called before `scan_progress_set_scanned`.
+    fn scan_progress_enable(&self, num_total: usize);
+
+    /// Disable (hide) the scan progress indicator.
+    fn scan_progress_disable(&self);
+
+    /// Set the number of items that have been scanned so far.
+    fn scan_progress_set_scanned(&self, num_scanned: usize) -> Result<()>;
 }
 
 /// Shared format for validation errors.
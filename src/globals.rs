@@ -6,3 +6,13 @@ pub static VERBOSE: state::InitCell<bool> = state::InitCell::new();
 pub fn is_verbose_enabled() -> bool {
     VERBOSE.get().eq(&true)
 }
+
+/// A global boolean indicating whether transcoding decisions should be narrated
+/// (see `transcode --explain`). Unset for commands other than `transcode`.
+pub static EXPLAIN: state::InitCell<bool> = state::InitCell::new();
+
+/// Shorthand to get the global flag value for explanations.
+#[inline]
+pub fn is_explain_enabled() -> bool {
+    EXPLAIN.try_get().eq(&Some(&true))
+}
@@ -0,0 +1,9 @@
+//! The euphony transcoding engine, usable without the `euphony` binary's terminal
+//! (see `commands::transcode_collection` and `commands::transcode::events::TranscodeEventSink`).
+
+pub mod cancellation;
+pub mod commands;
+pub mod console;
+pub mod globals;
+
+pub const EUPHONY_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,29 +1,39 @@
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
 use std::process::exit;
+use std::sync::Arc;
 use std::thread;
 use std::thread::Scope;
+use std::time::Instant;
 
-use clap::{Args, Parser, Subcommand};
-use crossterm::style::Stylize;
-use euphony_configuration::Configuration;
-use miette::{miette, Context, Result};
-
-use crate::console::frontends::terminal_ui::terminal::FancyTerminalBackend;
-use crate::console::frontends::{
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use euphony::commands::transcode::interrupt::is_interrupted;
+use euphony::commands::transcode::FfmpegUnavailableError;
+use euphony::commands::{
+    self,
+    Shard,
+    TranscodeOptions,
+    TranscodeRunSummary,
+    ValidateOptions,
+};
+use euphony::console::frontends::shared::logging::LogFileOptions;
+use euphony::console::frontends::terminal_ui::terminal::FancyTerminalBackend;
+use euphony::console::frontends::{
     BareTerminalBackend,
+    NdjsonTerminalBackend,
     SimpleTerminal,
     TranscodeTerminal,
     ValidationTerminal,
 };
-use crate::console::{LogBackend, LogToFileBackend, TerminalBackend};
-use crate::globals::VERBOSE;
-
-mod cancellation;
-mod commands;
-mod console;
-mod globals;
-
-pub const EUPHONY_VERSION: &str = env!("CARGO_PKG_VERSION");
+use euphony::console::tracing_output::install_tracing_subscriber;
+use euphony::console::{LogToFileBackend, TerminalBackend};
+use euphony::globals::{EXPLAIN, VERBOSE};
+use euphony_configuration::device_profile::DEFAULT_PROFILE_NAME;
+use euphony_configuration::error::ToolsConfigurationError;
+use euphony_configuration::Configuration;
+use miette::{miette, Context, Result};
+use tracing::level_filters::LevelFilter;
 
 #[derive(PartialEq, Eq)]
 #[derive(Subcommand)]
@@ -31,9 +41,39 @@ enum CLICommand {
     #[command(
         name = "transcode",
         visible_aliases(["transcode-collection"]),
-        about = "Transcode all libraries into the aggregated library."
+        about = "Transcode all libraries into the aggregated library \
+                 (or into the directory of a profile, see --profile)."
+    )]
+    TranscodeAll(TranscodeCollectionArgs),
+
+    #[command(
+        name = "transcode-library",
+        about = "Transcode a single library (given by its name or path) into the aggregated \
+                 library, skipping unchanged albums just like \"transcode\"."
+    )]
+    TranscodeLibrary(TranscodeLibraryArgs),
+
+    #[command(
+        name = "transcode-album",
+        about = "Transcode a single album directory into the aggregated library (e.g. after \
+                 fixing a tag), without scanning the rest of the collection."
+    )]
+    TranscodeAlbum(TranscodeAlbumArgs),
+
+    #[command(
+        name = "mirror",
+        about = "Copy all libraries into a separate directory as-is (audio files included, \
+                 without transcoding), skipping unchanged albums just like \"transcode\"."
+    )]
+    Mirror(MirrorArgs),
+
+    #[command(
+        name = "sync-device",
+        about = "Transcode all libraries (or a single one) into the directory of a device \
+                 profile (see device_profiles in the configuration), applying the profile's \
+                 overrides (output format, filesystem, size budget, playlist) for the run."
     )]
-    TranscodeAll(TranscodeAllArgs),
+    SyncDevice(SyncDeviceArgs),
 
     #[command(
         name = "validate",
@@ -43,17 +83,80 @@ enum CLICommand {
     )]
     ValidateAll(ValidateAllArgs),
 
+    #[command(
+        name = "validate-album",
+        about = "Validate a single album directory, using the same checks as the \
+                 \"validate\" command (including collisions with other libraries)."
+    )]
+    ValidateAlbum(ValidateAlbumArgs),
+
     #[command(
         name = "show-config",
         about = "Loads, validates and prints the current configuration."
     )]
-    ShowConfig,
+    ShowConfig(ShowConfigArgs),
 
     #[command(
         name = "list-libraries",
         about = "List all the registered libraries registered in the configuration."
     )]
-    ListLibraries,
+    ListLibraries(ListLibrariesArgs),
+
+    #[command(
+        name = "compare-libraries",
+        about = "Compare two libraries and list the albums present in one, but not the other \
+                 (artist and album names are compared case-insensitively)."
+    )]
+    CompareLibraries(CompareLibrariesArgs),
+
+    #[command(
+        name = "clean",
+        about = "List the files and directories in the aggregated library that no longer \
+                 correspond to any source file (e.g. transcodes of deleted albums) \
+                 and optionally delete them."
+    )]
+    Clean(CleanArgs),
+
+    #[command(
+        name = "completions",
+        hide = true,
+        about = "Print a shell completion script for euphony's commands and options \
+                 to the standard output (e.g. `euphony completions bash > /etc/bash_completion.d/euphony`)."
+    )]
+    Completions(CompletionsArgs),
+}
+
+impl CLICommand {
+    /// Returns the shared `transcode` options of the transcode-like commands
+    /// (`None` for all other commands).
+    fn transcode_args(&self) -> Option<&TranscodeAllArgs> {
+        match self {
+            CLICommand::TranscodeAll(transcode_all_args) => {
+                Some(&transcode_all_args.transcode_args)
+            }
+            CLICommand::TranscodeLibrary(library_args) => {
+                Some(&library_args.transcode_args)
+            }
+            CLICommand::TranscodeAlbum(album_args) => {
+                Some(&album_args.transcode_args)
+            }
+            CLICommand::Mirror(mirror_args) => Some(&mirror_args.transcode_args),
+            CLICommand::SyncDevice(sync_device_args) => {
+                Some(&sync_device_args.transcode_args)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Format of the transcoding progress output (see `--progress-format`).
+#[derive(ValueEnum, Copy, Clone, Eq, PartialEq)]
+enum ProgressFormat {
+    /// Human-readable output (the terminal UI or the bare terminal).
+    Human,
+
+    /// Newline-delimited JSON events on the standard output.
+    Ndjson,
 }
 
 #[derive(Args, Eq, PartialEq)]
@@ -63,24 +166,430 @@ struct TranscodeAllArgs {
         help = "Whether to disable any fancy terminal UI and simply print into the console. \
                 Keep in mind that this is a really bare version without any progress bars, but \
                 can be useful for debugging or for cases where you simply don't want \
-                a constantly-updating terminal UI (e.g. for saving logs)."
+                a constantly-updating terminal UI (e.g. for saving logs). \
+                If neither this nor --tui is set, the bare terminal is used automatically \
+                when the standard output is not a terminal."
     )]
     bare_terminal: bool,
 
+    #[arg(
+        long = "tui",
+        conflicts_with = "bare_terminal",
+        help = "Force the fancy terminal UI, even if the standard output doesn't appear \
+                to be a terminal (e.g. in a mis-detected terminal multiplexer). If the terminal UI \
+                can't be initialized, euphony exits with an error instead of falling back \
+                to the bare terminal."
+    )]
+    tui: bool,
+
+    #[arg(
+        long = "progress-format",
+        value_enum,
+        default_value_t = ProgressFormat::Human,
+        conflicts_with_all = ["bare_terminal", "tui"],
+        help = "Format of the progress output. With \"ndjson\", every album, file and progress \
+                update is written to the standard output as a single line of JSON \
+                (e.g. for external dashboards), while the human-readable log output \
+                goes to the standard error instead."
+    )]
+    progress_format: ProgressFormat,
+
     #[arg(
         long = "log-to-file",
-        help = "Path to the log file. If this is unset, no logs are saved."
+        help = "Path to the log file. Defaults to logging.default_log_output_path \
+                in the configuration; if neither is set, no logs are saved."
     )]
     log_to_file: Option<PathBuf>,
+
+    #[arg(
+        long = "log-append",
+        help = "Append to the log file if it already exists \
+                (overrides logging.append_to_existing_log_file)."
+    )]
+    log_append: bool,
+
+    #[arg(
+        long = "log-overwrite",
+        conflicts_with = "log_append",
+        help = "Overwrite the log file if it already exists \
+                (overrides logging.append_to_existing_log_file)."
+    )]
+    log_overwrite: bool,
+
+    #[arg(
+        long = "use-cached-scan",
+        help = "Skip scanning libraries that appear unchanged since the last transcode. \
+                This compares a quick fingerprint of each library's directory tree \
+                (file names, sizes and modification times), so in-place file modifications \
+                that keep both are not detected - only use this when you know the libraries \
+                haven't changed."
+    )]
+    use_cached_scan: bool,
+
+    #[arg(
+        long = "print-unchanged",
+        help = "Also list the albums that are up to date and are therefore skipped \
+                (useful for confirming which albums euphony considers unchanged)."
+    )]
+    print_unchanged: bool,
+
+    #[arg(
+        long = "parallel-scan",
+        help = "Scan libraries and albums for changes in parallel (using the configured \
+                number of transcode threads). This can considerably speed up the scanning \
+                phase of large collections, but is more taxing on the disk."
+    )]
+    parallel_scan: bool,
+
+    #[arg(
+        long = "explain",
+        help = "Narrate each decision in plain language: why each library and album \
+                needs processing (or doesn't) and what happens to each file and why. \
+                Useful for understanding what euphony does, especially on first use."
+    )]
+    explain: bool,
+
+    #[arg(
+        long = "stdin-paths",
+        help = "Instead of scanning the libraries for changes, read newline-delimited source \
+                file paths from the standard input and transcode (or copy) exactly those files. \
+                Saved album states are ignored and not updated. Paths that are not inside \
+                any registered library are reported and skipped."
+    )]
+    stdin_paths: bool,
+
+    #[arg(
+        long = "shard",
+        value_name = "INDEX/COUNT",
+        conflicts_with = "stdin_paths",
+        help = "Only process the changed albums that belong to the given shard (e.g. 1/3), \
+                which makes it possible to split a large transcode across multiple machines: \
+                running shards 1/3, 2/3 and 3/3 covers every album exactly once. Albums are \
+                assigned to shards by a stable hash of their path. All machines must share \
+                the same source libraries and aggregated library (e.g. via a network mount). \
+                Library states are not updated, so finish with a regular run."
+    )]
+    shard: Option<Shard>,
+
+    #[arg(
+        long = "dry-run",
+        conflicts_with = "stdin_paths",
+        help = "Scan the libraries for changes as usual, but only list the albums and files \
+                that would be transcoded, copied or deleted (with per-library counts). \
+                ffmpeg is not run, the aggregated library is not touched and no state files \
+                are saved. Uses the bare terminal unless --tui is given."
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long = "status-file",
+        value_name = "FILE",
+        conflicts_with = "stdin_paths",
+        help = "Periodically (every few seconds) write a small JSON file with the current \
+                library, album and file counts and percentages to this path, e.g. for \
+                a monitoring dashboard. The file is replaced atomically, so readers \
+                never see a partially-written file."
+    )]
+    status_file: Option<PathBuf>,
+
+    #[arg(
+        long = "yes",
+        visible_alias = "overwrite",
+        help = "Proceed even if more albums than allowed by \
+                aggregated_library.max_reprocess_percentage would be processed again \
+                (otherwise euphony aborts before processing anything)."
+    )]
+    yes: bool,
+
+    #[arg(
+        long = "ignore-free-space",
+        help = "Proceed even if the estimated output would leave less than \
+                aggregated_library.free_space_margin_mib of free space on the \
+                aggregated library's volume (otherwise euphony aborts before processing anything)."
+    )]
+    ignore_free_space: bool,
+
+    #[arg(
+        short = 'j',
+        long = "threads",
+        value_name = "N",
+        help = "Number of files to transcode or copy in parallel, overriding \
+                aggregated_library.transcode_threads for this run only \
+                (the configuration file is left unchanged). The minimum value is 1."
+    )]
+    threads: Option<usize>,
+
+    #[arg(
+        long = "prune-empty-dirs",
+        conflicts_with = "shard",
+        help = "At the end of the run, remove all empty directories in the aggregated library \
+                (e.g. left behind by albums that produced no output files). Directories \
+                containing hidden files (such as euphony's state files) are kept, unless \
+                --force is given as well."
+    )]
+    prune_empty_dirs: bool,
+
+    #[arg(
+        long = "force",
+        requires = "prune_empty_dirs",
+        help = "With --prune-empty-dirs, also remove directories that contain nothing but \
+                hidden files (the hidden files are removed along with them)."
+    )]
+    force: bool,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct TranscodeCollectionArgs {
+    #[arg(
+        long = "profile",
+        value_name = "NAME",
+        help = "Transcode into the directory of the given profile (a [device_profiles.<NAME>] \
+                or [profiles.<NAME>] table in the configuration) with its overrides applied, \
+                just like sync-device. Each profile keeps its own state files, so profiles \
+                don't interfere with each other. \"default\" (or omitting this option) \
+                transcodes into the aggregated library."
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long = "library",
+        visible_alias = "only",
+        value_name = "NAME",
+        help = "Only transcode the given library (by its name as shown by list-libraries \
+                or by the path to its directory). Can be repeated to transcode several \
+                libraries. If omitted, all libraries are transcoded."
+    )]
+    libraries: Vec<String>,
+
+    #[arg(
+        long = "watch",
+        conflicts_with_all = ["dry_run", "shard", "stdin_paths"],
+        help = "After transcoding, keep running and watch the libraries for changes: \
+                whenever files are changed, added or removed, only the affected albums \
+                are transcoded again (once the changes have settled for a few seconds). \
+                Stop watching with Ctrl-C."
+    )]
+    watch: bool,
+
+    #[command(flatten)]
+    transcode_args: TranscodeAllArgs,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct TranscodeLibraryArgs {
+    #[arg(
+        help = "Name of the library to transcode (as shown by list-libraries) \
+                or the path to its directory."
+    )]
+    library: String,
+
+    #[command(flatten)]
+    transcode_args: TranscodeAllArgs,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct TranscodeAlbumArgs {
+    #[arg(
+        help = "Path to the album directory to transcode. It must be inside one of the \
+                registered libraries (i.e. at <library>/<artist>/<album>). As the rest of \
+                the library is not scanned, the library state is not updated."
+    )]
+    album_path: PathBuf,
+
+    #[command(flatten)]
+    transcode_args: TranscodeAllArgs,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct MirrorArgs {
+    #[arg(
+        help = "Path to the directory to mirror the libraries into. It must not be the \
+                aggregated library, as the copies would clash with the transcoded files. \
+                Mirroring keeps its own state files, so it doesn't interfere with transcoding."
+    )]
+    output_directory: PathBuf,
+
+    #[command(flatten)]
+    transcode_args: TranscodeAllArgs,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct SyncDeviceArgs {
+    #[arg(
+        long = "profile",
+        help = "Name of the device profile to sync (a [device_profiles.<NAME>] table \
+                in the configuration). Each profile keeps its own state files, \
+                so syncing a device doesn't interfere with regular transcoding."
+    )]
+    profile: String,
+
+    #[arg(
+        long = "library",
+        help = "Only sync a single library (given by its name or path) instead of all of them."
+    )]
+    library: Option<String>,
+
+    #[command(flatten)]
+    transcode_args: TranscodeAllArgs,
 }
 
 #[derive(Args, Eq, PartialEq)]
 struct ValidateAllArgs {
     #[arg(
         long = "log-to-file",
-        help = "Path to the log file. If this is unset, no logs are saved."
+        help = "Path to the log file. Defaults to logging.default_log_output_path \
+                in the configuration; if neither is set, no logs are saved."
     )]
     log_to_file: Option<PathBuf>,
+
+    #[arg(
+        long = "log-append",
+        help = "Append to the log file if it already exists \
+                (overrides logging.append_to_existing_log_file)."
+    )]
+    log_append: bool,
+
+    #[arg(
+        long = "log-overwrite",
+        conflicts_with = "log_append",
+        help = "Overwrite the log file if it already exists \
+                (overrides logging.append_to_existing_log_file)."
+    )]
+    log_overwrite: bool,
+
+    #[arg(
+        long = "baseline",
+        help = "Path to a validation report (see --report). Validation errors that are \
+                already present in the baseline are not displayed and the command exits \
+                with the validation errors exit code (4) only if there are new validation errors."
+    )]
+    baseline: Option<PathBuf>,
+
+    #[arg(
+        long = "report",
+        help = "Path to save a JSON validation report of all current validation errors into. \
+                The report can later be used as a baseline (see --baseline)."
+    )]
+    report: Option<PathBuf>,
+
+    #[arg(
+        long = "fix",
+        help = "After validating, delete the displayed unexpected files (files that are not \
+                allowed in their library) or, if validation.quarantine_directory is set, \
+                move them there. Each deleted or moved file is logged. \
+                Asks for confirmation first, unless --yes is passed."
+    )]
+    fix: bool,
+
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t = OutputFormat::Human,
+        conflicts_with = "fix",
+        help = "Output format. With \"json\", the validation errors are printed as a JSON \
+                report (violations grouped by library, each with a category, a path \
+                relative to the library and a message)."
+    )]
+    format: OutputFormat,
+
+    #[arg(
+        long = "yes",
+        requires = "fix",
+        help = "Don't ask for confirmation before fixing unexpected files (see --fix)."
+    )]
+    yes: bool,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct ValidateAlbumArgs {
+    #[arg(
+        help = "Path to the album directory to validate. It must be inside one of the \
+                registered libraries (i.e. at <library>/<artist>/<album>)."
+    )]
+    album_path: PathBuf,
+
+    #[command(flatten)]
+    validate_args: ValidateAllArgs,
+}
+
+/// Output format of the `show-config`, `list-libraries` and `validate` commands (see `--format`).
+#[derive(ValueEnum, Copy, Clone, Eq, PartialEq)]
+enum OutputFormat {
+    /// Human-readable text.
+    Human,
+
+    /// Pretty-printed JSON (e.g. for scripting).
+    Json,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct ShowConfigArgs {
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t = OutputFormat::Human,
+        help = "Output format. With \"json\", the resolved configuration \
+                is printed as JSON instead of human-readable text."
+    )]
+    format: OutputFormat,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct ListLibrariesArgs {
+    #[arg(
+        long = "with-stats",
+        help = "Also show the number of albums and the most recently modified album \
+                of each library. This requires a quick pass over each library \
+                (only modification times are checked)."
+    )]
+    with_stats: bool,
+
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t = OutputFormat::Human,
+        help = "Output format. With \"json\", the libraries (keyed by their keys) \
+                are printed as JSON instead of human-readable text."
+    )]
+    format: OutputFormat,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct CompareLibrariesArgs {
+    #[arg(help = "Key or name of the first library.")]
+    library_a: String,
+
+    #[arg(help = "Key or name of the second library.")]
+    library_b: String,
+
+    #[arg(
+        long = "json",
+        help = "Print the comparison as JSON instead of human-readable text."
+    )]
+    json: bool,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct CleanArgs {
+    #[arg(
+        long = "log-to-file",
+        help = "Path to the log file. Defaults to logging.default_log_output_path \
+                in the configuration; if neither is set, no logs are saved."
+    )]
+    log_to_file: Option<PathBuf>,
+
+    #[arg(
+        long = "delete",
+        help = "Actually remove the stale files and directories. Without this option, \
+                they are only listed (along with how much space removing them would free up)."
+    )]
+    delete: bool,
+}
+
+#[derive(Args, Eq, PartialEq)]
+struct CompletionsArgs {
+    #[arg(value_enum, help = "Shell to generate the completion script for.")]
+    shell: Shell,
 }
 
 #[derive(Parser)]
@@ -102,7 +611,8 @@ struct CLIArgs {
         global = true,
         help = "Optionally a path to your configuration file. Without this option, \
                 euphony tries to load ./data/configuration.toml (relative to the binary), \
-                but understandably this might not always be the most convenient location."
+                but understandably this might not always be the most convenient location. \
+                Pass \"-\" to read the configuration from the standard input instead."
     )]
     config: Option<String>,
 
@@ -110,26 +620,77 @@ struct CLIArgs {
         short = 'v',
         long = "verbose",
         global = true,
-        help = "Increase the verbosity of output."
+        help = "Increase the verbosity of output (this includes debug-level events)."
     )]
     verbose: bool,
 
+    #[arg(
+        long = "trace",
+        global = true,
+        help = "Like --verbose, but also includes trace-level events \
+                (e.g. the thread pool's scheduling and the threads events come from)."
+    )]
+    trace: bool,
+
     #[command(subcommand)]
     command: CLICommand,
 }
 
+/// Passing this as `--config` reads the configuration from the standard input.
+const STDIN_CONFIGURATION_PATH: &str = "-";
+
 /// Load and return the configuration, given the command line arguments
-/// (`-c`/`--config` can override the load path).
+/// (`-c`/`--config` can override the load path, `-c -` reads it from the standard input).
 fn get_configuration(args: &CLIArgs) -> Result<Configuration> {
-    if args.config.is_some() {
-        Configuration::load_from_path(args.config.clone().unwrap())
-    } else {
-        Configuration::load_default_path()
+    match args.config.as_deref() {
+        Some(STDIN_CONFIGURATION_PATH) => {
+            let uses_stdin_paths = args
+                .command
+                .transcode_args()
+                .is_some_and(|transcode_args| transcode_args.stdin_paths);
+
+            if uses_stdin_paths {
+                return Err(miette!(
+                    "The configuration and --stdin-paths can't both be read \
+                    from the standard input."
+                ));
+            }
+
+            Configuration::load_from_reader(io::stdin().lock())
+        }
+        Some(configuration_path) => {
+            Configuration::load_from_path(configuration_path)
+        }
+        None => Configuration::load_default_path(),
+    }
+}
+
+/// Returns the log file options from the configuration,
+/// with `--log-append` or `--log-overwrite` taking precedence.
+fn get_log_file_options(
+    config: &Configuration,
+    log_append: bool,
+    log_overwrite: bool,
+) -> LogFileOptions {
+    let mut options = LogFileOptions::from_configuration(&config.logging);
+
+    if log_append {
+        options.append = true;
+    } else if log_overwrite {
+        options.append = false;
     }
+
+    options
 }
 
 /// Initializes and returns a terminal backend for transcoding.
-/// If `use_bare` is true, this will return `BareConsoleBackend`, otherwise `TUITerminalBackend`.
+///
+/// The backend is chosen in the following order of precedence:
+/// - if `progress_format` is `ProgressFormat::Ndjson`, this returns `NdjsonTerminalBackend`,
+/// - if `force_fancy_terminal` is true (`--tui`), this returns `FancyTerminalBackend`,
+/// - if `use_bare_terminal` is true (`--bare-terminal`), this returns `BareConsoleBackend`,
+/// - otherwise `FancyTerminalBackend` is used if the standard output is a terminal
+///   and `BareConsoleBackend` if it isn't (e.g. when piping the output into a file).
 ///
 /// `FancyTerminalBackend` has a better and dynamic terminal UI, but is harder to debug non-UI bugs.
 ///
@@ -138,102 +699,213 @@ fn get_configuration(args: &CLIArgs) -> Result<Configuration> {
 fn get_transcode_terminal<'scope>(
     config: &Configuration,
     use_bare_terminal: bool,
-) -> TranscodeTerminal<'_, 'scope> {
-    if use_bare_terminal {
-        BareTerminalBackend::new().into()
+    force_fancy_terminal: bool,
+    progress_format: ProgressFormat,
+) -> Result<TranscodeTerminal<'_, 'scope>> {
+    if progress_format == ProgressFormat::Ndjson {
+        return Ok(NdjsonTerminalBackend::new().into());
+    }
+
+    let use_fancy_terminal = if force_fancy_terminal {
+        true
+    } else if use_bare_terminal {
+        false
+    } else {
+        io::stdout().is_terminal()
+    };
+
+    if use_fancy_terminal {
+        Ok(FancyTerminalBackend::new(config)
+            .wrap_err_with(|| {
+                miette!("Could not create fancy terminal UI backend.")
+            })?
+            .into())
     } else {
-        FancyTerminalBackend::new(config)
-            .expect("Could not create fancy terminal UI backend.")
-            .into()
+        Ok(BareTerminalBackend::new().into())
+    }
+}
+
+
+/// Why a command failed, which decides euphony's exit code (see `FailureCategory::exit_code`).
+/// The exit codes are documented in the README, so scripts can branch on them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FailureCategory {
+    /// Any error that doesn't fit into the other categories
+    /// (the configuration can't be loaded or is invalid, a library doesn't exist, etc.).
+    Other,
+
+    /// A required external tool (ffmpeg) can't be run.
+    Tool,
+
+    /// The run finished, but some files failed to be processed.
+    FailedFiles,
+
+    /// Validation found (new) validation errors.
+    ValidationErrors,
+
+    /// The run was interrupted (Ctrl-C).
+    Interrupted,
+}
+
+impl FailureCategory {
+    fn exit_code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Tool => 2,
+            Self::FailedFiles => 3,
+            Self::ValidationErrors => 4,
+            // The conventional `128 + SIGINT`.
+            Self::Interrupted => 130,
+        }
+    }
+}
+
+/// A failed command: its category and the error to print,
+/// unless the command has already logged everything there is to know.
+struct CommandFailure {
+    category: FailureCategory,
+
+    error: Option<miette::Report>,
+}
+
+impl CommandFailure {
+    fn without_error(category: FailureCategory) -> Self {
+        Self {
+            category,
+            error: None,
+        }
+    }
+}
+
+impl From<miette::Report> for CommandFailure {
+    fn from(error: miette::Report) -> Self {
+        let is_tool_error =
+            error.downcast_ref::<FfmpegUnavailableError>().is_some()
+                || matches!(
+                    error.downcast_ref::<ToolsConfigurationError>(),
+                    Some(ToolsConfigurationError::BinaryNotFound { .. })
+                );
+
+        let category = if is_tool_error {
+            FailureCategory::Tool
+        } else {
+            FailureCategory::Other
+        };
+
+        Self {
+            category,
+            error: Some(error),
+        }
     }
 }
 
+
 /// Initializes the required terminal backend and executes the given CLI command.
 fn run_requested_cli_command<'config: 'scope, 'scope, 'scope_env: 'scope>(
     args: CLIArgs,
-    config: &'config Configuration,
+    config: &'config Arc<Configuration>,
     scope: &'scope Scope<'scope, 'scope_env>,
-) -> Result<()> {
-    if let CLICommand::TranscodeAll(transcode_args) = args.command {
-        // `transcode`/`transcode-all` has two available terminal frontends:
-        // - the fancy one uses `ratatui` for a full-fledged terminal UI with progress bars and multiple "windows",
-        // - the bare one (enabled with --bare-terminal) is a simple console echo implementation (no progress bars, etc.).
-        let terminal =
-            get_transcode_terminal(config, transcode_args.bare_terminal);
-
-        if let Some(log_file_path) = transcode_args
-            .log_to_file
-            .or_else(|| config.logging.default_log_output_path.clone())
-        {
-            terminal
-                .enable_saving_logs_to_file(log_file_path, scope)
-                .wrap_err_with(|| {
-                    miette!("Failed to enable logging to disk.")
-                })?;
-        }
+) -> Result<(), CommandFailure> {
+    if let CLICommand::TranscodeAll(args) = args.command {
+        // With a profile, the run is the same as syncing a device (see `main`).
+        let transcode_command = if args.watch {
+            TranscodeCommand::Watch(args.libraries)
+        } else {
+            match config.aggregated_library.device_profile {
+                Some(_) => TranscodeCommand::SyncDevice(args.libraries),
+                None => TranscodeCommand::All(args.libraries),
+            }
+        };
+
+        run_transcode_command(
+            config,
+            scope,
+            args.transcode_args,
+            transcode_command,
+        )
+    } else if let CLICommand::TranscodeLibrary(args) = args.command {
+        run_transcode_command(
+            config,
+            scope,
+            args.transcode_args,
+            TranscodeCommand::Library(args.library),
+        )
+    } else if let CLICommand::TranscodeAlbum(args) = args.command {
+        run_transcode_command(
+            config,
+            scope,
+            args.transcode_args,
+            TranscodeCommand::Album(args.album_path),
+        )
+    } else if let CLICommand::Mirror(args) = args.command {
+        run_transcode_command(
+            config,
+            scope,
+            args.transcode_args,
+            TranscodeCommand::Mirror,
+        )
+    } else if let CLICommand::SyncDevice(args) = args.command {
+        run_transcode_command(
+            config,
+            scope,
+            args.transcode_args,
+            TranscodeCommand::SyncDevice(args.library.into_iter().collect()),
+        )
+    } else if let CLICommand::ValidateAll(args) = args.command {
+        run_validation_command(config, scope, args, None)
+    } else if let CLICommand::ValidateAlbum(args) = args.command {
+        run_validation_command(
+            config,
+            scope,
+            args.validate_args,
+            Some(args.album_path),
+        )
+    } else if let CLICommand::ShowConfig(args) = args.command {
+        let mut terminal: SimpleTerminal = BareTerminalBackend::new().into();
 
         terminal.setup(scope).wrap_err_with(|| {
             miette!("Failed to set up terminal UI backend.")
         })?;
 
 
-        let result = commands::cmd_transcode_all(config, &terminal)
-            .wrap_err_with(|| {
-                miette!("Failed to execute transcode command to completion.")
-            });
-        if let Err(error) = result {
-            terminal.log_println(format!("{error}").dark_red());
-        }
+        let result = commands::cmd_show_config(
+            config,
+            &mut terminal,
+            args.format == OutputFormat::Json,
+        );
 
 
         terminal.destroy().wrap_err_with(|| {
             miette!("Failed to destroy terminal UI backend.")
         })?;
 
-        Ok(())
-    } else if let CLICommand::ValidateAll(args) = args.command {
-        let mut terminal: ValidationTerminal = BareTerminalBackend::new().into();
-
-        if let Some(log_file_path) = args
-            .log_to_file
-            .or_else(|| config.logging.default_log_output_path.clone())
-        {
-            terminal
-                .enable_saving_logs_to_file(log_file_path, scope)
-                .wrap_err_with(|| {
-                    miette!("Failed to enable logging to disk.")
-                })?;
-        }
+        result
+            .wrap_err_with(|| miette!("Failed to show configuration."))
+            .map_err(CommandFailure::from)
+    } else if let CLICommand::ListLibraries(args) = args.command {
+        let mut terminal: SimpleTerminal = BareTerminalBackend::new().into();
 
         terminal.setup(scope).wrap_err_with(|| {
             miette!("Failed to set up terminal UI backend.")
         })?;
 
 
-
-        let result = commands::cmd_validate(config, &mut terminal)
-            .wrap_err_with(|| {
-                miette!("Failed to execute transcode command to completion.")
-            });
-
-        match result {
-            Ok(_) => {}
-            Err(error) => {
-                terminal.log_println(format!(
-                    "{}: {}",
-                    "Something went wrong while validating:".red(),
-                    error,
-                ));
-            }
-        };
+        let result = commands::cmd_list_libraries(
+            config,
+            &mut terminal,
+            args.with_stats,
+            args.format == OutputFormat::Json,
+        );
 
 
         terminal.destroy().wrap_err_with(|| {
             miette!("Failed to destroy terminal UI backend.")
         })?;
 
-        Ok(())
-    } else if args.command == CLICommand::ShowConfig {
+        result
+            .wrap_err_with(|| miette!("Failed to list libraries."))
+            .map_err(CommandFailure::from)
+    } else if let CLICommand::CompareLibraries(args) = args.command {
         let mut terminal: SimpleTerminal = BareTerminalBackend::new().into();
 
         terminal.setup(scope).wrap_err_with(|| {
@@ -241,57 +913,461 @@ fn run_requested_cli_command<'config: 'scope, 'scope, 'scope_env: 'scope>(
         })?;
 
 
-        commands::cmd_show_config(config, &mut terminal);
+        let result = commands::cmd_compare_libraries(
+            config,
+            &mut terminal,
+            &args.library_a,
+            &args.library_b,
+            args.json,
+        );
 
 
         terminal.destroy().wrap_err_with(|| {
             miette!("Failed to destroy terminal UI backend.")
         })?;
 
-        Ok(())
-    } else if args.command == CLICommand::ListLibraries {
+        result
+            .wrap_err_with(|| miette!("Failed to compare libraries."))
+            .map_err(CommandFailure::from)
+    } else if let CLICommand::Clean(args) = args.command {
         let mut terminal: SimpleTerminal = BareTerminalBackend::new().into();
 
+        // Unlike the other maintenance commands, this one can delete files,
+        // so its output is saved just like that of transcoding and validation.
+        if let Some(log_file_path) = args
+            .log_to_file
+            .or_else(|| config.logging.default_log_output_path.clone())
+        {
+            terminal
+                .enable_saving_logs_to_file(
+                    log_file_path,
+                    &LogFileOptions::from_configuration(&config.logging),
+                    scope,
+                )
+                .wrap_err_with(|| {
+                    miette!("Failed to enable logging to disk.")
+                })?;
+        }
+
         terminal.setup(scope).wrap_err_with(|| {
             miette!("Failed to set up terminal UI backend.")
         })?;
 
 
-        commands::cmd_list_libraries(config, &mut terminal);
+        let result = commands::cmd_clean(config, &mut terminal, args.delete);
 
 
         terminal.destroy().wrap_err_with(|| {
             miette!("Failed to destroy terminal UI backend.")
         })?;
 
-        Ok(())
+        result
+            .wrap_err_with(|| miette!("Failed to clean the aggregated library."))
+            .map_err(CommandFailure::from)
     } else {
         panic!("Unrecognized command!");
     }
 }
 
+
+/// Which of the commands that share the `transcode` options is being run.
+enum TranscodeCommand {
+    /// `transcode`: all libraries or, if not empty, only the given ones
+    /// (each given by its name or path).
+    All(Vec<String>),
+
+    /// `transcode --watch`: like `All`, but the libraries are watched for changes afterwards
+    /// (with `--profile`, `config` is already a device profile configuration).
+    Watch(Vec<String>),
+
+    /// `transcode-library`: a single library, given by its name or path.
+    Library(String),
+
+    /// `transcode-album`: a single album, given by its directory path.
+    Album(PathBuf),
+
+    /// `mirror`: all libraries, copied as-is (`config` must already be a mirror configuration,
+    /// see `Configuration::into_mirror_configuration`).
+    Mirror,
+
+    /// `sync-device`: all libraries or, if not empty, only the given ones (by name or path),
+    /// transcoded into the directory of a device profile (`config` must already be a device
+    /// profile configuration, see `Configuration::into_device_profile_configuration`).
+    SyncDevice(Vec<String>),
+}
+
+
+/// Initializes the terminal backend and runs the given transcode-like command
+/// (`transcode`, `transcode-library`, `transcode-album` or `mirror`).
+fn run_transcode_command<'config: 'scope, 'scope, 'scope_env: 'scope>(
+    config: &'config Arc<Configuration>,
+    scope: &'scope Scope<'scope, 'scope_env>,
+    transcode_args: TranscodeAllArgs,
+    command: TranscodeCommand,
+) -> Result<(), CommandFailure> {
+    EXPLAIN.set(transcode_args.explain);
+
+    let is_restricted_to_libraries = match &command {
+        TranscodeCommand::All(library_names_or_paths)
+        | TranscodeCommand::Watch(library_names_or_paths)
+        | TranscodeCommand::SyncDevice(library_names_or_paths) => {
+            !library_names_or_paths.is_empty()
+        }
+        TranscodeCommand::Library(_) | TranscodeCommand::Album(_) => true,
+        TranscodeCommand::Mirror => false,
+    };
+
+    if transcode_args.stdin_paths && is_restricted_to_libraries {
+        return Err(miette!(
+            "--stdin-paths can only be used with the transcode, mirror and sync-device \
+            commands (and not together with --library)."
+        )
+        .into());
+    }
+
+    // This must happen before the terminal UI is set up, as the paths are piped in through stdin.
+    let explicit_source_file_paths = if transcode_args.stdin_paths {
+        Some(commands::read_paths_from_stdin()?)
+    } else {
+        None
+    };
+
+    // `transcode`/`transcode-all` has two available terminal frontends:
+    // - the fancy one uses `ratatui` for a full-fledged terminal UI with progress bars and multiple "windows",
+    // - the bare one (enabled with --bare-terminal) is a simple console echo implementation (no progress bars, etc.).
+    // If neither --bare-terminal nor --tui is set, the bare one is used when stdout is not a terminal.
+    // With --progress-format ndjson, a machine-readable one is used instead (JSON events on stdout).
+    // A dry run only prints a preview, which would vanish along with the terminal UI.
+    let terminal = get_transcode_terminal(
+        config,
+        transcode_args.bare_terminal || transcode_args.dry_run,
+        transcode_args.tui,
+        transcode_args.progress_format,
+    )?;
+
+    if let Some(log_file_path) = transcode_args
+        .log_to_file
+        .or_else(|| config.logging.default_log_output_path.clone())
+    {
+        terminal
+            .enable_saving_logs_to_file(
+                log_file_path,
+                &get_log_file_options(
+                    config,
+                    transcode_args.log_append,
+                    transcode_args.log_overwrite,
+                ),
+                scope,
+            )
+            .wrap_err_with(|| miette!("Failed to enable logging to disk."))?;
+    }
+
+    terminal.setup(scope).wrap_err_with(|| {
+        if transcode_args.tui {
+            miette!(
+                "Failed to set up terminal UI backend (forced with --tui, \
+                use --bare-terminal if your terminal doesn't support it)."
+            )
+        } else {
+            miette!("Failed to set up terminal UI backend.")
+        }
+    })?;
+
+
+    let transcode_options = TranscodeOptions {
+        use_cached_scan: transcode_args.use_cached_scan,
+        print_unchanged: transcode_args.print_unchanged,
+        parallel_scan: transcode_args.parallel_scan,
+        shard: transcode_args.shard,
+        dry_run: transcode_args.dry_run,
+        status_file_path: transcode_args.status_file,
+        allow_mass_reprocessing: transcode_args.yes,
+        ignore_free_space: transcode_args.ignore_free_space,
+        prune_empty_directories: transcode_args.prune_empty_dirs,
+        prune_directories_with_hidden_files: transcode_args.force,
+    };
+
+    // Watching never completes and explicit file lists are meant for scripts, so only
+    // the other runs end with a desktop notification (see `ui.notify_on_completion`).
+    let is_watching = matches!(command, TranscodeCommand::Watch(_));
+    let notifies_on_completion = !transcode_args.dry_run
+        && explicit_source_file_paths.is_none()
+        && !is_watching;
+    let time_command_start = Instant::now();
+
+    let result = match explicit_source_file_paths {
+        Some(source_file_paths) => {
+            commands::cmd_transcode_paths(config, &terminal, source_file_paths)
+        }
+        None => match command {
+            TranscodeCommand::All(library_names_or_paths) => {
+                commands::cmd_transcode_all(
+                    config,
+                    &terminal,
+                    &library_names_or_paths,
+                    transcode_options,
+                )
+            }
+            TranscodeCommand::Watch(library_names_or_paths) => {
+                commands::cmd_transcode_watch(
+                    config,
+                    &terminal,
+                    &library_names_or_paths,
+                    transcode_options,
+                )
+                .map(|_| TranscodeRunSummary::default())
+            }
+            TranscodeCommand::Library(library_name_or_path) => {
+                commands::cmd_transcode_library(
+                    config,
+                    &terminal,
+                    &library_name_or_path,
+                    transcode_options,
+                )
+            }
+            TranscodeCommand::Album(album_directory_path) => {
+                commands::cmd_transcode_album(
+                    config,
+                    &terminal,
+                    &album_directory_path,
+                    transcode_options,
+                )
+            }
+            TranscodeCommand::Mirror => {
+                commands::cmd_mirror_all(config, &terminal, transcode_options)
+            }
+            TranscodeCommand::SyncDevice(library_names_or_paths) => {
+                commands::cmd_sync_device(
+                    config,
+                    &terminal,
+                    &library_names_or_paths,
+                    transcode_options,
+                )
+            }
+        },
+    }
+    .wrap_err_with(|| {
+        miette!("Failed to execute transcode command to completion.")
+    });
+
+
+    terminal
+        .destroy()
+        .wrap_err_with(|| miette!("Failed to destroy terminal UI backend."))?;
+
+    if notifies_on_completion {
+        commands::notify_transcode_completion(
+            config,
+            &result,
+            time_command_start.elapsed(),
+        );
+    }
+
+    // Watching is meant to be stopped with Ctrl-C, so that isn't treated as a failure.
+    if is_interrupted() && !is_watching {
+        return Err(CommandFailure {
+            category: FailureCategory::Interrupted,
+            error: result.err(),
+        });
+    }
+
+    // The error is returned (instead of being logged) only once the terminal backend
+    // has been destroyed, so it is printed after the terminal UI is gone.
+    let summary = result?;
+
+    // The failed files have already been logged (along with the run summary).
+    if summary.num_errored_albums > 0 {
+        return Err(CommandFailure::without_error(
+            FailureCategory::FailedFiles,
+        ));
+    }
+
+    Ok(())
+}
+
+
+/// Initializes the terminal backend and runs either the `validate` command
+/// or, if `album_directory_path` is set, the `validate-album` command.
+fn run_validation_command<'config: 'scope, 'scope, 'scope_env: 'scope>(
+    config: &'config Configuration,
+    scope: &'scope Scope<'scope, 'scope_env>,
+    args: ValidateAllArgs,
+    album_directory_path: Option<PathBuf>,
+) -> Result<(), CommandFailure> {
+    let mut terminal: ValidationTerminal = BareTerminalBackend::new().into();
+
+    if let Some(log_file_path) = args
+        .log_to_file
+        .or_else(|| config.logging.default_log_output_path.clone())
+    {
+        terminal
+            .enable_saving_logs_to_file(
+                log_file_path,
+                &get_log_file_options(
+                    config,
+                    args.log_append,
+                    args.log_overwrite,
+                ),
+                scope,
+            )
+            .wrap_err_with(|| miette!("Failed to enable logging to disk."))?;
+    }
+
+    terminal
+        .setup(scope)
+        .wrap_err_with(|| miette!("Failed to set up terminal UI backend."))?;
+
+
+
+    let is_using_baseline = args.baseline.is_some();
+    let is_outputting_json = args.format == OutputFormat::Json;
+    let validate_options = ValidateOptions {
+        baseline_file_path: args.baseline,
+        report_file_path: args.report,
+        fix_unexpected_files: args.fix,
+        skip_fix_confirmation: args.yes,
+        output_json: is_outputting_json,
+    };
+
+    let result = match album_directory_path {
+        Some(album_directory_path) => commands::cmd_validate_album(
+            config,
+            &mut terminal,
+            &album_directory_path,
+            validate_options,
+        ),
+        None => commands::cmd_validate(config, &mut terminal, validate_options),
+    }
+    .wrap_err_with(|| {
+        miette!("Failed to execute validation command to completion.")
+    });
+
+    terminal
+        .destroy()
+        .wrap_err_with(|| miette!("Failed to destroy terminal UI backend."))?;
+
+    let num_validation_errors = result?;
+    if num_validation_errors == 0 {
+        return Ok(());
+    }
+
+    // Validation errors fail the run (e.g. when running in CI). When comparing against
+    // a baseline, only the new ones are counted. The errors themselves have already been
+    // displayed, but the JSON report and baseline comparisons also end with their count.
+    let error = if is_using_baseline {
+        Some(miette!(
+            "{} new validation errors (compared to the baseline).",
+            num_validation_errors
+        ))
+    } else if is_outputting_json {
+        Some(miette!(
+            "{} validation errors.",
+            num_validation_errors
+        ))
+    } else {
+        None
+    };
+
+    Err(CommandFailure {
+        category: FailureCategory::ValidationErrors,
+        error,
+    })
+}
+
+/// Load the configuration and apply the command-specific overrides to it
+/// (the `mirror` output directory, the device profile and `--threads`).
+fn get_command_configuration(args: &CLIArgs) -> Result<Configuration> {
+    let mut configuration = get_configuration(args)
+        .wrap_err_with(|| miette!("Could not load configuration."))?;
+
+    // `mirror` runs the same processing as `transcode`, only with a copy-only configuration.
+    if let CLICommand::Mirror(mirror_args) = &args.command {
+        configuration = configuration
+            .into_mirror_configuration(&mirror_args.output_directory)
+            .wrap_err_with(|| miette!("Invalid mirror output directory."))?;
+    }
+
+    // `sync-device` runs the same processing as `transcode`, only with the profile's overrides applied.
+    let device_profile = match &args.command {
+        CLICommand::SyncDevice(sync_device_args) => {
+            Some(sync_device_args.profile.as_str())
+        }
+        CLICommand::TranscodeAll(transcode_all_args) => transcode_all_args
+            .profile
+            .as_deref()
+            .filter(|profile| *profile != DEFAULT_PROFILE_NAME),
+        _ => None,
+    };
+
+    if let Some(device_profile) = device_profile {
+        configuration = configuration
+            .into_device_profile_configuration(device_profile)
+            .wrap_err_with(|| miette!("Could not apply device profile."))?;
+    }
+
+    // `-j`/`--threads` only overrides the number of threads for this run.
+    if let Some(transcode_threads) = args
+        .command
+        .transcode_args()
+        .and_then(|transcode_args| transcode_args.threads)
+    {
+        configuration = configuration
+            .with_transcode_threads(transcode_threads)
+            .wrap_err_with(|| miette!("Invalid --threads value."))?;
+    }
+
+    Ok(configuration)
+}
+
+
 /// Entry function for `euphony`.
 ///
 /// Parses CLI arguments, loads the configuration file and starts executing the requested command.
 fn main() -> Result<()> {
     let args = CLIArgs::parse();
-    VERBOSE.set(args.verbose);
+    VERBOSE.set(args.verbose || args.trace);
 
-    let configuration = get_configuration(&args)
-        .wrap_err_with(|| miette!("Could not load configuration."))?;
+    let tracing_max_level = if args.trace {
+        LevelFilter::TRACE
+    } else if args.verbose {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    };
+    install_tracing_subscriber(tracing_max_level)?;
 
-    thread::scope(|scope| {
-        let command_result =
-            run_requested_cli_command(args, &configuration, scope);
+    // Completion scripts only depend on the command line interface, not on the configuration.
+    if let CLICommand::Completions(completions_args) = &args.command {
+        clap_complete::generate(
+            completions_args.shell,
+            &mut CLIArgs::command(),
+            "euphony",
+            &mut io::stdout(),
+        );
 
-        match command_result {
-            Ok(_) => exit(0),
-            Err(error) => {
-                eprintln!("{:?}", error);
-                exit(1);
-            }
-        };
-    });
+        return Ok(());
+    }
+
+    // Commands return their errors (instead of exiting the process) only after tearing down
+    // their terminal backend, so the exit code is decided here.
+    let command_result = get_command_configuration(&args)
+        .map_err(CommandFailure::from)
+        .and_then(|configuration| {
+            // Shared with the worker threads of the transcoding commands.
+            let configuration = Arc::new(configuration);
+
+            thread::scope(|scope| {
+                run_requested_cli_command(args, &configuration, scope)
+            })
+        });
+
+    if let Err(failure) = command_result {
+        if let Some(error) = failure.error {
+            eprintln!("{:?}", error);
+        }
+
+        exit(failure.category.exit_code());
+    }
 
     Ok(())
 }
@@ -0,0 +1,259 @@
+//! End-to-end tests of the transcoding engine (`transcode_collection`) on small synthetic
+//! libraries. A stub "ffmpeg" shell script (which simply copies its input to its output)
+//! stands in for the real one, so these tests only run on Unix.
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use euphony::commands::transcode::target::TranscodeTarget;
+use euphony::commands::{TranscodeOptions, TranscodeRunSummary};
+use euphony::console::frontends::{BareTerminalBackend, TranscodeTerminal};
+use euphony::globals::VERBOSE;
+use euphony_configuration::Configuration;
+use tempfile::TempDir;
+
+/// Answers `-version` (see `ensure_ffmpeg_is_available`) and otherwise copies
+/// the file after `-i` to the last argument (the output file).
+const STUB_FFMPEG_SCRIPT: &str = r#"#!/bin/sh
+if [ "$1" = "-version" ]; then
+    echo "ffmpeg version 0.0-stub"
+    exit 0
+fi
+
+while [ "$#" -gt 0 ]; do
+    if [ "$1" = "-i" ]; then
+        shift
+        input_file="$1"
+    fi
+
+    output_file="$1"
+    shift
+done
+
+cp "$input_file" "$output_file"
+"#;
+
+/// Shared with the album state tests of `euphony_library`.
+const CONFIGURATION_TEMPLATE: &str = include_str!("fixtures/configuration.toml");
+
+const LIBRARY_STATE_FILE_NAME: &str = ".library.state.euphony";
+const SOURCE_ALBUM_STATE_FILE_NAME: &str = ".album.source-state.euphony";
+const TRANSCODED_ALBUM_STATE_FILE_NAME: &str = ".album.transcode-state.euphony";
+
+
+/// A temporary collection with a single source library (`Lossless`, with `flac`
+/// audio files and `jpg` data files) and its aggregated library.
+struct TestCollection {
+    /// Removes everything once the test is done.
+    _directory: TempDir,
+
+    root_path: PathBuf,
+}
+
+impl TestCollection {
+    fn new() -> Self {
+        // Commands normally set this from the command line arguments.
+        VERBOSE.set(false);
+
+        let directory = TempDir::new().unwrap();
+        let root_path = dunce::canonicalize(directory.path()).unwrap();
+
+        fs::create_dir_all(root_path.join("Lossless")).unwrap();
+        fs::create_dir_all(root_path.join("Aggregated")).unwrap();
+
+        let ffmpeg_path = root_path.join("ffmpeg");
+        fs::write(&ffmpeg_path, STUB_FFMPEG_SCRIPT).unwrap();
+        fs::set_permissions(&ffmpeg_path, fs::Permissions::from_mode(0o755))
+            .unwrap();
+
+        fs::write(
+            root_path.join("configuration.toml"),
+            CONFIGURATION_TEMPLATE
+                .replace("{ROOT}", &root_path.to_string_lossy()),
+        )
+        .unwrap();
+
+        Self {
+            _directory: directory,
+            root_path,
+        }
+    }
+
+    /// Create (or overwrite) a file in the `Artist/Album` album of the source library.
+    fn write_album_file(&self, file_name: &str, contents: &str) {
+        let album_directory_path = self.source_album_path();
+        fs::create_dir_all(&album_directory_path).unwrap();
+
+        fs::write(album_directory_path.join(file_name), contents).unwrap();
+    }
+
+    fn remove_album_file(&self, file_name: &str) {
+        fs::remove_file(self.source_album_path().join(file_name)).unwrap();
+    }
+
+    fn source_album_path(&self) -> PathBuf {
+        self.root_path.join("Lossless/Artist/Album")
+    }
+
+    fn aggregated_album_path(&self) -> PathBuf {
+        self.root_path.join("Aggregated/Artist/Album")
+    }
+
+    fn read_aggregated_album_file(&self, file_name: &str) -> String {
+        fs::read_to_string(self.aggregated_album_path().join(file_name)).unwrap()
+    }
+
+    /// Load the configuration (as `transcode` would) and transcode the entire collection.
+    fn transcode(&self) -> TranscodeRunSummary {
+        let configuration = Arc::new(
+            Configuration::load_from_path(
+                self.root_path.join("configuration.toml"),
+            )
+            .unwrap(),
+        );
+        let terminal: TranscodeTerminal = BareTerminalBackend::new().into();
+
+        euphony::commands::transcode::transcode_collection(
+            &configuration,
+            &terminal,
+            TranscodeOptions::default(),
+            &TranscodeTarget::EntireCollection,
+        )
+        .unwrap()
+    }
+
+    /// Load the configuration and transcode exactly the given files of the `Artist/Album` album
+    /// (as `transcode --stdin-paths` would).
+    fn transcode_album_files(&self, file_names: &[&str]) -> TranscodeRunSummary {
+        let configuration = Arc::new(
+            Configuration::load_from_path(
+                self.root_path.join("configuration.toml"),
+            )
+            .unwrap(),
+        );
+        let terminal: TranscodeTerminal = BareTerminalBackend::new().into();
+
+        euphony::commands::cmd_transcode_paths(
+            &configuration,
+            &terminal,
+            file_names
+                .iter()
+                .map(|file_name| self.source_album_path().join(file_name))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    /// Transcode the collection, assert that exactly one album was processed without
+    /// failures, then transcode it again and assert that nothing was processed,
+    /// as nothing has changed since (i.e. the saved album states are up to date).
+    fn assert_transcoded_once_then_skipped(&self) {
+        let summary = self.transcode();
+        assert_eq!(summary.num_processed_albums, 1);
+        assert_eq!(summary.num_errored_albums, 0);
+
+        let summary = self.transcode();
+        assert_eq!(summary.num_processed_albums, 0);
+        assert_eq!(summary.num_errored_albums, 0);
+    }
+}
+
+
+/// Sorted names of the files (not directories) in `directory_path`.
+fn file_names_in(directory_path: &Path) -> Vec<String> {
+    let mut file_names: Vec<String> = fs::read_dir(directory_path)
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_type().unwrap().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+
+    file_names.sort_unstable();
+    file_names
+}
+
+
+#[test]
+fn transcodes_audio_files_and_copies_data_files() {
+    let collection = TestCollection::new();
+    collection.write_album_file("01 - Intro.flac", "intro");
+    collection.write_album_file("02 - Song.flac", "song");
+    collection.write_album_file("cover.jpg", "cover");
+    collection.write_album_file("notes.txt", "untracked");
+
+    collection.assert_transcoded_once_then_skipped();
+
+    assert_eq!(
+        file_names_in(&collection.aggregated_album_path()),
+        vec![
+            TRANSCODED_ALBUM_STATE_FILE_NAME,
+            "01 - Intro.mp3",
+            "02 - Song.mp3",
+            "cover.jpg",
+        ]
+    );
+    assert_eq!(
+        collection.read_aggregated_album_file("02 - Song.mp3"),
+        "song"
+    );
+    assert!(collection
+        .source_album_path()
+        .join(SOURCE_ALBUM_STATE_FILE_NAME)
+        .is_file());
+    assert!(collection
+        .root_path
+        .join("Lossless")
+        .join(LIBRARY_STATE_FILE_NAME)
+        .is_file());
+}
+
+#[test]
+fn processes_only_albums_that_changed_since_the_last_run() {
+    let collection = TestCollection::new();
+    collection.write_album_file("01 - Intro.flac", "intro");
+    collection.write_album_file("02 - Song.flac", "song");
+
+    collection.assert_transcoded_once_then_skipped();
+
+    // A changed file (size) is transcoded again.
+    collection.write_album_file("02 - Song.flac", "song (remastered)");
+    collection.assert_transcoded_once_then_skipped();
+    assert_eq!(
+        collection.read_aggregated_album_file("02 - Song.mp3"),
+        "song (remastered)"
+    );
+
+    // A removed file is removed from the aggregated library as well.
+    collection.remove_album_file("01 - Intro.flac");
+    collection.assert_transcoded_once_then_skipped();
+    assert_eq!(
+        file_names_in(&collection.aggregated_album_path()),
+        vec![TRANSCODED_ALBUM_STATE_FILE_NAME, "02 - Song.mp3"]
+    );
+}
+
+#[test]
+fn merges_explicitly_transcoded_files_into_saved_album_states() {
+    let collection = TestCollection::new();
+    collection.write_album_file("01 - Intro.flac", "intro");
+    collection.write_album_file("02 - Song.flac", "song");
+
+    collection.assert_transcoded_once_then_skipped();
+
+    collection.write_album_file("02 - Song.flac", "song (remastered)");
+    let summary = collection.transcode_album_files(&["02 - Song.flac"]);
+    assert_eq!(summary.num_processed_albums, 1);
+    assert_eq!(summary.num_errored_albums, 0);
+    assert_eq!(
+        collection.read_aggregated_album_file("02 - Song.mp3"),
+        "song (remastered)"
+    );
+
+    // The changed file is already up to date in the saved album states.
+    let summary = collection.transcode();
+    assert_eq!(summary.num_processed_albums, 0);
+    assert_eq!(summary.num_errored_albums, 0);
+}